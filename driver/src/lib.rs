@@ -1,9 +1,12 @@
 use std::{
     env,
-    io::{Read, Write},
+    io::{self, Read, Write},
     net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream},
     sync::Arc,
+    time::Duration,
 };
+mod pool;
+mod row;
 pub mod server;
 mod tls;
 
@@ -13,20 +16,35 @@ use native_protocol::{
     messages::{
         self,
         auth::AuthResponse,
+        batch::Batch,
         query::{Consistency, Query, QueryParams},
     },
     types::Bytes,
     Serializable,
 };
 use rustls::{ClientConfig, ClientConnection, StreamOwned};
-use tls::configure_client;
+use tls::{configure_client, configure_client_with_mode};
+
+pub use native_protocol::messages::batch::BatchType;
+pub use pool::{CassandraPool, PooledConnection};
+pub use row::{FromColumnValue, Row, RowError};
+pub use tls::TlsMode;
 
 pub struct CassandraClient {
     stream: StreamOwned<ClientConnection, TcpStream>,
     config: ClientConfig,
+    // Timeout de lectura y escritura de las queries que no indican uno propio
+    timeout: Duration,
+    // Stream id del ultimo pedido: cada pedido lleva uno nuevo y su respuesta lo repite
+    last_stream_id: i16,
+    // Bytes leidos que todavia no completan un frame
+    pending: Vec<u8>,
 }
 
 const NATIVE_PORT: u16 = 0x4645;
+/// Timeout used to connect, and to send each query and wait for its answer, unless the client
+/// is created with `connect_with_timeout` or the query runs with `execute_with_timeout`.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
 
 #[derive(Debug)]
 pub enum ClientError {
@@ -39,6 +57,7 @@ pub enum ClientError {
     IOError,
     SerializationError,
     DeserializationError,
+    CertificateError,
 }
 
 #[derive(Debug)]
@@ -47,68 +66,160 @@ pub enum QueryResult {
     Error(messages::error::Error),
 }
 
+impl QueryResult {
+    /// Returns the rows of the result, or `None` if the query did not return rows
+    /// (an error, a schema change, etc).
+    pub fn rows(&self) -> Option<Vec<Row>> {
+        match self {
+            QueryResult::Result(messages::result::result_::Result::Rows(rows)) => {
+                Some(rows.rows_content.iter().cloned().map(Row::from).collect())
+            }
+            _ => None,
+        }
+    }
+}
+
 impl CassandraClient {
     /// Creates a connection with the node at `ip`.
     pub fn connect(ip: Ipv4Addr) -> Result<Self, ClientError> {
-        // Configurar TLS sin verificación de certificados
-        let config = configure_client();
-        let config_arc = Arc::new(config.clone());
+        // Verifica el certificado del nodo contra el del proyecto
+        Self::connect_with_config(ip, configure_client())
+    }
 
-        let server_name = rustls::pki_types::ServerName::try_from("databaseserver")
-            .map_err(|_| ClientError::ServerError)?;
-        let conn = ClientConnection::new(config_arc, server_name)
-            .map_err(|_| ClientError::ConnectionError)?;
+    pub fn connect_with_config(ip: Ipv4Addr, config: ClientConfig) -> Result<Self, ClientError> {
+        Self::connect_to(
+            Self::node_addr(ip)?,
+            config,
+            "databaseserver",
+            DEFAULT_TIMEOUT,
+        )
+    }
+
+    /// Creates a connection with the node at `ip` like `connect`, but waiting up to `timeout`
+    /// instead of `DEFAULT_TIMEOUT` to connect and for each query, e.g. for queries that return
+    /// large results.
+    ///
+    /// A single query can still use another timeout through `execute_with_timeout`.
+    pub fn connect_with_timeout(ip: Ipv4Addr, timeout: Duration) -> Result<Self, ClientError> {
+        Self::connect_to(
+            Self::node_addr(ip)?,
+            configure_client(),
+            "databaseserver",
+            timeout,
+        )
+    }
 
-        let addr = if let Ok(var) = env::var("NODE_ADDR") {
-            var.parse().map_err(|_| ClientError::AddrError)?
+    // `NODE_ADDR` reemplaza la direccion del nodo, p. ej. para conectarse a traves de un proxy
+    fn node_addr(ip: Ipv4Addr) -> Result<SocketAddr, ClientError> {
+        if let Ok(var) = env::var("NODE_ADDR") {
+            var.parse().map_err(|_| ClientError::AddrError)
         } else {
-            SocketAddr::new(IpAddr::V4(ip), NATIVE_PORT)
-        };
+            Ok(SocketAddr::new(IpAddr::V4(ip), NATIVE_PORT))
+        }
+    }
 
-        let sock = TcpStream::connect(addr).map_err(|_| ClientError::ConnectionError)?;
-        sock.set_read_timeout(Some(std::time::Duration::from_secs(3)))
-            .map_err(|_| ClientError::TimeoutError)?;
-        sock.set_write_timeout(Some(std::time::Duration::from_secs(3)))
-            .map_err(|_| ClientError::TimeoutError)?;
-        let tls = StreamOwned::new(conn, sock);
+    /// Creates a connection with the node at `ip`, checking its certificate as `mode` says.
+    ///
+    /// Unlike `connect`, the TLS handshake is completed before returning, so a node whose
+    /// certificate is rejected makes this function fail with `ClientError::ConnectionError`
+    /// instead of the first query. `TlsMode::Insecure` has to be asked for explicitly; `connect`
+    /// always verifies against the project certificate.
+    ///
+    /// Returns `ClientError::CertificateError` if the CA bundle of `TlsMode::Verify` cannot be
+    /// loaded.
+    pub fn connect_with_tls(ip: Ipv4Addr, mode: &TlsMode) -> Result<Self, ClientError> {
+        let config = configure_client_with_mode(mode)?;
+        let addr = SocketAddr::new(IpAddr::V4(ip), NATIVE_PORT);
 
-        Ok(Self {
-            stream: tls,
-            config: config,
-        })
+        let mut client = Self::connect_to(addr, config, &mode.server_name(), DEFAULT_TIMEOUT)?;
+        client.complete_handshake()?;
+        Ok(client)
     }
 
-    pub fn connect_with_config(ip: Ipv4Addr, config: ClientConfig) -> Result<Self, ClientError> {
+    /// Creates a connection with the first node of `ips` that accepts it.
+    ///
+    /// The contact points are tried in order. A node counts as reachable once the TCP
+    /// connection is accepted and the TLS handshake completes, each step bounded by
+    /// the same 3 seconds timeout used for reads and writes.
+    ///
+    /// Returns `ClientError::ConnectionError` if no node could be reached.
+    pub fn connect_multi(ips: &[Ipv4Addr]) -> Result<Self, ClientError> {
+        let config = configure_client();
+
+        for ip in ips {
+            let addr = SocketAddr::new(IpAddr::V4(*ip), NATIVE_PORT);
+            let client = Self::connect_to(addr, config.clone(), "databaseserver", DEFAULT_TIMEOUT)
+                .and_then(|mut client| {
+                    client.complete_handshake()?;
+                    Ok(client)
+                });
+
+            if let Ok(client) = client {
+                return Ok(client);
+            }
+        }
+
+        Err(ClientError::ConnectionError)
+    }
+
+    fn connect_to(
+        addr: SocketAddr,
+        config: ClientConfig,
+        server_name: &str,
+        timeout: Duration,
+    ) -> Result<Self, ClientError> {
         let config_arc = Arc::new(config.clone());
-        // Configurar TLS sin verificación de certificados
-        let server_name = rustls::pki_types::ServerName::try_from("databaseserver")
+        let server_name = rustls::pki_types::ServerName::try_from(server_name.to_string())
             .map_err(|_| ClientError::ServerError)?;
         let conn = ClientConnection::new(config_arc, server_name)
             .map_err(|_| ClientError::ConnectionError)?;
 
-        let addr = if let Ok(var) = env::var("NODE_ADDR") {
-            var.parse().map_err(|_| ClientError::AddrError)?
-        } else {
-            SocketAddr::new(IpAddr::V4(ip), NATIVE_PORT)
-        };
-
-        let sock = TcpStream::connect(addr).map_err(|_| ClientError::ConnectionError)?;
-        sock.set_read_timeout(Some(std::time::Duration::from_secs(3)))
-            .map_err(|_| ClientError::TimeoutError)?;
-        sock.set_write_timeout(Some(std::time::Duration::from_secs(3)))
-            .map_err(|_| ClientError::TimeoutError)?;
+        let sock =
+            TcpStream::connect_timeout(&addr, timeout).map_err(|_| ClientError::ConnectionError)?;
         let tls = StreamOwned::new(conn, sock);
 
-        Ok(Self {
+        let mut client = Self {
             stream: tls,
             config: config,
-        })
+            timeout,
+            last_stream_id: 0,
+            pending: Vec::new(),
+        };
+        client.set_socket_timeout(timeout)?;
+        Ok(client)
+    }
+
+    fn set_socket_timeout(&mut self, timeout: Duration) -> Result<(), ClientError> {
+        self.stream
+            .sock
+            .set_read_timeout(Some(timeout))
+            .map_err(|_| ClientError::TimeoutError)?;
+        self.stream
+            .sock
+            .set_write_timeout(Some(timeout))
+            .map_err(|_| ClientError::TimeoutError)
+    }
+
+    // El handshake TLS normalmente ocurre en la primera escritura
+    fn complete_handshake(&mut self) -> Result<(), ClientError> {
+        while self.stream.conn.is_handshaking() {
+            self.stream
+                .conn
+                .complete_io(&mut self.stream.sock)
+                .map_err(|_| ClientError::ConnectionError)?;
+        }
+        Ok(())
     }
 
     pub fn config(&self) -> ClientConfig {
         self.config.clone()
     }
 
+    /// Timeout used by `execute` to send a query and wait for its answer.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
     /// Execute a query.
     pub fn execute(
         &mut self,
@@ -125,6 +236,45 @@ impl CassandraClient {
         }
     }
 
+    /// Executes a query like `execute`, but waiting up to `timeout` to send it and for its
+    /// answer instead of the timeout of the client. Later queries go back to the timeout of
+    /// the client, even if this one fails.
+    ///
+    /// Returns `ClientError::TimeoutError` if the node does not answer within `timeout`. If the
+    /// answer arrives later, the next query of the client discards it.
+    pub fn execute_with_timeout(
+        &mut self,
+        query: &str,
+        consistency_str: &str,
+        timeout: Duration,
+    ) -> Result<QueryResult, ClientError> {
+        self.set_socket_timeout(timeout)?;
+        let result = self.execute(query, consistency_str);
+        self.set_socket_timeout(self.timeout)?;
+        result
+    }
+
+    /// Executes `queries` in a single `BATCH` request, answered with a single result.
+    ///
+    /// A `Logged` batch is applied as a whole even if the coordinator fails halfway, while the
+    /// statements of an `Unlogged` one are applied each on its own.
+    pub fn batch(
+        &mut self,
+        queries: &[&str],
+        batch_type: BatchType,
+        consistency_str: &str,
+    ) -> Result<QueryResult, ClientError> {
+        let consistency =
+            Consistency::from_string(consistency_str).map_err(|_| ClientError::ConsistencyError)?;
+        let queries = queries.iter().map(|query| query.to_string()).collect();
+        let batch = Frame::Batch(Batch::new(batch_type, queries, consistency));
+        match self.send_frame(batch)? {
+            Frame::Result(res) => Ok(QueryResult::Result(res)),
+            Frame::Error(err) => Ok(QueryResult::Error(err)),
+            _ => Err(ClientError::InvalidFrame),
+        }
+    }
+
     pub fn startup(&mut self) -> Result<(), ClientError> {
         let startup = Frame::Startup;
 
@@ -185,26 +335,400 @@ impl CassandraClient {
     ) -> Result<Frame, ClientError> {
         let params = QueryParams::new(consistency, vec![]);
         let query = Query::new(cql_query.to_string(), params);
-        let query = Frame::Query(query);
+        self.send_frame(Frame::Query(query))
+    }
 
-        // Escribir la consulta en el stream
+    // Envia un pedido y espera el frame que lo responde
+    //
+    // Cada pedido va con un stream id nuevo. Las respuestas con otro stream id son de pedidos
+    // que ya vencieron su timeout, y se descartan.
+    fn send_frame(&mut self, request: Frame) -> Result<Frame, ClientError> {
+        let stream_id = self.next_stream_id();
         self.stream
             .write_all(
-                query
-                    .to_bytes()
+                request
+                    .to_bytes_with_stream(stream_id)
                     .map_err(|_| ClientError::SerializationError)?
                     .as_slice(),
             )
             .map_err(|_| ClientError::IOError)?;
 
-        let mut result = [0u8; 850000];
+        loop {
+            let bytes = self.read_frame_bytes()?;
+            if Frame::stream_of(&bytes).map_err(|_| ClientError::DeserializationError)? == stream_id
+            {
+                return Frame::from_bytes(&bytes).map_err(|_| ClientError::DeserializationError);
+            }
+        }
+    }
+
+    // Los stream ids negativos los reserva el protocolo para los eventos del servidor
+    fn next_stream_id(&mut self) -> i16 {
+        self.last_stream_id = self.last_stream_id.checked_add(1).unwrap_or(1);
+        self.last_stream_id
+    }
 
-        self.stream
-            .read(&mut result)
-            .map_err(|_| ClientError::IOError)?;
+    // Lee del stream hasta tener un frame completo y devuelve sus bytes
+    fn read_frame_bytes(&mut self) -> Result<Vec<u8>, ClientError> {
+        loop {
+            if let Some(length) =
+                Frame::length_of(&self.pending).filter(|length| *length <= self.pending.len())
+            {
+                return Ok(self.pending.drain(..length).collect());
+            }
+
+            let mut buffer = [0u8; 16384];
+            let read = self.stream.read(&mut buffer).map_err(|e| match e.kind() {
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => ClientError::TimeoutError,
+                _ => ClientError::IOError,
+            })?;
+            if read == 0 {
+                return Err(ClientError::ConnectionError);
+            }
+            self.pending.extend_from_slice(&buffer[..read]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+    use rustls::{ServerConfig, ServerConnection};
+    use std::net::TcpListener;
+    use std::path::Path;
+    use std::thread;
+
+    fn server_config() -> ServerConfig {
+        let certs_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("..")
+            .join("certs");
+        let certs = CertificateDer::pem_file_iter(certs_path.join("cert.crt"))
+            .unwrap()
+            .map(|cert| cert.unwrap())
+            .collect();
+        let private_key = PrivateKeyDer::from_pem_file(certs_path.join("cert.key")).unwrap();
+
+        rustls::crypto::aws_lc_rs::default_provider()
+            .install_default()
+            .ok();
+        ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(certs, private_key)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_connect_multi_skips_unreachable_nodes() {
+        let config = Arc::new(server_config());
+        let listener = TcpListener::bind((Ipv4Addr::new(127, 0, 0, 23), NATIVE_PORT)).unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut conn = ServerConnection::new(config).unwrap();
+            conn.complete_io(&mut stream).unwrap();
+        });
+
+        // Nadie escucha en 127.0.0.22, la conexion se rechaza
+        let client = CassandraClient::connect_multi(&[
+            Ipv4Addr::new(127, 0, 0, 22),
+            Ipv4Addr::new(127, 0, 0, 23),
+        ]);
+
+        assert!(client.is_ok());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_connect_multi_fails_if_every_node_is_down() {
+        let client = CassandraClient::connect_multi(&[
+            Ipv4Addr::new(127, 0, 0, 24),
+            Ipv4Addr::new(127, 0, 0, 25),
+        ]);
+
+        assert!(matches!(client, Err(ClientError::ConnectionError)));
+    }
+
+    fn spawn_tls_server(ip: Ipv4Addr) -> thread::JoinHandle<()> {
+        let config = Arc::new(server_config());
+        let listener = TcpListener::bind((ip, NATIVE_PORT)).unwrap();
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut conn = ServerConnection::new(config).unwrap();
+            // El handshake falla del lado del servidor si el cliente rechaza el certificado
+            let _ = conn.complete_io(&mut stream);
+        })
+    }
+
+    /// Answers each query of `connections` clients with `Void`, `delay` after receiving it.
+    fn spawn_slow_server(
+        ip: Ipv4Addr,
+        connections: usize,
+        delay: Duration,
+    ) -> thread::JoinHandle<()> {
+        let config = Arc::new(server_config());
+        let listener = TcpListener::bind((ip, NATIVE_PORT)).unwrap();
+
+        thread::spawn(move || {
+            for _ in 0..connections {
+                let (stream, _) = listener.accept().unwrap();
+                let conn = ServerConnection::new(config.clone()).unwrap();
+                let mut tls = StreamOwned::new(conn, stream);
+                let mut query = [0u8; 1024];
+                if tls.read(&mut query).unwrap_or(0) == 0 {
+                    continue;
+                }
+
+                thread::sleep(delay);
+                let answer = Frame::Result(messages::result::result_::Result::Void);
+                let stream_id = Frame::stream_of(&query).unwrap();
+                // El cliente puede haber cortado la conexion al vencer su timeout
+                let _ = tls.write_all(&answer.to_bytes_with_stream(stream_id).unwrap());
+                let _ = tls.flush();
+            }
+        })
+    }
+
+    #[test]
+    fn test_execute_with_timeout_waits_longer_than_the_default() {
+        let ip = Ipv4Addr::new(127, 0, 0, 57);
+        let server = spawn_slow_server(ip, 2, Duration::from_millis(500));
+
+        // Con el timeout del cliente la respuesta llega tarde
+        let mut client =
+            CassandraClient::connect_with_timeout(ip, Duration::from_millis(200)).unwrap();
+        assert_eq!(client.timeout(), Duration::from_millis(200));
+        let result = client.execute("SELECT * FROM sky.flights", "one");
+        assert!(matches!(result, Err(ClientError::TimeoutError)));
+        drop(client);
+
+        let mut client =
+            CassandraClient::connect_with_timeout(ip, Duration::from_millis(200)).unwrap();
+        let result =
+            client.execute_with_timeout("SELECT * FROM sky.flights", "one", Duration::from_secs(3));
+        assert!(matches!(
+            result,
+            Ok(QueryResult::Result(messages::result::result_::Result::Void))
+        ));
+        // El timeout del cliente vuelve a ser el de antes
+        assert_eq!(
+            client.stream.sock.read_timeout().unwrap(),
+            Some(Duration::from_millis(200))
+        );
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_a_late_answer_is_not_taken_as_the_answer_of_the_next_query() {
+        let ip = Ipv4Addr::new(127, 0, 0, 58);
+        let config = Arc::new(server_config());
+        let listener = TcpListener::bind((ip, NATIVE_PORT)).unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let conn = ServerConnection::new(config).unwrap();
+            let mut tls = StreamOwned::new(conn, stream);
+
+            // La primera query se contesta recien cuando llega la segunda, que el cliente manda
+            // despues de dejar de esperar la primera
+            let first = Frame::stream_of(&read_request(&mut tls)).unwrap();
+            let second = Frame::stream_of(&read_request(&mut tls)).unwrap();
+            let late = Frame::Result(messages::result::result_::Result::Void);
+            tls.write_all(&late.to_bytes_with_stream(first).unwrap())
+                .unwrap();
+            tls.flush().unwrap();
+
+            let answer = Frame::Error(messages::error::Error::ServerError("second".to_string()));
+            tls.write_all(&answer.to_bytes_with_stream(second).unwrap())
+                .unwrap();
+            tls.flush().unwrap();
+        });
+
+        let mut client =
+            CassandraClient::connect_with_timeout(ip, Duration::from_millis(200)).unwrap();
+        let result = client.execute("SELECT * FROM sky.flights", "one");
+        assert!(matches!(result, Err(ClientError::TimeoutError)));
+
+        let result =
+            client.execute_with_timeout("SELECT * FROM sky.planes", "one", Duration::from_secs(3));
+        match result {
+            Ok(QueryResult::Error(messages::error::Error::ServerError(message))) => {
+                assert_eq!(message, "second")
+            }
+            other => panic!("expected the answer of the second query, got {:?}", other),
+        }
+
+        server.join().unwrap();
+    }
+
+    /// Answers the first request of a client with `Void`, and returns the frame it received.
+    fn spawn_mock_node(ip: Ipv4Addr) -> thread::JoinHandle<Frame> {
+        let config = Arc::new(server_config());
+        let listener = TcpListener::bind((ip, NATIVE_PORT)).unwrap();
+
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let conn = ServerConnection::new(config).unwrap();
+            let mut tls = StreamOwned::new(conn, stream);
+            let request = read_request(&mut tls);
+
+            let answer = Frame::Result(messages::result::result_::Result::Void);
+            let stream_id = Frame::stream_of(&request).unwrap();
+            tls.write_all(&answer.to_bytes_with_stream(stream_id).unwrap())
+                .unwrap();
+            tls.flush().unwrap();
+            Frame::from_bytes(&request).unwrap()
+        })
+    }
+
+    /// Reads a whole frame sent by the client: its 9-byte header, and then as many bytes as the
+    /// length of its body.
+    fn read_request(tls: &mut impl Read) -> Vec<u8> {
+        let mut request = vec![0u8; 9];
+        tls.read_exact(&mut request).unwrap();
+        let length = u32::from_be_bytes(request[5..9].try_into().unwrap()) as usize;
+        request.resize(9 + length, 0);
+        tls.read_exact(&mut request[9..]).unwrap();
+        request
+    }
+
+    #[test]
+    fn test_batch_sends_every_statement_in_a_single_frame() {
+        let ip = Ipv4Addr::new(127, 0, 0, 30);
+        let node = spawn_mock_node(ip);
+
+        let statements = [
+            "INSERT INTO sky.flights (id, origin) VALUES ('1', 'EZE')",
+            "UPDATE sky.flights SET origin = 'AEP' WHERE id = '2'",
+            "DELETE FROM sky.flights WHERE id = '3'",
+        ];
+        let mut client = CassandraClient::connect(ip).unwrap();
+        let result = client.batch(&statements, BatchType::Unlogged, "quorum");
+        assert!(matches!(
+            result,
+            Ok(QueryResult::Result(messages::result::result_::Result::Void))
+        ));
+
+        let Frame::Batch(batch) = node.join().unwrap() else {
+            panic!("expected a batch frame");
+        };
+        assert_eq!(batch.batch_type, BatchType::Unlogged);
+        assert_eq!(batch.queries, statements);
+        assert_eq!(batch.consistency, Consistency::Quorum);
+    }
+
+    #[test]
+    fn test_verify_mode_rejects_certificate_from_unknown_ca() {
+        let ip = Ipv4Addr::new(127, 0, 0, 26);
+        let server = spawn_tls_server(ip);
+
+        // El nodo usa un certificado autofirmado que no emitió esta CA
+        let mode = TlsMode::Verify {
+            ca_bundle: Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("..")
+                .join("certs")
+                .join("test_ca.crt"),
+            server_name: "databaseserver".to_string(),
+        };
+        let client = CassandraClient::connect_with_tls(ip, &mode);
+
+        assert!(matches!(client, Err(ClientError::ConnectionError)));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_insecure_mode_accepts_self_signed_certificate() {
+        let ip = Ipv4Addr::new(127, 0, 0, 27);
+        let server = spawn_tls_server(ip);
+
+        let client = CassandraClient::connect_with_tls(ip, &TlsMode::Insecure);
+
+        assert!(client.is_ok());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_verify_mode_accepts_project_certificate() {
+        let ip = Ipv4Addr::new(127, 0, 0, 28);
+        let server = spawn_tls_server(ip);
+
+        let client = CassandraClient::connect_with_tls(ip, &TlsMode::project_certificate());
+
+        assert!(client.is_ok());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_verify_mode_fails_if_ca_bundle_is_missing() {
+        let mode = TlsMode::Verify {
+            ca_bundle: "/nonexistent/ca.crt".into(),
+            server_name: "databaseserver".to_string(),
+        };
+        let client = CassandraClient::connect_with_tls(Ipv4Addr::new(127, 0, 0, 29), &mode);
+
+        assert!(matches!(client, Err(ClientError::CertificateError)));
+    }
+
+    fn flights_result() -> QueryResult {
+        use native_protocol::messages::result::rows::{ColumnType, ColumnValue, Row, Rows};
+
+        let row = Row::from([
+            (
+                "number".to_string(),
+                ColumnValue::Ascii("AR1130".to_string()),
+            ),
+            ("height".to_string(), ColumnValue::Int(10000)),
+        ]);
+
+        QueryResult::Result(messages::result::result_::Result::Rows(Rows::new(
+            vec![
+                ("number".to_string(), ColumnType::Ascii),
+                ("height".to_string(), ColumnType::Int),
+            ],
+            vec![row],
+        )))
+    }
+
+    #[test]
+    fn test_rows_reads_int_column_as_int() {
+        let rows = flights_result().rows().unwrap();
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].get::<i32>("height"), Ok(10000));
+        assert_eq!(rows[0].get::<i64>("height"), Ok(10000));
+        assert_eq!(rows[0].get::<String>("number"), Ok("AR1130".to_string()));
+    }
+
+    #[test]
+    fn test_rows_reading_int_column_as_string_fails() {
+        let rows = flights_result().rows().unwrap();
+
+        assert!(matches!(
+            rows[0].get::<String>("height"),
+            Err(RowError::TypeMismatch {
+                expected: "text",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_rows_reading_missing_column_fails() {
+        let rows = flights_result().rows().unwrap();
+
+        assert_eq!(
+            rows[0].get::<i32>("speed"),
+            Err(RowError::MissingColumn("speed".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_rows_is_none_for_results_without_rows() {
+        let result = QueryResult::Result(messages::result::result_::Result::Void);
 
-        // Decodificar la respuesta
-        let result = Frame::from_bytes(&result).map_err(|_| ClientError::DeserializationError)?;
-        Ok(result)
+        assert!(result.rows().is_none());
     }
 }
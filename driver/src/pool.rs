@@ -0,0 +1,213 @@
+use std::{
+    net::Ipv4Addr,
+    ops::{Deref, DerefMut},
+    sync::{Condvar, Mutex, MutexGuard},
+};
+
+use crate::{CassandraClient, ClientError};
+
+/// A pool of connections to a node, so several threads can run queries at the same time.
+///
+/// Connections are opened on demand, up to `size`, and are already started up when handed
+/// out. `acquire` gives a connection to a single caller until the returned `PooledConnection`
+/// is dropped; if every connection is in use it blocks until one is released.
+pub struct CassandraPool {
+    ip: Ipv4Addr,
+    size: usize,
+    state: Mutex<PoolState>,
+    released: Condvar,
+}
+
+struct PoolState {
+    idle: Vec<CassandraClient>,
+    // Conexiones abiertas, incluidas las que estan prestadas
+    open: usize,
+    closed: bool,
+}
+
+impl CassandraPool {
+    /// Creates a pool of at most `size` connections (at least one) with the node at `ip`.
+    ///
+    /// No connection is opened until the first `acquire`.
+    pub fn new(ip: Ipv4Addr, size: usize) -> Self {
+        Self {
+            ip,
+            size: size.max(1),
+            state: Mutex::new(PoolState {
+                idle: Vec::new(),
+                open: 0,
+                closed: false,
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Maximum number of connections of the pool.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Takes a connection from the pool.
+    ///
+    /// An idle connection is reused if there is one; otherwise a new one is opened if the
+    /// pool is not full yet, or the call waits for another caller to release theirs.
+    ///
+    /// ### Errors
+    /// - Any error of `CassandraClient::connect` or `CassandraClient::startup` when a new
+    ///   connection has to be opened.
+    /// - `ClientError::ConnectionError` if the pool was closed.
+    pub fn acquire(&self) -> Result<PooledConnection<'_>, ClientError> {
+        let mut state = self.lock_state()?;
+
+        loop {
+            if state.closed {
+                return Err(ClientError::ConnectionError);
+            }
+
+            if let Some(client) = state.idle.pop() {
+                return Ok(PooledConnection::new(self, client));
+            }
+
+            if state.open < self.size {
+                state.open += 1;
+                // La conexion se abre sin bloquear al resto del pool
+                drop(state);
+
+                return match Self::open_connection(self.ip) {
+                    Ok(client) => Ok(PooledConnection::new(self, client)),
+                    Err(e) => {
+                        self.forget_connection();
+                        Err(e)
+                    }
+                };
+            }
+
+            state = self
+                .released
+                .wait(state)
+                .map_err(|_| ClientError::ConnectionError)?;
+        }
+    }
+
+    /// Closes the idle connections. Connections in use are closed when they are released,
+    /// and later calls to `acquire` fail.
+    pub fn close(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.closed = true;
+            let idle = state.idle.len();
+            state.idle.clear();
+            state.open -= idle;
+        }
+        self.released.notify_all();
+    }
+
+    fn open_connection(ip: Ipv4Addr) -> Result<CassandraClient, ClientError> {
+        let mut client = CassandraClient::connect(ip)?;
+        client.startup()?;
+        Ok(client)
+    }
+
+    fn lock_state(&self) -> Result<MutexGuard<'_, PoolState>, ClientError> {
+        self.state.lock().map_err(|_| ClientError::ConnectionError)
+    }
+
+    fn release(&self, client: CassandraClient) {
+        if let Ok(mut state) = self.state.lock() {
+            if state.closed {
+                state.open -= 1;
+            } else {
+                state.idle.push(client);
+            }
+        }
+        self.released.notify_one();
+    }
+
+    // Deja lugar para abrir otra conexion en reemplazo
+    fn forget_connection(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.open -= 1;
+        }
+        self.released.notify_one();
+    }
+}
+
+/// A connection taken from a `CassandraPool`. It goes back to the pool when dropped.
+pub struct PooledConnection<'a> {
+    pool: &'a CassandraPool,
+    client: Option<CassandraClient>,
+}
+
+impl<'a> PooledConnection<'a> {
+    fn new(pool: &'a CassandraPool, client: CassandraClient) -> Self {
+        Self {
+            pool,
+            client: Some(client),
+        }
+    }
+
+    /// Replaces the connection with a new one to the same node, already started up.
+    ///
+    /// If opening it fails the old connection is kept, so it can be tried again.
+    pub fn reconnect(&mut self) -> Result<(), ClientError> {
+        self.client = Some(CassandraPool::open_connection(self.pool.ip)?);
+        Ok(())
+    }
+
+    /// Closes the connection instead of giving it back to the pool, which opens a new one
+    /// the next time it needs it. Useful once a query failed and the stream may be broken.
+    pub fn discard(mut self) {
+        if self.client.take().is_some() {
+            self.pool.forget_connection();
+        }
+    }
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = CassandraClient;
+
+    fn deref(&self) -> &Self::Target {
+        // Solo es None despues de `discard`, que consume la conexion
+        self.client
+            .as_ref()
+            .expect("pooled connection already released")
+    }
+}
+
+impl DerefMut for PooledConnection<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.client
+            .as_mut()
+            .expect("pooled connection already released")
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(client) = self.client.take() {
+            self.pool.release(client);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failed_connection_does_not_use_a_slot_of_the_pool() {
+        // Nadie escucha en 127.0.0.41
+        let pool = CassandraPool::new(Ipv4Addr::new(127, 0, 0, 41), 1);
+
+        assert!(matches!(pool.acquire(), Err(ClientError::ConnectionError)));
+        // Si el lugar quedara ocupado, este acquire se bloquearia para siempre
+        assert!(matches!(pool.acquire(), Err(ClientError::ConnectionError)));
+    }
+
+    #[test]
+    fn test_closed_pool_does_not_hand_out_connections() {
+        let pool = CassandraPool::new(Ipv4Addr::new(127, 0, 0, 41), 2);
+        pool.close();
+
+        assert!(matches!(pool.acquire(), Err(ClientError::ConnectionError)));
+    }
+}
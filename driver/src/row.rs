@@ -0,0 +1,183 @@
+use std::{collections::BTreeMap, fmt, net::IpAddr};
+
+use native_protocol::messages::result::rows::{self, ColumnValue};
+
+/// A row returned by a `SELECT`, indexed by column name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    columns: BTreeMap<String, ColumnValue>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowError {
+    /// The row has no column with this name.
+    MissingColumn(String),
+    /// The value of the column cannot be read as the requested type.
+    TypeMismatch {
+        column: String,
+        expected: &'static str,
+        found: ColumnValue,
+    },
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RowError::MissingColumn(column) => write!(f, "Column '{}' not found in row", column),
+            RowError::TypeMismatch {
+                column,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Column '{}' cannot be read as {}, found {:?}",
+                column, expected, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RowError {}
+
+/// Types that can be read from a `ColumnValue` with `Row::get`.
+pub trait FromColumnValue: Sized {
+    /// Name of the type, used in `RowError::TypeMismatch`.
+    const TYPE_NAME: &'static str;
+
+    /// Returns the value if the column holds this type, `None` otherwise.
+    fn from_column_value(value: &ColumnValue) -> Option<Self>;
+}
+
+impl FromColumnValue for i32 {
+    const TYPE_NAME: &'static str = "int";
+
+    fn from_column_value(value: &ColumnValue) -> Option<Self> {
+        match value {
+            ColumnValue::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl FromColumnValue for i64 {
+    const TYPE_NAME: &'static str = "bigint";
+
+    fn from_column_value(value: &ColumnValue) -> Option<Self> {
+        match value {
+            ColumnValue::Bigint(value)
+            | ColumnValue::Counter(value)
+            | ColumnValue::Timestamp(value) => Some(*value),
+            // Un int siempre entra en un bigint
+            ColumnValue::Int(value) => Some(i64::from(*value)),
+            _ => None,
+        }
+    }
+}
+
+impl FromColumnValue for f32 {
+    const TYPE_NAME: &'static str = "float";
+
+    fn from_column_value(value: &ColumnValue) -> Option<Self> {
+        match value {
+            ColumnValue::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl FromColumnValue for f64 {
+    const TYPE_NAME: &'static str = "double";
+
+    fn from_column_value(value: &ColumnValue) -> Option<Self> {
+        match value {
+            ColumnValue::Double(value) => Some(*value),
+            ColumnValue::Float(value) => Some(f64::from(*value)),
+            _ => None,
+        }
+    }
+}
+
+impl FromColumnValue for bool {
+    const TYPE_NAME: &'static str = "boolean";
+
+    fn from_column_value(value: &ColumnValue) -> Option<Self> {
+        match value {
+            ColumnValue::Boolean(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl FromColumnValue for String {
+    const TYPE_NAME: &'static str = "text";
+
+    fn from_column_value(value: &ColumnValue) -> Option<Self> {
+        match value {
+            // Las columnas TEXT llegan como Ascii
+            ColumnValue::Ascii(value)
+            | ColumnValue::Varchar(value)
+            | ColumnValue::Custom(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FromColumnValue for IpAddr {
+    const TYPE_NAME: &'static str = "inet";
+
+    fn from_column_value(value: &ColumnValue) -> Option<Self> {
+        match value {
+            ColumnValue::Inet(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl Row {
+    pub fn new(columns: BTreeMap<String, ColumnValue>) -> Self {
+        Self { columns }
+    }
+
+    /// Reads the value of `column` as a `T`.
+    ///
+    /// Returns `RowError::MissingColumn` if the row has no such column, or
+    /// `RowError::TypeMismatch` if its value is of a type that cannot be converted to `T`.
+    pub fn get<T: FromColumnValue>(&self, column: &str) -> Result<T, RowError> {
+        let value = self
+            .columns
+            .get(column)
+            .ok_or_else(|| RowError::MissingColumn(column.to_string()))?;
+
+        T::from_column_value(value).ok_or_else(|| RowError::TypeMismatch {
+            column: column.to_string(),
+            expected: T::TYPE_NAME,
+            found: value.clone(),
+        })
+    }
+
+    /// Returns the raw value of `column`, if present.
+    pub fn value(&self, column: &str) -> Option<&ColumnValue> {
+        self.columns.get(column)
+    }
+
+    /// Names of the columns of the row, in alphabetical order.
+    pub fn columns(&self) -> impl Iterator<Item = &str> {
+        self.columns.keys().map(String::as_str)
+    }
+}
+
+impl From<BTreeMap<String, ColumnValue>> for Row {
+    fn from(columns: BTreeMap<String, ColumnValue>) -> Self {
+        Self::new(columns)
+    }
+}
+
+impl From<rows::Row> for Row {
+    fn from(row: rows::Row) -> Self {
+        Self::new(
+            row.iter()
+                .map(|(column, value)| (column.to_string(), value.clone()))
+                .collect(),
+        )
+    }
+}
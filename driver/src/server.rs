@@ -1,4 +1,14 @@
-use native_protocol::{frame::Frame, messages::query::Query, types::Bytes, Serializable};
+use std::fmt;
+
+use native_protocol::{
+    frame::Frame,
+    messages::{
+        batch::{Batch, BatchType},
+        query::{Query, QueryParams},
+    },
+    types::Bytes,
+    Serializable,
+};
 
 #[derive(Debug)]
 pub enum RequestError {
@@ -6,6 +16,18 @@ pub enum RequestError {
     InvalidConversion,
 }
 
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            RequestError::InvalidFrame => {
+                "Unexpected frame, only STARTUP, AUTH_RESPONSE, QUERY and BATCH are accepted"
+            }
+            RequestError::InvalidConversion => "Malformed frame",
+        };
+        write!(f, "{}", description)
+    }
+}
+
 #[derive(Debug)]
 pub enum Request {
     Startup,
@@ -28,6 +50,72 @@ pub fn handle_client_request(bytes: &[u8]) -> Result<Request, RequestError> {
             Ok(Request::AuthResponse(r))
         }
         Frame::Query(query) => Ok(Request::Query(query)),
+        Frame::Batch(batch) => Ok(Request::Query(batch_as_query(batch))),
         _ => Err(RequestError::InvalidFrame),
     }
 }
+
+// Un `BATCH` del protocolo se ejecuta como la sentencia `BEGIN BATCH ... APPLY BATCH` de CQL
+fn batch_as_query(batch: Batch) -> Query {
+    let kind = match batch.batch_type {
+        BatchType::Logged => "",
+        BatchType::Unlogged => "UNLOGGED ",
+        BatchType::Counter => "COUNTER ",
+    };
+    let statements: Vec<&str> = batch
+        .queries
+        .iter()
+        .map(|query| query.trim().trim_end_matches(';'))
+        .collect();
+    let query = format!("BEGIN {}BATCH {}; APPLY BATCH", kind, statements.join("; "));
+    Query::new(query, QueryParams::new(batch.consistency, vec![]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use native_protocol::messages::{auth::AuthSuccess, query::Consistency};
+
+    #[test]
+    fn test_batch_frames_are_run_as_a_batch_statement() {
+        let batch = Batch::new(
+            BatchType::Unlogged,
+            vec![
+                "INSERT INTO sky.flights (id) VALUES ('1');".to_string(),
+                "DELETE FROM sky.flights WHERE id = '2'".to_string(),
+            ],
+            Consistency::Quorum,
+        );
+        let bytes = Frame::Batch(batch).to_bytes().unwrap();
+
+        let Ok(Request::Query(query)) = handle_client_request(&bytes) else {
+            panic!("expected a query");
+        };
+        assert_eq!(
+            query.get_query(),
+            "BEGIN UNLOGGED BATCH INSERT INTO sky.flights (id) VALUES ('1'); DELETE FROM sky.flights WHERE id = '2'; APPLY BATCH"
+        );
+        assert_eq!(query.get_consistency(), "QUORUM");
+    }
+
+    #[test]
+    fn test_malformed_and_unexpected_frames_are_errors() {
+        // Opcode inexistente
+        let garbage = [
+            0x03, 0x00, 0x00, 0x01, 0x7F, 0x00, 0x00, 0x00, 0x02, 0xAB, 0xCD,
+        ];
+        assert!(matches!(
+            handle_client_request(&garbage),
+            Err(RequestError::InvalidConversion)
+        ));
+
+        // Un frame de respuesta no es un pedido valido
+        let response = Frame::AuthSuccess(AuthSuccess::default())
+            .to_bytes()
+            .unwrap();
+        assert!(matches!(
+            handle_client_request(&response),
+            Err(RequestError::InvalidFrame)
+        ));
+    }
+}
@@ -1,28 +1,66 @@
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
 use rustls::pki_types::pem::PemObject;
-use rustls::pki_types::CertificateDer;
-use rustls::{ClientConfig, RootCertStore};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
 
 use std::env;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-fn load_root_cert(path: &Path) -> RootCertStore {
-    let cert = CertificateDer::from_pem_file(path).expect("Failed to load certificate");
-    let mut certs = RootCertStore::empty();
-    certs
-        .add(cert)
-        .expect("Failed to add certificate to root store");
-    certs
+use crate::ClientError;
+
+/// How the client checks the certificate presented by a node.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TlsMode {
+    /// Accepts any certificate, without checking who signed it or the name it was issued for.
+    /// Only meant for tests and local clusters.
+    Insecure,
+    /// Accepts only certificates signed by one of the CA certificates in `ca_bundle`
+    /// (a PEM file) and issued for `server_name`.
+    Verify {
+        ca_bundle: PathBuf,
+        server_name: String,
+    },
 }
 
-pub fn configure_client() -> ClientConfig {
-    // Usar CARGO_MANIFEST_DIR para resolver la ruta al archivo `cert.crt`
-    let cert_path: PathBuf = Path::new(env!("CARGO_MANIFEST_DIR"))
-        .join("..") // Retroceder un nivel desde el crate actual
-        .join("certs") // Carpeta donde está el certificado
-        .join("cert.crt");
+impl TlsMode {
+    /// Verifies nodes against the certificate in the `certs` folder of the project, which is the
+    /// one the nodes use.
+    pub fn project_certificate() -> Self {
+        // Usar CARGO_MANIFEST_DIR para resolver la ruta al archivo `cert.crt`
+        let cert_path: PathBuf = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("..") // Retroceder un nivel desde el crate actual
+            .join("certs") // Carpeta donde está el certificado
+            .join("cert.crt");
+
+        TlsMode::Verify {
+            ca_bundle: cert_path,
+            server_name: "databaseserver".to_string(),
+        }
+    }
+
+    /// The name the client expects in the certificate of the node.
+    ///
+    /// In `Insecure` mode the name is not checked, so any valid name is returned.
+    pub(crate) fn server_name(&self) -> String {
+        match self {
+            TlsMode::Insecure => "databaseserver".to_string(),
+            TlsMode::Verify { server_name, .. } => server_name.clone(),
+        }
+    }
+}
 
-    let root_store = load_root_cert(&cert_path);
+fn load_root_certs(path: &Path) -> Result<RootCertStore, ClientError> {
+    let mut certs = RootCertStore::empty();
+    for cert in CertificateDer::pem_file_iter(path).map_err(|_| ClientError::CertificateError)? {
+        let cert = cert.map_err(|_| ClientError::CertificateError)?;
+        certs.add(cert).map_err(|_| ClientError::CertificateError)?;
+    }
+    Ok(certs)
+}
 
+fn install_crypto_provider() {
     // Configurar el proveedor criptográfico
     match rustls::crypto::aws_lc_rs::default_provider().install_default() {
         Ok(_) => {}
@@ -30,8 +68,85 @@ pub fn configure_client() -> ClientConfig {
             eprintln!("Failed to install CryptoProvider: {:?}", err);
         }
     }
+}
 
-    ClientConfig::builder()
-        .with_root_certificates(root_store)
-        .with_no_client_auth()
+pub fn configure_client() -> ClientConfig {
+    configure_client_with_mode(&TlsMode::project_certificate()).expect("Failed to load certificate")
+}
+
+/// Builds the TLS configuration of the client for the given `mode`.
+///
+/// Returns `ClientError::CertificateError` if the CA bundle of `Verify` cannot be read.
+pub fn configure_client_with_mode(mode: &TlsMode) -> Result<ClientConfig, ClientError> {
+    install_crypto_provider();
+
+    match mode {
+        TlsMode::Insecure => {
+            let provider = rustls::crypto::aws_lc_rs::default_provider();
+            Ok(ClientConfig::builder()
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate(Arc::new(
+                    provider,
+                ))))
+                .with_no_client_auth())
+        }
+        TlsMode::Verify { ca_bundle, .. } => {
+            let root_store = load_root_certs(ca_bundle)?;
+            Ok(ClientConfig::builder()
+                .with_root_certificates(root_store)
+                .with_no_client_auth())
+        }
+    }
+}
+
+/// Verifier used by `TlsMode::Insecure`.
+///
+/// The certificate itself is not checked, but the handshake signatures still are, so the
+/// connection is encrypted with the key of the certificate the node presented.
+#[derive(Debug)]
+struct AcceptAnyCertificate(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
 }
@@ -0,0 +1,76 @@
+//! Where the simulator reads its commands from: the terminal, or a script file with one
+//! command, or one answer to a prompt, per line.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+
+use crate::types::sim_error::SimError;
+
+pub struct Console {
+    input: Box<dyn BufRead>,
+    interactive: bool,
+}
+
+impl Console {
+    /// Reads from the standard input.
+    pub fn interactive() -> Self {
+        Self {
+            input: Box::new(BufReader::new(io::stdin())),
+            interactive: true,
+        }
+    }
+
+    /// Reads the lines of the file at `path`, as if they were typed in the terminal.
+    pub fn script(path: &str) -> Result<Self, SimError> {
+        let file = File::open(path)
+            .map_err(|e| SimError::Other(format!("Could not open the script {}: {}", path, e)))?;
+
+        Ok(Self {
+            input: Box::new(BufReader::new(file)),
+            interactive: false,
+        })
+    }
+
+    pub fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    /// Shows `prompt` and reads the next line, without the surrounding whitespace.
+    /// Returns `None` once there is nothing left to read.
+    ///
+    /// Scripts echo the line they read, so the output shows what was run.
+    pub fn read_line(&mut self, prompt: &str) -> Option<String> {
+        print!("{}", prompt);
+        io::stdout().flush().ok();
+
+        let mut line = String::new();
+        match self.input.read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => {
+                let line = line.trim().to_string();
+                if !self.interactive {
+                    println!("{}", line);
+                }
+                Some(line)
+            }
+        }
+    }
+
+    /// Like `read_line`, but for the answers a command needs: running out of lines is an error.
+    pub fn prompt_input(&mut self, prompt: &str) -> Result<String, SimError> {
+        self.read_line(prompt).ok_or_else(|| {
+            SimError::Other(format!(
+                "The input ended before answering '{}'",
+                prompt.trim()
+            ))
+        })
+    }
+
+    /// Clears the terminal. Scripts keep their output.
+    pub fn clean_scr(&self) {
+        if self.interactive {
+            print!("\x1B[2J\x1B[1;1H");
+            io::stdout().flush().ok();
+        }
+    }
+}
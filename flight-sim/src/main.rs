@@ -1,29 +1,27 @@
+mod console;
 mod types;
 
 use crate::types::airport::Airport;
 use crate::types::flight::Flight;
 use chrono::{NaiveDateTime, Utc};
-use std::{
-    io::{self, Write},
-    sync::{Arc, Mutex},
-};
+use console::Console;
+use std::sync::Arc;
 use threadpool::ThreadPool;
 use types::{client::Client, sim_error::SimError, simulation::Simulation, timer::Timer};
 
-fn clean_scr() {
-    print!("\x1B[2J\x1B[1;1H");
-    io::stdout().flush().unwrap();
-}
+// Hilos que actualizan vuelos en paralelo
+const UPDATE_THREADS: usize = 4;
 
-fn add_flight(sim: &mut Simulation) -> Result<(), SimError> {
-    clean_scr();
-    let flight_number = prompt_input("Enter the flight number: ");
-    let origin = prompt_input("Enter the origin IATA code: ");
-    let destination = prompt_input("Enter the destination IATA code: ");
-    let departure_time = prompt_input("Enter the departure time (DD-MM-YYYY HH:MM:SS): ");
-    let arrival_time = prompt_input("Enter the arrival time (DD-MM-YYYY HH:MM:SS): ");
+fn add_flight(sim: &mut Simulation, console: &mut Console) -> Result<(), SimError> {
+    console.clean_scr();
+    let flight_number = console.prompt_input("Enter the flight number: ")?;
+    let origin = console.prompt_input("Enter the origin IATA code: ")?;
+    let destination = console.prompt_input("Enter the destination IATA code: ")?;
+    let departure_time =
+        console.prompt_input("Enter the departure time (DD-MM-YYYY HH:MM:SS): ")?;
+    let arrival_time = console.prompt_input("Enter the arrival time (DD-MM-YYYY HH:MM:SS): ")?;
 
-    let avg_speed_input = prompt_input("Enter the average speed (in km/h): ");
+    let avg_speed_input = console.prompt_input("Enter the average speed (in km/h): ")?;
     let avg_speed: i32 = match avg_speed_input.parse() {
         Ok(speed) => speed,
         Err(_) => return Err(SimError::InvalidInput),
@@ -37,26 +35,25 @@ fn add_flight(sim: &mut Simulation) -> Result<(), SimError> {
         &departure_time,
         &arrival_time,
         avg_speed,
-    )
-    .map_err(|_| SimError::InvalidFlight("Flight details are incorrect.".to_string()))?;
+    )?;
 
     sim.add_flight(flight)?;
 
     Ok(())
 }
 
-fn add_airport(sim: &mut Simulation) -> Result<(), SimError> {
-    clean_scr();
-    let iata_code = prompt_input("Enter the IATA code: ");
-    let country = prompt_input("Enter the country: ");
-    let name = prompt_input("Enter the airport name: ");
-    let latitude_input = prompt_input("Enter the latitude: ");
+fn add_airport(sim: &mut Simulation, console: &mut Console) -> Result<(), SimError> {
+    console.clean_scr();
+    let iata_code = console.prompt_input("Enter the IATA code: ")?;
+    let country = console.prompt_input("Enter the country: ")?;
+    let name = console.prompt_input("Enter the airport name: ")?;
+    let latitude_input = console.prompt_input("Enter the latitude: ")?;
     let latitude: f64 = match latitude_input.parse() {
         Ok(lat) => lat,
         Err(_) => return Err(SimError::InvalidInput),
     };
 
-    let longitude_input = prompt_input("Enter the longitude: ");
+    let longitude_input = console.prompt_input("Enter the longitude: ")?;
     let longitude: f64 = match longitude_input.parse() {
         Ok(lon) => lon,
         Err(_) => return Err(SimError::InvalidInput),
@@ -68,8 +65,8 @@ fn add_airport(sim: &mut Simulation) -> Result<(), SimError> {
     Ok(())
 }
 
-fn set_time_rate(sim: &mut Simulation) -> Result<(), SimError> {
-    let minutes_input = prompt_input("Enter the time rate (in minutes): ");
+fn set_time_rate(sim: &mut Simulation, console: &mut Console) -> Result<(), SimError> {
+    let minutes_input = console.prompt_input("Enter the time rate (in minutes): ")?;
     let minutes: i64 = match minutes_input.parse() {
         Ok(m) => m,
         Err(_) => return Err(SimError::InvalidInput),
@@ -80,27 +77,56 @@ fn set_time_rate(sim: &mut Simulation) -> Result<(), SimError> {
 }
 
 fn main() -> Result<(), SimError> {
+    let mut console = match script_path(std::env::args().skip(1))? {
+        Some(path) => Console::script(&path)?,
+        None => Console::interactive(),
+    };
+
     let ip = "127.0.0.1".parse().expect("Invalid IP format");
 
-    let db_client = Arc::new(Mutex::new(
-        Client::new(ip).map_err(|_| SimError::ClientError)?,
-    ));
+    // Una conexion por hilo de actualizacion, mas una para la sincronizacion y la consola
+    let db_client = Arc::new(Client::new(ip, UPDATE_THREADS + 1)?);
     let now: NaiveDateTime = Utc::now().naive_local();
 
     let timer = Timer::new(now, 1);
 
-    let thread_pool = Arc::new(ThreadPool::new(4));
+    let thread_pool = Arc::new(ThreadPool::new(UPDATE_THREADS));
 
     let mut sim = Simulation::new(db_client, timer, thread_pool);
 
     sim.start();
 
+    run_commands(&mut sim, &mut console);
+
+    sim.stop();
+    Ok(())
+}
+
+/// Returns the file given with `--script <file>`, if any.
+fn script_path(mut args: impl Iterator<Item = String>) -> Result<Option<String>, SimError> {
+    match args.next().as_deref() {
+        None => Ok(None),
+        Some("--script") => match (args.next(), args.next()) {
+            (Some(path), None) => Ok(Some(path)),
+            _ => Err(SimError::Other(
+                "Usage: simulator [--script <file>]".to_string(),
+            )),
+        },
+        Some(arg) => Err(SimError::Other(format!("Unknown argument: {}", arg))),
+    }
+}
+
+/// Runs the commands read from `console` until `exit` or the end of the input.
+///
+/// Each command reads its own details from the following lines, like the interactive
+/// prompts do, so a script is just what would be typed in the terminal.
+fn run_commands(sim: &mut Simulation, console: &mut Console) {
     loop {
-        println!("Enter command (type '-h' or '--help' for options): ");
-        let mut command = String::new();
-        io::stdin()
-            .read_line(&mut command)
-            .expect("Failed to read input");
+        let Some(command) =
+            console.read_line("Enter command (type '-h' or '--help' for options): \n")
+        else {
+            break;
+        };
 
         let args: Vec<&str> = command.split_whitespace().collect();
         if args.is_empty() {
@@ -109,19 +135,24 @@ fn main() -> Result<(), SimError> {
 
         match args[0] {
             "add-flight" => {
-                if add_flight(&mut sim).is_err() {
-                    println!("{}", SimError::InvalidInput);
+                if let Err(e) = add_flight(sim, console) {
+                    println!("{}", e);
                 }
             }
 
             "add-airport" => {
-                if add_airport(&mut sim).is_err() {
+                if add_airport(sim, console).is_err() {
                     println!("{}", SimError::InvalidInput);
                 }
             }
 
             "list-flights" => {
-                sim.display_flights();
+                // Un script no puede salir del modo en vivo, se muestran una sola vez
+                if console.is_interactive() {
+                    sim.display_flights();
+                } else {
+                    sim.print_flights();
+                }
             }
 
             "list-airports" => {
@@ -129,15 +160,15 @@ fn main() -> Result<(), SimError> {
             }
 
             "time-rate" => {
-                clean_scr();
-                if set_time_rate(&mut sim).is_err() {
+                console.clean_scr();
+                if set_time_rate(sim, console).is_err() {
                     println!("{}", SimError::InvalidInput);
                 }
             }
 
             "test-data" => {
-                clean_scr();
-                if add_test_dynamic_data(&mut sim).is_err() {
+                console.clean_scr();
+                if add_test_dynamic_data(sim).is_err() {
                     println!("{}", SimError::InvalidInput);
                 }
             }
@@ -152,30 +183,17 @@ fn main() -> Result<(), SimError> {
                 println!("Simulation resumed");
             }
 
-            "-h" | "help" => print_help(),
+            "-h" | "help" => print_help(console),
 
             "exit" => break,
 
             _ => eprintln!("Invalid command. Use -h for help."),
         }
     }
-
-    sim.stop();
-    Ok(())
-}
-
-fn prompt_input(prompt: &str) -> String {
-    print!("{}", prompt);
-    io::stdout().flush().unwrap();
-    let mut input = String::new();
-    io::stdin()
-        .read_line(&mut input)
-        .expect("Failed to read input");
-    input.trim().to_string()
 }
 
-fn print_help() {
-    clean_scr();
+fn print_help(console: &Console) {
+    console.clean_scr();
     println!("Available commands:");
     println!("  add-flight");
     println!("    Adds a new flight to the simulation. You'll be prompted for each detail.");
@@ -195,6 +213,9 @@ fn print_help() {
     println!("    Adds four airports and four flights to the simulation.");
     println!("  exit");
     println!("    Closes this application.");
+    println!();
+    println!("Run the simulator with --script <file> to read the commands, and the answers to");
+    println!("their prompts, from a file instead.");
 }
 
 fn _add_test_static_data(sim: &mut Simulation) -> Result<(), SimError> {
@@ -793,3 +814,61 @@ fn add_test_dynamic_data(sim: &mut Simulation) -> Result<(), SimError> {
     println!("Test data added successfully!");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::mock_node::MockNode;
+    use std::fs;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_script_adds_an_airport_and_a_flight() {
+        let ip = Ipv4Addr::new(127, 0, 0, 47);
+        let _node = MockNode::spawn(ip);
+        let mut sim = Simulation::new(
+            Arc::new(Client::new(ip, 1).unwrap()),
+            Timer::new(Utc::now().naive_utc(), 1),
+            Arc::new(ThreadPool::new(1)),
+        );
+
+        let script = std::env::temp_dir().join("flight_sim_test_script.txt");
+        fs::write(
+            &script,
+            "add-airport\nEZE\nARG\nEzeiza\n-34.8\n-58.5\n\
+             add-airport\nMDZ\nARG\nMendoza\n-32.9\n-68.8\n\
+             add-flight\nAR1001\nEZE\nMDZ\n01-12-2024 10:00:00\n01-12-2024 12:00:00\n500\n\
+             list-flights\n\
+             exit\n\
+             add-airport\nCOR\nARG\nCordoba\n-31.3\n-64.2\n",
+        )
+        .unwrap();
+
+        let mut console = Console::script(script.to_str().unwrap()).unwrap();
+        run_commands(&mut sim, &mut console);
+        fs::remove_file(&script).ok();
+
+        // Lo que sigue a `exit` no se ejecuta
+        let airports = sim.get_airports().unwrap();
+        let mut codes: Vec<&String> = airports.keys().collect();
+        codes.sort();
+        assert_eq!(codes, ["EZE", "MDZ"]);
+
+        let flights = sim.flights.read().unwrap();
+        assert_eq!(flights.len(), 1);
+        let flight = flights["AR1001"].read().unwrap();
+        assert_eq!(flight.origin.iata_code, "EZE");
+        assert_eq!(flight.destination.iata_code, "MDZ");
+        assert_eq!(flight.average_speed, 500);
+    }
+
+    #[test]
+    fn test_script_argument() {
+        let args = |args: &[&str]| script_path(args.iter().map(|arg| arg.to_string()));
+
+        assert!(matches!(args(&[]), Ok(None)));
+        assert!(matches!(args(&["--script", "demo.txt"]), Ok(Some(path)) if path == "demo.txt"));
+        assert!(args(&["--script"]).is_err());
+        assert!(args(&["--verbose"]).is_err());
+    }
+}
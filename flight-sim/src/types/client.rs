@@ -1,13 +1,23 @@
 use chrono::{DateTime, NaiveDateTime, NaiveTime};
-use driver::{CassandraClient, ClientError, QueryResult};
-use native_protocol::messages::result::rows::ColumnValue;
+use driver::{CassandraPool, ClientError, PooledConnection, QueryResult};
 use native_protocol::messages::result::{result_, rows};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::HashMap;
 use std::net::Ipv4Addr;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
 
 use crate::types::airport::Airport;
 use crate::types::flight::Flight;
 use crate::types::flight_status::FlightStatus;
+use crate::types::sim_error::SimError;
+
+/// Times a query is retried after the connection with the node is lost.
+const MAX_RETRIES: u32 = 4;
+/// Wait before the first retry. It doubles after each failed attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+/// Position updates kept before they are sent even if the tick did not end yet.
+const MAX_PENDING_UPDATES: usize = 300;
 
 /// A client for interacting with a Cassandra database, specifically for
 /// managing flight simulation data.
@@ -15,39 +25,96 @@ use crate::types::flight_status::FlightStatus;
 /// The `Client` handles creating keyspaces and tables, inserting and updating
 /// data, and fetching information from the database.
 pub struct Client {
-    cassandra_client: CassandraClient,
-    ip: Ipv4Addr,
+    pool: CassandraPool,
+    // Actualizaciones de posicion que todavia no se mandaron
+    pending_updates: Mutex<Vec<String>>,
 }
 
 impl Client {
     /// Initializes the flight simulation by connecting to Cassandra and setting up the keyspace and tables.
-    pub fn new(ip: Ipv4Addr) -> Result<Self, ClientError> {
-        let mut cassandra_client = CassandraClient::connect(ip)?;
-
-        cassandra_client.startup()?;
-
-        let mut client = Self {
-            cassandra_client,
-            ip,
+    ///
+    /// Up to `connections` operations can run at the same time, each one on its own connection
+    /// with the node.
+    pub fn new(ip: Ipv4Addr, connections: usize) -> Result<Self, SimError> {
+        let client = Self {
+            pool: CassandraPool::new(ip, connections),
+            pending_updates: Mutex::new(Vec::new()),
         };
         client.setup_keyspace_and_tables()?;
 
         Ok(client)
     }
 
-    fn recreate_client(&mut self) -> Result<(), ClientError> {
-        let mut cassandra_client =
-            CassandraClient::connect_with_config(self.ip, self.cassandra_client.config())?;
+    /// Sends the pending position updates and closes the connections with the node.
+    pub fn close_pool(&self) {
+        if let Err(e) = self.flush_updates() {
+            eprintln!("Failed to send the pending flight updates. Error: {}", e);
+        }
+        self.pool.close();
+    }
+
+    /// Sends every pending position update (see `update_flight`) in a single batch.
+    pub fn flush_updates(&self) -> Result<(), SimError> {
+        let updates = {
+            let mut pending = self
+                .pending_updates
+                .lock()
+                .map_err(|_| SimError::ClientError)?;
+            std::mem::take(&mut *pending)
+        };
+        if updates.is_empty() {
+            return Ok(());
+        }
 
-        cassandra_client.startup()?;
+        // Las posiciones no necesitan el batch log, se pisan en el proximo tick
+        let batch = format!("BEGIN UNLOGGED BATCH {} APPLY BATCH", updates.join(" "));
 
-        self.cassandra_client = cassandra_client;
+        let mut connection = self.acquire()?;
+        if let QueryResult::Error(e) = self.execute(&mut connection, &batch, "one")? {
+            eprintln!(
+                "Failed to update {} flight rows. Error: {:?}",
+                updates.len(),
+                e
+            );
+        }
 
         Ok(())
     }
 
+    /// Takes a connection from the pool, retrying with backoff while the node cannot be reached.
+    fn acquire(&self) -> Result<PooledConnection<'_>, SimError> {
+        with_backoff(|| self.pool.acquire())
+    }
+
+    /// Runs `query` on `connection`.
+    ///
+    /// If the connection was lost (the node restarted, for example), it is opened again and
+    /// started up before retrying the query, waiting longer after each failed attempt. The
+    /// error is only returned once `MAX_RETRIES` retries failed.
+    fn execute(
+        &self,
+        connection: &mut PooledConnection<'_>,
+        query: &str,
+        consistency: &str,
+    ) -> Result<QueryResult, SimError> {
+        let mut lost = false;
+
+        with_backoff(|| {
+            if lost {
+                connection.reconnect()?;
+                lost = false;
+            }
+
+            connection.execute(query, consistency).inspect_err(|e| {
+                lost = is_connection_error(e);
+            })
+        })
+    }
+
     /// Sets up the keyspace and required tables in Cassandra
-    fn setup_keyspace_and_tables(&mut self) -> Result<(), ClientError> {
+    fn setup_keyspace_and_tables(&self) -> Result<(), SimError> {
+        let mut connection = self.acquire()?;
+
         let create_keyspace_query = r#"
             CREATE KEYSPACE sky
             WITH REPLICATION = {
@@ -55,8 +122,7 @@ impl Client {
                 'replication_factor': 2
             };
         "#;
-        self.cassandra_client
-            .execute(create_keyspace_query, "quorum")?;
+        self.execute(&mut connection, create_keyspace_query, "quorum")?;
 
         let create_flights_table = r#"
             CREATE TABLE sky.flights (
@@ -72,8 +138,7 @@ impl Client {
                 PRIMARY KEY (airport, direction, departure_time, arrival_time, number)
             )
             "#;
-        self.cassandra_client
-            .execute(create_flights_table, "quorum")?;
+        self.execute(&mut connection, create_flights_table, "quorum")?;
 
         let create_flight_info_table = r#"
             CREATE TABLE sky.flight_info (
@@ -86,8 +151,7 @@ impl Client {
                 PRIMARY KEY (number)
             )
         "#;
-        self.cassandra_client
-            .execute(create_flight_info_table, "quorum")?;
+        self.execute(&mut connection, create_flight_info_table, "quorum")?;
 
         let create_airports_table = r#"
             CREATE TABLE sky.airports (
@@ -99,26 +163,24 @@ impl Client {
                 PRIMARY KEY (country, iata)
             )
         "#;
-        self.cassandra_client
-            .execute(create_airports_table, "quorum")?;
+        self.execute(&mut connection, create_airports_table, "quorum")?;
 
         println!("Keyspace and tables created successfully.");
         Ok(())
     }
 
     /// Inserts an airport into the Cassandra database.
-    pub fn insert_airport(&mut self, airport: &Airport) -> Result<(), ClientError> {
+    pub fn insert_airport(&self, airport: &Airport) -> Result<(), SimError> {
         let insert_airport_query = format!(
             "INSERT INTO sky.airports (iata, country, name, lat, lon) VALUES ('{}', '{}', '{}', {}, {});",
             airport.iata_code, airport.country, airport.name, airport.latitude, airport.longitude
         );
 
-        if let Err(e) = self
-            .cassandra_client
-            .execute(&insert_airport_query, "quorum")
-        {
-            eprintln!("Failed to add the airport. Error: {:?}", e);
-            return Ok(());
+        let mut connection = self.acquire()?;
+
+        if let Err(e) = self.execute(&mut connection, &insert_airport_query, "quorum") {
+            eprintln!("Failed to add the airport. Error: {}", e);
+            return Err(e);
         }
 
         println!("Airport '{}' added successfully.", airport.iata_code);
@@ -126,7 +188,7 @@ impl Client {
     }
 
     /// Inserts a flight into the Cassandra database.
-    pub fn insert_flight(&mut self, flight: &Flight) -> Result<(), ClientError> {
+    pub fn insert_flight(&self, flight: &Flight) -> Result<(), SimError> {
         let insert_departure_query = format!(
             "INSERT INTO sky.flights (number, status, lat, lon, angle, departure_time, arrival_time, airport, direction) VALUES ('{}', '{}', {}, {}, {}, {}, {}, '{}', 'departure');",
             flight.flight_number,
@@ -161,28 +223,21 @@ impl Client {
             flight.destination.iata_code
         );
 
-        if let Err(e) = self
-            .cassandra_client
-            .execute(&insert_departure_query, "quorum")
-        {
-            eprintln!("Failed to add the flight. Error: {:?}", e);
-            return Ok(());
+        let mut connection = self.acquire()?;
+
+        if let Err(e) = self.execute(&mut connection, &insert_departure_query, "quorum") {
+            eprintln!("Failed to add the flight. Error: {}", e);
+            return Err(e);
         }
 
-        if let Err(e) = self
-            .cassandra_client
-            .execute(&insert_arrival_query, "quorum")
-        {
-            eprintln!("Failed to add the flight (arrival). Error: {:?}", e);
-            return Ok(());
+        if let Err(e) = self.execute(&mut connection, &insert_arrival_query, "quorum") {
+            eprintln!("Failed to add the flight (arrival). Error: {}", e);
+            return Err(e);
         }
 
-        if let Err(e) = self
-            .cassandra_client
-            .execute(&insert_flight_info_query, "one")
-        {
-            eprintln!("Failed to add the flight info. Error: {:?}", e);
-            return Ok(());
+        if let Err(e) = self.execute(&mut connection, &insert_flight_info_query, "one") {
+            eprintln!("Failed to add the flight info. Error: {}", e);
+            return Err(e);
         }
 
         println!("Flight '{}' added successfully.", flight.flight_number);
@@ -191,9 +246,13 @@ impl Client {
     }
 
     /// Updates flight details in the Cassandra database.
-    pub fn update_flight(&mut self, flight: &Flight) -> Result<(), ClientError> {
+    ///
+    /// The updates are not sent right away: they are kept until the next `flush_updates`, so
+    /// the position of every flight moved in a tick is written with a single batch. They are
+    /// flushed earlier if `MAX_PENDING_UPDATES` is reached.
+    pub fn update_flight(&self, flight: &Flight) -> Result<(), SimError> {
         let update_query_status_departure = format!(
-            "UPDATE sky.flights SET lat = {}, lon = {}, angle = {} WHERE airport = '{}' AND direction = '{}' AND departure_time = {} AND arrival_time = {} AND number = {};",
+            "UPDATE sky.flights SET lat = {}, lon = {}, angle = {} WHERE airport = '{}' AND direction = '{}' AND departure_time = {} AND arrival_time = {} AND number = '{}';",
             flight.latitude,
             flight.longitude,
             flight.angle,
@@ -204,17 +263,8 @@ impl Client {
             flight.flight_number
         );
 
-        if let Err(e) = self
-            .cassandra_client
-            .execute(&update_query_status_departure, "one")
-        {
-            eprintln!("Failed to update the flight (departure). Error: {:?}", e);
-            self.recreate_client()?;
-            return Ok(());
-        }
-
         let update_query_status_arrival = format!(
-            "UPDATE sky.flights SET lat = {}, lon = {}, angle = {} WHERE airport = '{}' AND direction = '{}' AND departure_time = {} AND arrival_time = {} AND number = {};",
+            "UPDATE sky.flights SET lat = {}, lon = {}, angle = {} WHERE airport = '{}' AND direction = '{}' AND departure_time = {} AND arrival_time = {} AND number = '{}';",
             flight.latitude,
             flight.longitude,
             flight.angle,
@@ -225,32 +275,33 @@ impl Client {
             flight.flight_number
         );
 
-        if let Err(e) = self
-            .cassandra_client
-            .execute(&update_query_status_arrival, "one")
-        {
-            eprintln!("Failed to update the flight (arrival). Error: {:?}", e);
-            return Ok(());
-        }
-
         let update_query_flight_info = format!(
             "UPDATE sky.flight_info SET fuel = {}, speed = {}, height = {} WHERE number = '{}';",
             flight.fuel_level, flight.average_speed, flight.altitude, flight.flight_number
         );
 
-        if let Err(e) = self
-            .cassandra_client
-            .execute(&update_query_flight_info, "one")
-        {
-            eprintln!("Failed to update the flight info. Error: {:?}", e);
-            return Ok(());
+        let pending = {
+            let mut pending = self
+                .pending_updates
+                .lock()
+                .map_err(|_| SimError::ClientError)?;
+            pending.extend([
+                update_query_status_departure,
+                update_query_status_arrival,
+                update_query_flight_info,
+            ]);
+            pending.len()
+        };
+
+        if pending >= MAX_PENDING_UPDATES {
+            self.flush_updates()?;
         }
 
         Ok(())
     }
 
     /// Updates flight status and some details in the Cassandra database.
-    pub fn update_flight_status(&mut self, flight: &Flight) -> Result<(), ClientError> {
+    pub fn update_flight_status(&self, flight: &Flight) -> Result<(), SimError> {
         let update_query_status_departure = format!(
             "UPDATE sky.flights SET status = '{}', lat = {}, lon = {}, WHERE airport = '{}' AND direction = '{}' AND departure_time = {} AND arrival_time = {} AND number = {};",
             flight.status.as_str(),
@@ -263,16 +314,14 @@ impl Client {
             flight.flight_number
         );
 
-        if let Err(e) = self
-            .cassandra_client
-            .execute(&update_query_status_departure, "quorum")
-        {
+        let mut connection = self.acquire()?;
+
+        if let Err(e) = self.execute(&mut connection, &update_query_status_departure, "quorum") {
             eprintln!(
                 "Failed to update the flight status (departure). Error: {:?}",
                 e
             );
-            self.recreate_client()?;
-            return Ok(());
+            return Err(e);
         }
 
         let update_query_status_arrival = format!(
@@ -287,15 +336,12 @@ impl Client {
             flight.flight_number
         );
 
-        if let Err(e) = self
-            .cassandra_client
-            .execute(&update_query_status_arrival, "quorum")
-        {
+        if let Err(e) = self.execute(&mut connection, &update_query_status_arrival, "quorum") {
             eprintln!(
                 "Failed to update the flight status (arrival). Error: {:?}",
                 e
             );
-            return Ok(());
+            return Err(e);
         }
 
         Ok(())
@@ -303,10 +349,11 @@ impl Client {
 
     /// Fetches flights from the database for the given date and list of airports.
     pub fn fetch_flights(
-        &mut self,
+        &self,
         date: NaiveDateTime,
         airports: &HashMap<String, Airport>,
-    ) -> Result<Vec<Flight>, ClientError> {
+    ) -> Result<Vec<Flight>, SimError> {
+        let mut connection = self.acquire()?;
         let from = NaiveDateTime::new(date.date(), NaiveTime::from_hms_opt(0, 0, 0).unwrap());
         let from = from.and_utc().timestamp();
 
@@ -318,11 +365,11 @@ impl Client {
                 "SELECT number, status, lat, lon, angle, departure_time, arrival_time, direction FROM sky.flights WHERE airport = '{airport_code}' AND direction = 'departure' AND arrival_time > {from}"
             );
 
-            let result = self.cassandra_client.execute(&query, "quorum")?;
+            let result = self.execute(&mut connection, &query, "quorum")?;
 
             if let QueryResult::Result(result_::Result::Rows(res)) = result {
                 for row in res.rows_content {
-                    let flight = Client::build_flight_from_row(self, &row, airport)?;
+                    let flight = Client::build_flight_from_row(&row, airport)?;
                     flights.push(flight);
                 }
             }
@@ -332,10 +379,9 @@ impl Client {
     }
 
     fn build_flight_from_row(
-        &mut self,
-        row: &BTreeMap<String, ColumnValue>,
+        row: &rows::Row,
         selected_airport: &Airport,
-    ) -> Result<Flight, ClientError> {
+    ) -> Result<Flight, SimError> {
         let mut flight = Flight {
             flight_number: "XXXX".to_string(),
             status: FlightStatus::Scheduled,
@@ -358,18 +404,18 @@ impl Client {
                 flight.flight_number = number.to_string();
             }
         } else {
-            return Err(ClientError::ServerError);
+            return Err(SimError::ClientError);
         }
 
         if let Some(status) = row.get("status") {
             if let rows::ColumnValue::Ascii(status) = status {
                 match FlightStatus::from_str(status) {
                     Ok(status) => flight.status = status,
-                    Err(_) => return Err(ClientError::ServerError),
+                    Err(_) => return Err(SimError::ClientError),
                 }
             }
         } else {
-            return Err(ClientError::ServerError);
+            return Err(SimError::ClientError);
         }
 
         if let Some(departure_time) = row.get("departure_time") {
@@ -377,11 +423,11 @@ impl Client {
                 if let Some(datetime) = DateTime::from_timestamp(*departure_time, 0) {
                     flight.departure_time = datetime.naive_utc()
                 } else {
-                    return Err(ClientError::ServerError);
+                    return Err(SimError::ClientError);
                 }
             }
         } else {
-            return Err(ClientError::ServerError);
+            return Err(SimError::ClientError);
         }
 
         if let Some(arrival_time) = row.get("arrival_time") {
@@ -389,11 +435,11 @@ impl Client {
                 if let Some(datetime) = DateTime::from_timestamp(*arrival_time, 0) {
                     flight.arrival_time = datetime.naive_utc()
                 } else {
-                    return Err(ClientError::ServerError);
+                    return Err(SimError::ClientError);
                 }
             }
         } else {
-            return Err(ClientError::ServerError);
+            return Err(SimError::ClientError);
         }
 
         if let Some(lat) = row.get("lat") {
@@ -401,7 +447,7 @@ impl Client {
                 flight.latitude = *lat;
             }
         } else {
-            return Err(ClientError::ServerError);
+            return Err(SimError::ClientError);
         }
 
         if let Some(lon) = row.get("lon") {
@@ -409,7 +455,7 @@ impl Client {
                 flight.longitude = *lon;
             }
         } else {
-            return Err(ClientError::ServerError);
+            return Err(SimError::ClientError);
         }
 
         if let Some(angle) = row.get("angle") {
@@ -417,24 +463,25 @@ impl Client {
                 flight.angle = *angle;
             }
         } else {
-            return Err(ClientError::ServerError);
+            return Err(SimError::ClientError);
         }
 
         Ok(flight)
     }
 
     pub fn fetch_flight_info(
-        &mut self,
+        &self,
         flight: &mut Flight,
         airports: &HashMap<String, Airport>,
-    ) -> Result<(), ClientError> {
+    ) -> Result<(), SimError> {
+        let mut connection = self.acquire()?;
         let number = &flight.flight_number;
 
         let query = format!(
             "SELECT fuel, height, speed, destination FROM sky.flight_info WHERE number = '{number}'"
         );
 
-        let result = self.cassandra_client.execute(&query, "one")?;
+        let result = self.execute(&mut connection, &query, "one")?;
 
         if let QueryResult::Result(result_::Result::Rows(res)) = result {
             for row in res.rows_content {
@@ -443,7 +490,7 @@ impl Client {
                         flight.fuel_level = *fuel;
                     }
                 } else {
-                    return Err(ClientError::ServerError);
+                    return Err(SimError::ClientError);
                 }
 
                 if let Some(height) = row.get("height") {
@@ -451,7 +498,7 @@ impl Client {
                         flight.altitude = *height;
                     }
                 } else {
-                    return Err(ClientError::ServerError);
+                    return Err(SimError::ClientError);
                 }
 
                 if let Some(speed) = row.get("speed") {
@@ -459,7 +506,7 @@ impl Client {
                         flight.average_speed = *speed;
                     }
                 } else {
-                    return Err(ClientError::ServerError);
+                    return Err(SimError::ClientError);
                 }
 
                 if let Some(destination) = row.get("destination") {
@@ -467,11 +514,11 @@ impl Client {
                         if let Some(airport) = airports.get(destination) {
                             flight.destination = airport.clone();
                         } else {
-                            return Err(ClientError::ServerError);
+                            return Err(SimError::ClientError);
                         }
                     }
                 } else {
-                    return Err(ClientError::ServerError);
+                    return Err(SimError::ClientError);
                 }
             }
         }
@@ -479,3 +526,134 @@ impl Client {
         Ok(())
     }
 }
+
+// Errores de los que se puede salir abriendo otra conexion
+fn is_connection_error(error: &ClientError) -> bool {
+    matches!(
+        error,
+        ClientError::ConnectionError
+            | ClientError::IOError
+            | ClientError::TimeoutError
+            | ClientError::DeserializationError
+    )
+}
+
+/// Runs `attempt` until it succeeds, sleeping `INITIAL_BACKOFF` (doubled each time) between
+/// tries. Errors that are not caused by the connection are returned right away.
+fn with_backoff<T>(mut attempt: impl FnMut() -> Result<T, ClientError>) -> Result<T, SimError> {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut retries = 0;
+
+    loop {
+        match attempt() {
+            Ok(value) => return Ok(value),
+            Err(e) if !is_connection_error(&e) => return Err(SimError::ClientError),
+            Err(e) if retries == MAX_RETRIES => {
+                return Err(SimError::ConnectionLost(format!(
+                    "{:?} after {} retries",
+                    e, MAX_RETRIES
+                )))
+            }
+            Err(e) => {
+                eprintln!(
+                    "Connection with the node failed ({:?}), retrying in {:?}",
+                    e, backoff
+                );
+                thread::sleep(backoff);
+                backoff *= 2;
+                retries += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::mock_node::{flight, MockNode};
+
+    fn airport() -> Airport {
+        Airport::new(
+            "EZE".to_string(),
+            "ARG".to_string(),
+            "Ezeiza".to_string(),
+            -34.8,
+            -58.5,
+        )
+    }
+
+    #[test]
+    fn test_client_reconnects_when_the_node_drops_the_connection() {
+        let ip = Ipv4Addr::new(127, 0, 0, 43);
+        let node = MockNode::spawn(ip);
+        let client = Client::new(ip, 1).unwrap();
+
+        node.drop_next_queries(1);
+
+        assert!(client.insert_airport(&airport()).is_ok());
+
+        let inserts: Vec<(usize, String)> = node
+            .queries()
+            .into_iter()
+            .filter(|(_, query)| query.starts_with("INSERT INTO sky.airports"))
+            .collect();
+        assert_eq!(inserts.len(), 1);
+        // La conexion que uso el setup se perdio, el insert llega por una nueva
+        assert_ne!(inserts[0].0, 0);
+    }
+
+    #[test]
+    fn test_client_gives_up_after_max_retries() {
+        let ip = Ipv4Addr::new(127, 0, 0, 44);
+        let node = MockNode::spawn(ip);
+        let client = Client::new(ip, 1).unwrap();
+
+        node.drop_next_queries(usize::MAX);
+
+        assert!(matches!(
+            client.insert_airport(&airport()),
+            Err(SimError::ConnectionLost(_))
+        ));
+    }
+
+    #[test]
+    fn test_position_updates_of_a_tick_are_sent_in_one_batch() {
+        let ip = Ipv4Addr::new(127, 0, 0, 45);
+        let node = MockNode::spawn(ip);
+        let client = Client::new(ip, 1).unwrap();
+        let before = node.queries().len();
+
+        for number in ["AR1001", "AR1002", "AR1003", "AR1004", "AR1005"] {
+            client.update_flight(&flight(number)).unwrap();
+        }
+        // Nada se manda hasta el final del tick
+        assert_eq!(node.queries().len(), before);
+
+        client.flush_updates().unwrap();
+
+        let queries = node.queries();
+        assert_eq!(queries.len(), before + 1);
+        let batch = &queries[before].1;
+        assert!(batch.starts_with("BEGIN UNLOGGED BATCH"));
+        assert_eq!(batch.matches("UPDATE ").count(), 15);
+
+        // Sin actualizaciones pendientes no se manda otro batch
+        client.flush_updates().unwrap();
+        assert_eq!(node.queries().len(), before + 1);
+    }
+
+    #[test]
+    fn test_close_pool_flushes_the_pending_updates() {
+        let ip = Ipv4Addr::new(127, 0, 0, 46);
+        let node = MockNode::spawn(ip);
+        let client = Client::new(ip, 1).unwrap();
+
+        client.update_flight(&flight("AR1001")).unwrap();
+        client.close_pool();
+
+        let queries = node.queries();
+        let batch = &queries.last().unwrap().1;
+        assert!(batch.starts_with("BEGIN UNLOGGED BATCH"));
+        assert!(batch.contains("number = 'AR1001'"));
+    }
+}
@@ -27,6 +27,15 @@ const EARTH_RADIUS_KM: f64 = 6371.0;
 
 impl Flight {
     /// Creates a new flight from the information given from the console interface.
+    ///
+    /// The times must have the `DD-MM-YYYY HH:MM:SS` format.
+    ///
+    /// ### Errors
+    /// - `SimError::SameOriginAndDestination` if both codes are the same.
+    /// - `SimError::AirportNotFound` if the origin or the destination is not in `airports`.
+    /// - `SimError::InvalidDateFormat` if a time cannot be parsed.
+    /// - `SimError::ArrivalNotAfterDeparture` if the flight would land before it departs.
+    /// - `SimError::InvalidSpeed` if `average_speed` is not positive.
     pub fn new_from_console(
         airports: RwLockReadGuard<HashMap<String, Airport>>,
         flight_number: &str,
@@ -36,6 +45,10 @@ impl Flight {
         arrival_time_str: &str,
         average_speed: i32,
     ) -> Result<Self, SimError> {
+        if origin_code == destination_code {
+            return Err(SimError::SameOriginAndDestination(origin_code.to_string()));
+        }
+
         let origin = airports
             .get(origin_code)
             .ok_or_else(|| SimError::AirportNotFound(origin_code.to_string()))?
@@ -49,8 +62,15 @@ impl Flight {
         let departure_time = parse_datetime(departure_time_str)?;
         let arrival_time = parse_datetime(arrival_time_str)?;
 
-        if arrival_time <= departure_time || average_speed <= 0 {
-            return Err(SimError::InvalidInput);
+        if arrival_time <= departure_time {
+            return Err(SimError::ArrivalNotAfterDeparture(
+                arrival_time_str.to_string(),
+                departure_time_str.to_string(),
+            ));
+        }
+
+        if average_speed <= 0 {
+            return Err(SimError::InvalidSpeed(average_speed));
         }
 
         let starting_latitude = origin.latitude;
@@ -213,3 +233,112 @@ fn haversine_distance(origin_lat: f64, origin_lon: f64, dest_lat: f64, dest_lon:
 
     EARTH_RADIUS_KM * c
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::RwLock;
+
+    fn airports() -> RwLock<HashMap<String, Airport>> {
+        let mut airports = HashMap::new();
+        for (iata_code, name, latitude, longitude) in [
+            ("EZE", "Ezeiza", -34.8, -58.5),
+            ("MDZ", "Mendoza", -32.9, -68.8),
+        ] {
+            let airport = Airport::new(
+                iata_code.to_string(),
+                "ARG".to_string(),
+                name.to_string(),
+                latitude,
+                longitude,
+            );
+            airports.insert(iata_code.to_string(), airport);
+        }
+        RwLock::new(airports)
+    }
+
+    fn new_flight(
+        origin: &str,
+        destination: &str,
+        departure: &str,
+        arrival: &str,
+        speed: i32,
+    ) -> Result<Flight, SimError> {
+        let airports = airports();
+        Flight::new_from_console(
+            airports.read().unwrap(),
+            "AR1001",
+            origin,
+            destination,
+            departure,
+            arrival,
+            speed,
+        )
+    }
+
+    #[test]
+    fn test_valid_flight() {
+        let flight = new_flight(
+            "EZE",
+            "MDZ",
+            "01-12-2024 10:00:00",
+            "01-12-2024 12:00:00",
+            500,
+        )
+        .unwrap();
+
+        assert_eq!(flight.origin.iata_code, "EZE");
+        assert_eq!(flight.destination.iata_code, "MDZ");
+        assert!(flight.total_distance > 0.0);
+    }
+
+    #[test]
+    fn test_flight_with_a_malformed_date() {
+        let result = new_flight("EZE", "MDZ", "2024-12-01 10:00", "01-12-2024 12:00:00", 500);
+
+        assert!(
+            matches!(result, Err(SimError::InvalidDateFormat(date)) if date == "2024-12-01 10:00")
+        );
+    }
+
+    #[test]
+    fn test_flight_must_arrive_after_it_departs() {
+        for arrival in ["01-12-2024 09:00:00", "01-12-2024 10:00:00"] {
+            let result = new_flight("EZE", "MDZ", "01-12-2024 10:00:00", arrival, 500);
+
+            assert!(matches!(
+                result,
+                Err(SimError::ArrivalNotAfterDeparture(arr, dep))
+                    if arr == arrival && dep == "01-12-2024 10:00:00"
+            ));
+        }
+    }
+
+    #[test]
+    fn test_flight_speed_must_be_positive() {
+        for speed in [0, -300] {
+            let result = new_flight(
+                "EZE",
+                "MDZ",
+                "01-12-2024 10:00:00",
+                "01-12-2024 12:00:00",
+                speed,
+            );
+
+            assert!(matches!(result, Err(SimError::InvalidSpeed(s)) if s == speed));
+        }
+    }
+
+    #[test]
+    fn test_flight_cannot_land_where_it_departs() {
+        let result = new_flight(
+            "EZE",
+            "EZE",
+            "01-12-2024 10:00:00",
+            "01-12-2024 12:00:00",
+            500,
+        );
+
+        assert!(matches!(result, Err(SimError::SameOriginAndDestination(code)) if code == "EZE"));
+    }
+}
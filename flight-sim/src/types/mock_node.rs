@@ -0,0 +1,211 @@
+//! A fake node for the tests of the simulator. It speaks just enough of the native protocol
+//! over TLS for the driver: `Startup` is answered with `Ready` and every query with `Void`,
+//! each one on the stream id of its request.
+//! It also has the flight the tests send to it.
+
+use crate::types::airport::Airport;
+use crate::types::flight::Flight;
+use crate::types::flight_status::FlightStatus;
+use chrono::Utc;
+use native_protocol::{frame::Frame, messages::result::result_, Serializable};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const NATIVE_PORT: u16 = 0x4645;
+/// How long held queries wait for the rest before they are answered anyway, so a test whose
+/// queries never overlap fails on its assertions instead of hanging.
+const HOLD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Queries received by the node, each one with the number of the connection it came from.
+type ReceivedQueries = Arc<Mutex<Vec<(usize, String)>>>;
+
+/// Queries that are not answered until `held` of them arrived.
+#[derive(Default)]
+struct HeldQueries {
+    held: usize,
+    arrived: usize,
+}
+
+type QueryGate = Arc<(Mutex<HeldQueries>, Condvar)>;
+
+pub struct MockNode {
+    received: ReceivedQueries,
+    drops_left: Arc<AtomicUsize>,
+    gate: QueryGate,
+}
+
+impl MockNode {
+    /// Starts listening at `ip`. Every connection is served on its own thread.
+    pub fn spawn(ip: Ipv4Addr) -> Self {
+        let config = Arc::new(server_config());
+        let listener = TcpListener::bind((ip, NATIVE_PORT)).unwrap();
+        let node = Self {
+            received: Arc::new(Mutex::new(Vec::new())),
+            drops_left: Arc::new(AtomicUsize::new(0)),
+            gate: QueryGate::default(),
+        };
+
+        let received = Arc::clone(&node.received);
+        let drops_left = Arc::clone(&node.drops_left);
+        let gate = Arc::clone(&node.gate);
+        thread::spawn(move || {
+            for (connection_id, stream) in listener.incoming().enumerate() {
+                let Ok(stream) = stream else {
+                    return;
+                };
+                let config = Arc::clone(&config);
+                let received = Arc::clone(&received);
+                let drops_left = Arc::clone(&drops_left);
+                let gate = Arc::clone(&gate);
+                thread::spawn(move || {
+                    serve_connection(connection_id, stream, config, received, drops_left, gate)
+                });
+            }
+        });
+
+        node
+    }
+
+    /// The next `count` queries close their connection instead of being answered, as if the
+    /// node had restarted.
+    pub fn drop_next_queries(&self, count: usize) {
+        self.drops_left.store(count, Ordering::SeqCst);
+    }
+
+    /// The next `count` queries are answered only once all of them arrived, so they must be
+    /// in flight at the same time, each one on its own connection.
+    pub fn hold_next_queries(&self, count: usize) {
+        let (held, _) = &*self.gate;
+        *held.lock().unwrap() = HeldQueries {
+            held: count,
+            arrived: 0,
+        };
+    }
+
+    /// Queries answered so far, with the number of the connection each one came from.
+    pub fn queries(&self) -> Vec<(usize, String)> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+/// A flight from EZE to MDZ, departing now, to send to the node.
+pub fn flight(number: &str) -> Flight {
+    let now = Utc::now().naive_utc();
+
+    Flight {
+        flight_number: number.to_string(),
+        status: FlightStatus::Scheduled,
+        departure_time: now,
+        arrival_time: now + chrono::Duration::hours(2),
+        origin: Airport::new(
+            "EZE".to_string(),
+            "ARG".to_string(),
+            "Ezeiza".to_string(),
+            -34.8,
+            -58.5,
+        ),
+        destination: Airport::new(
+            "MDZ".to_string(),
+            "ARG".to_string(),
+            "Mendoza".to_string(),
+            -32.9,
+            -68.8,
+        ),
+        latitude: -34.8,
+        longitude: -58.5,
+        angle: 0.0,
+        altitude: 0,
+        fuel_level: 100.0,
+        total_distance: 0.0,
+        distance_traveled: 0.0,
+        average_speed: 500,
+    }
+}
+
+fn server_config() -> ServerConfig {
+    let certs_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("..")
+        .join("certs");
+    let certs = CertificateDer::pem_file_iter(certs_path.join("cert.crt"))
+        .unwrap()
+        .map(|cert| cert.unwrap())
+        .collect();
+    let private_key = PrivateKeyDer::from_pem_file(certs_path.join("cert.key")).unwrap();
+
+    rustls::crypto::aws_lc_rs::default_provider()
+        .install_default()
+        .ok();
+    ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, private_key)
+        .unwrap()
+}
+
+fn serve_connection(
+    connection_id: usize,
+    stream: TcpStream,
+    config: Arc<ServerConfig>,
+    received: ReceivedQueries,
+    drops_left: Arc<AtomicUsize>,
+    gate: QueryGate,
+) {
+    let mut tls = StreamOwned::new(ServerConnection::new(config).unwrap(), stream);
+    let mut buffer = [0u8; 16384];
+
+    loop {
+        let read = match tls.read(&mut buffer) {
+            Ok(0) | Err(_) => return,
+            Ok(read) => read,
+        };
+        let Ok(stream_id) = Frame::stream_of(&buffer[..read]) else {
+            return;
+        };
+        let response = match Frame::from_bytes(&buffer[..read]) {
+            Ok(Frame::Startup) => Frame::Ready,
+            Ok(Frame::Query(query)) => {
+                let dropped = drops_left
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                        left.checked_sub(1)
+                    })
+                    .is_ok();
+                if dropped {
+                    // Se cierra la conexion sin responder
+                    return;
+                }
+
+                received.lock().unwrap().push((connection_id, query.query));
+                wait_for_held_queries(&gate);
+                Frame::Result(result_::Result::Void)
+            }
+            _ => return,
+        };
+
+        // La respuesta lleva el stream id del pedido, como espera el driver
+        let response = response.to_bytes_with_stream(stream_id).unwrap();
+        if tls.write_all(&response).is_err() {
+            return;
+        }
+    }
+}
+
+// Si la consulta es una de las retenidas, espera a que lleguen las demas
+fn wait_for_held_queries(gate: &QueryGate) {
+    let (held, all_arrived) = &**gate;
+    let mut held = held.lock().unwrap();
+    if held.arrived >= held.held {
+        return;
+    }
+    held.arrived += 1;
+    all_arrived.notify_all();
+    let _ = all_arrived
+        .wait_timeout_while(held, HOLD_TIMEOUT, |held| held.arrived < held.held)
+        .unwrap();
+}
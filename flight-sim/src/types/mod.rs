@@ -13,3 +13,6 @@ pub mod simulation;
 pub mod flight;
 
 pub mod client;
+
+#[cfg(test)]
+pub mod mock_node;
@@ -4,14 +4,17 @@ use std::fmt;
 #[derive(Debug)]
 pub enum SimError {
     InvalidInput,
-    InvalidFlight(String), // For invalid flight details (e.g., wrong date format)
-    AirportNotFound(String), // If airport can't be found
-    InvalidDateFormat(String), // When the date format is incorrect
-    TimerLockError(String), // Para errores de bloqueo del Timer
-    TimerStartError(String), // Para errores al iniciar el Timer
-    InvalidDuration(String), // Cuando se pasa una duración inválida
-    Other(String),         // Generic error case with a custom message
-    ClientError,           // If something went wrong with the client
+    AirportNotFound(String),                  // If airport can't be found
+    InvalidDateFormat(String),                // When the date format is incorrect
+    ArrivalNotAfterDeparture(String, String), // Arrival time (first) is not later than the departure
+    InvalidSpeed(i32),                        // The average speed of a flight is not positive
+    SameOriginAndDestination(String), // The flight would land at the airport it departs from
+    TimerLockError(String),           // Para errores de bloqueo del Timer
+    TimerStartError(String),          // Para errores al iniciar el Timer
+    InvalidDuration(String),          // Cuando se pasa una duración inválida
+    Other(String),                    // Generic error case with a custom message
+    ClientError,                      // If something went wrong with the client
+    ConnectionLost(String),           // The node could not be reached even after retrying
 }
 
 /// Implement the Display trait for user-friendly error messages
@@ -21,18 +24,39 @@ impl fmt::Display for SimError {
             SimError::InvalidInput => {
                 write!(f, "Invalid input. Please check your input and try again.")
             }
-            SimError::InvalidFlight(ref flight) => write!(f, "Invalid flight details: {}", flight),
             SimError::AirportNotFound(ref iata_code) => {
                 write!(f, "Airport not found: {}", iata_code)
             }
             SimError::InvalidDateFormat(ref date_str) => {
-                write!(f, "Invalid date format: {}", date_str)
+                write!(
+                    f,
+                    "Invalid date format: '{}'. Expected DD-MM-YYYY HH:MM:SS",
+                    date_str
+                )
             }
+            SimError::ArrivalNotAfterDeparture(arrival, departure) => write!(
+                f,
+                "The arrival time ({}) must be later than the departure time ({})",
+                arrival, departure
+            ),
+            SimError::InvalidSpeed(speed) => write!(
+                f,
+                "Invalid average speed: {} km/h. It must be greater than 0",
+                speed
+            ),
+            SimError::SameOriginAndDestination(iata_code) => write!(
+                f,
+                "The origin and the destination are the same airport: {}",
+                iata_code
+            ),
             SimError::TimerLockError(msg) => write!(f, "Timer lock error: {}", msg),
             SimError::TimerStartError(msg) => write!(f, "Timer start error: {}", msg),
             SimError::InvalidDuration(msg) => write!(f, "Invalid duration: {}", msg),
             SimError::Other(ref message) => write!(f, "Error: {}", message),
             SimError::ClientError => write!(f, "Something went wrong with the client"),
+            SimError::ConnectionLost(msg) => {
+                write!(f, "Lost the connection with the node: {}", msg)
+            }
         }
     }
 }
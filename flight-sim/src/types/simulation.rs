@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use std::io::Write;
-use std::sync::{mpsc, Arc, Mutex, RwLock, RwLockReadGuard};
+use std::sync::{mpsc, Arc, RwLock, RwLockReadGuard};
 use std::time::Duration;
 use std::{io, thread};
 use threadpool::ThreadPool;
@@ -21,14 +21,14 @@ use super::TICK_FREQUENCY_MILLIS;
 pub struct Simulation {
     pub flights: Arc<RwLock<HashMap<String, Arc<RwLock<Flight>>>>>, // Flights wrapped in Arc<RwLock>
     pub airports: Arc<RwLock<HashMap<String, Airport>>>,            // Airports
-    pub db: Arc<Mutex<Client>>,                                     // DB Client protected by Mutex
-    pub timer: Arc<Timer>,                                          // Timer
-    pub thread_pool: Arc<ThreadPool>,                               // ThreadPool
+    pub db: Arc<Client>,              // DB Client with a pool of connections
+    pub timer: Arc<Timer>,            // Timer
+    pub thread_pool: Arc<ThreadPool>, // ThreadPool
 }
 
 impl Simulation {
     /// Create a new simulation
-    pub fn new(db: Arc<Mutex<Client>>, timer: Arc<Timer>, thread_pool: Arc<ThreadPool>) -> Self {
+    pub fn new(db: Arc<Client>, timer: Arc<Timer>, thread_pool: Arc<ThreadPool>) -> Self {
         Simulation {
             flights: Arc::new(RwLock::new(HashMap::new())),
             airports: Arc::new(RwLock::new(HashMap::new())),
@@ -47,6 +47,11 @@ impl Simulation {
         let timer = Arc::clone(&self.timer);
 
         let _ = timer.start(move |current_time, tick_count| {
+            // Las posiciones calculadas en el tick anterior se escriben juntas
+            if let Err(e) = db.flush_updates() {
+                eprintln!("Failed to flush flight updates: {:?}", e);
+            }
+
             {
                 if let Ok(flights_lock) = flights.try_read() {
                     for flight_arc in flights_lock.values() {
@@ -62,19 +67,15 @@ impl Simulation {
                                 let updated_state =
                                     flight_lock.check_states_and_update_flight(current_time);
 
-                                // Update the database
-                                if let Ok(mut db_lock) = db.lock() {
-                                    let result = if updated_state {
-                                        db_lock.update_flight_status(&flight_lock)
-                                    } else {
-                                        db_lock.update_flight(&flight_lock)
-                                    };
-
-                                    if let Err(e) = result {
-                                        eprintln!("Database update error: {:?}", e);
-                                    }
+                                // Update the database, each flight on its own connection
+                                let result = if updated_state {
+                                    db.update_flight_status(&flight_lock)
                                 } else {
-                                    eprintln!("Failed to lock DB for updating flight.");
+                                    db.update_flight(&flight_lock)
+                                };
+
+                                if let Err(e) = result {
+                                    eprintln!("Database update error: {:?}", e);
                                 }
                             } else {
                                 eprintln!("Failed to lock flight for update. Skipping.");
@@ -89,24 +90,16 @@ impl Simulation {
             // Synchronize with the database every 5 ticks
             if tick_count % 5 == 0 {
                 let mut flights_from_db = Vec::new();
-                {
-                    if let Ok(mut db_lock) = db.lock() {
-                        if let Ok(airport_list) = airports.read() {
-                            flights_from_db =
-                                match db_lock.fetch_flights(current_time, &airport_list) {
-                                    Ok(flights) => flights,
-                                    Err(e) => {
-                                        eprintln!("Failed to fetch flights from DB: {:?}", e);
-                                        return;
-                                    }
-                                };
-                        } else {
-                            eprintln!("Failed to lock airports for read. Skipping database sync.");
+                if let Ok(airport_list) = airports.read() {
+                    flights_from_db = match db.fetch_flights(current_time, &airport_list) {
+                        Ok(flights) => flights,
+                        Err(e) => {
+                            eprintln!("Failed to fetch flights from DB: {:?}", e);
+                            return;
                         }
-                    } else {
-                        eprintln!("Failed to lock DB for fetching flights. Skipping.");
-                        return;
-                    }
+                    };
+                } else {
+                    eprintln!("Failed to lock airports for read. Skipping database sync.");
                 }
 
                 if let Ok(mut flights_lock) = flights.try_write() {
@@ -117,16 +110,8 @@ impl Simulation {
                                     if flight_lock.status != flight.status {
                                         flight_lock.status = flight.status;
                                         flight_lock.check_states_and_update_flight(current_time);
-                                        if let Ok(mut db_lock) = db.lock() {
-                                            let result = db_lock.update_flight_status(&flight_lock);
-                                            if let Err(e) = result {
-                                                eprintln!("Database update error: {:?}", e);
-                                            }
-                                        } else {
-                                            eprintln!(
-                                                "Failed to lock DB for fetching flights. Skipping."
-                                            );
-                                            return;
+                                        if let Err(e) = db.update_flight_status(&flight_lock) {
+                                            eprintln!("Database update error: {:?}", e);
                                         }
                                     }
                                 } else {
@@ -137,16 +122,14 @@ impl Simulation {
                                 }
                             }
                             None => {
-                                if let Ok(mut db_lock) = db.lock() {
-                                    if let Err(e) = db_lock
-                                        .fetch_flight_info(&mut flight, &airports.read().unwrap())
-                                    {
-                                        eprintln!(
-                                            "Failed to fetch additional flight info for {:?}: {:?}",
-                                            flight.flight_number, e
-                                        );
-                                        continue;
-                                    }
+                                if let Err(e) =
+                                    db.fetch_flight_info(&mut flight, &airports.read().unwrap())
+                                {
+                                    eprintln!(
+                                        "Failed to fetch additional flight info for {:?}: {:?}",
+                                        flight.flight_number, e
+                                    );
+                                    continue;
                                 }
                                 flights_lock.insert(
                                     flight.flight_number.clone(),
@@ -164,11 +147,7 @@ impl Simulation {
 
     /// Adds an airport to the simulation.
     pub fn add_airport(&self, airport: Airport) -> Result<(), SimError> {
-        {
-            let mut db = self.db.lock().map_err(|_| SimError::ClientError)?;
-            db.insert_airport(&airport)
-                .map_err(|_| SimError::ClientError)?;
-        }
+        self.db.insert_airport(&airport)?;
 
         let mut airports_lock = self
             .airports
@@ -181,11 +160,7 @@ impl Simulation {
 
     /// Adds a flight to the simulation.
     pub fn add_flight(&self, flight: Flight) -> Result<(), SimError> {
-        {
-            let mut db = self.db.lock().map_err(|_| SimError::ClientError)?;
-            db.insert_flight(&flight)
-                .map_err(|_| SimError::ClientError)?;
-        }
+        self.db.insert_flight(&flight)?;
 
         let mut flights_lock = self
             .flights
@@ -215,34 +190,9 @@ impl Simulation {
         loop {
             io::stdout().flush().ok();
 
-            if let Ok(flights_lock) = self.flights.try_read() {
-                print!("\x1B[2J\x1B[1;1H");
-                if let Ok(time) = self.timer.current_time.try_lock() {
-                    println!("Current time: {}", time.format("%d-%m-%Y %H:%M:%S"));
-                }
-                if flights_lock.is_empty() {
-                    println!("No flights available.");
-                } else {
-                    println!(
-                        "\n{:<15} {:<10} {:<15} {:<15} {:<10} {:<10}",
-                        "Flight Number", "Status", "Origin", "Destination", "Latitude", "Longitude"
-                    );
-                    for flight_arc in flights_lock.values() {
-                        if let Ok(flight_lock) = flight_arc.try_read() {
-                            println!(
-                                "{:<15} {:<10} {:<15} {:<15} {:<10.4} {:<10.4}",
-                                flight_lock.flight_number,
-                                flight_lock.status.as_str(),
-                                flight_lock.origin.iata_code,
-                                flight_lock.destination.iata_code,
-                                flight_lock.latitude,
-                                flight_lock.longitude
-                            );
-                        }
-                    }
-                }
-                println!("\nPress 'q' and Enter to exit list-flights mode");
-            }
+            print!("\x1B[2J\x1B[1;1H");
+            self.print_flights();
+            println!("\nPress 'q' and Enter to exit list-flights mode");
 
             if rx.try_recv().is_ok() {
                 break;
@@ -252,6 +202,38 @@ impl Simulation {
         }
     }
 
+    /// Prints the current time and the flights once. Flights that are being updated are skipped.
+    pub fn print_flights(&self) {
+        let Ok(flights_lock) = self.flights.try_read() else {
+            return;
+        };
+
+        if let Ok(time) = self.timer.current_time.try_lock() {
+            println!("Current time: {}", time.format("%d-%m-%Y %H:%M:%S"));
+        }
+        if flights_lock.is_empty() {
+            println!("No flights available.");
+        } else {
+            println!(
+                "\n{:<15} {:<10} {:<15} {:<15} {:<10} {:<10}",
+                "Flight Number", "Status", "Origin", "Destination", "Latitude", "Longitude"
+            );
+            for flight_arc in flights_lock.values() {
+                if let Ok(flight_lock) = flight_arc.try_read() {
+                    println!(
+                        "{:<15} {:<10} {:<15} {:<15} {:<10.4} {:<10.4}",
+                        flight_lock.flight_number,
+                        flight_lock.status.as_str(),
+                        flight_lock.origin.iata_code,
+                        flight_lock.destination.iata_code,
+                        flight_lock.latitude,
+                        flight_lock.longitude
+                    );
+                }
+            }
+        }
+    }
+
     /// List the airports in the simulation
     pub fn list_airports(&self) {
         if let Ok(airports_lock) = self.airports.read() {
@@ -272,10 +254,11 @@ impl Simulation {
         self.timer.set_tick_advance(minutes)
     }
 
-    /// Stop the timer and the threadpool.
+    /// Stop the timer and the threadpool, and close the connections with the database.
     pub fn stop(&self) {
         self.timer.stop();
         self.thread_pool.join();
+        self.db.close_pool();
     }
 
     /// Return a clone of the list of airports
@@ -304,3 +287,57 @@ fn should_update(flight: &Arc<RwLock<Flight>>) -> bool {
     }
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::mock_node::{flight, MockNode};
+    use chrono::Utc;
+    use std::collections::HashSet;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_concurrent_add_flight_use_distinct_pooled_connections() {
+        let ip = Ipv4Addr::new(127, 0, 0, 42);
+        let node = MockNode::spawn(ip);
+
+        let client = Client::new(ip, 4).unwrap();
+        let sim = Simulation::new(
+            Arc::new(client),
+            Timer::new(Utc::now().naive_utc(), 1),
+            Arc::new(ThreadPool::new(1)),
+        );
+
+        let numbers = ["AR1001", "AR1002", "AR1003", "AR1004"];
+        // El primer insert de cada vuelo se contesta cuando llegaron los cuatro
+        node.hold_next_queries(numbers.len());
+        thread::scope(|scope| {
+            let handles: Vec<_> = numbers
+                .iter()
+                .map(|number| scope.spawn(|| sim.add_flight(flight(number))))
+                .collect();
+
+            for handle in handles {
+                assert!(handle.join().unwrap().is_ok());
+            }
+        });
+
+        let queries = node.queries();
+        let flight_connections: HashSet<usize> = queries
+            .iter()
+            .filter(|(_, query)| query.contains("INSERT INTO sky.flights "))
+            .map(|(connection_id, _)| *connection_id)
+            .collect();
+        assert_eq!(flight_connections.len(), numbers.len());
+
+        // Cada vuelo se escribe completo: salida, llegada e informacion
+        let inserts = queries
+            .iter()
+            .filter(|(_, query)| query.starts_with("INSERT"))
+            .count();
+        assert_eq!(inserts, numbers.len() * 3);
+        assert_eq!(sim.flights.read().unwrap().len(), numbers.len());
+
+        sim.db.close_pool();
+    }
+}
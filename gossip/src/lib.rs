@@ -14,20 +14,50 @@ use std::{
     net::Ipv4Addr,
 };
 use structures::{
-    application_state::{KeyspaceSchema, NodeStatus, Schema, TableSchema},
+    application_state::{DataDigest, KeyspaceSchema, NodeLoad, NodeStatus, Schema, TableSchema},
     endpoint_state::EndpointState,
     heartbeat_state::HeartbeatState,
 };
 pub mod messages;
+#[cfg(test)]
+mod simulator;
 pub mod structures;
 
+/// Maximum amount of nodes an endpoint gossips with on each round.
+pub const GOSSIP_FANOUT: usize = 3;
+
+/// Maximum amount of digests in a `SYN`, so its size does not grow with the cluster. The
+/// endpoints that do not fit are gossiped about in the next rounds.
+pub const MAX_DIGESTS_PER_SYN: usize = 64;
+
 /// Struct to represent the gossiper node.
 ///
 /// ### Fields
 /// - `endpoints_state`: HashMap containing the state of all the endpoints that the gossiper knows about.
+/// - `events`: What the gossiper learned about the endpoints since the last `take_events`.
+/// - `syn_cursor`: Position, among the other endpoints sorted by ip, of the first one the next
+///   `SYN` carries the digest of.
+/// - `fanout`: Maximum amount of nodes picked on each round, `GOSSIP_FANOUT` by default.
 #[derive(Clone)]
 pub struct Gossiper {
     pub endpoints_state: HashMap<Ipv4Addr, EndpointState>,
+    pub events: Vec<GossipEvent>,
+    pub syn_cursor: usize,
+    pub fanout: usize,
+}
+
+/// A change in the state of an endpoint that the node has to act on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GossipEvent {
+    /// The endpoint started a new generation, so it restarted: the writes it missed while it
+    /// was down, or the data it lost, have to be repaired.
+    Restarted(Ipv4Addr),
+    /// The endpoint `replacement` joined in the place of the dead endpoint `dead`, which was
+    /// forgotten: it owns the token ranges of `dead` and has to receive their rows.
+    Replaced {
+        dead: Ipv4Addr,
+        replacement: Ipv4Addr,
+    },
 }
 
 #[derive(Debug)]
@@ -38,6 +68,7 @@ pub enum GossipError {
     NoSuchKeyspace,
     KeyspaceAlreadyExists,
     TableAlreadyExists,
+    NoSuchTable,
 }
 
 impl fmt::Display for GossipError {
@@ -49,6 +80,7 @@ impl fmt::Display for GossipError {
             GossipError::NoSuchKeyspace => "The given keyspace does not exist",
             GossipError::KeyspaceAlreadyExists => "The given keyspace already exists",
             GossipError::TableAlreadyExists => "The given table already exists",
+            GossipError::NoSuchTable => "The given table does not exist",
         };
         write!(f, "{}", description)
     }
@@ -65,7 +97,52 @@ impl Gossiper {
     pub fn new() -> Self {
         Self {
             endpoints_state: HashMap::new(),
+            events: Vec::new(),
+            syn_cursor: 0,
+            fanout: GOSSIP_FANOUT,
+        }
+    }
+
+    /// Returns the events gossip produced since the last call, oldest first, and forgets them.
+    pub fn take_events(&mut self) -> Vec<GossipEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Stores the state received for an endpoint, and records a `GossipEvent::Restarted` if it
+    /// has a newer generation than the known one.
+    ///
+    /// A generation of 0 is the state of an endpoint nothing was heard from yet, like a seed,
+    /// so learning its first generation is not a restart.
+    ///
+    /// A state that replaces a dead endpoint makes the gossiper forget that endpoint and
+    /// records a `GossipEvent::Replaced` the first time. The states of a replaced endpoint
+    /// that other nodes still gossip about are ignored.
+    fn update_endpoint_state(&mut self, address: Ipv4Addr, state: EndpointState) {
+        if self.is_replaced(address) {
+            return;
         }
+        if let Some(dead) = state.application_state.replaces {
+            let known_replacement = self
+                .endpoints_state
+                .get(&address)
+                .is_some_and(|known| known.application_state.replaces == Some(dead));
+            if !known_replacement {
+                self.events.push(GossipEvent::Replaced {
+                    dead,
+                    replacement: address,
+                });
+            }
+            self.endpoints_state.remove(&dead);
+        }
+
+        let restarted = self.endpoints_state.get(&address).is_some_and(|known| {
+            known.heartbeat_state.generation != 0
+                && state.heartbeat_state.generation > known.heartbeat_state.generation
+        });
+        if restarted {
+            self.events.push(GossipEvent::Restarted(address));
+        }
+        self.endpoints_state.insert(address, state);
     }
 
     /// Increment the version of the heartbeat state of the endpoint with the given ip.
@@ -93,6 +170,33 @@ impl Gossiper {
         self
     }
 
+    /// Sets the maximum amount of nodes picked on each round. At least one is picked.
+    pub fn with_fanout(mut self, fanout: usize) -> Self {
+        self.fanout = fanout.max(1);
+        self
+    }
+
+    /// Makes the endpoint with the given ip the replacement of the dead endpoint `dead`: its
+    /// state advertises it, so every node forgets `dead` once it learns about it, and this
+    /// gossiper forgets it right away.
+    pub fn replace(&mut self, ip: Ipv4Addr, dead: Ipv4Addr) -> Result<(), GossipError> {
+        self.endpoints_state
+            .get_mut(&ip)
+            .ok_or(GossipError::NoEndpointStateForIp)?
+            .application_state
+            .set_replaces(dead);
+        self.endpoints_state.remove(&dead);
+
+        Ok(())
+    }
+
+    /// Whether a known endpoint replaced the endpoint with the given ip.
+    pub fn is_replaced(&self, ip: Ipv4Addr) -> bool {
+        self.endpoints_state
+            .values()
+            .any(|state| state.application_state.replaces == Some(ip))
+    }
+
     /// Changes the status of the application state of the endpoint with the given ip.
     pub fn change_status(&mut self, ip: Ipv4Addr, status: NodeStatus) -> Result<(), GossipError> {
         let app_state = &mut self
@@ -107,6 +211,70 @@ impl Gossiper {
         Ok(())
     }
 
+    /// Publishes the load of the endpoint with the given ip.
+    ///
+    /// The version of the application state is only bumped when the load actually changes,
+    /// so that the new values are propagated in the next rounds without forcing every
+    /// round to resend an unchanged state.
+    pub fn update_load(&mut self, ip: Ipv4Addr, load: NodeLoad) -> Result<(), GossipError> {
+        self.endpoints_state
+            .get_mut(&ip)
+            .ok_or(GossipError::NoEndpointStateForIp)?
+            .application_state
+            .set_load(load);
+
+        Ok(())
+    }
+
+    /// Returns the last load advertised by the endpoint with the given ip, if any.
+    pub fn get_load(&self, ip: Ipv4Addr) -> Option<NodeLoad> {
+        self.endpoints_state
+            .get(&ip)
+            .and_then(|state| state.application_state.load)
+    }
+
+    /// Publishes the digests of the token ranges stored by the endpoint with the given ip.
+    ///
+    /// Like the load, the version is only bumped when a digest changed.
+    pub fn update_data_digests(
+        &mut self,
+        ip: Ipv4Addr,
+        data_digests: BTreeMap<String, DataDigest>,
+    ) -> Result<(), GossipError> {
+        self.endpoints_state
+            .get_mut(&ip)
+            .ok_or(GossipError::NoEndpointStateForIp)?
+            .application_state
+            .set_data_digests(data_digests);
+
+        Ok(())
+    }
+
+    /// Returns the digests of the token ranges last advertised by the endpoint with the given ip.
+    pub fn get_data_digests(&self, ip: Ipv4Addr) -> Option<&BTreeMap<String, DataDigest>> {
+        self.endpoints_state
+            .get(&ip)
+            .map(|state| &state.application_state.data_digests)
+    }
+
+    /// Publishes the datacenter of the endpoint with the given ip.
+    pub fn set_datacenter(&mut self, ip: Ipv4Addr, datacenter: &str) -> Result<(), GossipError> {
+        self.endpoints_state
+            .get_mut(&ip)
+            .ok_or(GossipError::NoEndpointStateForIp)?
+            .application_state
+            .set_datacenter(datacenter);
+
+        Ok(())
+    }
+
+    /// Returns the datacenter advertised by the endpoint with the given ip, if any.
+    pub fn get_datacenter(&self, ip: Ipv4Addr) -> Option<String> {
+        self.endpoints_state
+            .get(&ip)
+            .and_then(|state| state.application_state.datacenter.clone())
+    }
+
     /// Returns a copy of the application state of the endpoint with the given ip.
     pub fn get_status(&self, ip: Ipv4Addr) -> Result<NodeStatus, GossipError> {
         let app_state = self
@@ -134,6 +302,26 @@ impl Gossiper {
         most_updated_schema.cloned()
     }
 
+    /// Returns the schema of the endpoint with the given ip merged with the schemas of
+    /// every other known endpoint, as done by `Schema::merge`.
+    pub fn get_merged_schema(&self, ip: Ipv4Addr) -> Result<Schema, GossipError> {
+        let mut schema = self
+            .endpoints_state
+            .get(&ip)
+            .ok_or(GossipError::NoEndpointStateForIp)?
+            .application_state
+            .schema
+            .clone();
+
+        for (endpoint_ip, state) in &self.endpoints_state {
+            if *endpoint_ip != ip {
+                schema.merge(&state.application_state.schema);
+            }
+        }
+
+        Ok(schema)
+    }
+
     /// Removes the keyspace from the application state of the endpoint with the given ip.
     pub fn remove_keyspace(&mut self, ip: Ipv4Addr, keyspace: &str) -> Result<(), GossipError> {
         // Find the app state of the given ip
@@ -143,11 +331,13 @@ impl Gossiper {
             .ok_or(GossipError::NoEndpointStateForIp)?
             .application_state;
 
-        // TODO: make it an app state or schema method which also alters the timestamp
         app_state.schema.keyspaces.remove(keyspace);
 
         app_state.version += 1;
-        app_state.schema.timestamp = Utc::now().timestamp_millis();
+        let timestamp = app_state
+            .schema
+            .next_timestamp(Utc::now().timestamp_millis());
+        app_state.schema.touch_keyspace(keyspace, timestamp);
 
         Ok(())
     }
@@ -165,15 +355,17 @@ impl Gossiper {
             .ok_or(GossipError::NoEndpointStateForIp)?
             .application_state;
 
+        let keyspace_name = keyspace.get_name();
+
         // Add the keyspace to the schema
         if !app_state
             .schema
             .keyspaces
             .keys()
-            .any(|k| *k == keyspace.get_name())
+            .any(|k| *k == keyspace_name)
         {
             app_state.schema.keyspaces.insert(
-                keyspace.get_name(),
+                keyspace_name.clone(),
                 KeyspaceSchema {
                     inner: keyspace,
                     tables: Vec::new(),
@@ -184,7 +376,10 @@ impl Gossiper {
         }
 
         app_state.version += 1;
-        app_state.schema.timestamp = Utc::now().timestamp_millis();
+        let timestamp = app_state
+            .schema
+            .next_timestamp(Utc::now().timestamp_millis());
+        app_state.schema.touch_keyspace(&keyspace_name, timestamp);
 
         Ok(())
     }
@@ -224,15 +419,61 @@ impl Gossiper {
                 }
             }
 
+            let table_name = table.get_name();
             let table_schema = TableSchema::new(table);
 
             keyspace.tables.push(table_schema);
+
+            let timestamp = app_state
+                .schema
+                .next_timestamp(Utc::now().timestamp_millis());
+            app_state
+                .schema
+                .touch_table(kesyapce_name, &table_name, timestamp);
         } else {
             return Err(GossipError::NoSuchKeyspace);
         }
 
         app_state.version += 1;
-        app_state.schema.timestamp = Utc::now().timestamp_millis();
+
+        Ok(())
+    }
+
+    /// Replaces the definition of a table in the keyspace of the application state of the endpoint with the given ip.
+    ///
+    /// The table is modified in place, so changes made to other tables of the keyspace are kept.
+    pub fn update_table(
+        &mut self,
+        ip: Ipv4Addr,
+        table: CreateTable,
+        keyspace_name: &str,
+    ) -> Result<(), GossipError> {
+        // Find the app state of the given ip
+        let app_state = &mut self
+            .endpoints_state
+            .get_mut(&ip)
+            .ok_or(GossipError::NoEndpointStateForIp)?
+            .application_state;
+
+        let keyspace = app_state
+            .schema
+            .keyspaces
+            .get_mut(keyspace_name)
+            .ok_or(GossipError::NoSuchKeyspace)?;
+
+        let table_name = table.get_name();
+        keyspace
+            .get_table_mut(&table_name)
+            .map_err(|_| GossipError::NoSuchTable)?
+            .inner = table;
+
+        app_state.version += 1;
+        let timestamp = app_state
+            .schema
+            .next_timestamp(Utc::now().timestamp_millis());
+        app_state
+            .schema
+            .touch_table(keyspace_name, &table_name, timestamp);
 
         Ok(())
     }
@@ -262,7 +503,10 @@ impl Gossiper {
         if let Some((_, k_schema)) = k {
             k_schema.tables.retain(|t| t.inner.get_name() != table);
             app_state.version += 1;
-            app_state.schema.timestamp = Utc::now().timestamp_millis();
+            let timestamp = app_state
+                .schema
+                .next_timestamp(Utc::now().timestamp_millis());
+            app_state.schema.touch_table(keyspace, table, timestamp);
 
             Ok(())
         } else {
@@ -275,26 +519,66 @@ impl Gossiper {
         self.change_status(ip, NodeStatus::Dead)
     }
 
-    /// Picks 3 random ips from the gossiper state, excluding the given ip.
+    /// Picks up to `fanout` random ips from the gossiper state.
+    ///
+    /// The result never includes `exclude` nor the nodes marked as dead, so it has fewer ips
+    /// when fewer nodes are known, and it is empty for a node that knows no other live node.
     pub fn pick_ips(&self, exclude: Ipv4Addr) -> Vec<&Ipv4Addr> {
         let mut rng = thread_rng();
         let ips: Vec<&Ipv4Addr> = self
             .endpoints_state
             .iter()
-            .filter(|(&ip, state)| {
-                ip != exclude && state.application_state.status != NodeStatus::Dead
-            })
+            .filter(|(&ip, state)| ip != exclude && !state.application_state.status.is_dead())
             .map(|(ip, _)| ip)
-            .choose_multiple(&mut rng, 3);
+            .choose_multiple(&mut rng, self.fanout);
         ips
     }
 
+    /// Starts a gossip round of the endpoint with the given ip.
+    ///
+    /// The heartbeat of the endpoint is incremented first, so its state keeps advancing even
+    /// when there is no one to gossip with. Returns the ips chosen by `pick_ips` and the `SYN`
+    /// message to send to each of them.
+    pub fn start_round(
+        &mut self,
+        ip: Ipv4Addr,
+    ) -> Result<(Vec<Ipv4Addr>, GossipMessage), GossipError> {
+        self.heartbeat(ip)?;
+
+        let ips = self.pick_ips(ip).into_iter().copied().collect();
+        Ok((ips, self.create_syn(ip)))
+    }
+
     /// Creates a Syn message with the digests of the endpoints in the gossiper state.
-    pub fn create_syn(&self, from: Ipv4Addr) -> GossipMessage {
-        let digests: Vec<Digest> = self
+    ///
+    /// A `SYN` carries at most `MAX_DIGESTS_PER_SYN` digests: the one of `from` and a page of
+    /// the other endpoints. Each call continues, sorted by ip, where the previous page ended
+    /// and wraps around, so every endpoint is gossiped about every few rounds.
+    pub fn create_syn(&mut self, from: Ipv4Addr) -> GossipMessage {
+        let own_digest = self
             .endpoints_state
-            .iter()
-            .map(|(k, v)| Digest::from_heartbeat_state(*k, &v.heartbeat_state))
+            .get(&from)
+            .map(|state| Digest::from_heartbeat_state(from, &state.heartbeat_state));
+        let mut others: Vec<Ipv4Addr> = self
+            .endpoints_state
+            .keys()
+            .copied()
+            .filter(|ip| *ip != from)
+            .collect();
+        others.sort();
+
+        let page_size = others
+            .len()
+            .min(MAX_DIGESTS_PER_SYN - usize::from(own_digest.is_some()));
+        let start = self.syn_cursor.checked_rem(others.len()).unwrap_or(0);
+        self.syn_cursor = start + page_size;
+
+        let page = others.iter().cycle().skip(start).take(page_size);
+        let digests: Vec<Digest> = own_digest
+            .into_iter()
+            .chain(page.map(|ip| {
+                Digest::from_heartbeat_state(*ip, &self.endpoints_state[ip].heartbeat_state)
+            }))
             .collect();
 
         let syn = Syn::new(digests);
@@ -334,7 +618,7 @@ impl Gossiper {
                     }
                     std::cmp::Ordering::Equal => continue,
                 }
-            } else {
+            } else if !self.is_replaced(digest.address) {
                 // si no tengo info de ese nodo, entonces mi digest está desactualizado
                 // le mando el digest correspondiente a ese nodo con version y generacion en 0
                 stale_digests.push(Digest::from_heartbeat_state(
@@ -351,14 +635,19 @@ impl Gossiper {
     }
 
     /// Handles an Ack message and returns the corresponding Ack2 message.
+    ///
+    /// The `ACK` comes from a peer, so it is not trusted to match the `SYN` it answers: the
+    /// digests of endpoints this gossiper does not know, and the stale digests newer than the
+    /// local state, are skipped. The `ACK2` carries the info of the rest.
     pub fn handle_ack(&mut self, ack: &Ack) -> Ack2 {
         let mut updated_info = BTreeMap::new();
 
         for digest in &ack.stale_digests {
-            let my_state = self
-                .endpoints_state
-                .get(&digest.address)
-                .expect("There MUST be an endpoint state for an IP received in an ACK.");
+            // El nodo se pudo olvidar entre el SYN y el ACK, por haber sido reemplazado, o el
+            // par pide uno que nunca se le mando
+            let Some(my_state) = self.endpoints_state.get(&digest.address) else {
+                continue;
+            };
 
             let my_digest = Digest::from_heartbeat_state(digest.address, &my_state.heartbeat_state);
 
@@ -374,26 +663,24 @@ impl Gossiper {
                     // Si el de él está desactualizado, le mando la info para que lo actualice
                     updated_info.insert(my_digest, my_state.application_state.clone());
                 }
-                std::cmp::Ordering::Greater => {
-                    // Si el mío está desactualizado, hubo un problema, se debería haber mandado
-                    // el digest en el Syn
-                    panic!("Something went wrong, a digest incoming in an ACK should never be greater than the local state");
-                }
-                std::cmp::Ordering::Equal => continue,
+                // Si el mío está desactualizado no tengo nada para mandarle: el par debería
+                // haber mandado su info en el ACK, se actualiza en otra ronda
+                std::cmp::Ordering::Greater | std::cmp::Ordering::Equal => continue,
             }
         }
 
         for (digest, info) in &ack.updated_info {
-            let _my_state = self
-                .endpoints_state
-                .get(&digest.address)
-                .expect("There MUST be an endpoint state for an IP received in an ACK.");
+            let Some(my_state) = self.endpoints_state.get(&digest.address) else {
+                continue;
+            };
 
-            // El ACK debe contener info más actualizada que la mía
-            //assert!(digest.get_heartbeat_state() > my_state.heartbeat_state);
+            // El ACK debería traer info más actualizada que la mía, pero puede llegar
+            // desordenado: en ese caso me quedo con lo que tengo
+            if digest.get_heartbeat_state() <= my_state.heartbeat_state {
+                continue;
+            }
 
-            // la actualizo
-            self.endpoints_state.insert(
+            self.update_endpoint_state(
                 digest.address,
                 EndpointState::new(
                     info.clone(),
@@ -408,26 +695,20 @@ impl Gossiper {
     /// Handles an Ack2 message and updates the local state.
     pub fn handle_ack2(&mut self, ack2: &Ack2) {
         for (digest, info) in &ack2.updated_info {
-            if let Some(_my_state) = self.endpoints_state.get(&digest.address) {
-                // El ACK2 debe contener info más actualizada que la mía
-                //assert!(digest.get_heartbeat_state() > my_state.heartbeat_state);
-
-                self.endpoints_state.insert(
-                    digest.address,
-                    EndpointState::new(
-                        info.clone(),
-                        HeartbeatState::new(digest.generation, digest.version),
-                    ),
-                );
-            } else {
-                self.endpoints_state.insert(
-                    digest.address,
-                    EndpointState::new(
-                        info.clone(),
-                        HeartbeatState::new(digest.generation, digest.version),
-                    ),
-                );
+            // Un ACK2 atrasado no debe pisar un estado más nuevo
+            if let Some(my_state) = self.endpoints_state.get(&digest.address) {
+                if digest.get_heartbeat_state() <= my_state.heartbeat_state {
+                    continue;
+                }
             }
+
+            self.update_endpoint_state(
+                digest.address,
+                EndpointState::new(
+                    info.clone(),
+                    HeartbeatState::new(digest.generation, digest.version),
+                ),
+            );
         }
     }
 }
@@ -436,6 +717,7 @@ impl Gossiper {
 mod tests {
     use super::*;
     use messages::Payload;
+    use std::collections::HashSet;
     use std::str::FromStr;
     use structures::application_state::ApplicationState;
 
@@ -457,6 +739,7 @@ mod tests {
 
         let gossiper = Gossiper {
             endpoints_state: local_state.clone(),
+            ..Gossiper::new()
         };
 
         let ack = gossiper.handle_syn(&syn);
@@ -497,6 +780,7 @@ mod tests {
 
         let gossiper = Gossiper {
             endpoints_state: local_state.clone(),
+            ..Gossiper::new()
         };
 
         let ack = gossiper.handle_syn(&syn);
@@ -537,6 +821,7 @@ mod tests {
 
         let gossiper = Gossiper {
             endpoints_state: local_state.clone(),
+            ..Gossiper::new()
         };
 
         let ack = gossiper.handle_syn(&syn);
@@ -569,6 +854,7 @@ mod tests {
 
         let gossiper = Gossiper {
             endpoints_state: local_state.clone(),
+            ..Gossiper::new()
         };
 
         let ack = gossiper.handle_syn(&syn);
@@ -601,6 +887,7 @@ mod tests {
 
         let mut gossiper = Gossiper {
             endpoints_state: local_state.clone(),
+            ..Gossiper::new()
         };
 
         let ack2 = gossiper.handle_ack(&ack);
@@ -638,6 +925,7 @@ mod tests {
 
         let mut gossiper = Gossiper {
             endpoints_state: local_state.clone(),
+            ..Gossiper::new()
         };
 
         let ack2 = gossiper.handle_ack(&ack);
@@ -675,6 +963,7 @@ mod tests {
 
         let mut gossiper = Gossiper {
             endpoints_state: local_state.clone(),
+            ..Gossiper::new()
         };
 
         let ack2 = gossiper.handle_ack(&ack);
@@ -720,6 +1009,7 @@ mod tests {
 
         let mut gossiper = Gossiper {
             endpoints_state: local_state.clone(),
+            ..Gossiper::new()
         };
 
         let ack2 = gossiper.handle_ack(&ack);
@@ -759,6 +1049,7 @@ mod tests {
 
         let mut gossiper = Gossiper {
             endpoints_state: local_state.clone(),
+            ..Gossiper::new()
         };
 
         let ack2 = gossiper.handle_ack(&ack);
@@ -807,6 +1098,7 @@ mod tests {
 
         let mut gossiper = Gossiper {
             endpoints_state: local_state.clone(),
+            ..Gossiper::new()
         };
 
         let ack2 = gossiper.handle_ack(&ack);
@@ -842,6 +1134,67 @@ mod tests {
         );
     }
 
+    #[test]
+    fn incoming_ack_with_unknown_ips_is_skipped() {
+        let known_ip = Ipv4Addr::from_str("127.0.0.2").unwrap();
+        let unknown_ip = Ipv4Addr::from_str("127.0.0.9").unwrap();
+
+        // the peer asks for, and sends info about, an ip that was never in the syn
+        let ack = Ack::new(
+            vec![Digest::new(unknown_ip, 6, 1), Digest::new(known_ip, 6, 1)],
+            BTreeMap::from([(
+                Digest::new(unknown_ip, 8, 7),
+                ApplicationState::new(NodeStatus::Normal, 9, Schema::default()),
+            )]),
+        );
+        let mut gossiper = Gossiper::new().with_endpoint_state(known_ip);
+        gossiper
+            .endpoints_state
+            .get_mut(&known_ip)
+            .unwrap()
+            .heartbeat_state = HeartbeatState::new(7, 2);
+
+        let ack2 = gossiper.handle_ack(&ack);
+
+        // the known ip is still answered
+        assert_eq!(
+            ack2.updated_info.keys().copied().collect::<Vec<Digest>>(),
+            vec![Digest::new(known_ip, 7, 2)]
+        );
+        assert!(!gossiper.endpoints_state.contains_key(&unknown_ip));
+    }
+
+    #[test]
+    fn incoming_ack_stale_digest_greater_than_local_state_is_skipped() {
+        let ip_1 = Ipv4Addr::from_str("127.0.0.2").unwrap();
+        let ip_2 = Ipv4Addr::from_str("127.0.0.7").unwrap();
+
+        // the peer claims a newer state of ip_1 than the local one, but asks for it anyway
+        let ack = Ack::new(
+            vec![Digest::new(ip_1, 9, 1), Digest::new(ip_2, 6, 1)],
+            BTreeMap::new(),
+        );
+        let mut gossiper = Gossiper::new()
+            .with_endpoint_state(ip_1)
+            .with_endpoint_state(ip_2);
+        for ip in [ip_1, ip_2] {
+            gossiper
+                .endpoints_state
+                .get_mut(&ip)
+                .unwrap()
+                .heartbeat_state = HeartbeatState::new(7, 2);
+        }
+        let local_state = gossiper.endpoints_state.clone();
+
+        let ack2 = gossiper.handle_ack(&ack);
+
+        assert_eq!(
+            ack2.updated_info.keys().copied().collect::<Vec<Digest>>(),
+            vec![Digest::new(ip_2, 7, 2)]
+        );
+        assert_eq!(gossiper.endpoints_state, local_state);
+    }
+
     #[test]
     fn incoming_ack2_updated_info() {
         let ip_1 = Ipv4Addr::from_str("127.0.0.2").unwrap();
@@ -877,6 +1230,7 @@ mod tests {
 
         let mut gossiper = Gossiper {
             endpoints_state: local_state.clone(),
+            ..Gossiper::new()
         };
 
         gossiper.handle_ack2(&ack2);
@@ -918,6 +1272,7 @@ mod tests {
 
         let gossiper = Gossiper {
             endpoints_state: local_state.clone(),
+            ..Gossiper::new()
         };
 
         let ack = gossiper.handle_syn(&syn);
@@ -939,9 +1294,10 @@ mod tests {
 
         let mut gossiper = Gossiper {
             endpoints_state: local_state.clone(),
+            ..Gossiper::new()
         };
 
-        let _ = gossiper.handle_ack2(&ack);
+        gossiper.handle_ack2(&ack);
 
         assert_eq!(
             gossiper
@@ -961,6 +1317,210 @@ mod tests {
         );
     }
 
+    #[test]
+    fn outdated_ack2_does_not_regress_local_state() {
+        let ip = Ipv4Addr::from_str("127.0.0.2").unwrap();
+
+        // Llega un ACK2 atrasado, con una versión menor a la local
+        let ack2 = Ack2::new(BTreeMap::from([(
+            Digest::new(ip, 3, 1),
+            ApplicationState::new(NodeStatus::Bootstrap, 1, Schema::default()),
+        )]));
+
+        let local_state: HashMap<Ipv4Addr, EndpointState> = HashMap::from([(
+            ip,
+            EndpointState::new(
+                ApplicationState::new(NodeStatus::Normal, 6, Schema::default()),
+                HeartbeatState::new(3, 4),
+            ),
+        )]);
+
+        let mut gossiper = Gossiper {
+            endpoints_state: local_state.clone(),
+            ..Gossiper::new()
+        };
+
+        gossiper.handle_ack2(&ack2);
+
+        assert_eq!(gossiper.endpoints_state, local_state);
+    }
+
+    #[test]
+    fn generation_jump_is_reported_once_as_a_restart() {
+        let ip = Ipv4Addr::from_str("127.0.0.2").unwrap();
+        let seed = Ipv4Addr::from_str("127.0.0.3").unwrap();
+        let state = |generation, version| {
+            (
+                Digest::new(ip, generation, version),
+                ApplicationState::new(NodeStatus::Normal, 1, Schema::default()),
+            )
+        };
+
+        let mut gossiper = Gossiper::new().with_seeds(vec![seed]);
+        gossiper.endpoints_state.insert(
+            ip,
+            EndpointState::new(
+                ApplicationState::new(NodeStatus::Normal, 1, Schema::default()),
+                HeartbeatState::new(3, 40),
+            ),
+        );
+
+        // Otra version de la misma generacion no es un reinicio
+        gossiper.handle_ack2(&Ack2::new(BTreeMap::from([state(3, 41)])));
+        assert!(gossiper.take_events().is_empty());
+
+        gossiper.handle_ack2(&Ack2::new(BTreeMap::from([state(4, 1)])));
+        gossiper.handle_ack2(&Ack2::new(BTreeMap::from([state(4, 1)])));
+        gossiper.handle_ack(&Ack::new(vec![], BTreeMap::from([state(4, 2)])));
+        assert_eq!(gossiper.take_events(), [GossipEvent::Restarted(ip)]);
+        assert!(gossiper.take_events().is_empty());
+
+        // La primera generacion de un seed del que no se sabia nada no es un reinicio
+        gossiper.handle_ack2(&Ack2::new(BTreeMap::from([(
+            Digest::new(seed, 9, 1),
+            ApplicationState::new(NodeStatus::Normal, 1, Schema::default()),
+        )])));
+        assert!(gossiper.take_events().is_empty());
+    }
+
+    #[test]
+    fn a_replaced_endpoint_is_forgotten_and_not_learned_again() {
+        let ip = Ipv4Addr::from_str("127.0.0.1").unwrap();
+        let dead = Ipv4Addr::from_str("127.0.0.2").unwrap();
+        let replacement = Ipv4Addr::from_str("127.0.0.3").unwrap();
+        let mut gossiper = Gossiper::new()
+            .with_endpoint_state(ip)
+            .with_endpoint_state(dead);
+        gossiper.kill(dead).unwrap();
+
+        let mut replacement_state = ApplicationState::new(NodeStatus::Bootstrap, 1, Schema::new());
+        replacement_state.set_replaces(dead);
+        for version in [1, 2] {
+            gossiper.handle_ack2(&Ack2::new(BTreeMap::from([(
+                Digest::new(replacement, 1, version),
+                replacement_state.clone(),
+            )])));
+        }
+        assert!(!gossiper.endpoints_state.contains_key(&dead));
+        assert!(gossiper.is_replaced(dead));
+        assert_eq!(
+            gossiper.take_events(),
+            [GossipEvent::Replaced { dead, replacement }]
+        );
+
+        // Un nodo que todavia no se entero sigue mandando el estado del reemplazado
+        let ack = gossiper.handle_syn(&Syn::new(vec![Digest::new(dead, 1, 50)]));
+        assert!(ack.stale_digests.is_empty());
+        gossiper.handle_ack2(&Ack2::new(BTreeMap::from([(
+            Digest::new(dead, 1, 50),
+            ApplicationState::new(NodeStatus::Dead, 1, Schema::new()),
+        )])));
+        assert!(!gossiper.endpoints_state.contains_key(&dead));
+        assert!(gossiper.take_events().is_empty());
+    }
+
+    #[test]
+    fn outdated_ack_does_not_regress_local_state() {
+        let ip = Ipv4Addr::from_str("127.0.0.2").unwrap();
+
+        let ack = Ack::new(
+            vec![],
+            BTreeMap::from([(
+                Digest::new(ip, 3, 4),
+                ApplicationState::new(NodeStatus::Bootstrap, 1, Schema::default()),
+            )]),
+        );
+
+        let local_state: HashMap<Ipv4Addr, EndpointState> = HashMap::from([(
+            ip,
+            EndpointState::new(
+                ApplicationState::new(NodeStatus::Normal, 6, Schema::default()),
+                HeartbeatState::new(3, 4),
+            ),
+        )]);
+
+        let mut gossiper = Gossiper {
+            endpoints_state: local_state.clone(),
+            ..Gossiper::new()
+        };
+
+        let ack2 = gossiper.handle_ack(&ack);
+
+        assert!(ack2.updated_info.is_empty());
+        assert_eq!(gossiper.endpoints_state, local_state);
+    }
+
+    #[test]
+    fn round_without_peers_still_increments_the_heartbeat() {
+        let ip = Ipv4Addr::from_str("127.0.0.1").unwrap();
+        let mut gossiper = Gossiper::new().with_endpoint_state(ip);
+
+        let (ips, _) = gossiper.start_round(ip).unwrap();
+        assert!(ips.is_empty());
+        let (ips, _) = gossiper.start_round(ip).unwrap();
+        assert!(ips.is_empty());
+
+        assert_eq!(
+            gossiper.endpoints_state.get(&ip).unwrap().heartbeat_state,
+            HeartbeatState::new(0, 2)
+        );
+    }
+
+    #[test]
+    fn syns_are_capped_and_cover_every_endpoint_over_the_rounds() {
+        let ip = Ipv4Addr::new(10, 0, 0, 1);
+        let peers: Vec<Ipv4Addr> = (0..150).map(|i| Ipv4Addr::new(10, 0, 1, i)).collect();
+        let mut gossiper = Gossiper::new()
+            .with_endpoint_state(ip)
+            .with_seeds(peers.clone());
+
+        let mut gossiped = HashSet::new();
+        for _ in 0..3 {
+            let digests = match gossiper.create_syn(ip).payload {
+                messages::Payload::Syn(syn) => syn.digests,
+                _ => panic!("expected a SYN"),
+            };
+            assert_eq!(digests.len(), MAX_DIGESTS_PER_SYN);
+            // Cada SYN lleva el digest del que lo manda
+            assert_eq!(digests[0].address, ip);
+            gossiped.extend(digests.iter().map(|digest| digest.address));
+        }
+
+        // 3 SYNs de 63 pares cubren a los 150
+        assert_eq!(gossiped.len(), peers.len() + 1);
+        assert!(peers.iter().all(|peer| gossiped.contains(peer)));
+    }
+
+    #[test]
+    fn round_with_one_peer_only_picks_that_peer() {
+        let ip = Ipv4Addr::from_str("127.0.0.1").unwrap();
+        let peer = Ipv4Addr::from_str("127.0.0.2").unwrap();
+        let dead = Ipv4Addr::from_str("127.0.0.3").unwrap();
+        let mut gossiper = Gossiper::new()
+            .with_endpoint_state(ip)
+            .with_seeds(vec![peer, dead]);
+        gossiper.kill(dead).unwrap();
+
+        let (ips, syn) = gossiper.start_round(ip).unwrap();
+        assert_eq!(ips, vec![peer]);
+        assert_eq!(syn.from, ip);
+        assert_eq!(
+            gossiper.endpoints_state.get(&ip).unwrap().heartbeat_state,
+            HeartbeatState::new(0, 1)
+        );
+    }
+
+    #[test]
+    fn pick_ips_never_returns_more_than_the_fanout() {
+        let ip = Ipv4Addr::from_str("127.0.0.1").unwrap();
+        let peers: Vec<Ipv4Addr> = (2..10).map(|i| Ipv4Addr::new(127, 0, 0, i)).collect();
+        let gossiper = Gossiper::new().with_endpoint_state(ip).with_seeds(peers);
+
+        let ips = gossiper.pick_ips(ip);
+        assert_eq!(ips.len(), GOSSIP_FANOUT);
+        assert!(!ips.contains(&&ip));
+    }
+
     #[test]
     fn test_gossip_flow() {
         let client_ip = Ipv4Addr::from_str("127.0.0.1").unwrap();
@@ -1033,6 +1593,7 @@ mod tests {
 
         let mut gossiper_server = Gossiper {
             endpoints_state: server_state.clone(),
+            ..Gossiper::new()
         };
 
         // server handles syn and sends ack to client
@@ -1057,6 +1618,7 @@ mod tests {
 
         let mut gossiper_client = Gossiper {
             endpoints_state: client_state.clone(),
+            ..Gossiper::new()
         };
 
         // client handles ack, updates its state and sends ack2 to server
@@ -1119,6 +1681,7 @@ mod tests {
                     HeartbeatState::default(),
                 ),
             )]),
+            ..Gossiper::new()
         };
 
         gossiper.change_status(ip, NodeStatus::Normal).unwrap();
@@ -1143,12 +1706,48 @@ mod tests {
         );
     }
 
+    #[test]
+    fn update_load() {
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+
+        let mut gossiper = Gossiper {
+            endpoints_state: HashMap::from([(
+                ip,
+                EndpointState::new(
+                    ApplicationState::new(NodeStatus::Normal, 2, Schema::default()),
+                    HeartbeatState::default(),
+                ),
+            )]),
+            ..Gossiper::new()
+        };
+
+        assert_eq!(gossiper.get_load(ip), None);
+
+        let load = NodeLoad {
+            pending_queries: 4,
+            queue_depth: 1,
+        };
+        gossiper.update_load(ip, load).unwrap();
+
+        assert_eq!(gossiper.get_load(ip), Some(load));
+        assert_eq!(
+            gossiper
+                .endpoints_state
+                .get(&ip)
+                .unwrap()
+                .application_state
+                .version,
+            3
+        );
+    }
+
     #[test]
     fn change_status_non_existent() {
         let ip = Ipv4Addr::new(127, 0, 0, 1);
 
         let mut gossiper = Gossiper {
             endpoints_state: HashMap::new(),
+            ..Gossiper::new()
         };
 
         let result = gossiper.change_status(ip, NodeStatus::Normal);
@@ -1168,6 +1767,7 @@ mod tests {
                     HeartbeatState::new(7, 2),
                 ),
             )]),
+            ..Gossiper::new()
         };
 
         gossiper.remove_keyspace(ip, "keyspace").unwrap();
@@ -1198,6 +1798,7 @@ mod tests {
 
         let mut gossiper = Gossiper {
             endpoints_state: HashMap::new(),
+            ..Gossiper::new()
         };
 
         let result = gossiper.remove_keyspace(ip, "keyspace");
@@ -1217,6 +1818,7 @@ mod tests {
                     HeartbeatState::new(7, 2),
                 ),
             )]),
+            ..Gossiper::new()
         };
 
         gossiper
@@ -1259,12 +1861,50 @@ mod tests {
         );
     }
 
+    #[test]
+    fn schema_changes_keep_increasing_the_timestamp_after_the_clock_goes_back() {
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+
+        // El esquema viene de un momento que para el reloj local es una hora en el futuro
+        let mut schema = Schema::default();
+        let future = Utc::now().timestamp_millis() + 3_600_000;
+        schema.touch_keyspace("flights", future);
+
+        let mut gossiper = Gossiper {
+            endpoints_state: HashMap::from([(
+                ip,
+                EndpointState::new(
+                    ApplicationState::new(NodeStatus::Normal, 2, schema),
+                    HeartbeatState::new(7, 2),
+                ),
+            )]),
+            ..Gossiper::new()
+        };
+
+        gossiper
+            .add_keyspace(
+                ip,
+                CreateKeyspace {
+                    name: "airports".to_string(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        gossiper.remove_keyspace(ip, "flights").unwrap();
+
+        let schema = &gossiper.endpoints_state[&ip].application_state.schema;
+        assert_eq!(schema.versions["airports"], future + 1);
+        assert_eq!(schema.versions["flights"], future + 2);
+        assert_eq!(schema.timestamp, future + 2);
+    }
+
     #[test]
     fn add_keyspace_non_existent_ip() {
         let ip = Ipv4Addr::new(127, 0, 0, 1);
 
         let mut gossiper = Gossiper {
             endpoints_state: HashMap::new(),
+            ..Gossiper::new()
         };
 
         let result = gossiper.add_keyspace(ip, CreateKeyspace::default());
@@ -1306,6 +1946,7 @@ mod tests {
                     HeartbeatState::new(7, 2),
                 ),
             )]),
+            ..Gossiper::new()
         };
 
         gossiper.remove_table(ip, "keyspace", "table1").unwrap();
@@ -1339,6 +1980,7 @@ mod tests {
 
         let mut gossiper = Gossiper {
             endpoints_state: HashMap::new(),
+            ..Gossiper::new()
         };
 
         let result = gossiper.remove_table(ip, "keyspace", "table1");
@@ -1358,6 +2000,7 @@ mod tests {
                     HeartbeatState::new(7, 2),
                 ),
             )]),
+            ..Gossiper::new()
         };
 
         let result = gossiper.remove_table(ip, "keyspace", "table1");
@@ -1388,11 +2031,13 @@ mod tests {
                                 },
                             )]),
                             timestamp: 0,
+                            versions: HashMap::new(),
                         },
                     ),
                     HeartbeatState::new(7, 2),
                 ),
             )]),
+            ..Gossiper::new()
         };
 
         gossiper
@@ -1409,6 +2054,14 @@ mod tests {
             )
             .unwrap();
 
+        let timestamp = gossiper
+            .endpoints_state
+            .get(&ip)
+            .unwrap()
+            .application_state
+            .schema
+            .timestamp;
+
         assert_eq!(
             gossiper
                 .endpoints_state
@@ -1436,13 +2089,8 @@ mod tests {
                     }
                 )]),
                 // copy timestamp from insertion
-                timestamp: gossiper
-                    .endpoints_state
-                    .get(&ip)
-                    .unwrap()
-                    .application_state
-                    .schema
-                    .timestamp
+                timestamp,
+                versions: HashMap::from([("keyspace.table".to_string(), timestamp)]),
             }
         );
 
@@ -1463,6 +2111,7 @@ mod tests {
 
         let mut gossiper = Gossiper {
             endpoints_state: HashMap::new(),
+            ..Gossiper::new()
         };
 
         let result = gossiper.add_table(
@@ -1492,6 +2141,7 @@ mod tests {
                     HeartbeatState::new(7, 2),
                 ),
             )]),
+            ..Gossiper::new()
         };
 
         let result = gossiper.add_table(
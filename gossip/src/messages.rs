@@ -32,12 +32,9 @@ pub struct Digest {
 }
 
 impl Ord for Digest {
+    /// Digests are ordered like their `HeartbeatState`: by generation first, then by version.
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        if self.generation == other.generation {
-            self.version.cmp(&other.version)
-        } else {
-            self.generation.cmp(&other.generation)
-        }
+        self.get_heartbeat_state().cmp(&other.get_heartbeat_state())
     }
 }
 
@@ -684,8 +681,13 @@ mod tests {
             version: 0xffffffff,
             schema: Schema {
                 timestamp: 0,
+                versions: HashMap::new(),
                 keyspaces: HashMap::new(),
             },
+            load: None,
+            datacenter: None,
+            replaces: None,
+            data_digests: BTreeMap::new(),
         };
 
         let mut updated_info = BTreeMap::new();
@@ -733,6 +735,7 @@ mod tests {
             version: 0x1,
             schema: Schema {
                 timestamp: 10,
+                versions: HashMap::new(),
                 keyspaces: HashMap::from([(
                     "keyspace".to_string(),
                     KeyspaceSchema::new(
@@ -760,6 +763,10 @@ mod tests {
                     ),
                 )]),
             },
+            load: None,
+            datacenter: None,
+            replaces: None,
+            data_digests: BTreeMap::new(),
         };
 
         let node2 = Digest {
@@ -773,6 +780,7 @@ mod tests {
             version: 0x1,
             schema: Schema {
                 timestamp: 10,
+                versions: HashMap::new(),
                 keyspaces: HashMap::from([(
                     "keyspace".to_string(),
                     KeyspaceSchema::new(
@@ -800,6 +808,10 @@ mod tests {
                     ),
                 )]),
             },
+            load: None,
+            datacenter: None,
+            replaces: None,
+            data_digests: BTreeMap::new(),
         };
 
         let mut updated_info = BTreeMap::new();
@@ -885,6 +897,10 @@ mod tests {
             status: NodeStatus::Normal,
             version: 0x1,
             schema: Schema::default(),
+            load: None,
+            datacenter: None,
+            replaces: None,
+            data_digests: BTreeMap::new(),
         };
 
         let mut updated_info = BTreeMap::new();
@@ -914,6 +930,10 @@ mod tests {
             status: NodeStatus::Normal,
             version: 1,
             schema: Schema::default(),
+            load: None,
+            datacenter: None,
+            replaces: None,
+            data_digests: BTreeMap::new(),
         };
 
         let node2 = Digest {
@@ -926,6 +946,10 @@ mod tests {
             status: NodeStatus::Normal,
             version: 2,
             schema: Schema::default(),
+            load: None,
+            datacenter: None,
+            replaces: None,
+            data_digests: BTreeMap::new(),
         };
 
         let mut updated_info = BTreeMap::new();
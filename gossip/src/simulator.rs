@@ -0,0 +1,244 @@
+//! In-memory cluster of gossipers, to test how the state of the endpoints spreads across
+//! several nodes without opening sockets.
+//!
+//! Each node has a channel as its inbox, and the messages travel through it as the bytes the
+//! real nodes would send. Time only moves when a round is run, so the generations the nodes
+//! start with, and the amount of rounds something takes to spread, do not depend on the
+//! machine running the tests.
+
+use std::{
+    collections::BTreeMap,
+    net::Ipv4Addr,
+    sync::mpsc::{channel, Receiver, Sender},
+};
+
+use crate::{
+    messages::{GossipMessage, Payload},
+    Gossiper,
+};
+
+/// Milliseconds the clock of the simulation moves forward on each round.
+pub const ROUND_MILLIS: u128 = 1000;
+
+/// A node of the simulation: its gossiper and the inbox where the other nodes leave their
+/// messages.
+struct SimulatedNode {
+    gossiper: Gossiper,
+    sender: Sender<Vec<u8>>,
+    inbox: Receiver<Vec<u8>>,
+}
+
+/// Cluster of gossipers that exchange their messages in memory.
+///
+/// ### Fields
+/// - `nodes`: The nodes of the cluster, by ip.
+/// - `clock`: Milliseconds of the simulated time, used as the generation of the nodes that join.
+/// - `rounds`: Rounds run since the cluster was created.
+pub struct SimulatedCluster {
+    nodes: BTreeMap<Ipv4Addr, SimulatedNode>,
+    clock: u128,
+    rounds: usize,
+}
+
+impl SimulatedCluster {
+    /// Creates a cluster without nodes, with its clock at the first round.
+    pub fn new() -> Self {
+        Self {
+            nodes: BTreeMap::new(),
+            clock: ROUND_MILLIS,
+            rounds: 0,
+        }
+    }
+
+    /// Adds a node that knows only itself and `seeds`, as a node that starts does.
+    ///
+    /// The node starts a new generation taken from the clock, so a node that joins again with
+    /// the same ip is seen by the others as restarted.
+    pub fn join(&mut self, ip: Ipv4Addr, seeds: Vec<Ipv4Addr>) {
+        let seeds = seeds.into_iter().filter(|seed| *seed != ip).collect();
+        let mut gossiper = Gossiper::new().with_endpoint_state(ip).with_seeds(seeds);
+        if let Some(state) = gossiper.endpoints_state.get_mut(&ip) {
+            state.heartbeat_state.generation = self.clock;
+        }
+
+        let (sender, inbox) = channel();
+        self.nodes.insert(
+            ip,
+            SimulatedNode {
+                gossiper,
+                sender,
+                inbox,
+            },
+        );
+    }
+
+    /// Gossiper of the node with the given ip.
+    pub fn gossiper(&self, ip: Ipv4Addr) -> Option<&Gossiper> {
+        self.nodes.get(&ip).map(|node| &node.gossiper)
+    }
+
+    /// Gossiper of the node with the given ip, to change the state the node gossips about.
+    pub fn gossiper_mut(&mut self, ip: Ipv4Addr) -> Option<&mut Gossiper> {
+        self.nodes.get_mut(&ip).map(|node| &mut node.gossiper)
+    }
+
+    /// Rounds run since the cluster was created.
+    pub fn rounds(&self) -> usize {
+        self.rounds
+    }
+
+    /// Whether `condition` holds for the gossiper of every node.
+    pub fn all(&self, condition: impl Fn(&Gossiper) -> bool) -> bool {
+        self.nodes.values().all(|node| condition(&node.gossiper))
+    }
+
+    /// Runs a gossip round: the clock moves forward, every node, in ip order, sends its `SYN`
+    /// to the nodes it picks, and the messages are delivered until no node has anything left
+    /// to answer.
+    pub fn run_round(&mut self) {
+        self.clock += ROUND_MILLIS;
+        self.rounds += 1;
+
+        let ips: Vec<Ipv4Addr> = self.nodes.keys().copied().collect();
+        for ip in &ips {
+            let Some(node) = self.nodes.get_mut(ip) else {
+                continue;
+            };
+            let Ok((peers, syn)) = node.gossiper.start_round(*ip) else {
+                continue;
+            };
+            for peer in peers {
+                self.send(peer, &syn);
+            }
+        }
+
+        self.deliver(&ips);
+    }
+
+    /// Runs rounds until `condition` holds for the gossiper of every node, or until
+    /// `max_rounds` were run.
+    ///
+    /// # Returns
+    /// - `Some(rounds)` with the rounds run until the condition held, 0 if it already did.
+    /// - `None` if it still does not hold after `max_rounds`.
+    pub fn run_until(
+        &mut self,
+        max_rounds: usize,
+        condition: impl Fn(&Gossiper) -> bool,
+    ) -> Option<usize> {
+        for rounds in 0..=max_rounds {
+            if self.all(&condition) {
+                return Some(rounds);
+            }
+            if rounds < max_rounds {
+                self.run_round();
+            }
+        }
+        None
+    }
+
+    // Deja el mensaje en la bandeja del nodo; si no hay un nodo con esa ip se pierde
+    fn send(&self, to: Ipv4Addr, message: &GossipMessage) {
+        if let Some(node) = self.nodes.get(&to) {
+            let _ = node.sender.send(message.as_bytes());
+        }
+    }
+
+    // Reparte los mensajes pendientes hasta que ninguna bandeja tenga mas
+    fn deliver(&mut self, ips: &[Ipv4Addr]) {
+        loop {
+            let mut delivered = false;
+            for ip in ips {
+                let Some(node) = self.nodes.get_mut(ip) else {
+                    continue;
+                };
+                let messages: Vec<Vec<u8>> = node.inbox.try_iter().collect();
+                for bytes in messages {
+                    delivered = true;
+                    let Ok(message) = GossipMessage::from_bytes(&bytes) else {
+                        continue;
+                    };
+                    let Some(node) = self.nodes.get_mut(ip) else {
+                        continue;
+                    };
+                    let reply = match &message.payload {
+                        Payload::Syn(syn) => Some(Payload::Ack(node.gossiper.handle_syn(syn))),
+                        Payload::Ack(ack) => Some(Payload::Ack2(node.gossiper.handle_ack(ack))),
+                        Payload::Ack2(ack2) => {
+                            node.gossiper.handle_ack2(ack2);
+                            None
+                        }
+                    };
+                    if let Some(reply) = reply {
+                        self.send(message.from, &GossipMessage::new(*ip, reply));
+                    }
+                }
+            }
+            if !delivered {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use query_creator::clauses::keyspace::create_keyspace_cql::CreateKeyspace;
+
+    /// Rounds the state of a node has to take to reach every other node of the tests. The
+    /// peers are picked at random, so it leaves room for unlucky picks.
+    const MAX_ROUNDS: usize = 10;
+
+    #[test]
+    fn a_new_node_and_its_schema_reach_the_whole_cluster() {
+        let seed = Ipv4Addr::new(127, 0, 0, 1);
+        let ips: Vec<Ipv4Addr> = (1..=4).map(|i| Ipv4Addr::new(127, 0, 0, i)).collect();
+        let mut cluster = SimulatedCluster::new();
+        for ip in &ips {
+            cluster.join(*ip, vec![seed]);
+        }
+        let knows_all = |ips: Vec<Ipv4Addr>| {
+            move |gossiper: &Gossiper| {
+                ips.iter()
+                    .all(|ip| gossiper.endpoints_state.contains_key(ip))
+            }
+        };
+        assert!(cluster
+            .run_until(MAX_ROUNDS, knows_all(ips.clone()))
+            .is_some());
+
+        let new_ip = Ipv4Addr::new(127, 0, 0, 5);
+        cluster.join(new_ip, vec![seed]);
+        cluster
+            .gossiper_mut(new_ip)
+            .unwrap()
+            .add_keyspace(
+                new_ip,
+                CreateKeyspace {
+                    name: "sky".to_string(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let generation = cluster.gossiper(new_ip).unwrap().endpoints_state[&new_ip]
+            .heartbeat_state
+            .generation;
+        assert!(!cluster.all(knows_all(vec![new_ip])));
+
+        // El nodo nuevo tambien tiene que conocer a los demas
+        let mut all_ips = ips.clone();
+        all_ips.push(new_ip);
+        let knows_the_cluster = knows_all(all_ips);
+        let rounds = cluster.run_until(MAX_ROUNDS, |gossiper| {
+            knows_the_cluster(gossiper)
+                && gossiper.endpoints_state[&new_ip].heartbeat_state.generation == generation
+                && gossiper
+                    .get_most_updated_schema()
+                    .is_some_and(|schema| schema.keyspaces.contains_key("sky"))
+        });
+
+        assert!(rounds.is_some_and(|rounds| rounds > 0));
+        assert!(cluster.rounds() <= 2 * MAX_ROUNDS);
+    }
+}
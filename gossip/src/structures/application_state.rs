@@ -5,9 +5,10 @@ use query_creator::clauses::{
     types::{column::Column, datatype::DataType},
 };
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fmt::{self, Display},
     io::{Cursor, Read},
+    net::Ipv4Addr,
 };
 
 pub trait CursorSerializable {
@@ -395,6 +396,10 @@ pub struct Schema {
     // no puedo usar Keyspace porque sino tengo una
     // dependencia circular entre node y gossip
     pub keyspaces: HashMap<String, KeyspaceSchema>,
+    /// Last time each keyspace (`keyspace`) and table (`keyspace.table`) was created,
+    /// altered or dropped. Dropped objects keep their entry, so a merge can tell a
+    /// drop apart from an object the other node has not seen yet.
+    pub versions: HashMap<String, i64>,
 }
 
 impl Schema {
@@ -402,9 +407,149 @@ impl Schema {
         Schema {
             timestamp: 0,
             keyspaces: HashMap::new(),
+            versions: HashMap::new(),
         }
     }
 
+    fn table_key(keyspace: &str, table: &str) -> String {
+        format!("{}.{}", keyspace, table)
+    }
+
+    /// Timestamp for a new change of this schema, given the clock of the node reads `now`.
+    ///
+    /// It is always greater than the current timestamp, so a change made after the clock went
+    /// back still wins against the previous ones when schemas are merged.
+    pub fn next_timestamp(&self, now: i64) -> i64 {
+        now.max(self.timestamp.saturating_add(1))
+    }
+
+    /// Records that the keyspace was created, altered or dropped at `timestamp`.
+    pub fn touch_keyspace(&mut self, keyspace: &str, timestamp: i64) {
+        self.versions.insert(keyspace.to_string(), timestamp);
+        self.timestamp = timestamp;
+    }
+
+    /// Records that the table of `keyspace` was created, altered or dropped at `timestamp`.
+    pub fn touch_table(&mut self, keyspace: &str, table: &str, timestamp: i64) {
+        self.versions
+            .insert(Self::table_key(keyspace, table), timestamp);
+        self.timestamp = timestamp;
+    }
+
+    /// Version of an object of a schema. Objects created before versions were tracked
+    /// take the timestamp of the whole schema, and objects the schema never knew about
+    /// lose against any other version.
+    fn version_of(versions: &HashMap<String, i64>, timestamp: i64, key: &str, exists: bool) -> i64 {
+        match versions.get(key) {
+            Some(version) => *version,
+            None if exists => timestamp,
+            None => i64::MIN,
+        }
+    }
+
+    /// Merges `other` into this schema, keeping the newest version of each keyspace
+    /// and of each table independently.
+    ///
+    /// Two nodes that concurrently create different keyspaces (or different tables
+    /// of the same keyspace) end up with both of them, instead of the schema with the
+    /// lower timestamp being discarded as a whole. When the same object differs, the
+    /// one modified last wins, whether that modification created, altered or dropped it.
+    /// On a tie the current version is kept.
+    pub fn merge(&mut self, other: &Schema) {
+        let mut keyspace_names: Vec<String> = self
+            .keyspaces
+            .keys()
+            .chain(other.keyspaces.keys())
+            .cloned()
+            .collect();
+        keyspace_names.sort();
+        keyspace_names.dedup();
+
+        for name in keyspace_names {
+            let theirs_keyspace = other.keyspaces.get(&name);
+            let mine = Self::version_of(
+                &self.versions,
+                self.timestamp,
+                &name,
+                self.keyspaces.contains_key(&name),
+            );
+            let theirs = Self::version_of(
+                &other.versions,
+                other.timestamp,
+                &name,
+                theirs_keyspace.is_some(),
+            );
+
+            if theirs > mine {
+                match theirs_keyspace {
+                    Some(keyspace) => {
+                        self.keyspaces
+                            .entry(name.clone())
+                            .or_insert_with(|| KeyspaceSchema::new(keyspace.inner.clone(), vec![]))
+                            .inner = keyspace.inner.clone();
+                    }
+                    None => {
+                        self.keyspaces.remove(&name);
+                    }
+                }
+            }
+
+            // Las tablas se comparan una por una, aunque el keyspace no haya cambiado
+            let Some(current) = self.keyspaces.get_mut(&name) else {
+                continue;
+            };
+            let theirs_tables = theirs_keyspace
+                .map(|keyspace| keyspace.tables.as_slice())
+                .unwrap_or(&[]);
+
+            let mut table_names: Vec<String> = current
+                .tables
+                .iter()
+                .chain(theirs_tables)
+                .map(|table| table.get_name())
+                .collect();
+            table_names.sort();
+            table_names.dedup();
+
+            for table_name in table_names {
+                let key = Self::table_key(&name, &table_name);
+                let mine_index = current
+                    .tables
+                    .iter()
+                    .position(|table| table.get_name() == table_name);
+                let theirs_table = theirs_tables
+                    .iter()
+                    .find(|table| table.get_name() == table_name);
+
+                let mine =
+                    Self::version_of(&self.versions, self.timestamp, &key, mine_index.is_some());
+                let theirs = Self::version_of(
+                    &other.versions,
+                    other.timestamp,
+                    &key,
+                    theirs_table.is_some(),
+                );
+
+                if theirs > mine {
+                    match (mine_index, theirs_table) {
+                        (Some(index), Some(table)) => current.tables[index] = table.clone(),
+                        (None, Some(table)) => current.tables.push(table.clone()),
+                        (Some(index), None) => {
+                            current.tables.remove(index);
+                        }
+                        (None, None) => {}
+                    }
+                }
+            }
+        }
+
+        for (key, version) in &other.versions {
+            let entry = self.versions.entry(key.clone()).or_insert(*version);
+            *entry = (*entry).max(*version);
+        }
+        self.timestamp = self.timestamp.max(other.timestamp);
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
 
@@ -424,6 +569,14 @@ impl Schema {
             bytes.extend_from_slice(&keyspace_schema_bytes);
         }
 
+        bytes.extend_from_slice(&(self.versions.len() as u32).to_be_bytes());
+
+        for (object, version) in &self.versions {
+            bytes.extend_from_slice(&(object.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(object.as_bytes());
+            bytes.extend_from_slice(&version.to_be_bytes());
+        }
+
         bytes
     }
 
@@ -462,9 +615,39 @@ impl Schema {
             keyspaces.insert(keyspace_name, keyspace_schema);
         }
 
+        let mut versions_len_bytes = [0u8; 4];
+        cursor
+            .read_exact(&mut versions_len_bytes)
+            .map_err(|_| MessageError::CursorError)?;
+        let versions_len = u32::from_be_bytes(versions_len_bytes);
+
+        let mut versions = HashMap::new();
+
+        for _ in 0..versions_len {
+            let mut object_len_bytes = [0u8; 4];
+            cursor
+                .read_exact(&mut object_len_bytes)
+                .map_err(|_| MessageError::CursorError)?;
+            let object_len = u32::from_be_bytes(object_len_bytes);
+
+            let mut object_bytes = vec![0u8; object_len as usize];
+            cursor
+                .read_exact(&mut object_bytes)
+                .map_err(|_| MessageError::CursorError)?;
+            let object = String::from_utf8(object_bytes).map_err(|_| MessageError::CursorError)?;
+
+            let mut version_bytes = [0u8; 8];
+            cursor
+                .read_exact(&mut version_bytes)
+                .map_err(|_| MessageError::CursorError)?;
+
+            versions.insert(object, i64::from_be_bytes(version_bytes));
+        }
+
         Ok(Schema {
             keyspaces,
             timestamp,
+            versions,
         })
     }
 }
@@ -476,10 +659,49 @@ impl Schema {
 /// - `status`: The status of the node.
 /// - `version`: The version of the ApplicationState.
 /// - `schema`: The schema of the cluster.
+/// - `load`: How busy the node is, if it has already advertised it.
+/// - `datacenter`: The datacenter the node runs in, if it was configured.
+/// - `replaces`: The dead node whose tokens the node took, if it joined as its replacement.
+/// - `data_digests`: The digest of the rows the node stores of each table, by `keyspace.table`.
 pub struct ApplicationState {
     pub status: NodeStatus,
     pub version: u32,
     pub schema: Schema,
+    pub load: Option<NodeLoad>,
+    pub datacenter: Option<String>,
+    pub replaces: Option<Ipv4Addr>,
+    pub data_digests: BTreeMap<String, DataDigest>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+/// The work a node has waiting, advertised so coordinators can send reads to the replicas
+/// that are less busy.
+///
+/// ### Fields
+/// - `pending_queries`: The queries the node coordinates that have not been answered yet.
+/// - `queue_depth`: The internode messages received but not yet processed.
+pub struct NodeLoad {
+    pub pending_queries: u32,
+    pub queue_depth: u32,
+}
+
+impl NodeLoad {
+    /// All the work waiting in the node, used to compare it with other nodes.
+    pub fn total(&self) -> u64 {
+        u64::from(self.pending_queries) + u64::from(self.queue_depth)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+/// Summary of the rows a node stores of a token range of a table, advertised so that a replica
+/// that is missing data in the range can be told apart from its peers.
+///
+/// ### Fields
+/// - `rows`: The rows stored, tombstones included.
+/// - `checksum`: A checksum of the rows that does not depend on the order they are stored in.
+pub struct DataDigest {
+    pub rows: u64,
+    pub checksum: u32,
 }
 
 /// Represents the schema of the keyspace.
@@ -612,6 +834,21 @@ impl KeyspaceSchema {
         }
     }
 
+    /// Retrieves a mutable reference to a table by its name, to modify it in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `table_name` - The name of the table to search for.
+    ///
+    /// # Returns
+    /// Returns the found table or a `SchemaError` if not found.
+    pub fn get_table_mut(&mut self, table_name: &str) -> Result<&mut TableSchema, SchemaError> {
+        self.tables
+            .iter_mut()
+            .find(|table| table.get_name() == table_name)
+            .ok_or(SchemaError::InvalidTable(table_name.to_string()))
+    }
+
     /// Removes a table by its name.
     ///
     /// # Arguments
@@ -773,6 +1010,10 @@ impl ApplicationState {
             status,
             version,
             schema,
+            load: None,
+            datacenter: None,
+            replaces: None,
+            data_digests: BTreeMap::new(),
         }
     }
 
@@ -781,6 +1022,39 @@ impl ApplicationState {
         self.version += 1;
     }
 
+    /// Sets the load of the node. The version only changes if the load did, so an idle node
+    /// does not make its peers exchange the same state on every round.
+    pub fn set_load(&mut self, load: NodeLoad) {
+        if self.load != Some(load) {
+            self.load = Some(load);
+            self.version += 1;
+        }
+    }
+
+    /// Sets the datacenter of the node, bumping the version only if it changed.
+    pub fn set_datacenter(&mut self, datacenter: &str) {
+        if self.datacenter.as_deref() != Some(datacenter) {
+            self.datacenter = Some(datacenter.to_string());
+            self.version += 1;
+        }
+    }
+
+    /// Sets the dead node this node replaces, bumping the version only if it changed.
+    pub fn set_replaces(&mut self, dead: Ipv4Addr) {
+        if self.replaces != Some(dead) {
+            self.replaces = Some(dead);
+            self.version += 1;
+        }
+    }
+
+    /// Sets the digests of the tables of the node, bumping the version only if one changed.
+    pub fn set_data_digests(&mut self, data_digests: BTreeMap<String, DataDigest>) {
+        if self.data_digests != data_digests {
+            self.data_digests = data_digests;
+            self.version += 1;
+        }
+    }
+
     /// ```md
     /// 0    8    16   24   32
     /// +----+----+----+----+
@@ -791,7 +1065,41 @@ impl ApplicationState {
     /// |       schema      |
     /// |        ...        |
     /// +----+----+----+----+
+    /// |has_load|          |
+    /// +----+----+----+----+
+    /// |  pending_queries  |
+    /// +----+----+----+----+
+    /// |    queue_depth    |
+    /// +----+----+----+----+
+    /// |has_dc  |          |
+    /// +----+----+----+----+
+    /// |     dc_length     |
+    /// +----+----+----+----+
+    /// |    datacenter     |
+    /// |        ...        |
+    /// +----+----+----+----+
+    /// |has_rep |          |
+    /// +----+----+----+----+
+    /// |     replaces      |
+    /// +----+----+----+----+
+    /// |   digests_count   |
+    /// +----+----+----+----+
+    /// |    name_length    |
+    /// +----+----+----+----+
+    /// |       name        |
+    /// |        ...        |
+    /// +----+----+----+----+
+    /// |       rows        |
+    /// |                   |
+    /// +----+----+----+----+
+    /// |     checksum      |
+    /// +----+----+----+----+
     /// ```
+    /// `pending_queries` and `queue_depth` are only present if `has_load` is 1, and the
+    /// datacenter (`dc_length` bytes of UTF-8) only if `has_dc` is 1. The ip of the replaced
+    /// node is only present if `has_rep` is 1. The name, rows and
+    /// checksum are repeated for each of the `digests_count` tables.
+    ///
     /// Convert the `ApplicationState` message to a byte slice.
     pub fn as_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
@@ -806,6 +1114,40 @@ impl ApplicationState {
 
         bytes.extend_from_slice(&schemas_bytes);
 
+        match self.load {
+            Some(load) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&load.pending_queries.to_be_bytes());
+                bytes.extend_from_slice(&load.queue_depth.to_be_bytes());
+            }
+            None => bytes.push(0),
+        }
+
+        match &self.datacenter {
+            Some(datacenter) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&(datacenter.len() as u32).to_be_bytes());
+                bytes.extend_from_slice(datacenter.as_bytes());
+            }
+            None => bytes.push(0),
+        }
+
+        match self.replaces {
+            Some(dead) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&dead.octets());
+            }
+            None => bytes.push(0),
+        }
+
+        bytes.extend_from_slice(&(self.data_digests.len() as u32).to_be_bytes());
+        for (table, digest) in &self.data_digests {
+            bytes.extend_from_slice(&(table.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(table.as_bytes());
+            bytes.extend_from_slice(&digest.rows.to_be_bytes());
+            bytes.extend_from_slice(&digest.checksum.to_be_bytes());
+        }
+
         bytes
     }
 
@@ -839,10 +1181,127 @@ impl ApplicationState {
 
         let schema = Schema::from_bytes(cursor)?;
 
+        let mut has_load = [0u8; 1];
+        cursor
+            .read_exact(&mut has_load)
+            .map_err(|_| MessageError::CursorError)?;
+        let load = match has_load[0] {
+            0 => None,
+            1 => {
+                let mut pending_queries_bytes = [0u8; 4];
+                cursor
+                    .read_exact(&mut pending_queries_bytes)
+                    .map_err(|_| MessageError::CursorError)?;
+                let mut queue_depth_bytes = [0u8; 4];
+                cursor
+                    .read_exact(&mut queue_depth_bytes)
+                    .map_err(|_| MessageError::CursorError)?;
+
+                Some(NodeLoad {
+                    pending_queries: u32::from_be_bytes(pending_queries_bytes),
+                    queue_depth: u32::from_be_bytes(queue_depth_bytes),
+                })
+            }
+            value => {
+                return Err(MessageError::InvalidValue(format!(
+                    "Invalid load flag: {}",
+                    value
+                )))
+            }
+        };
+
+        let mut has_datacenter = [0u8; 1];
+        cursor
+            .read_exact(&mut has_datacenter)
+            .map_err(|_| MessageError::CursorError)?;
+        let datacenter = match has_datacenter[0] {
+            0 => None,
+            1 => {
+                let mut length_bytes = [0u8; 4];
+                cursor
+                    .read_exact(&mut length_bytes)
+                    .map_err(|_| MessageError::CursorError)?;
+                let mut datacenter_bytes = vec![0u8; u32::from_be_bytes(length_bytes) as usize];
+                cursor
+                    .read_exact(&mut datacenter_bytes)
+                    .map_err(|_| MessageError::CursorError)?;
+
+                Some(String::from_utf8(datacenter_bytes).map_err(|_| {
+                    MessageError::InvalidValue("Invalid datacenter name".to_string())
+                })?)
+            }
+            value => {
+                return Err(MessageError::InvalidValue(format!(
+                    "Invalid datacenter flag: {}",
+                    value
+                )))
+            }
+        };
+
+        let mut has_replaces = [0u8; 1];
+        cursor
+            .read_exact(&mut has_replaces)
+            .map_err(|_| MessageError::CursorError)?;
+        let replaces = match has_replaces[0] {
+            0 => None,
+            1 => {
+                let mut ip_bytes = [0u8; 4];
+                cursor
+                    .read_exact(&mut ip_bytes)
+                    .map_err(|_| MessageError::CursorError)?;
+                Some(Ipv4Addr::from(ip_bytes))
+            }
+            value => {
+                return Err(MessageError::InvalidValue(format!(
+                    "Invalid replaces flag: {}",
+                    value
+                )))
+            }
+        };
+
+        let mut count_bytes = [0u8; 4];
+        cursor
+            .read_exact(&mut count_bytes)
+            .map_err(|_| MessageError::CursorError)?;
+        let mut data_digests = BTreeMap::new();
+        for _ in 0..u32::from_be_bytes(count_bytes) {
+            let mut length_bytes = [0u8; 4];
+            cursor
+                .read_exact(&mut length_bytes)
+                .map_err(|_| MessageError::CursorError)?;
+            let mut table_bytes = vec![0u8; u32::from_be_bytes(length_bytes) as usize];
+            cursor
+                .read_exact(&mut table_bytes)
+                .map_err(|_| MessageError::CursorError)?;
+            let table = String::from_utf8(table_bytes)
+                .map_err(|_| MessageError::InvalidValue("Invalid table name".to_string()))?;
+
+            let mut rows_bytes = [0u8; 8];
+            cursor
+                .read_exact(&mut rows_bytes)
+                .map_err(|_| MessageError::CursorError)?;
+            let mut checksum_bytes = [0u8; 4];
+            cursor
+                .read_exact(&mut checksum_bytes)
+                .map_err(|_| MessageError::CursorError)?;
+
+            data_digests.insert(
+                table,
+                DataDigest {
+                    rows: u64::from_be_bytes(rows_bytes),
+                    checksum: u32::from_be_bytes(checksum_bytes),
+                },
+            );
+        }
+
         Ok(ApplicationState {
             status,
             version,
             schema,
+            load,
+            datacenter,
+            replaces,
+            data_digests,
         })
     }
 }
@@ -897,6 +1356,7 @@ impl NodeStatus {
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
+    use std::net::Ipv4Addr;
 
     use query_creator::clauses::{
         keyspace::create_keyspace_cql::CreateKeyspace,
@@ -905,7 +1365,8 @@ mod tests {
     };
 
     use crate::structures::application_state::{
-        ApplicationState, CursorSerializable, KeyspaceSchema, NodeStatus, Schema, TableSchema,
+        ApplicationState, CursorSerializable, DataDigest, KeyspaceSchema, NodeLoad, NodeStatus,
+        Schema, TableSchema,
     };
 
     #[test]
@@ -920,6 +1381,86 @@ mod tests {
 
         assert_eq!(app_state.status, NodeStatus::Bootstrap);
         assert_eq!(app_state.version, 1);
+        assert_eq!(app_state.load, None);
+    }
+
+    #[test]
+    fn load_to_from_bytes_and_bumps_version() {
+        let mut app_state = ApplicationState::new(NodeStatus::Normal, 1, Schema::new());
+        let load = NodeLoad {
+            pending_queries: 7,
+            queue_depth: 3,
+        };
+
+        app_state.set_load(load);
+        assert_eq!(app_state.version, 2);
+
+        // publicar la misma carga no genera una nueva version
+        app_state.set_load(load);
+        assert_eq!(app_state.version, 2);
+
+        let bytes = app_state.as_bytes();
+        let mut cursor = std::io::Cursor::new(bytes.as_slice());
+        let decoded = ApplicationState::from_bytes(&mut cursor).unwrap();
+
+        assert_eq!(decoded.load, Some(load));
+        assert_eq!(decoded.version, 2);
+    }
+
+    #[test]
+    fn datacenter_to_from_bytes_and_bumps_version() {
+        let mut app_state = ApplicationState::new(NodeStatus::Normal, 1, Schema::new());
+
+        app_state.set_datacenter("dc-norte");
+        app_state.set_datacenter("dc-norte");
+        assert_eq!(app_state.version, 2);
+
+        let bytes = app_state.as_bytes();
+        let mut cursor = std::io::Cursor::new(bytes.as_slice());
+        let decoded = ApplicationState::from_bytes(&mut cursor).unwrap();
+
+        assert_eq!(decoded.datacenter, Some("dc-norte".to_string()));
+        assert_eq!(decoded, app_state);
+    }
+
+    #[test]
+    fn replaces_to_from_bytes_and_bumps_version() {
+        let mut app_state = ApplicationState::new(NodeStatus::Bootstrap, 1, Schema::new());
+        let dead = Ipv4Addr::new(127, 0, 0, 9);
+
+        app_state.set_replaces(dead);
+        app_state.set_replaces(dead);
+        assert_eq!(app_state.version, 2);
+
+        let bytes = app_state.as_bytes();
+        let mut cursor = std::io::Cursor::new(bytes.as_slice());
+        let decoded = ApplicationState::from_bytes(&mut cursor).unwrap();
+
+        assert_eq!(decoded.replaces, Some(dead));
+        assert_eq!(decoded, app_state);
+    }
+
+    #[test]
+    fn data_digests_to_from_bytes_and_bump_version() {
+        let mut app_state = ApplicationState::new(NodeStatus::Normal, 1, Schema::new());
+        let data_digests = std::collections::BTreeMap::from([(
+            "sky.flights".to_string(),
+            DataDigest {
+                rows: 12,
+                checksum: 0xdeadbeef,
+            },
+        )]);
+
+        app_state.set_data_digests(data_digests.clone());
+        app_state.set_data_digests(data_digests.clone());
+        assert_eq!(app_state.version, 2);
+
+        let bytes = app_state.as_bytes();
+        let mut cursor = std::io::Cursor::new(bytes.as_slice());
+        let decoded = ApplicationState::from_bytes(&mut cursor).unwrap();
+
+        assert_eq!(decoded.data_digests, data_digests);
+        assert_eq!(decoded, app_state);
     }
 
     #[test]
@@ -1035,6 +1576,7 @@ mod tests {
     fn schema_to_from_bytes() {
         let expected_schema = Schema {
             timestamp: 100,
+            versions: HashMap::from([("keyspace.table".to_string(), 90)]),
             keyspaces: HashMap::from([(
                 "keyspace".to_string(),
                 KeyspaceSchema {
@@ -1071,4 +1613,146 @@ mod tests {
 
         assert_eq!(expected_schema, schema);
     }
+
+    fn keyspace(name: &str, replication_factor: u32) -> KeyspaceSchema {
+        KeyspaceSchema::new(
+            CreateKeyspace {
+                name: name.to_string(),
+                replication_factor,
+                ..Default::default()
+            },
+            vec![],
+        )
+    }
+
+    fn table(name: &str, keyspace: &str) -> TableSchema {
+        TableSchema::new(CreateTable {
+            name: name.to_string(),
+            keyspace_used_name: keyspace.to_string(),
+            if_not_exists_clause: false,
+            columns: vec![],
+            clustering_columns_in_order: vec![],
+        })
+    }
+
+    #[test]
+    fn merge_keeps_keyspaces_created_concurrently() {
+        let mut schema = Schema::new();
+        schema
+            .keyspaces
+            .insert("flights".to_string(), keyspace("flights", 1));
+        schema.touch_keyspace("flights", 100);
+
+        let mut other = Schema::new();
+        other
+            .keyspaces
+            .insert("airports".to_string(), keyspace("airports", 1));
+        other.touch_keyspace("airports", 200);
+
+        schema.merge(&other);
+
+        assert!(schema.keyspaces.contains_key("flights"));
+        assert!(schema.keyspaces.contains_key("airports"));
+        assert_eq!(schema.timestamp, 200);
+    }
+
+    #[test]
+    fn next_timestamp_increases_when_the_clock_goes_back() {
+        let mut schema = Schema::new();
+        let first = schema.next_timestamp(1_000);
+        schema.touch_keyspace("flights", first);
+
+        // El reloj retrocede 10 segundos
+        let second = schema.next_timestamp(first - 10_000);
+        schema.touch_keyspace("flights", second);
+
+        assert_eq!(second, first + 1);
+        assert_eq!(schema.timestamp, first + 1);
+        assert_eq!(schema.next_timestamp(5_000), 5_000);
+    }
+
+    #[test]
+    fn merge_keeps_newest_version_of_same_keyspace() {
+        let mut schema = Schema::new();
+        schema
+            .keyspaces
+            .insert("flights".to_string(), keyspace("flights", 1));
+        schema.touch_keyspace("flights", 100);
+        // Un cambio posterior en otro keyspace no hace mas nuevo a `flights`
+        schema
+            .keyspaces
+            .insert("airports".to_string(), keyspace("airports", 1));
+        schema.touch_keyspace("airports", 300);
+
+        let mut other = Schema::new();
+        other
+            .keyspaces
+            .insert("flights".to_string(), keyspace("flights", 3));
+        other.touch_keyspace("flights", 200);
+
+        schema.merge(&other);
+
+        assert_eq!(schema.keyspaces["flights"].get_replication_factor(), 3);
+        assert!(schema.keyspaces.contains_key("airports"));
+    }
+
+    #[test]
+    fn merge_keeps_tables_created_concurrently_in_same_keyspace() {
+        let mut schema = Schema::new();
+        let mut flights = keyspace("flights", 1);
+        flights.tables.push(table("departures", "flights"));
+        schema.keyspaces.insert("flights".to_string(), flights);
+        schema.touch_keyspace("flights", 100);
+        schema.touch_table("flights", "departures", 150);
+
+        let mut other = Schema::new();
+        let mut flights = keyspace("flights", 1);
+        flights.tables.push(table("arrivals", "flights"));
+        other.keyspaces.insert("flights".to_string(), flights);
+        other.touch_keyspace("flights", 100);
+        other.touch_table("flights", "arrivals", 120);
+
+        schema.merge(&other);
+
+        let mut tables: Vec<String> = schema.keyspaces["flights"]
+            .tables
+            .iter()
+            .map(|table| table.get_name())
+            .collect();
+        tables.sort();
+        assert_eq!(tables, vec!["arrivals", "departures"]);
+    }
+
+    #[test]
+    fn merge_applies_newer_drop() {
+        let mut schema = Schema::new();
+        schema
+            .keyspaces
+            .insert("flights".to_string(), keyspace("flights", 1));
+        schema.touch_keyspace("flights", 100);
+
+        // El otro nodo vio el keyspace y despues lo borro
+        let mut other = Schema::new();
+        other.touch_keyspace("flights", 200);
+
+        schema.merge(&other);
+
+        assert!(!schema.keyspaces.contains_key("flights"));
+        assert_eq!(schema.versions.get("flights"), Some(&200));
+    }
+
+    #[test]
+    fn schema_versions_to_from_bytes() {
+        let mut expected_schema = Schema::new();
+        expected_schema.touch_keyspace("flights", 100);
+        expected_schema.touch_table("flights", "departures", 200);
+
+        let bytes = expected_schema.to_bytes();
+
+        let mut cursor = std::io::Cursor::new(bytes.as_slice());
+
+        let schema = Schema::from_bytes(&mut cursor).unwrap();
+
+        assert_eq!(expected_schema, schema);
+    }
 }
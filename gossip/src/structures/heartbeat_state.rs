@@ -1,7 +1,12 @@
-#[derive(Debug, Clone, Copy, PartialEq, Ord, PartialOrd, Eq, Default)]
-/// The ordering of `HeartbeatState` is lexicographical based on the `generation` first and then `version`. `Ord` does this.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 /// Represents the heartbeat state of the endpoint in the cluster at a given point in time.
 ///
+/// The ordering of `HeartbeatState` is lexicographical based on the `generation` first and then
+/// `version`, so any state of a newer generation is greater, whatever its version.
+///
+/// The version never wraps around: when it reaches `u32::MAX`, `inc_version` starts a new
+/// generation with version 0, which still orders after every state of the previous generation.
+///
 /// ### Fields
 /// - `generation`: The generation of the node.
 /// - `version`: The version of the node.
@@ -20,14 +25,37 @@ impl HeartbeatState {
     }
 
     /// Increments the version of the `HeartbeatState`.
+    ///
+    /// If the version would overflow, the generation is incremented and the version is reset
+    /// to 0 instead, so the new state is still greater than the previous one.
     pub fn inc_version(&mut self) {
-        self.version += 1;
+        match self.version.checked_add(1) {
+            Some(version) => self.version = version,
+            None => {
+                self.generation = self.generation.saturating_add(1);
+                self.version = 0;
+            }
+        }
+    }
+}
+
+impl Ord for HeartbeatState {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.generation, self.version).cmp(&(other.generation, other.version))
+    }
+}
+
+impl PartialOrd for HeartbeatState {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::HeartbeatState;
+    use crate::messages::Digest;
+    use std::net::Ipv4Addr;
 
     #[test]
     fn heartbeat_state_ordering() {
@@ -49,4 +77,57 @@ mod tests {
         assert!(heartbeat_state_4 > heartbeat_state_2);
         assert!(heartbeat_state_4 > heartbeat_state_3);
     }
+
+    #[test]
+    fn inc_version_starts_a_new_generation_instead_of_wrapping() {
+        let before = HeartbeatState::new(5, u32::MAX);
+        let mut after = before;
+        after.inc_version();
+
+        assert_eq!(after, HeartbeatState::new(6, 0));
+        assert!(after > before);
+
+        // Los digests se ordenan igual que los heartbeat states
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        assert!(
+            Digest::from_heartbeat_state(ip, &after) > Digest::from_heartbeat_state(ip, &before)
+        );
+    }
+
+    #[test]
+    fn same_generation_is_ordered_by_version() {
+        let older = HeartbeatState::new(3, 1);
+        let newer = HeartbeatState::new(3, 2);
+
+        assert!(older < newer);
+        assert_eq!(older.cmp(&older), std::cmp::Ordering::Equal);
+        assert_eq!(newer.max(older), newer);
+    }
+
+    #[test]
+    fn newer_generation_wins_over_a_higher_version() {
+        let older = HeartbeatState::new(3, 100);
+        let newer = HeartbeatState::new(4, 1);
+
+        assert!(older < newer);
+
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        assert!(
+            Digest::from_heartbeat_state(ip, &older) < Digest::from_heartbeat_state(ip, &newer)
+        );
+    }
+
+    #[test]
+    fn digest_round_trips_the_heartbeat_state() {
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        for state in [
+            HeartbeatState::default(),
+            HeartbeatState::new(7, 3),
+            HeartbeatState::new(u128::MAX, u32::MAX),
+        ] {
+            let digest = Digest::from_heartbeat_state(ip, &state);
+            assert_eq!(digest.address, ip);
+            assert_eq!(digest.get_heartbeat_state(), state);
+        }
+    }
 }
@@ -1,7 +1,7 @@
-use std::{net::Ipv4Addr, str::FromStr};
+use std::net::Ipv4Addr;
 
 use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Utc};
-use driver::{self, CassandraClient, QueryResult};
+use driver::{self, CassandraPool, ClientError, QueryResult};
 use native_protocol::messages::result::{result_, rows};
 use walkers::Position;
 
@@ -10,7 +10,9 @@ use crate::types::{Airport, Flight, FlightInfo, FlightStatus};
 #[derive(Debug, Clone)]
 pub struct DBError;
 
-const IP: &str = "127.0.0.1";
+const IP: Ipv4Addr = Ipv4Addr::new(127, 0, 0, 1);
+// La interfaz consulta desde un solo hilo, con una conexion alcanza
+const POOL_SIZE: usize = 1;
 
 /// A trait that defines the required methods for a provider to manage flight
 /// and airport data. This trait is implemented by any structure that interacts
@@ -45,8 +47,12 @@ pub trait Provider {
 ///
 /// The `Db` struct is responsible for connecting to a Cassandra database and
 /// executing queries required by the graphical interface of the flight simulator.
+///
+/// The connection is taken from a `CassandraPool`. If the node goes down the queries fail
+/// and `is_connected` turns `false`; the next query opens a new connection.
 pub struct Db {
-    driver: CassandraClient,
+    pool: CassandraPool,
+    connected: bool,
 }
 
 impl Default for Db {
@@ -56,18 +62,94 @@ impl Default for Db {
 }
 
 impl Db {
-    /// Creates a new instance of the `Db` struct, establishing a connection to the database.
+    /// Creates a new instance of the `Db` struct.
+    ///
+    /// The connection with the database is opened by the first query, so the interface can
+    /// start while the node is down.
     pub fn new() -> Self {
-        let mut driver = CassandraClient::connect(Ipv4Addr::from_str(IP).unwrap()).unwrap();
-        driver.startup().unwrap();
-        Self { driver: driver }
+        Self {
+            pool: CassandraPool::new(IP, POOL_SIZE),
+            connected: false,
+        }
+    }
+
+    /// Whether the last query reached the database.
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Maps the rows of a `SELECT` on `sky.flights` into flights.
+    ///
+    /// The projection must have every column of the table. Any other result is mapped into
+    /// no flights.
+    pub fn fetch_flights(result: QueryResult) -> Result<Vec<Flight>, DBError> {
+        match result {
+            QueryResult::Result(result_::Result::Rows(res)) => {
+                res.rows_content.iter().map(flight_from_row).collect()
+            }
+            _ => Ok(Vec::new()),
+        }
     }
 
     fn execute_query(&mut self, query: &str, consistency: &str) -> Result<QueryResult, DBError> {
-        self.driver.execute(query, consistency).map_err(|_| DBError)
+        let Ok(mut connection) = self.pool.acquire() else {
+            self.connected = false;
+            return Err(DBError);
+        };
+
+        match connection.execute(query, consistency) {
+            Ok(result) => {
+                self.connected = true;
+                Ok(result)
+            }
+            Err(
+                ClientError::ConnectionError | ClientError::IOError | ClientError::TimeoutError,
+            ) => {
+                // La conexion quedo rota, el pool abre otra en la proxima consulta
+                connection.discard();
+                self.connected = false;
+                Err(DBError)
+            }
+            Err(_) => Err(DBError),
+        }
     }
 }
 
+fn flight_from_row(row: &rows::Row) -> Result<Flight, DBError> {
+    let ascii = |column: &str| match row.get(column) {
+        Some(rows::ColumnValue::Ascii(value)) => Ok(value.to_string()),
+        _ => Err(DBError),
+    };
+    let timestamp = |column: &str| match row.get(column) {
+        Some(rows::ColumnValue::Timestamp(value)) => Ok(*value),
+        _ => Err(DBError),
+    };
+
+    let position = match (row.get("lat"), row.get("lon")) {
+        (Some(rows::ColumnValue::Double(latitud)), Some(rows::ColumnValue::Double(longitud))) => {
+            Position::from_lat_lon(*latitud, *longitud)
+        }
+        _ => return Err(DBError),
+    };
+
+    let heading = match row.get("angle") {
+        Some(rows::ColumnValue::Float(angle)) => *angle,
+        _ => return Err(DBError),
+    };
+
+    Ok(Flight {
+        number: ascii("number")?,
+        status: ascii("status")?,
+        position,
+        heading,
+        departure_time: timestamp("departure_time")?,
+        arrival_time: timestamp("arrival_time")?,
+        airport: ascii("airport")?,
+        direction: ascii("direction")?,
+        info: None,
+    })
+}
+
 impl Provider for Db {
     /// Get the airports from a country from the database to show them in the graphical interface.
     fn get_airports_by_country(
@@ -141,75 +223,7 @@ impl Provider for Db {
             .execute_query(query.as_str(), "quorum")
             .map_err(|_| DBError)?;
 
-        let mut flights: Vec<Flight> = Vec::new();
-
-        if let QueryResult::Result(result_::Result::Rows(res)) = result {
-            for row in res.rows_content {
-                let mut flight = Flight {
-                    number: String::new(),
-                    status: String::new(),
-                    position: Position::from_lat_lon(0.0, 0.0),
-                    heading: 0.0,
-                    departure_time: 0,
-                    arrival_time: 0,
-                    airport: String::new(),
-                    direction: String::new(),
-                    info: None,
-                };
-
-                if let Some(number) = row.get("number") {
-                    if let rows::ColumnValue::Ascii(number) = number {
-                        flight.number = number.to_string();
-                    }
-                } else {
-                    return Err(DBError);
-                }
-
-                if let Some(status) = row.get("status") {
-                    if let rows::ColumnValue::Ascii(status) = status {
-                        flight.status = status.to_string();
-                    }
-                } else {
-                    return Err(DBError);
-                }
-
-                if let Some(departure_time) = row.get("departure_time") {
-                    if let rows::ColumnValue::Timestamp(departure_time) = departure_time {
-                        flight.departure_time = *departure_time;
-                    }
-                } else {
-                    return Err(DBError);
-                }
-
-                if let Some(arrival_time) = row.get("arrival_time") {
-                    if let rows::ColumnValue::Timestamp(arrival_time) = arrival_time {
-                        flight.arrival_time = *arrival_time;
-                    }
-                } else {
-                    return Err(DBError);
-                }
-
-                if let Some(airport) = row.get("airport") {
-                    if let rows::ColumnValue::Ascii(airport) = airport {
-                        flight.airport = airport.to_string();
-                    }
-                } else {
-                    return Err(DBError);
-                }
-
-                if let Some(direction) = row.get("direction") {
-                    if let rows::ColumnValue::Ascii(direction) = direction {
-                        flight.direction = direction.to_string();
-                    }
-                } else {
-                    return Err(DBError);
-                }
-
-                flights.push(flight);
-            }
-        }
-
-        Ok(flights)
+        Self::fetch_flights(result)
     }
 
     fn get_arrival_flights(
@@ -231,75 +245,7 @@ impl Provider for Db {
             .execute_query(query.as_str(), "quorum")
             .map_err(|_| DBError)?;
 
-        let mut flights: Vec<Flight> = Vec::new();
-
-        if let QueryResult::Result(result_::Result::Rows(res)) = result {
-            for row in res.rows_content {
-                let mut flight = Flight {
-                    number: String::new(),
-                    status: String::new(),
-                    position: Position::from_lat_lon(0.0, 0.0),
-                    heading: 0.0,
-                    departure_time: 0,
-                    arrival_time: 0,
-                    airport: String::new(),
-                    direction: String::new(),
-                    info: None,
-                };
-
-                if let Some(number) = row.get("number") {
-                    if let rows::ColumnValue::Ascii(number) = number {
-                        flight.number = number.to_string();
-                    }
-                } else {
-                    return Err(DBError);
-                }
-
-                if let Some(status) = row.get("status") {
-                    if let rows::ColumnValue::Ascii(status) = status {
-                        flight.status = status.to_string();
-                    }
-                } else {
-                    return Err(DBError);
-                }
-
-                if let Some(departure_time) = row.get("departure_time") {
-                    if let rows::ColumnValue::Timestamp(departure_time) = departure_time {
-                        flight.departure_time = *departure_time;
-                    }
-                } else {
-                    return Err(DBError);
-                }
-
-                if let Some(arrival_time) = row.get("arrival_time") {
-                    if let rows::ColumnValue::Timestamp(arrival_time) = arrival_time {
-                        flight.arrival_time = *arrival_time;
-                    }
-                } else {
-                    return Err(DBError);
-                }
-
-                if let Some(airport) = row.get("airport") {
-                    if let rows::ColumnValue::Ascii(airport) = airport {
-                        flight.airport = airport.to_string();
-                    }
-                } else {
-                    return Err(DBError);
-                }
-
-                if let Some(direction) = row.get("direction") {
-                    if let rows::ColumnValue::Ascii(direction) = direction {
-                        flight.direction = direction.to_string();
-                    }
-                } else {
-                    return Err(DBError);
-                }
-
-                flights.push(flight);
-            }
-        }
-
-        Ok(flights)
+        Self::fetch_flights(result)
     }
 
     fn get_flight_info(&mut self, number: &str) -> std::result::Result<FlightInfo, DBError> {
@@ -388,97 +334,13 @@ impl Provider for Db {
             .execute_query(query.as_str(), "one")
             .map_err(|_| DBError)?;
 
-        let mut flights: Vec<Flight> = Vec::new();
-
-        if let QueryResult::Result(result_::Result::Rows(res)) = result {
-            for row in res.rows_content {
-                let mut flight = Flight {
-                    number: String::new(),
-                    status: String::new(),
-                    position: Position::from_lat_lon(0.0, 0.0),
-                    heading: 0.0,
-                    departure_time: 0,
-                    arrival_time: 0,
-                    airport: String::new(),
-                    direction: String::new(),
-                    info: None,
-                };
-
-                if let Some(number) = row.get("number") {
-                    if let rows::ColumnValue::Ascii(number) = number {
-                        flight.number = number.to_string();
-                    }
-                } else {
-                    return Err(DBError);
-                }
-
-                if let Some(status) = row.get("status") {
-                    if let rows::ColumnValue::Ascii(status) = status {
-                        flight.status = status.to_string();
-                    }
-                } else {
-                    return Err(DBError);
-                }
-
-                if let Some(departure_time) = row.get("departure_time") {
-                    if let rows::ColumnValue::Timestamp(departure_time) = departure_time {
-                        flight.departure_time = *departure_time;
-                    }
-                } else {
-                    return Err(DBError);
-                }
-
-                if let Some(arrival_time) = row.get("arrival_time") {
-                    if let rows::ColumnValue::Timestamp(arrival_time) = arrival_time {
-                        flight.arrival_time = *arrival_time;
-                    }
-                } else {
-                    return Err(DBError);
-                }
-
-                if let Some(airport) = row.get("airport") {
-                    if let rows::ColumnValue::Ascii(airport) = airport {
-                        flight.airport = airport.to_string();
-                    }
-                } else {
-                    return Err(DBError);
-                }
-
-                if let Some(direction) = row.get("direction") {
-                    if let rows::ColumnValue::Ascii(direction) = direction {
-                        flight.direction = direction.to_string();
-                    }
-                } else {
-                    return Err(DBError);
-                }
-
-                if let (Some(lat), Some(lon)) = (row.get("lat"), row.get("lon")) {
-                    if let (
-                        rows::ColumnValue::Double(latitud),
-                        rows::ColumnValue::Double(longitud),
-                    ) = (lat, lon)
-                    {
-                        flight.position = Position::from_lat_lon(*latitud, *longitud);
-                    }
-                } else {
-                    return Err(DBError);
-                }
-
-                if let Some(angle) = row.get("angle") {
-                    if let rows::ColumnValue::Float(angle) = angle {
-                        flight.heading = *angle;
-                    }
-                } else {
-                    return Err(DBError);
-                }
-
-                if flight.status == FlightStatus::OnTime.as_str()
+        let flights = Self::fetch_flights(result)?
+            .into_iter()
+            .filter(|flight| {
+                flight.status == FlightStatus::OnTime.as_str()
                     || flight.status == FlightStatus::Delayed.as_str()
-                {
-                    flights.push(flight);
-                }
-            }
-        }
+            })
+            .collect();
 
         Ok(flights)
     }
@@ -592,3 +454,101 @@ impl Provider for Db {
         self.get_airports_by_country("ARG")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use native_protocol::messages::result::rows::{ColumnType, ColumnValue, Rows};
+
+    fn flights_columns() -> Vec<(String, ColumnType)> {
+        [
+            ("number", ColumnType::Ascii),
+            ("status", ColumnType::Ascii),
+            ("lat", ColumnType::Double),
+            ("lon", ColumnType::Double),
+            ("angle", ColumnType::Float),
+            ("departure_time", ColumnType::Timestamp),
+            ("arrival_time", ColumnType::Timestamp),
+            ("airport", ColumnType::Ascii),
+            ("direction", ColumnType::Ascii),
+        ]
+        .into_iter()
+        .map(|(name, column_type)| (name.to_string(), column_type))
+        .collect()
+    }
+
+    fn flight_row(number: &str) -> rows::Row {
+        rows::Row::from([
+            ("number".to_string(), ColumnValue::Ascii(number.to_string())),
+            (
+                "status".to_string(),
+                ColumnValue::Ascii("on time".to_string()),
+            ),
+            ("lat".to_string(), ColumnValue::Double(-34.8)),
+            ("lon".to_string(), ColumnValue::Double(-58.5)),
+            ("angle".to_string(), ColumnValue::Float(270.0)),
+            (
+                "departure_time".to_string(),
+                ColumnValue::Timestamp(1_733_040_000),
+            ),
+            (
+                "arrival_time".to_string(),
+                ColumnValue::Timestamp(1_733_047_200),
+            ),
+            ("airport".to_string(), ColumnValue::Ascii("EZE".to_string())),
+            (
+                "direction".to_string(),
+                ColumnValue::Ascii("departure".to_string()),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_fetch_flights_maps_the_rows_into_flights() {
+        let result = QueryResult::Result(result_::Result::Rows(Rows::new(
+            flights_columns(),
+            vec![flight_row("AR1001"), flight_row("AR1002")],
+        )));
+
+        let flights = Db::fetch_flights(result).unwrap();
+
+        assert_eq!(flights.len(), 2);
+        assert_eq!(
+            flights[0],
+            Flight {
+                number: "AR1001".to_string(),
+                status: "on time".to_string(),
+                position: Position::from_lat_lon(-34.8, -58.5),
+                heading: 270.0,
+                departure_time: 1_733_040_000,
+                arrival_time: 1_733_047_200,
+                airport: "EZE".to_string(),
+                direction: "departure".to_string(),
+                info: None,
+            }
+        );
+        assert_eq!(flights[1].number, "AR1002");
+    }
+
+    #[test]
+    fn test_fetch_flights_without_a_column_fails() {
+        let row = flight_row("AR1001")
+            .iter()
+            .filter(|(name, _)| *name != "angle")
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+        let result = QueryResult::Result(result_::Result::Rows(Rows::new(
+            flights_columns(),
+            vec![row],
+        )));
+
+        assert!(Db::fetch_flights(result).is_err());
+    }
+
+    #[test]
+    fn test_fetch_flights_of_a_void_result_is_empty() {
+        let result = QueryResult::Result(result_::Result::Void);
+
+        assert!(Db::fetch_flights(result).unwrap().is_empty());
+    }
+}
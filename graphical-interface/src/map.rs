@@ -12,7 +12,7 @@ use crate::{
     db::{Db, Provider},
     plugins,
     state::{SelectionState, ViewState},
-    types::{CountryTracker, _MapBounds},
+    types::{_MapBounds, CountryTracker},
     widgets::{WidgetAddFlight, WidgetAirport, WidgetFlight},
     windows,
 };
@@ -170,6 +170,7 @@ impl eframe::App for MyApp {
                 {
                     use windows::*;
                     zoom(ui, &mut self.map_memory);
+                    connection_status(ui, self.db.is_connected());
                 }
             });
     }
@@ -1,4 +1,4 @@
-use egui::{Align2, RichText, Ui, Window};
+use egui::{Align2, Color32, RichText, Ui, Window};
 use walkers::MapMemory;
 
 /// Simple GUI to zoom in and out.
@@ -20,3 +20,21 @@ pub fn zoom(ui: &Ui, map_memory: &mut MapMemory) {
             });
         });
 }
+
+/// Shows whether the data on the map is live or the database cannot be reached.
+pub fn connection_status(ui: &Ui, connected: bool) {
+    let (text, color) = if connected {
+        ("● Connected", Color32::GREEN)
+    } else {
+        ("● Disconnected, retrying...", Color32::RED)
+    };
+
+    Window::new("Connection")
+        .collapsible(false)
+        .resizable(false)
+        .title_bar(false)
+        .anchor(Align2::LEFT_TOP, [10., 10.])
+        .show(ui.ctx(), |ui| {
+            ui.label(RichText::new(text).color(color));
+        });
+}
@@ -8,6 +8,7 @@ use crate::{
     header::{Flags, FrameHeader, Opcode, Version},
     messages::{
         auth::{AuthChallenge, AuthResponse, AuthSuccess, Authenticate},
+        batch::Batch,
         error::Error,
         query::Query,
         result::result_::Result,
@@ -24,6 +25,8 @@ pub enum Frame {
     Ready,
     /// Performs a CQL query.
     Query(Query),
+    /// Performs several CQL queries, answered with a single result.
+    Batch(Batch),
     /// The result to a query.
     Result(Result),
     /// Indicates an error processing a request.
@@ -38,6 +41,38 @@ pub enum Frame {
     AuthChallenge(AuthChallenge),
 }
 
+/// Size of the header that starts every frame.
+pub const HEADER_LENGTH: usize = 9;
+
+impl Frame {
+    /// Serializes the frame like `to_bytes`, with `stream` as its stream id.
+    ///
+    /// A client may send several requests on the same connection without waiting for their
+    /// answers, each with its own stream id. The answer to a request has to carry the stream id
+    /// of the request, since answers can arrive in a different order.
+    pub fn to_bytes_with_stream(&self, stream: i16) -> std::result::Result<Vec<u8>, NativeError> {
+        let mut bytes = self.to_bytes()?;
+        bytes[2..4].copy_from_slice(&stream.to_be_bytes());
+        Ok(bytes)
+    }
+
+    /// Returns the stream id of the frame serialized at the start of `bytes`.
+    pub fn stream_of(bytes: &[u8]) -> std::result::Result<i16, NativeError> {
+        match bytes.get(2..4) {
+            Some(stream) => Ok(i16::from_be_bytes([stream[0], stream[1]])),
+            None => Err(NativeError::NotEnoughBytes),
+        }
+    }
+
+    /// Returns the length, header included, of the frame serialized at the start of `bytes`,
+    /// or `None` if `bytes` does not have the whole header yet.
+    pub fn length_of(bytes: &[u8]) -> Option<usize> {
+        let length = bytes.get(5..HEADER_LENGTH)?;
+        let body_length = u32::from_be_bytes([length[0], length[1], length[2], length[3]]);
+        Some(HEADER_LENGTH + body_length as usize)
+    }
+}
+
 impl Serializable for Frame {
     /// 0         8        16        24        32         40
     /// +---------+---------+---------+---------+---------+
@@ -54,7 +89,9 @@ impl Serializable for Frame {
         let mut bytes = Vec::new();
 
         let version = match self {
-            Frame::Startup | Frame::Query(_) | Frame::AuthResponse(_) => Version::RequestV3,
+            Frame::Startup | Frame::Query(_) | Frame::Batch(_) | Frame::AuthResponse(_) => {
+                Version::RequestV3
+            }
             Frame::Ready
             | Frame::Result(_)
             | Frame::Error(_)
@@ -67,6 +104,7 @@ impl Serializable for Frame {
             Frame::Startup => Opcode::Startup,
             Frame::Ready => Opcode::Ready,
             Frame::Query(_) => Opcode::Query,
+            Frame::Batch(_) => Opcode::Batch,
             Frame::Result(_) => Opcode::Result,
             Frame::Error(_) => Opcode::Error,
             Frame::AuthChallenge(_) => Opcode::AuthChallenge,
@@ -84,6 +122,7 @@ impl Serializable for Frame {
             Frame::Startup => vec![0x00, 0x00], // View 4.1.1., the startup body is a [string map] of options, but we do not use them. The [string map] requires 2 bytes for the length nonetheless, therefore, the 0x0000.
             Frame::Ready => Vec::new(),
             Frame::Query(query) => query.to_bytes()?,
+            Frame::Batch(batch) => batch.to_bytes()?,
             Frame::Result(result) => result.to_bytes()?,
             Frame::Error(error) => error.to_bytes()?,
             Frame::AuthChallenge(auth_challenge) => auth_challenge.to_bytes()?,
@@ -158,6 +197,7 @@ impl Serializable for Frame {
             Opcode::Startup => Self::Startup,
             Opcode::Ready => Self::Ready,
             Opcode::Query => Self::Query(Query::from_bytes(&body)?),
+            Opcode::Batch => Self::Batch(Batch::from_bytes(&body)?),
             Opcode::Error => Self::Error(Error::from_bytes(&body)?),
             Opcode::Result => Self::Result(Result::from_bytes(&body)?),
             Opcode::AuthChallenge => Self::AuthChallenge(AuthChallenge::from_bytes(&body)?),
@@ -174,12 +214,11 @@ impl Serializable for Frame {
 #[cfg(test)]
 mod tests {
 
-    use std::collections::BTreeMap;
-
     use crate::{
         messages::{
+            batch::BatchType,
             query::{Consistency, QueryParams},
-            result::rows::{ColumnType, ColumnValue, Rows},
+            result::rows::{ColumnType, ColumnValue, Row, Rows},
         },
         types::Bytes,
     };
@@ -285,6 +324,26 @@ mod tests {
         assert_eq!(query.params, query_params);
     }
 
+    #[test]
+    fn bytes_to_frame_batch() {
+        let batch = Batch::new(
+            BatchType::Unlogged,
+            vec![
+                "INSERT INTO sky.flights (id) VALUES ('1')".to_string(),
+                "DELETE FROM sky.flights WHERE id = '2'".to_string(),
+            ],
+            Consistency::Quorum,
+        );
+        let bytes = Frame::Batch(batch.clone()).to_bytes().unwrap();
+        // Version de pedido y opcode BATCH
+        assert_eq!((bytes[0], bytes[4]), (0x03, 0x0D));
+
+        let Frame::Batch(read) = Frame::from_bytes(&bytes).unwrap() else {
+            panic!("expected a batch");
+        };
+        assert_eq!(read, batch);
+    }
+
     #[test]
     fn bytes_to_frame_result() {
         let cols = vec![
@@ -292,11 +351,11 @@ mod tests {
             ("name".to_string(), ColumnType::Varchar),
         ];
         let rows_content = vec![
-            BTreeMap::from([
+            Row::from([
                 ("age".to_string(), ColumnValue::Int(1)),
                 ("name".to_string(), ColumnValue::Varchar("John".to_string())),
             ]),
-            BTreeMap::from([
+            Row::from([
                 ("age".to_string(), ColumnValue::Int(2)),
                 ("name".to_string(), ColumnValue::Varchar("Doe".to_string())),
             ]),
@@ -426,4 +485,30 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_frame_keeps_the_stream_id_it_was_serialized_with() {
+        let frame = Frame::Ready;
+        let bytes = frame.to_bytes_with_stream(0x0102).unwrap();
+
+        assert_eq!(
+            bytes,
+            vec![0x83, 0x00, 0x01, 0x02, 0x02, 0x00, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(Frame::stream_of(&bytes).unwrap(), 0x0102);
+        assert!(matches!(Frame::from_bytes(&bytes).unwrap(), Frame::Ready));
+        assert_eq!(Frame::stream_of(&frame.to_bytes().unwrap()).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_length_of_a_frame_includes_its_header() {
+        let query = Query::new(
+            "SELECT * FROM table".to_string(),
+            QueryParams::new(Consistency::One, vec![]),
+        );
+        let bytes = Frame::Query(query).to_bytes().unwrap();
+
+        assert_eq!(Frame::length_of(&bytes), Some(bytes.len()));
+        assert_eq!(Frame::length_of(&bytes[..HEADER_LENGTH - 1]), None);
+    }
 }
@@ -0,0 +1,261 @@
+use std::io::{Cursor, Read};
+
+use crate::{errors::NativeError, messages::query::Consistency, Serializable};
+
+/// Kind of a query of a batch that is a query string, and not the id of a prepared statement.
+const QUERY_KIND_STRING: u8 = 0x00;
+/// Bytes of the shortest query of a batch: its kind, an empty string and no values.
+const MIN_QUERY_LENGTH: usize = 1 + 4 + 2;
+
+/// Whether the coordinator writes the batch to its batchlog before applying it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BatchType {
+    /// The batch is applied as a whole, even if the coordinator fails halfway.
+    Logged = 0x00,
+    /// The statements are applied without a batchlog, each on its own.
+    Unlogged = 0x01,
+    /// A batch of counter updates.
+    Counter = 0x02,
+}
+
+impl BatchType {
+    fn from_byte(byte: u8) -> Result<Self, NativeError> {
+        match byte {
+            0x00 => Ok(BatchType::Logged),
+            0x01 => Ok(BatchType::Unlogged),
+            0x02 => Ok(BatchType::Counter),
+            _ => Err(NativeError::InvalidVariant),
+        }
+    }
+}
+
+/// Executes several queries in a single request, answered with a single result.
+///
+/// Only query strings without bound values are supported: a batch with prepared statements
+/// or values cannot be read.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Batch {
+    pub batch_type: BatchType,
+    pub queries: Vec<String>,
+    pub consistency: Consistency,
+}
+
+impl Batch {
+    pub fn new(batch_type: BatchType, queries: Vec<String>, consistency: Consistency) -> Self {
+        Batch {
+            batch_type,
+            queries,
+            consistency,
+        }
+    }
+}
+
+impl Serializable for Batch {
+    /// ```md
+    /// 0         8        16        24        32
+    /// +---------+---------+---------+---------+
+    /// |  type   |  queries count (2)|  kind   |
+    /// +---------+---------+---------+---------+
+    /// |        query length (4 bytes)         |
+    /// +---------+---------+---------+---------+
+    /// |              query bytes              |
+    /// +                                       +
+    /// |                 ...                   |
+    /// +---------+---------+---------+---------+
+    /// | values count (2)  |  next query ...   |
+    /// +---------+---------+---------+---------+
+    /// |  consistency (2)  | flags(1)|
+    /// +---------+---------+---------+
+    /// ```
+    fn to_bytes(&self) -> Result<Vec<u8>, NativeError> {
+        let mut bytes = vec![self.batch_type as u8];
+
+        let count =
+            u16::try_from(self.queries.len()).map_err(|_| NativeError::SerializationError)?;
+        bytes.extend_from_slice(&count.to_be_bytes());
+
+        for query in &self.queries {
+            bytes.push(QUERY_KIND_STRING);
+            let query_len =
+                u32::try_from(query.len()).map_err(|_| NativeError::SerializationError)?;
+            bytes.extend_from_slice(&query_len.to_be_bytes());
+            bytes.extend_from_slice(query.as_bytes());
+            // Las queries no tienen valores
+            bytes.extend_from_slice(&0u16.to_be_bytes());
+        }
+
+        bytes.extend_from_slice(&(self.consistency.to_code()? as u16).to_be_bytes());
+        // Sin serial consistency ni timestamp
+        bytes.push(0x00);
+
+        Ok(bytes)
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, NativeError> {
+        let mut cursor = Cursor::new(bytes);
+
+        let batch_type = BatchType::from_byte(read_u8(&mut cursor)?)?;
+        let count = read_u16(&mut cursor)?;
+
+        // La cantidad la manda el cliente: no se reserva mas de lo que entra en el frame
+        let mut queries =
+            Vec::with_capacity((count as usize).min(remaining(&cursor) / MIN_QUERY_LENGTH));
+        for _ in 0..count {
+            // Los ids de prepared statements no estan soportados
+            if read_u8(&mut cursor)? != QUERY_KIND_STRING {
+                return Err(NativeError::InvalidVariant);
+            }
+
+            let query_len = read_u32(&mut cursor)? as usize;
+            if query_len > remaining(&cursor) {
+                return Err(NativeError::CursorError);
+            }
+            let mut query_bytes = vec![0u8; query_len];
+            cursor
+                .read_exact(&mut query_bytes)
+                .map_err(|_| NativeError::CursorError)?;
+            queries.push(
+                String::from_utf8(query_bytes).map_err(|_| NativeError::DeserializationError)?,
+            );
+
+            if read_u16(&mut cursor)? != 0 {
+                return Err(NativeError::DeserializationError);
+            }
+        }
+
+        let consistency = Consistency::from_code(read_u16(&mut cursor)?)?;
+        // Los flags piden parametros opcionales que no se usan
+        let _flags = read_u8(&mut cursor)?;
+
+        Ok(Batch {
+            batch_type,
+            queries,
+            consistency,
+        })
+    }
+}
+
+// Bytes del frame que todavia no se leyeron
+fn remaining(cursor: &Cursor<&[u8]>) -> usize {
+    cursor
+        .get_ref()
+        .len()
+        .saturating_sub(cursor.position() as usize)
+}
+
+fn read_u8(cursor: &mut Cursor<&[u8]>) -> Result<u8, NativeError> {
+    let mut byte = [0u8];
+    cursor
+        .read_exact(&mut byte)
+        .map_err(|_| NativeError::CursorError)?;
+    Ok(byte[0])
+}
+
+fn read_u16(cursor: &mut Cursor<&[u8]>) -> Result<u16, NativeError> {
+    let mut bytes = [0u8; 2];
+    cursor
+        .read_exact(&mut bytes)
+        .map_err(|_| NativeError::CursorError)?;
+    Ok(u16::from_be_bytes(bytes))
+}
+
+fn read_u32(cursor: &mut Cursor<&[u8]>) -> Result<u32, NativeError> {
+    let mut bytes = [0u8; 4];
+    cursor
+        .read_exact(&mut bytes)
+        .map_err(|_| NativeError::CursorError)?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_to_bytes_ok() {
+        let batch = Batch::new(
+            BatchType::Unlogged,
+            vec!["INSERT 1".to_string(), "DELETE".to_string()],
+            Consistency::Quorum,
+        );
+
+        // Tipo (Unlogged) y cantidad de queries
+        let mut expected_bytes: Vec<u8> = vec![0x01, 0x00, 0x02];
+        // Primera query: kind, longitud, "INSERT 1", sin valores
+        expected_bytes.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x08]);
+        expected_bytes.extend_from_slice(b"INSERT 1");
+        expected_bytes.extend_from_slice(&[0x00, 0x00]);
+        // Segunda query: kind, longitud, "DELETE", sin valores
+        expected_bytes.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x06]);
+        expected_bytes.extend_from_slice(b"DELETE");
+        expected_bytes.extend_from_slice(&[0x00, 0x00]);
+        // Consistency (Quorum) y flags
+        expected_bytes.extend_from_slice(&[0x00, 0x04, 0x00]);
+
+        assert_eq!(batch.to_bytes().unwrap(), expected_bytes);
+    }
+
+    #[test]
+    fn batch_from_bytes_keeps_every_query_and_the_type() {
+        for batch_type in [BatchType::Logged, BatchType::Unlogged, BatchType::Counter] {
+            let batch = Batch::new(
+                batch_type,
+                vec![
+                    "INSERT INTO sky.flights (id) VALUES ('1')".to_string(),
+                    "UPDATE sky.flights SET origin = 'EZE' WHERE id = '2'".to_string(),
+                    "DELETE FROM sky.flights WHERE id = '3'".to_string(),
+                ],
+                Consistency::One,
+            );
+
+            let bytes = batch.to_bytes().unwrap();
+            assert_eq!(Batch::from_bytes(&bytes).unwrap(), batch);
+        }
+    }
+
+    #[test]
+    fn batch_with_prepared_statements_or_values_is_rejected() {
+        let bytes = Batch::new(
+            BatchType::Logged,
+            vec!["INSERT".to_string()],
+            Consistency::One,
+        )
+        .to_bytes()
+        .unwrap();
+
+        // El id de un prepared statement
+        let mut prepared = bytes.clone();
+        prepared[3] = 0x01;
+        assert!(Batch::from_bytes(&prepared).is_err());
+
+        // Un valor para la query
+        let mut with_values = bytes.clone();
+        with_values[15] = 0x01;
+        assert!(Batch::from_bytes(&with_values).is_err());
+
+        assert!(Batch::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn batch_with_counts_longer_than_the_frame_is_rejected() {
+        // Dice tener 65535 queries y trae una sola
+        let mut bytes = Batch::new(
+            BatchType::Logged,
+            vec!["INSERT".to_string()],
+            Consistency::One,
+        )
+        .to_bytes()
+        .unwrap();
+        bytes[1..3].copy_from_slice(&u16::MAX.to_be_bytes());
+        assert!(Batch::from_bytes(&bytes).is_err());
+
+        // Una query que dice medir 4 GB
+        let mut bytes: Vec<u8> = vec![0x00, 0x00, 0x01, QUERY_KIND_STRING];
+        bytes.extend_from_slice(&u32::MAX.to_be_bytes());
+        bytes.extend_from_slice(b"INSERT");
+        assert!(matches!(
+            Batch::from_bytes(&bytes),
+            Err(NativeError::CursorError)
+        ));
+    }
+}
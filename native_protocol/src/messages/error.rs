@@ -2,7 +2,7 @@ use std::io::Read;
 
 use crate::{errors::NativeError, Serializable};
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum ErrorCode {
     ServerError = 0x0000,
     ProtocolError = 0x000A,
@@ -53,6 +53,8 @@ impl ErrorCode {
 #[derive(Debug, PartialEq)]
 pub struct WriteTimeout;
 #[derive(Debug, PartialEq)]
+pub struct ReadTimeout;
+#[derive(Debug, PartialEq)]
 pub struct UnavailableException;
 
 #[derive(Debug, PartialEq)]
@@ -61,6 +63,8 @@ pub enum Error {
     ServerError(String),
     /// Timeout exception during a write request.
     WriteTimeout(String, WriteTimeout),
+    /// Timeout exception during a read request.
+    ReadTimeout(String, ReadTimeout),
     /// Some client message triggered a protocol violation (for instance
     /// a QUERY message is sent before a STARTUP one has been sent).
     ProtocolError(String),
@@ -73,6 +77,11 @@ pub enum Error {
     /// The request was a read request but the coordinator node is
     /// bootstrapping.
     IsBootstrapping(String),
+    /// The query is invalid (for instance it cannot be parsed, or it uses
+    /// a keyspace or a table that does not exist).
+    Invalid(String),
+    /// The query attempted to create a keyspace or a table that already exists.
+    AlreadyExists(String),
 }
 
 impl Serializable for Error {
@@ -88,6 +97,10 @@ impl Serializable for Error {
                 bytes.extend_from_slice(&ErrorCode::WriteTimeout.to_u32().to_be_bytes());
                 bytes.extend_from_slice(message.as_bytes());
             }
+            Error::ReadTimeout(message, _) => {
+                bytes.extend_from_slice(&ErrorCode::ReadTimeout.to_u32().to_be_bytes());
+                bytes.extend_from_slice(message.as_bytes());
+            }
             Error::ProtocolError(message) => {
                 bytes.extend_from_slice(&ErrorCode::ProtocolError.to_u32().to_be_bytes());
                 bytes.extend_from_slice(message.as_bytes());
@@ -104,6 +117,14 @@ impl Serializable for Error {
                 bytes.extend_from_slice(&ErrorCode::IsBootstrapping.to_u32().to_be_bytes());
                 bytes.extend_from_slice(message.as_bytes());
             }
+            Error::Invalid(message) => {
+                bytes.extend_from_slice(&ErrorCode::Invalid.to_u32().to_be_bytes());
+                bytes.extend_from_slice(message.as_bytes());
+            }
+            Error::AlreadyExists(message) => {
+                bytes.extend_from_slice(&ErrorCode::AlreadyExists.to_u32().to_be_bytes());
+                bytes.extend_from_slice(message.as_bytes());
+            }
         }
 
         Ok(bytes)
@@ -127,20 +148,62 @@ impl Serializable for Error {
         let message =
             String::from_utf8(message_bytes).map_err(|_| NativeError::DeserializationError)?;
 
+        Error::from_code(code, message)
+    }
+}
+
+impl Error {
+    /// Builds the error that corresponds to `code`, carrying `message`.
+    ///
+    /// Returns `NativeError::InvalidVariant` if there is no variant for the code.
+    pub fn from_code(code: ErrorCode, message: String) -> std::result::Result<Error, NativeError> {
         let error = match code {
             ErrorCode::ServerError => Error::ServerError(message),
             ErrorCode::WriteTimeout => Error::WriteTimeout(message, WriteTimeout),
+            ErrorCode::ReadTimeout => Error::ReadTimeout(message, ReadTimeout),
             ErrorCode::ProtocolError => Error::ProtocolError(message),
             ErrorCode::Overloaded => Error::Overloaded(message),
             ErrorCode::UnavailableException => {
                 Error::UnavailableException(message, UnavailableException)
             }
             ErrorCode::IsBootstrapping => Error::IsBootstrapping(message),
+            ErrorCode::Invalid => Error::Invalid(message),
+            ErrorCode::AlreadyExists => Error::AlreadyExists(message),
             _ => return Err(NativeError::InvalidVariant),
         };
 
         Ok(error)
     }
+
+    /// Returns the code sent to the client for this error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::ServerError(_) => ErrorCode::ServerError,
+            Error::WriteTimeout(_, _) => ErrorCode::WriteTimeout,
+            Error::ReadTimeout(_, _) => ErrorCode::ReadTimeout,
+            Error::ProtocolError(_) => ErrorCode::ProtocolError,
+            Error::Overloaded(_) => ErrorCode::Overloaded,
+            Error::UnavailableException(_, _) => ErrorCode::UnavailableException,
+            Error::IsBootstrapping(_) => ErrorCode::IsBootstrapping,
+            Error::Invalid(_) => ErrorCode::Invalid,
+            Error::AlreadyExists(_) => ErrorCode::AlreadyExists,
+        }
+    }
+
+    /// Returns the message that describes the error.
+    pub fn message(&self) -> &str {
+        match self {
+            Error::ServerError(message)
+            | Error::WriteTimeout(message, _)
+            | Error::ReadTimeout(message, _)
+            | Error::ProtocolError(message)
+            | Error::Overloaded(message)
+            | Error::UnavailableException(message, _)
+            | Error::IsBootstrapping(message)
+            | Error::Invalid(message)
+            | Error::AlreadyExists(message) => message,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -193,4 +256,18 @@ mod tests {
 
         assert_eq!(error, Error::ProtocolError("Protocol error".to_string()));
     }
+
+    #[test]
+    fn test_request_errors_round_trip() {
+        for error in [
+            Error::Invalid("Keyspace sky does not exist".to_string()),
+            Error::AlreadyExists("Table sky.flights already exists".to_string()),
+            Error::ReadTimeout("2 of 3 replicas answered".to_string(), ReadTimeout),
+            Error::WriteTimeout("1 of 3 replicas answered".to_string(), WriteTimeout),
+        ] {
+            let bytes = error.to_bytes().unwrap();
+            assert_eq!(&bytes[..4], &error.code().to_u32().to_be_bytes());
+            assert_eq!(Error::from_bytes(&bytes).unwrap(), error);
+        }
+    }
 }
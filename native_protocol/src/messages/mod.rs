@@ -1,4 +1,5 @@
 pub mod auth;
+pub mod batch;
 pub mod error;
 pub mod query;
 pub mod result;
@@ -2,7 +2,7 @@ use std::io::Read;
 
 use crate::{errors::NativeError, Serializable};
 
-enum ConsistencyCode {
+pub(crate) enum ConsistencyCode {
     Any = 0x0000,
     One = 0x0001,
     Two = 0x0002,
@@ -67,7 +67,7 @@ impl Consistency {
         }
     }
 
-    fn to_code(&self) -> Result<ConsistencyCode, NativeError> {
+    pub(crate) fn to_code(&self) -> Result<ConsistencyCode, NativeError> {
         let consistency_code = match self {
             Consistency::Any => ConsistencyCode::Any,
             Consistency::One => ConsistencyCode::One,
@@ -85,7 +85,7 @@ impl Consistency {
         Ok(consistency_code)
     }
 
-    fn from_code(consistency_code: u16) -> Result<Self, NativeError> {
+    pub(crate) fn from_code(consistency_code: u16) -> Result<Self, NativeError> {
         let consistency = match consistency_code {
             0x0000 => Consistency::Any,
             0x0001 => Consistency::One,
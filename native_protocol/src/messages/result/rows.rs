@@ -34,7 +34,7 @@ enum ColumnTypeCode {
     Tuple = 0x0031,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ColumnType {
     Custom(String),
     Ascii,
@@ -238,7 +238,7 @@ impl OptionSerializable for ColumnType {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ColumnValue {
     Custom(String),
     Ascii(String), // this is actually an ascii string
@@ -268,9 +268,62 @@ pub enum ColumnValue {
         fields: Vec<(String, ColumnType)>,
     }, */
     Tuple(Vec<ColumnValue>),
+    /// A column without a value. It is sent as a `[bytes]` of length -1, so it has no
+    /// contents of its own.
+    Null,
 }
 
+/// How a missing value is written in the rows stored by the nodes, so it is never mistaken
+/// for an empty text.
+pub const STORAGE_NULL: &str = "\\N";
+
 impl ColumnValue {
+    /// The value as it is written in the rows stored by the nodes, where the values of a row
+    /// are separated by `,` and the row ends with `;` and its timestamp.
+    ///
+    /// - Texts escape `\`, `,`, `;` and line breaks with a `\`, so the text `\N` is never
+    ///   read back as [`STORAGE_NULL`]. See [`unescape_storage_text`].
+    /// - `Blob` and `Varint` are their bytes in hexadecimal after `0x`, and a `Decimal` is its
+    ///   scale, a `:` and its unscaled bytes in the same form.
+    /// - Numbers, booleans, UUIDs and addresses are written as Rust displays them, which
+    ///   parses back to the same value.
+    ///
+    /// # Errors
+    /// Collections and tuples are never stored, so they return `NativeError::InvalidVariant`.
+    pub fn to_storage_string(&self) -> std::result::Result<String, NativeError> {
+        let storage_string = match self {
+            ColumnValue::Custom(text) | ColumnValue::Ascii(text) | ColumnValue::Varchar(text) => {
+                escape_storage_text(text)
+            }
+            ColumnValue::Bigint(value) | ColumnValue::Counter(value) => value.to_string(),
+            ColumnValue::Blob(bytes) | ColumnValue::Varint(bytes) => encode_storage_hex(bytes),
+            ColumnValue::Boolean(value) => value.to_string(),
+            ColumnValue::Decimal { scale, unscaled } => {
+                format!("{}:{}", scale, encode_storage_hex(unscaled))
+            }
+            ColumnValue::Double(value) => value.to_string(),
+            ColumnValue::Float(value) => value.to_string(),
+            ColumnValue::Int(value) => value.to_string(),
+            ColumnValue::Timestamp(value) => value.to_string(),
+            ColumnValue::Uuid(uuid) | ColumnValue::Timeuuid(uuid) => uuid.to_string(),
+            ColumnValue::Inet(address) => address.to_string(),
+            ColumnValue::Null => STORAGE_NULL.to_string(),
+            ColumnValue::List(_) | ColumnValue::Set(_) | ColumnValue::Tuple(_) => {
+                return Err(NativeError::InvalidVariant)
+            }
+        };
+        Ok(storage_string)
+    }
+
+    /// The value as the `[bytes]` of a row or collection: `Null` is a negative length, any
+    /// other value is its length followed by its contents.
+    pub fn to_value_bytes(&self) -> std::result::Result<Vec<u8>, NativeError> {
+        match self {
+            ColumnValue::Null => Bytes::None.to_bytes(),
+            value => Bytes::Vec(value.to_bytes()?).to_bytes(),
+        }
+    }
+
     pub fn to_bytes(&self) -> std::result::Result<Vec<u8>, NativeError> {
         let mut bytes = Vec::new();
 
@@ -337,8 +390,7 @@ impl ColumnValue {
                 bytes.extend_from_slice(number_of_elements.to_be_bytes().as_slice());
 
                 for value in inner_value {
-                    let value_bytes = Bytes::Vec(value.to_bytes()?).to_bytes()?;
-                    bytes.extend_from_slice(&value_bytes);
+                    bytes.extend_from_slice(&value.to_value_bytes()?);
                 }
             }
             /* ColumnValue::Map(key_value, value_value) => {
@@ -351,8 +403,7 @@ impl ColumnValue {
                 bytes.extend_from_slice(number_of_elements.to_be_bytes().as_slice());
 
                 for value in inner_value {
-                    let value_bytes = Bytes::Vec(value.to_bytes()?).to_bytes()?;
-                    bytes.extend_from_slice(&value_bytes);
+                    bytes.extend_from_slice(&value.to_value_bytes()?);
                 }
             }
             // A UDT value is composed of successive [bytes] values, one for each field of the UDT
@@ -367,10 +418,11 @@ impl ColumnValue {
                 bytes.extend_from_slice(number_of_elements.to_be_bytes().as_slice());
 
                 for value in inner_value {
-                    let value_bytes = Bytes::Vec(value.to_bytes()?).to_bytes()?;
-                    bytes.extend_from_slice(&value_bytes);
+                    bytes.extend_from_slice(&value.to_value_bytes()?);
                 }
             }
+            // Sin contenido: el largo negativo lo escribe `to_value_bytes`
+            ColumnValue::Null => {}
         }
         Ok(bytes)
     }
@@ -530,6 +582,86 @@ impl ColumnValue {
     }
 }
 
+/// Escapes a text to be stored as a value of a row, see [`ColumnValue::to_storage_string`].
+pub fn escape_storage_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ',' => escaped.push_str("\\,"),
+            ';' => escaped.push_str("\\;"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reverts [`escape_storage_text`]. A `\` before any other character is kept as is, so texts
+/// stored before they were escaped read back the same.
+pub fn unescape_storage_text(stored: &str) -> String {
+    let mut text = String::with_capacity(stored.len());
+    let mut chars = stored.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => text.push('\n'),
+            Some('r') => text.push('\r'),
+            Some(escaped @ ('\\' | ',' | ';')) => text.push(escaped),
+            Some(other) => {
+                text.push('\\');
+                text.push(other);
+            }
+            None => text.push('\\'),
+        }
+    }
+    text
+}
+
+/// Splits the values of a stored row on the `,` that are not escaped.
+pub fn split_storage_values(row: &str) -> Vec<&str> {
+    let mut values = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in row.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ',' => {
+                values.push(&row[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    values.push(&row[start..]);
+    values
+}
+
+/// Writes bytes as stored in a row: `0x` followed by two hexadecimal digits per byte.
+pub fn encode_storage_hex(bytes: &[u8]) -> String {
+    let digits: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("0x{}", digits)
+}
+
+/// Reverts [`encode_storage_hex`]; the `0x` is optional.
+pub fn decode_storage_hex(stored: &str) -> std::result::Result<Vec<u8>, NativeError> {
+    let digits = stored.strip_prefix("0x").unwrap_or(stored);
+    if !digits.len().is_multiple_of(2) || !digits.is_ascii() {
+        return Err(NativeError::DeserializationError);
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| NativeError::DeserializationError)
+        })
+        .collect()
+}
+
 fn list_from_cursor(
     cursor: &mut std::io::Cursor<&[u8]>,
     col_type: &ColumnType,
@@ -563,8 +695,87 @@ fn list_from_cursor(
     elements
 }
 
-// key: column name, value: column value
-type Row = BTreeMap<String, ColumnValue>;
+/// A row of a `Rows` result.
+///
+/// The values are kept in the order of the columns of the result (the order of the
+/// projection of the `SELECT`), so iterating a row gives the columns in the order the
+/// client asked for them. They can also be looked up by column name.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Row {
+    values: Vec<(String, ColumnValue)>,
+}
+
+impl Row {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the value of the next column of the row.
+    pub fn push(&mut self, column: String, value: ColumnValue) {
+        self.values.push((column, value));
+    }
+
+    /// Returns the value of the column named `column`, if the row has it.
+    pub fn get(&self, column: &str) -> Option<&ColumnValue> {
+        self.values
+            .iter()
+            .find(|(name, _)| name == column)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the name and value of the column at `index`.
+    pub fn get_at(&self, index: usize) -> Option<(&str, &ColumnValue)> {
+        self.values
+            .get(index)
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
+    /// Names and values of the columns, in the order of the result.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ColumnValue)> {
+        self.values
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
+    /// Names of the columns, in the order of the result.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.values.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Values of the columns, in the order of the result.
+    pub fn values(&self) -> impl Iterator<Item = &ColumnValue> {
+        self.values.iter().map(|(_, value)| value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl FromIterator<(String, ColumnValue)> for Row {
+    fn from_iter<T: IntoIterator<Item = (String, ColumnValue)>>(iter: T) -> Self {
+        Self {
+            values: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<const N: usize> From<[(String, ColumnValue); N]> for Row {
+    fn from(values: [(String, ColumnValue); N]) -> Self {
+        values.into_iter().collect()
+    }
+}
+
+/// The columns of the map end up in alphabetical order.
+impl From<BTreeMap<String, ColumnValue>> for Row {
+    fn from(values: BTreeMap<String, ColumnValue>) -> Self {
+        values.into_iter().collect()
+    }
+}
 
 #[derive(Debug, PartialEq)]
 /// Indicates a set of rows.
@@ -595,13 +806,13 @@ impl Serializable for Rows {
         bytes.extend_from_slice(&self.rows_count.to_be_bytes());
 
         for row in &self.rows_content {
-            for col in &self.metadata.col_spec_i {
-                let col_name = &col.name;
-
-                let value = row.get(col_name).unwrap();
-                let value_bytes = Bytes::Vec(value.to_bytes()?).to_bytes()?;
-
-                bytes.extend_from_slice(&value_bytes);
+            for (index, col) in self.metadata.col_spec_i.iter().enumerate() {
+                // Cada valor va en la posicion de su columna; si no coincide se busca por nombre
+                let value = match row.get_at(index) {
+                    Some((name, value)) if name == col.name => value,
+                    _ => row.get(&col.name).ok_or(NativeError::SerializationError)?,
+                };
+                bytes.extend_from_slice(&value.to_value_bytes()?);
             }
         }
 
@@ -617,20 +828,16 @@ impl Serializable for Rows {
 
         let mut rows_content = Vec::new();
         for _ in 0..rows_count {
-            let mut row = BTreeMap::new();
+            let mut row = Row::new();
             for col_spec in &metadata.col_spec_i {
-                let value_bytes = Bytes::from_bytes(&mut cursor)?;
-
-                let bytes_ = if let Bytes::Vec(bytes) = value_bytes {
-                    bytes
-                } else {
-                    Err(NativeError::DeserializationError)?
+                let value = match Bytes::from_bytes(&mut cursor)? {
+                    Bytes::Vec(bytes_) => {
+                        let mut cursor2 = Cursor::new(bytes_.as_slice());
+                        ColumnValue::from_bytes(&mut cursor2, &col_spec.type_)?
+                    }
+                    Bytes::None => ColumnValue::Null,
                 };
-
-                let mut cursor2 = Cursor::new(bytes_.as_slice());
-
-                let value = ColumnValue::from_bytes(&mut cursor2, &col_spec.type_)?;
-                row.insert(col_spec.name.clone(), value);
+                row.push(col_spec.name.clone(), value);
             }
             rows_content.push(row);
         }
@@ -645,7 +852,6 @@ impl Serializable for Rows {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::BTreeMap;
     use std::io::Cursor;
 
     use crate::messages::result::metadata::{ColumnSpec, MetadataFlags, TableSpec};
@@ -656,7 +862,10 @@ mod tests {
         types::{Bytes, CassandraString, Int},
     };
 
-    use super::{ColumnType, ColumnValue};
+    use super::{
+        decode_storage_hex, encode_storage_hex, escape_storage_text, split_storage_values,
+        unescape_storage_text, ColumnType, ColumnValue, Row,
+    };
 
     #[test]
     fn blob_to_column_value() {
@@ -771,7 +980,7 @@ mod tests {
                 }],
             },
             rows_count: Int::from(2),
-            rows_content: vec![BTreeMap::from([(
+            rows_content: vec![Row::from([(
                 "test_column".to_string(),
                 ColumnValue::Int(1),
             )])],
@@ -818,8 +1027,8 @@ mod tests {
             },
             rows_count: Int::from(2),
             rows_content: vec![
-                BTreeMap::from([("test_column".to_string(), ColumnValue::Int(1))]),
-                BTreeMap::from([("test_column".to_string(), ColumnValue::Int(2))]),
+                Row::from([("test_column".to_string(), ColumnValue::Int(1))]),
+                Row::from([("test_column".to_string(), ColumnValue::Int(2))]),
             ],
         };
 
@@ -849,7 +1058,7 @@ mod tests {
                 }],
             },
             rows_count: Int::from(2),
-            rows_content: vec![BTreeMap::from([(
+            rows_content: vec![Row::from([(
                 "test_column".to_string(),
                 ColumnValue::Int(1),
             )])],
@@ -880,11 +1089,11 @@ mod tests {
             ("name".to_string(), ColumnType::Varchar),
         ];
         let rows = vec![
-            BTreeMap::from([
+            Row::from([
                 ("age".to_string(), ColumnValue::Int(1)),
                 ("name".to_string(), ColumnValue::Varchar("John".to_string())),
             ]),
-            BTreeMap::from([
+            Row::from([
                 ("age".to_string(), ColumnValue::Int(2)),
                 ("name".to_string(), ColumnValue::Varchar("Doe".to_string())),
             ]),
@@ -908,7 +1117,7 @@ mod tests {
             ("weight".to_string(), ColumnType::Int),
         ];
         let rows = vec![
-            BTreeMap::from([
+            Row::from([
                 (
                     "user_id".to_string(),
                     ColumnValue::Uuid(uuid::Uuid::from_u128(0x1234567890abcdef1234567890abcdef)),
@@ -920,7 +1129,7 @@ mod tests {
                 ),
                 ("weight".to_string(), ColumnValue::Int(70)),
             ]),
-            BTreeMap::from([
+            Row::from([
                 (
                     "user_id".to_string(),
                     ColumnValue::Uuid(uuid::Uuid::from_u128(0x1234567890abcdef1234567890cdefab)),
@@ -952,7 +1161,7 @@ mod tests {
             ("email2".to_string(), ColumnType::Ascii),
         ];
         let rows = vec![
-            BTreeMap::from([
+            Row::from([
                 ("age".to_string(), ColumnValue::Int(25)),
                 ("name".to_string(), ColumnValue::Ascii("John".to_string())),
                 (
@@ -964,7 +1173,7 @@ mod tests {
                     ColumnValue::Ascii("marston@hotmail.com".to_string()),
                 ),
             ]),
-            BTreeMap::from([
+            Row::from([
                 ("age".to_string(), ColumnValue::Int(30)),
                 ("name".to_string(), ColumnValue::Ascii("Bill".to_string())),
                 (
@@ -986,4 +1195,79 @@ mod tests {
 
         assert_eq!(rows, expected_rows)
     }
+
+    #[test]
+    fn rows_keep_the_order_of_the_columns() {
+        let cols = vec![
+            ("name".to_string(), ColumnType::Ascii),
+            ("age".to_string(), ColumnType::Int),
+        ];
+        // La fila se arma en otro orden que el de las columnas
+        let row = Row::from([
+            ("age".to_string(), ColumnValue::Int(25)),
+            ("name".to_string(), ColumnValue::Ascii("John".to_string())),
+        ]);
+
+        let bytes = Rows::new(cols, vec![row]).to_bytes().unwrap();
+        let rows = Rows::from_bytes(&bytes).unwrap();
+
+        let names: Vec<&str> = rows.rows_content[0].keys().collect();
+        assert_eq!(names, vec!["name", "age"]);
+        assert_eq!(
+            rows.rows_content[0].get_at(1),
+            Some(("age", &ColumnValue::Int(25)))
+        );
+    }
+
+    #[test]
+    fn rows_with_a_missing_column_cannot_be_serialized() {
+        let cols = vec![
+            ("name".to_string(), ColumnType::Ascii),
+            ("age".to_string(), ColumnType::Int),
+        ];
+        let row = Row::from([("age".to_string(), ColumnValue::Int(25))]);
+
+        assert!(Rows::new(cols, vec![row]).to_bytes().is_err());
+    }
+
+    #[test]
+    fn null_values_are_sent_without_contents_and_read_back_as_null() {
+        let cols = vec![
+            ("name".to_string(), ColumnType::Ascii),
+            ("nickname".to_string(), ColumnType::Ascii),
+        ];
+        let row = Row::from([
+            ("name".to_string(), ColumnValue::Ascii(String::new())),
+            ("nickname".to_string(), ColumnValue::Null),
+        ]);
+
+        assert_eq!(ColumnValue::Null.to_value_bytes().unwrap(), vec![0xFF; 4]);
+        let bytes = Rows::new(cols, vec![row]).to_bytes().unwrap();
+        let rows = Rows::from_bytes(&bytes).unwrap();
+
+        let row = &rows.rows_content[0];
+        assert_eq!(row.get("name"), Some(&ColumnValue::Ascii(String::new())));
+        assert_eq!(row.get("nickname"), Some(&ColumnValue::Null));
+    }
+
+    #[test]
+    fn storage_values_split_only_on_unescaped_commas() {
+        let texts = ["a,b", "c\\", "", "d;e"];
+        let row: Vec<String> = texts.iter().map(|text| escape_storage_text(text)).collect();
+        let row = row.join(",");
+
+        let values = split_storage_values(&row);
+        assert_eq!(values.len(), texts.len());
+        for (value, text) in values.into_iter().zip(texts) {
+            assert_eq!(unescape_storage_text(value), text);
+        }
+    }
+
+    #[test]
+    fn storage_hex_round_trips() {
+        let bytes = [0x00, 0x0F, 0xF0, 0xFF];
+        assert_eq!(encode_storage_hex(&bytes), "0x000ff0ff");
+        assert_eq!(decode_storage_hex("0x000ff0ff").unwrap(), bytes);
+        assert!(decode_storage_hex("0xabc").is_err());
+    }
 }
@@ -0,0 +1,262 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+
+use crate::errors::NodeError;
+
+/// Default amount of messages that can be waiting to be processed on a single
+/// internode connection before the overflow policy kicks in.
+pub const DEFAULT_INTERNODE_QUEUE_CAPACITY: usize = 128;
+
+/// What to do when a message arrives and the queue is already full.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverflowPolicy {
+    /// The producer waits until the consumer frees a slot.
+    Block,
+    /// The oldest queued message accepted by the eviction predicate is discarded
+    /// to make room. If no queued message can be evicted, the producer blocks.
+    DropOldest,
+    /// The new message is handed back to the producer without waiting.
+    Reject,
+}
+
+struct QueueState<T> {
+    items: VecDeque<T>,
+    closed: bool,
+}
+
+/// A FIFO queue with a fixed capacity shared between producer and consumer threads.
+///
+/// Every push and pop updates a shared depth counter, so several queues can report
+/// their combined depth (e.g. all the internode connections of a node).
+pub struct BoundedQueue<T> {
+    state: Mutex<QueueState<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    depth: Arc<AtomicUsize>,
+}
+
+impl<T> BoundedQueue<T> {
+    /// Creates an empty queue that holds at most `capacity` items (at least one).
+    pub fn new(capacity: usize, depth: Arc<AtomicUsize>) -> Self {
+        BoundedQueue {
+            state: Mutex::new(QueueState {
+                items: VecDeque::new(),
+                closed: false,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            capacity: capacity.max(1),
+            depth,
+        }
+    }
+
+    /// Adds `item` to the back of the queue, applying `policy` if the queue is full.
+    ///
+    /// `evictable` decides which queued items may be discarded under `OverflowPolicy::DropOldest`.
+    ///
+    /// # Returns
+    /// - `Ok(Some(item))` with the discarded item if one had to be dropped, or with `item`
+    ///   itself if the queue was full under `OverflowPolicy::Reject`.
+    /// - `Ok(None)` if nothing was dropped.
+    /// - `Err(NodeError::OtherError)` if the queue was closed by the consumer.
+    pub fn push<F>(
+        &self,
+        item: T,
+        policy: OverflowPolicy,
+        evictable: F,
+    ) -> Result<Option<T>, NodeError>
+    where
+        F: Fn(&T) -> bool,
+    {
+        let mut state = self.state.lock()?;
+        let mut dropped = None;
+
+        while state.items.len() >= self.capacity && !state.closed {
+            if policy == OverflowPolicy::DropOldest {
+                if let Some(pos) = state.items.iter().position(&evictable) {
+                    dropped = state.items.remove(pos);
+                    self.depth.fetch_sub(1, Ordering::SeqCst);
+                    break;
+                }
+            }
+            if policy == OverflowPolicy::Reject {
+                return Ok(Some(item));
+            }
+            state = self.not_full.wait(state)?;
+        }
+
+        if state.closed {
+            return Err(NodeError::OtherError);
+        }
+
+        state.items.push_back(item);
+        self.depth.fetch_add(1, Ordering::SeqCst);
+        self.not_empty.notify_one();
+        Ok(dropped)
+    }
+
+    /// Takes the item at the front of the queue, waiting until one is available.
+    ///
+    /// Returns `None` once the queue is closed and every pending item was consumed.
+    pub fn pop(&self) -> Result<Option<T>, NodeError> {
+        let mut state = self.state.lock()?;
+        loop {
+            if let Some(item) = state.items.pop_front() {
+                self.depth.fetch_sub(1, Ordering::SeqCst);
+                self.not_full.notify_one();
+                return Ok(Some(item));
+            }
+            if state.closed {
+                return Ok(None);
+            }
+            state = self.not_empty.wait(state)?;
+        }
+    }
+
+    /// Closes the queue, waking up any thread blocked on it.
+    ///
+    /// Items still queued can be popped, but no new item is accepted.
+    pub fn close(&self) -> Result<(), NodeError> {
+        let mut state = self.state.lock()?;
+        state.closed = true;
+        self.not_empty.notify_all();
+        self.not_full.notify_all();
+        Ok(())
+    }
+
+    /// Amount of items currently waiting in this queue.
+    pub fn len(&self) -> Result<usize, NodeError> {
+        Ok(self.state.lock()?.items.len())
+    }
+
+    /// Returns `true` if no item is waiting in this queue.
+    pub fn is_empty(&self) -> Result<bool, NodeError> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Maximum amount of items the queue holds before applying the overflow policy.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    #[derive(Debug, PartialEq)]
+    enum Msg {
+        Syn(u32),
+        Query(u32),
+    }
+
+    fn is_syn(msg: &Msg) -> bool {
+        matches!(msg, Msg::Syn(_))
+    }
+
+    #[test]
+    fn test_drop_oldest_syn_when_full() {
+        let depth = Arc::new(AtomicUsize::new(0));
+        let queue = BoundedQueue::new(3, Arc::clone(&depth));
+
+        queue
+            .push(Msg::Query(1), OverflowPolicy::DropOldest, is_syn)
+            .unwrap();
+        queue
+            .push(Msg::Syn(1), OverflowPolicy::DropOldest, is_syn)
+            .unwrap();
+        queue
+            .push(Msg::Syn(2), OverflowPolicy::DropOldest, is_syn)
+            .unwrap();
+
+        // La cola esta llena: el syn mas viejo se descarta y la query se conserva
+        let dropped = queue
+            .push(Msg::Syn(3), OverflowPolicy::DropOldest, is_syn)
+            .unwrap();
+
+        assert_eq!(dropped, Some(Msg::Syn(1)));
+        assert_eq!(queue.len().unwrap(), 3);
+        assert_eq!(depth.load(Ordering::SeqCst), 3);
+        assert_eq!(queue.pop().unwrap(), Some(Msg::Query(1)));
+        assert_eq!(queue.pop().unwrap(), Some(Msg::Syn(2)));
+        assert_eq!(queue.pop().unwrap(), Some(Msg::Syn(3)));
+        assert!(queue.is_empty().unwrap());
+        assert_eq!(depth.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_queue_never_grows_past_capacity() {
+        let depth = Arc::new(AtomicUsize::new(0));
+        let queue = BoundedQueue::new(4, Arc::clone(&depth));
+
+        for i in 0..100 {
+            queue
+                .push(Msg::Syn(i), OverflowPolicy::DropOldest, is_syn)
+                .unwrap();
+            assert!(queue.len().unwrap() <= queue.capacity());
+        }
+
+        assert_eq!(depth.load(Ordering::SeqCst), 4);
+        assert_eq!(queue.pop().unwrap(), Some(Msg::Syn(96)));
+    }
+
+    #[test]
+    fn test_block_policy_waits_for_consumer() {
+        let depth = Arc::new(AtomicUsize::new(0));
+        let queue = Arc::new(BoundedQueue::new(1, depth));
+        queue
+            .push(Msg::Query(1), OverflowPolicy::Block, is_syn)
+            .unwrap();
+
+        let producer_queue = Arc::clone(&queue);
+        let producer = thread::spawn(move || {
+            producer_queue
+                .push(Msg::Query(2), OverflowPolicy::Block, is_syn)
+                .unwrap()
+        });
+
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(queue.len().unwrap(), 1);
+        assert_eq!(queue.pop().unwrap(), Some(Msg::Query(1)));
+
+        assert_eq!(producer.join().unwrap(), None);
+        assert_eq!(queue.pop().unwrap(), Some(Msg::Query(2)));
+    }
+
+    #[test]
+    fn test_reject_policy_hands_back_the_new_item_when_full() {
+        let depth = Arc::new(AtomicUsize::new(0));
+        let queue = BoundedQueue::new(1, Arc::clone(&depth));
+        queue
+            .push(Msg::Query(1), OverflowPolicy::Reject, is_syn)
+            .unwrap();
+
+        let rejected = queue
+            .push(Msg::Query(2), OverflowPolicy::Reject, is_syn)
+            .unwrap();
+
+        assert_eq!(rejected, Some(Msg::Query(2)));
+        assert_eq!(depth.load(Ordering::SeqCst), 1);
+        assert_eq!(queue.pop().unwrap(), Some(Msg::Query(1)));
+        assert!(queue.is_empty().unwrap());
+    }
+
+    #[test]
+    fn test_closed_queue_rejects_push() {
+        let queue = BoundedQueue::new(2, Arc::new(AtomicUsize::new(0)));
+        queue
+            .push(Msg::Query(1), OverflowPolicy::Block, is_syn)
+            .unwrap();
+        queue.close().unwrap();
+
+        assert!(queue
+            .push(Msg::Query(2), OverflowPolicy::Block, is_syn)
+            .is_err());
+        assert_eq!(queue.pop().unwrap(), Some(Msg::Query(1)));
+        assert_eq!(queue.pop().unwrap(), None);
+    }
+}
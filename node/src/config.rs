@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use gossip::GOSSIP_FANOUT;
+
+use crate::storage_engine::durability::DurabilityPolicy;
+use crate::{CLIENT_NODE_PORT, INTERNODE_PORT};
+
+/// Time between two gossip rounds of a node, unless its configuration sets another.
+pub const DEFAULT_GOSSIP_INTERVAL: Duration = Duration::from_millis(1000);
+
+/// Password clients authenticate with, unless the configuration of the node sets another.
+pub const DEFAULT_PASSWORD: &str = "admin";
+
+/// Settings a `Node` is created with by [`crate::Node::from_config`].
+///
+/// Only the ip, the seeds and the storage path are required, every other setting starts with
+/// the value `Node::new` uses and can be changed with the `with_` methods.
+#[derive(Debug, Clone)]
+pub struct NodeConfig {
+    pub(crate) ip: Ipv4Addr,
+    pub(crate) seeds: Vec<Ipv4Addr>,
+    pub(crate) storage_path: PathBuf,
+    pub(crate) client_port: u16,
+    pub(crate) internode_port: u16,
+    pub(crate) gossip_interval: Duration,
+    pub(crate) gossip_fanout: usize,
+    pub(crate) durability: DurabilityPolicy,
+    pub(crate) password: Option<String>,
+    pub(crate) replaces: Option<Ipv4Addr>,
+    pub(crate) datacenter: Option<String>,
+    pub(crate) keyspace_quotas: HashMap<String, u64>,
+}
+
+impl NodeConfig {
+    /// Configuration of the node with the given `ip`, that joins the cluster through `seeds` and
+    /// keeps its data in `storage_path`, with the default value of every other setting.
+    pub fn new(ip: Ipv4Addr, seeds: Vec<Ipv4Addr>, storage_path: PathBuf) -> Self {
+        Self {
+            ip,
+            seeds,
+            storage_path,
+            client_port: CLIENT_NODE_PORT,
+            internode_port: INTERNODE_PORT,
+            gossip_interval: DEFAULT_GOSSIP_INTERVAL,
+            gossip_fanout: GOSSIP_FANOUT,
+            durability: DurabilityPolicy::default(),
+            password: Some(DEFAULT_PASSWORD.to_string()),
+            replaces: None,
+            datacenter: None,
+            keyspace_quotas: HashMap::new(),
+        }
+    }
+
+    /// Sets the port the node listens on for clients.
+    pub fn with_client_port(mut self, port: u16) -> Self {
+        self.client_port = port;
+        self
+    }
+
+    /// Sets the port the node listens on for other nodes. Every node of the cluster has to use
+    /// the same one, since it is also the port the node sends its messages to.
+    pub fn with_internode_port(mut self, port: u16) -> Self {
+        self.internode_port = port;
+        self
+    }
+
+    /// Sets the time between two gossip rounds.
+    pub fn with_gossip_interval(mut self, interval: Duration) -> Self {
+        self.gossip_interval = interval;
+        self
+    }
+
+    /// Sets the maximum amount of nodes the node gossips with on each round.
+    pub fn with_gossip_fanout(mut self, fanout: usize) -> Self {
+        self.gossip_fanout = fanout.max(1);
+        self
+    }
+
+    /// Sets when the table files the node writes are synced to disk.
+    pub fn with_durability(mut self, durability: DurabilityPolicy) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    /// Sets the password clients have to authenticate with before sending queries.
+    pub fn with_password(mut self, password: &str) -> Self {
+        self.password = Some(password.to_string());
+        self
+    }
+
+    /// Lets clients send queries without authenticating.
+    pub fn without_authentication(mut self) -> Self {
+        self.password = None;
+        self
+    }
+
+    /// Makes the node join as the replacement of the dead node `dead`, on a new host: it takes
+    /// the tokens of `dead`, so it owns the same ranges, the other nodes forget `dead` and send
+    /// the replacement the rows of those ranges.
+    pub fn with_replaced_node(mut self, dead: Ipv4Addr) -> Self {
+        self.replaces = Some(dead);
+        self
+    }
+
+    /// Sets the datacenter the node runs in, which it gossips to the other nodes so that
+    /// `LOCAL_ONE` and `LOCAL_QUORUM` only count the replicas of the datacenter of their
+    /// coordinator. Nodes without one are all in the same datacenter.
+    pub fn with_datacenter(mut self, datacenter: &str) -> Self {
+        self.datacenter = Some(datacenter.to_string());
+        self
+    }
+
+    /// Limits the data of `keyspace` on the node to `bytes`: writes that would go over it fail.
+    /// Keyspaces without a quota have no limit. See [`crate::storage_engine::StorageEngine::keyspace_size`].
+    pub fn with_keyspace_quota(mut self, keyspace: &str, bytes: u64) -> Self {
+        self.keyspace_quotas.insert(keyspace.to_string(), bytes);
+        self
+    }
+}
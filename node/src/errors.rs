@@ -8,6 +8,7 @@ use super::storage_engine::errors::StorageEngineError;
 use gossip::structures::application_state::SchemaError;
 use logger::LoggerError;
 use native_protocol::errors::NativeError;
+use native_protocol::messages::error::{Error, ReadTimeout, UnavailableException, WriteTimeout};
 use partitioner::errors::PartitionerError;
 use query_creator::errors::CQLError; // Importar LoggerError
 
@@ -46,6 +47,12 @@ pub enum NodeError {
     GossipError,
     /// Error related to schema updating.
     SchemaError(SchemaError),
+    /// The replicas the consistency level of a query needs cannot answer it.
+    Unavailable(String),
+    /// The replicas of a read did not answer before the coordinator stopped waiting.
+    ReadTimeout(String),
+    /// The replicas of a write did not answer before the coordinator stopped waiting.
+    WriteTimeout(String),
 }
 
 impl Display for NodeError {
@@ -68,6 +75,9 @@ impl Display for NodeError {
             NodeError::LoggerError(e) => write!(f, "Logger Error: {}", e),
             NodeError::GossipError => write!(f, "Gossip Error"),
             NodeError::SchemaError(e) => write!(f, "Schema Error: {}", e),
+            NodeError::Unavailable(reason) => write!(f, "Unavailable: {}", reason),
+            NodeError::ReadTimeout(reason) => write!(f, "Read timeout: {}", reason),
+            NodeError::WriteTimeout(reason) => write!(f, "Write timeout: {}", reason),
         }
     }
 }
@@ -122,7 +132,40 @@ impl From<LoggerError> for NodeError {
 }
 
 impl From<SchemaError> for NodeError {
-    fn from(_value: SchemaError) -> Self {
-        NodeError::OtherError
+    fn from(error: SchemaError) -> Self {
+        NodeError::SchemaError(error)
+    }
+}
+
+impl From<&NodeError> for Error {
+    /// Maps the error to the native protocol error reported to the client.
+    ///
+    /// Query and storage errors keep the mapping of `CQLError` and `StorageEngineError`,
+    /// a missing keyspace or column of the schema is `Invalid`, failing to reach other
+    /// nodes is `UnavailableException` and replicas that do not answer in time are a
+    /// `ReadTimeout` or a `WriteTimeout`. Anything else is a `ServerError`.
+    fn from(error: &NodeError) -> Self {
+        match error {
+            NodeError::CQLError(e) => Error::from(e),
+            NodeError::StorageEngineError(e) => Error::from(e),
+            NodeError::KeyspaceError | NodeError::SchemaError(_) => {
+                Error::Invalid(error.to_string())
+            }
+            NodeError::InternodeError
+            | NodeError::PartitionerError(_)
+            | NodeError::Unavailable(_) => {
+                Error::UnavailableException(error.to_string(), UnavailableException)
+            }
+            NodeError::ReadTimeout(_) => Error::ReadTimeout(error.to_string(), ReadTimeout),
+            NodeError::WriteTimeout(_) => Error::WriteTimeout(error.to_string(), WriteTimeout),
+            NodeError::NativeError(_) => Error::ProtocolError(error.to_string()),
+            _ => Error::ServerError(error.to_string()),
+        }
+    }
+}
+
+impl From<NodeError> for Error {
+    fn from(error: NodeError) -> Self {
+        Error::from(&error)
     }
 }
@@ -0,0 +1,97 @@
+use gossip::structures::application_state::NodeStatus;
+use native_protocol::frame::Frame;
+use native_protocol::messages::result::result_;
+use native_protocol::messages::result::rows::{ColumnType, ColumnValue, Row, Rows};
+
+/// Reserved query that asks a node whether it is ready to serve. The node answers it on its
+/// own, without parsing it as CQL or contacting other nodes.
+pub const HEALTH_CHECK_QUERY: &str = "SELECT * FROM system.health";
+
+/// State of a node as reported to operators and load balancers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HealthReport {
+    /// Status of the node in the cluster, as gossiped to the other nodes.
+    pub status: NodeStatus,
+    /// Whether the node finished joining the cluster and receiving its data.
+    pub bootstrapped: bool,
+    /// Whether every node in `Normal` status has the same schema as this one.
+    pub schema_agreement: bool,
+}
+
+impl HealthReport {
+    /// A node is ready when it is `Normal`, bootstrapped and agrees on the schema.
+    pub fn is_ready(&self) -> bool {
+        self.status.is_normal() && self.bootstrapped && self.schema_agreement
+    }
+
+    /// Builds the answer to `HEALTH_CHECK_QUERY`: a single row with the fields of the report
+    /// and whether the node is ready.
+    pub fn to_frame(&self) -> Frame {
+        let columns = vec![
+            ("status".to_string(), ColumnType::Ascii),
+            ("ready".to_string(), ColumnType::Boolean),
+            ("bootstrapped".to_string(), ColumnType::Boolean),
+            ("schema_agreement".to_string(), ColumnType::Boolean),
+        ];
+        let row = Row::from([
+            (
+                "status".to_string(),
+                ColumnValue::Ascii(format!("{:?}", self.status)),
+            ),
+            ("ready".to_string(), ColumnValue::Boolean(self.is_ready())),
+            (
+                "bootstrapped".to_string(),
+                ColumnValue::Boolean(self.bootstrapped),
+            ),
+            (
+                "schema_agreement".to_string(),
+                ColumnValue::Boolean(self.schema_agreement),
+            ),
+        ]);
+
+        Frame::Result(result_::Result::Rows(Rows::new(columns, vec![row])))
+    }
+}
+
+/// Whether `query` is `HEALTH_CHECK_QUERY`, ignoring case, extra whitespace and a final `;`.
+pub fn is_health_check(query: &str) -> bool {
+    let normalized: Vec<String> = query
+        .trim()
+        .trim_end_matches(';')
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect();
+    normalized.join(" ") == HEALTH_CHECK_QUERY.to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_the_health_check_query_is_recognized_in_any_case() {
+        assert!(is_health_check(HEALTH_CHECK_QUERY));
+        assert!(is_health_check("  select *   FROM System.Health;\n"));
+        assert!(!is_health_check("SELECT * FROM sky.health"));
+    }
+
+    #[test]
+    fn test_a_node_is_only_ready_when_every_check_passes() {
+        let ready = HealthReport {
+            status: NodeStatus::Normal,
+            bootstrapped: true,
+            schema_agreement: true,
+        };
+        assert!(ready.is_ready());
+        assert!(!HealthReport {
+            schema_agreement: false,
+            ..ready
+        }
+        .is_ready());
+        assert!(!HealthReport {
+            status: NodeStatus::Leaving,
+            ..ready
+        }
+        .is_ready());
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::Ipv4Addr;
+use std::sync::atomic::AtomicUsize;
+use std::sync::{Arc, Mutex};
+
+use crate::bounded_queue::BoundedQueue;
+use crate::errors::NodeError;
+use crate::internode_protocol::message::InternodeMessage;
+
+/// Most writes kept for a single replica. Past it new writes are refused, so the coordinator
+/// counts them as failed instead of acknowledging a write at `ANY` with a hint it dropped: a
+/// replica that stays away that long gets the rest of its data back through read repair.
+pub const MAX_HINTS_PER_NODE: usize = 1024;
+
+/// Most writes waiting to be sent in the background to a single replica. Past it the replica
+/// is not keeping up, so new writes for it fail right away and it gets them through read
+/// repair.
+pub const MAX_PENDING_WRITES_PER_NODE: usize = 128;
+
+/// When the coordinator of a write answers the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReplicationMode {
+    /// As soon as the consistency level is met. The writes to the other replicas are sent in
+    /// the background, and the ones that cannot be delivered are kept as hints.
+    #[default]
+    Async,
+    /// Once every replica acknowledged the write, whatever the consistency level.
+    Sync,
+}
+
+/// Writes a coordinator could not deliver to a replica, kept until gossip reports the replica
+/// alive again and they can be sent once more.
+///
+/// Hints live only in the memory of the coordinator: the ones of a node that restarts are
+/// lost, and their replicas get those writes back through read repair.
+#[derive(Debug, Default)]
+pub struct HintedWrites {
+    by_node: HashMap<Ipv4Addr, VecDeque<InternodeMessage>>,
+}
+
+impl HintedWrites {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps `message` to be sent to `target` later, after the hints it already has.
+    ///
+    /// Returns `false`, without keeping it, if `target` already has [`MAX_HINTS_PER_NODE`]
+    /// hints.
+    pub fn add(&mut self, target: Ipv4Addr, message: InternodeMessage) -> bool {
+        let hints = self.by_node.entry(target).or_default();
+        if hints.len() >= MAX_HINTS_PER_NODE {
+            return false;
+        }
+        hints.push_back(message);
+        true
+    }
+
+    /// Puts back, before the hints added since, the writes taken for `target` that could not
+    /// be sent. They were already accepted, so they are kept even past the limit.
+    pub fn restore(&mut self, target: Ipv4Addr, messages: Vec<InternodeMessage>) {
+        let hints = self.by_node.entry(target).or_default();
+        for message in messages.into_iter().rev() {
+            hints.push_front(message);
+        }
+    }
+
+    /// Amount of writes waiting to be sent to `target`.
+    pub fn pending(&self, target: Ipv4Addr) -> usize {
+        self.by_node.get(&target).map_or(0, VecDeque::len)
+    }
+
+    /// Nodes with writes waiting for them.
+    pub fn targets(&self) -> Vec<Ipv4Addr> {
+        self.by_node.keys().copied().collect()
+    }
+
+    /// Removes and returns the writes for `target`, oldest first.
+    pub fn take(&mut self, target: Ipv4Addr) -> Vec<InternodeMessage> {
+        self.by_node
+            .remove(&target)
+            .map(Vec::from)
+            .unwrap_or_default()
+    }
+}
+
+/// A write waiting to be sent in the background, with the open query it answers.
+pub type PendingWrite = (InternodeMessage, i32);
+
+/// Writes waiting to be sent in the background to each replica under
+/// `ReplicationMode::Async`.
+///
+/// Each replica has its own queue, emptied by a single thread, so a slow replica only holds
+/// up its own writes and never more than [`MAX_PENDING_WRITES_PER_NODE`] of them.
+#[derive(Clone, Default)]
+pub struct PendingWrites {
+    by_node: Arc<Mutex<HashMap<Ipv4Addr, Arc<BoundedQueue<PendingWrite>>>>>,
+}
+
+impl PendingWrites {
+    /// Returns the queue of the writes for `target`.
+    ///
+    /// The first time a queue is asked for `target`, `start_sender` is called with it to start
+    /// the thread that sends its writes.
+    pub fn queue_of<F>(
+        &self,
+        target: Ipv4Addr,
+        start_sender: F,
+    ) -> Result<Arc<BoundedQueue<PendingWrite>>, NodeError>
+    where
+        F: FnOnce(Arc<BoundedQueue<PendingWrite>>),
+    {
+        let mut by_node = self.by_node.lock()?;
+        if let Some(queue) = by_node.get(&target) {
+            return Ok(Arc::clone(queue));
+        }
+        let queue = Arc::new(BoundedQueue::new(
+            MAX_PENDING_WRITES_PER_NODE,
+            Arc::new(AtomicUsize::new(0)),
+        ));
+        by_node.insert(target, Arc::clone(&queue));
+        start_sender(Arc::clone(&queue));
+        Ok(queue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internode_protocol::message::InternodeMessageContent;
+    use crate::internode_protocol::query::InternodeQuery;
+
+    fn write(open_query_id: u32) -> InternodeMessage {
+        InternodeMessage::new(
+            Ipv4Addr::new(10, 0, 0, 1),
+            InternodeMessageContent::Query(InternodeQuery {
+                query_string: "INSERT INTO sky.flights (id) VALUES (1)".to_string(),
+                open_query_id,
+                client_id: 0,
+                replication: true,
+                keyspace_name: "sky".to_string(),
+                timestamp: 1,
+            }),
+        )
+    }
+
+    fn open_query_id(message: &InternodeMessage) -> u32 {
+        match &message.content {
+            InternodeMessageContent::Query(query) => query.open_query_id,
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_hints_are_taken_in_the_order_they_were_added() {
+        let replica = Ipv4Addr::new(10, 0, 0, 2);
+        let mut hints = HintedWrites::new();
+        assert!(hints.add(replica, write(1)));
+        assert!(hints.add(replica, write(2)));
+
+        assert_eq!(hints.pending(replica), 2);
+        assert_eq!(hints.targets(), vec![replica]);
+        let taken: Vec<u32> = hints.take(replica).iter().map(open_query_id).collect();
+        assert_eq!(taken, vec![1, 2]);
+        assert_eq!(hints.pending(replica), 0);
+        assert!(hints.targets().is_empty());
+    }
+
+    #[test]
+    fn test_hints_past_the_limit_are_refused() {
+        let replica = Ipv4Addr::new(10, 0, 0, 2);
+        let mut hints = HintedWrites::new();
+        for id in 0..MAX_HINTS_PER_NODE as u32 {
+            assert!(hints.add(replica, write(id)));
+        }
+        assert!(!hints.add(replica, write(MAX_HINTS_PER_NODE as u32)));
+
+        let taken = hints.take(replica);
+        assert_eq!(taken.len(), MAX_HINTS_PER_NODE);
+        assert_eq!(open_query_id(&taken[0]), 0);
+    }
+
+    #[test]
+    fn test_each_replica_gets_a_single_sender() {
+        let pending = PendingWrites::default();
+        let mut started = Vec::new();
+        let first = Ipv4Addr::new(10, 0, 0, 2);
+        let second = Ipv4Addr::new(10, 0, 0, 3);
+
+        let queue = pending.queue_of(first, |_| started.push(first)).unwrap();
+        let again = pending.queue_of(first, |_| started.push(first)).unwrap();
+        pending.queue_of(second, |_| started.push(second)).unwrap();
+
+        assert!(Arc::ptr_eq(&queue, &again));
+        assert_eq!(started, vec![first, second]);
+        assert_eq!(queue.capacity(), MAX_PENDING_WRITES_PER_NODE);
+    }
+
+    #[test]
+    fn test_restored_hints_go_back_before_the_newer_ones() {
+        let replica = Ipv4Addr::new(10, 0, 0, 2);
+        let mut hints = HintedWrites::new();
+        hints.add(replica, write(1));
+        hints.add(replica, write(2));
+        let mut taken = hints.take(replica);
+        hints.add(replica, write(3));
+
+        // La primera se envio, la segunda no
+        taken.remove(0);
+        hints.restore(replica, taken);
+        let pending: Vec<u32> = hints.take(replica).iter().map(open_query_id).collect();
+        assert_eq!(pending, vec![2, 3]);
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use native_protocol::frame::Frame;
+use native_protocol::Serializable;
+
+use crate::Node;
+
+/// How long the answer of a write with an idempotency key is kept. A retry of the write that
+/// arrives later is applied again.
+pub const IDEMPOTENCY_WINDOW: Duration = Duration::from_secs(60);
+
+/// How long a retry waits for the attempt that is applying the same write before it is
+/// answered with a write timeout.
+pub const IN_FLIGHT_WAIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Clause a client adds at the end of a write so that retrying it applies it once:
+/// `INSERT INTO ... VALUES (...) USING IDEMPOTENCY KEY '<key>'`.
+const IDEMPOTENCY_CLAUSE: &str = "USING IDEMPOTENCY KEY";
+
+/// Splits the `USING IDEMPOTENCY KEY '<key>'` clause off the end of `query`.
+///
+/// Returns the query without the clause and the key, or `query` as is and `None` if it does
+/// not end with a well formed clause.
+pub fn split_idempotency_key(query: &str) -> (&str, Option<String>) {
+    let trimmed = query.trim_end().trim_end_matches(';').trim_end();
+    // `to_ascii_uppercase` no cambia el largo, asi la posicion sirve para `trimmed`
+    let Some(start) = trimmed.to_ascii_uppercase().rfind(IDEMPOTENCY_CLAUSE) else {
+        return (query, None);
+    };
+
+    let key = trimmed[start + IDEMPOTENCY_CLAUSE.len()..].trim();
+    match key
+        .strip_prefix('\'')
+        .and_then(|key| key.strip_suffix('\''))
+    {
+        Some(key) if !key.is_empty() && !key.contains('\'') => {
+            (trimmed[..start].trim_end(), Some(key.to_string()))
+        }
+        _ => (query, None),
+    }
+}
+
+/// Identifies a write a client may retry: the key it gave the write, scoped to the table and
+/// partition the write modifies, so the same key in another partition is another write.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IdempotencyKey {
+    /// `keyspace.table` of the write.
+    pub table: String,
+    /// Values of the partition key columns of the write.
+    pub partition: Vec<String>,
+    /// Key the client sent with the write.
+    pub key: String,
+}
+
+/// What the coordinator knows of a write with an idempotency key when it arrives.
+#[derive(Debug)]
+pub enum Reservation {
+    /// No attempt of the write is running or was applied within the window: the write is
+    /// reserved for this attempt, which has to `record` its answer or `release` it.
+    Reserved,
+    /// Another attempt of the write is running; its answer has to be waited for.
+    InFlight,
+    /// The write was applied within the window, with this answer.
+    Applied(Frame),
+}
+
+// Estado de una escritura con clave de idempotencia
+#[derive(Debug)]
+enum Entry {
+    InFlight,
+    Applied(Instant, Vec<u8>),
+}
+
+/// Answers of the writes with an idempotency key the coordinator applied recently, and the
+/// writes that are being applied.
+///
+/// A retry of one of them is answered with the answer of the first attempt instead of being
+/// applied again, e.g. so an `INSERT` with `uuid()` does not add a second row, or an
+/// `IF NOT EXISTS` that was applied is not reported as not applied. A retry that arrives
+/// while the first attempt is still running waits for its answer.
+///
+/// Each coordinator keeps its own writes: a retry sent to another node than the first attempt
+/// is applied again.
+#[derive(Debug)]
+pub struct IdempotentWrites {
+    window: Duration,
+    // Las respuestas se guardan serializadas, `Frame` no se puede clonar
+    entries: HashMap<IdempotencyKey, Entry>,
+    // Avisa a los reintentos que esperan que una escritura en curso termino
+    settled: Arc<Condvar>,
+}
+
+impl Default for IdempotentWrites {
+    fn default() -> Self {
+        Self::new(IDEMPOTENCY_WINDOW)
+    }
+}
+
+impl IdempotentWrites {
+    /// Keeps the answers of the writes for `window`.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            entries: HashMap::new(),
+            settled: Arc::new(Condvar::new()),
+        }
+    }
+
+    /// Reserves the write identified by `key` for the attempt that calls it, unless another
+    /// attempt is running or was applied within the window.
+    pub fn reserve(&mut self, key: &IdempotencyKey) -> Reservation {
+        self.forget_expired();
+        match self.entries.get(key) {
+            Some(Entry::InFlight) => Reservation::InFlight,
+            Some(Entry::Applied(_, answer)) => match Frame::from_bytes(answer) {
+                Ok(answer) => Reservation::Applied(answer),
+                Err(_) => Reservation::InFlight,
+            },
+            None => {
+                self.entries.insert(key.clone(), Entry::InFlight);
+                Reservation::Reserved
+            }
+        }
+    }
+
+    /// The answer of the write identified by `key`, if it was applied within the window.
+    pub fn answer_of(&mut self, key: &IdempotencyKey) -> Option<Frame> {
+        self.forget_expired();
+        match self.entries.get(key)? {
+            Entry::Applied(_, answer) => Frame::from_bytes(answer).ok(),
+            Entry::InFlight => None,
+        }
+    }
+
+    /// Keeps `answer` as the answer of the write identified by `key`, and wakes the attempts
+    /// waiting for it.
+    pub fn record(&mut self, key: IdempotencyKey, answer: &Frame) {
+        self.forget_expired();
+        match answer.to_bytes() {
+            Ok(answer) => {
+                self.entries
+                    .insert(key, Entry::Applied(Instant::now(), answer));
+            }
+            Err(_) => {
+                self.entries.remove(&key);
+            }
+        }
+        self.settled.notify_all();
+    }
+
+    /// Drops the reservation of the write identified by `key` without an answer, e.g. because
+    /// it failed, so the next attempt applies it. Wakes the attempts waiting for it.
+    pub fn release(&mut self, key: &IdempotencyKey) {
+        if matches!(self.entries.get(key), Some(Entry::InFlight)) {
+            self.entries.remove(key);
+        }
+        self.settled.notify_all();
+    }
+
+    /// Condition notified each time a write being applied is recorded or released. It is
+    /// waited on with the lock of the node that holds these writes.
+    pub fn settled(&self) -> Arc<Condvar> {
+        Arc::clone(&self.settled)
+    }
+
+    /// Amount of writes whose answers are kept.
+    pub fn len(&self) -> usize {
+        self.entries
+            .values()
+            .filter(|entry| matches!(entry, Entry::Applied(..)))
+            .count()
+    }
+
+    /// Whether no answer is kept.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Las escrituras en curso no vencen, las libera quien las reservo
+    fn forget_expired(&mut self) {
+        let window = self.window;
+        self.entries.retain(|_, entry| match entry {
+            Entry::InFlight => true,
+            Entry::Applied(applied_at, _) => applied_at.elapsed() < window,
+        });
+    }
+}
+
+/// Reservation of a write of the node taken with [`IdempotentWrites::reserve`].
+///
+/// The attempt that holds it settles it with its answer. If it is dropped unsettled, e.g.
+/// because the attempt panicked, the write is released so its retries do not wait for it.
+pub struct ReservedWrite {
+    node: Arc<Mutex<Node>>,
+    key: Option<IdempotencyKey>,
+}
+
+impl ReservedWrite {
+    /// Holds the reservation of the write identified by `key` in the writes of `node`.
+    pub fn new(node: Arc<Mutex<Node>>, key: IdempotencyKey) -> Self {
+        Self {
+            node,
+            key: Some(key),
+        }
+    }
+
+    /// Records `answer` if the write was applied, or releases the write so a retry applies it.
+    pub fn settle(mut self, answer: &Frame) {
+        let Some(key) = self.key.take() else {
+            return;
+        };
+        // Solo se recuerdan las escrituras que se aplicaron, un error se puede reintentar
+        if let Ok(mut node) = self.node.lock() {
+            match answer {
+                Frame::Result(_) => node.idempotent_writes.record(key, answer),
+                _ => node.idempotent_writes.release(&key),
+            }
+        }
+    }
+}
+
+impl Drop for ReservedWrite {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            if let Ok(mut node) = self.node.lock() {
+                node.idempotent_writes.release(&key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use native_protocol::messages::result::result_;
+
+    #[test]
+    fn test_split_idempotency_key() {
+        assert_eq!(
+            split_idempotency_key(
+                "DELETE FROM sky.flights WHERE id = 1 using idempotency key 'a-1';"
+            ),
+            (
+                "DELETE FROM sky.flights WHERE id = 1",
+                Some("a-1".to_string())
+            )
+        );
+        let query = "INSERT INTO sky.flights (id) VALUES (1)";
+        assert_eq!(split_idempotency_key(query), (query, None));
+        let unquoted = "INSERT INTO sky.flights (id) VALUES (1) USING IDEMPOTENCY KEY a";
+        assert_eq!(split_idempotency_key(unquoted), (unquoted, None));
+    }
+
+    #[test]
+    fn test_answers_are_forgotten_after_the_window() {
+        let key = IdempotencyKey {
+            table: "sky.flights".to_string(),
+            partition: vec!["1".to_string()],
+            key: "a-1".to_string(),
+        };
+        let mut writes = IdempotentWrites::new(Duration::from_millis(20));
+        writes.record(key.clone(), &Frame::Result(result_::Result::Void));
+        assert!(matches!(
+            writes.answer_of(&key),
+            Some(Frame::Result(result_::Result::Void))
+        ));
+
+        // La misma clave en otra particion es otra escritura
+        let other_partition = IdempotencyKey {
+            partition: vec!["2".to_string()],
+            ..key.clone()
+        };
+        assert!(writes.answer_of(&other_partition).is_none());
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(writes.answer_of(&key).is_none());
+        assert!(writes.is_empty());
+    }
+
+    #[test]
+    fn test_a_reserved_write_is_in_flight_until_it_is_recorded_or_released() {
+        let key = IdempotencyKey {
+            table: "sky.flights".to_string(),
+            partition: vec!["1".to_string()],
+            key: "a-1".to_string(),
+        };
+        let mut writes = IdempotentWrites::default();
+        assert!(matches!(writes.reserve(&key), Reservation::Reserved));
+        assert!(matches!(writes.reserve(&key), Reservation::InFlight));
+        assert!(writes.is_empty());
+
+        // Un intento que fallo libera la clave para el proximo
+        writes.release(&key);
+        assert!(matches!(writes.reserve(&key), Reservation::Reserved));
+
+        writes.record(key.clone(), &Frame::Result(result_::Result::Void));
+        assert!(matches!(
+            writes.reserve(&key),
+            Reservation::Applied(Frame::Result(result_::Result::Void))
+        ));
+        assert_eq!(writes.len(), 1);
+    }
+}
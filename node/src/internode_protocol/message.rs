@@ -1,10 +1,20 @@
-use super::{query::InternodeQuery, response::InternodeResponse, InternodeSerializable};
+use super::{
+    query::InternodeQuery, read_request::InternodeReadRequest, response::InternodeResponse,
+    InternodeSerializable,
+};
 use gossip::messages::GossipMessage;
+use gossip::structures::application_state::Schema;
 use std::{
+    fmt,
     io::{Cursor, Read},
     net::Ipv4Addr,
 };
 
+/// The version of the internode protocol spoken by this node.\
+/// It is sent as the first byte of every message, so a node can reject messages
+/// written by a version it does not understand instead of misreading them.
+pub const PROTOCOL_VERSION: u8 = 1;
+
 /// The opcode of an internode message.\
 /// The opcode is used to determine the type of message being sent.
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -12,38 +22,47 @@ enum Opcode {
     Query = 0x01,
     Response = 0x02,
     Gossip = 0x03,
+    ReadRequest = 0x04,
+    SchemaPull = 0x05,
+    SchemaPush = 0x06,
 }
 
 /// The header of an internode message.
 ///
 /// ### Fields
 ///
+/// * `version` - The version of the protocol the message was written with.
 /// * `opcode` - The opcode of the message.
 /// * `ip` - The IP address of the node that sent the message.
 #[derive(Debug, PartialEq)]
 struct InternodeHeader {
+    version: u8,
     opcode: Opcode,
     ip: Ipv4Addr,
     length: u32,
 }
 
-const HEADER_SIZE: usize = 9;
+const HEADER_SIZE: usize = 10;
+
+/// Size of the CRC32 that closes every message.
+const CHECKSUM_SIZE: usize = 4;
 
 impl InternodeSerializable for InternodeHeader {
     /// ```md
     /// 0    8    16   24   32
     /// +----+----+----+----+
-    /// |         ip        |
+    /// | v  |      ip      |
     /// +----+----+----+----+
-    /// |  content_length   |
+    /// | ip |content_length|
     /// +----+----+----+----+
-    /// | op |              |
+    /// |len | op |         |
     /// +----+----+----+----+
     /// ```
     /// Serializes the header into a byte vector.
     fn as_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
 
+        bytes.push(self.version);
         bytes.extend_from_slice(&self.ip.octets());
         bytes.extend_from_slice(&self.length.to_be_bytes());
         bytes.push(self.opcode as u8);
@@ -52,39 +71,60 @@ impl InternodeSerializable for InternodeHeader {
     }
 
     /// Deserializes the header from a byte slice.
+    ///
+    /// Fails with `InternodeMessageError::UnsupportedVersion` if the message was written
+    /// with a protocol version other than `PROTOCOL_VERSION`.
     fn from_bytes(bytes: &[u8]) -> Result<Self, InternodeMessageError>
     where
         Self: Sized,
     {
         let mut cursor = Cursor::new(bytes);
 
+        let mut version_byte = [0u8; 1];
+        cursor
+            .read_exact(&mut version_byte)
+            .map_err(|_| InternodeMessageError::Malformed)?;
+
+        let version = version_byte[0];
+        if version != PROTOCOL_VERSION {
+            return Err(InternodeMessageError::UnsupportedVersion(version));
+        }
+
         let mut ip_bytes = [0u8; 4];
         cursor
             .read_exact(&mut ip_bytes)
-            .map_err(|_| InternodeMessageError)?;
+            .map_err(|_| InternodeMessageError::Malformed)?;
 
         let ip = Ipv4Addr::from(ip_bytes);
 
         let mut len_bytes = [0u8; 4];
         cursor
             .read_exact(&mut len_bytes)
-            .map_err(|_| InternodeMessageError)?;
+            .map_err(|_| InternodeMessageError::Malformed)?;
 
         let length = u32::from_be_bytes(len_bytes);
 
         let mut opcode_byte = [0u8; 1];
         cursor
             .read_exact(&mut opcode_byte)
-            .map_err(|_| InternodeMessageError)?;
+            .map_err(|_| InternodeMessageError::Malformed)?;
 
         let opcode = match opcode_byte[0] {
             0x01 => Opcode::Query,
             0x02 => Opcode::Response,
             0x03 => Opcode::Gossip,
-            _ => return Err(InternodeMessageError),
+            0x04 => Opcode::ReadRequest,
+            0x05 => Opcode::SchemaPull,
+            0x06 => Opcode::SchemaPush,
+            _ => return Err(InternodeMessageError::Malformed),
         };
 
-        Ok(InternodeHeader { opcode, ip, length })
+        Ok(InternodeHeader {
+            version,
+            opcode,
+            ip,
+            length,
+        })
     }
 }
 
@@ -95,11 +135,17 @@ impl InternodeSerializable for InternodeHeader {
 /// * `Query` - A query message.
 /// * `Response` - A response message.
 /// * `Gossip` - A gossip message.
+/// * `ReadRequest` - A `SELECT` sent to a replica, with the consistency asked by the client.
+/// * `SchemaPull` - Asks the receiver for its whole schema, sent by a node that is joining.
+/// * `SchemaPush` - The schema of the sender, the answer to a `SchemaPull`.
 #[derive(Debug, PartialEq, Clone)]
 pub enum InternodeMessageContent {
     Query(InternodeQuery),
     Response(InternodeResponse),
     Gossip(GossipMessage),
+    ReadRequest(InternodeReadRequest),
+    SchemaPull,
+    SchemaPush(Schema),
 }
 
 /// A message transmitted between nodes via the internode protocol.
@@ -125,8 +171,34 @@ impl InternodeMessage {
 }
 
 /// An error that occurs when serializing or deserializing an internode message.
-#[derive(Debug)]
-pub struct InternodeMessageError;
+///
+/// ### Variants
+///
+/// * `Malformed` - The bytes do not form a valid message.
+/// * `UnsupportedVersion` - The message was written with a protocol version this node does not understand.
+/// * `ChecksumMismatch` - The CRC32 of the received bytes does not match the one sent, the message was corrupted.
+#[derive(Debug, PartialEq)]
+pub enum InternodeMessageError {
+    Malformed,
+    UnsupportedVersion(u8),
+    ChecksumMismatch,
+}
+
+impl fmt::Display for InternodeMessageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InternodeMessageError::Malformed => write!(f, "Malformed internode message"),
+            InternodeMessageError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported internode protocol version {} (expected {})",
+                version, PROTOCOL_VERSION
+            ),
+            InternodeMessageError::ChecksumMismatch => {
+                write!(f, "Internode message checksum mismatch")
+            }
+        }
+    }
+}
 
 impl InternodeSerializable for InternodeMessage {
     /// ```md
@@ -134,10 +206,13 @@ impl InternodeSerializable for InternodeMessage {
     /// +----+----+----+----+
     /// |       header      |
     /// +----+----+----+----+
-    /// |head|  content...
+    /// |     header    |  content...
+    /// +----+----+----+----+
+    /// |       crc32       |
     /// +----+----+----+----+
     /// ```
-    /// Serializes the message into a byte vector.
+    /// Serializes the message into a byte vector.\
+    /// The message ends with the CRC32 of the header and the content.
     fn as_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
 
@@ -145,15 +220,23 @@ impl InternodeSerializable for InternodeMessage {
             InternodeMessageContent::Query(_) => Opcode::Query,
             InternodeMessageContent::Response(_) => Opcode::Response,
             InternodeMessageContent::Gossip(_) => Opcode::Gossip,
+            InternodeMessageContent::ReadRequest(_) => Opcode::ReadRequest,
+            InternodeMessageContent::SchemaPull => Opcode::SchemaPull,
+            InternodeMessageContent::SchemaPush(_) => Opcode::SchemaPush,
         };
 
         let content_bytes = match &self.content {
             InternodeMessageContent::Query(internode_query) => internode_query.as_bytes(),
             InternodeMessageContent::Response(internode_response) => internode_response.as_bytes(),
             InternodeMessageContent::Gossip(gossip_message) => gossip_message.as_bytes(),
+            InternodeMessageContent::ReadRequest(read_request) => read_request.as_bytes(),
+            // El pedido no lleva contenido, el que lo recibe contesta a `from`
+            InternodeMessageContent::SchemaPull => vec![],
+            InternodeMessageContent::SchemaPush(schema) => schema.to_bytes(),
         };
 
         let header = InternodeHeader {
+            version: PROTOCOL_VERSION,
             ip: self.from,
             opcode,
             length: content_bytes.len() as u32,
@@ -162,34 +245,73 @@ impl InternodeSerializable for InternodeMessage {
         bytes.extend_from_slice(&header.as_bytes());
         bytes.extend_from_slice(&content_bytes);
 
+        let checksum = crc32fast::hash(&bytes);
+        bytes.extend_from_slice(&checksum.to_be_bytes());
+
         bytes
     }
 
     /// Deserializes the message from a byte slice.
+    ///
+    /// The checksum is verified before interpreting the header, so a corrupted message
+    /// is reported as `InternodeMessageError::ChecksumMismatch` and not as a parse error.
     fn from_bytes(bytes: &[u8]) -> Result<Self, InternodeMessageError> {
         let mut cursor = Cursor::new(bytes);
 
         let mut header_bytes = [0u8; HEADER_SIZE];
         cursor
             .read_exact(&mut header_bytes)
-            .map_err(|_| InternodeMessageError)?;
+            .map_err(|_| InternodeMessageError::Malformed)?;
 
-        let header =
-            InternodeHeader::from_bytes(&header_bytes).map_err(|_| InternodeMessageError)?;
-        let mut content_bytes = vec![0u8; header.length as usize];
+        // El largo esta despues de la version (1 byte) y la ip (4 bytes)
+        let mut len_bytes = [0u8; 4];
+        len_bytes.copy_from_slice(&header_bytes[5..9]);
+        let length = u32::from_be_bytes(len_bytes) as usize;
+
+        let end = HEADER_SIZE
+            .checked_add(length)
+            .and_then(|end| end.checked_add(CHECKSUM_SIZE))
+            .ok_or(InternodeMessageError::Malformed)?;
+        if bytes.len() < end {
+            return Err(InternodeMessageError::Malformed);
+        }
+
+        let mut content_bytes = vec![0u8; length];
         cursor
             .read_exact(&mut content_bytes)
-            .map_err(|_| InternodeMessageError)?;
+            .map_err(|_| InternodeMessageError::Malformed)?;
+
+        let mut checksum_bytes = [0u8; CHECKSUM_SIZE];
+        cursor
+            .read_exact(&mut checksum_bytes)
+            .map_err(|_| InternodeMessageError::Malformed)?;
+
+        if crc32fast::hash(&bytes[..HEADER_SIZE + length]) != u32::from_be_bytes(checksum_bytes) {
+            return Err(InternodeMessageError::ChecksumMismatch);
+        }
+
+        let header = InternodeHeader::from_bytes(&header_bytes)?;
 
         let content = match header.opcode {
             Opcode::Query => InternodeMessageContent::Query(
-                InternodeQuery::from_bytes(&content_bytes).map_err(|_| InternodeMessageError)?,
+                InternodeQuery::from_bytes(&content_bytes)
+                    .map_err(|_| InternodeMessageError::Malformed)?,
             ),
             Opcode::Response => InternodeMessageContent::Response({
-                InternodeResponse::from_bytes(&content_bytes).map_err(|_| InternodeMessageError)?
+                InternodeResponse::from_bytes(&content_bytes)
+                    .map_err(|_| InternodeMessageError::Malformed)?
             }),
             Opcode::Gossip => InternodeMessageContent::Gossip(
-                GossipMessage::from_bytes(&content_bytes).map_err(|_| InternodeMessageError)?,
+                GossipMessage::from_bytes(&content_bytes)
+                    .map_err(|_| InternodeMessageError::Malformed)?,
+            ),
+            Opcode::ReadRequest => InternodeMessageContent::ReadRequest(
+                InternodeReadRequest::from_bytes(&content_bytes)?,
+            ),
+            Opcode::SchemaPull => InternodeMessageContent::SchemaPull,
+            Opcode::SchemaPush => InternodeMessageContent::SchemaPush(
+                Schema::from_bytes(&mut Cursor::new(content_bytes.as_slice()))
+                    .map_err(|_| InternodeMessageError::Malformed)?,
             ),
         };
         let message = InternodeMessage {
@@ -219,6 +341,7 @@ mod tests {
     #[test]
     fn test_header_to_bytes() {
         let header = InternodeHeader {
+            version: PROTOCOL_VERSION,
             opcode: Opcode::Query,
             ip: Ipv4Addr::new(127, 0, 0, 1),
             length: 0,
@@ -227,6 +350,7 @@ mod tests {
         let header_bytes = header.as_bytes();
 
         let mut bytes = Vec::new();
+        bytes.push(PROTOCOL_VERSION);
         bytes.extend_from_slice(&header.ip.octets());
         bytes.extend_from_slice(&header.length.to_be_bytes());
         bytes.push(header.opcode as u8);
@@ -237,6 +361,7 @@ mod tests {
     #[test]
     fn test_header_from_bytes() {
         let header = InternodeHeader {
+            version: PROTOCOL_VERSION,
             opcode: Opcode::Query,
             ip: Ipv4Addr::new(127, 0, 0, 1),
             length: 0,
@@ -281,6 +406,7 @@ mod tests {
         let mut bytes = Vec::new();
 
         let header = InternodeHeader {
+            version: PROTOCOL_VERSION,
             opcode: Opcode::Query,
             ip: Ipv4Addr::new(127, 0, 0, 1),
             length: query_bytes.len() as u32,
@@ -288,6 +414,8 @@ mod tests {
 
         bytes.extend_from_slice(&header.as_bytes());
         bytes.extend_from_slice(&query_bytes);
+        let checksum = crc32fast::hash(&bytes);
+        bytes.extend_from_slice(&checksum.to_be_bytes());
 
         assert_eq!(message_bytes, bytes);
     }
@@ -339,6 +467,7 @@ mod tests {
         let mut bytes = Vec::new();
 
         let header = InternodeHeader {
+            version: PROTOCOL_VERSION,
             opcode: Opcode::Response,
             ip: Ipv4Addr::new(127, 0, 0, 1),
             length: response_bytes.len() as u32,
@@ -346,6 +475,8 @@ mod tests {
 
         bytes.extend_from_slice(&header.as_bytes());
         bytes.extend_from_slice(&response_bytes);
+        let checksum = crc32fast::hash(&bytes);
+        bytes.extend_from_slice(&checksum.to_be_bytes());
 
         assert_eq!(message_bytes, bytes);
     }
@@ -373,4 +504,149 @@ mod tests {
 
         assert_eq!(parsed_message, message);
     }
+
+    #[test]
+    fn test_message_from_bytes_read_request() {
+        let message = InternodeMessage {
+            from: Ipv4Addr::new(127, 0, 0, 1),
+            content: InternodeMessageContent::ReadRequest(InternodeReadRequest {
+                select: query_creator::clauses::select_cql::Select::deserialize(
+                    "SELECT id FROM sky.flights WHERE id = 1",
+                )
+                .unwrap(),
+                open_query_id: 9,
+                client_id: 2,
+                replication: false,
+                keyspace_name: "sky".to_string(),
+                consistency: crate::open_query_handler::ConsistencyLevel::Quorum,
+                timestamp: 5,
+            }),
+        };
+
+        let message_bytes = message.as_bytes();
+        assert_eq!(message_bytes[HEADER_SIZE - 1], Opcode::ReadRequest as u8);
+
+        let parsed_message = InternodeMessage::from_bytes(&message_bytes).unwrap();
+        assert_eq!(parsed_message, message);
+    }
+
+    #[test]
+    fn test_message_round_trips_at_current_version() {
+        let message = InternodeMessage {
+            from: Ipv4Addr::new(127, 0, 0, 1),
+            content: InternodeMessageContent::Response(InternodeResponse {
+                open_query_id: 3,
+                status: InternodeResponseStatus::Ok,
+                content: None,
+            }),
+        };
+
+        let message_bytes = message.as_bytes();
+        assert_eq!(message_bytes[0], PROTOCOL_VERSION);
+
+        let parsed_message = InternodeMessage::from_bytes(&message_bytes).unwrap();
+        assert_eq!(parsed_message, message);
+    }
+
+    #[test]
+    fn test_message_from_future_version_is_rejected() {
+        let message = InternodeMessage {
+            from: Ipv4Addr::new(127, 0, 0, 1),
+            content: InternodeMessageContent::Response(InternodeResponse {
+                open_query_id: 3,
+                status: InternodeResponseStatus::Ok,
+                content: None,
+            }),
+        };
+
+        let mut message_bytes = message.as_bytes();
+        message_bytes[0] = PROTOCOL_VERSION + 1;
+        let checksum_start = message_bytes.len() - CHECKSUM_SIZE;
+        let checksum = crc32fast::hash(&message_bytes[..checksum_start]);
+        message_bytes[checksum_start..].copy_from_slice(&checksum.to_be_bytes());
+
+        assert_eq!(
+            InternodeMessage::from_bytes(&message_bytes),
+            Err(InternodeMessageError::UnsupportedVersion(
+                PROTOCOL_VERSION + 1
+            ))
+        );
+    }
+
+    #[test]
+    fn test_bit_flipped_message_is_checksum_mismatch() {
+        let query = InternodeQuery {
+            query_string: "SELECT * FROM something".to_string(),
+            open_query_id: 1,
+            client_id: 1,
+            replication: false,
+            keyspace_name: "keyspace".to_string(),
+            timestamp: 1,
+        };
+
+        let message = InternodeMessage {
+            from: Ipv4Addr::new(127, 0, 0, 1),
+            content: InternodeMessageContent::Query(query),
+        };
+
+        let mut message_bytes = message.as_bytes();
+        message_bytes[HEADER_SIZE + 3] ^= 0b0000_0100;
+
+        assert_eq!(
+            InternodeMessage::from_bytes(&message_bytes),
+            Err(InternodeMessageError::ChecksumMismatch)
+        );
+    }
+
+    #[test]
+    fn test_schema_pull_and_push_round_trip() {
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        let mut gossiper = gossip::Gossiper::new().with_endpoint_state(ip);
+        let query = query_creator::QueryCreator::new()
+            .handle_query(
+                "CREATE KEYSPACE sky WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 1}"
+                    .to_string(),
+            )
+            .unwrap();
+        let query_creator::Query::CreateKeyspace(keyspace) = query else {
+            panic!("expected a keyspace");
+        };
+        gossiper.add_keyspace(ip, keyspace).unwrap();
+        let schema = gossiper.endpoints_state[&ip]
+            .application_state
+            .schema
+            .clone();
+
+        for content in [
+            InternodeMessageContent::SchemaPull,
+            InternodeMessageContent::SchemaPush(schema),
+        ] {
+            let message = InternodeMessage::new(ip, content);
+            assert_eq!(
+                InternodeMessage::from_bytes(&message.as_bytes()).unwrap(),
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn test_message_followed_by_padding_is_accepted() {
+        let message = InternodeMessage {
+            from: Ipv4Addr::new(127, 0, 0, 1),
+            content: InternodeMessageContent::Response(InternodeResponse {
+                open_query_id: 3,
+                status: InternodeResponseStatus::Ok,
+                content: None,
+            }),
+        };
+
+        // El reader entrega el buffer completo, con ceros despues del mensaje
+        let mut message_bytes = message.as_bytes();
+        message_bytes.extend_from_slice(&[0u8; 32]);
+
+        assert_eq!(
+            InternodeMessage::from_bytes(&message_bytes).unwrap(),
+            message
+        );
+    }
 }
@@ -3,12 +3,13 @@
 //! This module contains the definitions for the internode protocol messages, queries, and responses.
 //!
 //! The internode protocol is used to communicate between nodes in the cluster. It is a custom
-//! protocol that is used to send queries, responses, and gossip messages between nodes.
+//! protocol that is used to send queries, read requests, responses, and gossip messages between nodes.
 
 use message::InternodeMessageError;
 
 pub mod message;
 pub mod query;
+pub mod read_request;
 pub mod response;
 
 /// The InternodeSerializable trait is used to serialize and deserialize internode protocol messages.\
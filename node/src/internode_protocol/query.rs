@@ -123,52 +123,52 @@ impl InternodeSerializable for InternodeQuery {
         let mut open_query_id_bytes = [0u8; 4];
         cursor
             .read_exact(&mut open_query_id_bytes)
-            .map_err(|_| InternodeMessageError)?;
+            .map_err(|_| InternodeMessageError::Malformed)?;
         let open_query_id = u32::from_be_bytes(open_query_id_bytes);
 
         let mut client_id_bytes = [0u8; 4];
         cursor
             .read_exact(&mut client_id_bytes)
-            .map_err(|_| InternodeMessageError)?;
+            .map_err(|_| InternodeMessageError::Malformed)?;
         let client_id = u32::from_be_bytes(client_id_bytes);
 
         let mut timestamp_bytes = [0u8; 8];
         cursor
             .read_exact(&mut timestamp_bytes)
-            .map_err(|_| InternodeMessageError)?;
+            .map_err(|_| InternodeMessageError::Malformed)?;
         let timestamp = i64::from_be_bytes(timestamp_bytes);
 
         let mut replication_byte = [0u8; 1];
         cursor
             .read_exact(&mut replication_byte)
-            .map_err(|_| InternodeMessageError)?;
+            .map_err(|_| InternodeMessageError::Malformed)?;
         let replication = replication_byte[0] != 0;
 
         let mut keyspace_name_len_bytes = [0u8; 4];
         cursor
             .read_exact(&mut keyspace_name_len_bytes)
-            .map_err(|_| InternodeMessageError)?;
+            .map_err(|_| InternodeMessageError::Malformed)?;
         let keyspace_name_len = u32::from_be_bytes(keyspace_name_len_bytes) as usize;
 
         let mut keyspace_name_bytes = vec![0u8; keyspace_name_len];
         cursor
             .read_exact(&mut keyspace_name_bytes)
-            .map_err(|_| InternodeMessageError)?;
+            .map_err(|_| InternodeMessageError::Malformed)?;
         let keyspace_name =
-            String::from_utf8(keyspace_name_bytes).map_err(|_| InternodeMessageError)?;
+            String::from_utf8(keyspace_name_bytes).map_err(|_| InternodeMessageError::Malformed)?;
 
         let mut query_string_len_bytes = [0u8; 4];
         cursor
             .read_exact(&mut query_string_len_bytes)
-            .map_err(|_| InternodeMessageError)?;
+            .map_err(|_| InternodeMessageError::Malformed)?;
         let query_string_len = u32::from_be_bytes(query_string_len_bytes) as usize;
 
         let mut query_string_bytes = vec![0u8; query_string_len];
         cursor
             .read_exact(&mut query_string_bytes)
-            .map_err(|_| InternodeMessageError)?;
+            .map_err(|_| InternodeMessageError::Malformed)?;
         let query_string =
-            String::from_utf8(query_string_bytes).map_err(|_| InternodeMessageError)?;
+            String::from_utf8(query_string_bytes).map_err(|_| InternodeMessageError::Malformed)?;
 
         Ok(InternodeQuery {
             query_string,
@@ -0,0 +1,224 @@
+//! Read request sent by a coordinator node to the replicas of a partition.
+//!
+//! Unlike an `InternodeQuery`, which carries any statement as text, a read request carries the
+//! parsed `Select` together with everything the replica and the coordinator need to agree on:
+//! the open query the answer belongs to and the consistency the client asked for.
+
+use std::io::{Cursor, Read};
+
+use super::{message::InternodeMessageError, InternodeSerializable};
+use crate::open_query_handler::ConsistencyLevel;
+use query_creator::clauses::select_cql::Select;
+
+/// A `SELECT` sent by a coordinator node to a replica.
+///
+/// ### Fields
+/// - `select`: The `SELECT` to run.
+/// - `open_query_id`: The `id` of the open query in the coordinator. The replica answers with it.
+/// - `client_id`: The client that owns the query in the coordinator.
+/// - `replication`: The replica should read the replicated data it stores, not its owned data.
+/// - `keyspace_name`: Keyspace on which the query acts.
+/// - `consistency`: The consistency level the client asked for.
+/// - `timestamp`: The timestamp when the coordinator node received the query.
+#[derive(Debug, PartialEq, Clone)]
+pub struct InternodeReadRequest {
+    /// The `SELECT` to run.
+    pub select: Select,
+    /// The `id` of the open query in the coordinator. The replica answers with it.
+    pub open_query_id: u32,
+    /// The client that owns the query in the coordinator.
+    pub client_id: u32,
+    /// The replica should read the replicated data it stores, not its owned data.
+    pub replication: bool,
+    /// Keyspace on which the query acts.
+    pub keyspace_name: String,
+    /// The consistency level the client asked for.
+    pub consistency: ConsistencyLevel,
+    /// The timestamp when the coordinator node received the query.
+    pub timestamp: i64,
+}
+
+fn consistency_to_byte(consistency: ConsistencyLevel) -> u8 {
+    match consistency {
+        ConsistencyLevel::Any => 0x00,
+        ConsistencyLevel::One => 0x01,
+        ConsistencyLevel::Two => 0x02,
+        ConsistencyLevel::Three => 0x03,
+        ConsistencyLevel::Quorum => 0x04,
+        ConsistencyLevel::All => 0x05,
+        ConsistencyLevel::LocalQuorum => 0x06,
+        ConsistencyLevel::LocalOne => 0x07,
+        ConsistencyLevel::EachQuorum => 0x08,
+    }
+}
+
+fn consistency_from_byte(byte: u8) -> Result<ConsistencyLevel, InternodeMessageError> {
+    match byte {
+        0x00 => Ok(ConsistencyLevel::Any),
+        0x01 => Ok(ConsistencyLevel::One),
+        0x02 => Ok(ConsistencyLevel::Two),
+        0x03 => Ok(ConsistencyLevel::Three),
+        0x04 => Ok(ConsistencyLevel::Quorum),
+        0x05 => Ok(ConsistencyLevel::All),
+        0x06 => Ok(ConsistencyLevel::LocalQuorum),
+        0x07 => Ok(ConsistencyLevel::LocalOne),
+        0x08 => Ok(ConsistencyLevel::EachQuorum),
+        _ => Err(InternodeMessageError::Malformed),
+    }
+}
+
+// Lee un string precedido por su largo (`u32`)
+fn read_string(cursor: &mut Cursor<&[u8]>) -> Result<String, InternodeMessageError> {
+    let mut len_bytes = [0u8; 4];
+    cursor
+        .read_exact(&mut len_bytes)
+        .map_err(|_| InternodeMessageError::Malformed)?;
+
+    let mut string_bytes = vec![0u8; u32::from_be_bytes(len_bytes) as usize];
+    cursor
+        .read_exact(&mut string_bytes)
+        .map_err(|_| InternodeMessageError::Malformed)?;
+
+    String::from_utf8(string_bytes).map_err(|_| InternodeMessageError::Malformed)
+}
+
+impl InternodeSerializable for InternodeReadRequest {
+    /// ```md
+    /// 0    8    16   24   32
+    /// +----+----+----+----+
+    /// |   open_query_id   |
+    /// +----+----+----+----+
+    /// |     client_id     |
+    /// +----+----+----+----+
+    /// |     timestamp     |
+    /// +----+----+----+----+
+    /// |     timestamp     |
+    /// +----+----+----+----+
+    /// |rep |cons| keyspace_
+    /// +----+----+----+----+
+    /// |name_len |keyspace_
+    /// +----+----+----+----+
+    /// |        ...        |
+    /// +----+----+----+----+
+    /// |   select_length   |
+    /// +----+----+----+----+
+    /// |      select       |
+    /// |        ...        |
+    /// +----+----+----+----+
+    /// ```
+    /// Serializes the `InternodeReadRequest` struct into a byte vector.\
+    /// The `SELECT` is written as the CQL string of `Select::serialize`.
+    fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        bytes.extend(&self.open_query_id.to_be_bytes());
+        bytes.extend(&self.client_id.to_be_bytes());
+        bytes.extend(&self.timestamp.to_be_bytes());
+
+        bytes.push(self.replication as u8);
+        bytes.push(consistency_to_byte(self.consistency));
+
+        bytes.extend(&(self.keyspace_name.len() as u32).to_be_bytes());
+        bytes.extend(self.keyspace_name.as_bytes());
+
+        let select = self.select.serialize();
+        bytes.extend(&(select.len() as u32).to_be_bytes());
+        bytes.extend(select.as_bytes());
+
+        bytes
+    }
+
+    /// Deserializes a byte vector into an `InternodeReadRequest` struct.
+    fn from_bytes(bytes: &[u8]) -> Result<Self, InternodeMessageError>
+    where
+        Self: Sized,
+    {
+        let mut cursor = Cursor::new(bytes);
+
+        let mut open_query_id_bytes = [0u8; 4];
+        cursor
+            .read_exact(&mut open_query_id_bytes)
+            .map_err(|_| InternodeMessageError::Malformed)?;
+        let open_query_id = u32::from_be_bytes(open_query_id_bytes);
+
+        let mut client_id_bytes = [0u8; 4];
+        cursor
+            .read_exact(&mut client_id_bytes)
+            .map_err(|_| InternodeMessageError::Malformed)?;
+        let client_id = u32::from_be_bytes(client_id_bytes);
+
+        let mut timestamp_bytes = [0u8; 8];
+        cursor
+            .read_exact(&mut timestamp_bytes)
+            .map_err(|_| InternodeMessageError::Malformed)?;
+        let timestamp = i64::from_be_bytes(timestamp_bytes);
+
+        let mut flags = [0u8; 2];
+        cursor
+            .read_exact(&mut flags)
+            .map_err(|_| InternodeMessageError::Malformed)?;
+        let replication = flags[0] != 0;
+        let consistency = consistency_from_byte(flags[1])?;
+
+        let keyspace_name = read_string(&mut cursor)?;
+        let select = Select::deserialize(&read_string(&mut cursor)?)
+            .map_err(|_| InternodeMessageError::Malformed)?;
+
+        Ok(InternodeReadRequest {
+            select,
+            open_query_id,
+            client_id,
+            replication,
+            keyspace_name,
+            consistency,
+            timestamp,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_request(consistency: ConsistencyLevel) -> InternodeReadRequest {
+        InternodeReadRequest {
+            select: Select::deserialize("SELECT id, origin FROM sky.flights WHERE id = 7").unwrap(),
+            open_query_id: 42,
+            client_id: 3,
+            replication: true,
+            keyspace_name: "sky".to_string(),
+            consistency,
+            timestamp: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn test_read_request_round_trip() {
+        for consistency in [
+            ConsistencyLevel::Any,
+            ConsistencyLevel::One,
+            ConsistencyLevel::Two,
+            ConsistencyLevel::Three,
+            ConsistencyLevel::Quorum,
+            ConsistencyLevel::All,
+        ] {
+            let request = read_request(consistency);
+
+            let parsed = InternodeReadRequest::from_bytes(&request.as_bytes()).unwrap();
+
+            assert_eq!(parsed, request);
+        }
+    }
+
+    #[test]
+    fn test_read_request_with_unknown_consistency_is_malformed() {
+        let mut bytes = read_request(ConsistencyLevel::Quorum).as_bytes();
+        // La consistencia va despues de los ids, el timestamp y el flag de replicacion
+        bytes[17] = 0x7f;
+
+        assert_eq!(
+            InternodeReadRequest::from_bytes(&bytes),
+            Err(InternodeMessageError::Malformed)
+        );
+    }
+}
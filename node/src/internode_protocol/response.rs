@@ -1,14 +1,52 @@
 //! TODO: Add documentation
 
 use super::{message::InternodeMessageError, InternodeSerializable};
+use crate::NodeError;
+use native_protocol::messages::error::{Error, ErrorCode};
 use std::io::{Cursor, Read};
 
 /// The status of a response sent by a node in response of a coordinator query.
-/// It can be either `Ok` or `Error`.
+/// It can be either `Ok` or `Error`, the latter with the reason of the failure.
 #[derive(Debug, PartialEq, Clone)]
 pub enum InternodeResponseStatus {
-    Ok = 0x00,
-    Error = 0x01,
+    Ok,
+    Error(InternodeErrorDetail),
+}
+
+/// Why a query failed in the node that answers with an error.
+///
+/// ### Fields
+/// - `code`: The native protocol error code to report to the client.
+/// - `message`: A readable description of the error.
+#[derive(Debug, PartialEq, Clone)]
+pub struct InternodeErrorDetail {
+    pub code: u32,
+    pub message: String,
+}
+
+impl InternodeErrorDetail {
+    /// Creates a new `InternodeErrorDetail`.
+    pub fn new(code: ErrorCode, message: String) -> Self {
+        Self {
+            code: code.to_u32(),
+            message,
+        }
+    }
+
+    /// Converts the detail into the native protocol error sent to the client.\
+    /// Codes without a native variant are reported as a `ServerError` with the same message.
+    pub fn to_native_error(&self) -> Error {
+        ErrorCode::from_u32(self.code)
+            .and_then(|code| Error::from_code(code, self.message.clone()))
+            .unwrap_or_else(|_| Error::ServerError(self.message.clone()))
+    }
+}
+
+impl From<&NodeError> for InternodeErrorDetail {
+    fn from(error: &NodeError) -> Self {
+        let error = Error::from(error);
+        InternodeErrorDetail::new(error.code(), error.message().to_string())
+    }
 }
 
 /// The content of a response sent by a node in response of a coordinator query.
@@ -118,7 +156,7 @@ impl InternodeSerializable for InternodeResponseContent {
         let mut columns_len_bytes = [0u8; 4];
         cursor
             .read_exact(&mut columns_len_bytes)
-            .map_err(|_| InternodeMessageError)?;
+            .map_err(|_| InternodeMessageError::Malformed)?;
         let columns_len = u32::from_be_bytes(columns_len_bytes) as usize;
 
         let mut columns = Vec::with_capacity(columns_len);
@@ -126,14 +164,15 @@ impl InternodeSerializable for InternodeResponseContent {
             let mut column_len_bytes = [0u8; 4];
             cursor
                 .read_exact(&mut column_len_bytes)
-                .map_err(|_| InternodeMessageError)?;
+                .map_err(|_| InternodeMessageError::Malformed)?;
             let column_len = u32::from_be_bytes(column_len_bytes) as usize;
 
             let mut column_bytes = vec![0u8; column_len];
             cursor
                 .read_exact(&mut column_bytes)
-                .map_err(|_| InternodeMessageError)?;
-            let column = String::from_utf8(column_bytes).map_err(|_| InternodeMessageError)?;
+                .map_err(|_| InternodeMessageError::Malformed)?;
+            let column =
+                String::from_utf8(column_bytes).map_err(|_| InternodeMessageError::Malformed)?;
 
             columns.push(column);
         }
@@ -141,7 +180,7 @@ impl InternodeSerializable for InternodeResponseContent {
         let mut select_columns_len_bytes = [0u8; 4];
         cursor
             .read_exact(&mut select_columns_len_bytes)
-            .map_err(|_| InternodeMessageError)?;
+            .map_err(|_| InternodeMessageError::Malformed)?;
         let select_columns_len = u32::from_be_bytes(select_columns_len_bytes) as usize;
 
         let mut select_columns = Vec::with_capacity(select_columns_len);
@@ -149,15 +188,15 @@ impl InternodeSerializable for InternodeResponseContent {
             let mut select_column_len_bytes = [0u8; 4];
             cursor
                 .read_exact(&mut select_column_len_bytes)
-                .map_err(|_| InternodeMessageError)?;
+                .map_err(|_| InternodeMessageError::Malformed)?;
             let select_column_len = u32::from_be_bytes(select_column_len_bytes) as usize;
 
             let mut select_column_bytes = vec![0u8; select_column_len];
             cursor
                 .read_exact(&mut select_column_bytes)
-                .map_err(|_| InternodeMessageError)?;
-            let select_column =
-                String::from_utf8(select_column_bytes).map_err(|_| InternodeMessageError)?;
+                .map_err(|_| InternodeMessageError::Malformed)?;
+            let select_column = String::from_utf8(select_column_bytes)
+                .map_err(|_| InternodeMessageError::Malformed)?;
 
             select_columns.push(select_column);
         }
@@ -165,7 +204,7 @@ impl InternodeSerializable for InternodeResponseContent {
         let mut values_len_bytes = [0u8; 4];
         cursor
             .read_exact(&mut values_len_bytes)
-            .map_err(|_| InternodeMessageError)?;
+            .map_err(|_| InternodeMessageError::Malformed)?;
         let values_len = u32::from_be_bytes(values_len_bytes) as usize;
 
         let mut values = Vec::with_capacity(values_len);
@@ -174,7 +213,7 @@ impl InternodeSerializable for InternodeResponseContent {
             let mut value_len_bytes = [0u8; 4];
             cursor
                 .read_exact(&mut value_len_bytes)
-                .map_err(|_| InternodeMessageError)?;
+                .map_err(|_| InternodeMessageError::Malformed)?;
             let value_len = u32::from_be_bytes(value_len_bytes) as usize;
 
             let mut value = Vec::with_capacity(value_len);
@@ -182,15 +221,15 @@ impl InternodeSerializable for InternodeResponseContent {
                 let mut value_part_len_bytes = [0u8; 4];
                 cursor
                     .read_exact(&mut value_part_len_bytes)
-                    .map_err(|_| InternodeMessageError)?;
+                    .map_err(|_| InternodeMessageError::Malformed)?;
                 let value_part_len = u32::from_be_bytes(value_part_len_bytes) as usize;
 
                 let mut value_part_bytes = vec![0u8; value_part_len];
                 cursor
                     .read_exact(&mut value_part_bytes)
-                    .map_err(|_| InternodeMessageError)?;
-                let value_part =
-                    String::from_utf8(value_part_bytes).map_err(|_| InternodeMessageError)?;
+                    .map_err(|_| InternodeMessageError::Malformed)?;
+                let value_part = String::from_utf8(value_part_bytes)
+                    .map_err(|_| InternodeMessageError::Malformed)?;
 
                 value.push(value_part);
             }
@@ -250,6 +289,17 @@ impl InternodeSerializable for InternodeResponse {
     /// |      content      |
     /// +----+----+----+----+
     /// ```
+    /// If the status is `Error`, the status byte is followed by the error detail:
+    /// ```md
+    /// 0    8    16   24   32
+    /// +----+----+----+----+
+    /// |stat|   error_code |
+    /// +----+----+----+----+
+    /// |code| msg_len |msg |
+    /// +----+----+----+----+
+    /// |     message...    |
+    /// +----+----+----+----+
+    /// ```
     /// Serializes the `InternodeResponse` into a `Vec<u8>`.
     fn as_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
@@ -257,12 +307,16 @@ impl InternodeSerializable for InternodeResponse {
         // Serializa el ID de la query abierta
         bytes.extend(&self.open_query_id.to_be_bytes());
 
-        // Serializa el estado
-        let status_byte = match self.status {
-            InternodeResponseStatus::Ok => 0x00,
-            InternodeResponseStatus::Error => 0x01,
-        };
-        bytes.push(status_byte);
+        // Serializa el estado, y el detalle si es un error
+        match &self.status {
+            InternodeResponseStatus::Ok => bytes.push(0x00),
+            InternodeResponseStatus::Error(detail) => {
+                bytes.push(0x01);
+                bytes.extend(detail.code.to_be_bytes());
+                bytes.extend((detail.message.len() as u16).to_be_bytes());
+                bytes.extend(detail.message.as_bytes());
+            }
+        }
 
         // Serializa el contenido
         if let Some(content) = &self.content {
@@ -283,25 +337,47 @@ impl InternodeSerializable for InternodeResponse {
         let mut open_query_id_bytes = [0u8; 4];
         cursor
             .read_exact(&mut open_query_id_bytes)
-            .map_err(|_| InternodeMessageError)?;
+            .map_err(|_| InternodeMessageError::Malformed)?;
         let open_query_id = u32::from_be_bytes(open_query_id_bytes);
 
         // Deserializa el estado
         let mut status_byte = [0u8; 1];
         cursor
             .read_exact(&mut status_byte)
-            .map_err(|_| InternodeMessageError)?;
+            .map_err(|_| InternodeMessageError::Malformed)?;
         let status = match status_byte[0] {
             0x00 => InternodeResponseStatus::Ok,
-            0x01 => InternodeResponseStatus::Error,
-            _ => return Err(InternodeMessageError),
+            0x01 => {
+                let mut code_bytes = [0u8; 4];
+                cursor
+                    .read_exact(&mut code_bytes)
+                    .map_err(|_| InternodeMessageError::Malformed)?;
+
+                let mut message_len_bytes = [0u8; 2];
+                cursor
+                    .read_exact(&mut message_len_bytes)
+                    .map_err(|_| InternodeMessageError::Malformed)?;
+                let message_len = u16::from_be_bytes(message_len_bytes) as usize;
+
+                let mut message_bytes = vec![0u8; message_len];
+                cursor
+                    .read_exact(&mut message_bytes)
+                    .map_err(|_| InternodeMessageError::Malformed)?;
+
+                InternodeResponseStatus::Error(InternodeErrorDetail {
+                    code: u32::from_be_bytes(code_bytes),
+                    message: String::from_utf8(message_bytes)
+                        .map_err(|_| InternodeMessageError::Malformed)?,
+                })
+            }
+            _ => return Err(InternodeMessageError::Malformed),
         };
 
         // Deserializa el contenido
         let mut content_len_bytes = [0u8; 2];
         cursor
             .read_exact(&mut content_len_bytes)
-            .map_err(|_| InternodeMessageError)?;
+            .map_err(|_| InternodeMessageError::Malformed)?;
         let content_len = u16::from_be_bytes(content_len_bytes);
 
         let content = if content_len == 0 {
@@ -310,10 +386,10 @@ impl InternodeSerializable for InternodeResponse {
             let mut content_bytes = vec![0u8; content_len as usize];
             cursor
                 .read_exact(&mut content_bytes)
-                .map_err(|_| InternodeMessageError)?;
+                .map_err(|_| InternodeMessageError::Malformed)?;
             Some(
                 InternodeResponseContent::from_bytes(&content_bytes)
-                    .map_err(|_| InternodeMessageError)?,
+                    .map_err(|_| InternodeMessageError::Malformed)?,
             )
         };
 
@@ -350,7 +426,7 @@ mod tests {
 
         let status_byte = match response.status {
             InternodeResponseStatus::Ok => 0x00,
-            InternodeResponseStatus::Error => 0x01,
+            InternodeResponseStatus::Error(_) => 0x01,
         };
         bytes.push(status_byte);
 
@@ -399,7 +475,7 @@ mod tests {
 
         let content_bytes = content.as_bytes();
 
-        let mut bytes = Vec::new();
+        let mut bytes: Vec<u8> = Vec::new();
 
         let columns_len = content.columns.len() as u32;
         bytes.extend(&columns_len.to_be_bytes());
@@ -475,7 +551,7 @@ mod tests {
 
         let status_byte = match response.status {
             InternodeResponseStatus::Ok => 0x00,
-            InternodeResponseStatus::Error => 0x01,
+            InternodeResponseStatus::Error(_) => 0x01,
         };
         bytes.push(status_byte);
 
@@ -499,4 +575,68 @@ mod tests {
 
         assert_eq!(parsed_response, response);
     }
+
+    #[test]
+    fn test_error_response_keeps_detail() {
+        let response = InternodeResponse {
+            open_query_id: 7,
+            status: InternodeResponseStatus::Error(InternodeErrorDetail::new(
+                ErrorCode::Overloaded,
+                "too many queries".to_string(),
+            )),
+            content: None,
+        };
+
+        let parsed_response = InternodeResponse::from_bytes(&response.as_bytes()).unwrap();
+
+        assert_eq!(parsed_response, response);
+        if let InternodeResponseStatus::Error(detail) = parsed_response.status {
+            assert_eq!(
+                detail.to_native_error(),
+                Error::Overloaded("too many queries".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_error_detail_keeps_the_kind_of_storage_and_query_errors() {
+        use crate::storage_engine::errors::StorageEngineError;
+        use query_creator::errors::CQLError;
+
+        let code_of = |error: NodeError| InternodeErrorDetail::from(&error).code;
+
+        assert_eq!(
+            code_of(NodeError::StorageEngineError(
+                StorageEngineError::ColumnNotFound
+            )),
+            ErrorCode::Invalid.to_u32()
+        );
+        assert_eq!(
+            code_of(NodeError::StorageEngineError(
+                StorageEngineError::TableHeaderMismatch
+            )),
+            ErrorCode::AlreadyExists.to_u32()
+        );
+        assert_eq!(
+            code_of(NodeError::StorageEngineError(
+                StorageEngineError::StorageFull {
+                    keyspace: "sky".to_string(),
+                    quota: 1,
+                }
+            )),
+            ErrorCode::Overloaded.to_u32()
+        );
+        assert_eq!(
+            code_of(NodeError::StorageEngineError(StorageEngineError::IoError)),
+            ErrorCode::ServerError.to_u32()
+        );
+        assert_eq!(
+            code_of(NodeError::CQLError(CQLError::InvalidColumn)),
+            ErrorCode::Invalid.to_u32()
+        );
+        assert_eq!(
+            code_of(NodeError::CQLError(CQLError::TableAlreadyExist)),
+            ErrorCode::AlreadyExists.to_u32()
+        );
+    }
 }
@@ -2,16 +2,22 @@
 
 use crate::internode_protocol::message::{InternodeMessage, InternodeMessageContent};
 use crate::internode_protocol::query::InternodeQuery;
-use crate::internode_protocol::response::{InternodeResponse, InternodeResponseStatus};
-use crate::open_query_handler::OpenQueryHandler;
+use crate::internode_protocol::read_request::InternodeReadRequest;
+use crate::internode_protocol::response::{
+    InternodeErrorDetail, InternodeResponse, InternodeResponseStatus,
+};
+use crate::open_query_handler::{ConsistencyLevel, OpenQueryHandler};
+use crate::storage_engine::{cql_literal, StorageEngine};
 use crate::utils::{check_keyspace, check_table, connect_and_send_message};
-use crate::{storage_engine, Node, NodeError, Query, QueryExecution, INTERNODE_PORT};
-use chrono::Utc;
+use crate::{storage_engine, Node, NodeError, Query, QueryExecution};
 use gossip::messages::GossipMessage;
-use gossip::structures::application_state::TableSchema;
+use gossip::structures::application_state::{DataDigest, TableSchema};
 use logger::{Color, Logger};
 use native_protocol::frame::Frame;
 use native_protocol::messages::error;
+use native_protocol::messages::result::rows::{
+    split_storage_values, unescape_storage_text, ColumnType,
+};
 use partitioner::Partitioner;
 use query_creator::clauses::keyspace::{
     alter_keyspace_cql::AlterKeyspace, create_keyspace_cql::CreateKeyspace,
@@ -23,17 +29,54 @@ use query_creator::clauses::table::{
 use query_creator::clauses::types::column::Column;
 use query_creator::clauses::use_cql::Use;
 use query_creator::clauses::{
-    delete_cql::Delete, insert_cql::Insert, select_cql::Select, update_cql::Update,
+    delete_cql::Delete,
+    insert_cql::Insert,
+    select_cql::{CellFunction, Select, COUNT_ALL},
+    update_cql::Update,
 };
-use query_creator::{CreateClientResponse, NeedsKeyspace, NeedsTable, QueryCreator};
-use std::collections::HashMap;
+use query_creator::errors::CQLError;
+use query_creator::{
+    create_rows_frame, ConditionalWrite, CreateClientResponse, NeedsKeyspace, NeedsTable,
+    QueryCreator,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::net::{Ipv4Addr, TcpStream};
-use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
 /// Struct that represents the handler for internode communication protocol.
 pub struct InternodeProtocolHandler;
 
+/// What the coordinator of a read knows of the replicas of its keyspace, used to backfill the
+/// replicas that are missing the rows read.
+#[derive(Debug, Clone, Default)]
+pub struct ReplicaDigests {
+    /// Replicas each partition of the keyspace has.
+    pub replication_factor: usize,
+    /// Digests of the tables gossiped by each node, as in `Node::data_digests_snapshot`.
+    pub data_digests: BTreeMap<Ipv4Addr, BTreeMap<String, DataDigest>>,
+}
+
+/// What the coordinator of a query uses to close it: the keyspace it runs on and how to
+/// reach the replicas the read repair writes to.
+pub struct CoordinatorContext {
+    /// Address of this node, the coordinator of the query.
+    pub self_ip: Ipv4Addr,
+    /// Port the other nodes listen on for internode messages.
+    pub internode_port: u16,
+    /// Keyspace of the query, empty if it has none.
+    pub keyspace_name: String,
+    /// Open connections to the other nodes.
+    pub connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+    /// Partitioner of the cluster, used to find the replicas of a row.
+    pub partitioner: Partitioner,
+    /// What the coordinator knows of the replicas of the keyspace.
+    pub replica_digests: ReplicaDigests,
+    /// Storage of this node, where the rows it has to repair are written.
+    pub storage: StorageEngine,
+    /// Logger of this node.
+    pub logger: Logger,
+}
+
 impl InternodeProtocolHandler {
     /// Creates a new `InternodeProtocolHandler` for handling internode commands
     /// and responses between nodes in a distributed setting.
@@ -58,6 +101,9 @@ impl InternodeProtocolHandler {
     ///       - `InternodeMessageContent::Query`: Represents a query to be executed on this node.
     ///       - `InternodeMessageContent::Response`: Represents a response to a previously issued query.
     ///       - `InternodeMessageContent::Gossip`: Represents a gossip protocol message for cluster state sharing.
+    ///       - `InternodeMessageContent::ReadRequest`: Represents a `SELECT` sent by a coordinator to this replica.
+    ///       - `InternodeMessageContent::SchemaPull`: A joining node asks for the schema of this node.
+    ///       - `InternodeMessageContent::SchemaPush`: The schema sent back by the node this one pulled it from.
     ///     - `from`: The identifier of the node that sent the message.
     /// - `connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>`
     ///   - A thread-safe map of active connections to other nodes in the cluster.
@@ -80,7 +126,13 @@ impl InternodeProtocolHandler {
     /// 3. **Gossip Handling**:
     ///    - If the message content is `InternodeMessageContent::Gossip`, calls `handle_gossip_command`.
     ///    - Updates the node's internal state based on the gossip protocol message.
-    /// 4. **Error Handling**:
+    /// 4. **Read Request Handling**:
+    ///    - If the message content is `InternodeMessageContent::ReadRequest`, runs the `SELECT` locally and
+    ///      answers the coordinator with the open query id of the request.
+    /// 5. **Schema Sync**:
+    ///    - A `SchemaPull` is answered with a `SchemaPush` of the whole schema this node knows.
+    ///    - A `SchemaPush` is merged into the schema of this node, see `Node::apply_pushed_schema`.
+    /// 6. **Error Handling**:
     ///    - Any errors encountered during the handling of commands are returned as `NodeError`.
     ///
     /// # Message Types
@@ -136,9 +188,63 @@ impl InternodeProtocolHandler {
                 self.handle_gossip_command(node, &message, connections)?;
                 Ok(())
             }
+            InternodeMessageContent::ReadRequest(request) => {
+                log.info(
+                    &format!(
+                        "INTERNODE (Query: {:?}): I RECEIVED READ {:?} ({:?}) from {:?}",
+                        request.open_query_id,
+                        request.select.serialize(),
+                        request.consistency,
+                        message.from
+                    ),
+                    Color::Blue,
+                    true,
+                )?;
+                self.handle_read_request_command(node, request, connections, message.from)
+            }
+            InternodeMessageContent::SchemaPull => {
+                log.info(
+                    &format!("INTERNODE: I RECEIVED SCHEMA PULL from {:?}", message.from),
+                    Color::Cyan,
+                    true,
+                )?;
+                Self::handle_schema_pull_command(node, message.from, connections)
+            }
+            InternodeMessageContent::SchemaPush(schema) => {
+                log.info(
+                    &format!("INTERNODE: I RECEIVED SCHEMA PUSH from {:?}", message.from),
+                    Color::Cyan,
+                    true,
+                )?;
+                node.lock()?.apply_pushed_schema(&schema)
+            }
         }
     }
 
+    // Answers a node that is joining with the whole schema this node knows
+    fn handle_schema_pull_command(
+        node: &Arc<Mutex<Node>>,
+        node_ip: Ipv4Addr,
+        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+    ) -> Result<(), NodeError> {
+        let (self_ip, internode_port, schema) = {
+            let guard_node = node.lock()?;
+            let self_ip = guard_node.get_ip();
+            let schema = guard_node
+                .gossiper
+                .get_merged_schema(self_ip)
+                .map_err(|_| NodeError::GossipError)?;
+            (self_ip, guard_node.internode_port, schema)
+        };
+
+        connect_and_send_message(
+            node_ip,
+            internode_port,
+            connections,
+            InternodeMessage::new(self_ip, InternodeMessageContent::SchemaPush(schema)),
+        )
+    }
+
     /// Adds an OK response to an open query, determines if the query is complete, and sends the final response to the client.
     ///
     /// # Purpose
@@ -153,28 +259,21 @@ impl InternodeProtocolHandler {
     ///   - The response received from another node, containing query results or status.
     /// - `open_query_id: i32`
     ///   - The unique identifier of the open query being processed.
-    /// - `keyspace_name: String`
-    ///   - The name of the keyspace associated with the query.
     /// - `table: Option<TableSchema>`
     ///   - An optional table schema that defines the structure of the table involved in the query.
     /// - `columns: Vec<Column>`
     ///   - A vector of column metadata associated with the query, used for filtering and organizing results.
-    /// - `self_ip: Ipv4Addr`
-    ///   - The IP address of the current node processing the query.
     /// - `from: Ipv4Addr`
     ///   - The IP address of the node that sent the response.
-    /// - `connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>`
-    ///   - A thread-safe map of connections to other nodes in the cluster.
-    ///   - Keys are node addresses as strings, and values are `TcpStream` objects for internode communication.
-    /// - `partitioner: Partitioner`
-    ///   - The partitioner used to distribute and retrieve data within the cluster.
-    /// - `storage_path: PathBuf`
-    ///   - The file system path for accessing local storage.
+    /// - `context: &CoordinatorContext`
+    ///   - The keyspace of the query and what this node, its coordinator, uses to reach the
+    ///     replicas and to write the rows it has to repair in its own storage.
     ///
     /// # Returns
-    /// - `Result<(), NodeError>`
+    /// - `Result<bool, NodeError>`
     ///   - On success:
-    ///     - Returns `Ok(())`, indicating that the response was successfully added and processed.
+    ///     - Returns `Ok(true)` if the read repair rewrote rows in this node's own storage, so the
+    ///       caller can drop the reads it cached, and `Ok(false)` otherwise.
     ///   - On failure:
     ///     - Returns `Err(NodeError)` with details about the error encountered during processing.
     ///
@@ -188,6 +287,8 @@ impl InternodeProtocolHandler {
     ///      - Performs a read repair operation to ensure consistency across nodes:
     ///        - Identifies the most up-to-date row based on the responses.
     ///        - Updates inconsistent nodes to align with the most recent data.
+    ///    - Conditional writes skip the read repair: the only row is the `[applied]` flag,
+    ///      `true` when no replica reported the condition as unmet.
     /// 3. **Filter and Join Columns**:
     ///    - Filters and organizes the rows based on the query's select columns and metadata from the response.
     /// 4. **Create Client Response**:
@@ -210,16 +311,11 @@ impl InternodeProtocolHandler {
         query_handler: &mut OpenQueryHandler,
         response: &InternodeResponse,
         open_query_id: i32,
-        keyspace_name: String,
         table: Option<TableSchema>,
         columns: Vec<Column>,
-        self_ip: Ipv4Addr,
         from: Ipv4Addr,
-        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
-        partitioner: Partitioner,
-        storage_path: PathBuf,
-        logger: Logger,
-    ) -> Result<(), NodeError> {
+        context: &CoordinatorContext,
+    ) -> Result<bool, NodeError> {
         if let Some(open_query) =
             query_handler.add_ok_response_and_get_if_closed(open_query_id, response.clone(), from)
         {
@@ -228,45 +324,82 @@ impl InternodeProtocolHandler {
             // and do READ REPAIR
 
             let mut rows = vec![];
-            if let Some(table) = table {
-                rows = Self::read_repair(
+            let mut repaired_self = false;
+            // Tipos de las columnas pedidas por un SELECT, tomados del schema de la tabla
+            let mut select_types = None;
+            if open_query.get_query().is_conditional() {
+                // Una escritura condicional se reporta aplicada solo si todas las replicas la aplicaron
+                let applied = contents_of_different_nodes.iter().all(|(_, response)| {
+                    response.content.as_ref().is_none_or(|content| {
+                        content.values.first().and_then(|row| row.first())
+                            != Some(&"false".to_string())
+                    })
+                });
+                rows = vec![applied.to_string()];
+            } else if let Some(table) = table {
+                (rows, repaired_self) = Self::read_repair(
                     contents_of_different_nodes,
                     columns.clone(),
-                    self_ip,
-                    keyspace_name.clone(),
                     table.clone(),
-                    connections,
-                    partitioner,
-                    storage_path,
+                    context,
                 )?;
+                // Cada nodo ordeno y limito solo sus filas, hay que hacerlo de nuevo con todas.
+                // Con `GROUP BY` los nodos devuelven las filas de los grupos, que se agrupan
+                // recien aca, una vez reconciliadas entre las replicas
+                let mut group_columns = None;
+                if let (Query::Select(mut select), Some(content)) =
+                    (open_query.get_query(), &response.content)
+                {
+                    if select.is_grouped() {
+                        select.columns = content.select_columns.clone();
+                        rows = storage_engine::select::group_merged_rows(rows, &select, &table)?;
+                        group_columns = Some(select.group_by.into_iter().chain(select.columns));
+                    } else {
+                        rows = storage_engine::select::order_and_limit_merged_rows(
+                            rows, &select, &table,
+                        )?;
+                    }
+                }
 
                 rows = if let Some(content) = &response.content {
-                    Self::filter_and_join_columns(
-                        rows,
-                        content.select_columns.clone(),
-                        content.columns.clone(),
-                    )
+                    // El resto de las queries arma su propia respuesta, p. ej. un SchemaChange
+                    if let Query::Select(_) = open_query.get_query() {
+                        select_types = Some(Self::select_column_types(
+                            &columns,
+                            &content.select_columns,
+                        )?);
+                    }
+                    // Las filas de los grupos tienen las columnas del `GROUP BY` y las pedidas
+                    let row_columns = match group_columns {
+                        Some(group_columns) => group_columns.collect(),
+                        None => content.columns.clone(),
+                    };
+                    Self::filter_and_join_columns(rows, content.select_columns.clone(), row_columns)
                 } else {
                     vec![]
                 };
             };
 
             let connection = open_query.get_connection();
-            let frame =
-                open_query
-                    .get_query()
-                    .create_client_response(columns, keyspace_name, rows)?;
+            let frame = match select_types {
+                Some(col_types) => create_rows_frame(col_types, &rows)?,
+                None => open_query.get_query().create_client_response(
+                    columns,
+                    context.keyspace_name.clone(),
+                    rows,
+                )?,
+            };
 
-            logger.info(
+            context.logger.info(
                 &format!("NATIVE: I sent FRAME RESPONSE to client",),
                 Color::Yellow,
                 true,
             )?;
 
             connection.send(frame).map_err(|_| NodeError::OtherError)?;
-            Ok(())
+            Ok(repaired_self)
         } else {
-            Ok(())
+            Ok(false)
         }
     }
 
@@ -286,25 +419,18 @@ impl InternodeProtocolHandler {
     /// - `columns: Vec<Column>`
     ///   - A vector of column metadata that defines the structure of the table. This includes information about
     ///     primary keys and clustering columns used to identify and order rows.
-    /// - `self_ip: Ipv4Addr`
-    ///   - The IP address of the current node performing the read repair.
-    /// - `keyspace_name: String`
-    ///   - The name of the keyspace associated with the table being queried.
     /// - `table: TableSchema`
     ///   - The schema of the table being queried. This includes details about columns, keys, and clustering order.
-    /// - `connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>`
-    ///   - A thread-safe map of active connections to other nodes in the cluster.
-    ///     - Keys are node addresses as strings.
-    ///     - Values are thread-safe `TcpStream` objects for internode communication.
-    /// - `partitioner: Partitioner`
-    ///   - The partitioner responsible for determining the placement of data in the cluster based on primary keys.
-    /// - `storage_path: PathBuf`
-    ///   - The file system path for accessing local storage.
+    /// - `context: &CoordinatorContext`
+    ///   - The node performing the read repair and the keyspace of the table. Its `replica_digests` hold the
+    ///     replication factor of the keyspace and the table digests gossiped by each node, used to find
+    ///     the replicas that own a row but were not asked for it and may be missing it.
     ///
     /// # Returns
-    /// - `Result<Vec<String>, NodeError>`
+    /// - `Result<(Vec<String>, bool), NodeError>`
     ///   - On success:
-    ///     - Returns a `Vec<String>` containing the rows of the latest consistent data, formatted as strings.
+    ///     - Returns a `Vec<String>` containing the rows of the latest consistent data, formatted as strings,
+    ///       and whether any of them had to be rewritten in this node's own storage.
     ///   - On failure:
     ///     - Returns `Err(NodeError)` if an error occurs during the repair process or node communication.
     ///
@@ -316,10 +442,15 @@ impl InternodeProtocolHandler {
     ///    - Uses the `find_latest_versions` helper function to construct a mapping of keys to their latest values.
     /// 3. **Repair Outdated Nodes**:
     ///    - Updates nodes with outdated data by sending the latest version of inconsistent rows:
-    ///      - If the outdated node is not the current node (`self_ip`), sends an update query to the affected node.
+    ///      - If the outdated node is not the current node (`context.self_ip`), sends an update query to the affected node.
     ///      - If the outdated node is the current node, applies the update locally using the storage engine.
     ///    - Uses the `repair_nodes` helper function for this step.
-    /// 4. **Return Consistent Data**:
+    /// 4. **Backfill Missing Replicas**:
+    ///    - Sends the latest version of each live row to the replicas that own it but do not have it: the ones
+    ///      that answered without the row, and the ones that were not asked (as in a read at `ONE`) whose
+    ///      gossiped digest of the table differs from the one of the replica that had the row.
+    ///    - Uses the `backfill_missing_replicas` helper function for this step.
+    /// 5. **Return Consistent Data**:
     ///    - Returns the rows corresponding to the latest consistent data after performing repairs.
     ///
     /// # Key Internal Logic
@@ -329,6 +460,10 @@ impl InternodeProtocolHandler {
     /// - **Timestamp Comparison**:
     ///   - Timestamps are used to identify the most recent version of a row.
     ///   - Rows with older timestamps are considered outdated and are repaired.
+    /// - **Tombstones**:
+    ///   - A deleted row is reported by its replica as a tombstone. When it is strictly newer than every
+    ///     live version of the same key, the key is left out of the result and replicas that still have
+    ///     the row are repaired with a `DELETE` instead of an `INSERT`.
     /// - **Node Communication**:
     ///   - Uses internode communication to propagate updates to other nodes as part of the repair process.
     ///
@@ -352,13 +487,9 @@ impl InternodeProtocolHandler {
     fn read_repair(
         contents_of_different_nodes: Vec<(Ipv4Addr, InternodeResponse)>,
         columns: Vec<Column>,
-        self_ip: Ipv4Addr,
-        keyspace_name: String,
         table: TableSchema,
-        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
-        partitioner: Partitioner,
-        storage_path: PathBuf,
-    ) -> Result<Vec<String>, NodeError> {
+        context: &CoordinatorContext,
+    ) -> Result<(Vec<String>, bool), NodeError> {
         let primary_key_indices = Self::get_key_indices(&columns, true);
         let clustering_column_indices = Self::get_key_indices(&columns, false);
 
@@ -368,21 +499,26 @@ impl InternodeProtocolHandler {
             &clustering_column_indices,
         );
 
-        let updated_rows = Self::repair_nodes(
+        let backfilled_self = Self::backfill_missing_replicas(
+            &contents_of_different_nodes,
+            &columns,
+            &primary_key_indices,
+            &clustering_column_indices,
+            &latest_versions,
+            &table,
+            context,
+        )?;
+
+        let (rows, repaired_self) = Self::repair_nodes(
             contents_of_different_nodes,
             &columns,
             &primary_key_indices,
             &clustering_column_indices,
             latest_versions,
-            &self_ip,
-            &keyspace_name,
             table,
-            &connections,
-            &partitioner,
-            storage_path,
+            context,
         )?;
-
-        Ok(updated_rows)
+        Ok((rows, repaired_self || backfilled_self))
     }
 
     fn get_key_indices(columns: &[Column], is_partition_key: bool) -> Vec<usize> {
@@ -415,8 +551,8 @@ impl InternodeProtocolHandler {
                         Self::build_key(value, primary_key_indices, clustering_column_indices);
                     let current_timestamp = Self::get_timestamp(value);
 
-                    if let Some((_, latest_timestamp, _)) = latest_versions.get(&key) {
-                        if *latest_timestamp < current_timestamp {
+                    if let Some((_, _, latest_value)) = latest_versions.get(&key) {
+                        if Self::get_version(value) > Self::get_version(latest_value) {
                             latest_versions
                                 .insert(key, (*node_ip, current_timestamp, value.clone()));
                         }
@@ -448,8 +584,23 @@ impl InternodeProtocolHandler {
     }
 
     fn get_timestamp(value: &[String]) -> i64 {
-        let timestamp_index = value.len() - 1;
-        value[timestamp_index].parse::<i64>().unwrap_or(0)
+        Self::get_version(value).0
+    }
+
+    fn is_tombstone(value: &[String]) -> bool {
+        !Self::get_version(value).1
+    }
+
+    // Version de una fila: su timestamp y si esta viva. Un tombstone solo le gana a una
+    // fila viva si es estrictamente mas nuevo, con el mismo timestamp gana la fila viva
+    fn get_version(value: &[String]) -> (i64, bool) {
+        value
+            .last()
+            .map(|time_of_row| {
+                let (timestamp, is_tombstone) = storage_engine::parse_row_time(time_of_row);
+                (timestamp, !is_tombstone)
+            })
+            .unwrap_or((0, true))
     }
 
     fn repair_nodes(
@@ -458,14 +609,12 @@ impl InternodeProtocolHandler {
         primary_key_indices: &[usize],
         clustering_column_indices: &[usize],
         latest_versions: HashMap<String, (Ipv4Addr, i64, Vec<String>)>,
-        self_ip: &Ipv4Addr,
-        keyspace_name: &String,
         table: TableSchema,
-        connections: &Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
-        partitioner: &Partitioner,
-        storage_path: PathBuf,
-    ) -> Result<Vec<String>, NodeError> {
+        context: &CoordinatorContext,
+    ) -> Result<(Vec<String>, bool), NodeError> {
         let mut updated_rows: Vec<String> = Vec::new();
+        // Si alguna fila se corrigio en el propio nodo
+        let mut repaired_self = false;
         let table_name = &table.get_name();
         for (node_ip, response) in &contents_of_different_nodes {
             if let Some(content) = &response.content {
@@ -473,67 +622,205 @@ impl InternodeProtocolHandler {
                     let key =
                         Self::build_key(value, primary_key_indices, clustering_column_indices);
 
-                    if let Some((latest_ip, latest_timestamp, latest_value)) =
-                        latest_versions.get(&key)
-                    {
-                        let current_timestamp = Self::get_timestamp(value);
-
-                        if node_ip != latest_ip && current_timestamp < *latest_timestamp {
-                            let insert_query = Self::generate_insert_query(
-                                keyspace_name,
-                                table_name,
-                                columns,
-                                latest_value,
-                            );
-
+                    if let Some((latest_ip, _, latest_value)) = latest_versions.get(&key) {
+                        if node_ip != latest_ip
+                            && Self::get_version(value) < Self::get_version(latest_value)
+                        {
                             let replication = Self::get_is_replication(
                                 latest_value,
                                 primary_key_indices,
-                                partitioner,
+                                &context.partitioner,
                                 node_ip,
                             )?;
 
-                            if node_ip != self_ip {
-                                Self::send_update_to_node(
-                                    *node_ip,
-                                    connections,
-                                    insert_query,
-                                    self_ip,
-                                    keyspace_name,
-                                    replication,
-                                )?;
-                            } else {
-                                let latest_values = latest_value
-                                    .iter()
-                                    .map(|v| v.as_str())
-                                    .take(latest_value.len() - 1)
-                                    .collect();
-
-                                Self::update_this_node(
-                                    self_ip,
-                                    keyspace_name,
-                                    replication,
+                            if Self::is_tombstone(latest_value) {
+                                // La replica todavia tiene la fila que otra borro
+                                let delete_query = Self::generate_delete_query(
+                                    &context.keyspace_name,
                                     table_name,
-                                    latest_values,
-                                    table.get_clustering_column_in_order(),
                                     columns,
-                                    storage_path.clone(),
-                                )?;
-                                // Opcional: manejar lógica para actualizar el propio nodo si es necesario
+                                    primary_key_indices,
+                                    clustering_column_indices,
+                                    latest_value,
+                                );
+
+                                if *node_ip != context.self_ip {
+                                    Self::send_update_to_node(
+                                        *node_ip,
+                                        delete_query,
+                                        replication,
+                                        Self::get_timestamp(latest_value),
+                                        context,
+                                    )?;
+                                } else {
+                                    repaired_self = true;
+                                    Self::delete_in_this_node(
+                                        &context.keyspace_name,
+                                        replication,
+                                        &table,
+                                        &delete_query,
+                                        Self::get_timestamp(latest_value),
+                                        &context.storage,
+                                    )?;
+                                }
+                                continue;
                             }
+
+                            repaired_self |= Self::write_latest_version(
+                                *node_ip,
+                                latest_value,
+                                replication,
+                                columns,
+                                &table,
+                                context,
+                            )?;
                         }
                     }
                 }
             }
         }
 
+        // Las filas cuya ultima version es un tombstone no se devuelven
         updated_rows.extend(
             latest_versions
                 .into_iter()
+                .filter(|(_, (_, _, value))| !Self::is_tombstone(value))
                 .map(|(_, (_, _, value))| value.join(",")),
         );
 
-        Ok(updated_rows)
+        Ok((updated_rows, repaired_self))
+    }
+
+    // Escribe la ultima version de una fila en `node_ip`; `true` si `node_ip` es este nodo
+    fn write_latest_version(
+        node_ip: Ipv4Addr,
+        latest_value: &[String],
+        replication: bool,
+        columns: &[Column],
+        table: &TableSchema,
+        context: &CoordinatorContext,
+    ) -> Result<bool, NodeError> {
+        let keyspace_name = &context.keyspace_name;
+        let table_name = &table.get_name();
+        if node_ip != context.self_ip {
+            let insert_query =
+                Self::generate_insert_query(keyspace_name, table_name, columns, latest_value);
+            Self::send_update_to_node(
+                node_ip,
+                insert_query,
+                replication,
+                Self::get_timestamp(latest_value),
+                context,
+            )?;
+            return Ok(false);
+        }
+
+        let latest_values = latest_value
+            .iter()
+            .map(|v| v.as_str())
+            .take(latest_value.len() - 1)
+            .collect();
+        Self::update_this_node(
+            keyspace_name,
+            replication,
+            table_name,
+            latest_values,
+            table.get_clustering_column_in_order(),
+            columns,
+            &context.storage,
+            Self::get_timestamp(latest_value),
+        )?;
+        Ok(true)
+    }
+
+    /// Sends the latest version of each live row read to the replicas that own it but may not
+    /// have it, and returns whether this node was one of them.
+    ///
+    /// A replica that answered the read is missing a row if its answer did not have it. A
+    /// replica that was not asked, as happens with reads at `ONE`, is only written to when the
+    /// digest it gossips for the token range of the row differs from the one of the replica the
+    /// row came from, so replicas that agree are not sent rows they already have. The rows keep
+    /// the timestamp they were written with, so a replica with a newer version keeps it.
+    fn backfill_missing_replicas(
+        contents_of_different_nodes: &[(Ipv4Addr, InternodeResponse)],
+        columns: &[Column],
+        primary_key_indices: &[usize],
+        clustering_column_indices: &[usize],
+        latest_versions: &HashMap<String, (Ipv4Addr, i64, Vec<String>)>,
+        table: &TableSchema,
+        context: &CoordinatorContext,
+    ) -> Result<bool, NodeError> {
+        let partitioner = &context.partitioner;
+        let replica_digests = &context.replica_digests;
+        // Las claves que devolvio cada replica que contesto
+        let keys_by_replica: HashMap<Ipv4Addr, HashSet<String>> = contents_of_different_nodes
+            .iter()
+            .map(|(node_ip, response)| {
+                let keys = response
+                    .content
+                    .iter()
+                    .flat_map(|content| &content.values)
+                    .map(|value| {
+                        Self::build_key(value, primary_key_indices, clustering_column_indices)
+                    })
+                    .collect();
+                (*node_ip, keys)
+            })
+            .collect();
+        // Cada nodo anuncia un digest por rango de tokens, identificado por el duenio del rango
+        let digest_of = |node_ip: &Ipv4Addr, range_owner: &Ipv4Addr| {
+            let range_key = format!(
+                "{}.{}@{}",
+                context.keyspace_name,
+                table.get_name(),
+                range_owner
+            );
+            replica_digests
+                .data_digests
+                .get(node_ip)
+                .and_then(|digests| digests.get(&range_key))
+        };
+
+        let mut backfilled_self = false;
+        for (key, (latest_ip, _, latest_value)) in latest_versions {
+            if Self::is_tombstone(latest_value) {
+                continue;
+            }
+            let partition_key: Vec<String> = primary_key_indices
+                .iter()
+                .map(|&index| unescape_storage_text(&latest_value[index]))
+                .collect();
+            let range_owner = partitioner.get_ip_for_partition(&partition_key)?;
+            let owners =
+                partitioner.get_replicas(&partition_key, replica_digests.replication_factor)?;
+
+            for owner in owners.iter().filter(|owner| *owner != latest_ip) {
+                let is_missing = match keys_by_replica.get(owner) {
+                    Some(keys) => !keys.contains(key),
+                    None => digest_of(owner, &range_owner) != digest_of(latest_ip, &range_owner),
+                };
+                if !is_missing {
+                    continue;
+                }
+
+                let replication = Self::get_is_replication(
+                    latest_value,
+                    primary_key_indices,
+                    partitioner,
+                    owner,
+                )?;
+                backfilled_self |= Self::write_latest_version(
+                    *owner,
+                    latest_value,
+                    replication,
+                    columns,
+                    table,
+                    context,
+                )?;
+            }
+        }
+
+        Ok(backfilled_self)
     }
 
     fn get_is_replication(
@@ -545,13 +832,11 @@ impl InternodeProtocolHandler {
         // Construir la clave particionada a partir de los valores de las claves primarias
         let value_partitioner_key: Vec<String> = primary_key_indices
             .iter()
-            .map(|&index| latest_value[index].clone())
+            .map(|&index| unescape_storage_text(&latest_value[index]))
             .collect();
 
-        let value_to_hash = value_partitioner_key.join("");
-
         // Determinar si el nodo necesita replicación
-        let is_replication = partitioner.get_ip(value_to_hash)? != *node_ip;
+        let is_replication = partitioner.get_ip_for_partition(&value_partitioner_key)? != *node_ip;
 
         Ok(is_replication)
     }
@@ -577,7 +862,7 @@ impl InternodeProtocolHandler {
             &latest_value
                 .iter()
                 .take(latest_value.len().saturating_sub(1))
-                .map(|val| format!("'{}'", val))
+                .map(|val| cql_literal(val))
                 .collect::<Vec<String>>()
                 .join(","),
         );
@@ -586,41 +871,88 @@ impl InternodeProtocolHandler {
         insert_query
     }
 
+    fn generate_delete_query(
+        keyspace_name: &String,
+        table_name: &String,
+        columns: &[Column],
+        primary_key_indices: &[usize],
+        clustering_column_indices: &[usize],
+        latest_value: &[String],
+    ) -> String {
+        let conditions: Vec<String> = primary_key_indices
+            .iter()
+            .chain(clustering_column_indices)
+            .map(|&index| {
+                format!(
+                    "{} = {}",
+                    columns[index].name,
+                    cql_literal(&latest_value[index])
+                )
+            })
+            .collect();
+
+        format!(
+            "DELETE FROM {}.{} WHERE {}",
+            keyspace_name,
+            table_name,
+            conditions.join(" AND ")
+        )
+    }
+
+    fn delete_in_this_node(
+        keyspace_name: &str,
+        replication: bool,
+        table: &TableSchema,
+        delete_query: &str,
+        timestamp: i64,
+        storage: &StorageEngine,
+    ) -> Result<(), NodeError> {
+        let delete = Delete::deserialize(delete_query).map_err(NodeError::CQLError)?;
+        storage.delete(delete, table.clone(), keyspace_name, replication, timestamp)?;
+        Ok(())
+    }
+
     fn send_update_to_node(
         node_ip: Ipv4Addr,
-        connections: &Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
         query: String,
-        self_ip: &Ipv4Addr,
-        keyspace_name: &String,
         replication: bool,
+        timestamp: i64,
+        context: &CoordinatorContext,
     ) -> Result<(), NodeError> {
+        // La fila se escribe con el timestamp que tenia, asi no le gana a una escritura mas
+        // nueva que la replica ya tenga
         let message = InternodeMessage::new(
-            *self_ip,
+            context.self_ip,
             InternodeMessageContent::Query(InternodeQuery {
                 query_string: query,
                 open_query_id: 0,
                 client_id: 0,
                 replication: replication,
-                keyspace_name: keyspace_name.clone(),
-                timestamp: Utc::now().timestamp(),
+                keyspace_name: context.keyspace_name.clone(),
+                timestamp,
             }),
         );
 
-        connect_and_send_message(node_ip, INTERNODE_PORT, connections.clone(), message)?;
+        connect_and_send_message(
+            node_ip,
+            context.internode_port,
+            context.connections.clone(),
+            message,
+        )?;
         Ok(())
     }
 
     fn update_this_node(
-        self_ip: &Ipv4Addr,
         keyspace_name: &String,
         replication: bool,
         table_name: &String,
         values: Vec<&str>,
         clustering_columns_in_order: Vec<String>,
         columns: &[Column],
-        path: PathBuf,
+        storage: &StorageEngine,
+        timestamp: i64,
     ) -> Result<(), NodeError> {
-        storage_engine::StorageEngine::new(path, self_ip.to_string()).insert(
+        storage.insert(
             &keyspace_name,
             &table_name,
             values,
@@ -628,36 +960,89 @@ impl InternodeProtocolHandler {
             clustering_columns_in_order,
             replication,
             false,
-            Utc::now().timestamp(),
+            timestamp,
         )?;
         Ok(())
     }
 
+    /// Returns the name and type of each of the `select_columns`, as defined by the
+    /// `columns` of the table. `COUNT(*)` is returned as the `bigint` column `count`, and
+    /// `WRITETIME(col)` and `TTL(col)` as the `bigint` and `int` columns `writetime(col)` and
+    /// `ttl(col)`, as Cassandra names them.
+    fn select_column_types(
+        columns: &[Column],
+        select_columns: &[String],
+    ) -> Result<Vec<(String, ColumnType)>, NodeError> {
+        select_columns
+            .iter()
+            .map(|name| {
+                if name == COUNT_ALL {
+                    return Ok(("count".to_string(), ColumnType::Bigint));
+                }
+                if let Some((function, column)) = CellFunction::of_column(name) {
+                    let column_type = match function {
+                        CellFunction::WriteTime => ColumnType::Bigint,
+                        CellFunction::Ttl => ColumnType::Int,
+                    };
+                    return Ok((
+                        format!("{}({})", function.name().to_lowercase(), column),
+                        column_type,
+                    ));
+                }
+                let column = columns
+                    .iter()
+                    .find(|column| &column.name == name)
+                    .ok_or(NodeError::CQLError(CQLError::InvalidColumn))?;
+                Ok((name.clone(), ColumnType::from(column.data_type)))
+            })
+            .collect()
+    }
+
+    /// Keeps only the values of the `select_columns` in each row, in that order.
+    ///
+    /// Each row has the values of every one of the `columns` followed by the time it was
+    /// written, which `WRITETIME` and `TTL` are computed from (see
+    /// [`storage_engine::select::cell_function_value`]).
     fn filter_and_join_columns(
         rows: Vec<String>,
         select_columns: Vec<String>,
         columns: Vec<String>,
     ) -> Vec<String> {
-        // Crear el encabezado con las columnas seleccionadas
-        let mut result = vec![select_columns.join(",")];
+        let mut result = vec![];
 
-        // Obtener los índices de las columnas seleccionadas
-        let selected_indices: Vec<usize> = select_columns
+        // Obtener los índices de las columnas seleccionadas, y la funcion que se les aplica
+        let selected_indices: Vec<(usize, Option<CellFunction>)> = select_columns
             .iter()
-            .filter_map(|col| columns.iter().position(|c| c == col))
+            .filter_map(|col| match CellFunction::of_column(col) {
+                Some((function, column)) => columns
+                    .iter()
+                    .position(|c| c == column)
+                    .map(|i| (i, Some(function))),
+                None => columns.iter().position(|c| c == col).map(|i| (i, None)),
+            })
             .collect();
 
         // Procesar cada fila de valores
         let filtered_rows: Vec<String> = rows
             .iter()
             .map(|row| {
-                // Dividir la fila en sus componentes (se asume que están separadas por comas)
-                let row_values: Vec<&str> = row.split(',').collect();
+                // Dividir la fila en sus componentes, separadas por las comas que no estan escapadas
+                let row_values: Vec<&str> = split_storage_values(row);
 
                 // Seleccionar solo los valores correspondientes a los índices de las columnas seleccionadas
                 selected_indices
                     .iter()
-                    .map(|&i| row_values.get(i).unwrap_or(&"").to_string()) // Crear copias de los valores
+                    .map(|&(i, function)| {
+                        let value = row_values.get(i).unwrap_or(&"");
+                        match function {
+                            Some(function) => storage_engine::select::cell_function_value(
+                                function,
+                                value,
+                                row_values.get(columns.len()).unwrap_or(&""),
+                            ),
+                            None => value.to_string(), // Crear copias de los valores
+                        }
+                    })
                     .collect::<Vec<String>>()
                     .join(",")
             })
@@ -682,6 +1067,9 @@ impl InternodeProtocolHandler {
     ///   - This handler is used to mark the query with an error response and check if all responses have been received.
     /// - `open_query_id: i32`
     ///   - The unique identifier of the open query being processed.
+    /// - `detail: Option<&InternodeErrorDetail>`
+    ///   - Why the query failed, as reported by the replica. `None` if the failure happened
+    ///     before reaching a replica (for example, the node could not be contacted).
     ///
     /// # Returns
     /// - `Result<(), NodeError>`
@@ -696,7 +1084,8 @@ impl InternodeProtocolHandler {
     ///    - Determines if the query is complete (i.e., all responses, including the error response, have been received).
     /// 2. **Construct Error Frame**:
     ///    - If the query is complete:
-    ///      - Creates an error response frame using the `Frame::Error` constructor with a `ServerError` message.
+    ///      - Creates an error response frame from `detail`, so the client receives the code and message
+    ///        reported by the replica. Without a detail, a generic `ServerError` is sent.
     /// 3. **Send Error Response**:
     ///    - Sends the error response frame to the client over the connection associated with the query.
     ///    - Ensures the connection is flushed to deliver the response promptly.
@@ -720,12 +1109,17 @@ impl InternodeProtocolHandler {
     pub fn add_error_response_to_open_query_and_send_response_if_closed(
         query_handler: &mut OpenQueryHandler,
         open_query_id: i32,
+        detail: Option<&InternodeErrorDetail>,
     ) -> Result<(), NodeError> {
         if let Some(open_query) = query_handler.add_error_response_and_get_if_closed(open_query_id)
         {
             let connection = open_query.get_connection();
 
-            let error_frame = Frame::Error(error::Error::ServerError(".".to_string()));
+            let error = match detail {
+                Some(detail) => detail.to_native_error(),
+                None => error::Error::ServerError(".".to_string()),
+            };
+            let error_frame = Frame::Error(error);
 
             connection
                 .send(error_frame)
@@ -736,6 +1130,46 @@ impl InternodeProtocolHandler {
         }
     }
 
+    /// Records that a write of the open query could not reach a replica, and whether it was
+    /// `kept` as a hint for it, and answers the client if the query closed with it.
+    ///
+    /// Under `ANY` a kept hint acknowledges the write and the client gets the result of the
+    /// write; under any other level, or if the hints of the replica were full, it is an error
+    /// response. A write at `ANY` that no replica and no hint took is unavailable.
+    pub fn add_hinted_response_to_open_query_and_send_response_if_closed(
+        query_handler: &mut OpenQueryHandler,
+        open_query_id: i32,
+        kept: bool,
+    ) -> Result<(), NodeError> {
+        let closed = if kept {
+            query_handler.add_hinted_response_and_get_if_closed(open_query_id)
+        } else {
+            query_handler.add_error_response_and_get_if_closed(open_query_id)
+        };
+        let Some(open_query) = closed else {
+            return Ok(());
+        };
+
+        let any = open_query.get_consistency_level() == ConsistencyLevel::Any;
+        let frame = if any && kept {
+            // Las escrituras responden sin filas, no hacen falta las columnas ni el keyspace
+            open_query
+                .get_query()
+                .create_client_response(vec![], String::new(), vec![])?
+        } else if any {
+            Frame::Error(error::Error::UnavailableException(
+                "no replica and no hint took the write".to_string(),
+                error::UnavailableException,
+            ))
+        } else {
+            Frame::Error(error::Error::ServerError(".".to_string()))
+        };
+        open_query
+            .get_connection()
+            .send(frame)
+            .map_err(|_| NodeError::OtherError)
+    }
+
     // Handles a query command received from another node.
     fn handle_query_command(
         &self,
@@ -766,10 +1200,12 @@ impl InternodeProtocolHandler {
         }
 
         let self_ip;
+        let internode_port;
         let logger;
         {
             let guard_node = node.lock()?;
             self_ip = guard_node.get_ip();
+            internode_port = guard_node.internode_port;
             logger = guard_node.get_logger();
         };
         let query_split: Vec<&str> = query.query_string.split_whitespace().collect();
@@ -884,34 +1320,98 @@ impl InternodeProtocolHandler {
 
         let response: Option<((i32, i32), InternodeResponse)> = result?;
 
-        if let Some(responses) = response {
-            let (_, value): ((i32, i32), InternodeResponse) = responses.clone();
-
-            if query.open_query_id != 0 {
-                logger.info(
-                    &format!(
-                        "INTERNODE (Query: {:?}): I SENT OK to coordinator node: {:?}",
-                        query.open_query_id, node_ip
-                    ),
-                    Color::Green,
-                    true,
-                )?;
-
-                connect_and_send_message(
-                    node_ip,
-                    INTERNODE_PORT,
-                    connections,
-                    InternodeMessage {
-                        from: self_ip,
-                        content: InternodeMessageContent::Response(value),
-                    },
-                )?;
-            }
+        if query.open_query_id != 0 {
+            Self::reply_to_coordinator(
+                response,
+                self_ip,
+                node_ip,
+                internode_port,
+                &logger,
+                connections,
+            )?;
         }
 
         Ok(())
     }
 
+    // Handles a read request from a coordinator node: runs the `SELECT` over the data of this
+    // node and answers with the open query id of the request.
+    fn handle_read_request_command(
+        &self,
+        node: &Arc<Mutex<Node>>,
+        request: InternodeReadRequest,
+        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+        node_ip: Ipv4Addr,
+    ) -> Result<(), NodeError> {
+        let (self_ip, internode_port, logger, storage_path) = {
+            let mut guard_node = node.lock()?;
+            let keyspace = guard_node
+                .get_keyspace(&request.keyspace_name)?
+                .ok_or(NodeError::KeyspaceError)?;
+            guard_node
+                .get_open_handle_query()
+                .set_keyspace_of_query(request.open_query_id as i32, keyspace);
+            (
+                guard_node.get_ip(),
+                guard_node.internode_port,
+                guard_node.get_logger(),
+                guard_node.storage_path.clone(),
+            )
+        };
+
+        let response = QueryExecution::new(node.clone(), connections.clone(), storage_path)?
+            .execute(
+                Query::Select(request.select),
+                true,
+                request.replication,
+                request.open_query_id as i32,
+                request.client_id as i32,
+                Some(request.timestamp),
+            )?;
+
+        Self::reply_to_coordinator(
+            response,
+            self_ip,
+            node_ip,
+            internode_port,
+            &logger,
+            connections,
+        )
+    }
+
+    // Sends the response of a query run for a coordinator node back to it.
+    fn reply_to_coordinator(
+        response: Option<((i32, i32), InternodeResponse)>,
+        self_ip: Ipv4Addr,
+        coordinator_ip: Ipv4Addr,
+        internode_port: u16,
+        logger: &Logger,
+        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+    ) -> Result<(), NodeError> {
+        let Some((_, value)) = response else {
+            return Ok(());
+        };
+
+        logger.info(
+            &format!(
+                "INTERNODE (Query: {:?}): I SENT OK to coordinator node: {:?}",
+                value.open_query_id, coordinator_ip
+            ),
+            Color::Green,
+            true,
+        )?;
+
+        connect_and_send_message(
+            coordinator_ip,
+            internode_port,
+            connections,
+            InternodeMessage {
+                from: self_ip,
+                content: InternodeMessageContent::Response(value),
+            },
+        )
+    }
+
     // Handles a response command from another node.
     fn handle_response_command(
         &self,
@@ -920,30 +1420,28 @@ impl InternodeProtocolHandler {
         from: Ipv4Addr,
         connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
     ) -> Result<(), NodeError> {
-        let self_ip;
-        let partitioner;
-        let storage_path;
-        let logger;
-        {
-            let guard_node = node.lock()?;
-            self_ip = guard_node.get_ip();
-            partitioner = guard_node.get_partitioner();
-            storage_path = guard_node.storage_path.clone();
-            logger = guard_node.get_logger();
-        }
         let mut guard_node = node.lock()?;
 
-        let query_handler = guard_node.get_open_handle_query();
-
-        let keyspace = query_handler.get_keyspace_of_query(response.open_query_id as i32)?;
-
-        let keyspace_name = if let Some(value) = keyspace {
-            value.get_name()
-        } else {
-            "".to_string()
+        let keyspace = guard_node
+            .get_open_handle_query()
+            .get_keyspace_of_query(response.open_query_id as i32)?;
+        let context = CoordinatorContext {
+            self_ip: guard_node.get_ip(),
+            internode_port: guard_node.internode_port,
+            keyspace_name: keyspace
+                .as_ref()
+                .map_or_else(String::new, |keyspace| keyspace.get_name()),
+            connections,
+            partitioner: guard_node.get_partitioner(),
+            replica_digests: guard_node.replica_digests(keyspace.as_ref()),
+            storage: guard_node.storage_engine(),
+            logger: guard_node.get_logger(),
         };
+        let logger = context.logger.clone();
+
+        let query_handler = guard_node.get_open_handle_query();
 
-        match response.status {
+        match &response.status {
             InternodeResponseStatus::Ok => {
                 logger.info(
                     &format!(
@@ -954,20 +1452,19 @@ impl InternodeProtocolHandler {
                     true,
                 )?;
 
-                self.process_ok_response(
+                let repaired_self = self.process_ok_response(
                     query_handler,
                     response,
                     response.open_query_id as i32,
-                    keyspace_name,
-                    self_ip,
                     from,
-                    connections,
-                    partitioner,
-                    storage_path.clone(),
-                    logger,
+                    &context,
                 )?;
+                // La lectura corrigio filas de este nodo, lo cacheado quedo viejo
+                if repaired_self {
+                    guard_node.clear_cached_reads();
+                }
             }
-            InternodeResponseStatus::Error => {
+            InternodeResponseStatus::Error(detail) => {
                 logger.info(
                     &format!(
                         "INTERNODE (Query: {}): I RECEIVED OK RESPONSE {:?} from {:?}",
@@ -976,7 +1473,7 @@ impl InternodeProtocolHandler {
                     Color::Red,
                     true,
                 )?;
-                self.process_error_response(query_handler, response.open_query_id as i32)?;
+                self.process_error_response(query_handler, response.open_query_id as i32, detail)?;
             }
         }
 
@@ -1002,7 +1499,7 @@ impl InternodeProtocolHandler {
 
                 let result = connect_and_send_message(
                     gossip_message.from,
-                    INTERNODE_PORT,
+                    guard_node.internode_port,
                     connections,
                     InternodeMessage::new(
                         guard_node.get_ip(),
@@ -1022,7 +1519,7 @@ impl InternodeProtocolHandler {
 
                 let result = connect_and_send_message(
                     gossip_message.from,
-                    INTERNODE_PORT,
+                    guard_node.internode_port,
                     connections,
                     InternodeMessage::new(
                         guard_node.get_ip(),
@@ -1041,6 +1538,9 @@ impl InternodeProtocolHandler {
             }
         };
 
+        // Un nodo que se reinicio se repara en la proxima ronda de gossip
+        guard_node.schedule_repairs_of_restarted_peers();
+
         Ok(())
     }
 
@@ -1050,14 +1550,9 @@ impl InternodeProtocolHandler {
         query_handler: &mut OpenQueryHandler,
         response: &InternodeResponse,
         open_query_id: i32,
-        keyspace_name: String,
-        self_ip: Ipv4Addr,
         from: Ipv4Addr,
-        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
-        partitioner: Partitioner,
-        storage_path: PathBuf,
-        logger: Logger,
-    ) -> Result<(), NodeError> {
+        context: &CoordinatorContext,
+    ) -> Result<bool, NodeError> {
         // Obtener la consulta abierta
 
         let columns;
@@ -1066,7 +1561,7 @@ impl InternodeProtocolHandler {
             let open_query = if let Some(value) = query_handler.get_query_mut(&open_query_id) {
                 value
             } else {
-                return Ok(());
+                return Ok(false);
             };
 
             table = open_query.get_table();
@@ -1076,22 +1571,17 @@ impl InternodeProtocolHandler {
                 .map_or_else(Vec::new, |table| table.get_columns());
         }
         // Llamar a la función con los valores copiados, sin `open_query` en uso
-        Self::add_ok_response_to_open_query_and_send_response_if_closed(
+        let repaired_self = Self::add_ok_response_to_open_query_and_send_response_if_closed(
             query_handler,
             response,
             open_query_id,
-            keyspace_name,
             table,
             columns,
-            self_ip,
             from,
-            connections,
-            partitioner,
-            storage_path,
-            logger,
+            context,
         )?;
 
-        Ok(())
+        Ok(repaired_self)
     }
 
     // Procesa la respuesta cuando el estado es "OK"
@@ -1099,10 +1589,12 @@ impl InternodeProtocolHandler {
         &self,
         query_handler: &mut OpenQueryHandler,
         open_query_id: i32,
+        detail: &InternodeErrorDetail,
     ) -> Result<(), NodeError> {
         Self::add_error_response_to_open_query_and_send_response_if_closed(
             query_handler,
             open_query_id,
+            Some(detail),
         )?;
 
         Ok(())
@@ -1347,3 +1839,1174 @@ impl InternodeProtocolHandler {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internode_protocol::response::InternodeResponseContent;
+    use crate::internode_protocol::InternodeSerializable;
+    use crate::INTERNODE_PORT;
+    use gossip::structures::application_state::NodeLoad;
+    use native_protocol::messages::result::result_;
+    use native_protocol::messages::result::rows::ColumnValue;
+    use query_creator::errors::CQLError;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::path::{Path, PathBuf};
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_replica_error_detail_reaches_client() {
+        let mut query_handler = OpenQueryHandler::new();
+        let (tx_reply, rx_reply) = mpsc::channel();
+        let query = QueryCreator::new()
+            .handle_query("SELECT id FROM flights WHERE id = 1".to_string())
+            .unwrap();
+        let open_query_id = query_handler.new_open_query(1, tx_reply, query, "ONE", None, None);
+
+        // La replica falla con un error concreto y lo manda en la respuesta
+        let replica_error = NodeError::CQLError(CQLError::InvalidSyntax);
+        let detail = InternodeErrorDetail::from(&replica_error);
+
+        InternodeProtocolHandler::add_error_response_to_open_query_and_send_response_if_closed(
+            &mut query_handler,
+            open_query_id,
+            Some(&detail),
+        )
+        .unwrap();
+
+        match rx_reply.recv().unwrap() {
+            Frame::Error(error::Error::Invalid(message)) => {
+                assert_eq!(message, CQLError::InvalidSyntax.to_string())
+            }
+            other => panic!("unexpected frame {:?}", other),
+        }
+    }
+
+    fn flights_table() -> TableSchema {
+        let query = QueryCreator::new()
+            .handle_query(
+                "CREATE TABLE sky.flights (id INT, origin TEXT, PRIMARY KEY (id))".to_string(),
+            )
+            .unwrap();
+        match query {
+            Query::CreateTable(create_table) => TableSchema::new(create_table),
+            _ => panic!("expected CREATE TABLE"),
+        }
+    }
+
+    // Contexto de un coordinador de queries de `sky` que guarda sus filas en `storage_path`
+    fn coordinator_context(
+        self_ip: Ipv4Addr,
+        partitioner: Partitioner,
+        replica_digests: ReplicaDigests,
+        storage_path: &Path,
+    ) -> CoordinatorContext {
+        std::fs::create_dir_all(storage_path).unwrap();
+        CoordinatorContext {
+            self_ip,
+            internode_port: INTERNODE_PORT,
+            keyspace_name: "sky".to_string(),
+            connections: Arc::new(Mutex::new(HashMap::new())),
+            partitioner,
+            replica_digests,
+            storage: StorageEngine::new(storage_path.to_path_buf(), self_ip.to_string()),
+            logger: Logger::new(storage_path, &self_ip.to_string()).unwrap(),
+        }
+    }
+
+    fn select_response(values: Vec<Vec<&str>>) -> InternodeResponse {
+        InternodeResponse::new(
+            1,
+            InternodeResponseStatus::Ok,
+            Some(InternodeResponseContent {
+                columns: vec!["id".to_string(), "origin".to_string()],
+                select_columns: vec!["id".to_string(), "origin".to_string()],
+                values: values
+                    .into_iter()
+                    .map(|row| row.into_iter().map(String::from).collect())
+                    .collect(),
+            }),
+        )
+    }
+
+    #[test]
+    fn test_read_repair_drops_row_deleted_in_newer_replica() {
+        let self_ip = Ipv4Addr::new(127, 0, 0, 30);
+        let lagging_ip = Ipv4Addr::new(127, 0, 0, 31);
+        let listener = TcpListener::bind((lagging_ip, INTERNODE_PORT)).unwrap();
+
+        let mut partitioner = Partitioner::new();
+        partitioner.add_node(self_ip).unwrap();
+        partitioner.add_node(lagging_ip).unwrap();
+
+        let table = flights_table();
+        let responses = vec![
+            (
+                self_ip,
+                select_response(vec![vec!["1", "EZE", "200;tombstone"]]),
+            ),
+            (lagging_ip, select_response(vec![vec!["1", "EZE", "100"]])),
+        ];
+
+        let storage_path = PathBuf::from(format!("/tmp/read_repair_test_{}", Uuid::new_v4()));
+        let context = coordinator_context(
+            self_ip,
+            partitioner,
+            ReplicaDigests::default(),
+            &storage_path,
+        );
+        let (rows, repaired_self) =
+            InternodeProtocolHandler::read_repair(responses, table.get_columns(), table, &context)
+                .unwrap();
+
+        assert!(rows.is_empty());
+        assert!(!repaired_self);
+
+        // La replica atrasada recibe el borrado
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buffer = vec![0u8; 1024];
+        let read = stream.read(&mut buffer).unwrap();
+        let message = InternodeMessage::from_bytes(&buffer[..read]).unwrap();
+        match message.content {
+            InternodeMessageContent::Query(query) => {
+                assert_eq!(query.query_string, "DELETE FROM sky.flights WHERE id = '1'");
+                // El borrado conserva su timestamp
+                assert_eq!(query.timestamp, 200);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_create_table_answered_with_content_is_a_schema_change() {
+        let self_ip = Ipv4Addr::new(127, 0, 0, 85);
+        let mut partitioner = Partitioner::new();
+        partitioner.add_node(self_ip).unwrap();
+        let query = QueryCreator::new()
+            .handle_query(
+                "CREATE TABLE sky.legs (flight INT, leg INT, PRIMARY KEY ((flight), leg))"
+                    .to_string(),
+            )
+            .unwrap();
+        let table = match &query {
+            Query::CreateTable(create_table) => TableSchema::new(create_table.clone()),
+            _ => panic!("expected CREATE TABLE"),
+        };
+        let storage_path = PathBuf::from(format!("/tmp/ddl_response_test_{}", Uuid::new_v4()));
+        let context = coordinator_context(
+            self_ip,
+            partitioner,
+            ReplicaDigests::default(),
+            &storage_path,
+        );
+
+        let mut query_handler = OpenQueryHandler::new();
+        let (tx_reply, rx_reply) = mpsc::channel();
+        let open_query_id =
+            query_handler.new_open_query(1, tx_reply, query, "ONE", Some(table.clone()), None);
+        let response = InternodeResponse::new(
+            open_query_id as u32,
+            InternodeResponseStatus::Ok,
+            Some(InternodeResponseContent {
+                columns: vec![],
+                select_columns: vec![],
+                values: vec![],
+            }),
+        );
+        InternodeProtocolHandler::add_ok_response_to_open_query_and_send_response_if_closed(
+            &mut query_handler,
+            &response,
+            open_query_id,
+            Some(table.clone()),
+            table.get_columns(),
+            self_ip,
+            &context,
+        )
+        .unwrap();
+
+        match rx_reply.recv_timeout(Duration::from_secs(1)).unwrap() {
+            Frame::Result(result_::Result::SchemaChange(_)) => {}
+            other => panic!("expected a schema change, got {:?}", other),
+        }
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_read_repair_backfills_a_replica_that_answered_without_the_row() {
+        let self_ip = Ipv4Addr::new(127, 0, 0, 78);
+        let missing_ip = Ipv4Addr::new(127, 0, 0, 79);
+        let listener = TcpListener::bind((missing_ip, INTERNODE_PORT)).unwrap();
+
+        let mut partitioner = Partitioner::new();
+        partitioner.add_node(self_ip).unwrap();
+        partitioner.add_node(missing_ip).unwrap();
+
+        let table = flights_table();
+        let responses = vec![
+            (self_ip, select_response(vec![vec!["1", "EZE", "100"]])),
+            (missing_ip, select_response(vec![])),
+        ];
+
+        let storage_path = PathBuf::from(format!("/tmp/read_repair_test_{}", Uuid::new_v4()));
+        let context = coordinator_context(
+            self_ip,
+            partitioner,
+            ReplicaDigests {
+                replication_factor: 2,
+                ..ReplicaDigests::default()
+            },
+            &storage_path,
+        );
+        let (rows, repaired_self) =
+            InternodeProtocolHandler::read_repair(responses, table.get_columns(), table, &context)
+                .unwrap();
+
+        assert_eq!(rows, vec!["1,EZE,100"]);
+        assert!(!repaired_self);
+
+        // La fila llega con el timestamp con el que se escribio, no con el de la lectura
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buffer = vec![0u8; 1024];
+        let read = stream.read(&mut buffer).unwrap();
+        match InternodeMessage::from_bytes(&buffer[..read])
+            .unwrap()
+            .content
+        {
+            InternodeMessageContent::Query(query) => {
+                assert_eq!(
+                    query.query_string,
+                    "INSERT INTO sky.flights (id,origin) VALUES ('1','EZE');"
+                );
+                assert_eq!(query.timestamp, 100);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_read_at_one_backfills_the_replicas_missing_the_row() {
+        let ips = [
+            Ipv4Addr::new(127, 0, 0, 86),
+            Ipv4Addr::new(127, 0, 0, 87),
+            Ipv4Addr::new(127, 0, 0, 88),
+        ];
+        let storage_path = PathBuf::from(format!("/tmp/backfill_test_{}", Uuid::new_v4()));
+        let nodes: Vec<Arc<Mutex<Node>>> = ips
+            .iter()
+            .map(|ip| {
+                Arc::new(Mutex::new(
+                    Node::new(*ip, vec![ips[0]], storage_path.join(ip.to_string())).unwrap(),
+                ))
+            })
+            .collect();
+        // Los tres nodos atienden los mensajes de los otros como un nodo en marcha
+        for (node, ip) in nodes.iter().zip(ips) {
+            let node = Arc::clone(node);
+            std::thread::spawn(move || {
+                Node::handle_node_connections(node, Arc::new(Mutex::new(HashMap::new())), ip)
+            });
+            while std::net::TcpStream::connect((ip, INTERNODE_PORT)).is_err() {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+        Node::join_cluster(&nodes[0], &[]).unwrap();
+        for node in &nodes[1..] {
+            Node::join_cluster(node, &nodes[..1]).unwrap();
+        }
+        Node::gossip_with(&nodes[0], &nodes).unwrap();
+
+        let coordinator = &nodes[0];
+        let run = |query: &str| {
+            let client_id = coordinator.lock().unwrap().generate_client_id();
+            let (tx_reply, rx_reply) = mpsc::channel();
+            Node::handle_query_execution(
+                query,
+                "ONE",
+                coordinator,
+                Arc::new(Mutex::new(HashMap::new())),
+                tx_reply,
+                client_id,
+            )
+            .unwrap();
+            rx_reply.recv_timeout(Duration::from_secs(5)).unwrap()
+        };
+        run("CREATE KEYSPACE sky WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 3}");
+        run("CREATE TABLE sky.flights (id INT, origin TEXT, PRIMARY KEY (id))");
+
+        // Solo el coordinador tiene la fila, escrita en 100
+        let table = flights_table();
+        let folder_of = |node: &Arc<Mutex<Node>>| {
+            let guard = node.lock().unwrap();
+            guard
+                .get_partitioner()
+                .get_ip_for_partition(&["7"])
+                .unwrap()
+                != guard.get_ip()
+        };
+        let storage = coordinator.lock().unwrap().storage_engine();
+        storage
+            .insert(
+                "sky",
+                "flights",
+                vec!["7", "EZE"],
+                table.get_columns(),
+                vec![],
+                folder_of(coordinator),
+                false,
+                100,
+            )
+            .unwrap();
+
+        // Las otras dos replicas anuncian por gossip que no tienen nada en ese rango
+        for node in &nodes {
+            Node::publish_data_digests(node).unwrap();
+        }
+        Node::gossip_with(&nodes[0], &nodes).unwrap();
+
+        match run("SELECT id, origin FROM sky.flights WHERE id = 7") {
+            Frame::Result(result_::Result::Rows(rows)) => assert_eq!(rows.rows_content.len(), 1),
+            other => panic!("unexpected frame {:?}", other),
+        }
+
+        // Despues de la lectura las tres replicas tienen la fila, con su timestamp original
+        for node in &nodes {
+            let storage = node.lock().unwrap().storage_engine();
+            let replication = folder_of(node);
+            let deadline = Instant::now() + Duration::from_secs(5);
+            let mut rows = storage
+                .scan_range("sky", &table, replication, 0, u64::MAX)
+                .unwrap();
+            while rows.is_empty() && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(20));
+                rows = storage
+                    .scan_range("sky", &table, replication, 0, u64::MAX)
+                    .unwrap();
+            }
+            assert_eq!(rows, vec!["7,EZE;100"]);
+        }
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_rows_of_several_replicas_are_sorted_and_limited_by_the_coordinator() {
+        let self_ip = Ipv4Addr::new(127, 0, 0, 83);
+        let other_ip = Ipv4Addr::new(127, 0, 0, 84);
+        let mut partitioner = Partitioner::new();
+        partitioner.add_node(self_ip).unwrap();
+        partitioner.add_node(other_ip).unwrap();
+        let table = match QueryCreator::new()
+            .handle_query(
+                "CREATE TABLE sky.legs (flight INT, leg INT, origin TEXT, PRIMARY KEY ((flight), leg))"
+                    .to_string(),
+            )
+            .unwrap()
+        {
+            Query::CreateTable(create_table) => TableSchema::new(create_table),
+            _ => panic!("expected CREATE TABLE"),
+        };
+        let storage_path = PathBuf::from(format!("/tmp/merge_replicas_test_{}", Uuid::new_v4()));
+        let context = coordinator_context(
+            self_ip,
+            partitioner,
+            ReplicaDigests::default(),
+            &storage_path,
+        );
+
+        let legs_read = |query: &str, responses: Vec<(Ipv4Addr, Vec<Vec<&str>>)>| {
+            let mut query_handler = OpenQueryHandler::new();
+            let (tx_reply, rx_reply) = mpsc::channel();
+            let query = QueryCreator::new().handle_query(query.to_string()).unwrap();
+            let open_query_id = query_handler.new_open_query(
+                responses.len() as i32,
+                tx_reply,
+                query,
+                "ALL",
+                Some(table.clone()),
+                None,
+            );
+            for (from, values) in responses {
+                let response = InternodeResponse::new(
+                    open_query_id as u32,
+                    InternodeResponseStatus::Ok,
+                    Some(InternodeResponseContent {
+                        columns: vec![
+                            "flight".to_string(),
+                            "leg".to_string(),
+                            "origin".to_string(),
+                        ],
+                        select_columns: vec!["leg".to_string()],
+                        values: values
+                            .into_iter()
+                            .map(|row| row.into_iter().map(String::from).collect())
+                            .collect(),
+                    }),
+                );
+                InternodeProtocolHandler::add_ok_response_to_open_query_and_send_response_if_closed(
+                    &mut query_handler,
+                    &response,
+                    open_query_id,
+                    Some(table.clone()),
+                    table.get_columns(),
+                    from,
+                    &context,
+                )
+                .unwrap();
+            }
+            match rx_reply.recv_timeout(Duration::from_secs(1)).unwrap() {
+                Frame::Result(result_::Result::Rows(rows)) => rows
+                    .rows_content
+                    .iter()
+                    .map(|row| row.get("leg").cloned().unwrap())
+                    .collect::<Vec<ColumnValue>>(),
+                other => panic!("expected rows, got {:?}", other),
+            }
+        };
+        let legs = |legs: &[i32]| {
+            legs.iter()
+                .map(|leg| ColumnValue::Int(*leg))
+                .collect::<Vec<_>>()
+        };
+
+        // Las dos replicas tienen las mismas filas, cada una en otro orden
+        let rows = vec![
+            vec!["1", "3", "AEP", "100"],
+            vec!["1", "1", "EZE", "100"],
+            vec!["1", "2", "COR", "100"],
+        ];
+        let mut reversed = rows.clone();
+        reversed.reverse();
+        assert_eq!(
+            legs_read(
+                "SELECT leg FROM sky.legs WHERE flight = 1 LIMIT 2",
+                vec![(self_ip, rows.clone()), (other_ip, reversed.clone())],
+            ),
+            legs(&[1, 2])
+        );
+        assert_eq!(
+            legs_read(
+                "SELECT leg FROM sky.legs WHERE flight = 1 ORDER BY leg DESC",
+                vec![(self_ip, rows), (other_ip, reversed)],
+            ),
+            legs(&[3, 2, 1])
+        );
+
+        // En un full scan cada nodo responde por sus particiones y el limite es del total
+        assert_eq!(
+            legs_read(
+                "SELECT leg FROM sky.legs PER PARTITION LIMIT 1 ALLOW FILTERING",
+                vec![
+                    (
+                        self_ip,
+                        vec![vec!["1", "2", "AEP", "100"], vec!["1", "1", "EZE", "100"]]
+                    ),
+                    (
+                        other_ip,
+                        vec![vec!["2", "5", "COR", "100"], vec!["2", "4", "EZE", "100"]]
+                    ),
+                ],
+            )
+            .len(),
+            2
+        );
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_groups_count_the_rows_of_every_replica_once() {
+        let self_ip = Ipv4Addr::new(127, 0, 0, 93);
+        let replica_ips = [Ipv4Addr::new(127, 0, 0, 91), Ipv4Addr::new(127, 0, 0, 92)];
+        // Las replicas reciben las filas que les faltan, nadie las tiene que leer
+        let _listeners: Vec<TcpListener> = replica_ips
+            .iter()
+            .map(|ip| TcpListener::bind((*ip, INTERNODE_PORT)).unwrap())
+            .collect();
+        let mut partitioner = Partitioner::new();
+        for ip in replica_ips {
+            partitioner.add_node(ip).unwrap();
+        }
+        let table = match QueryCreator::new()
+            .handle_query(
+                "CREATE TABLE sky.legs (flight INT, leg INT, origin TEXT, PRIMARY KEY ((flight), leg))"
+                    .to_string(),
+            )
+            .unwrap()
+        {
+            Query::CreateTable(create_table) => TableSchema::new(create_table),
+            _ => panic!("expected CREATE TABLE"),
+        };
+        let storage_path = PathBuf::from(format!("/tmp/group_replicas_test_{}", Uuid::new_v4()));
+        let context = coordinator_context(
+            self_ip,
+            partitioner,
+            ReplicaDigests {
+                replication_factor: 2,
+                ..ReplicaDigests::default()
+            },
+            &storage_path,
+        );
+
+        let mut query_handler = OpenQueryHandler::new();
+        let (tx_reply, rx_reply) = mpsc::channel();
+        let query = QueryCreator::new()
+            .handle_query(
+                "SELECT flight, COUNT(*) FROM sky.legs WHERE flight = 1 GROUP BY flight"
+                    .to_string(),
+            )
+            .unwrap();
+        let open_query_id =
+            query_handler.new_open_query(2, tx_reply, query, "ALL", Some(table.clone()), None);
+        // Cada replica se perdio una fila distinta, y una tiene una version mas nueva del tramo 2
+        let answers = [
+            vec![vec!["1", "1", "EZE", "100"], vec!["1", "2", "EZE", "100"]],
+            vec![vec!["1", "2", "COR", "200"], vec!["1", "3", "AEP", "100"]],
+        ];
+        for (from, values) in replica_ips.into_iter().zip(answers) {
+            let response = InternodeResponse::new(
+                open_query_id as u32,
+                InternodeResponseStatus::Ok,
+                Some(InternodeResponseContent {
+                    columns: vec![
+                        "flight".to_string(),
+                        "leg".to_string(),
+                        "origin".to_string(),
+                    ],
+                    select_columns: vec!["flight".to_string(), COUNT_ALL.to_string()],
+                    values: values
+                        .into_iter()
+                        .map(|row| row.into_iter().map(String::from).collect())
+                        .collect(),
+                }),
+            );
+            InternodeProtocolHandler::add_ok_response_to_open_query_and_send_response_if_closed(
+                &mut query_handler,
+                &response,
+                open_query_id,
+                Some(table.clone()),
+                table.get_columns(),
+                from,
+                &context,
+            )
+            .unwrap();
+        }
+
+        match rx_reply.recv_timeout(Duration::from_secs(1)).unwrap() {
+            Frame::Result(result_::Result::Rows(rows)) => {
+                assert_eq!(rows.rows_content.len(), 1);
+                assert_eq!(
+                    rows.rows_content[0].get("flight"),
+                    Some(&ColumnValue::Int(1))
+                );
+                assert_eq!(
+                    rows.rows_content[0].get("count"),
+                    Some(&ColumnValue::Bigint(3))
+                );
+            }
+            other => panic!("expected rows, got {:?}", other),
+        }
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_replica_answers_read_request_with_its_open_query_id() {
+        let coordinator_ip = Ipv4Addr::new(127, 0, 0, 34);
+        let replica_ip = Ipv4Addr::new(127, 0, 0, 35);
+        let listener = TcpListener::bind((coordinator_ip, INTERNODE_PORT)).unwrap();
+        let storage_path = PathBuf::from(format!("/tmp/read_request_test_{}", Uuid::new_v4()));
+        let replica = Arc::new(Mutex::new(
+            Node::new(replica_ip, vec![replica_ip], storage_path.clone()).unwrap(),
+        ));
+
+        for query in [
+            "CREATE KEYSPACE sky WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 1}",
+            "CREATE TABLE sky.flights (id INT, origin TEXT, PRIMARY KEY (id))",
+            "INSERT INTO sky.flights (id, origin) VALUES (1, 'EZE')",
+        ] {
+            let client_id = replica.lock().unwrap().generate_client_id();
+            let (tx_reply, rx_reply) = mpsc::channel();
+            Node::handle_query_execution(
+                query,
+                "ONE",
+                &replica,
+                Arc::new(Mutex::new(HashMap::new())),
+                tx_reply,
+                client_id,
+            )
+            .unwrap();
+            rx_reply.recv().unwrap();
+        }
+
+        let request = InternodeReadRequest {
+            select: Select::deserialize("SELECT id, origin FROM sky.flights WHERE id = 1").unwrap(),
+            open_query_id: 77,
+            client_id: 5,
+            replication: false,
+            keyspace_name: "sky".to_string(),
+            consistency: crate::open_query_handler::ConsistencyLevel::Quorum,
+            timestamp: 10,
+        };
+        InternodeProtocolHandler::new()
+            .handle_command(
+                &replica,
+                InternodeMessage::new(
+                    coordinator_ip,
+                    InternodeMessageContent::ReadRequest(request),
+                ),
+                Arc::new(Mutex::new(HashMap::new())),
+            )
+            .unwrap();
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buffer = vec![0u8; 1024];
+        let read = stream.read(&mut buffer).unwrap();
+        let message = InternodeMessage::from_bytes(&buffer[..read]).unwrap();
+        assert_eq!(message.from, replica_ip);
+        match message.content {
+            InternodeMessageContent::Response(response) => {
+                assert_eq!(response.open_query_id, 77);
+                assert_eq!(response.status, InternodeResponseStatus::Ok);
+                let values = response.content.unwrap().values;
+                assert_eq!(values.len(), 1);
+                assert_eq!(values[0][..2], ["1".to_string(), "EZE".to_string()]);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_insert_reaches_every_replica() {
+        let coordinator_ip = Ipv4Addr::new(127, 0, 0, 48);
+        let replica_ips = [Ipv4Addr::new(127, 0, 0, 49), Ipv4Addr::new(127, 0, 0, 50)];
+        let all_ips = vec![coordinator_ip, replica_ips[0], replica_ips[1]];
+        let schema = [
+            "CREATE KEYSPACE sky WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 3}",
+            "CREATE TABLE sky.flights (id INT, origin TEXT, PRIMARY KEY (id))",
+        ];
+        let run = |node: &Arc<Mutex<Node>>, query: &str| {
+            let client_id = node.lock().unwrap().generate_client_id();
+            let (tx_reply, rx_reply) = mpsc::channel();
+            Node::handle_query_execution(
+                query,
+                "ONE",
+                node,
+                Arc::new(Mutex::new(HashMap::new())),
+                tx_reply,
+                client_id,
+            )
+            .unwrap();
+            rx_reply.recv().unwrap()
+        };
+
+        // Las respuestas de las replicas vuelven al coordinador
+        let _coordinator_listener = TcpListener::bind((coordinator_ip, INTERNODE_PORT)).unwrap();
+        let listeners: Vec<TcpListener> = replica_ips
+            .iter()
+            .map(|ip| TcpListener::bind((*ip, INTERNODE_PORT)).unwrap())
+            .collect();
+
+        let storage_paths: Vec<PathBuf> = all_ips
+            .iter()
+            .map(|_| PathBuf::from(format!("/tmp/replica_insert_test_{}", Uuid::new_v4())))
+            .collect();
+        let coordinator = Arc::new(Mutex::new(
+            Node::new(coordinator_ip, all_ips.clone(), storage_paths[0].clone()).unwrap(),
+        ));
+        let replicas: Vec<Arc<Mutex<Node>>> = replica_ips
+            .iter()
+            .zip(&storage_paths[1..])
+            .map(|(ip, path)| {
+                Arc::new(Mutex::new(Node::new(*ip, vec![*ip], path.clone()).unwrap()))
+            })
+            .collect();
+        for node in std::iter::once(&coordinator).chain(&replicas) {
+            for query in schema {
+                run(node, query);
+            }
+        }
+
+        let response = run(
+            &coordinator,
+            "INSERT INTO sky.flights (id, origin) VALUES (7, 'EZE')",
+        );
+        assert!(!matches!(response, Frame::Error(_)), "{:?}", response);
+
+        // Cada replica recibe el insert y lo aplica
+        for (listener, replica) in listeners.iter().zip(&replicas) {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buffer = vec![0u8; 1024];
+            let read = stream.read(&mut buffer).unwrap();
+            let message = InternodeMessage::from_bytes(&buffer[..read]).unwrap();
+            assert_eq!(message.from, coordinator_ip);
+            InternodeProtocolHandler::new()
+                .handle_command(replica, message, Arc::new(Mutex::new(HashMap::new())))
+                .unwrap();
+        }
+
+        // El duenio la guarda en su carpeta principal y el resto en la de replicacion
+        let owners = coordinator
+            .lock()
+            .unwrap()
+            .get_partitioner()
+            .get_replicas(&["7"], 3)
+            .unwrap();
+        assert_eq!(owners.len(), 3);
+        for (ip, path) in all_ips.iter().zip(&storage_paths) {
+            let keyspace_folder = path
+                .join(format!("keyspaces_of_{}", ip.to_string().replace('.', "_")))
+                .join("sky");
+            let (stored, other) = if *ip == owners[0] {
+                (keyspace_folder.clone(), keyspace_folder.join("replication"))
+            } else {
+                (keyspace_folder.join("replication"), keyspace_folder.clone())
+            };
+            let rows = std::fs::read_to_string(stored.join("flights.csv")).unwrap();
+            assert!(
+                rows.lines().any(|line| line.starts_with("7,EZE")),
+                "{}: {}",
+                ip,
+                rows
+            );
+            let rows = std::fs::read_to_string(other.join("flights.csv")).unwrap_or_default();
+            assert!(!rows.contains("7,EZE"), "{}: {}", ip, rows);
+        }
+
+        for path in storage_paths {
+            std::fs::remove_dir_all(path).ok();
+        }
+    }
+
+    // Coordinador con dos replicas que escuchan en `replica_ips`, con el keyspace `sky`
+    // (replication_factor 2) y la tabla `sky.flights`, y el id de una particion que el
+    // coordinador no guarda, asi lee solo de las replicas
+    struct ReplicatedRead {
+        coordinator: Arc<Mutex<Node>>,
+        listeners: HashMap<Ipv4Addr, TcpListener>,
+        storage_path: PathBuf,
+        id: String,
+        // Replicas de la particion, el duenio primero
+        replicas: Vec<Ipv4Addr>,
+    }
+
+    fn replicated_read(
+        label: &str,
+        coordinator_ip: Ipv4Addr,
+        replica_ips: [Ipv4Addr; 2],
+        speculative_retry_delay: Duration,
+    ) -> ReplicatedRead {
+        let listeners = replica_ips
+            .iter()
+            .map(|ip| (*ip, TcpListener::bind((*ip, INTERNODE_PORT)).unwrap()))
+            .collect();
+
+        let storage_path = PathBuf::from(format!("/tmp/{}_{}", label, Uuid::new_v4()));
+        let coordinator = Arc::new(Mutex::new(
+            Node::new(
+                coordinator_ip,
+                vec![coordinator_ip, replica_ips[0], replica_ips[1]],
+                storage_path.clone(),
+            )
+            .unwrap(),
+        ));
+        coordinator
+            .lock()
+            .unwrap()
+            .set_speculative_retry_delay(speculative_retry_delay);
+
+        run_at_one(
+            &coordinator,
+            "CREATE KEYSPACE sky WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 2}",
+        )
+        .recv()
+        .unwrap();
+        run_at_one(
+            &coordinator,
+            "CREATE TABLE sky.flights (id INT, origin TEXT, PRIMARY KEY (id))",
+        )
+        .recv()
+        .unwrap();
+
+        let partitioner = coordinator.lock().unwrap().get_partitioner();
+        let (id, replicas) = (0..)
+            .map(|id: i32| {
+                let replicas = partitioner.get_replicas(&[id.to_string()], 2).unwrap();
+                (id.to_string(), replicas)
+            })
+            .find(|(_, replicas)| !replicas.contains(&coordinator_ip))
+            .unwrap();
+
+        ReplicatedRead {
+            coordinator,
+            listeners,
+            storage_path,
+            id,
+            replicas,
+        }
+    }
+
+    fn run_at_one(coordinator: &Arc<Mutex<Node>>, query: &str) -> mpsc::Receiver<Frame> {
+        let client_id = coordinator.lock().unwrap().generate_client_id();
+        let (tx_reply, rx_reply) = mpsc::channel();
+        Node::handle_query_execution(
+            query,
+            "ONE",
+            coordinator,
+            Arc::new(Mutex::new(HashMap::new())),
+            tx_reply,
+            client_id,
+        )
+        .unwrap();
+        rx_reply
+    }
+
+    fn accept_read_request(listener: &TcpListener) -> InternodeReadRequest {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buffer = vec![0u8; 1024];
+        let read = stream.read(&mut buffer).unwrap();
+        match InternodeMessage::from_bytes(&buffer[..read])
+            .unwrap()
+            .content
+        {
+            InternodeMessageContent::ReadRequest(request) => request,
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    // Contesta la lectura `open_query_id` desde `from` con la fila `id, origin`
+    fn answer_read(
+        coordinator: &Arc<Mutex<Node>>,
+        from: Ipv4Addr,
+        open_query_id: u32,
+        id: &str,
+        origin: &str,
+    ) {
+        let mut response = select_response(vec![vec![id, origin, "100"]]);
+        response.open_query_id = open_query_id;
+        InternodeProtocolHandler::new()
+            .handle_command(
+                coordinator,
+                InternodeMessage::new(from, InternodeMessageContent::Response(response)),
+                Arc::new(Mutex::new(HashMap::new())),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_read_is_answered_by_the_speculative_replica_when_one_is_slow() {
+        let delay = Duration::from_millis(50);
+        let ReplicatedRead {
+            coordinator,
+            mut listeners,
+            storage_path,
+            id,
+            replicas,
+        } = replicated_read(
+            "speculative_read_test",
+            Ipv4Addr::new(127, 0, 0, 51),
+            [Ipv4Addr::new(127, 0, 0, 52), Ipv4Addr::new(127, 0, 0, 53)],
+            delay,
+        );
+        let (slow_ip, fast_ip) = (replicas[0], replicas[1]);
+
+        let started = Instant::now();
+        let rx_reply = run_at_one(
+            &coordinator,
+            &format!("SELECT id, origin FROM sky.flights WHERE id = {}", id),
+        );
+
+        // El duenio recibe la lectura enseguida pero nunca contesta
+        let slow_request = accept_read_request(&listeners.remove(&slow_ip).unwrap());
+        assert!(!slow_request.replication);
+
+        // Pasada la demora, la lectura tambien va a la otra replica
+        let fast_request = accept_read_request(&listeners.remove(&fast_ip).unwrap());
+        assert!(started.elapsed() >= delay);
+        assert!(fast_request.replication);
+        assert_eq!(fast_request.open_query_id, slow_request.open_query_id);
+
+        answer_read(
+            &coordinator,
+            fast_ip,
+            fast_request.open_query_id,
+            &id,
+            "EZE",
+        );
+
+        match rx_reply.recv_timeout(Duration::from_secs(1)).unwrap() {
+            Frame::Result(result_::Result::Rows(rows)) => {
+                assert_eq!(rows.rows_content.len(), 1);
+                assert_eq!(
+                    rows.rows_content[0].get("origin"),
+                    Some(&ColumnValue::Ascii("EZE".to_string()))
+                );
+            }
+            other => panic!("unexpected frame {:?}", other),
+        }
+
+        // La respuesta tardia del duenio se descarta
+        answer_read(
+            &coordinator,
+            slow_ip,
+            fast_request.open_query_id,
+            &id,
+            "AEP",
+        );
+        assert!(rx_reply.recv_timeout(delay).is_err());
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_read_prefers_the_replica_advertising_less_load() {
+        // Sin reintento especulativo durante el test, asi solo se pregunta a una replica
+        let ReplicatedRead {
+            coordinator,
+            mut listeners,
+            storage_path,
+            id,
+            replicas,
+        } = replicated_read(
+            "load_aware_read_test",
+            Ipv4Addr::new(127, 0, 0, 54),
+            [Ipv4Addr::new(127, 0, 0, 55), Ipv4Addr::new(127, 0, 0, 56)],
+            Duration::from_secs(30),
+        );
+        let (busy_ip, idle_ip) = (replicas[0], replicas[1]);
+
+        // El duenio anuncia mas carga que la otra replica
+        {
+            let mut coordinator_guard = coordinator.lock().unwrap();
+            coordinator_guard
+                .gossiper
+                .update_load(
+                    busy_ip,
+                    NodeLoad {
+                        pending_queries: 40,
+                        queue_depth: 10,
+                    },
+                )
+                .unwrap();
+            coordinator_guard
+                .gossiper
+                .update_load(
+                    idle_ip,
+                    NodeLoad {
+                        pending_queries: 1,
+                        queue_depth: 0,
+                    },
+                )
+                .unwrap();
+        }
+
+        let rx_reply = run_at_one(
+            &coordinator,
+            &format!("SELECT id, origin FROM sky.flights WHERE id = {}", id),
+        );
+
+        let request = accept_read_request(&listeners.remove(&idle_ip).unwrap());
+        assert!(request.replication);
+        answer_read(&coordinator, idle_ip, request.open_query_id, &id, "EZE");
+
+        match rx_reply.recv_timeout(Duration::from_secs(1)).unwrap() {
+            Frame::Result(result_::Result::Rows(rows)) => {
+                assert_eq!(rows.rows_content.len(), 1);
+            }
+            other => panic!("unexpected frame {:?}", other),
+        }
+
+        // La replica ocupada nunca recibio la lectura
+        let busy_listener = listeners.remove(&busy_ip).unwrap();
+        busy_listener.set_nonblocking(true).unwrap();
+        assert!(busy_listener.accept().is_err());
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_writetime_and_ttl_are_taken_from_the_time_of_the_row() {
+        use query_creator::clauses::insert_cql::NULL_VALUE;
+        use query_creator::clauses::types::datatype::DataType;
+
+        let columns = vec![
+            Column::new("id", DataType::Int, true, false),
+            Column::new("origin", DataType::String, false, true),
+        ];
+        let select_columns = vec![
+            "origin".to_string(),
+            "WRITETIME(origin)".to_string(),
+            "TTL(origin)".to_string(),
+        ];
+
+        let types =
+            InternodeProtocolHandler::select_column_types(&columns, &select_columns).unwrap();
+        assert_eq!(
+            types,
+            vec![
+                ("origin".to_string(), ColumnType::Ascii),
+                ("writetime(origin)".to_string(), ColumnType::Bigint),
+                ("ttl(origin)".to_string(), ColumnType::Int),
+            ]
+        );
+
+        let rows = InternodeProtocolHandler::filter_and_join_columns(
+            vec![
+                "1,EZE,1700000000".to_string(),
+                format!("2,{},1700000001", NULL_VALUE),
+            ],
+            select_columns,
+            vec!["id".to_string(), "origin".to_string()],
+        );
+        assert_eq!(
+            rows,
+            vec![
+                format!("EZE,1700000000,{}", NULL_VALUE),
+                format!("{},{},{}", NULL_VALUE, NULL_VALUE, NULL_VALUE),
+            ]
+        );
+        assert!(create_rows_frame(types, &rows).is_ok());
+    }
+
+    #[test]
+    fn test_latest_version_between_tombstone_and_live_row() {
+        let ip = Ipv4Addr::new(127, 0, 0, 32);
+        let other_ip = Ipv4Addr::new(127, 0, 0, 33);
+
+        // El borrado tiene que ser estrictamente mas nuevo que la fila
+        let responses = vec![
+            (ip, select_response(vec![vec!["1", "AEP", "300"]])),
+            (
+                other_ip,
+                select_response(vec![vec!["1", "AEP", "300;tombstone"]]),
+            ),
+        ];
+        let latest = InternodeProtocolHandler::find_latest_versions(&responses, &[0], &[]);
+        assert!(!InternodeProtocolHandler::is_tombstone(&latest["1"].2));
+
+        let responses = vec![
+            (ip, select_response(vec![vec!["1", "AEP", "300"]])),
+            (
+                other_ip,
+                select_response(vec![vec!["1", "AEP", "301;tombstone"]]),
+            ),
+        ];
+        let latest = InternodeProtocolHandler::find_latest_versions(&responses, &[0], &[]);
+        assert!(InternodeProtocolHandler::is_tombstone(&latest["1"].2));
+
+        // Una escritura posterior al borrado vuelve a crear la fila
+        let responses = vec![
+            (ip, select_response(vec![vec!["1", "AEP", "400"]])),
+            (
+                other_ip,
+                select_response(vec![vec!["1", "EZE", "300;tombstone"]]),
+            ),
+        ];
+        let latest = InternodeProtocolHandler::find_latest_versions(&responses, &[0], &[]);
+        assert_eq!(latest["1"].2, vec!["1", "AEP", "400"]);
+    }
+
+    #[test]
+    fn test_repair_insert_keeps_the_quotes_of_a_text() {
+        use query_creator::clauses::insert_cql::Insert;
+        use query_creator::clauses::types::datatype::DataType;
+
+        let columns = vec![
+            Column::new("id", DataType::Int, true, false),
+            Column::new("origin", DataType::String, false, false),
+        ];
+        let latest_value = vec![
+            "1".to_string(),
+            "O'Hare\\, IL".to_string(),
+            "300".to_string(),
+        ];
+
+        let query = InternodeProtocolHandler::generate_insert_query(
+            &"sky".to_string(),
+            &"flights".to_string(),
+            &columns,
+            &latest_value,
+        );
+        let insert = Insert::deserialize(&query).unwrap();
+        assert_eq!(insert.values, vec!["1", "O'Hare, IL"]);
+    }
+
+    #[test]
+    fn test_a_node_with_empty_schema_pulls_every_keyspace_of_a_seed() {
+        let seed_ip = Ipv4Addr::new(127, 0, 0, 57);
+        let joining_ip = Ipv4Addr::new(127, 0, 0, 58);
+        let listener = TcpListener::bind((joining_ip, INTERNODE_PORT)).unwrap();
+        let seed = Arc::new(Mutex::new(
+            Node::new(
+                seed_ip,
+                vec![seed_ip],
+                PathBuf::from(format!("/tmp/schema_pull_seed_{}", Uuid::new_v4())),
+            )
+            .unwrap(),
+        ));
+        let joining = Arc::new(Mutex::new(
+            Node::new(
+                joining_ip,
+                vec![seed_ip],
+                PathBuf::from(format!("/tmp/schema_pull_joining_{}", Uuid::new_v4())),
+            )
+            .unwrap(),
+        ));
+
+        for query in [
+            "CREATE KEYSPACE sky WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 1}",
+            "CREATE KEYSPACE ground WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 1}",
+        ] {
+            let client_id = seed.lock().unwrap().generate_client_id();
+            let (tx_reply, rx_reply) = mpsc::channel();
+            Node::handle_query_execution(
+                query,
+                "ONE",
+                &seed,
+                Arc::new(Mutex::new(HashMap::new())),
+                tx_reply,
+                client_id,
+            )
+            .unwrap();
+            rx_reply.recv().unwrap();
+        }
+
+        InternodeProtocolHandler::new()
+            .handle_command(
+                &seed,
+                InternodeMessage::new(joining_ip, InternodeMessageContent::SchemaPull),
+                Arc::new(Mutex::new(HashMap::new())),
+            )
+            .unwrap();
+
+        // El seed contesta con todo su schema
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buffer = vec![0u8; 16 * 1024];
+        let read = stream.read(&mut buffer).unwrap();
+        let message = InternodeMessage::from_bytes(&buffer[..read]).unwrap();
+        assert_eq!(message.from, seed_ip);
+        assert!(matches!(
+            message.content,
+            InternodeMessageContent::SchemaPush(_)
+        ));
+
+        InternodeProtocolHandler::new()
+            .handle_command(&joining, message, Arc::new(Mutex::new(HashMap::new())))
+            .unwrap();
+
+        let joining = joining.lock().unwrap();
+        assert!(joining.schema_pulled);
+        for keyspace in ["sky", "ground"] {
+            assert!(joining.get_keyspace(keyspace).unwrap().is_some());
+        }
+    }
+}
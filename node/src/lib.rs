@@ -1,57 +1,112 @@
 // Local modules firstsrc/lib
+pub mod bounded_queue;
+pub mod config;
 mod errors;
+pub mod health;
+pub mod hinted_handoff;
+pub mod idempotency;
 mod internode_protocol;
 mod internode_protocol_handler;
+pub mod metrics;
 mod open_query_handler;
+pub mod query_cache;
 mod query_execution;
 pub mod storage_engine;
+pub mod stream_throttle;
 mod utils;
 
 // Standard libraries
-use std::collections::HashMap;
-use std::io::{BufReader, Read, Write};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{BufReader, ErrorKind, Read, Write};
 use std::net::{Ipv4Addr, SocketAddrV4, TcpListener, TcpStream};
 use std::path::{Path, PathBuf};
-use std::sync::mpsc::Sender;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
 use std::sync::{mpsc, Arc, Mutex};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use std::{env, thread, vec};
 
 // External libraries
+use bounded_queue::{BoundedQueue, OverflowPolicy, DEFAULT_INTERNODE_QUEUE_CAPACITY};
 use chrono::Utc;
+use config::NodeConfig;
 use driver::server::{handle_client_request, Request};
 use errors::NodeError;
-use gossip::structures::application_state::{KeyspaceSchema, NodeStatus, Schema, TableSchema};
-use gossip::Gossiper;
-use internode_protocol::message::{InternodeMessage, InternodeMessageContent};
+use gossip::messages::Payload;
+use gossip::structures::application_state::{
+    CursorSerializable, DataDigest, KeyspaceSchema, NodeLoad, NodeStatus, Schema, TableSchema,
+};
+use gossip::{GossipEvent, Gossiper};
+use health::HealthReport;
+use hinted_handoff::{HintedWrites, PendingWrites, ReplicationMode};
+use idempotency::{
+    split_idempotency_key, IdempotencyKey, IdempotentWrites, Reservation, ReservedWrite,
+    IN_FLIGHT_WAIT_TIMEOUT,
+};
+use internode_protocol::message::{
+    InternodeMessage, InternodeMessageContent, InternodeMessageError,
+};
 use internode_protocol::response::{
     InternodeResponse, InternodeResponseContent, InternodeResponseStatus,
 };
 use internode_protocol::InternodeSerializable;
-use internode_protocol_handler::InternodeProtocolHandler;
+use internode_protocol_handler::{CoordinatorContext, InternodeProtocolHandler, ReplicaDigests};
 // use keyspace::Keyspace;
 use logger::{Color, Logger};
+use metrics::{Metrics, MetricsSnapshot};
 use native_protocol::frame::Frame;
 use native_protocol::messages::auth::{AuthSuccess, Authenticate};
 use native_protocol::messages::error;
-use native_protocol::Serializable;
 use open_query_handler::OpenQueryHandler;
 use partitioner::Partitioner;
+use query_cache::QueryCache;
+use query_creator::clauses::batch_cql::Batch;
 use query_creator::clauses::keyspace::create_keyspace_cql::CreateKeyspace;
 use query_creator::clauses::table::create_table_cql::CreateTable;
 use query_creator::clauses::types::column::Column;
 use query_creator::errors::CQLError;
-use query_creator::{GetTableName, GetUsedKeyspace, NeedsKeyspace, NeedsTable, Query};
+use query_creator::{
+    CreateClientResponse, GetTableName, GetUsedKeyspace, NeedsKeyspace, NeedsTable, Query,
+};
 use query_creator::{NeededResponses, QueryCreator};
 use query_execution::QueryExecution;
 use rustls::pki_types::pem::PemObject;
 use rustls::pki_types::{CertificateDer, PrivateKeyDer};
 use rustls::{ServerConfig, ServerConnection, StreamOwned};
+use storage_engine::data_redistribution::Peers;
+use storage_engine::durability::Durability;
+use storage_engine::table_schema::ClusteringOrderCache;
 use storage_engine::StorageEngine;
+use stream_throttle::StreamThrottle;
 use utils::{check_keyspace, check_table, connect_and_send_message};
 
 const CLIENT_NODE_PORT: u16 = 0x4645; // Hexadecimal of "FE" (FERRUM) = 17989
 const INTERNODE_PORT: u16 = 0x554D; // Hexadecimal of "UM" (FERRUM) = 21837
+/// How long the connection of a client waits for new requests before writing the answers
+/// of the queries that finished in the meantime.
+const CLIENT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Threads that run the queries of each client connection, and queries that can wait for one.
+const DEFAULT_CLIENT_WORKERS: usize = 8;
+/// How long a coordinator waits for the replicas of a read before asking another one.
+const DEFAULT_SPECULATIVE_RETRY_DELAY: Duration = Duration::from_millis(100);
+/// How long a coordinator waits for the replicas of a query before it answers the client with
+/// a timeout.
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a starting node waits for a seed to answer its `SchemaPull` before it serves
+/// clients with the schema it has.
+const SCHEMA_PULL_TIMEOUT: Duration = Duration::from_secs(2);
+/// How often the node reads its tables again to publish their digests through gossip.
+const DATA_DIGEST_INTERVAL: Duration = Duration::from_secs(30);
+/// Largest frame a client may send. A longer length in a header is taken as garbage, since
+/// the connection can no longer tell where the next frame starts.
+const MAX_CLIENT_FRAME_LENGTH: usize = 256 * 1024 * 1024;
+
+// Una consulta de un cliente que espera un worker de su conexion
+struct ClientQuery {
+    stream_id: i16,
+    query: String,
+    consistency_level: String,
+}
 
 /// Represents a node within the distributed network.
 /// The node can manage keyspaces, tables, and handle connections between nodes and clients.
@@ -67,6 +122,50 @@ pub struct Node {
     logger: Logger,
     /// Represents the latest known schema of the cluster.
     schema: Schema,
+    /// Maximum amount of messages waiting to be processed on each internode connection.
+    internode_queue_capacity: usize,
+    /// Amount of internode messages currently waiting to be processed, across all connections.
+    internode_queue_depth: Arc<AtomicUsize>,
+    /// Runtime counters, shared with the threads that serve clients, peers and gossip.
+    metrics: Arc<Metrics>,
+    /// Time without answers after which a read is also sent to the next replica.
+    speculative_retry_delay: Duration,
+    /// Time a client waits for the answer of its query before it gets a timeout error.
+    request_timeout: Duration,
+    /// Threads that run the queries of each client connection.
+    client_workers: usize,
+    /// Recent results of the reads served from this node's storage, `None` while disabled.
+    query_cache: Option<QueryCache>,
+    /// Limit of the rows per second sent to other nodes when the data is redistributed.
+    stream_throttle: StreamThrottle,
+    /// When a write is answered: once its consistency level is met, or after every replica.
+    replication_mode: ReplicationMode,
+    /// Writes for replicas that could not be reached, sent again when they come back.
+    hints: HintedWrites,
+    /// Writes waiting to be sent in the background to each replica.
+    pending_writes: PendingWrites,
+    /// Answers of the recent writes with an idempotency key, to answer their retries.
+    idempotent_writes: IdempotentWrites,
+    /// Nodes the node was started with, asked for the schema before serving clients.
+    seeds: Vec<Ipv4Addr>,
+    /// Whether another node already answered a `SchemaPull` of this node.
+    schema_pulled: bool,
+    /// Nodes that restarted and have to be sent the rows of theirs this node stores.
+    scheduled_repairs: Vec<Ipv4Addr>,
+    /// Port the node listens on for clients.
+    client_port: u16,
+    /// Port every node of the cluster listens on for the others.
+    internode_port: u16,
+    /// Time between two gossip rounds.
+    gossip_interval: Duration,
+    /// When the table files the node writes are synced to disk, shared by all its engines.
+    durability: Durability,
+    /// Clustering order of the tables, read from their definitions and shared by all its engines.
+    clustering_orders: ClusteringOrderCache,
+    /// Password clients authenticate with, `None` if they do not have to.
+    password: Option<String>,
+    /// Bytes that each keyspace with a quota can take on this node.
+    keyspace_quotas: HashMap<String, u64>,
 }
 
 impl Node {
@@ -99,7 +198,8 @@ impl Node {
     ///    - Iterates over the `seeds_nodes` list to add additional nodes to the partitioner, excluding the current node.
     /// 2. **Storage Engine Setup**:
     ///    - Initializes a `StorageEngine` with the provided `storage_path` and node's IP address.
-    ///    - Resets storage folders to ensure a clean state for the node.
+    ///    - Prepares the storage folders. A node that restarts keeps its data and rebuilds its
+    ///      schema from disk with `StorageEngine::load_schema_from_disk`.
     /// 3. **Node Components**:
     ///    - Creates and configures the following components for the node:
     ///      - `OpenQueryHandler`: Manages queries currently being processed by the node.
@@ -112,9 +212,11 @@ impl Node {
     /// - **Seed Nodes**:
     ///   - Seed nodes are critical for the initial discovery of other nodes in the cluster.
     ///   - The current node (`ip`) is excluded from being added as its own seed.
-    /// - **Storage Engine Reset**:
-    ///   - Resetting storage folders ensures no residual data interferes with the node's operation.
-    ///   - This operation should be used with caution in production environments to avoid unintended data loss.
+    /// - **Restarts**:
+    ///   - Only the temporary files of interrupted writes are removed; keyspaces and rows are kept.
+    ///   - The schema rebuilt from disk has no versions, so the one gossiped by the cluster wins.
+    /// - **Settings**:
+    ///   - Every other setting takes its default value, see [`NodeConfig`] and [`Node::from_config`].
     ///
     /// # Errors
     /// - Returns `NodeError` in the following scenarios:
@@ -132,18 +234,98 @@ impl Node {
         seeds_nodes: Vec<Ipv4Addr>,
         storage_path: PathBuf,
     ) -> Result<Node, NodeError> {
-        let mut partitioner = Partitioner::new();
-        partitioner.add_node(ip)?;
+        Self::from_config(NodeConfig::new(ip, seeds_nodes, storage_path))
+    }
 
-        let storage_engine = StorageEngine::new(storage_path.clone(), ip.to_string());
-        storage_engine.reset_folders()?;
+    /// Creates a node with the settings of `config`, as [`Node::new`] does with the default
+    /// ones: the ports it listens on, how often and with how many nodes it gossips, when its
+    /// writes are synced to disk, and how clients authenticate.
+    ///
+    /// A node configured as the replacement of a dead node starts with the tokens of that node
+    /// in its partitioner, and gossips that it replaces it. The token it takes is kept on disk,
+    /// so it keeps it when it restarts, even without being configured as a replacement again.
+    ///
+    /// # Errors
+    /// The same as [`Node::new`].
+    pub fn from_config(config: NodeConfig) -> Result<Node, NodeError> {
+        let NodeConfig {
+            ip,
+            seeds: seeds_nodes,
+            storage_path,
+            client_port,
+            internode_port,
+            gossip_interval,
+            gossip_fanout,
+            durability,
+            password,
+            replaces,
+            datacenter,
+            keyspace_quotas,
+        } = config;
+
+        let durability = Durability::new(durability);
+        let storage_engine = StorageEngine::new(storage_path.clone(), ip.to_string())
+            .with_durability(durability.clone());
+        let is_restart = storage_engine.reset_folders()?;
+
+        // Un reemplazo que se reinicia vuelve al token que tomo, aunque ya no se lo configure
+        let mut partitioner = Partitioner::new();
+        let adopted = storage_engine.load_adopted_token()?;
+        let replaces = match (replaces, adopted) {
+            (Some(dead), Some((token, replaced))) if dead == replaced => {
+                partitioner.add_node_at(ip, token)?;
+                Some(dead)
+            }
+            (Some(dead), _) => {
+                partitioner.replace_node(dead, ip)?;
+                if let Some(token) = partitioner.token_of(&ip) {
+                    storage_engine.save_adopted_token(token, dead)?;
+                }
+                Some(dead)
+            }
+            (None, Some((token, replaced))) => {
+                partitioner.add_node_at(ip, token)?;
+                Some(replaced)
+            }
+            (None, None) => {
+                partitioner.add_node(ip)?;
+                None
+            }
+        };
 
         for seed_ip in seeds_nodes.clone() {
-            if seed_ip != ip {
+            if seed_ip != ip && Some(seed_ip) != replaces {
                 partitioner.add_node(seed_ip)?;
             }
         }
 
+        let mut gossiper = Gossiper::new()
+            .with_endpoint_state(ip)
+            .with_seeds(seeds_nodes.clone())
+            .with_fanout(gossip_fanout);
+        if let Some(dead) = replaces {
+            gossiper
+                .replace(ip, dead)
+                .map_err(|_| NodeError::GossipError)?;
+        }
+        if let Some(datacenter) = &datacenter {
+            gossiper
+                .set_datacenter(ip, datacenter)
+                .map_err(|_| NodeError::GossipError)?;
+        }
+
+        // Un nodo que se reinicia vuelve a conocer las tablas que tiene guardadas
+        let schema = if is_restart {
+            storage_engine.load_schema_from_disk()?
+        } else {
+            Schema::new()
+        };
+        if let Some(endpoint_state) = gossiper.endpoints_state.get_mut(&ip) {
+            endpoint_state.application_state.set_schema(schema.clone());
+            // Cada arranque es una generacion nueva, asi los demas notan que se reinicio
+            endpoint_state.heartbeat_state.generation = Utc::now().timestamp_millis() as u128;
+        }
+
         Ok(Node {
             ip,
             partitioner,
@@ -151,11 +333,31 @@ impl Node {
             clients_keyspace: HashMap::new(),
             last_client_id: 0,
             storage_path: storage_path.clone(),
-            gossiper: Gossiper::new()
-                .with_endpoint_state(ip)
-                .with_seeds(seeds_nodes),
+            gossiper,
             logger: Logger::new(&storage_path, &ip.to_string())?,
-            schema: Schema::new(),
+            schema,
+            internode_queue_capacity: DEFAULT_INTERNODE_QUEUE_CAPACITY,
+            internode_queue_depth: Arc::new(AtomicUsize::new(0)),
+            metrics: Arc::new(Metrics::new()),
+            speculative_retry_delay: DEFAULT_SPECULATIVE_RETRY_DELAY,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            client_workers: DEFAULT_CLIENT_WORKERS,
+            query_cache: None,
+            stream_throttle: StreamThrottle::unlimited(),
+            replication_mode: ReplicationMode::default(),
+            hints: HintedWrites::new(),
+            pending_writes: PendingWrites::default(),
+            idempotent_writes: IdempotentWrites::default(),
+            seeds: seeds_nodes,
+            schema_pulled: false,
+            scheduled_repairs: Vec::new(),
+            client_port,
+            internode_port,
+            gossip_interval,
+            durability,
+            clustering_orders: ClusteringOrderCache::default(),
+            password,
+            keyspace_quotas,
         })
     }
 
@@ -190,7 +392,8 @@ impl Node {
     ///    - Sends periodic heartbeat messages to indicate the node is alive.
     ///
     /// 2. **Cluster Communication**:
-    ///    - Picks target nodes for gossip communication using the `pick_ips` function from the `Gossiper`.
+    ///    - Picks target nodes for gossip communication using the `start_round` function from the `Gossiper`,
+    ///      which also increments the heartbeat of the node even if there are no other nodes to gossip with.
     ///    - Sends `SYN` messages to target nodes, carrying the node's state information.
     ///    - Handles node failures by marking unreachable nodes as `Dead` and triggering redistributions if necessary.
     ///    - Adds newly discovered nodes to the partitioner and integrates them into the cluster.
@@ -219,10 +422,9 @@ impl Node {
     /// - Redistributing data is a resource-intensive operation and should be handled carefully in large clusters.
     ///
     /// # Errors
-    /// - Returns `NodeError` in the following scenarios:
-    ///   - Failure to communicate with other nodes (e.g., network issues).
-    ///   - Errors in partitioner operations, such as adding or removing nodes.
-    ///   - Locking issues while accessing the `Node` or `connections`.
+    /// - Returns `NodeError::LockError` if the `Node` cannot be locked before starting the thread.
+    /// - Errors inside a round (partitioner, schema or gossip errors) are logged and the next
+    ///   round runs as usual. The thread only stops if the `Node` mutex gets poisoned.
     ///
     /// # Importance
     /// The gossip protocol ensures that all nodes in the cluster have a consistent view of the system's state.
@@ -239,194 +441,173 @@ impl Node {
         node: Arc<Mutex<Node>>,
         connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
     ) -> Result<(), NodeError> {
+        let (metrics, logger, interval) = {
+            let node_guard = node.lock()?;
+            (
+                Arc::clone(&node_guard.metrics),
+                node_guard.get_logger(),
+                node_guard.gossip_interval,
+            )
+        };
+        let node_for_digests = Arc::clone(&node);
+        let digests_logger = logger.clone();
+        let node_for_repairs = Arc::clone(&node);
+        let repairs_logger = logger.clone();
+        let repairs_connections = Arc::clone(&connections);
         let _ = thread::spawn(move || {
             let initial_gossip = Instant::now();
-            let mut log;
-            loop {
-                {
-                    {
-                        let mut node_guard = match node.lock() {
-                            Ok(guard) => guard,
-                            Err(_) => return NodeError::LockError,
-                        };
-
-                        let ip = node_guard.ip;
-                        log = node_guard.get_logger();
-                        if initial_gossip.elapsed().as_millis() > 3000 {
-                            node_guard
-                                .gossiper
-                                .change_status(ip, NodeStatus::Normal)
-                                .ok();
-                        }
-                        let _ = node_guard.gossiper.heartbeat(ip);
-                    }
+            let error = Self::run_gossip_rounds(
+                || {
+                    Self::gossip_round(&node, &connections, initial_gossip)?;
+                    metrics.record_gossip_round();
+                    Ok(())
+                },
+                &logger,
+                interval,
+            );
+            let _ = logger.error(&format!("GOSSIP: Stopped: {}", error), true);
+        });
 
-                    let ips: Vec<Ipv4Addr>;
-                    let syn;
-                    {
-                        let node_guard = match node.lock() {
-                            Ok(guard) => guard,
-                            Err(_) => return NodeError::LockError,
-                        };
-                        ips = node_guard
-                            .gossiper
-                            .pick_ips(node_guard.get_ip())
-                            .iter()
-                            .map(|x| **x)
-                            .collect();
-                        syn = node_guard.gossiper.create_syn(node_guard.ip);
-                    }
+        // Leer todas las tablas es caro: los digests se publican cada tanto, en su propio hilo
+        let digests_node = Arc::clone(&node_for_digests);
+        let _ = thread::spawn(move || {
+            let error = Self::run_gossip_rounds(
+                || Self::publish_data_digests(&digests_node),
+                &digests_logger,
+                DATA_DIGEST_INTERVAL,
+            );
+            let _ = digests_logger.error(&format!("DIGESTS: Stopped: {}", error), true);
+        });
 
-                    let mut node_guard = match node.lock() {
-                        Ok(guard) => guard,
-                        Err(_) => return NodeError::LockError,
-                    };
+        // Los nodos que se reiniciaron reciben las filas de sus rangos sin demorar al gossip
+        let _ = thread::spawn(move || {
+            let error = Self::run_gossip_rounds(
+                || Self::run_scheduled_repairs(&node_for_repairs, &repairs_connections),
+                &repairs_logger,
+                interval,
+            );
+            let _ = repairs_logger.error(&format!("REPAIR: Stopped: {}", error), true);
+        });
+        Ok(())
+    }
 
-                    for ip in ips {
-                        let connections_clone = Arc::clone(&connections);
-                        let msg = InternodeMessage::new(
-                            ip.clone(),
-                            InternodeMessageContent::Gossip(syn.clone()),
-                        );
+    /// Runs `round` every `interval` until it fails with an error later rounds cannot recover from.
+    ///
+    /// A failed round is logged and the next one runs as usual: a partitioner or schema error only
+    /// affects that round, and the state it works on is read again from the gossiper every time.
+    /// A `LockError` means the mutex of the node is poisoned, so every later round would fail
+    /// too; in that case the loop stops and the error is returned.
+    fn run_gossip_rounds<F>(mut round: F, logger: &Logger, interval: Duration) -> NodeError
+    where
+        F: FnMut() -> Result<(), NodeError>,
+    {
+        loop {
+            match round() {
+                Ok(()) => {}
+                Err(NodeError::LockError) => return NodeError::LockError,
+                Err(e) => {
+                    let _ = logger.error(&format!("GOSSIP: Round failed: {}", e), true);
+                }
+            }
+            thread::sleep(interval);
+        }
+    }
 
-                        if connect_and_send_message(ip, INTERNODE_PORT, connections_clone, msg)
-                            .is_err()
-                        {
-                            node_guard.gossiper.kill(ip).ok();
-                        }
-                    }
+    /// Runs one round of the gossip loop of `start_gossip`: heartbeat, `SYN` to the picked
+    /// peers, and then the schema and partitioner updates with what the gossiper knows.
+    fn gossip_round(
+        node: &Arc<Mutex<Node>>,
+        connections: &Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+        initial_gossip: Instant,
+    ) -> Result<(), NodeError> {
+        {
+            let (ips, syn) = {
+                let mut node_guard = node.lock()?;
+
+                let ip = node_guard.ip;
+                // Un nodo que se está yendo no vuelve a Normal
+                let is_leaving = node_guard
+                    .gossiper
+                    .get_status(ip)
+                    .map(|status| status.is_leaving())
+                    .unwrap_or(false);
+                if initial_gossip.elapsed().as_millis() > 3000 && !is_leaving {
+                    node_guard
+                        .gossiper
+                        .change_status(ip, NodeStatus::Normal)
+                        .ok();
                 }
 
-                // After each gossip round, update the schema of the node
-                {
-                    let mut node_guard = match node.lock() {
-                        Ok(guard) => guard,
-                        Err(_) => return NodeError::LockError,
-                    };
+                // Se publica la carga actual para que los coordinadores elijan la replica menos ocupada
+                let load = NodeLoad {
+                    pending_queries: node_guard.open_query_handler.open_queries() as u32,
+                    queue_depth: node_guard.internode_queue_depth() as u32,
+                };
+                node_guard.gossiper.update_load(ip, load).ok();
+
+                // El heartbeat avanza aunque no haya nadie con quien hablar
+                node_guard
+                    .gossiper
+                    .start_round(ip)
+                    .map_err(|_| NodeError::GossipError)?
+            };
 
-                    let ip = node_guard.ip;
+            let mut node_guard = node.lock()?;
 
-                    // Sets the schema of the current node to the most updated schema
-                    if let Some(schema) = node_guard.gossiper.get_most_updated_schema() {
-                        if let Some(endpoint_state) =
-                            node_guard.gossiper.endpoints_state.get_mut(&ip)
-                        {
-                            endpoint_state.application_state.set_schema(schema);
-                        } else {
-                            return NodeError::GossipError;
-                        }
-                    }
+            for ip in ips {
+                let connections_clone = Arc::clone(connections);
+                let msg = InternodeMessage::new(ip, InternodeMessageContent::Gossip(syn.clone()));
 
-                    // Updates the latest schema from the gossiper
-                    if let Err(e) = node_guard.set_latest_schema_from_gossiper() {
-                        return e;
-                    };
+                if connect_and_send_message(ip, node_guard.internode_port, connections_clone, msg)
+                    .is_err()
+                {
+                    node_guard.gossiper.kill(ip).ok();
                 }
+            }
+        }
 
-                // After each gossip round, update the partitioner
-                {
-                    // Bloqueo del mutex solo para extraer lo necesario
-                    let (storage_path, self_ip, keyspaces, logger) = {
-                        let node_guard = match node.lock() {
-                            Ok(guard) => guard,
-                            Err(_) => return NodeError::LockError,
-                        };
+        // Las escrituras que no llegaron se reenvian a las replicas que volvieron
+        Self::replay_hints(node, connections)?;
+
+        // After each gossip round, update the schema of the node
+        node.lock()?.sync_schema_with_gossiper()?;
+
+        // After each gossip round, update the partitioner
+        let mut node_guard = node.lock()?;
+        let logger = node_guard.get_logger();
+        if node_guard.update_partitioner_from_gossiper()? {
+            let _ = logger.info("START REDISTRIBUTION...", Color::Cyan, true);
+
+            let keyspaces: Vec<KeyspaceSchema> =
+                node_guard.schema.keyspaces.values().cloned().collect();
+            let partitioner = node_guard.partitioner.clone();
+            let storage_engine = node_guard.storage_engine();
+            let throttle = node_guard.stream_throttle();
+            let peers = Peers {
+                connections: connections.clone(),
+                internode_port: node_guard.internode_port,
+                logger: logger.clone(),
+            };
+            // El nodo sigue atendiendo consultas mientras el throttle espera
+            drop(node_guard);
 
-                        (
-                            node_guard.storage_path.clone(), // Clonar el path de almacenamiento
-                            node_guard.get_ip().to_string(), // Clonar el IP
-                            node_guard.schema.keyspaces.clone(),
-                            node_guard.get_logger(), // Clonar los keyspaces desde el guard     // Referencia mutable al particionador
-                        )
-                    };
-                    let mut node_guard = match node.lock() {
-                        Ok(guard) => guard,
-                        Err(_) => return NodeError::LockError,
-                    };
-                    let endpoints_states = &node_guard.gossiper.endpoints_state.clone();
-                    let partitioner = &mut node_guard.partitioner;
-                    let mut needs_to_redistribute = false;
-
-                    for (ip, state) in endpoints_states {
-                        let is_in_partitioner: bool;
-                        let result = partitioner.node_already_in_partitioner(ip);
-                        if let Ok(is_in) = result {
-                            is_in_partitioner = is_in;
-                        } else {
-                            return NodeError::PartitionerError(
-                                partitioner::errors::PartitionerError::HashError,
-                            );
-                        }
+            let redistribution_result =
+                storage_engine.redistribute_data(keyspaces, &partitioner, &peers, &throttle);
 
-                        if state.application_state.status.is_dead() {
-                            if is_in_partitioner {
-                                needs_to_redistribute = true;
-                                partitioner.remove_node(*ip).ok();
-                                let _ = log.info(
-                                    &format!(
-                                        "NODE {:?} IS DEAD .. New Ring: {:?}",
-                                        ip, partitioner
-                                    ),
-                                    Color::Red,
-                                    true,
-                                );
-                            }
-                        } else {
-                            if !is_in_partitioner {
-                                //println!("se acaba de unir un nodo, redistribuyo");
-                                needs_to_redistribute = true;
-                                partitioner.add_node(*ip).ok();
-                                let _ = log.info(
-                                    &format!("NEW NODE {:?} .. New Ring: {:?}", ip, partitioner),
-                                    Color::Green,
-                                    true,
-                                );
-                            }
-                        }
-                    }
+            // Las filas que este nodo guarda cambiaron
+            node.lock()?.clear_cached_reads();
 
-                    if needs_to_redistribute {
-                        let _ = logger.info("START REDISTRIBUTION...", Color::Cyan, true);
-
-                        // Clonar las variables necesarias para el nuevo hilo
-                        let storage_path = storage_path.clone();
-                        let self_ip = self_ip.clone();
-                        let partitioner = partitioner.clone();
-                        let logger = logger.clone();
-                        let connections = connections.clone();
-                        let keyspaces: Vec<KeyspaceSchema> = keyspaces.values().cloned().collect();
-
-                        let redistribution_result =
-                            storage_engine::StorageEngine::new(storage_path, self_ip)
-                                .redistribute_data(
-                                    keyspaces,
-                                    &partitioner,
-                                    logger.clone(),
-                                    connections,
-                                );
-
-                        match redistribution_result {
-                            Ok(_) => {
-                                let _ =
-                                    logger
-                                        .clone()
-                                        .info("END REDISTRIBUTION...", Color::Cyan, true);
-                            }
-                            Err(e) => {
-                                let _ = logger
-                                    .clone()
-                                    .error(&format!("REDISTRIBUTION FAILED! {:?}", e), true);
-                            }
-                        }
-                    }
+            match redistribution_result {
+                Ok(_) => {
+                    let _ = logger.info("END REDISTRIBUTION...", Color::Cyan, true);
+                }
+                Err(e) => {
+                    let _ = logger.error(&format!("REDISTRIBUTION FAILED! {:?}", e), true);
                 }
-                let gossip_logger = log.clone();
-                let _ = gossip_logger
-                    .clone()
-                    .info("GOSSIP: New Gossip Round", Color::White, true);
-                thread::sleep(std::time::Duration::from_millis(1000));
             }
-        });
+        }
+
+        let _ = logger.info("GOSSIP: New Gossip Round", Color::White, true);
         Ok(())
     }
 
@@ -466,6 +647,8 @@ impl Node {
     /// 3. **Response Calculation**:
     ///    - Determines the number of responses required for the query to satisfy the consistency level:
     ///      - For `NeededResponseCount::One`, requires one response.
+    ///      - For `NeededResponseCount::AllLiveNodes`, requires a response from every node gossip
+    ///        reports as alive, at consistency level `ALL`.
     ///      - For `NeededResponseCount::Specific`, calculates the responses based on the query's specified requirement
     ///        and the replication factor, but caps it at the total number of nodes in the cluster.
     /// 4. **Open Query Initialization**:
@@ -516,6 +699,12 @@ impl Node {
                     calculated_responses
                 }
             }
+            query_creator::NeededResponseCount::AllLiveNodes => self.live_nodes().len(),
+        };
+        // El DDL espera a todos los nodos vivos sin importar el nivel de consistencia
+        let consistency_level = match query.needed_responses() {
+            query_creator::NeededResponseCount::AllLiveNodes => "ALL",
+            _ => consistency_level,
         };
 
         Ok(self.open_query_handler.new_open_query(
@@ -528,6 +717,27 @@ impl Node {
         ))
     }
 
+    /// Nodes of the ring that gossip reports as alive, this one included, in no particular order.
+    ///
+    /// They are the nodes a DDL query is sent to and waits for. A node that is down, or that
+    /// is still joining, gets the change through the gossiped schema instead.
+    fn live_nodes(&self) -> Vec<Ipv4Addr> {
+        let mut nodes: Vec<Ipv4Addr> = self
+            .gossiper
+            .endpoints_state
+            .iter()
+            .filter(|(_, state)| {
+                let status = state.application_state.status;
+                status.is_normal() || status.is_leaving()
+            })
+            .map(|(ip, _)| *ip)
+            .collect();
+        if !nodes.contains(&self.ip) {
+            nodes.push(self.ip);
+        }
+        nodes
+    }
+
     fn get_ip(&self) -> Ipv4Addr {
         self.ip
     }
@@ -535,411 +745,1094 @@ impl Node {
     pub fn get_logger(&self) -> Logger {
         self.logger.clone()
     }
-    fn get_ip_string(&self) -> String {
-        self.ip.to_string()
+
+    /// Storage engine over the data of this node, that syncs its writes as the durability
+    /// policy of the node says and keeps its keyspaces within their quotas.
+    fn storage_engine(&self) -> StorageEngine {
+        StorageEngine::new(self.storage_path.clone(), self.ip.to_string())
+            .with_durability(self.durability.clone())
+            .with_clustering_order_cache(self.clustering_orders.clone())
+            .with_keyspace_quotas(&self.keyspace_quotas)
     }
 
-    fn get_how_many_nodes_i_know(&self) -> usize {
-        self.partitioner.get_nodes().len() - 1
+    /// Syncs to disk every table file this node wrote and did not sync yet.
+    pub fn flush(&self) -> Result<(), NodeError> {
+        Ok(self.durability.flush()?)
+    }
+    /// Sets how many messages can wait on each internode connection before the
+    /// overflow policy is applied. Only affects connections accepted afterwards.
+    pub fn set_internode_queue_capacity(&mut self, capacity: usize) {
+        self.internode_queue_capacity = capacity.max(1);
     }
 
-    fn get_partitioner(&self) -> Partitioner {
-        self.partitioner.clone()
+    /// Sets how long a read waits for its replicas before it is also sent to the next one
+    /// that holds the partition.
+    pub fn set_speculative_retry_delay(&mut self, delay: Duration) {
+        self.speculative_retry_delay = delay;
     }
 
-    fn get_open_handle_query(&mut self) -> &mut OpenQueryHandler {
-        &mut self.open_query_handler
+    /// Sets how long the coordinator waits for the replicas of a query before it answers the
+    /// client with a `ReadTimeout`, for a `SELECT`, or a `WriteTimeout`, for anything else.
+    pub fn set_request_timeout(&mut self, timeout: Duration) {
+        self.request_timeout = timeout;
     }
 
-    fn generate_client_id(&mut self) -> i32 {
-        self.last_client_id += 1;
-        self.clients_keyspace.insert(self.last_client_id, None);
-        self.last_client_id
+    /// Sets how many queries of a single client connection run at once. As many more wait for
+    /// a free thread, and past that the node stops reading from the connection. Only affects
+    /// connections accepted afterwards.
+    pub fn set_client_workers(&mut self, workers: usize) {
+        self.client_workers = workers.max(1);
     }
 
-    fn update_schema_in_storage(&self, old_schema: Schema) -> Result<(), NodeError> {
-        let storage = StorageEngine::new(self.storage_path.clone(), self.ip.to_string());
+    /// Keeps the results of up to `capacity` local reads for `ttl`, so identical `SELECT`s
+    /// arriving close together are answered without scanning the table again. Any write this
+    /// node applies to a table drops its cached results. Replaces the previous cache, if any.
+    ///
+    /// See `query_cache::DEFAULT_QUERY_CACHE_CAPACITY` and `DEFAULT_QUERY_CACHE_TTL` for
+    /// reasonable values.
+    pub fn enable_query_cache(&mut self, capacity: usize, ttl: Duration) {
+        self.query_cache = Some(QueryCache::new(capacity, ttl));
+    }
 
-        // Process new or updated keyspaces
-        for (keyspace_name, keyspace) in self.schema.keyspaces.clone() {
-            if !old_schema.keyspaces.contains_key(&keyspace_name) {
-                // Create a new keyspace
-                storage.create_keyspace(&keyspace_name)?;
-            }
+    /// Stops caching reads and drops every cached result.
+    pub fn disable_query_cache(&mut self) {
+        self.query_cache = None;
+    }
 
-            let old_tables = old_schema
-                .keyspaces
-                .get(&keyspace_name)
-                .map(|keyspace| keyspace.tables.clone())
-                .unwrap_or_else(Vec::new);
+    /// Returns the cache of local reads, if it is enabled.
+    pub fn query_cache(&self) -> Option<&QueryCache> {
+        self.query_cache.as_ref()
+    }
 
-            // Update existing keyspace
-            self.update_keyspace_tables(&storage, &keyspace_name, old_tables, keyspace.tables)?
-        }
+    /// Limits the rows per second this node streams to others when the ring changes and its
+    /// data is redistributed. `None`, the default, streams as fast as possible.
+    ///
+    /// The limit can be changed while a redistribution is running through the handle
+    /// returned by [`Node::stream_throttle`].
+    pub fn set_stream_throughput(&mut self, rows_per_second: Option<u64>) {
+        self.stream_throttle.set_limit(rows_per_second);
+    }
 
-        // Process deleted keyspaces
-        for (keyspace_name, keyspace) in old_schema.clone().keyspaces {
-            if !self.schema.keyspaces.contains_key(&keyspace_name) {
-                // Drop keyspace
-                storage.drop_keyspace(&keyspace_name, &self.ip.to_string())?;
-            } else {
-                // Drop tables from existing keyspace
+    /// Returns a handle to the throttle of the redistribution, which shares its limit with
+    /// the node.
+    pub fn stream_throttle(&self) -> StreamThrottle {
+        self.stream_throttle.clone()
+    }
 
-                let new_tables = self
-                    .schema
-                    .keyspaces
-                    .get(&keyspace_name)
-                    .map(|keyspace| keyspace.tables.clone())
-                    .unwrap_or_else(Vec::new);
+    /// Sets whether writes coordinated by this node are answered as soon as their consistency
+    /// level is met (`ReplicationMode::Async`, the default) or only after every replica
+    /// acknowledged them (`ReplicationMode::Sync`).
+    pub fn set_replication_mode(&mut self, mode: ReplicationMode) {
+        self.replication_mode = mode;
+    }
 
-                self.remove_obsolete_tables(&storage, &keyspace_name, keyspace.tables, new_tables)?;
-            }
-        }
-        Ok(())
+    /// Returns when writes coordinated by this node are answered, see `set_replication_mode`.
+    pub fn replication_mode(&self) -> ReplicationMode {
+        self.replication_mode
     }
 
-    // Updates tables in an existing keyspace by creating new tables if they don't exist.
-    fn update_keyspace_tables(
-        &self,
-        storage: &StorageEngine,
-        keyspace_name: &str,
-        old_tables: Vec<TableSchema>,
-        new_tables: Vec<TableSchema>,
+    /// Returns how many writes for `ip` are kept as hints, waiting for it to come back.
+    pub fn pending_hints(&self, ip: Ipv4Addr) -> usize {
+        self.hints.pending(ip)
+    }
+
+    /// Sends the hinted writes of every node that gossip reports alive.
+    ///
+    /// The writes of a node are sent in order; if one fails, it and the ones after it are
+    /// kept for the next round, before the hints added meanwhile.
+    fn replay_hints(
+        node: &Arc<Mutex<Node>>,
+        connections: &Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
     ) -> Result<(), NodeError> {
-        for table in new_tables {
-            if old_tables
-                .iter()
-                .find(|old_table| old_table.get_name() == table.get_name())
-                .is_none()
-            {
-                // Create a new table
-                let cols = table.get_columns();
-                let col_names: Vec<&str> = cols.iter().map(|c| c.name.as_str()).collect();
+        let (internode_port, pending): (u16, Vec<(Ipv4Addr, Vec<InternodeMessage>)>) = {
+            let mut node_guard = node.lock()?;
+            let alive: Vec<Ipv4Addr> = node_guard
+                .hints
+                .targets()
+                .into_iter()
+                .filter(|ip| {
+                    node_guard
+                        .gossiper
+                        .get_status(*ip)
+                        .is_ok_and(|status| status.is_alive())
+                })
+                .collect();
+            let pending = alive
+                .into_iter()
+                .map(|ip| (ip, node_guard.hints.take(ip)))
+                .collect();
+            (node_guard.internode_port, pending)
+        };
 
-                storage.create_table(keyspace_name, &table.get_name(), col_names)?
+        for (ip, messages) in pending {
+            let mut messages = messages.into_iter();
+            while let Some(message) = messages.next() {
+                if connect_and_send_message(
+                    ip,
+                    internode_port,
+                    connections.clone(),
+                    message.clone(),
+                )
+                .is_err()
+                {
+                    let mut node_guard = node.lock()?;
+                    node_guard
+                        .hints
+                        .restore(ip, std::iter::once(message).chain(messages).collect());
+                    break;
+                }
             }
         }
         Ok(())
     }
 
-    // Removes tables from an existing keyspace that are no longer present in the updated schema.
-    fn remove_obsolete_tables(
-        &self,
-        storage: &StorageEngine,
-        keyspace_name: &str,
-        old_tables: Vec<TableSchema>,
-        new_tables: Vec<TableSchema>,
-    ) -> Result<(), NodeError> {
-        for table in old_tables {
-            if new_tables
-                .iter()
-                .find(|new_table| new_table.get_name() == table.get_name())
-                .is_none()
-            {
-                // Drop table
-                storage.drop_table(keyspace_name, &table.get_name())?;
+    /// Makes a write wait for every one of its `replicas` when the node replicates
+    /// synchronously. Under `ReplicationMode::Async` the consistency level decides.
+    fn apply_replication_mode_to_query(&mut self, open_query_id: i32, replicas: &[Ipv4Addr]) {
+        if self.replication_mode != ReplicationMode::Sync {
+            return;
+        }
+        if let Some(query) = self.open_query_handler.get_query_mut(&open_query_id) {
+            query.wait_for_every_node(replicas.len());
+        }
+    }
+
+    /// Sets the datacenter this node runs in and publishes it to the other nodes through
+    /// gossip. Coordinators use it to serve `LOCAL_QUORUM` and `LOCAL_ONE` with the replicas of
+    /// their own datacenter. Nodes that never set one are all in the same datacenter.
+    pub fn set_datacenter(&mut self, datacenter: &str) -> Result<(), NodeError> {
+        self.gossiper
+            .set_datacenter(self.ip, datacenter)
+            .map_err(|_| NodeError::GossipError)
+    }
+
+    /// Schedules an anti-entropy repair for each node gossip reported as restarted, or as the
+    /// replacement of a dead node, since the last call, unless one is already pending. Returns
+    /// the nodes scheduled by this call.
+    ///
+    /// A node that restarted may have missed writes while it was down or lost its data, and a
+    /// replacement starts without the rows of the ranges it took, so the next repair round
+    /// sends it the rows of its ranges this node stores.
+    pub fn schedule_repairs_of_restarted_peers(&mut self) -> Vec<Ipv4Addr> {
+        let mut scheduled = Vec::new();
+        for event in self.gossiper.take_events() {
+            let ip = match event {
+                GossipEvent::Restarted(ip) => ip,
+                GossipEvent::Replaced { replacement, .. } => replacement,
+            };
+            if ip != self.ip && !self.scheduled_repairs.contains(&ip) {
+                self.scheduled_repairs.push(ip);
+                scheduled.push(ip);
             }
         }
-        Ok(())
+        scheduled
     }
 
-    fn set_latest_schema_from_gossiper(&mut self) -> Result<(), NodeError> {
-        let old_schema = self.schema.clone();
+    /// Returns the nodes with an anti-entropy repair waiting for the next repair round.
+    pub fn scheduled_repairs(&self) -> &[Ipv4Addr] {
+        &self.scheduled_repairs
+    }
 
-        // acá se actualiza el schema del nodo
-        self.schema = match self.gossiper.endpoints_state.get(&self.ip) {
-            Some(endpoint_state) => endpoint_state.application_state.schema.clone(),
-            None => return Err(NodeError::LockError),
+    /// Runs the anti-entropy repairs scheduled for the nodes that gossip reports alive, and
+    /// keeps the others for a later round. `start_gossip` calls it on its own thread, every
+    /// gossip interval, since streaming the rows can take much longer than a gossip round.
+    fn run_scheduled_repairs(
+        node: &Arc<Mutex<Node>>,
+        connections: &Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+    ) -> Result<(), NodeError> {
+        let (replicas, keyspaces, partitioner, storage_engine, throttle, peers) = {
+            let mut node_guard = node.lock()?;
+            node_guard.schedule_repairs_of_restarted_peers();
+            let (alive, waiting): (Vec<Ipv4Addr>, Vec<Ipv4Addr>) =
+                node_guard.scheduled_repairs.iter().partition(|ip| {
+                    node_guard
+                        .gossiper
+                        .get_status(**ip)
+                        .is_ok_and(|status| status.is_alive())
+                });
+            if alive.is_empty() {
+                return Ok(());
+            }
+            node_guard.scheduled_repairs = waiting;
+            (
+                alive,
+                node_guard
+                    .schema
+                    .keyspaces
+                    .values()
+                    .cloned()
+                    .collect::<Vec<KeyspaceSchema>>(),
+                node_guard.partitioner.clone(),
+                node_guard.storage_engine(),
+                node_guard.stream_throttle(),
+                Peers {
+                    connections: connections.clone(),
+                    internode_port: node_guard.internode_port,
+                    logger: node_guard.get_logger(),
+                },
+            )
         };
 
-        self.update_schema_in_storage(old_schema)?;
-        //println!("Schema updated: {:?}", self.schema);
+        let logger = &peers.logger;
+        for replica in replicas {
+            match storage_engine.repair_replica(
+                keyspaces.clone(),
+                &partitioner,
+                replica,
+                &peers,
+                &throttle,
+            ) {
+                Ok(rows) => {
+                    let _ = logger.info(
+                        &format!(
+                            "REPAIR: sent {} row(s) to {} after it restarted",
+                            rows, replica
+                        ),
+                        Color::Cyan,
+                        true,
+                    );
+                }
+                Err(e) => {
+                    let _ = logger.error(&format!("REPAIR of {} FAILED! {:?}", replica, e), true);
+                }
+            }
+        }
         Ok(())
     }
 
-    fn add_keyspace(&mut self, new_keyspace: CreateKeyspace) -> Result<(), NodeError> {
-        self.gossiper
-            .add_keyspace(self.ip, new_keyspace)
-            .map_err(|_| NodeError::KeyspaceError)?;
-
-        // We manually update the latest schema right after modification so
-        // we don't have to wait for the next gossip round.
-        self.set_latest_schema_from_gossiper()?;
+    /// Takes a snapshot of the files this node stores of `keyspace.table` inside `dest_dir`,
+    /// for a backup, and returns its folder. See [`StorageEngine::snapshot`].
+    pub fn snapshot_table(
+        &self,
+        keyspace: &str,
+        table: &str,
+        dest_dir: &Path,
+    ) -> Result<PathBuf, NodeError> {
+        let storage = self.storage_engine();
+        Ok(storage.snapshot(keyspace, table, dest_dir)?)
+    }
 
+    /// Brings `keyspace.table` back to a snapshot taken by [`Node::snapshot_table`], and drops
+    /// the reads of the table the node had cached.
+    pub fn restore_table_snapshot(
+        &mut self,
+        keyspace: &str,
+        table: &str,
+        snapshot_path: &Path,
+    ) -> Result<(), NodeError> {
+        let storage = self.storage_engine();
+        storage.restore_snapshot(keyspace, table, snapshot_path)?;
+        self.invalidate_cached_reads(keyspace, table);
         Ok(())
     }
 
-    fn remove_keyspace(&mut self, keyspace_name: String) -> Result<(), NodeError> {
-        self.gossiper
-            .remove_keyspace(self.ip, &keyspace_name)
-            .map_err(|_| NodeError::KeyspaceError)?;
+    /// Reads the rows this node stores of every table and publishes their digests through
+    /// gossip, one per token range, so that a replica that diverges from its peers in a range
+    /// shows in [`Node::data_digests_snapshot`].
+    ///
+    /// The tables are read without holding the lock of the node, which is only taken to copy
+    /// the schema and the partitioner and to publish the result.
+    pub fn publish_data_digests(node: &Arc<Mutex<Node>>) -> Result<(), NodeError> {
+        let (storage, keyspaces, partitioner) = {
+            let node_guard = node.lock()?;
+            (
+                node_guard.storage_engine(),
+                node_guard
+                    .schema
+                    .keyspaces
+                    .values()
+                    .cloned()
+                    .collect::<Vec<KeyspaceSchema>>(),
+                node_guard.partitioner.clone(),
+            )
+        };
 
-        // Recorre los clients_keyspace para encontrar y actualizar keyspaces coincidentes
-        for (_, client_keyspace) in self.clients_keyspace.iter_mut() {
-            if let Some(ref keyspace) = client_keyspace {
-                if keyspace == &keyspace_name {
-                    *client_keyspace = None;
+        let mut data_digests = BTreeMap::new();
+        for keyspace in &keyspaces {
+            for table in keyspace.get_tables() {
+                let digests =
+                    storage.data_digests_by_range(&keyspace.get_name(), &table, &partitioner)?;
+                for (owner, digest) in digests {
+                    data_digests.insert(
+                        format!("{}.{}@{}", keyspace.get_name(), table.get_name(), owner),
+                        digest,
+                    );
                 }
             }
         }
 
-        // We manually update the latest schema right after modification so
-        // we don't have to wait for the next gossip round.
-        self.set_latest_schema_from_gossiper()?;
+        let mut node_guard = node.lock()?;
+        let ip = node_guard.ip;
+        node_guard
+            .gossiper
+            .update_data_digests(ip, data_digests)
+            .map_err(|_| NodeError::GossipError)
+    }
 
-        Ok(())
+    /// Whether `ip` advertised the same datacenter as this node.
+    pub fn is_in_local_datacenter(&self, ip: Ipv4Addr) -> bool {
+        ip == self.ip || self.gossiper.get_datacenter(ip) == self.gossiper.get_datacenter(self.ip)
     }
 
-    fn _set_actual_keyspace(
+    // Para LOCAL_QUORUM y LOCAL_ONE la query solo espera a las replicas de este datacenter,
+    // y para EACH_QUORUM a un quorum de cada datacenter. Si no hay ninguna replica en este
+    // datacenter, una query LOCAL_* no se puede cumplir: se cierra y falla de inmediato.
+    fn set_local_replicas_of_query(
         &mut self,
-        keyspace_name: String,
-        client_id: i32,
+        open_query_id: i32,
+        replicas: &[Ipv4Addr],
     ) -> Result<(), NodeError> {
-        // Configurar el keyspace actual del cliente usando el índice encontrado
-        self.clients_keyspace.insert(client_id, Some(keyspace_name));
-
+        let local_replicas: HashSet<Ipv4Addr> = replicas
+            .iter()
+            .copied()
+            .filter(|ip| self.is_in_local_datacenter(*ip))
+            .collect();
+        let is_datacenter_local = self
+            .open_query_handler
+            .get_query_mut(&open_query_id)
+            .is_some_and(|query| query.get_consistency_level().is_datacenter_local());
+        if is_datacenter_local && local_replicas.is_empty() {
+            self.open_query_handler.remove_query(open_query_id);
+            return Err(NodeError::Unavailable(
+                "no replica of the partition is in the local datacenter".to_string(),
+            ));
+        }
+        // Los nodos que no anunciaron su datacenter quedan todos juntos en uno sin nombre
+        let replica_datacenters = replicas
+            .iter()
+            .map(|ip| (*ip, self.gossiper.get_datacenter(*ip).unwrap_or_default()))
+            .collect();
+        if let Some(query) = self.open_query_handler.get_query_mut(&open_query_id) {
+            query.set_local_replicas(local_replicas);
+            query.set_replica_datacenters(replica_datacenters);
+        }
         Ok(())
     }
 
-    fn _update_keyspace(&mut self, client_id: i32, new_keyspace: KeyspaceSchema) {
-        let new_key_name = new_keyspace.clone().get_name().clone();
-        self.clients_keyspace
-            .insert(client_id, Some(new_key_name.clone()));
+    fn invalidate_cached_reads(&mut self, keyspace: &str, table: &str) {
+        if let Some(cache) = self.query_cache.as_mut() {
+            cache.invalidate_table(keyspace, table);
+        }
+    }
 
-        for (_, (kespace_name, _)) in self.schema.keyspaces.clone().iter().enumerate() {
-            if kespace_name == &new_key_name {
-                self.schema
-                    .keyspaces
-                    .insert(new_key_name.clone(), new_keyspace.clone());
-            }
-            // if new_key_name == keyspace.get_name() {
-            //     self.keyspaces[i] = new_keyspace.clone();
-            // }
+    fn clear_cached_reads(&mut self) {
+        if let Some(cache) = self.query_cache.as_mut() {
+            cache.clear();
         }
-        // unimplemented!()
     }
 
-    fn add_table(&mut self, new_table: CreateTable, keyspace_name: &str) -> Result<(), NodeError> {
+    /// Returns the amount of internode messages received but not yet processed.
+    pub fn internode_queue_depth(&self) -> usize {
+        self.internode_queue_depth.load(Ordering::SeqCst)
+    }
+
+    /// Returns the current value of the node's runtime counters.
+    pub fn metrics_snapshot(&self) -> Result<MetricsSnapshot, NodeError> {
+        self.metrics.snapshot()
+    }
+
+    /// Status of every node this node knows through gossip, including itself.
+    pub fn membership_snapshot(&self) -> BTreeMap<Ipv4Addr, NodeStatus> {
         self.gossiper
-            .add_table(self.ip, new_table, keyspace_name)
-            .map_err(|_| NodeError::GossipError)?;
+            .endpoints_state
+            .iter()
+            .map(|(ip, state)| (*ip, state.application_state.status))
+            .collect()
+    }
 
-        // We manually update the latest schema right after modification so
-        // we don't have to wait for the next gossip round.
-        self.set_latest_schema_from_gossiper()?;
+    /// Digests of the token ranges advertised by every node this node knows through gossip, by
+    /// `keyspace.table@owner`, where `owner` is the node the range belongs to. Replicas of the
+    /// same range that hold the same rows have the same digest.
+    pub fn data_digests_snapshot(&self) -> BTreeMap<Ipv4Addr, BTreeMap<String, DataDigest>> {
+        self.gossiper
+            .endpoints_state
+            .iter()
+            .map(|(ip, state)| (*ip, state.application_state.data_digests.clone()))
+            .collect()
+    }
 
-        Ok(())
+    /// Replication factor of `keyspace` and the table digests gossiped by every node, for the
+    /// coordinator of a read to backfill the replicas that are missing the rows read.
+    pub fn replica_digests(&self, keyspace: Option<&KeyspaceSchema>) -> ReplicaDigests {
+        ReplicaDigests {
+            replication_factor: keyspace
+                .map_or(1, |keyspace| keyspace.get_replication_factor() as usize),
+            data_digests: self.data_digests_snapshot(),
+        }
     }
 
-    fn get_table(
-        &self,
-        table_name: String,
-        client_keyspace: KeyspaceSchema,
-    ) -> Result<TableSchema, NodeError> {
-        // Busca y devuelve la tabla solicitada
-        // TODO: acá buscar en schema, no en client_keyspace ???
+    /// Reports whether the node is ready to serve, as answered to `health::HEALTH_CHECK_QUERY`.
+    ///
+    /// The node is bootstrapped once it left the `Bootstrap` status. Data is redistributed
+    /// without holding the node lock, so a bootstrapped node may still be streaming rows to or
+    /// from its peers after the partitioner changed, and a read served meanwhile can miss them.
+    /// The schema is agreed when every node in `Normal` status, as known through gossip, has the
+    /// same schema as this one.
+    pub fn health(&self) -> HealthReport {
+        let status = self
+            .gossiper
+            .get_status(self.ip)
+            .unwrap_or(NodeStatus::Bootstrap);
+        let own_schema = self
+            .gossiper
+            .endpoints_state
+            .get(&self.ip)
+            .map(|state| &state.application_state.schema);
+        let schema_agreement = self
+            .gossiper
+            .endpoints_state
+            .values()
+            .filter(|state| state.application_state.status.is_normal())
+            .all(|state| Some(&state.application_state.schema) == own_schema);
+
+        HealthReport {
+            status,
+            bootstrapped: !status.is_starting(),
+            schema_agreement,
+        }
+    }
 
-        client_keyspace
-            .get_table(&table_name)
-            .map_err(|_| NodeError::CQLError(CQLError::InvalidTable))
+    /// Joins the cluster formed by `seeds` without going through the TCP listeners.
+    ///
+    /// The node is marked as `Normal` and then runs a gossip round with the seeds, as done by
+    /// `gossip_with`. Data is not redistributed.
+    ///
+    /// Calling it with no seeds starts a new cluster with this node alone. A seed must have
+    /// joined a cluster itself before other nodes can learn its status through it. A node that
+    /// was down can call it again to catch up with the cluster, including its schema.
+    pub fn join_cluster(
+        node: &Arc<Mutex<Node>>,
+        seeds: &[Arc<Mutex<Node>>],
+    ) -> Result<(), NodeError> {
+        {
+            let mut guard_node = node.lock()?;
+            let ip = guard_node.ip;
+            guard_node
+                .gossiper
+                .change_status(ip, NodeStatus::Normal)
+                .map_err(|_| NodeError::GossipError)?;
+        }
+
+        Self::gossip_with(node, seeds)
     }
 
-    fn remove_table(&mut self, table_name: String, open_query_id: i32) -> Result<(), NodeError> {
-        // Obtiene el keyspace actual del cliente
-        let keyspace_name = self
-            .get_open_handle_query()
-            .get_keyspace_of_query(open_query_id)?
-            .ok_or(NodeError::KeyspaceError)?
-            .get_name();
+    /// Runs a gossip round between the node and `peers` in process.
+    ///
+    /// The node sends its heartbeat and exchanges a full round (`SYN`, `ACK` and `ACK2`, in
+    /// both directions) with each peer, so both sides learn about each other. Afterwards every
+    /// node involved updates its partitioner and its schema from the gossiped state, as the
+    /// gossip loop of `start_gossip` does.
+    pub fn gossip_with(
+        node: &Arc<Mutex<Node>>,
+        peers: &[Arc<Mutex<Node>>],
+    ) -> Result<(), NodeError> {
+        {
+            let mut guard_node = node.lock()?;
+            let ip = guard_node.ip;
+            guard_node
+                .gossiper
+                .heartbeat(ip)
+                .map_err(|_| NodeError::GossipError)?;
+        }
+
+        for peer in peers {
+            if Arc::ptr_eq(node, peer) {
+                continue;
+            }
+            Self::exchange_gossip(node, peer)?;
+            Self::exchange_gossip(peer, node)?;
+
+            let mut guard_peer = peer.lock()?;
+            guard_peer.update_partitioner_from_gossiper()?;
+            guard_peer.sync_schema_with_gossiper()?;
+        }
+
+        let mut guard_node = node.lock()?;
+        guard_node.update_partitioner_from_gossiper()?;
+        guard_node.sync_schema_with_gossiper()
+    }
 
+    /// Starts leaving the cluster.
+    ///
+    /// The node is marked as `Leaving` and removed from its own partitioner. Other nodes remove
+    /// it from theirs once they learn the new status through gossip, and a running gossip loop
+    /// does not bring the node back to `Normal`.
+    pub fn leave_cluster(&mut self) -> Result<(), NodeError> {
+        let ip = self.ip;
         self.gossiper
-            .remove_table(self.ip, &keyspace_name, &table_name)
-            .map_err(|_| NodeError::KeyspaceError)?;
+            .change_status(ip, NodeStatus::Leaving)
+            .map_err(|_| NodeError::GossipError)?;
+        self.gossiper
+            .heartbeat(ip)
+            .map_err(|_| NodeError::GossipError)?;
 
-        // We manually update the latest schema right after modification so
-        // we don't have to wait for the next gossip round.
-        self.set_latest_schema_from_gossiper()?;
+        self.update_partitioner_from_gossiper()?;
+        Ok(())
+    }
+
+    /// Runs a gossip round started by `from` against `to`, handling every message in process.
+    fn exchange_gossip(from: &Arc<Mutex<Node>>, to: &Arc<Mutex<Node>>) -> Result<(), NodeError> {
+        let mut from_guard = from.lock()?;
+        let mut to_guard = to.lock()?;
+
+        // El que inicia necesita una entrada para el otro nodo, como con las seeds de `new`
+        let to_ip = to_guard.ip;
+        from_guard
+            .gossiper
+            .endpoints_state
+            .entry(to_ip)
+            .or_default();
+
+        let from_ip = from_guard.ip;
+        let syn = match from_guard.gossiper.create_syn(from_ip).payload {
+            Payload::Syn(syn) => syn,
+            _ => return Err(NodeError::GossipError),
+        };
+        let ack = to_guard.gossiper.handle_syn(&syn);
+        let ack2 = from_guard.gossiper.handle_ack(&ack);
+        to_guard.gossiper.handle_ack2(&ack2);
 
         Ok(())
     }
 
-    fn update_table(
-        &mut self,
-        _keyspace_name: &str,
-        _new_table: CreateTable,
-    ) -> Result<(), NodeError> {
-        // // Encuentra el índice del Keyspace en el Vec
-        // if let Some(index) = self
-        //     .keyspaces
-        //     .iter()
-        //     .position(|k| k.get_name() == keyspace_name)
-        // {
-        //     // Obtenemos una referencia mutable al Keyspace en el índice encontrado
-        //     let keyspace = &mut self.keyspaces[index];
-
-        //     // Encuentra la posición de la tabla a actualizar en el keyspace
-        //     let table_index = keyspace
-        //         .tables
-        //         .iter()
-        //         .position(|table| table.get_name() == new_table.get_name())
-        //         .ok_or(NodeError::CQLError(CQLError::InvalidTable))?;
-
-        //     // Reemplaza la tabla existente con la nueva en el Keyspace
-        //     keyspace.tables[table_index] = Table::new(new_table);
-        //     Ok(())
-        // } else {
-        //     // Retorna un error si el Keyspace no se encuentra
-        //     Err(NodeError::KeyspaceError)
-        // }
-        unimplemented!()
+    /// Merges the schemas gossiped by every node into the schema of this node, keeping the
+    /// newest version of each keyspace and table, and applies the result to the storage.
+    ///
+    /// DDL queries are applied by every node of the ring that gossip reports as alive before
+    /// the client is answered. This is how the rest of the nodes get them, including nodes that
+    /// were down when the query ran and catch up once they gossip again.
+    fn sync_schema_with_gossiper(&mut self) -> Result<(), NodeError> {
+        let ip = self.ip;
+
+        let schema = self
+            .gossiper
+            .get_merged_schema(ip)
+            .map_err(|_| NodeError::GossipError)?;
+        let endpoint_state = self
+            .gossiper
+            .endpoints_state
+            .get_mut(&ip)
+            .ok_or(NodeError::GossipError)?;
+        if endpoint_state.application_state.schema != schema {
+            endpoint_state.application_state.set_schema(schema);
+        }
+
+        // Updates the latest schema from the gossiper
+        self.set_latest_schema_from_gossiper()
     }
 
-    fn table_already_exist(
-        &mut self,
-        table_name: String,
-        keyspace_name: String,
+    /// Merges `schema`, pushed by another node after a `SchemaPull`, into the schema of this
+    /// node, keeping the newest version of each keyspace and table as gossip does.
+    pub fn apply_pushed_schema(&mut self, schema: &Schema) -> Result<(), NodeError> {
+        let ip = self.ip;
+        let endpoint_state = self
+            .gossiper
+            .endpoints_state
+            .get_mut(&ip)
+            .ok_or(NodeError::GossipError)?;
+
+        let mut merged = endpoint_state.application_state.schema.clone();
+        merged.merge(schema);
+        if endpoint_state.application_state.schema != merged {
+            endpoint_state.application_state.set_schema(merged);
+        }
+        self.schema_pulled = true;
+
+        self.set_latest_schema_from_gossiper()
+    }
+
+    /// Asks the seeds for their schema and waits up to `timeout` for the first answer, which
+    /// is applied by the internode listener when the `SchemaPush` arrives.
+    ///
+    /// Gossip would bring the schema as well, but only after a few rounds; a node that serves
+    /// clients before that rejects queries on tables it does not know yet. Returns whether a
+    /// seed answered in time. A node whose only seed is itself has nothing to wait for.
+    pub fn pull_schema_from_seeds(
+        node: &Arc<Mutex<Node>>,
+        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+        timeout: Duration,
     ) -> Result<bool, NodeError> {
-        let keyspace = self
-            .get_keyspace(&keyspace_name)?
-            .ok_or(NodeError::KeyspaceError)?;
+        let (self_ip, internode_port, seeds) = {
+            let guard_node = node.lock()?;
+            (
+                guard_node.ip,
+                guard_node.internode_port,
+                guard_node.seeds.clone(),
+            )
+        };
 
-        // Verifica si la tabla ya existe en el keyspace del cliente
-        for table in keyspace.get_tables() {
-            if table.get_name() == table_name {
+        let mut asked = false;
+        for seed in seeds.into_iter().filter(|seed| *seed != self_ip) {
+            let message = InternodeMessage::new(self_ip, InternodeMessageContent::SchemaPull);
+            asked |= connect_and_send_message(seed, internode_port, connections.clone(), message)
+                .is_ok();
+        }
+        if !asked {
+            return Ok(false);
+        }
+
+        let started = Instant::now();
+        while started.elapsed() < timeout {
+            if node.lock()?.schema_pulled {
                 return Ok(true);
             }
+            thread::sleep(CLIENT_POLL_INTERVAL);
         }
-
         Ok(false)
     }
 
-    fn get_client_keyspace(&self, client_id: i32) -> Result<Option<KeyspaceSchema>, NodeError> {
-        let keyspace_name = self
-            .clients_keyspace
-            .get(&client_id)
-            .ok_or(NodeError::InternodeProtocolError)
-            .cloned()?;
-
-        if let Some(value) = keyspace_name {
-            Ok(self.schema.keyspaces.get(&value).cloned())
-        } else {
-            Ok(None)
+    /// Adds to the partitioner the nodes that gossip reports as alive, and removes the ones that
+    /// are dead or leaving the cluster.
+    ///
+    /// Returns whether the partitioner changed, in which case data needs to be redistributed.
+    fn update_partitioner_from_gossiper(&mut self) -> Result<bool, NodeError> {
+        let endpoints_states = self.gossiper.endpoints_state.clone();
+        let mut changed = false;
+
+        for (ip, state) in &endpoints_states {
+            let is_in_partitioner = self.partitioner.node_already_in_partitioner(ip)?;
+            let status = state.application_state.status;
+
+            if status.is_dead() || status.is_leaving() {
+                if is_in_partitioner {
+                    changed = true;
+                    self.partitioner.remove_node(*ip).ok();
+                    let _ = self.logger.info(
+                        &format!(
+                            "NODE {:?} IS {:?} .. New Ring: {:?}",
+                            ip, status, self.partitioner
+                        ),
+                        Color::Red,
+                        true,
+                    );
+                }
+            } else if let (false, Some(dead)) =
+                (is_in_partitioner, state.application_state.replaces)
+            {
+                // El reemplazo toma los tokens del nodo muerto, aunque ya no este en el anillo
+                changed = true;
+                self.partitioner.replace_node(dead, *ip).ok();
+                let _ = self.logger.info(
+                    &format!(
+                        "NODE {:?} REPLACES {:?} .. New Ring: {:?}",
+                        ip, dead, self.partitioner
+                    ),
+                    Color::Green,
+                    true,
+                );
+            } else if !is_in_partitioner {
+                changed = true;
+                self.partitioner.add_node(*ip).ok();
+                let _ = self.logger.info(
+                    &format!("NEW NODE {:?} .. New Ring: {:?}", ip, self.partitioner),
+                    Color::Green,
+                    true,
+                );
+            }
         }
+
+        Ok(changed)
     }
 
-    fn get_keyspace(&self, keyspace_name: &str) -> Result<Option<KeyspaceSchema>, NodeError> {
-        Ok(self.schema.keyspaces.get(keyspace_name).cloned())
+    fn get_ip_string(&self) -> String {
+        self.ip.to_string()
     }
 
-    /// Starts the node's core functionalities, including internode connections, gossip, and client connections.
-    ///
-    /// # Purpose
-    /// This function is responsible for initializing and starting the main operational threads of a node
-    /// in a distributed database system. It ensures that the node can handle internode communication,
-    /// participate in the gossip protocol, and serve client requests simultaneously.
-    ///
-    /// # Parameters
-    /// - `node: Arc<Mutex<Node>>`
-    ///   - A thread-safe reference to the `Node` instance being started.
-    ///   - Contains the node's state, schema, partitioner, and other critical components.
-    /// - `connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>`
-    ///   - A thread-safe map of active TCP connections to other nodes and clients.
-    ///     - Keys are addresses (as strings), and values are `TcpStream` objects for communication.
-    ///
-    /// # Returns
-    /// - `Result<(), NodeError>`
-    ///   - On success:
-    ///     - Returns `Ok(())`, indicating that all threads started successfully and are running.
-    ///   - On failure:
-    ///     - Returns `Err(NodeError)` if any thread fails to start or encounters an unrecoverable error.
-    ///
-    /// # Behavior
-    /// 1. **Retrieve Node IP**:
-    ///    - Extracts the node's IP address by locking the `node` reference.
-    ///
-    /// 2. **Thread for Internode Connections**:
-    ///    - Creates a thread to handle connections between nodes in the cluster.
-    ///    - Uses the `handle_node_connections` function to manage internode communication and synchronize state.
-    ///
-    /// 3. **Thread for Gossip Protocol**:
-    ///    - Starts a background thread for the gossip protocol using `start_gossip`.
-    ///    - Gossip ensures cluster membership, state sharing, and failure detection.
-    ///
-    /// 4. **Thread for Client Connections**:
-    ///    - Creates a thread to handle incoming client connections and requests.
-    ///    - Uses the `handle_client_connections` function to manage client queries and responses.
-    ///
-    /// 5. **Thread Joining**:
-    ///    - Waits for the threads handling internode connections and client connections to complete using `join`.
-    ///    - Propagates errors if any thread encounters a failure or panic.
-    ///
-    /// # Error Handling
-    /// - Errors are logged for each thread individually using `unwrap_or_else` to ensure independent thread robustness.
-    /// - If any thread panics or fails during execution:
-    ///   - The error is captured and returned as a `NodeError`.
-    ///
-    /// # Notes
-    /// - **Thread-Safe Design**:
-    ///   - The function leverages `Arc<Mutex<T>>` to ensure safe concurrent access to shared resources (e.g., `node` and `connections`).
-    /// - **Critical Functionality**:
-    ///   - This function is a key entry point for activating a node in the cluster. Without it, the node cannot
-    ///     participate in cluster operations or serve clients.
-    ///
-    /// # Errors
-    /// - Returns `NodeError::InternodeError` if the internode connections thread fails.
-    /// - Returns `NodeError::ClientError` if the client connections thread fails.
-    /// - Errors during gossip are logged but do not cause the `start` function to fail.
-    ///
-    /// # Importance
-    /// This function encapsulates the primary operational lifecycle of a node in the system. By starting the gossip protocol,
-    /// managing internode connections, and handling client queries, it ensures the node's integration into the cluster
-    /// and its ability to serve requests efficiently.
+    fn get_how_many_nodes_i_know(&self) -> usize {
+        self.partitioner.get_nodes().len() - 1
+    }
 
-    pub fn start(
-        node: Arc<Mutex<Node>>,
-        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
-    ) -> Result<(), NodeError> {
-        let self_ip;
-        let log;
-        {
-            let node_guard = node.lock()?;
-            self_ip = node_guard.get_ip();
-            log = node_guard.get_logger().clone();
+    fn get_partitioner(&self) -> Partitioner {
+        self.partitioner.clone()
+    }
+
+    fn get_open_handle_query(&mut self) -> &mut OpenQueryHandler {
+        &mut self.open_query_handler
+    }
+
+    // Los ids crecen siempre; al dar la vuelta se saltean los de los clientes que siguen
+    // conectados, para que dos clientes nunca compartan el keyspace
+    fn generate_client_id(&mut self) -> i32 {
+        loop {
+            self.last_client_id = self.last_client_id.checked_add(1).unwrap_or(1);
+            if !self.clients_keyspace.contains_key(&self.last_client_id) {
+                break;
+            }
         }
+        self.clients_keyspace.insert(self.last_client_id, None);
+        self.last_client_id
+    }
 
-        let log_gossip = log.clone();
-        // Creates a thread to handle gossip
-        let gossip_connections = Arc::clone(&connections);
-        let node_gossip = Arc::clone(&node);
-        Self::start_gossip(node_gossip, gossip_connections).unwrap_or_else(|err| {
-            let message = format!("ERROR in GOSSIP: {:?}", err);
-            log_gossip.clone().error(&message, true).ok(); // Or handle the error as needed
-        });
+    /// Forgets a client whose connection closed, along with the keyspace it was using.
+    fn remove_client(&mut self, client_id: i32) {
+        self.clients_keyspace.remove(&client_id);
+    }
 
-        //thread::sleep(Duration::from_secs(2));
-        // Creates a thread to handle client connections
-        let client_connections_node = Arc::clone(&node);
-        let client_connections = Arc::clone(&connections);
-        let self_ip_client = self_ip;
+    fn update_schema_in_storage(&self, old_schema: Schema) -> Result<(), NodeError> {
+        let storage = self.storage_engine();
 
-        let log_client = log.clone();
-        let handle_client_thread = thread::spawn(move || {
-            Self::handle_client_connections(
-                client_connections_node,
-                client_connections,
-                self_ip_client,
-            )
-            .unwrap_or_else(|e| {
-                let message = format!("ERROR in CLIENT CONNECTIONS: {:?}", e);
-                log_client.clone().error(&message, true).ok();
+        // Process new or updated keyspaces
+        for (keyspace_name, keyspace) in self.schema.keyspaces.clone() {
+            let old_keyspace = old_schema.keyspaces.get(&keyspace_name);
+            if old_keyspace.is_none() {
+                // Create a new keyspace
+                storage.create_keyspace(&keyspace_name)?;
+            }
+
+            // La definicion se guarda junto a los datos para recuperarla al reiniciar
+            let replication_kept = old_keyspace.is_some_and(|old| {
+                old.get_replication_class() == keyspace.get_replication_class()
+                    && old.get_replication_factor() == keyspace.get_replication_factor()
             });
-        });
+            if !replication_kept {
+                storage.save_keyspace_schema(&keyspace.inner)?;
+            }
 
-        // Creates a thread to handle node connections
-        let node_connections_node = Arc::clone(&node);
-        let node_connections = Arc::clone(&connections);
-        let self_ip_node = self_ip.clone();
-        let log_internode = log.clone();
+            let old_tables = old_schema
+                .keyspaces
+                .get(&keyspace_name)
+                .map(|keyspace| keyspace.tables.clone())
+                .unwrap_or_else(Vec::new);
+
+            // Update existing keyspace
+            self.update_keyspace_tables(&storage, &keyspace_name, old_tables, keyspace.tables)?
+        }
+
+        // Process deleted keyspaces
+        for (keyspace_name, keyspace) in old_schema.clone().keyspaces {
+            if !self.schema.keyspaces.contains_key(&keyspace_name) {
+                // Drop keyspace
+                storage.drop_keyspace(&keyspace_name)?;
+            } else {
+                // Drop tables from existing keyspace
+
+                let new_tables = self
+                    .schema
+                    .keyspaces
+                    .get(&keyspace_name)
+                    .map(|keyspace| keyspace.tables.clone())
+                    .unwrap_or_else(Vec::new);
+
+                self.remove_obsolete_tables(&storage, &keyspace_name, keyspace.tables, new_tables)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Updates tables in an existing keyspace by creating new tables if they don't exist.
+    fn update_keyspace_tables(
+        &self,
+        storage: &StorageEngine,
+        keyspace_name: &str,
+        old_tables: Vec<TableSchema>,
+        new_tables: Vec<TableSchema>,
+    ) -> Result<(), NodeError> {
+        for table in new_tables {
+            let old_table = old_tables
+                .iter()
+                .find(|old_table| old_table.get_name() == table.get_name());
+            if old_table.is_none() {
+                // Create a new table
+                let cols = table.get_columns();
+                let col_names: Vec<&str> = cols.iter().map(|c| c.name.as_str()).collect();
+
+                storage.create_table(keyspace_name, &table.get_name(), col_names)?
+            }
+
+            // TableSchema solo compara nombres, asi que un ALTER se detecta por los bytes
+            let unchanged =
+                old_table.is_some_and(|old_table| old_table.to_bytes() == table.to_bytes());
+            if !unchanged {
+                storage.save_table_schema(keyspace_name, &table.inner)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Removes tables from an existing keyspace that are no longer present in the updated schema.
+    fn remove_obsolete_tables(
+        &self,
+        storage: &StorageEngine,
+        keyspace_name: &str,
+        old_tables: Vec<TableSchema>,
+        new_tables: Vec<TableSchema>,
+    ) -> Result<(), NodeError> {
+        for table in old_tables {
+            if new_tables
+                .iter()
+                .find(|new_table| new_table.get_name() == table.get_name())
+                .is_none()
+            {
+                // Drop table
+                storage.drop_table(keyspace_name, &table.get_name())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_latest_schema_from_gossiper(&mut self) -> Result<(), NodeError> {
+        let old_schema = self.schema.clone();
+
+        // acá se actualiza el schema del nodo
+        self.schema = match self.gossiper.endpoints_state.get(&self.ip) {
+            Some(endpoint_state) => endpoint_state.application_state.schema.clone(),
+            None => return Err(NodeError::LockError),
+        };
+
+        self.update_schema_in_storage(old_schema.clone())?;
+        // Un ALTER o DROP cambia las filas que devuelve una tabla
+        if self.schema != old_schema {
+            self.clear_cached_reads();
+        }
+        //println!("Schema updated: {:?}", self.schema);
+        Ok(())
+    }
+
+    fn add_keyspace(&mut self, new_keyspace: CreateKeyspace) -> Result<(), NodeError> {
+        self.gossiper
+            .add_keyspace(self.ip, new_keyspace)
+            .map_err(|_| NodeError::KeyspaceError)?;
+
+        // We manually update the latest schema right after modification so
+        // we don't have to wait for the next gossip round.
+        self.set_latest_schema_from_gossiper()?;
+
+        Ok(())
+    }
+
+    fn remove_keyspace(&mut self, keyspace_name: String) -> Result<(), NodeError> {
+        self.gossiper
+            .remove_keyspace(self.ip, &keyspace_name)
+            .map_err(|_| NodeError::KeyspaceError)?;
+
+        // Recorre los clients_keyspace para encontrar y actualizar keyspaces coincidentes
+        for (_, client_keyspace) in self.clients_keyspace.iter_mut() {
+            if let Some(ref keyspace) = client_keyspace {
+                if keyspace == &keyspace_name {
+                    *client_keyspace = None;
+                }
+            }
+        }
+
+        // We manually update the latest schema right after modification so
+        // we don't have to wait for the next gossip round.
+        self.set_latest_schema_from_gossiper()?;
+
+        Ok(())
+    }
+
+    fn set_actual_keyspace(
+        &mut self,
+        keyspace_name: String,
+        client_id: i32,
+    ) -> Result<(), NodeError> {
+        // Configurar el keyspace actual del cliente usando el índice encontrado
+        self.clients_keyspace.insert(client_id, Some(keyspace_name));
+
+        Ok(())
+    }
+
+    fn _update_keyspace(&mut self, client_id: i32, new_keyspace: KeyspaceSchema) {
+        let new_key_name = new_keyspace.clone().get_name().clone();
+        self.clients_keyspace
+            .insert(client_id, Some(new_key_name.clone()));
+
+        if let Some(keyspace) = self.schema.keyspaces.get_mut(&new_key_name) {
+            *keyspace = new_keyspace;
+        }
+    }
+
+    fn add_table(&mut self, new_table: CreateTable, keyspace_name: &str) -> Result<(), NodeError> {
+        self.gossiper
+            .add_table(self.ip, new_table, keyspace_name)
+            .map_err(|_| NodeError::GossipError)?;
+
+        // We manually update the latest schema right after modification so
+        // we don't have to wait for the next gossip round.
+        self.set_latest_schema_from_gossiper()?;
+
+        Ok(())
+    }
+
+    fn get_table(
+        &self,
+        table_name: String,
+        client_keyspace: KeyspaceSchema,
+    ) -> Result<TableSchema, NodeError> {
+        // Busca y devuelve la tabla solicitada
+        // TODO: acá buscar en schema, no en client_keyspace ???
+
+        client_keyspace
+            .get_table(&table_name)
+            .map_err(|_| NodeError::CQLError(CQLError::InvalidTable))
+    }
+
+    fn remove_table(&mut self, table_name: String, open_query_id: i32) -> Result<(), NodeError> {
+        // Obtiene el keyspace actual del cliente
+        let keyspace_name = self
+            .get_open_handle_query()
+            .get_keyspace_of_query(open_query_id)?
+            .ok_or(NodeError::KeyspaceError)?
+            .get_name();
+
+        self.gossiper
+            .remove_table(self.ip, &keyspace_name, &table_name)
+            .map_err(|_| NodeError::KeyspaceError)?;
+
+        // We manually update the latest schema right after modification so
+        // we don't have to wait for the next gossip round.
+        self.set_latest_schema_from_gossiper()?;
+
+        Ok(())
+    }
+
+    fn update_table(
+        &mut self,
+        keyspace_name: &str,
+        new_table: CreateTable,
+    ) -> Result<(), NodeError> {
+        self.gossiper
+            .update_table(self.ip, new_table, keyspace_name)
+            .map_err(|_| NodeError::CQLError(CQLError::InvalidTable))?;
+
+        // We manually update the latest schema right after modification so
+        // we don't have to wait for the next gossip round.
+        self.set_latest_schema_from_gossiper()?;
+
+        Ok(())
+    }
+
+    fn table_already_exist(
+        &mut self,
+        table_name: String,
+        keyspace_name: String,
+    ) -> Result<bool, NodeError> {
+        let keyspace = self
+            .get_keyspace(&keyspace_name)?
+            .ok_or(NodeError::KeyspaceError)?;
+
+        // Verifica si la tabla ya existe en el keyspace del cliente
+        for table in keyspace.get_tables() {
+            if table.get_name() == table_name {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn get_client_keyspace(&self, client_id: i32) -> Result<Option<KeyspaceSchema>, NodeError> {
+        let keyspace_name = self
+            .clients_keyspace
+            .get(&client_id)
+            .ok_or(NodeError::InternodeProtocolError)
+            .cloned()?;
+
+        if let Some(value) = keyspace_name {
+            Ok(self.schema.keyspaces.get(&value).cloned())
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn get_keyspace(&self, keyspace_name: &str) -> Result<Option<KeyspaceSchema>, NodeError> {
+        Ok(self.schema.keyspaces.get(keyspace_name).cloned())
+    }
+
+    /// Starts the node's core functionalities, including internode connections, gossip, and client connections.
+    ///
+    /// # Purpose
+    /// This function is responsible for initializing and starting the main operational threads of a node
+    /// in a distributed database system. It ensures that the node can handle internode communication,
+    /// participate in the gossip protocol, and serve client requests simultaneously.
+    ///
+    /// # Parameters
+    /// - `node: Arc<Mutex<Node>>`
+    ///   - A thread-safe reference to the `Node` instance being started.
+    ///   - Contains the node's state, schema, partitioner, and other critical components.
+    /// - `connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>`
+    ///   - A thread-safe map of active TCP connections to other nodes and clients.
+    ///     - Keys are addresses (as strings), and values are `TcpStream` objects for communication.
+    ///
+    /// # Returns
+    /// - `Result<(), NodeError>`
+    ///   - On success:
+    ///     - Returns `Ok(())`, indicating that all threads started successfully and are running.
+    ///   - On failure:
+    ///     - Returns `Err(NodeError)` if any thread fails to start or encounters an unrecoverable error.
+    ///
+    /// # Behavior
+    /// 1. **Retrieve Node IP**:
+    ///    - Extracts the node's IP address by locking the `node` reference.
+    ///
+    /// 2. **Thread for Internode Connections**:
+    ///    - Creates a thread to handle connections between nodes in the cluster.
+    ///    - Uses the `handle_node_connections` function to manage internode communication and synchronize state.
+    ///
+    /// 3. **Thread for Gossip Protocol**:
+    ///    - Starts a background thread for the gossip protocol using `start_gossip`.
+    ///    - Gossip ensures cluster membership, state sharing, and failure detection.
+    ///
+    /// 4. **Schema Pull**:
+    ///    - Asks the seeds for their schema with `pull_schema_from_seeds` and waits up to
+    ///      `SCHEMA_PULL_TIMEOUT` for it, so the node knows every table before serving clients.
+    ///
+    /// 5. **Thread for Client Connections**:
+    ///    - Creates a thread to handle incoming client connections and requests.
+    ///    - Uses the `handle_client_connections` function to manage client queries and responses.
+    ///
+    /// 6. **Thread Joining**:
+    ///    - Waits for the threads handling internode connections and client connections to complete using `join`.
+    ///    - Propagates errors if any thread encounters a failure or panic.
+    ///
+    /// # Error Handling
+    /// - Errors are logged for each thread individually using `unwrap_or_else` to ensure independent thread robustness.
+    /// - If any thread panics or fails during execution:
+    ///   - The error is captured and returned as a `NodeError`.
+    ///
+    /// # Notes
+    /// - **Thread-Safe Design**:
+    ///   - The function leverages `Arc<Mutex<T>>` to ensure safe concurrent access to shared resources (e.g., `node` and `connections`).
+    /// - **Critical Functionality**:
+    ///   - This function is a key entry point for activating a node in the cluster. Without it, the node cannot
+    ///     participate in cluster operations or serve clients.
+    ///
+    /// # Errors
+    /// - Returns `NodeError::InternodeError` if the internode connections thread fails.
+    /// - Returns `NodeError::ClientError` if the client connections thread fails.
+    /// - Errors during gossip are logged but do not cause the `start` function to fail.
+    ///
+    /// # Importance
+    /// This function encapsulates the primary operational lifecycle of a node in the system. By starting the gossip protocol,
+    /// managing internode connections, and handling client queries, it ensures the node's integration into the cluster
+    /// and its ability to serve requests efficiently.
+
+    pub fn start(
+        node: Arc<Mutex<Node>>,
+        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+    ) -> Result<(), NodeError> {
+        let self_ip;
+        let log;
+        {
+            let node_guard = node.lock()?;
+            self_ip = node_guard.get_ip();
+            log = node_guard.get_logger().clone();
+        }
+
+        let log_gossip = log.clone();
+        // Creates a thread to handle gossip
+        let gossip_connections = Arc::clone(&connections);
+        let node_gossip = Arc::clone(&node);
+        Self::start_gossip(node_gossip, gossip_connections).unwrap_or_else(|err| {
+            let message = format!("ERROR in GOSSIP: {:?}", err);
+            log_gossip.clone().error(&message, true).ok(); // Or handle the error as needed
+        });
+
+        // Creates a thread to handle node connections
+        let node_connections_node = Arc::clone(&node);
+        let node_connections = Arc::clone(&connections);
+        let self_ip_node = self_ip.clone();
+        let log_internode = log.clone();
         let handle_node_thread = thread::spawn(move || {
             Self::handle_node_connections(node_connections_node, node_connections, self_ip_node)
                 .unwrap_or_else(|err| {
@@ -948,433 +1841,3391 @@ impl Node {
                 });
         });
 
-        handle_node_thread
-            .join()
-            .map_err(|_| NodeError::InternodeError)?;
-        handle_client_thread
-            .join()
-            .map_err(|_| NodeError::ClientError)?;
+        // Un nodo que se une pide el schema antes de atender clientes
+        match Self::pull_schema_from_seeds(&node, Arc::clone(&connections), SCHEMA_PULL_TIMEOUT) {
+            Ok(true) => log.info("SCHEMA: pulled from the seeds", Color::Cyan, true)?,
+            Ok(false) => {}
+            Err(err) => log.error(&format!("ERROR in SCHEMA PULL: {:?}", err), true)?,
+        }
+
+        // Creates a thread to handle client connections
+        let client_connections_node = Arc::clone(&node);
+        let client_connections = Arc::clone(&connections);
+        let self_ip_client = self_ip;
+
+        let log_client = log.clone();
+        let handle_client_thread = thread::spawn(move || {
+            Self::handle_client_connections(
+                client_connections_node,
+                client_connections,
+                self_ip_client,
+            )
+            .unwrap_or_else(|e| {
+                let message = format!("ERROR in CLIENT CONNECTIONS: {:?}", e);
+                log_client.clone().error(&message, true).ok();
+            });
+        });
+
+        handle_node_thread
+            .join()
+            .map_err(|_| NodeError::InternodeError)?;
+        handle_client_thread
+            .join()
+            .map_err(|_| NodeError::ClientError)?;
+
+        Ok(())
+    }
+
+    fn handle_node_connections(
+        node: Arc<Mutex<Node>>,
+        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+        self_ip: std::net::Ipv4Addr,
+    ) -> Result<(), NodeError> {
+        let port = node.lock()?.internode_port;
+        let socket = SocketAddrV4::new(self_ip, port);
+        let listener = TcpListener::bind(socket)?;
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let node_clone = Arc::clone(&node);
+                    let stream = Arc::new(Mutex::new(stream)); // Encapsulates the stream in Arc<Mutex<TcpStream>>
+                    let connections_clone = Arc::clone(&connections);
+
+                    thread::spawn(move || {
+                        if let Err(e) = Node::handle_incoming_internode_messages(
+                            node_clone,
+                            stream,
+                            connections_clone,
+                        ) {
+                            eprintln!("{:?}", e);
+                        }
+                    });
+                }
+                Err(e) => {
+                    eprintln!("Error accepting internode connection: {:?}", e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_client_connections(
+        node: Arc<Mutex<Node>>,
+        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+        self_ip: std::net::Ipv4Addr,
+    ) -> Result<(), NodeError> {
+        // Cargar configuración TLS
+        let project_dir = env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string());
+        let path_certs = Path::new(&project_dir)
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join("certs");
+
+        // Cargar configuración TLS
+        let certs = CertificateDer::pem_file_iter(path_certs.join("cert.crt"))
+            .unwrap()
+            .map(|cert| cert.unwrap())
+            .collect();
+        let private_key = PrivateKeyDer::from_pem_file(path_certs.join("cert.key")).unwrap();
+
+        match rustls::crypto::aws_lc_rs::default_provider().install_default() {
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("Failed to install CryptoProvider: {:?}", err);
+            }
+        }
+
+        let config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(certs, private_key)
+            .unwrap();
+
+        let port = node.lock()?.client_port;
+        let socket = SocketAddrV4::new(self_ip, port); // Specific port for clients
+        let listener = TcpListener::bind(socket)?;
+
+        for stream in listener.incoming() {
+            match stream {
+                Ok(mut stream) => {
+                    // Crear una conexión TLS para el stream TCP
+                    let mut conn = ServerConnection::new(Arc::new(config.clone()))
+                        .expect("No se pudo crear la conexión TLS");
+
+                    conn.complete_io(&mut stream).unwrap();
+                    let connections_clone = Arc::clone(&connections);
+
+                    let stream = StreamOwned::new(conn, stream);
+
+                    let node_clone = Arc::clone(&node);
+                    thread::spawn(move || {
+                        Node::handle_incoming_client_messages(node_clone, stream, connections_clone)
+                    });
+                }
+                Err(e) => {
+                    eprintln!("Error accepting client connection: {:?}", e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Receives packets from the client
+    fn handle_incoming_client_messages(
+        node: Arc<Mutex<Node>>,
+        mut stream: StreamOwned<ServerConnection, TcpStream>,
+        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+    ) -> Result<(), NodeError> {
+        // La lectura no puede bloquear la escritura de las respuestas que ya terminaron
+        stream.sock.set_read_timeout(Some(CLIENT_POLL_INTERVAL))?;
+        Self::serve_client(node, &mut stream, connections)
+    }
+
+    /// Serves the requests a client sends through `stream` until it closes the connection.
+    ///
+    /// The queries run on a pool of worker threads of the connection, so a client can have
+    /// several queries in flight on the same connection. Their answers are written as they
+    /// finish, each one with the stream id of its request, so the client can match them even if
+    /// they arrive out of order. Once every worker is busy and as many queries are waiting, the
+    /// node stops reading from the connection until one finishes.
+    ///
+    /// Reads from `stream` must time out: the answers that are ready are written between reads.
+    /// Once the connection closes, for whatever reason, the node waits for the queries it
+    /// already read and forgets the client.
+    fn serve_client<S: Read + Write>(
+        node: Arc<Mutex<Node>>,
+        stream: &mut S,
+        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+    ) -> Result<(), NodeError> {
+        let (client_id, log, workers) = {
+            let mut guard_node = node.lock()?;
+            (
+                guard_node.generate_client_id(),
+                guard_node.get_logger(),
+                guard_node.client_workers,
+            )
+        };
+
+        let queries = Arc::new(BoundedQueue::new(workers, Arc::default()));
+        let (tx_answer, rx_answer) = mpsc::channel::<(i16, Frame)>();
+        let workers: Vec<_> = (0..workers)
+            .map(|_| {
+                let queries = Arc::clone(&queries);
+                let node = Arc::clone(&node);
+                let connections = Arc::clone(&connections);
+                let tx_answer = tx_answer.clone();
+                thread::spawn(move || {
+                    while let Ok(Some(ClientQuery {
+                        stream_id,
+                        query,
+                        consistency_level,
+                    })) = queries.pop()
+                    {
+                        let answer = Self::answer_client_query(
+                            &query,
+                            &consistency_level,
+                            &node,
+                            Arc::clone(&connections),
+                            client_id,
+                        );
+                        // Si el cliente ya cerro la conexion nadie espera la respuesta
+                        tx_answer.send((stream_id, answer)).ok();
+                    }
+                })
+            })
+            .collect();
+
+        let mut served = Self::serve_client_requests(&node, stream, &queries, &rx_answer, &log);
+        // Los workers terminan las consultas que quedan en la cola y se detienen
+        queries.close()?;
+        for worker in workers {
+            if worker.join().is_err() {
+                served = Err(NodeError::ThreadError);
+            }
+        }
+        node.lock()?.remove_client(client_id);
+        served
+    }
+
+    // Atiende los pedidos de un cliente hasta que cierra la conexion. Las consultas
+    // se pasan a los workers por `queries` y sus respuestas llegan por `rx_answer`.
+    fn serve_client_requests<S: Read + Write>(
+        node: &Arc<Mutex<Node>>,
+        stream: &mut S,
+        queries: &BoundedQueue<ClientQuery>,
+        rx_answer: &Receiver<(i16, Frame)>,
+        log: &Logger,
+    ) -> Result<(), NodeError> {
+        let password = node.lock()?.password.clone();
+        // Sin password configurada los clientes no se autentican
+        let mut is_authenticated = password.is_none();
+        // Bytes leidos que todavia no forman un frame completo
+        let mut pending: Vec<u8> = Vec::new();
+
+        loop {
+            let mut buffer = [0; 2048];
+
+            match stream.read(&mut buffer) {
+                Ok(0) => {
+                    // Connection closed
+                    break;
+                }
+                Ok(bytes_read) => pending.extend_from_slice(&buffer[..bytes_read]),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(_) => {
+                    // Another type of error
+                    return Err(NodeError::OtherError);
+                }
+            }
+
+            // Una lectura puede traer varios frames, o solo una parte de uno
+            while let Some(length) = Frame::length_of(&pending) {
+                if length > MAX_CLIENT_FRAME_LENGTH {
+                    let error = Frame::Error(error::Error::ProtocolError(format!(
+                        "Frame of {} bytes is longer than the maximum of {}",
+                        length, MAX_CLIENT_FRAME_LENGTH
+                    )));
+                    Self::write_client_frame(stream, &error, Frame::stream_of(&pending)?)?;
+                    log.warn("NATIVE: closing a connection that sent garbage", true)?;
+                    return Ok(());
+                }
+                if length > pending.len() {
+                    break;
+                }
+
+                let bytes: Vec<u8> = pending.drain(..length).collect();
+                let stream_id = Frame::stream_of(&bytes)?;
+                // Un frame invalido se contesta con un error y la conexion sigue
+                let request = match handle_client_request(&bytes) {
+                    Ok(request) => request,
+                    Err(err) => {
+                        log.warn(&format!("NATIVE: invalid frame from CLIENT: {}", err), true)?;
+                        let error = Frame::Error(error::Error::ProtocolError(err.to_string()));
+                        Self::write_client_frame(stream, &error, stream_id)?;
+                        continue;
+                    }
+                };
+
+                match request {
+                    Request::Startup => {
+                        let response = if is_authenticated {
+                            Frame::Ready
+                        } else {
+                            Frame::Authenticate(Authenticate::default())
+                        };
+                        Self::write_client_frame(stream, &response, stream_id)?;
+                    }
+                    Request::AuthResponse(given) => {
+                        let response =
+                            if password.as_ref().is_none_or(|password| *password == given) {
+                                is_authenticated = true;
+                                Frame::AuthSuccess(AuthSuccess::default())
+                            } else {
+                                Frame::Authenticate(Authenticate::default())
+                            };
+                        Self::write_client_frame(stream, &response, stream_id)?;
+                    }
+                    Request::Query(query) => {
+                        if !is_authenticated {
+                            let auth = Frame::Authenticate(Authenticate::default());
+                            Self::write_client_frame(stream, &auth, stream_id)?;
+                            continue;
+                        }
+                        // Handle the query
+                        let query = ClientQuery {
+                            stream_id,
+                            query: query.get_query().to_string(),
+                            consistency_level: query.get_consistency().to_string(),
+                        };
+                        log.info(
+                            &format!(
+                                "NATIVE: I RECEIVED {} whit CL: {} from CLIENT",
+                                query.query.replace("\n", ""),
+                                query.consistency_level,
+                            ),
+                            Color::Yellow,
+                            true,
+                        )?;
+
+                        // Con todos los workers ocupados y la cola llena se deja de leer
+                        queries.push(query, OverflowPolicy::Block, |_| false)?;
+                    }
+                };
+            }
+
+            // Se escriben las respuestas de las consultas que ya terminaron
+            while let Ok((stream_id, answer)) = rx_answer.try_recv() {
+                Self::write_client_frame(stream, &answer, stream_id)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Executes a query of a client and returns the frame that answers it
+    //
+    // Una escritura con `USING IDEMPOTENCY KEY` que ya se aplico en la ventana se contesta con
+    // la respuesta de entonces, sin aplicarla de nuevo. Si otro intento la esta aplicando, se
+    // espera su respuesta hasta `IN_FLIGHT_WAIT_TIMEOUT`. Solo se reconocen los intentos que
+    // llegaron a este coordinador.
+    fn answer_client_query(
+        query_str: &str,
+        consistency_level: &str,
+        node: &Arc<Mutex<Node>>,
+        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+        client_id: i32,
+    ) -> Frame {
+        let (query_str, idempotency_key) = split_idempotency_key(query_str);
+        let idempotency_key = idempotency_key.and_then(|key| {
+            let query = QueryCreator::new()
+                .handle_query(query_str.to_string())
+                .ok()?;
+            node.lock().ok()?.idempotency_key_of(&query, client_id, key)
+        });
+        let reservation = match idempotency_key {
+            Some(key) => match Self::reserve_idempotent_write(node, key, IN_FLIGHT_WAIT_TIMEOUT) {
+                Ok(reservation) => Some(reservation),
+                Err(answer) => return answer,
+            },
+            None => None,
+        };
+
+        let (tx_reply, rx_reply) = mpsc::channel();
+
+        let result = Node::handle_query_execution(
+            query_str,
+            consistency_level,
+            node,
+            connections,
+            tx_reply,
+            client_id,
+        )
+        // await resolution of the query
+        .and_then(|_| Self::await_reply(&rx_reply, query_str, node));
+
+        let reply = match result {
+            Ok(reply) => reply,
+            Err(e) => Frame::Error(error::Error::from(&e)),
+        };
+        if let Some(reservation) = reservation {
+            reservation.settle(&reply);
+        }
+        reply
+    }
+
+    // Reserva la escritura `key` para este intento. Si ya se aplico devuelve la respuesta con
+    // la que hay que contestar, esperando hasta `timeout` a que termine el intento que la este
+    // aplicando; si no termina, se contesta con un timeout de escritura.
+    fn reserve_idempotent_write(
+        node: &Arc<Mutex<Node>>,
+        key: IdempotencyKey,
+        timeout: Duration,
+    ) -> Result<ReservedWrite, Frame> {
+        let lock_error = || Frame::Error(error::Error::from(&NodeError::LockError));
+        let deadline = Instant::now() + timeout;
+        let mut guard = node.lock().map_err(|_| lock_error())?;
+        loop {
+            match guard.idempotent_writes.reserve(&key) {
+                Reservation::Reserved => return Ok(ReservedWrite::new(Arc::clone(node), key)),
+                Reservation::Applied(answer) => return Err(answer),
+                Reservation::InFlight => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return Err(Frame::Error(error::Error::WriteTimeout(
+                            "Timed out waiting for another attempt of the write".to_string(),
+                            error::WriteTimeout,
+                        )));
+                    }
+                    let settled = guard.idempotent_writes.settled();
+                    guard = settled
+                        .wait_timeout(guard, remaining)
+                        .map_err(|_| lock_error())?
+                        .0;
+                }
+            }
+        }
+    }
+
+    // Identifica la escritura `query` con la clave de idempotencia que le dio el cliente, en
+    // su tabla y particion. `None` si no es una escritura o no se sabe su particion.
+    fn idempotency_key_of(
+        &self,
+        query: &Query,
+        client_id: i32,
+        key: String,
+    ) -> Option<IdempotencyKey> {
+        let keyspace = match query.get_used_keyspace() {
+            Some(keyspace_name) => self.get_keyspace(&keyspace_name).ok()??,
+            None => self.get_client_keyspace(client_id).ok()??,
+        };
+        let table = self
+            .get_table(query.get_table_name()?, keyspace.clone())
+            .ok()?;
+        let partition_keys = table.get_partition_keys().ok()?;
+
+        let partition = match query {
+            Query::Insert(insert) => partition_keys
+                .iter()
+                .map(|name| {
+                    let position = insert.into_clause.columns.iter().position(|c| c == name)?;
+                    insert.values.get(position).cloned()
+                })
+                .collect::<Option<Vec<String>>>()?,
+            Query::Update(update) => update
+                .where_clause
+                .as_ref()?
+                .get_value_partitioner_key_condition(partition_keys)
+                .ok()?,
+            Query::Delete(delete) => delete
+                .where_clause
+                .as_ref()?
+                .get_value_partitioner_key_condition(partition_keys)
+                .ok()?,
+            _ => return None,
+        };
+
+        Some(IdempotencyKey {
+            table: format!("{}.{}", keyspace.get_name(), table.get_name()),
+            partition,
+            key,
+        })
+    }
+
+    fn write_client_frame<S: Write>(
+        stream: &mut S,
+        frame: &Frame,
+        stream_id: i16,
+    ) -> Result<(), NodeError> {
+        stream.write_all(&frame.to_bytes_with_stream(stream_id)?)?;
+        stream.flush()?;
+        Ok(())
+    }
+
+    /// Reads messages sent by another node and hands them to a worker thread through a bounded queue.
+    ///
+    /// The reader never waits for a message to be processed, so a slow handler only fills the queue.
+    /// When the queue is full, the oldest pending gossip `SYN` is dropped (a newer one carries
+    /// fresher state anyway); any other message blocks the reader until the worker frees a slot.
+    fn handle_incoming_internode_messages(
+        node: Arc<Mutex<Node>>,
+        stream: Arc<Mutex<TcpStream>>,
+        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+    ) -> Result<(), NodeError> {
+        // Clone the stream under Mutex protection and create the reader
+        let mut reader = {
+            let stream_guard = stream.lock()?;
+            BufReader::new(stream_guard.try_clone().map_err(NodeError::IoError)?)
+        };
+
+        let (capacity, depth, metrics) = {
+            let node_guard = node.lock()?;
+            (
+                node_guard.internode_queue_capacity,
+                Arc::clone(&node_guard.internode_queue_depth),
+                Arc::clone(&node_guard.metrics),
+            )
+        };
+        let queue: Arc<BoundedQueue<InternodeMessage>> =
+            Arc::new(BoundedQueue::new(capacity, depth));
+
+        let worker_queue = Arc::clone(&queue);
+        let worker_node = Arc::clone(&node);
+        let worker = thread::spawn(move || {
+            let internode_protocol_handler = InternodeProtocolHandler::new();
+            while let Ok(Some(message)) = worker_queue.pop() {
+                let result = internode_protocol_handler.handle_command(
+                    &worker_node,
+                    message.clone(),
+                    connections.clone(),
+                );
+
+                // If there's an error handling the command, stop consuming
+                if let Err(e) = result {
+                    eprintln!("{:?} when other node sent me {:?}", e, message);
+                    break;
+                }
+            }
+            worker_queue.close().ok();
+        });
+
+        let mut result = Ok(());
+        loop {
+            // Clean the buffer
+            let mut buffer = [0u8; 850000];
+
+            // Try to read a line
+            match reader.read(&mut buffer) {
+                Ok(0) => {
+                    // Connection closed
+                    break;
+                }
+                Ok(_) => {
+                    let message = match InternodeMessage::from_bytes(&buffer) {
+                        Ok(value) => value,
+                        Err(InternodeMessageError::ChecksumMismatch) => {
+                            metrics.record_internode_checksum_failure();
+                            eprintln!("Discarded corrupted internode message");
+                            continue;
+                        }
+                        Err(e @ InternodeMessageError::UnsupportedVersion(_)) => {
+                            eprintln!("Rejected internode message: {}", e);
+                            continue;
+                        }
+                        Err(_) => continue,
+                    };
+                    metrics.record_internode_message();
+
+                    let pushed = queue.push(message, OverflowPolicy::DropOldest, |queued| {
+                        matches!(
+                            &queued.content,
+                            InternodeMessageContent::Gossip(gossip) if matches!(gossip.payload, Payload::Syn(_))
+                        )
+                    });
+
+                    match pushed {
+                        Ok(Some(dropped)) => {
+                            eprintln!(
+                                "Internode queue full, dropped gossip from {:?}",
+                                dropped.from
+                            );
+                        }
+                        Ok(None) => {}
+                        // The worker stopped, there is nobody left to process messages
+                        Err(_) => break,
+                    }
+                }
+                Err(_) => {
+                    // Another type of error
+                    result = Err(NodeError::OtherError);
+                    break;
+                }
+            }
+        }
+
+        queue.close()?;
+        worker.join().map_err(|_| NodeError::ThreadError)?;
+        result
+    }
+
+    fn current_timestamp() -> i64 {
+        Utc::now().timestamp()
+    }
+
+    fn handle_query_execution(
+        query_str: &str,
+        consistency_level: &str,
+        node: &Arc<Mutex<Node>>,
+        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+        tx_reply: Sender<Frame>,
+        client_id: i32,
+    ) -> Result<(), NodeError> {
+        let metrics = Arc::clone(&node.lock()?.metrics);
+        metrics.record_query_received();
+
+        let result = Self::execute_client_query(
+            query_str,
+            consistency_level,
+            node,
+            connections,
+            tx_reply,
+            client_id,
+            &metrics,
+        );
+        if result.is_err() {
+            metrics.record_query_failed();
+        }
+        result
+    }
+
+    // Espera la respuesta de la consulta hasta el timeout del nodo. Si no llega, es un timeout de
+    // lectura para un `SELECT` y de escritura para el resto
+    fn await_reply(
+        rx_reply: &Receiver<Frame>,
+        query_str: &str,
+        node: &Arc<Mutex<Node>>,
+    ) -> Result<Frame, NodeError> {
+        let timeout = node.lock()?.request_timeout;
+        match rx_reply.recv_timeout(timeout) {
+            Ok(reply) => Ok(reply),
+            Err(RecvTimeoutError::Timeout) => {
+                let reason = format!("the replicas did not answer in {:?}", timeout);
+                match QueryCreator::new().handle_query(query_str.to_string()) {
+                    Ok(Query::Select(_)) => Err(NodeError::ReadTimeout(reason)),
+                    _ => Err(NodeError::WriteTimeout(reason)),
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(NodeError::OtherError),
+        }
+    }
+
+    fn execute_client_query(
+        query_str: &str,
+        consistency_level: &str,
+        node: &Arc<Mutex<Node>>,
+        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+        tx_reply: Sender<Frame>,
+        client_id: i32,
+        metrics: &Metrics,
+    ) -> Result<(), NodeError> {
+        // El chequeo de salud lo responde este nodo solo, aunque todavia no pueda atender consultas
+        if health::is_health_check(query_str) {
+            let frame = node.lock()?.health().to_frame();
+            return tx_reply.send(frame).map_err(|_| NodeError::OtherError);
+        }
+
+        let query = QueryCreator::new()
+            .handle_query(query_str.to_string())
+            .map_err(NodeError::CQLError)?;
+
+        Self::execute_query(
+            query,
+            consistency_level,
+            node,
+            connections,
+            tx_reply,
+            client_id,
+            metrics,
+        )
+    }
+
+    /// Runs the statements of a batch one after the other, each one as if the client had sent
+    /// it alone, and answers the client once all of them finished.
+    ///
+    /// The first statement that fails stops the batch and its error is the answer; the
+    /// statements before it stay applied.
+    fn execute_batch(
+        batch: Batch,
+        consistency_level: &str,
+        node: &Arc<Mutex<Node>>,
+        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+        tx_reply: Sender<Frame>,
+        client_id: i32,
+        metrics: &Metrics,
+    ) -> Result<(), NodeError> {
+        for statement in batch.statements.iter().cloned() {
+            let (tx_statement, rx_statement) = mpsc::channel();
+            Self::execute_query(
+                statement,
+                consistency_level,
+                node,
+                connections.clone(),
+                tx_statement,
+                client_id,
+                metrics,
+            )?;
+
+            let reply = rx_statement.recv().map_err(|_| NodeError::OtherError)?;
+            if let Frame::Error(_) = reply {
+                tx_reply.send(reply).map_err(|_| NodeError::OtherError)?;
+                return Ok(());
+            }
+        }
+
+        let frame = Query::Batch(batch).create_client_response(vec![], String::new(), vec![])?;
+        tx_reply.send(frame).map_err(|_| NodeError::OtherError)
+    }
+
+    fn execute_query(
+        query: Query,
+        consistency_level: &str,
+        node: &Arc<Mutex<Node>>,
+        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+        tx_reply: Sender<Frame>,
+        client_id: i32,
+        metrics: &Metrics,
+    ) -> Result<(), NodeError> {
+        let query = match query {
+            Query::Batch(batch) => {
+                return Self::execute_batch(
+                    batch,
+                    consistency_level,
+                    node,
+                    connections,
+                    tx_reply,
+                    client_id,
+                    metrics,
+                )
+            }
+            query => query,
+        };
+
+        if query.needs_keyspace() {
+            //println!("esta query: {:?} necesita un keyspace", query_str);
+            check_keyspace(node, &query, client_id, 6)?;
+        }
+
+        if query.needs_table() {
+            //println!("esta query: {:?} necesita una tabla", query_str);
+            check_table(node, &query, client_id, 6)?;
+        }
+
+        // EXPLAIN is answered by this node alone, without opening a query
+        if let Query::Explain(explain) = &query {
+            let keyspace = check_keyspace(node, &query, client_id, 1)?
+                .ok_or(NodeError::CQLError(CQLError::NoActualKeyspaceError))?;
+            let storage_path = node.lock()?.storage_path.clone();
+
+            let plan = QueryExecution::new(node.clone(), connections, storage_path)?
+                .explain(explain, &keyspace)?;
+            let frame = query.create_client_response(vec![], keyspace.get_name(), plan)?;
+            tx_reply.send(frame).map_err(|_| NodeError::OtherError)?;
+            return Ok(());
+        }
+
+        // USE only changes the keyspace of this client in this node
+        if let Query::Use(use_cql) = &query {
+            let keyspace_name = use_cql.get_name();
+            {
+                let mut guard_node = node.lock()?;
+                if guard_node.get_keyspace(&keyspace_name)?.is_none() {
+                    return Err(NodeError::CQLError(CQLError::KeyspaceDoesNotExist));
+                }
+                guard_node.set_actual_keyspace(keyspace_name.clone(), client_id)?;
+            }
+
+            let frame = query.create_client_response(vec![], keyspace_name, vec![])?;
+            tx_reply.send(frame).map_err(|_| NodeError::OtherError)?;
+            return Ok(());
+        }
+
+        let open_query_id;
+        let self_ip: Ipv4Addr;
+        let storage_path;
+        let logger;
+        {
+            let mut guard_node = node.lock()?;
+            let keyspace;
+            // Obtener el keyspace especificado o el actual del cliente
+            if let Some(keyspace_name) = query.get_used_keyspace() {
+                keyspace = guard_node.get_keyspace(&keyspace_name)?
+            } else {
+                keyspace = guard_node.get_client_keyspace(client_id)?;
+            }
+
+            // Intentar obtener el nombre de la tabla y buscar la tabla correspondiente en el keyspace
+            let table = query.get_table_name().and_then(|table_name| {
+                keyspace
+                    .clone()
+                    .and_then(|k| guard_node.get_table(table_name, k).ok())
+            });
+
+            if let Some(keyspace) = &keyspace {
+                match query {
+                    Query::Select(_) => metrics.record_read(&keyspace.get_name())?,
+                    Query::Insert(_) | Query::Update(_) | Query::Delete(_) => {
+                        metrics.record_write(&keyspace.get_name())?
+                    }
+                    _ => {}
+                }
+            }
+
+            // Agregar la consulta abierta
+            open_query_id = guard_node.add_open_query(
+                query.clone(),
+                consistency_level,
+                tx_reply,
+                table,
+                keyspace,
+            )?;
+            self_ip = guard_node.get_ip();
+            storage_path = guard_node.storage_path.clone();
+            logger = guard_node.get_logger();
+        }
+        let timestamp = Self::current_timestamp();
+
+        let response =
+            QueryExecution::new(node.clone(), connections.clone(), storage_path.clone())?.execute(
+                query.clone(),
+                false,
+                false,
+                open_query_id,
+                client_id,
+                Some(timestamp),
+            )?;
+
+        if let Some(((finished_responses, failed_nodes), content)) = response {
+            let mut guard_node = node.lock()?;
+            // Obtener el keyspace especificado o el actual del cliente
+
+            let keyspace = guard_node
+                .get_open_handle_query()
+                .get_keyspace_of_query(open_query_id)?
+                .clone();
+
+            // Intentar obtener el nombre de la tabla y buscar la tabla correspondiente en el keyspace
+            let table = query.get_table_name().and_then(|table_name| {
+                keyspace
+                    .clone()
+                    .and_then(|k| guard_node.get_table(table_name, k).ok())
+            });
+            let columns: Vec<Column> = {
+                if let Some(table) = table.clone() {
+                    table.get_columns()
+                } else {
+                    vec![]
+                }
+            };
+
+            let context = CoordinatorContext {
+                self_ip,
+                internode_port: guard_node.internode_port,
+                keyspace_name: keyspace
+                    .as_ref()
+                    .map_or_else(String::new, |keyspace| keyspace.get_name()),
+                connections: connections.clone(),
+                partitioner: guard_node.get_partitioner(),
+                replica_digests: guard_node.replica_digests(keyspace.as_ref()),
+                storage: guard_node.storage_engine(),
+                logger: logger.clone(),
+            };
+            let query_handler = guard_node.get_open_handle_query();
+            let mut repaired_self = false;
+
+            for _ in 0..finished_responses {
+                let mut select_columns: Vec<String> = vec![];
+                let mut values: Vec<Vec<String>> = vec![];
+                let mut complete_columns: Vec<String> = vec![];
+                if let Some(cont) = content.content.clone() {
+                    complete_columns = cont.columns.clone();
+                    select_columns = cont.select_columns.clone();
+                    values = cont.values.clone();
+                }
+
+                repaired_self |= InternodeProtocolHandler::add_ok_response_to_open_query_and_send_response_if_closed(
+                    query_handler,
+                    // TODO: convertir el content al content de la response
+                    &InternodeResponse::new(open_query_id as u32, InternodeResponseStatus::Ok, Some(InternodeResponseContent{
+                        columns: complete_columns,
+                        select_columns:  select_columns,
+                        values: values,
+                    })),
+                    open_query_id,
+                    table.clone(),
+                    columns.clone(),
+                    self_ip,
+                    &context,
+                )?;
+            }
+            for _ in 0..failed_nodes {
+                InternodeProtocolHandler::add_error_response_to_open_query_and_send_response_if_closed(
+                    query_handler,
+                    open_query_id,
+                    None,
+
+                )?;
+            }
+            if repaired_self {
+                guard_node.clear_cached_reads();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use native_protocol::messages::error::ErrorCode;
+    use native_protocol::messages::result::result_;
+    use native_protocol::messages::result::rows::{ColumnType, ColumnValue};
+    use query_creator::clauses::types::datatype::DataType;
+    use uuid::Uuid;
+
+    fn run_query(node: &Arc<Mutex<Node>>, query: &str) -> Result<Frame, NodeError> {
+        let client_id = node.lock()?.generate_client_id();
+        run_query_as(node, client_id, query)
+    }
+
+    fn run_query_as(
+        node: &Arc<Mutex<Node>>,
+        client_id: i32,
+        query: &str,
+    ) -> Result<Frame, NodeError> {
+        let connections = Arc::new(Mutex::new(HashMap::new()));
+        let (tx_reply, rx_reply) = mpsc::channel();
+        Node::handle_query_execution(query, "ONE", node, connections, tx_reply, client_id)?;
+        rx_reply.recv().map_err(|_| NodeError::OtherError)
+    }
+
+    const FLIGHTS: &str = "CREATE TABLE sky.flights (id INT, origin TEXT, PRIMARY KEY (id))";
+
+    // Carpeta nueva de /tmp para los datos de un test, con un nombre que empieza con `label`
+    fn temp_path(label: &str) -> PathBuf {
+        PathBuf::from(format!("/tmp/{}_{}", label, Uuid::new_v4()))
+    }
+
+    // Configuracion de un nodo solo, en 127.0.0.1, con sus datos en `storage_path`
+    fn local_config(storage_path: &Path) -> NodeConfig {
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        NodeConfig::new(ip, vec![ip], storage_path.to_path_buf())
+    }
+
+    // Nodo con la configuracion `config`, el keyspace `sky` (replication_factor 1) y la tabla
+    // que crea `create_table`
+    fn node_with_table(config: NodeConfig, create_table: &str) -> Arc<Mutex<Node>> {
+        let node = Arc::new(Mutex::new(Node::from_config(config).unwrap()));
+        run_query(
+            &node,
+            "CREATE KEYSPACE sky WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 1}",
+        )
+        .unwrap();
+        run_query(&node, create_table).unwrap();
+        node
+    }
+
+    #[test]
+    fn test_executing_queries_updates_metrics() {
+        let storage_path = temp_path("node_metrics_test");
+        let node = node_with_table(local_config(&storage_path), FLIGHTS);
+
+        run_query(
+            &node,
+            "INSERT INTO sky.flights (id, origin) VALUES (1, 'EZE')",
+        )
+        .unwrap();
+        run_query(&node, "SELECT id, origin FROM sky.flights WHERE id = 1").unwrap();
+        assert!(run_query(&node, "NOT A QUERY").is_err());
+
+        let snapshot = node.lock().unwrap().metrics_snapshot().unwrap();
+        assert_eq!(snapshot.queries_received, 5);
+        assert_eq!(snapshot.queries_failed, 1);
+        assert_eq!(snapshot.keyspaces["sky"].reads, 1);
+        assert_eq!(snapshot.keyspaces["sky"].writes, 1);
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    fn plan_lines(frame: Frame) -> Vec<String> {
+        match frame {
+            Frame::Result(result_::Result::Rows(rows)) => rows
+                .rows_content
+                .iter()
+                .filter_map(|row| match row.get("plan") {
+                    Some(ColumnValue::Ascii(line)) => Some(line.clone()),
+                    _ => None,
+                })
+                .collect(),
+            other => panic!("expected rows, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_explain_reports_point_read_and_full_scan() {
+        let storage_path = temp_path("node_explain_test");
+        let node = node_with_table(local_config(&storage_path), FLIGHTS);
+
+        let keyed = plan_lines(
+            run_query(
+                &node,
+                "EXPLAIN SELECT id, origin FROM sky.flights WHERE id = 1",
+            )
+            .unwrap(),
+        );
+        assert!(keyed.contains(&"table: sky.flights".to_string()));
+        assert!(keyed.contains(&"access: point read".to_string()));
+        assert!(keyed.contains(&"partition key: id = 1".to_string()));
+        assert!(keyed.contains(&"replicas: 127.0.0.1".to_string()));
+
+        let non_keyed = plan_lines(
+            run_query(
+                &node,
+                "EXPLAIN SELECT id, origin FROM sky.flights WHERE origin = 'EZE'",
+            )
+            .unwrap(),
+        );
+        assert!(non_keyed.contains(&"access: full scan".to_string()));
+        assert!(non_keyed.contains(&"partition key: not restricted".to_string()));
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_concurrent_add_table_keeps_both_tables() {
+        let storage_path = temp_path("node_add_table_test");
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        let node = Arc::new(Mutex::new(
+            Node::new(ip, vec![ip], storage_path.clone()).unwrap(),
+        ));
+
+        run_query(
+            &node,
+            "CREATE KEYSPACE sky WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 1}",
+        )
+        .unwrap();
+
+        let handles: Vec<_> = ["flights", "airports"]
+            .iter()
+            .map(|table_name| {
+                let node = Arc::clone(&node);
+                let query = format!("CREATE TABLE {} (id INT, PRIMARY KEY (id))", table_name);
+                thread::spawn(move || {
+                    let table = CreateTable::deserialize(&query).unwrap();
+                    node.lock().unwrap().add_table(table, "sky").unwrap();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let keyspace = node.lock().unwrap().get_keyspace("sky").unwrap().unwrap();
+        assert!(keyspace.get_table("flights").is_ok());
+        assert!(keyspace.get_table("airports").is_ok());
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_alter_table_updates_the_table_in_place() {
+        let storage_path = temp_path("node_alter_table_test");
+        let node = node_with_table(
+            local_config(&storage_path),
+            "CREATE TABLE sky.flights (id INT, PRIMARY KEY (id))",
+        );
+
+        run_query(
+            &node,
+            "CREATE TABLE sky.airports (id INT, PRIMARY KEY (id))",
+        )
+        .unwrap();
+        run_query(&node, "ALTER TABLE sky.flights ADD origin TEXT").unwrap();
+
+        let keyspace = node.lock().unwrap().get_keyspace("sky").unwrap().unwrap();
+        let flights = keyspace.get_table("flights").unwrap();
+        assert!(flights.get_column_index("origin").is_some());
+        assert!(keyspace.get_table("airports").is_ok());
+
+        // Agregar de nuevo la misma columna es un error y no cambia la tabla
+        let duplicated = run_query(&node, "ALTER TABLE sky.flights ADD origin TEXT").unwrap_err();
+        assert_eq!(
+            error::Error::from(&duplicated).code(),
+            ErrorCode::AlreadyExists
+        );
+        let keyspace = node.lock().unwrap().get_keyspace("sky").unwrap().unwrap();
+        let flights = keyspace.get_table("flights").unwrap();
+        assert_eq!(flights.get_columns().len(), 2);
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_added_column_reads_as_null_in_the_existing_rows() {
+        let storage_path = temp_path("node_added_column_test");
+        let node = node_with_table(local_config(&storage_path), FLIGHTS);
+
+        for query in [
+            "INSERT INTO sky.flights (id, origin) VALUES (1, 'EZE')",
+            "ALTER TABLE sky.flights ADD seats INT",
+            "INSERT INTO sky.flights (id, origin, seats) VALUES (2, 'AEP', 180)",
+        ] {
+            run_query(&node, query).unwrap();
+        }
+
+        let seats = |id: i32| match run_query(
+            &node,
+            &format!("SELECT seats FROM sky.flights WHERE id = {}", id),
+        )
+        .unwrap()
+        {
+            Frame::Result(result_::Result::Rows(rows)) => {
+                rows.rows_content[0].get("seats").cloned()
+            }
+            other => panic!("expected rows, got {:?}", other),
+        };
+
+        assert_eq!(seats(1), Some(ColumnValue::Null));
+        assert_eq!(seats(2), Some(ColumnValue::Int(180)));
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_writetime_reads_the_time_the_cell_was_written() {
+        let storage_path = temp_path("node_writetime_test");
+        let node = node_with_table(local_config(&storage_path), FLIGHTS);
+
+        let before = Node::current_timestamp();
+        run_query(
+            &node,
+            "INSERT INTO sky.flights (id, origin) VALUES (1, 'EZE')",
+        )
+        .unwrap();
+        let after = Node::current_timestamp();
+
+        match run_query(
+            &node,
+            "SELECT WRITETIME(origin), TTL(origin) FROM sky.flights WHERE id = 1",
+        )
+        .unwrap()
+        {
+            Frame::Result(result_::Result::Rows(rows)) => {
+                assert_eq!(rows.rows_content.len(), 1);
+                let row = &rows.rows_content[0];
+                match row.get("writetime(origin)") {
+                    Some(ColumnValue::Bigint(written_at)) => {
+                        assert!((before..=after).contains(written_at))
+                    }
+                    other => panic!("expected a bigint, got {:?}", other),
+                }
+                // Las filas se guardan sin TTL
+                assert_eq!(row.get("ttl(origin)"), Some(&ColumnValue::Null));
+            }
+            other => panic!("expected rows, got {:?}", other),
+        }
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_a_full_scan_needs_allow_filtering() {
+        let storage_path = temp_path("node_full_scan_test");
+        let node = node_with_table(local_config(&storage_path), FLIGHTS);
+        for query in [
+            "INSERT INTO sky.flights (id, origin) VALUES (1, 'EZE')",
+            "INSERT INTO sky.flights (id, origin) VALUES (2, 'AEP')",
+            "INSERT INTO sky.flights (id, origin) VALUES (3, 'EZE')",
+        ] {
+            run_query(&node, query).unwrap();
+        }
+
+        assert!(matches!(
+            run_query(&node, "SELECT id FROM sky.flights WHERE origin = 'EZE'"),
+            Err(NodeError::CQLError(CQLError::FullScanNotAllowed))
+        ));
+        assert!(matches!(
+            run_query(&node, "SELECT id FROM sky.flights"),
+            Err(NodeError::CQLError(CQLError::FullScanNotAllowed))
+        ));
+
+        let ids = |query: &str| match run_query(&node, query).unwrap() {
+            Frame::Result(result_::Result::Rows(rows)) => {
+                let mut ids: Vec<ColumnValue> = rows
+                    .rows_content
+                    .iter()
+                    .map(|row| row.get("id").cloned().unwrap())
+                    .collect();
+                ids.sort_by_key(|id| format!("{:?}", id));
+                ids
+            }
+            other => panic!("expected rows, got {:?}", other),
+        };
+        assert_eq!(
+            ids("SELECT id FROM sky.flights WHERE id = 2"),
+            vec![ColumnValue::Int(2)]
+        );
+        assert_eq!(
+            ids("SELECT id FROM sky.flights WHERE origin = 'EZE' ALLOW FILTERING"),
+            vec![ColumnValue::Int(1), ColumnValue::Int(3)]
+        );
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_restarted_node_keeps_its_data() {
+        let storage_path = temp_path("node_restart_test");
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        let node = node_with_table(local_config(&storage_path), FLIGHTS);
+        for query in ["INSERT INTO sky.flights (id, origin) VALUES (1, 'EZE')"] {
+            run_query(&node, query).unwrap();
+        }
+        drop(node);
+
+        // El nodo vuelve a levantarse sobre la misma carpeta
+        let node = Arc::new(Mutex::new(
+            Node::new(ip, vec![ip], storage_path.clone()).unwrap(),
+        ));
+        assert!(node.lock().unwrap().get_keyspace("sky").unwrap().is_some());
+
+        match run_query(&node, "SELECT id, origin FROM sky.flights WHERE id = 1").unwrap() {
+            Frame::Result(result_::Result::Rows(rows)) => {
+                assert_eq!(rows.rows_content.len(), 1);
+                assert_eq!(
+                    rows.rows_content[0].get("origin"),
+                    Some(&ColumnValue::Ascii("EZE".to_string()))
+                );
+            }
+            other => panic!("unexpected frame {:?}", other),
+        }
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_texts_with_commas_and_line_breaks_round_trip_through_the_node() {
+        let storage_path = temp_path("node_escape_test");
+        let node = node_with_table(
+            local_config(&storage_path),
+            "CREATE TABLE sky.notes (city TEXT, id INT, note TEXT, PRIMARY KEY ((city), id))",
+        );
+        for query in [
+            "INSERT INTO sky.notes (city, id, note) VALUES ('Buenos Aires, AR', 1, 'gate 3, \\ terminal\nA')",
+        ] {
+            run_query(&node, query).unwrap();
+        }
+
+        // La particion se encuentra por su valor original, con la coma
+        match run_query(
+            &node,
+            "SELECT city, note FROM sky.notes WHERE city = 'Buenos Aires, AR'",
+        )
+        .unwrap()
+        {
+            Frame::Result(result_::Result::Rows(rows)) => {
+                assert_eq!(rows.rows_content.len(), 1);
+                assert_eq!(
+                    rows.rows_content[0].get("city"),
+                    Some(&ColumnValue::Ascii("Buenos Aires, AR".to_string()))
+                );
+                assert_eq!(
+                    rows.rows_content[0].get("note"),
+                    Some(&ColumnValue::Ascii("gate 3, \\ terminal\nA".to_string()))
+                );
+            }
+            other => panic!("unexpected frame {:?}", other),
+        }
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_restarted_node_recovers_its_keyspaces_and_tables() {
+        let storage_path = temp_path("node_rehydrate_test");
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        let node = Arc::new(Mutex::new(
+            Node::new(ip, vec![ip], storage_path.clone()).unwrap(),
+        ));
+        for query in [
+            "CREATE KEYSPACE sky WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 3}",
+            "CREATE KEYSPACE ground WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 1}",
+            "CREATE TABLE sky.flights (id INT, origin TEXT, PRIMARY KEY (id))",
+            "CREATE TABLE sky.airports (code TEXT, city TEXT, PRIMARY KEY (code))",
+            "CREATE TABLE ground.crews (id INT, name TEXT, PRIMARY KEY (id))",
+        ] {
+            run_query(&node, query).unwrap();
+        }
+        drop(node);
+
+        let node = Node::new(ip, vec![ip], storage_path.clone()).unwrap();
+
+        let sky = node.get_keyspace("sky").unwrap().unwrap();
+        assert_eq!(sky.get_replication_factor(), 3);
+        let mut tables: Vec<String> = sky.get_tables().iter().map(|t| t.get_name()).collect();
+        tables.sort();
+        assert_eq!(tables, vec!["airports", "flights"]);
+        let airports = sky.get_table("airports").unwrap();
+        let columns: Vec<String> = airports
+            .get_columns()
+            .iter()
+            .map(|c| c.name.clone())
+            .collect();
+        assert_eq!(columns, vec!["code", "city"]);
+        assert_eq!(airports.get_partition_keys().unwrap(), vec!["code"]);
+
+        let ground = node.get_keyspace("ground").unwrap().unwrap();
+        assert_eq!(ground.get_replication_factor(), 1);
+        assert!(ground.get_table("crews").is_ok());
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_restarted_node_recovers_the_full_definition_of_its_tables() {
+        let storage_path = temp_path("node_table_schema_test");
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        let node = node_with_table(local_config(&storage_path), "CREATE TABLE sky.departures (airline TEXT, origin TEXT, departure TIMESTAMP, gate INT, seats INT, PRIMARY KEY ((airline, origin), departure, gate)) WITH CLUSTERING ORDER BY (departure DESC, gate ASC)");
+        for query in ["ALTER TABLE sky.departures ADD delayed BOOLEAN"] {
+            run_query(&node, query).unwrap();
+        }
+        let expected = node
+            .lock()
+            .unwrap()
+            .get_keyspace("sky")
+            .unwrap()
+            .unwrap()
+            .get_table("departures")
+            .unwrap();
+        drop(node);
+
+        let node = Node::new(ip, vec![ip], storage_path.clone()).unwrap();
+        let departures = node
+            .get_keyspace("sky")
+            .unwrap()
+            .unwrap()
+            .get_table("departures")
+            .unwrap();
+
+        assert_eq!(departures.to_bytes(), expected.to_bytes());
+        assert_eq!(
+            departures.get_partition_keys().unwrap(),
+            vec!["airline", "origin"]
+        );
+        assert_eq!(
+            departures.get_clustering_column_in_order(),
+            vec!["departure", "gate"]
+        );
+        let columns = departures.get_columns();
+        let departure = &columns[departures.get_column_index("departure").unwrap()];
+        assert_eq!(departure.data_type, DataType::Timestamp);
+        assert_eq!(departure.clustering_order, "DESC");
+        let delayed = &columns[departures.get_column_index("delayed").unwrap()];
+        assert_eq!(delayed.data_type, DataType::Boolean);
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_delete_by_partition_key_removes_every_row_of_the_partition() {
+        let storage_path = temp_path("node_partition_delete");
+        let node = node_with_table(
+            local_config(&storage_path),
+            "CREATE TABLE sky.flights (origin TEXT, id INT, PRIMARY KEY ((origin), id))",
+        );
+        for query in [
+            "INSERT INTO sky.flights (origin, id) VALUES ('EZE', 1)",
+            "INSERT INTO sky.flights (origin, id) VALUES ('EZE', 2)",
+            "INSERT INTO sky.flights (origin, id) VALUES ('AEP', 3)",
+            "DELETE FROM sky.flights WHERE origin = 'EZE'",
+        ] {
+            run_query(&node, query).unwrap();
+        }
+
+        let query = "SELECT origin FROM sky.flights WHERE origin = 'EZE'";
+        assert!(origins(run_query(&node, query).unwrap()).is_empty());
+        let query = "SELECT origin FROM sky.flights WHERE origin = 'AEP'";
+        assert_eq!(
+            origins(run_query(&node, query).unwrap()),
+            vec![ColumnValue::Ascii("AEP".to_string())]
+        );
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_health_check_reports_a_bootstrapping_node_as_not_ready() {
+        let storage_path = temp_path("node_health_test");
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        let node = Arc::new(Mutex::new(
+            Node::new(ip, vec![ip], storage_path.clone()).unwrap(),
+        ));
+        let ready =
+            |node: &Arc<Mutex<Node>>| match run_query(node, health::HEALTH_CHECK_QUERY).unwrap() {
+                Frame::Result(result_::Result::Rows(rows)) => {
+                    rows.rows_content[0].get("ready").cloned().unwrap()
+                }
+                other => panic!("expected rows, got {:?}", other),
+            };
+
+        let report = node.lock().unwrap().health();
+        assert_eq!(report.status, NodeStatus::Bootstrap);
+        assert!(!report.bootstrapped);
+        assert_eq!(ready(&node), ColumnValue::Boolean(false));
+
+        Node::join_cluster(&node, &[]).unwrap();
+        assert!(node.lock().unwrap().health().is_ready());
+        assert_eq!(ready(&node), ColumnValue::Boolean(true));
+
+        // Un nodo Normal con otro schema rompe el acuerdo
+        {
+            let mut guard = node.lock().unwrap();
+            let mut peer = guard.gossiper.endpoints_state[&ip].clone();
+            peer.application_state.schema.timestamp += 1;
+            guard
+                .gossiper
+                .endpoints_state
+                .insert(Ipv4Addr::new(127, 0, 0, 2), peer);
+            assert!(!guard.health().schema_agreement);
+        }
+        assert_eq!(ready(&node), ColumnValue::Boolean(false));
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_the_local_replicas_are_the_ones_gossiping_the_same_datacenter() {
+        let storage_path = temp_path("node_dc_test");
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        let mut node = Node::new(ip, vec![ip], storage_path.clone()).unwrap();
+        let local_peer = Ipv4Addr::new(127, 0, 0, 2);
+        let remote_peer = Ipv4Addr::new(127, 0, 0, 3);
+
+        node.set_datacenter("dc1").unwrap();
+        for (peer, datacenter) in [(local_peer, "dc1"), (remote_peer, "dc2")] {
+            let mut state = node.gossiper.endpoints_state[&ip].clone();
+            state.application_state.set_datacenter(datacenter);
+            node.gossiper.endpoints_state.insert(peer, state);
+        }
+
+        assert_eq!(node.gossiper.get_datacenter(ip), Some("dc1".to_string()));
+        assert!(node.is_in_local_datacenter(local_peer));
+        assert!(!node.is_in_local_datacenter(remote_peer));
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_the_keyspace_quotas_of_the_config_limit_the_writes_of_the_node() {
+        let storage_path = temp_path("node_quota_test");
+        let node = node_with_table(
+            local_config(&storage_path).with_keyspace_quota("sky", 1),
+            FLIGHTS,
+        );
+
+        let result = run_query(
+            &node,
+            "INSERT INTO sky.flights (id, origin) VALUES (1, 'EZE')",
+        );
+        assert!(
+            matches!(
+                result,
+                Err(NodeError::StorageEngineError(
+                    storage_engine::errors::StorageEngineError::StorageFull { .. }
+                ))
+            ),
+            "{:?}",
+            result
+        );
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_a_local_query_without_local_replicas_fails_as_unavailable() {
+        let storage_path = temp_path("node_local_unavailable_test");
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        let remote_peer = Ipv4Addr::new(127, 0, 0, 3);
+        let node = node_with_table(local_config(&storage_path).with_datacenter("dc1"), FLIGHTS);
+
+        // La unica replica de la particion esta en otro datacenter
+        let id = {
+            let mut guard = node.lock().unwrap();
+            assert_eq!(guard.gossiper.get_datacenter(ip), Some("dc1".to_string()));
+            let mut state = guard.gossiper.endpoints_state[&ip].clone();
+            state.application_state.set_datacenter("dc2");
+            guard.gossiper.endpoints_state.insert(remote_peer, state);
+            guard.partitioner.add_node(remote_peer).unwrap();
+            (0..)
+                .find(|id: &i32| {
+                    guard
+                        .partitioner
+                        .get_ip_for_partition(&[id.to_string()])
+                        .unwrap()
+                        == remote_peer
+                })
+                .unwrap()
+        };
+
+        for (query, consistency) in [
+            (
+                format!(
+                    "INSERT INTO sky.flights (id, origin) VALUES ({}, 'EZE')",
+                    id
+                ),
+                "LOCAL_ONE",
+            ),
+            (
+                format!("SELECT origin FROM sky.flights WHERE id = {}", id),
+                "LOCAL_QUORUM",
+            ),
+        ] {
+            let client_id = node.lock().unwrap().generate_client_id();
+            let (tx_reply, _rx_reply) = mpsc::channel();
+            let result = Node::handle_query_execution(
+                &query,
+                consistency,
+                &node,
+                Arc::new(Mutex::new(HashMap::new())),
+                tx_reply,
+                client_id,
+            );
+            match result {
+                Err(e) => assert_eq!(
+                    error::Error::from(&e).code(),
+                    ErrorCode::UnavailableException
+                ),
+                Ok(()) => panic!("{} at {} did not fail", query, consistency),
+            }
+            assert_eq!(node.lock().unwrap().open_query_handler.open_queries(), 0);
+        }
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_null_values_are_read_back_as_null_and_not_as_empty_text() {
+        let storage_path = temp_path("node_null_test");
+        let node = node_with_table(
+            local_config(&storage_path),
+            "CREATE TABLE sky.flights (id INT, gate TEXT, seats INT, PRIMARY KEY (id))",
+        );
+
+        run_query(
+            &node,
+            "INSERT INTO sky.flights (id, gate, seats) VALUES (1, null, 180)",
+        )
+        .unwrap();
+        run_query(
+            &node,
+            "INSERT INTO sky.flights (id, gate, seats) VALUES (2, '', 150)",
+        )
+        .unwrap();
+        // La columna que no se nombra queda en NULL
+        run_query(&node, "INSERT INTO sky.flights (id, gate) VALUES (3, 'A4')").unwrap();
+
+        let row = |id: i32| match run_query(
+            &node,
+            &format!("SELECT gate, seats FROM sky.flights WHERE id = {}", id),
+        )
+        .unwrap()
+        {
+            Frame::Result(result_::Result::Rows(rows)) => {
+                let row = &rows.rows_content[0];
+                (row.get("gate").cloned(), row.get("seats").cloned())
+            }
+            other => panic!("expected rows, got {:?}", other),
+        };
+
+        assert_eq!(
+            row(1),
+            (Some(ColumnValue::Null), Some(ColumnValue::Int(180)))
+        );
+        assert_eq!(
+            row(2),
+            (
+                Some(ColumnValue::Ascii(String::new())),
+                Some(ColumnValue::Int(150))
+            )
+        );
+        assert_eq!(
+            row(3),
+            (
+                Some(ColumnValue::Ascii("A4".to_string())),
+                Some(ColumnValue::Null)
+            )
+        );
+
+        // Una clave no puede ser NULL
+        assert!(run_query(
+            &node,
+            "INSERT INTO sky.flights (id, gate) VALUES (null, 'B1')"
+        )
+        .is_err());
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_overlapping_queries_on_one_connection_are_answered_with_their_stream_id() {
+        use native_protocol::messages::auth::AuthResponse;
+        use native_protocol::messages::query::{Consistency, Query as QueryMessage, QueryParams};
+        use native_protocol::types::Bytes;
+
+        let storage_path = temp_path("node_streams_test");
+        let node = node_with_table(local_config(&storage_path), FLIGHTS);
+        for query in ["INSERT INTO sky.flights (id, origin) VALUES (1, 'EZE')"] {
+            run_query(&node, query).unwrap();
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server_node = Arc::clone(&node);
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.set_read_timeout(Some(CLIENT_POLL_INTERVAL)).unwrap();
+            Node::serve_client(
+                server_node,
+                &mut stream,
+                Arc::new(Mutex::new(HashMap::new())),
+            )
+        });
+
+        let mut client = TcpStream::connect(address).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut pending = Vec::new();
+        let mut read_frame = |client: &mut TcpStream| read_client_frame(client, &mut pending);
 
-        Ok(())
+        let token = Bytes::Vec(b"admin".to_vec());
+        let auth = Frame::AuthResponse(AuthResponse::new(token));
+        client
+            .write_all(&auth.to_bytes_with_stream(1).unwrap())
+            .unwrap();
+        assert!(matches!(
+            read_frame(&mut client),
+            (1, Frame::AuthSuccess(_))
+        ));
+
+        // Las dos consultas se mandan juntas, antes de leer alguna respuesta
+        let query = |query: &str| {
+            Frame::Query(QueryMessage::new(
+                query.to_string(),
+                QueryParams::new(Consistency::One, vec![]),
+            ))
+        };
+        let mut requests = query("SELECT origin FROM sky.flights WHERE id = 1")
+            .to_bytes_with_stream(7)
+            .unwrap();
+        requests.extend(
+            query(health::HEALTH_CHECK_QUERY)
+                .to_bytes_with_stream(8)
+                .unwrap(),
+        );
+        client.write_all(&requests).unwrap();
+
+        let answers: HashMap<i16, Frame> = (0..2).map(|_| read_frame(&mut client)).collect();
+        match &answers[&7] {
+            Frame::Result(result_::Result::Rows(rows)) => assert_eq!(
+                rows.rows_content[0].get("origin"),
+                Some(&ColumnValue::Ascii("EZE".to_string()))
+            ),
+            other => panic!("expected the flight, got {:?}", other),
+        }
+        match &answers[&8] {
+            Frame::Result(result_::Result::Rows(rows)) => {
+                assert!(rows.rows_content[0].get("ready").is_some())
+            }
+            other => panic!("expected the health report, got {:?}", other),
+        }
+
+        std::fs::remove_dir_all(storage_path).ok();
     }
 
-    fn handle_node_connections(
-        node: Arc<Mutex<Node>>,
-        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
-        self_ip: std::net::Ipv4Addr,
-    ) -> Result<(), NodeError> {
-        let socket = SocketAddrV4::new(self_ip, INTERNODE_PORT);
-        let listener = TcpListener::bind(socket)?;
-        for stream in listener.incoming() {
-            match stream {
-                Ok(stream) => {
-                    let node_clone = Arc::clone(&node);
-                    let stream = Arc::new(Mutex::new(stream)); // Encapsulates the stream in Arc<Mutex<TcpStream>>
-                    let connections_clone = Arc::clone(&connections);
+    #[test]
+    fn test_the_queries_of_a_connection_wait_for_a_free_worker() {
+        use native_protocol::messages::auth::AuthResponse;
+        use native_protocol::messages::query::{Consistency, Query as QueryMessage, QueryParams};
+        use native_protocol::types::Bytes;
+
+        let storage_path = temp_path("node_client_workers_test");
+        let node = node_with_table(local_config(&storage_path), FLIGHTS);
+        node.lock().unwrap().set_client_workers(1);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server_node = Arc::clone(&node);
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.set_read_timeout(Some(CLIENT_POLL_INTERVAL)).unwrap();
+            Node::serve_client(
+                server_node,
+                &mut stream,
+                Arc::new(Mutex::new(HashMap::new())),
+            )
+        });
 
-                    thread::spawn(move || {
-                        if let Err(e) = Node::handle_incoming_internode_messages(
-                            node_clone,
-                            stream,
-                            connections_clone,
-                        ) {
-                            eprintln!("{:?}", e);
-                        }
-                    });
-                }
-                Err(e) => {
-                    eprintln!("Error accepting internode connection: {:?}", e);
-                }
+        let mut client = TcpStream::connect(address).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut pending = Vec::new();
+        let token = Bytes::Vec(b"admin".to_vec());
+        let auth = Frame::AuthResponse(AuthResponse::new(token));
+        client
+            .write_all(&auth.to_bytes_with_stream(1).unwrap())
+            .unwrap();
+        assert!(matches!(
+            read_client_frame(&mut client, &mut pending),
+            (1, Frame::AuthSuccess(_))
+        ));
+
+        // Mas consultas que workers y lugares en la cola, todas mandadas antes de leer
+        let mut requests = Vec::new();
+        for id in 0..6 {
+            let query = Frame::Query(QueryMessage::new(
+                format!(
+                    "INSERT INTO sky.flights (id, origin) VALUES ({}, 'EZE')",
+                    id
+                ),
+                QueryParams::new(Consistency::One, vec![]),
+            ));
+            requests.extend(query.to_bytes_with_stream(10 + id).unwrap());
+        }
+        client.write_all(&requests).unwrap();
+
+        // Con un solo worker las consultas corren de a una, en el orden en que llegaron
+        let answers: Vec<i16> = (0..6)
+            .map(|_| match read_client_frame(&mut client, &mut pending) {
+                (stream_id, Frame::Result(_)) => stream_id,
+                other => panic!("expected the insert to be applied, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(answers, (10..16).collect::<Vec<i16>>());
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_clients_that_disconnect_are_forgotten() {
+        let storage_path = temp_path("node_clients_test");
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        let node = Arc::new(Mutex::new(
+            Node::new(ip, vec![ip], storage_path.clone()).unwrap(),
+        ));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server_node = Arc::clone(&node);
+        let server = thread::spawn(move || {
+            for _ in 0..50 {
+                let (mut stream, _) = listener.accept().unwrap();
+                stream.set_read_timeout(Some(CLIENT_POLL_INTERVAL)).unwrap();
+                Node::serve_client(
+                    Arc::clone(&server_node),
+                    &mut stream,
+                    Arc::new(Mutex::new(HashMap::new())),
+                )
+                .unwrap();
             }
+        });
+        for _ in 0..50 {
+            drop(TcpStream::connect(address).unwrap());
         }
+        server.join().unwrap();
+        assert!(node.lock().unwrap().clients_keyspace.is_empty());
+
+        // Al dar la vuelta no se repite el id de un cliente conectado
+        let mut guard = node.lock().unwrap();
+        guard.last_client_id = 0;
+        let connected = guard.generate_client_id();
+        guard.last_client_id = i32::MAX;
+        let next = guard.generate_client_id();
+        assert_eq!((connected, next), (1, 2));
+        guard.remove_client(connected);
+        guard.remove_client(next);
+        assert!(guard.clients_keyspace.is_empty());
+        drop(guard);
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
 
-        Ok(())
+    #[test]
+    fn test_a_node_from_config_uses_its_settings() {
+        use gossip::structures::endpoint_state::EndpointState;
+        use native_protocol::messages::auth::AuthResponse;
+        use native_protocol::types::Bytes;
+        use storage_engine::durability::DurabilityPolicy;
+
+        let storage_path = temp_path("node_config_test");
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        let config = NodeConfig::new(ip, vec![ip], storage_path.clone())
+            .with_client_port(19042)
+            .with_internode_port(19043)
+            .with_gossip_interval(Duration::from_millis(250))
+            .with_gossip_fanout(2)
+            .with_durability(DurabilityPolicy::Never)
+            .with_password("secret");
+        let node = node_with_table(config, FLIGHTS);
+        {
+            let guard = node.lock().unwrap();
+            assert_eq!((guard.client_port, guard.internode_port), (19042, 19043));
+            assert_eq!(guard.gossip_interval, Duration::from_millis(250));
+        }
+
+        // Con `Never` las escrituras quedan sin sincronizar hasta el flush
+        run_query(
+            &node,
+            "INSERT INTO sky.flights (id, origin) VALUES (1, 'EZE')",
+        )
+        .unwrap();
+        {
+            let guard = node.lock().unwrap();
+            assert_eq!(guard.durability.syncs(), 0);
+            assert!(guard.durability.pending_files() > 0);
+            guard.flush().unwrap();
+            assert_eq!(guard.durability.pending_files(), 0);
+        }
+
+        // Cada ronda habla con a lo sumo `fanout` nodos
+        {
+            let mut guard = node.lock().unwrap();
+            for last in 2..=6 {
+                guard
+                    .gossiper
+                    .endpoints_state
+                    .insert(Ipv4Addr::new(127, 0, 0, last), EndpointState::default());
+            }
+            let (ips, _) = guard.gossiper.start_round(ip).unwrap();
+            assert_eq!(ips.len(), 2);
+        }
+
+        // Solo la password configurada autentica al cliente
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server_node = Arc::clone(&node);
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.set_read_timeout(Some(CLIENT_POLL_INTERVAL)).unwrap();
+            Node::serve_client(
+                server_node,
+                &mut stream,
+                Arc::new(Mutex::new(HashMap::new())),
+            )
+        });
+        let mut client = TcpStream::connect(address).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut pending = Vec::new();
+        for (password, authenticated) in [("admin", false), ("secret", true)] {
+            let token = Bytes::Vec(password.as_bytes().to_vec());
+            let auth = Frame::AuthResponse(AuthResponse::new(token));
+            client
+                .write_all(&auth.to_bytes_with_stream(1).unwrap())
+                .unwrap();
+            let (_, answer) = read_client_frame(&mut client, &mut pending);
+            assert_eq!(matches!(answer, Frame::AuthSuccess(_)), authenticated);
+        }
+
+        std::fs::remove_dir_all(storage_path).ok();
     }
 
-    fn handle_client_connections(
-        node: Arc<Mutex<Node>>,
-        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
-        self_ip: std::net::Ipv4Addr,
-    ) -> Result<(), NodeError> {
-        // Cargar configuración TLS
-        let project_dir = env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string());
-        let path_certs = Path::new(&project_dir)
-            .parent()
-            .unwrap_or_else(|| Path::new("."))
-            .join("certs");
+    #[test]
+    fn test_a_retried_write_with_an_idempotency_key_is_applied_once() {
+        let storage_path = temp_path("node_idempotency_test");
+        let node = node_with_table(
+            local_config(&storage_path),
+            "CREATE TABLE sky.events (flight TEXT, id UUID, kind TEXT, PRIMARY KEY ((flight), id))",
+        );
+
+        let client_id = node.lock().unwrap().generate_client_id();
+        let write = |key: &str| {
+            let query = format!(
+                "INSERT INTO sky.events (flight, id, kind) VALUES ('AR1', uuid(), 'boarding') USING IDEMPOTENCY KEY '{}'",
+                key
+            );
+            let connections = Arc::new(Mutex::new(HashMap::new()));
+            Node::answer_client_query(&query, "ONE", &node, connections, client_id)
+        };
+        let events = || match run_query(&node, "SELECT id FROM sky.events WHERE flight = 'AR1'") {
+            Ok(Frame::Result(result_::Result::Rows(rows))) => rows.rows_content.len(),
+            other => panic!("expected the events, got {:?}", other),
+        };
 
-        // Cargar configuración TLS
-        let certs = CertificateDer::pem_file_iter(path_certs.join("cert.crt"))
+        // El reintento se contesta igual que el primer intento, sin otro uuid()
+        assert!(matches!(write("k1"), Frame::Result(_)));
+        assert!(matches!(write("k1"), Frame::Result(_)));
+        assert_eq!(events(), 1);
+
+        // Otra clave es otra escritura
+        assert!(matches!(write("k2"), Frame::Result(_)));
+        assert_eq!(events(), 2);
+        assert_eq!(node.lock().unwrap().idempotent_writes.len(), 2);
+
+        // Dos intentos a la vez: uno aplica la escritura y el otro espera su respuesta
+        std::thread::scope(|scope| {
+            let attempts: Vec<_> = (0..2).map(|_| scope.spawn(|| write("k3"))).collect();
+            for attempt in attempts {
+                assert!(matches!(attempt.join().unwrap(), Frame::Result(_)));
+            }
+        });
+        assert_eq!(events(), 3);
+        assert_eq!(node.lock().unwrap().idempotent_writes.len(), 3);
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_a_retry_stops_waiting_for_an_attempt_that_does_not_finish() {
+        let storage_path = temp_path("node_idempotency_timeout_test");
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        let node = Arc::new(Mutex::new(
+            Node::new(ip, vec![ip], storage_path.clone()).unwrap(),
+        ));
+        let key = IdempotencyKey {
+            table: "sky.events".to_string(),
+            partition: vec!["AR1".to_string()],
+            key: "k1".to_string(),
+        };
+
+        let first = Node::reserve_idempotent_write(&node, key.clone(), Duration::ZERO).unwrap();
+        match Node::reserve_idempotent_write(&node, key.clone(), Duration::from_millis(20)) {
+            Err(Frame::Error(timeout)) => assert_eq!(timeout.code(), ErrorCode::WriteTimeout),
+            Err(other) => panic!("expected a write timeout, got {:?}", other),
+            Ok(_) => panic!("the write was reserved twice"),
+        }
+
+        // Un intento que termina sin respuesta libera la escritura para el proximo
+        drop(first);
+        assert!(Node::reserve_idempotent_write(&node, key, Duration::ZERO).is_ok());
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    // Lee el proximo frame que el nodo manda al cliente, con su stream id
+    fn read_client_frame(client: &mut TcpStream, pending: &mut Vec<u8>) -> (i16, Frame) {
+        use native_protocol::Serializable;
+
+        loop {
+            if let Some(length) =
+                Frame::length_of(pending).filter(|length| *length <= pending.len())
+            {
+                let bytes: Vec<u8> = pending.drain(..length).collect();
+                return (
+                    Frame::stream_of(&bytes).unwrap(),
+                    Frame::from_bytes(&bytes).unwrap(),
+                );
+            }
+            let mut buffer = [0; 2048];
+            let bytes_read = client.read(&mut buffer).unwrap();
+            assert!(bytes_read > 0, "the node closed the connection");
+            pending.extend_from_slice(&buffer[..bytes_read]);
+        }
+    }
+
+    #[test]
+    fn test_invalid_client_frames_are_answered_with_a_protocol_error() {
+        let storage_path = temp_path("node_garbage_test");
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        let node = Arc::new(Mutex::new(
+            Node::new(ip, vec![ip], storage_path.clone()).unwrap(),
+        ));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.set_read_timeout(Some(CLIENT_POLL_INTERVAL)).unwrap();
+            Node::serve_client(node, &mut stream, Arc::new(Mutex::new(HashMap::new())))
+        });
+
+        let mut client = TcpStream::connect(address).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut pending = Vec::new();
+
+        // Bytes al azar con un header que se puede leer
+        let garbage = [
+            0x03, 0x00, 0x00, 0x03, 0x7F, 0x00, 0x00, 0x00, 0x04, 0xDE, 0xAD, 0xBE, 0xEF,
+        ];
+        client.write_all(&garbage).unwrap();
+        assert!(matches!(
+            read_client_frame(&mut client, &mut pending),
+            (3, Frame::Error(error::Error::ProtocolError(_)))
+        ));
+
+        // Un frame valido que un cliente no deberia mandar
+        let unexpected = Frame::AuthSuccess(AuthSuccess::default());
+        client
+            .write_all(&unexpected.to_bytes_with_stream(4).unwrap())
+            .unwrap();
+        assert!(matches!(
+            read_client_frame(&mut client, &mut pending),
+            (4, Frame::Error(error::Error::ProtocolError(_)))
+        ));
+
+        // La conexion sigue atendiendo pedidos validos
+        client
+            .write_all(&Frame::Startup.to_bytes_with_stream(5).unwrap())
+            .unwrap();
+        assert!(matches!(
+            read_client_frame(&mut client, &mut pending),
+            (5, Frame::Authenticate(_))
+        ));
+
+        // Con un largo imposible no se sabe donde sigue el proximo frame: se cierra la conexion
+        client
+            .write_all(&[0x03, 0x00, 0x00, 0x06, 0x07, 0xFF, 0xFF, 0xFF, 0xFF])
+            .unwrap();
+        assert!(matches!(
+            read_client_frame(&mut client, &mut pending),
+            (6, Frame::Error(error::Error::ProtocolError(_)))
+        ));
+        assert!(server.join().unwrap().is_ok());
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    fn origins(frame: Frame) -> Vec<ColumnValue> {
+        match frame {
+            Frame::Result(result_::Result::Rows(rows)) => rows
+                .rows_content
+                .iter()
+                .filter_map(|row| row.get("origin").cloned())
+                .collect(),
+            other => panic!("unexpected frame {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_cached_read_does_not_scan_the_table_again() {
+        let storage_path = temp_path("node_cached_read_test");
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        let node = node_with_table(local_config(&storage_path), FLIGHTS);
+        node.lock()
             .unwrap()
-            .map(|cert| cert.unwrap())
-            .collect();
-        let private_key = PrivateKeyDer::from_pem_file(path_certs.join("cert.key")).unwrap();
+            .enable_query_cache(16, Duration::from_secs(60));
+        run_query(
+            &node,
+            "INSERT INTO sky.flights (id, origin) VALUES (1, 'EZE')",
+        )
+        .unwrap();
+
+        let select = "SELECT id, origin FROM sky.flights WHERE id = 1";
+        let first = origins(run_query(&node, select).unwrap());
+        assert_eq!(first, vec![ColumnValue::Ascii("EZE".to_string())]);
+
+        // Se vacia la tabla por fuera del nodo: solo la cache puede seguir devolviendo la fila
+        let file = flights_file(&storage_path, ip);
+        let header = std::fs::read_to_string(&file).unwrap();
+        std::fs::write(&file, format!("{}\n", header.lines().next().unwrap())).unwrap();
+
+        let second = origins(run_query(&node, select).unwrap());
+        assert_eq!(second, first);
+        let guard = node.lock().unwrap();
+        let cache = guard.query_cache().unwrap();
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+        drop(guard);
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
 
-        match rustls::crypto::aws_lc_rs::default_provider().install_default() {
-            Ok(_) => {}
-            Err(err) => {
-                eprintln!("Failed to install CryptoProvider: {:?}", err);
+    #[test]
+    fn test_a_write_to_the_table_invalidates_its_cached_reads() {
+        let storage_path = temp_path("node_cache_invalidation_test");
+        let node = node_with_table(local_config(&storage_path), FLIGHTS);
+        node.lock()
+            .unwrap()
+            .enable_query_cache(16, Duration::from_secs(60));
+        for query in [
+            "CREATE TABLE sky.airports (code TEXT, city TEXT, PRIMARY KEY (code))",
+            "INSERT INTO sky.flights (id, origin) VALUES (1, 'EZE')",
+            "INSERT INTO sky.airports (code, city) VALUES ('EZE', 'Buenos Aires')",
+        ] {
+            run_query(&node, query).unwrap();
+        }
+
+        let select = "SELECT id, origin FROM sky.flights WHERE id = 1";
+        let airports = "SELECT code, city FROM sky.airports WHERE code = 'EZE'";
+        run_query(&node, select).unwrap();
+        run_query(&node, airports).unwrap();
+        assert_eq!(node.lock().unwrap().query_cache().unwrap().len(), 2);
+
+        run_query(&node, "UPDATE sky.flights SET origin = 'AEP' WHERE id = 1").unwrap();
+        // Solo se descarta lo cacheado de la tabla escrita
+        assert_eq!(node.lock().unwrap().query_cache().unwrap().len(), 1);
+
+        let after_write = origins(run_query(&node, select).unwrap());
+        assert_eq!(after_write, vec![ColumnValue::Ascii("AEP".to_string())]);
+        let guard = node.lock().unwrap();
+        let cache = guard.query_cache().unwrap();
+        assert_eq!((cache.hits(), cache.misses()), (0, 3));
+        drop(guard);
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    fn flights_file(storage_path: &Path, ip: Ipv4Addr) -> PathBuf {
+        storage_path
+            .join(format!("keyspaces_of_{}", ip.to_string().replace('.', "_")))
+            .join("sky")
+            .join("flights.csv")
+    }
+
+    #[test]
+    fn test_alter_table_widening_a_column_keeps_its_rows() {
+        let storage_path = temp_path("node_widen_column_test");
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        let node = node_with_table(
+            local_config(&storage_path),
+            "CREATE TABLE sky.flights (id INT, seats INT, PRIMARY KEY (id))",
+        );
+
+        for query in [
+            "INSERT INTO sky.flights (id, seats) VALUES (1, 180)",
+            "INSERT INTO sky.flights (id) VALUES (2)",
+        ] {
+            run_query(&node, query).unwrap();
+        }
+        let rows_before = std::fs::read_to_string(flights_file(&storage_path, ip)).unwrap();
+
+        run_query(&node, "ALTER TABLE sky.flights MODIFY seats DOUBLE").unwrap();
+
+        let keyspace = node.lock().unwrap().get_keyspace("sky").unwrap().unwrap();
+        let flights = keyspace.get_table("flights").unwrap();
+        let seats = &flights.get_columns()[flights.get_column_index("seats").unwrap()];
+        assert_eq!(seats.data_type, DataType::Double);
+        assert_eq!(
+            std::fs::read_to_string(flights_file(&storage_path, ip)).unwrap(),
+            rows_before
+        );
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_alter_table_narrowing_with_incompatible_rows_is_rejected() {
+        let storage_path = temp_path("node_narrow_column_test");
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        let node = node_with_table(
+            local_config(&storage_path),
+            "CREATE TABLE sky.flights (id INT, price DOUBLE, PRIMARY KEY (id))",
+        );
+
+        for query in [
+            "INSERT INTO sky.flights (id, price) VALUES (1, 10.5)",
+            "INSERT INTO sky.flights (id, price) VALUES (2, 20)",
+        ] {
+            run_query(&node, query).unwrap();
+        }
+        let rows_before = std::fs::read_to_string(flights_file(&storage_path, ip)).unwrap();
+
+        let error = run_query(&node, "ALTER TABLE sky.flights MODIFY price INT").unwrap_err();
+        match &error {
+            NodeError::CQLError(CQLError::IncompatibleValues {
+                column,
+                data_type,
+                rows,
+            }) => {
+                assert_eq!(column, "price");
+                assert_eq!(data_type, "INT");
+                assert_eq!(rows, &vec!["1,10.5".to_string()]);
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+        assert_eq!(error::Error::from(&error).code(), ErrorCode::Invalid);
+
+        let keyspace = node.lock().unwrap().get_keyspace("sky").unwrap().unwrap();
+        let flights = keyspace.get_table("flights").unwrap();
+        let price = &flights.get_columns()[flights.get_column_index("price").unwrap()];
+        assert_eq!(price.data_type, DataType::Double);
+        assert_eq!(
+            std::fs::read_to_string(flights_file(&storage_path, ip)).unwrap(),
+            rows_before
+        );
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    fn applied(frame: Frame) -> bool {
+        match frame {
+            Frame::Result(result_::Result::Rows(rows)) => {
+                rows.rows_content[0].get("[applied]") == Some(&ColumnValue::Boolean(true))
             }
+            other => panic!("expected an [applied] row, got {:?}", other),
         }
+    }
 
-        let config = ServerConfig::builder()
-            .with_no_client_auth()
-            .with_single_cert(certs, private_key)
+    #[test]
+    fn test_conditional_writes_report_if_they_were_applied() {
+        let storage_path = temp_path("node_lwt_test");
+        let node = node_with_table(
+            local_config(&storage_path),
+            "CREATE TABLE sky.flights (id INT, version INT, origin TEXT, PRIMARY KEY (id))",
+        );
+
+        // IF NOT EXISTS solo inserta la primera vez
+        let query =
+            "INSERT INTO sky.flights (id, version, origin) VALUES (1, 1, 'EZE') IF NOT EXISTS";
+        assert!(applied(run_query(&node, query).unwrap()));
+        let query =
+            "INSERT INTO sky.flights (id, version, origin) VALUES (1, 5, 'AEP') IF NOT EXISTS";
+        assert!(!applied(run_query(&node, query).unwrap()));
+
+        let query =
+            "UPDATE sky.flights SET version = 2, origin = 'COR' WHERE id = 1 IF version = 1";
+        assert!(applied(run_query(&node, query).unwrap()));
+        // La version ya no es 1, la segunda actualizacion no se aplica
+        let query =
+            "UPDATE sky.flights SET version = 3, origin = 'MDZ' WHERE id = 1 IF version = 1";
+        assert!(!applied(run_query(&node, query).unwrap()));
+
+        let query = "DELETE FROM sky.flights WHERE id = 1 IF version = 1";
+        assert!(!applied(run_query(&node, query).unwrap()));
+
+        let frame = run_query(
+            &node,
+            "SELECT version, origin FROM sky.flights WHERE id = 1",
+        )
+        .unwrap();
+        match frame {
+            Frame::Result(result_::Result::Rows(rows)) => {
+                assert_eq!(rows.rows_content.len(), 1);
+                assert_eq!(
+                    rows.rows_content[0].get("version"),
+                    Some(&ColumnValue::Int(2))
+                );
+                assert_eq!(
+                    rows.rows_content[0].get("origin"),
+                    Some(&ColumnValue::Ascii("COR".to_string()))
+                );
+            }
+            other => panic!("expected rows, got {:?}", other),
+        }
+
+        let query = "DELETE FROM sky.flights WHERE id = 1 IF version = 2";
+        assert!(applied(run_query(&node, query).unwrap()));
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_deleted_rows_are_hidden_until_inserted_again() {
+        let storage_path = temp_path("node_tombstone_test");
+        let node = node_with_table(local_config(&storage_path), FLIGHTS);
+
+        let row_count = |node: &Arc<Mutex<Node>>| match run_query(
+            node,
+            "SELECT origin FROM sky.flights WHERE id = 1",
+        )
+        .unwrap()
+        {
+            Frame::Result(result_::Result::Rows(rows)) => rows.rows_content.len(),
+            other => panic!("expected rows, got {:?}", other),
+        };
+
+        run_query(
+            &node,
+            "INSERT INTO sky.flights (id, origin) VALUES (1, 'EZE')",
+        )
+        .unwrap();
+
+        run_query(&node, "DELETE FROM sky.flights WHERE id = 1").unwrap();
+        assert_eq!(row_count(&node), 0);
+
+        // El tombstone no cuenta como fila existente
+        let query = "INSERT INTO sky.flights (id, origin) VALUES (1, 'AEP') IF NOT EXISTS";
+        assert!(applied(run_query(&node, query).unwrap()));
+        assert_eq!(row_count(&node), 1);
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_use_sets_the_keyspace_of_the_client() {
+        let storage_path = temp_path("node_use_test");
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        let node = Arc::new(Mutex::new(
+            Node::new(ip, vec![ip], storage_path.clone()).unwrap(),
+        ));
+        let client_id = node.lock().unwrap().generate_client_id();
+
+        run_query(
+            &node,
+            "CREATE KEYSPACE world WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 1}",
+        )
+        .unwrap();
+
+        match run_query_as(&node, client_id, "USE world").unwrap() {
+            Frame::Result(result_::Result::SetKeyspace(keyspace)) => assert_eq!(keyspace, "world"),
+            other => panic!("expected set keyspace, got {:?}", other),
+        }
+
+        // Las queries sin keyspace usan el elegido con USE
+        run_query_as(
+            &node,
+            client_id,
+            "CREATE TABLE flights (id INT, origin TEXT, PRIMARY KEY (id))",
+        )
+        .unwrap();
+        run_query_as(
+            &node,
+            client_id,
+            "INSERT INTO flights (id, origin) VALUES (1, 'EZE')",
+        )
+        .unwrap();
+        match run_query(&node, "SELECT origin FROM world.flights WHERE id = 1").unwrap() {
+            Frame::Result(result_::Result::Rows(rows)) => assert_eq!(rows.rows_content.len(), 1),
+            other => panic!("expected rows, got {:?}", other),
+        }
+
+        // Otro cliente no tiene keyspace elegido
+        assert!(run_query(&node, "SELECT origin FROM flights WHERE id = 1").is_err());
+
+        match run_query_as(&node, client_id, "USE nonexistent") {
+            Err(NodeError::CQLError(CQLError::KeyspaceDoesNotExist)) => {}
+            other => panic!("expected a missing keyspace error, got {:?}", other),
+        }
+        // Un USE fallido no cambia el keyspace actual
+        assert!(run_query_as(&node, client_id, "SELECT origin FROM flights WHERE id = 1").is_ok());
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_nodes_join_and_leave_the_cluster_in_process() {
+        let storage_path = temp_path("node_join_test");
+        let ip_a = Ipv4Addr::new(127, 0, 0, 1);
+        let ip_b = Ipv4Addr::new(127, 0, 0, 2);
+        let node_a = Arc::new(Mutex::new(
+            Node::new(ip_a, vec![ip_a], storage_path.join("a")).unwrap(),
+        ));
+        let node_b = Arc::new(Mutex::new(
+            Node::new(ip_b, vec![ip_b], storage_path.join("b")).unwrap(),
+        ));
+
+        Node::join_cluster(&node_a, &[]).unwrap();
+        Node::join_cluster(&node_b, std::slice::from_ref(&node_a)).unwrap();
+
+        for node in [&node_a, &node_b] {
+            let guard = node.lock().unwrap();
+            let membership = guard.membership_snapshot();
+            assert_eq!(membership.get(&ip_a), Some(&NodeStatus::Normal));
+            assert_eq!(membership.get(&ip_b), Some(&NodeStatus::Normal));
+            assert!(guard.partitioner.contains_node(&ip_a));
+            assert!(guard.partitioner.contains_node(&ip_b));
+        }
+
+        node_b.lock().unwrap().leave_cluster().unwrap();
+        assert!(!node_b.lock().unwrap().partitioner.contains_node(&ip_b));
+
+        // A se entera en la siguiente ronda de gossip
+        Node::exchange_gossip(&node_a, &node_b).unwrap();
+        let mut guard_a = node_a.lock().unwrap();
+        assert_eq!(
+            guard_a.membership_snapshot().get(&ip_b),
+            Some(&NodeStatus::Leaving)
+        );
+        assert!(guard_a.update_partitioner_from_gossiper().unwrap());
+        assert_eq!(guard_a.partitioner.get_nodes(), vec![ip_a]);
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_replicas_with_different_rows_gossip_different_data_digests() {
+        let storage_path = temp_path("node_digest_test");
+        let ip_a = Ipv4Addr::new(127, 0, 0, 70);
+        let ip_b = Ipv4Addr::new(127, 0, 0, 71);
+        let node_a = Arc::new(Mutex::new(
+            Node::new(ip_a, vec![ip_a], storage_path.join("a")).unwrap(),
+        ));
+        let node_b = Arc::new(Mutex::new(
+            Node::new(ip_b, vec![ip_b], storage_path.join("b")).unwrap(),
+        ));
+        Node::join_cluster(&node_a, &[]).unwrap();
+        Node::join_cluster(&node_b, std::slice::from_ref(&node_a)).unwrap();
+
+        // Con dos nodos y replication_factor 2 los dos tienen todo el rango
+        run_query(
+            &node_a,
+            "CREATE KEYSPACE sky WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 2}",
+        )
+        .unwrap();
+        run_query(
+            &node_a,
+            "CREATE TABLE sky.flights (id INT, PRIMARY KEY (id))",
+        )
+        .unwrap();
+        Node::gossip_with(&node_a, std::slice::from_ref(&node_b)).unwrap();
+
+        let insert_into = |node: &Arc<Mutex<Node>>, id: &str| {
+            let guard = node.lock().unwrap();
+            StorageEngine::new(guard.storage_path.clone(), guard.ip.to_string())
+                .insert(
+                    "sky",
+                    "flights",
+                    vec![id],
+                    vec![Column::new("id", DataType::Int, true, false)],
+                    vec![],
+                    false,
+                    false,
+                    10,
+                )
+                .unwrap();
+        };
+        let digests_seen_by_a = || {
+            // Como en una ronda de gossip: se publican los digests y avanza el heartbeat
+            for (node, ip) in [(&node_a, ip_a), (&node_b, ip_b)] {
+                Node::publish_data_digests(node).unwrap();
+                node.lock().unwrap().gossiper.heartbeat(ip).unwrap();
+            }
+            Node::exchange_gossip(&node_a, &node_b).unwrap();
+            let snapshot = node_a.lock().unwrap().data_digests_snapshot();
+            (snapshot[&ip_a].clone(), snapshot[&ip_b].clone())
+        };
+        let range_of = |id: &str| {
+            let owner = node_a
+                .lock()
+                .unwrap()
+                .partitioner
+                .get_ip_for_partition(&[id])
+                .unwrap();
+            format!("sky.flights@{}", owner)
+        };
+
+        insert_into(&node_a, "1");
+        insert_into(&node_a, "2");
+        insert_into(&node_b, "1");
+        let (digests_a, digests_b) = digests_seen_by_a();
+        let (range_1, range_2) = (range_of("1"), range_of("2"));
+        // Solo difiere el rango de la fila que le falta a B
+        assert_ne!(
+            digests_a[&range_2],
+            digests_b.get(&range_2).copied().unwrap_or_default()
+        );
+        if range_1 != range_2 {
+            assert_eq!(digests_a[&range_1], digests_b[&range_1]);
+        }
+        let rows = |digests: &BTreeMap<String, DataDigest>| {
+            digests.values().map(|digest| digest.rows).sum::<u64>()
+        };
+        assert_eq!(rows(&digests_a), 2);
+        assert_eq!(rows(&digests_b), 1);
+
+        // B recibe la fila que le faltaba y vuelve a coincidir con A
+        let version_before = node_b.lock().unwrap().gossiper.endpoints_state[&ip_b]
+            .application_state
+            .version;
+        insert_into(&node_b, "2");
+        let (digests_a, digests_b) = digests_seen_by_a();
+        assert_eq!(digests_a, digests_b);
+        assert!(
+            node_a.lock().unwrap().gossiper.endpoints_state[&ip_b]
+                .application_state
+                .version
+                > version_before
+        );
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_a_replacement_takes_the_ranges_and_the_rows_of_the_dead_node() {
+        let storage_path = temp_path("node_replace_test");
+        let ip_a = Ipv4Addr::new(127, 0, 0, 75);
+        let dead_ip = Ipv4Addr::new(127, 0, 0, 76);
+        let replacement_ip = Ipv4Addr::new(127, 0, 0, 77);
+        let _coordinator_listener = TcpListener::bind((ip_a, INTERNODE_PORT)).unwrap();
+        let listener = TcpListener::bind((replacement_ip, INTERNODE_PORT)).unwrap();
+        let node_a = Arc::new(Mutex::new(
+            Node::new(ip_a, vec![ip_a], storage_path.join("a")).unwrap(),
+        ));
+        let dead = Arc::new(Mutex::new(
+            Node::new(dead_ip, vec![ip_a], storage_path.join("dead")).unwrap(),
+        ));
+        Node::join_cluster(&node_a, &[]).unwrap();
+        Node::join_cluster(&dead, std::slice::from_ref(&node_a)).unwrap();
+        let dead_token = node_a.lock().unwrap().partitioner.token_of(&dead_ip);
+        assert!(dead_token.is_some());
+
+        // Con dos nodos y replication_factor 2 los dos tienen todo el rango
+        run_query(
+            &node_a,
+            "CREATE KEYSPACE sky WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 2}",
+        )
+        .unwrap();
+        run_query(
+            &node_a,
+            "CREATE TABLE sky.flights (id INT, origin TEXT, PRIMARY KEY (id))",
+        )
+        .unwrap();
+        let table = {
+            let guard = node_a.lock().unwrap();
+            guard
+                .get_keyspace("sky")
+                .unwrap()
+                .unwrap()
+                .get_table("flights")
+                .unwrap()
+        };
+        node_a
+            .lock()
+            .unwrap()
+            .storage_engine()
+            .insert(
+                "sky",
+                "flights",
+                vec!["1", "EZE"],
+                table.get_columns(),
+                vec![],
+                false,
+                false,
+                10,
+            )
             .unwrap();
 
-        let socket = SocketAddrV4::new(self_ip, CLIENT_NODE_PORT); // Specific port for clients
-        let listener = TcpListener::bind(socket)?;
+        // El nodo muerto sale del anillo de A
+        {
+            let mut guard_a = node_a.lock().unwrap();
+            guard_a.gossiper.kill(dead_ip).unwrap();
+            assert!(guard_a.update_partitioner_from_gossiper().unwrap());
+            assert_eq!(guard_a.partitioner.get_nodes(), vec![ip_a]);
+        }
 
-        for stream in listener.incoming() {
-            match stream {
-                Ok(mut stream) => {
-                    // Crear una conexión TLS para el stream TCP
-                    let mut conn = ServerConnection::new(Arc::new(config.clone()))
-                        .expect("No se pudo crear la conexión TLS");
+        let replacement = Arc::new(Mutex::new(
+            Node::from_config(
+                NodeConfig::new(replacement_ip, vec![ip_a], storage_path.join("replacement"))
+                    .with_replaced_node(dead_ip),
+            )
+            .unwrap(),
+        ));
+        Node::join_cluster(&replacement, std::slice::from_ref(&node_a)).unwrap();
+
+        for node in [&node_a, &replacement] {
+            let guard = node.lock().unwrap();
+            assert_eq!(guard.partitioner.token_of(&replacement_ip), dead_token);
+            assert!(!guard.partitioner.contains_node(&dead_ip));
+            assert!(!guard.gossiper.endpoints_state.contains_key(&dead_ip));
+        }
 
-                    conn.complete_io(&mut stream).unwrap();
-                    let connections_clone = Arc::clone(&connections);
+        // A le manda al reemplazo las filas de sus rangos
+        let connections = Arc::new(Mutex::new(HashMap::new()));
+        Node::run_scheduled_repairs(&node_a, &connections).unwrap();
+        assert!(node_a.lock().unwrap().scheduled_repairs().is_empty());
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buffer = vec![0u8; 4096];
+        let read = stream.read(&mut buffer).unwrap();
+        let message = InternodeMessage::from_bytes(&buffer[..read]).unwrap();
+        InternodeProtocolHandler::new()
+            .handle_command(&replacement, message, connections)
+            .unwrap();
 
-                    let stream = StreamOwned::new(conn, stream);
+        let storage = replacement.lock().unwrap().storage_engine();
+        assert_eq!(
+            storage
+                .read_reconciled("sky", &table, &["1"], false)
+                .unwrap(),
+            Some("1,EZE;10".to_string())
+        );
+
+        // Al reiniciarse sin la configuracion de reemplazo conserva el token que tomo
+        drop(replacement);
+        let restarted =
+            Node::new(replacement_ip, vec![ip_a], storage_path.join("replacement")).unwrap();
+        assert_eq!(restarted.partitioner.token_of(&replacement_ip), dead_token);
+        assert_eq!(
+            restarted.gossiper.endpoints_state[&replacement_ip]
+                .application_state
+                .replaces,
+            Some(dead_ip)
+        );
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
 
-                    let node_clone = Arc::clone(&node);
-                    thread::spawn(move || {
-                        Node::handle_incoming_client_messages(node_clone, stream, connections_clone)
-                    });
-                }
-                Err(e) => {
-                    eprintln!("Error accepting client connection: {:?}", e);
-                }
+    #[test]
+    fn test_node_that_was_down_gets_the_tables_created_meanwhile() {
+        let storage_path = temp_path("node_ddl_test");
+        let ips = [
+            Ipv4Addr::new(127, 0, 0, 94),
+            Ipv4Addr::new(127, 0, 0, 95),
+            Ipv4Addr::new(127, 0, 0, 96),
+        ];
+        let nodes: Vec<Arc<Mutex<Node>>> = ips
+            .iter()
+            .map(|ip| {
+                let path = storage_path.join(ip.to_string());
+                Arc::new(Mutex::new(Node::new(*ip, vec![*ip], path).unwrap()))
+            })
+            .collect();
+        let (node_a, node_b, node_c) = (&nodes[0], &nodes[1], &nodes[2]);
+
+        Node::join_cluster(node_a, &[]).unwrap();
+        Node::join_cluster(node_b, &nodes[..1]).unwrap();
+        Node::join_cluster(node_c, &nodes[..2]).unwrap();
+
+        // C se cae: los demás no pueden hablar con él
+        for node in [node_a, node_b] {
+            node.lock().unwrap().gossiper.kill(ips[2]).unwrap();
+        }
+
+        // A y B atienden a los otros nodos, el DDL espera la respuesta de los dos
+        for (node, ip) in nodes.iter().zip(ips).take(2) {
+            let node = node.clone();
+            thread::spawn(move || {
+                Node::handle_node_connections(node, Arc::new(Mutex::new(HashMap::new())), ip)
+            });
+            while TcpStream::connect((ip, INTERNODE_PORT)).is_err() {
+                thread::sleep(Duration::from_millis(10));
             }
         }
 
-        Ok(())
+        run_query(
+            node_a,
+            "CREATE KEYSPACE sky WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 3}",
+        )
+        .unwrap();
+        run_query(
+            node_a,
+            "CREATE TABLE sky.flights (id INT, PRIMARY KEY (id))",
+        )
+        .unwrap();
+
+        let has_flights = |node: &Arc<Mutex<Node>>| {
+            node.lock()
+                .unwrap()
+                .get_keyspace("sky")
+                .unwrap()
+                .map(|keyspace| keyspace.get_table("flights").is_ok())
+                .unwrap_or(false)
+        };
+        // B aplico la tabla antes de que A respondiera, C no se espera
+        assert!(has_flights(node_b));
+        assert!(!has_flights(node_c));
+
+        // C vuelve y se pone al día con el schema en la siguiente ronda de gossip de B
+        Node::join_cluster(node_c, &nodes[1..2]).unwrap();
+        Node::gossip_with(node_b, &nodes[2..]).unwrap();
+        assert!(has_flights(node_c));
+        assert_eq!(
+            node_b.lock().unwrap().membership_snapshot().get(&ips[2]),
+            Some(&NodeStatus::Normal)
+        );
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_select_reports_the_type_of_each_selected_column() {
+        let storage_path = temp_path("node_select_types_test");
+        let node = node_with_table(local_config(&storage_path), "CREATE TABLE sky.flights (id INT, origin TEXT, delayed BOOLEAN, fuel FLOAT, PRIMARY KEY (id))");
+
+        run_query(
+            &node,
+            "INSERT INTO sky.flights (id, origin, delayed, fuel) VALUES (1, 'EZE', true, 10.5)",
+        )
+        .unwrap();
+
+        // Solo algunas columnas, en otro orden que el de la tabla
+        let frame = run_query(
+            &node,
+            "SELECT fuel, id, delayed FROM sky.flights WHERE id = 1",
+        );
+        let rows = match frame.unwrap() {
+            Frame::Result(result_::Result::Rows(rows)) => rows,
+            other => panic!("expected rows, got {:?}", other),
+        };
+        let col_types: Vec<(&str, &ColumnType)> = rows
+            .metadata
+            .col_spec_i
+            .iter()
+            .map(|spec| (spec.name.as_str(), &spec.type_))
+            .collect();
+        assert_eq!(
+            col_types,
+            vec![
+                ("fuel", &ColumnType::Float),
+                ("id", &ColumnType::Int),
+                ("delayed", &ColumnType::Boolean),
+            ]
+        );
+        assert_eq!(
+            rows.rows_content[0].get("fuel"),
+            Some(&ColumnValue::Float(10.5))
+        );
+        assert_eq!(
+            rows.rows_content[0].get("delayed"),
+            Some(&ColumnValue::Boolean(true))
+        );
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_select_returns_the_columns_in_the_order_of_the_projection() {
+        let storage_path = temp_path("node_select_order_test");
+        let node = node_with_table(
+            local_config(&storage_path),
+            "CREATE TABLE sky.flights (id INT, origin TEXT, fuel FLOAT, PRIMARY KEY (id))",
+        );
+
+        run_query(
+            &node,
+            "INSERT INTO sky.flights (id, origin, fuel) VALUES (1, 'EZE', 10.5)",
+        )
+        .unwrap();
+
+        // Ni el orden de la tabla ni el alfabetico
+        let frame = run_query(
+            &node,
+            "SELECT origin, id, fuel FROM sky.flights WHERE id = 1",
+        );
+        let rows = match frame.unwrap() {
+            Frame::Result(result_::Result::Rows(rows)) => rows,
+            other => panic!("expected rows, got {:?}", other),
+        };
+        let spec_names: Vec<&str> = rows
+            .metadata
+            .col_spec_i
+            .iter()
+            .map(|spec| spec.name.as_str())
+            .collect();
+        assert_eq!(spec_names, vec!["origin", "id", "fuel"]);
+
+        let row: Vec<(&str, &ColumnValue)> = rows.rows_content[0].iter().collect();
+        assert_eq!(
+            row,
+            vec![
+                ("origin", &ColumnValue::Ascii("EZE".to_string())),
+                ("id", &ColumnValue::Int(1)),
+                ("fuel", &ColumnValue::Float(10.5)),
+            ]
+        );
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_batch_applies_every_statement_and_answers_once() {
+        let storage_path = temp_path("node_batch_test");
+        let node = node_with_table(local_config(&storage_path), FLIGHTS);
+
+        let frame = run_query(
+            &node,
+            "BEGIN BATCH \
+             INSERT INTO sky.flights (id, origin) VALUES (1, 'EZE'); \
+             INSERT INTO sky.flights (id, origin) VALUES (2, 'AEP'); \
+             UPDATE sky.flights SET origin = 'MDZ' WHERE id = 1; \
+             APPLY BATCH",
+        )
+        .unwrap();
+        assert!(matches!(frame, Frame::Result(result_::Result::Void)));
+
+        for (id, origin) in [(1, "MDZ"), (2, "AEP")] {
+            let frame = run_query(
+                &node,
+                &format!("SELECT origin FROM sky.flights WHERE id = {}", id),
+            );
+            let rows = match frame.unwrap() {
+                Frame::Result(result_::Result::Rows(rows)) => rows,
+                other => panic!("expected rows, got {:?}", other),
+            };
+            assert_eq!(
+                rows.rows_content[0].get("origin"),
+                Some(&ColumnValue::Ascii(origin.to_string()))
+            );
+        }
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+
+    #[test]
+    fn test_partitioner_error_in_one_round_does_not_stop_the_gossip_thread() {
+        let storage_path = temp_path("node_gossip_rounds_test");
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        let node = Arc::new(Mutex::new(
+            Node::new(ip, vec![ip], storage_path.clone()).unwrap(),
+        ));
+        let connections = Arc::new(Mutex::new(HashMap::new()));
+        let logger = node.lock().unwrap().get_logger();
+        let heartbeat_of = |node: &Arc<Mutex<Node>>| {
+            node.lock().unwrap().gossiper.endpoints_state[&ip]
+                .heartbeat_state
+                .clone()
+        };
+        let initial_heartbeat = heartbeat_of(&node);
+
+        let round_node = Arc::clone(&node);
+        let handle = thread::spawn(move || {
+            let mut rounds = 0;
+            let error = Node::run_gossip_rounds(
+                || {
+                    rounds += 1;
+                    match rounds {
+                        1 => Err(NodeError::PartitionerError(
+                            partitioner::errors::PartitionerError::HashError,
+                        )),
+                        4 => Err(NodeError::LockError),
+                        _ => Node::gossip_round(&round_node, &connections, Instant::now()),
+                    }
+                },
+                &logger,
+                Duration::from_millis(0),
+            );
+            (rounds, error)
+        });
+
+        // Las rondas siguen después del error del particionador y solo un lock envenenado las corta
+        let (rounds, error) = handle.join().unwrap();
+        assert_eq!(rounds, 4);
+        assert!(matches!(error, NodeError::LockError));
+        // Las dos rondas completas avanzaron el heartbeat
+        let heartbeat = heartbeat_of(&node);
+        assert_eq!(heartbeat.generation, initial_heartbeat.generation);
+        assert_eq!(heartbeat.version, initial_heartbeat.version + 2);
+
+        std::fs::remove_dir_all(storage_path).ok();
     }
 
-    // Receives packets from the client
-    fn handle_incoming_client_messages(
-        node: Arc<Mutex<Node>>,
-        mut stream: StreamOwned<ServerConnection, TcpStream>,
-        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
-    ) -> Result<(), NodeError> {
-        // Clone the stream under Mutex protection and create the reader
+    #[test]
+    fn test_composite_partition_key_has_the_same_token_when_writing_and_reading() {
+        let storage_path = temp_path("node_partition_key_test");
+        let node = node_with_table(
+            local_config(&storage_path),
+            "CREATE TABLE sky.routes (origin TEXT, destination TEXT, day INT, PRIMARY KEY ((origin, destination), day))",
+        );
+
+        let table = {
+            let node_guard = node.lock().unwrap();
+            let keyspace = node_guard.get_keyspace("sky").unwrap().unwrap();
+            node_guard
+                .get_table("routes".to_string(), keyspace)
+                .unwrap()
+        };
+        let partition_keys = table.get_partition_keys().unwrap();
 
-        let client_id;
-        let log;
+        // Escritura: columnas en otro orden que el de la tabla
+        let insert = match QueryCreator::new()
+            .handle_query(
+                "INSERT INTO sky.routes (day, destination, origin) VALUES (3, 'MDZ', 'EZE')"
+                    .to_string(),
+            )
+            .unwrap()
+        {
+            Query::Insert(insert) => insert,
+            other => panic!("expected an insert, got {:?}", other),
+        };
+        let execution = QueryExecution::new(
+            node.clone(),
+            Arc::new(Mutex::new(HashMap::new())),
+            storage_path.clone(),
+        )
+        .unwrap();
+        let row = execution
+            .complete_row(
+                table.get_columns(),
+                insert.into_clause.columns.clone(),
+                insert.values.clone(),
+            )
+            .unwrap();
+        let written_key = QueryExecution::partition_key_values(&table.get_columns(), &row);
 
+        // Lectura: condiciones en otro orden que el de la clave
+        let select = match QueryCreator::new()
+            .handle_query(
+                "SELECT day FROM sky.routes WHERE destination = 'MDZ' AND origin = 'EZE' AND day = 3"
+                    .to_string(),
+            )
+            .unwrap()
         {
-            let mut guard_node = node.lock()?;
-            client_id = guard_node.generate_client_id();
-            log = guard_node.get_logger();
+            Query::Select(select) => select,
+            other => panic!("expected a select, got {:?}", other),
         };
+        let read_key = select
+            .where_clause
+            .unwrap()
+            .get_value_partitioner_key_condition(partition_keys)
+            .unwrap();
 
-        let mut is_authenticated = false;
+        assert_eq!(written_key, read_key);
+        assert_eq!(
+            Partitioner::partition_token(&written_key).unwrap(),
+            Partitioner::partition_token(&read_key).unwrap()
+        );
 
-        loop {
-            // Clean the buffer
+        std::fs::remove_dir_all(storage_path).ok();
+    }
 
-            let mut buffer = [0; 2048];
+    #[test]
+    fn test_failed_queries_report_specific_error_codes() {
+        let storage_path = temp_path("node_error_codes_test");
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        let node = Arc::new(Mutex::new(
+            Node::new(ip, vec![ip], storage_path.clone()).unwrap(),
+        ));
 
-            // Execute initial inserts if necessary
+        let parse_error = run_query(&node, "SELEC id FROM flights").unwrap_err();
+        assert_eq!(error::Error::from(&parse_error).code(), ErrorCode::Invalid);
 
-            // Try to read a line
-            let bytes_read = stream.read(&mut buffer);
+        // El cliente no eligio keyspace y la query no lo indica
+        let query = QueryCreator::new()
+            .handle_query("SELECT id FROM flights WHERE id = 1".to_string())
+            .unwrap();
+        let client_id = node.lock().unwrap().generate_client_id();
+        let missing_keyspace = check_keyspace(&node, &query, client_id, 1).unwrap_err();
+        match Frame::Error(error::Error::from(&missing_keyspace)) {
+            Frame::Error(error::Error::Invalid(message)) => {
+                assert_eq!(message, CQLError::NoActualKeyspaceError.to_string())
+            }
+            other => panic!("expected an invalid error, got {:?}", other),
+        }
 
-            match bytes_read {
-                Ok(0) => {
-                    // Connection closed
-                    break;
-                }
-                Ok(_) => {
-                    let request = handle_client_request(&buffer).unwrap();
+        let unreachable = error::Error::from(NodeError::InternodeError);
+        assert_eq!(unreachable.code(), ErrorCode::UnavailableException);
+        assert_eq!(
+            error::Error::from(NodeError::LockError).code(),
+            ErrorCode::ServerError
+        );
 
-                    match request {
-                        Request::Startup => {
-                            let auth = Frame::Authenticate(Authenticate::default()).to_bytes()?;
-                            stream.write(auth.as_slice())?;
-                            stream.flush()?;
-                        }
-                        Request::AuthResponse(password) => {
-                            let response = if password == "admin" {
-                                is_authenticated = true;
-                                Frame::AuthSuccess(AuthSuccess::default()).to_bytes()?
-                            } else {
-                                Frame::Authenticate(Authenticate::default()).to_bytes()?
-                            };
+        std::fs::remove_dir_all(storage_path).ok();
+    }
 
-                            stream.write(response.as_slice())?;
-                            stream.flush()?;
-                        }
-                        Request::Query(query) => {
-                            if !is_authenticated {
-                                let auth =
-                                    Frame::Authenticate(Authenticate::default()).to_bytes()?;
-                                stream.write(auth.as_slice())?;
-                                stream.flush()?;
-                                continue;
-                            }
-                            // Handle the query
-                            let query_str = query.get_query();
-                            let query_consistency_level: &str = &query.get_consistency();
-                            log.info(
-                                &format!(
-                                    "NATIVE: I RECEIVED {} whit CL: {} from CLIENT",
-                                    query_str.replace("\n", ""),
-                                    query_consistency_level,
-                                ),
-                                Color::Yellow,
-                                true,
-                            )?;
-
-                            let (tx_reply, rx_reply) = mpsc::channel();
-
-                            let result = Node::handle_query_execution(
-                                query_str,
-                                query_consistency_level,
-                                &node,
-                                connections.clone(),
-                                tx_reply,
-                                client_id,
-                            );
+    // Nodo con el keyspace `sky` de replication factor `replicas.len() + 1` que ve vivas a
+    // `replicas`, aunque no tienen el schema ni contestan por su cuenta
+    fn coordinator_with_replicas(
+        ip: Ipv4Addr,
+        replicas: &[Ipv4Addr],
+    ) -> (Arc<Mutex<Node>>, PathBuf) {
+        let storage_path = temp_path("node_replication_test");
+        let node = Arc::new(Mutex::new(
+            Node::new(ip, vec![ip], storage_path.clone()).unwrap(),
+        ));
+        run_query(
+            &node,
+            &format!(
+                "CREATE KEYSPACE sky WITH replication = {{'class': 'SimpleStrategy', 'replication_factor': {}}}",
+                replicas.len() + 1
+            ),
+        )
+        .unwrap();
+        run_query(
+            &node,
+            "CREATE TABLE sky.flights (id INT, origin TEXT, PRIMARY KEY (id))",
+        )
+        .unwrap();
+        {
+            let mut node = node.lock().unwrap();
+            let state = node.gossiper.endpoints_state[&ip].clone();
+            for replica in replicas {
+                node.partitioner.add_node(*replica).unwrap();
+                node.gossiper
+                    .endpoints_state
+                    .insert(*replica, state.clone());
+            }
+        }
+        (node, storage_path)
+    }
 
-                            if let Err(e) = result {
-                                let frame = Frame::Error(error::Error::ServerError(e.to_string()));
+    // Lee la escritura que le llega a una replica
+    fn accept_internode_query(listener: &TcpListener) -> InternodeMessage {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buffer = vec![0u8; 4096];
+        let read = stream.read(&mut buffer).unwrap();
+        InternodeMessage::from_bytes(&buffer[..read]).unwrap()
+    }
 
-                                let frame_bytes_result = &frame.to_bytes();
-                                let mut frame_bytes = &vec![];
-                                if let Ok(value) = frame_bytes_result {
-                                    frame_bytes = value;
-                                }
-                                stream.write(&frame_bytes)?;
-                                stream.flush()?;
-                            } else {
-                                // await resolution of the query
-                                let reply = rx_reply.recv().map_err(|_| NodeError::OtherError)?;
-                                stream.write(&reply.to_bytes()?)?;
-                            }
-                        }
-                    };
-                }
-                Err(_) => {
-                    // Another type of error
-                    return Err(NodeError::OtherError);
+    fn start_insert(node: &Arc<Mutex<Node>>) -> mpsc::Receiver<Frame> {
+        let client_id = node.lock().unwrap().generate_client_id();
+        let (tx_reply, rx_reply) = mpsc::channel();
+        Node::handle_query_execution(
+            "INSERT INTO sky.flights (id, origin) VALUES (1, 'EZE')",
+            "ONE",
+            node,
+            Arc::new(Mutex::new(HashMap::new())),
+            tx_reply,
+            client_id,
+        )
+        .unwrap();
+        rx_reply
+    }
+
+    #[test]
+    fn test_a_write_at_one_is_answered_before_the_other_replicas_acknowledge_it() {
+        let ip = Ipv4Addr::new(127, 0, 0, 59);
+        let replicas = [Ipv4Addr::new(127, 0, 0, 60), Ipv4Addr::new(127, 0, 0, 61)];
+        let listeners: Vec<TcpListener> = replicas
+            .iter()
+            .map(|replica| TcpListener::bind((*replica, INTERNODE_PORT)).unwrap())
+            .collect();
+        let (node, storage_path) = coordinator_with_replicas(ip, &replicas);
+        assert_eq!(
+            node.lock().unwrap().replication_mode(),
+            ReplicationMode::Async
+        );
+
+        // La escritura del coordinador alcanza para ONE, aunque las otras no contesten nunca
+        let rx_reply = start_insert(&node);
+        assert!(matches!(
+            rx_reply.recv_timeout(Duration::from_secs(2)),
+            Ok(Frame::Result(_))
+        ));
+
+        // Las otras dos replicas reciben la escritura igual
+        for listener in &listeners {
+            match accept_internode_query(listener).content {
+                InternodeMessageContent::Query(query) => {
+                    assert!(query.query_string.starts_with("INSERT INTO sky.flights"))
                 }
+                other => panic!("unexpected message {:?}", other),
             }
         }
 
-        Ok(())
+        std::fs::remove_dir_all(storage_path).ok();
     }
 
-    fn handle_incoming_internode_messages(
-        node: Arc<Mutex<Node>>,
-        stream: Arc<Mutex<TcpStream>>,
-        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
-    ) -> Result<(), NodeError> {
-        // Clone the stream under Mutex protection and create the reader
-        let mut reader = {
-            let stream_guard = stream.lock()?;
-            BufReader::new(stream_guard.try_clone().map_err(NodeError::IoError)?)
-        };
+    #[test]
+    fn test_a_synchronous_write_waits_for_every_replica() {
+        let ip = Ipv4Addr::new(127, 0, 0, 62);
+        let replicas = [Ipv4Addr::new(127, 0, 0, 63), Ipv4Addr::new(127, 0, 0, 64)];
+        let listeners: Vec<TcpListener> = replicas
+            .iter()
+            .map(|replica| TcpListener::bind((*replica, INTERNODE_PORT)).unwrap())
+            .collect();
+        let (node, storage_path) = coordinator_with_replicas(ip, &replicas);
+        node.lock()
+            .unwrap()
+            .set_replication_mode(ReplicationMode::Sync);
 
-        let internode_protocol_handler = InternodeProtocolHandler::new();
+        let rx_reply = start_insert(&node);
+        assert!(rx_reply.recv_timeout(Duration::from_millis(300)).is_err());
 
-        loop {
-            // Clean the buffer
-            let mut buffer = [0u8; 850000];
+        // Cada replica confirma la escritura
+        for (replica, listener) in replicas.iter().zip(&listeners) {
+            let InternodeMessageContent::Query(query) = accept_internode_query(listener).content
+            else {
+                panic!("expected the write");
+            };
+            let response =
+                InternodeResponse::new(query.open_query_id, InternodeResponseStatus::Ok, None);
+            InternodeProtocolHandler::new()
+                .handle_command(
+                    &node,
+                    InternodeMessage::new(*replica, InternodeMessageContent::Response(response)),
+                    Arc::new(Mutex::new(HashMap::new())),
+                )
+                .unwrap();
+        }
+        assert!(matches!(
+            rx_reply.recv_timeout(Duration::from_secs(2)),
+            Ok(Frame::Result(_))
+        ));
 
-            // Execute initial inserts if necessary
+        std::fs::remove_dir_all(storage_path).ok();
+    }
 
-            // Self::execute_querys(&node, connections.clone())?;
+    // Coordinador de un keyspace con factor de replicacion 1 y el id de una particion cuya unica
+    // replica acepta conexiones pero nunca contesta
+    fn coordinator_of_a_silent_replica(
+        ip: Ipv4Addr,
+        replica: Ipv4Addr,
+    ) -> (Arc<Mutex<Node>>, PathBuf, TcpListener, i32) {
+        let listener = TcpListener::bind((replica, INTERNODE_PORT)).unwrap();
+        let (node, storage_path) = coordinator_with_replicas(ip, &[]);
+        let id = {
+            let mut guard = node.lock().unwrap();
+            let state = guard.gossiper.endpoints_state[&ip].clone();
+            guard.partitioner.add_node(replica).unwrap();
+            guard.gossiper.endpoints_state.insert(replica, state);
+            guard.set_request_timeout(Duration::from_millis(200));
+            (1..)
+                .find(|id| {
+                    guard
+                        .partitioner
+                        .get_replicas(&[id.to_string()], 1)
+                        .unwrap()
+                        == vec![replica]
+                })
+                .unwrap()
+        };
+        (node, storage_path, listener, id)
+    }
 
-            // Try to read a line
-            let bytes_read = reader.read(&mut buffer);
-            let result = InternodeMessage::from_bytes(&buffer);
+    #[test]
+    fn test_a_write_whose_replicas_do_not_answer_times_out() {
+        let (node, storage_path, _listener, id) = coordinator_of_a_silent_replica(
+            Ipv4Addr::new(127, 0, 0, 97),
+            Ipv4Addr::new(127, 0, 0, 98),
+        );
+
+        let reply = Node::answer_client_query(
+            &format!(
+                "INSERT INTO sky.flights (id, origin) VALUES ({}, 'EZE')",
+                id
+            ),
+            "ONE",
+            &node,
+            Arc::new(Mutex::new(HashMap::new())),
+            0,
+        );
+
+        match reply {
+            Frame::Error(error) => assert_eq!(error.code(), ErrorCode::WriteTimeout),
+            other => panic!("expected a write timeout, got {:?}", other),
+        }
+        std::fs::remove_dir_all(storage_path).ok();
+    }
 
-            let message;
+    #[test]
+    fn test_a_read_whose_replicas_do_not_answer_times_out() {
+        let (node, storage_path, _listener, id) = coordinator_of_a_silent_replica(
+            Ipv4Addr::new(127, 0, 0, 99),
+            Ipv4Addr::new(127, 0, 0, 100),
+        );
+
+        let reply = Node::answer_client_query(
+            &format!("SELECT * FROM sky.flights WHERE id = {}", id),
+            "ONE",
+            &node,
+            Arc::new(Mutex::new(HashMap::new())),
+            0,
+        );
+
+        match reply {
+            Frame::Error(error) => assert_eq!(error.code(), ErrorCode::ReadTimeout),
+            other => panic!("expected a read timeout, got {:?}", other),
+        }
+        std::fs::remove_dir_all(storage_path).ok();
+    }
 
-            match result {
-                Ok(value) => {
-                    message = value;
-                }
-                Err(_) => {
-                    //println!("error al procesar mensaje internodo");
-                    // println!("Error al crear los bytes: {:?}", e);
+    #[test]
+    fn test_a_write_for_an_unreachable_replica_is_replayed_when_it_comes_back() {
+        let ip = Ipv4Addr::new(127, 0, 0, 65);
+        let replica = Ipv4Addr::new(127, 0, 0, 66);
+        let other_replica = Ipv4Addr::new(127, 0, 0, 67);
+        let _other_listener = TcpListener::bind((other_replica, INTERNODE_PORT)).unwrap();
+        let (node, storage_path) = coordinator_with_replicas(ip, &[replica, other_replica]);
+
+        // Nadie escucha en la primera replica: la escritura queda como hint
+        let rx_reply = start_insert(&node);
+        let reply = rx_reply.recv_timeout(Duration::from_secs(2));
+        assert!(matches!(reply, Ok(Frame::Result(_))), "{:?}", reply);
+        let started = Instant::now();
+        while node.lock().unwrap().pending_hints(replica) == 0 {
+            assert!(
+                started.elapsed() < Duration::from_secs(2),
+                "no hint was kept"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
 
-                    continue;
-                }
+        // Mientras la replica siga sin escuchar, el hint se guarda
+        let connections = Arc::new(Mutex::new(HashMap::new()));
+        Node::replay_hints(&node, &connections).unwrap();
+        assert_eq!(node.lock().unwrap().pending_hints(replica), 1);
+
+        let listener = TcpListener::bind((replica, INTERNODE_PORT)).unwrap();
+        Node::replay_hints(&node, &connections).unwrap();
+        assert_eq!(node.lock().unwrap().pending_hints(replica), 0);
+        match accept_internode_query(&listener).content {
+            InternodeMessageContent::Query(query) => {
+                assert!(query.query_string.starts_with("INSERT INTO sky.flights"))
             }
+            other => panic!("unexpected message {:?}", other),
+        }
 
-            match bytes_read {
-                Ok(0) => {
-                    // Connection closed
-                    break;
-                }
-                Ok(_) => {
-                    // Process the command with the protocol, passing the buffer and the necessary parameters
-                    let result = internode_protocol_handler.handle_command(
-                        &node,
-                        message.clone(),
-                        connections.clone(),
-                    );
+        std::fs::remove_dir_all(storage_path).ok();
+    }
 
-                    // If there's an error handling the command, exit the loop
-                    if let Err(e) = result {
-                        eprintln!("{:?} when other node sent me {:?}", e, message);
-                        break;
-                    }
-                }
-                Err(_) => {
-                    // Another type of error
-                    return Err(NodeError::OtherError);
-                }
-            }
+    #[test]
+    fn test_a_synchronous_write_for_an_unreachable_replica_is_kept_as_a_hint() {
+        let ip = Ipv4Addr::new(127, 0, 0, 80);
+        let replica = Ipv4Addr::new(127, 0, 0, 81);
+        let other_replica = Ipv4Addr::new(127, 0, 0, 82);
+        let _other_listener = TcpListener::bind((other_replica, INTERNODE_PORT)).unwrap();
+        let (node, storage_path) = coordinator_with_replicas(ip, &[replica, other_replica]);
+        node.lock()
+            .unwrap()
+            .set_replication_mode(ReplicationMode::Sync);
+
+        let _rx_reply = start_insert(&node);
+        let started = Instant::now();
+        while node.lock().unwrap().pending_hints(replica) == 0 {
+            assert!(
+                started.elapsed() < Duration::from_secs(2),
+                "no hint was kept"
+            );
+            thread::sleep(Duration::from_millis(10));
         }
+        assert_eq!(node.lock().unwrap().pending_hints(other_replica), 0);
 
-        Ok(())
+        std::fs::remove_dir_all(storage_path).ok();
     }
 
-    fn current_timestamp() -> i64 {
-        Utc::now().timestamp()
-    }
+    #[test]
+    fn test_a_write_at_any_succeeds_with_a_hint_when_every_replica_is_down() {
+        let ip = Ipv4Addr::new(127, 0, 0, 73);
+        let replica = Ipv4Addr::new(127, 0, 0, 74);
+        let (node, storage_path) = coordinator_with_replicas(ip, &[]);
+        // Con factor de replicacion 1, la unica replica de la particion es la que no escucha
+        let id = {
+            let mut guard = node.lock().unwrap();
+            let state = guard.gossiper.endpoints_state[&ip].clone();
+            guard.partitioner.add_node(replica).unwrap();
+            guard.gossiper.endpoints_state.insert(replica, state);
+            (1..)
+                .find(|id| {
+                    guard
+                        .partitioner
+                        .get_replicas(&[id.to_string()], 1)
+                        .unwrap()
+                        == vec![replica]
+                })
+                .unwrap()
+        };
 
-    fn handle_query_execution(
-        query_str: &str,
-        consistency_level: &str,
-        node: &Arc<Mutex<Node>>,
-        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
-        tx_reply: Sender<Frame>,
-        client_id: i32,
-    ) -> Result<(), NodeError> {
-        let query = QueryCreator::new()
-            .handle_query(query_str.to_string())
-            .map_err(NodeError::CQLError)?;
+        let insert = |consistency: &str| {
+            let client_id = node.lock().unwrap().generate_client_id();
+            let (tx_reply, rx_reply) = mpsc::channel();
+            Node::handle_query_execution(
+                &format!(
+                    "INSERT INTO sky.flights (id, origin) VALUES ({}, 'EZE')",
+                    id
+                ),
+                consistency,
+                &node,
+                Arc::new(Mutex::new(HashMap::new())),
+                tx_reply,
+                client_id,
+            )
+            .unwrap();
+            rx_reply.recv_timeout(Duration::from_secs(2))
+        };
 
-        if query.needs_keyspace() {
-            //println!("esta query: {:?} necesita un keyspace", query_str);
-            check_keyspace(node, &query, client_id, 6)?;
-        }
+        let reply = insert("ANY");
+        assert!(matches!(reply, Ok(Frame::Result(_))), "{:?}", reply);
+        assert_eq!(node.lock().unwrap().pending_hints(replica), 1);
 
-        if query.needs_table() {
-            //println!("esta query: {:?} necesita una tabla", query_str);
-            check_table(node, &query, client_id, 6)?;
-        }
+        // Con ONE el hint no alcanza: hace falta que conteste una replica
+        let reply = insert("ONE");
+        assert!(matches!(reply, Ok(Frame::Error(_))), "{:?}", reply);
+        assert_eq!(node.lock().unwrap().pending_hints(replica), 2);
 
-        let open_query_id;
-        let self_ip: Ipv4Addr;
-        let storage_path;
-        let logger;
+        // Sin lugar para otro hint, ANY no tiene quien se quede con la escritura
         {
-            let mut guard_node = node.lock()?;
-            let keyspace;
-            // Obtener el keyspace especificado o el actual del cliente
-            if let Some(keyspace_name) = query.get_used_keyspace() {
-                keyspace = guard_node.get_keyspace(&keyspace_name)?
-            } else {
-                keyspace = guard_node.get_client_keyspace(client_id)?;
+            let mut guard = node.lock().unwrap();
+            let hint = guard.hints.take(replica).remove(0);
+            while guard.hints.add(replica, hint.clone()) {}
+        }
+        match insert("ANY") {
+            Ok(Frame::Error(unavailable)) => {
+                assert_eq!(unavailable.code(), ErrorCode::UnavailableException)
             }
+            other => panic!("expected the write to be unavailable, got {:?}", other),
+        }
+        assert_eq!(
+            node.lock().unwrap().pending_hints(replica),
+            hinted_handoff::MAX_HINTS_PER_NODE
+        );
 
-            // Intentar obtener el nombre de la tabla y buscar la tabla correspondiente en el keyspace
-            let table = query.get_table_name().and_then(|table_name| {
-                keyspace
-                    .clone()
-                    .and_then(|k| guard_node.get_table(table_name, k).ok())
-            });
+        std::fs::remove_dir_all(storage_path).ok();
+    }
 
-            // Agregar la consulta abierta
-            open_query_id = guard_node.add_open_query(
-                query.clone(),
-                consistency_level,
-                tx_reply,
-                table,
-                keyspace,
-            )?;
-            self_ip = guard_node.get_ip();
-            storage_path = guard_node.storage_path.clone();
-            logger = guard_node.get_logger();
+    #[test]
+    fn test_a_replica_that_restarted_is_repaired_once_with_the_rows_it_owns() {
+        let ip = Ipv4Addr::new(127, 0, 0, 68);
+        let replica = Ipv4Addr::new(127, 0, 0, 69);
+        let listener = TcpListener::bind((replica, INTERNODE_PORT)).unwrap();
+        let (node, storage_path) = coordinator_with_replicas(ip, &[replica]);
+
+        let reply = start_insert(&node).recv_timeout(Duration::from_secs(2));
+        assert!(matches!(reply, Ok(Frame::Result(_))), "{:?}", reply);
+        accept_internode_query(&listener);
+
+        // La replica vuelve con una generacion nueva
+        let restarted = {
+            let state = &node.lock().unwrap().gossiper.endpoints_state[&replica];
+            let digest =
+                gossip::messages::Digest::new(replica, state.heartbeat_state.generation + 1, 1);
+            gossip::messages::Ack2::new(BTreeMap::from([(digest, state.application_state.clone())]))
+        };
+        {
+            let mut guard = node.lock().unwrap();
+            guard.gossiper.handle_ack2(&restarted);
+            guard.gossiper.handle_ack2(&restarted);
+            assert_eq!(guard.schedule_repairs_of_restarted_peers(), vec![replica]);
+            assert!(guard.schedule_repairs_of_restarted_peers().is_empty());
+            assert_eq!(guard.scheduled_repairs(), [replica]);
         }
-        let timestamp = Self::current_timestamp();
 
-        let response =
-            QueryExecution::new(node.clone(), connections.clone(), storage_path.clone())?.execute(
-                query.clone(),
+        let connections = Arc::new(Mutex::new(HashMap::new()));
+        Node::run_scheduled_repairs(&node, &connections).unwrap();
+        assert!(node.lock().unwrap().scheduled_repairs().is_empty());
+        match accept_internode_query(&listener).content {
+            InternodeMessageContent::Query(query) => {
+                assert!(query.query_string.starts_with("INSERT INTO sky.flights"))
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+
+        std::fs::remove_dir_all(storage_path).ok();
+    }
+    #[test]
+    fn test_a_restarted_replica_is_repaired_with_the_rows_deleted_meanwhile() {
+        let ip = Ipv4Addr::new(127, 0, 0, 89);
+        let replica = Ipv4Addr::new(127, 0, 0, 90);
+        let listener = TcpListener::bind((replica, INTERNODE_PORT)).unwrap();
+        let (node, storage_path) = coordinator_with_replicas(ip, &[replica]);
+
+        let (storage, table) = {
+            let guard = node.lock().unwrap();
+            let table = guard
+                .get_keyspace("sky")
+                .unwrap()
+                .unwrap()
+                .get_table("flights")
+                .unwrap();
+            (guard.storage_engine(), table)
+        };
+        storage
+            .insert(
+                "sky",
+                "flights",
+                vec!["1", "EZE"],
+                table.get_columns(),
+                vec![],
                 false,
                 false,
-                open_query_id,
-                client_id,
-                Some(timestamp),
-            )?;
-
-        if let Some(((finished_responses, failed_nodes), content)) = response {
-            let mut guard_node = node.lock()?;
-            // Obtener el keyspace especificado o el actual del cliente
-
-            let keyspace = guard_node
-                .get_open_handle_query()
-                .get_keyspace_of_query(open_query_id)?
-                .clone();
-
-            // Intentar obtener el nombre de la tabla y buscar la tabla correspondiente en el keyspace
-            let table = query.get_table_name().and_then(|table_name| {
-                keyspace
-                    .clone()
-                    .and_then(|k| guard_node.get_table(table_name, k).ok())
-            });
-            let columns: Vec<Column> = {
-                if let Some(table) = table.clone() {
-                    table.get_columns()
-                } else {
-                    vec![]
-                }
-            };
-
-            let keyspace_name: String = if let Some(key) = keyspace.clone() {
-                key.get_name()
-            } else {
-                "".to_string()
-            };
+                10,
+            )
+            .unwrap();
+        let delete = query_creator::clauses::delete_cql::Delete::deserialize(
+            "DELETE FROM sky.flights WHERE id = 1",
+        )
+        .unwrap();
+        storage.delete(delete, table, "sky", false, 20).unwrap();
+
+        node.lock().unwrap().scheduled_repairs.push(replica);
+        let connections = Arc::new(Mutex::new(HashMap::new()));
+        Node::run_scheduled_repairs(&node, &connections).unwrap();
+
+        // La replica recibe el borrado con su timestamp, no la fila que tenia antes
+        match accept_internode_query(&listener).content {
+            InternodeMessageContent::Query(query) => {
+                assert_eq!(query.query_string, "DELETE FROM sky.flights WHERE id = '1'");
+                assert_eq!(query.timestamp, 20);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
 
-            let partitioner = guard_node.get_partitioner();
-            let query_handler = guard_node.get_open_handle_query();
+        std::fs::remove_dir_all(storage_path).ok();
+    }
 
-            for _ in 0..finished_responses {
-                let mut select_columns: Vec<String> = vec![];
-                let mut values: Vec<Vec<String>> = vec![];
-                let mut complete_columns: Vec<String> = vec![];
-                if let Some(cont) = content.content.clone() {
-                    complete_columns = cont.columns.clone();
-                    select_columns = cont.select_columns.clone();
-                    values = cont.values.clone();
-                }
+    #[test]
+    fn test_a_null_column_is_repaired_as_null_and_not_as_text() {
+        use query_creator::clauses::insert_cql::NULL_VALUE;
+
+        let ip = Ipv4Addr::new(127, 0, 0, 101);
+        let replica = Ipv4Addr::new(127, 0, 0, 102);
+        let listener = TcpListener::bind((replica, INTERNODE_PORT)).unwrap();
+        let (node, storage_path) = coordinator_with_replicas(ip, &[replica]);
+
+        let (storage, table) = {
+            let guard = node.lock().unwrap();
+            let table = guard
+                .get_keyspace("sky")
+                .unwrap()
+                .unwrap()
+                .get_table("flights")
+                .unwrap();
+            (guard.storage_engine(), table)
+        };
+        storage
+            .insert(
+                "sky",
+                "flights",
+                vec!["1", NULL_VALUE],
+                table.get_columns(),
+                vec![],
+                false,
+                false,
+                10,
+            )
+            .unwrap();
 
-                InternodeProtocolHandler::add_ok_response_to_open_query_and_send_response_if_closed(
-                    query_handler,
-                    // TODO: convertir el content al content de la response
-                    &InternodeResponse::new(open_query_id as u32, InternodeResponseStatus::Ok, Some(InternodeResponseContent{
-                        columns: complete_columns,
-                        select_columns:  select_columns,
-                        values: values,
-                    })),
-                    open_query_id,
-                    keyspace_name.clone(),
-                    table.clone(),
-                    columns.clone(),
-                    self_ip,
-                    self_ip,
-                    connections.clone(),
-                    partitioner.clone(),
-                    storage_path.clone(),
-                    logger.clone(),
-                )?;
-            }
-            for _ in 0..failed_nodes {
-                InternodeProtocolHandler::add_error_response_to_open_query_and_send_response_if_closed(
-                    query_handler,
-                    open_query_id,
+        node.lock().unwrap().scheduled_repairs.push(replica);
+        let connections = Arc::new(Mutex::new(HashMap::new()));
+        Node::run_scheduled_repairs(&node, &connections).unwrap();
+        let repair = match accept_internode_query(&listener).content {
+            InternodeMessageContent::Query(query) => query.query_string,
+            other => panic!("unexpected message {:?}", other),
+        };
 
-                )?;
+        // La replica aplica el insert que recibio y lee la columna como null
+        let replica_path = temp_path("node_null_repair_test");
+        let repaired = node_with_table(local_config(&replica_path), FLIGHTS);
+        run_query(&repaired, &repair).unwrap();
+        match run_query(&repaired, "SELECT origin FROM sky.flights WHERE id = 1") {
+            Ok(Frame::Result(result_::Result::Rows(rows))) => {
+                assert_eq!(rows.rows_content[0].get("origin"), Some(&ColumnValue::Null))
             }
+            other => panic!("expected the repaired row, got {:?}", other),
         }
 
-        Ok(())
+        std::fs::remove_dir_all(storage_path).ok();
+        std::fs::remove_dir_all(replica_path).ok();
     }
 }
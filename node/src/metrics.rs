@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crate::errors::NodeError;
+use crate::storage_engine::metrics::{StorageMetrics, StorageMetricsSnapshot};
+
+/// Read and write counters of a single keyspace.
+#[derive(Debug, Default)]
+struct KeyspaceCounters {
+    reads: AtomicU64,
+    writes: AtomicU64,
+}
+
+/// Runtime counters of a node.
+///
+/// Global counters are plain atomics, so they can be incremented from any thread
+/// without taking the node lock. Per keyspace counters live behind a `RwLock` that
+/// is only written the first time a keyspace is seen.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    queries_received: AtomicU64,
+    queries_failed: AtomicU64,
+    internode_messages: AtomicU64,
+    internode_checksum_failures: AtomicU64,
+    gossip_rounds: AtomicU64,
+    storage: StorageMetrics,
+    keyspaces: RwLock<HashMap<String, Arc<KeyspaceCounters>>>,
+}
+
+/// Reads and writes received for a keyspace, as seen in a `MetricsSnapshot`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct KeyspaceMetrics {
+    pub reads: u64,
+    pub writes: u64,
+}
+
+/// Point in time copy of the values of `Metrics`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetricsSnapshot {
+    pub queries_received: u64,
+    pub queries_failed: u64,
+    pub internode_messages: u64,
+    pub internode_checksum_failures: u64,
+    pub gossip_rounds: u64,
+    pub storage: StorageMetricsSnapshot,
+    pub keyspaces: HashMap<String, KeyspaceMetrics>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a query received from a client.
+    pub fn record_query_received(&self) {
+        self.queries_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a client query that ended in an error.
+    pub fn record_query_failed(&self) {
+        self.queries_failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a message received from another node.
+    pub fn record_internode_message(&self) {
+        self.internode_messages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a message from another node that arrived corrupted.
+    pub fn record_internode_checksum_failure(&self) {
+        self.internode_checksum_failures
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a completed iteration of the gossip loop.
+    pub fn record_gossip_round(&self) {
+        self.gossip_rounds.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a read (`SELECT`) on `keyspace`.
+    pub fn record_read(&self, keyspace: &str) -> Result<(), NodeError> {
+        self.keyspace_counters(keyspace)?
+            .reads
+            .fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Counts a write (`INSERT`, `UPDATE` or `DELETE`) on `keyspace`.
+    pub fn record_write(&self, keyspace: &str) -> Result<(), NodeError> {
+        self.keyspace_counters(keyspace)?
+            .writes
+            .fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Counters shared by the storage engines of the node, to pass to `StorageEngine::with_metrics`.
+    pub fn storage(&self) -> StorageMetrics {
+        self.storage.clone()
+    }
+
+    /// Returns the current value of every counter.
+    pub fn snapshot(&self) -> Result<MetricsSnapshot, NodeError> {
+        let keyspaces = self
+            .keyspaces
+            .read()?
+            .iter()
+            .map(|(name, counters)| {
+                (
+                    name.clone(),
+                    KeyspaceMetrics {
+                        reads: counters.reads.load(Ordering::Relaxed),
+                        writes: counters.writes.load(Ordering::Relaxed),
+                    },
+                )
+            })
+            .collect();
+
+        Ok(MetricsSnapshot {
+            queries_received: self.queries_received.load(Ordering::Relaxed),
+            queries_failed: self.queries_failed.load(Ordering::Relaxed),
+            internode_messages: self.internode_messages.load(Ordering::Relaxed),
+            internode_checksum_failures: self.internode_checksum_failures.load(Ordering::Relaxed),
+            gossip_rounds: self.gossip_rounds.load(Ordering::Relaxed),
+            storage: self.storage.snapshot(),
+            keyspaces,
+        })
+    }
+
+    fn keyspace_counters(&self, keyspace: &str) -> Result<Arc<KeyspaceCounters>, NodeError> {
+        if let Some(counters) = self.keyspaces.read()?.get(keyspace) {
+            return Ok(Arc::clone(counters));
+        }
+        let mut keyspaces = self.keyspaces.write()?;
+        Ok(Arc::clone(
+            keyspaces.entry(keyspace.to_string()).or_default(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_metrics_start_at_zero() {
+        let snapshot = Metrics::new().snapshot().unwrap();
+        assert_eq!(snapshot, MetricsSnapshot::default());
+    }
+
+    #[test]
+    fn test_counters_are_incremented() {
+        let metrics = Metrics::new();
+        metrics.record_query_received();
+        metrics.record_query_received();
+        metrics.record_query_failed();
+        metrics.record_internode_message();
+        metrics.record_internode_checksum_failure();
+        metrics.record_gossip_round();
+        metrics.record_read("flights").unwrap();
+        metrics.record_write("flights").unwrap();
+        metrics.record_write("flights").unwrap();
+        metrics.record_write("airports").unwrap();
+
+        let snapshot = metrics.snapshot().unwrap();
+        assert_eq!(snapshot.queries_received, 2);
+        assert_eq!(snapshot.queries_failed, 1);
+        assert_eq!(snapshot.internode_messages, 1);
+        assert_eq!(snapshot.internode_checksum_failures, 1);
+        assert_eq!(snapshot.gossip_rounds, 1);
+        assert_eq!(
+            snapshot.keyspaces.get("flights"),
+            Some(&KeyspaceMetrics {
+                reads: 1,
+                writes: 2
+            })
+        );
+        assert_eq!(
+            snapshot.keyspaces.get("airports"),
+            Some(&KeyspaceMetrics {
+                reads: 0,
+                writes: 1
+            })
+        );
+    }
+}
@@ -3,13 +3,11 @@ use crate::internode_protocol::response::InternodeResponse;
 use gossip::structures::application_state::{KeyspaceSchema, TableSchema};
 use native_protocol::frame::Frame;
 use query_creator::Query;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::net::Ipv4Addr;
 use std::sync::mpsc::Sender;
 
-#[derive(Debug, PartialEq)]
-
 /// Represents the consistency levels available for queries in a distributed database.
 ///
 /// # Purpose
@@ -39,12 +37,21 @@ use std::sync::mpsc::Sender;
 ///   - The operation is considered successful only if all replicas respond.
 ///   - Provides the highest level of consistency but sacrifices availability and increases latency.
 ///   - Typically used when strict consistency is critical.
+/// - `LocalQuorum`
+///   - Like `Quorum`, but only the replicas in the datacenter of the coordinator are counted,
+///     so the answer never waits on another datacenter.
+/// - `LocalOne`
+///   - The operation is considered successful once one replica in the datacenter of the
+///     coordinator responds.
+/// - `EachQuorum`
+///   - The operation is considered successful once a quorum of the replicas of every
+///     datacenter responds.
 ///
 /// # Usage
 /// - The choice of consistency level depends on the application's requirements for consistency, availability, and latency.
 /// - Lower consistency levels (`Any`, `One`) prioritize availability and performance.
 /// - Higher consistency levels (`Quorum`, `All`) prioritize strict consistency but may reduce availability in case of node failures.
-
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ConsistencyLevel {
     Any,
     One,
@@ -52,6 +59,9 @@ pub enum ConsistencyLevel {
     Three,
     Quorum,
     All,
+    LocalQuorum,
+    LocalOne,
+    EachQuorum,
 }
 
 impl ConsistencyLevel {
@@ -60,7 +70,8 @@ impl ConsistencyLevel {
     /// # Arguments
     /// - `s: &str`
     ///   - The string representation of the consistency level.
-    ///     Valid values are `"any"`, `"one"`, `"two"`, `"three"`, `"quorum"`, and `"all"`.
+    ///     Valid values are `"any"`, `"one"`, `"two"`, `"three"`, `"quorum"`, `"all"`,
+    ///     `"local_quorum"`, `"local_one"` and `"each_quorum"`.
     ///
     /// # Returns
     /// - A `ConsistencyLevel` corresponding to the input string.
@@ -76,6 +87,9 @@ impl ConsistencyLevel {
             "three" => ConsistencyLevel::Three,
             "quorum" => ConsistencyLevel::Quorum,
             "all" => ConsistencyLevel::All,
+            "local_quorum" => ConsistencyLevel::LocalQuorum,
+            "local_one" => ConsistencyLevel::LocalOne,
+            "each_quorum" => ConsistencyLevel::EachQuorum,
             _ => ConsistencyLevel::All,
         }
     }
@@ -98,6 +112,9 @@ impl ConsistencyLevel {
     ///   - `Two`, `Three`: Requires two and three responses, respectively.
     ///   - `Quorum`: Requires more than half of the required responses.
     ///   - `All`: Requires all responses.
+    ///   - `LocalQuorum`, `LocalOne`: Like `Quorum` and `One`, where the responses are only
+    ///     those of the replicas in the local datacenter.
+    ///   - `EachQuorum`: Like `Quorum`, checked for the replicas of each datacenter.
     pub fn is_query_ready(&self, responses_received: usize, responses_needed: usize) -> bool {
         match self {
             ConsistencyLevel::Any => responses_received >= 1,
@@ -106,6 +123,9 @@ impl ConsistencyLevel {
             ConsistencyLevel::Three => responses_received >= 3,
             ConsistencyLevel::Quorum => responses_received >= (responses_needed / 2 + 1),
             ConsistencyLevel::All => responses_received >= responses_needed,
+            ConsistencyLevel::LocalQuorum => responses_received >= (responses_needed / 2 + 1),
+            ConsistencyLevel::LocalOne => responses_received >= 1,
+            ConsistencyLevel::EachQuorum => responses_received >= (responses_needed / 2 + 1),
         }
     }
 
@@ -124,6 +144,9 @@ impl ConsistencyLevel {
     ///   - `Two`, `Three`: Requires two and three responses, respectively.
     ///   - `Quorum`: Requires more than half of the required responses.
     ///   - `All`: Requires all responses.
+    ///   - `LocalQuorum`, `LocalOne`: Like `Quorum` and `One`, counting only the replicas in the
+    ///     local datacenter.
+    ///   - `EachQuorum`: Like `Quorum`, for the replicas of each datacenter.
     pub fn required_oks(&self, responses_needed: usize) -> usize {
         match self {
             ConsistencyLevel::Any => 1,
@@ -132,8 +155,19 @@ impl ConsistencyLevel {
             ConsistencyLevel::Three => 3,
             ConsistencyLevel::Quorum => responses_needed / 2 + 1,
             ConsistencyLevel::All => responses_needed,
+            ConsistencyLevel::LocalQuorum => responses_needed / 2 + 1,
+            ConsistencyLevel::LocalOne => 1,
+            ConsistencyLevel::EachQuorum => responses_needed / 2 + 1,
         }
     }
+
+    /// Whether only the replicas in the datacenter of the coordinator count for this level.
+    pub fn is_datacenter_local(&self) -> bool {
+        matches!(
+            self,
+            ConsistencyLevel::LocalQuorum | ConsistencyLevel::LocalOne
+        )
+    }
 }
 
 /// Represents an open query being processed in the distributed database system.
@@ -169,6 +203,12 @@ impl ConsistencyLevel {
 /// - `table: Option<TableSchema>`
 ///   - An optional schema of the table associated with the query.
 ///   - Used to validate and process the query's structure and data.
+/// - `local_replicas: Option<HashSet<Ipv4Addr>>`
+///   - For `LOCAL_*` levels, the replicas in the datacenter of the coordinator, once known.
+///   - Only their `OK`s are counted in `local_ok_responses` to close the query.
+/// - `replica_datacenters: Option<HashMap<Ipv4Addr, String>>`
+///   - For `EACH_QUORUM`, the datacenter of each replica of the query, once known.
+///   - The query closes once the replicas of every datacenter reach a quorum.
 ///
 /// # Usage
 /// - `OpenQuery` is created when a new query is initiated by a client.
@@ -201,6 +241,9 @@ pub struct OpenQuery {
     query: Query,
     consistency_level: ConsistencyLevel,
     table: Option<TableSchema>,
+    local_replicas: Option<HashSet<Ipv4Addr>>,
+    local_ok_responses: i32,
+    replica_datacenters: Option<HashMap<Ipv4Addr, String>>,
 }
 
 impl OpenQuery {
@@ -220,18 +263,62 @@ impl OpenQuery {
             query,
             consistency_level: ConsistencyLevel::from_str(consistencty),
             table,
+            local_replicas: None,
+            local_ok_responses: 0,
+            replica_datacenters: None,
         }
     }
 
+    /// The consistency level the client asked for.
+    pub fn get_consistency_level(&self) -> ConsistencyLevel {
+        self.consistency_level
+    }
+
     // Adds a response to the query and increments the count of actual responses.
     //
     // # Parameters
     // - `response`: The response to be added.
     fn add_ok_response(&mut self, response: InternodeResponse, from: Ipv4Addr) {
+        if self
+            .local_replicas
+            .as_ref()
+            .is_some_and(|local| local.contains(&from))
+        {
+            self.local_ok_responses += 1;
+        }
         self.acumulated_ok_responses.push((from, response));
         self.ok_responses += 1;
     }
 
+    /// Sets which of the replicas of the query are in the datacenter of the coordinator.
+    ///
+    /// Only has an effect on `LOCAL_*` levels: from then on the query closes with the `OK`s of
+    /// those replicas, and the other ones are still collected (e.g. for read repair) but never
+    /// waited for.
+    pub fn set_local_replicas(&mut self, local_replicas: HashSet<Ipv4Addr>) {
+        if !self.consistency_level.is_datacenter_local() {
+            return;
+        }
+        self.local_ok_responses = self
+            .acumulated_ok_responses
+            .iter()
+            .filter(|(from, _)| local_replicas.contains(from))
+            .count() as i32;
+        self.local_replicas = Some(local_replicas);
+    }
+
+    /// Sets the datacenter of each replica of the query.
+    ///
+    /// Only has an effect on `EACH_QUORUM`: from then on the query closes once a quorum of the
+    /// replicas of every datacenter answered `OK`, and fails as soon as one datacenter cannot
+    /// reach its quorum.
+    pub fn set_replica_datacenters(&mut self, replica_datacenters: HashMap<Ipv4Addr, String>) {
+        if self.consistency_level != ConsistencyLevel::EachQuorum {
+            return;
+        }
+        self.replica_datacenters = Some(replica_datacenters);
+    }
+
     // Adds a response to the query and increments the count of actual responses.
     //
     // # Parameters
@@ -240,11 +327,27 @@ impl OpenQuery {
         self.error_responses += 1;
     }
 
+    /// Makes the query wait for an answer from each of `nodes` nodes, whatever the consistency
+    /// level the client asked for. A full scan needs them all, since each node answers only
+    /// for the partitions it owns.
+    pub fn wait_for_every_node(&mut self, nodes: usize) {
+        self.needed_responses = nodes as i32;
+        self.consistency_level = ConsistencyLevel::All;
+        self.local_replicas = None;
+        self.replica_datacenters = None;
+    }
+
     // Checks if the query has received all needed responses.
     //
     // # Returns
     /// `true` if the query is closed (i.e., all responses have been received), `false` otherwise.
     fn is_close(&self) -> bool {
+        if let Some(local_replicas) = &self.local_replicas {
+            return self.is_local_close(local_replicas.len() as i32);
+        }
+        if let Some(replica_datacenters) = &self.replica_datacenters {
+            return self.is_each_quorum_close(replica_datacenters);
+        }
         self.consistency_level
             .is_query_ready(self.ok_responses as usize, self.needed_responses as usize)
             || !self.can_still_achieve_required_ok(
@@ -255,6 +358,58 @@ impl OpenQuery {
             )
     }
 
+    // Los errores no dicen de que nodo vienen: solo se da por perdida la query cuando ni
+    // suponiendo que todos los errores son de otros datacenters alcanzan las replicas locales
+    fn is_local_close(&self, local_needed: i32) -> bool {
+        let required_ok = self.consistency_level.required_oks(local_needed as usize) as i32;
+        if self
+            .consistency_level
+            .is_query_ready(self.local_ok_responses as usize, local_needed as usize)
+        {
+            return true;
+        }
+
+        let remote_replicas = self.needed_responses - local_needed;
+        let local_errors = (self.error_responses - remote_replicas).max(0);
+        let all_answered = self.ok_responses + self.error_responses >= self.needed_responses;
+        all_answered || !self.can_still_achieve_required_ok(local_needed, local_errors, required_ok)
+    }
+
+    // Igual que en `is_local_close`, un datacenter solo se da por perdido cuando ni suponiendo
+    // que los errores son de los otros datacenters le alcanzan sus replicas para el quorum
+    fn is_each_quorum_close(&self, replica_datacenters: &HashMap<Ipv4Addr, String>) -> bool {
+        let mut replicas_per_datacenter: HashMap<&str, i32> = HashMap::new();
+        for datacenter in replica_datacenters.values() {
+            *replicas_per_datacenter.entry(datacenter).or_default() += 1;
+        }
+        let mut oks_per_datacenter: HashMap<&str, i32> = HashMap::new();
+        for (from, _) in &self.acumulated_ok_responses {
+            if let Some(datacenter) = replica_datacenters.get(from) {
+                *oks_per_datacenter.entry(datacenter).or_default() += 1;
+            }
+        }
+
+        let every_quorum = replicas_per_datacenter
+            .iter()
+            .all(|(datacenter, &replicas)| {
+                let oks = oks_per_datacenter.get(datacenter).copied().unwrap_or(0);
+                self.consistency_level
+                    .is_query_ready(oks as usize, replicas as usize)
+            });
+        if every_quorum {
+            return true;
+        }
+
+        let all_answered = self.ok_responses + self.error_responses >= self.needed_responses;
+        let some_quorum_lost = replicas_per_datacenter.values().any(|&replicas| {
+            let remote_replicas = self.needed_responses - replicas;
+            let errors = (self.error_responses - remote_replicas).max(0);
+            let required_ok = self.consistency_level.required_oks(replicas as usize) as i32;
+            !self.can_still_achieve_required_ok(replicas, errors, required_ok)
+        });
+        all_answered || some_quorum_lost
+    }
+
     fn can_still_achieve_required_ok(
         &self,
         total_responses: i32,
@@ -419,6 +574,19 @@ impl OpenQueryHandler {
         }
     }
 
+    /// Returns how many queries are still waiting for responses. Gossiped as part of the
+    /// node load so coordinators can prefer less busy replicas.
+    pub fn open_queries(&self) -> usize {
+        self.queries.len()
+    }
+
+    /// Removes and returns the query with the given ID without answering it, e.g. because it
+    /// failed before any replica was asked.
+    pub fn remove_query(&mut self, open_query_id: i32) -> Option<OpenQuery> {
+        self.keyspaces_queries.remove(&open_query_id);
+        self.queries.remove(&open_query_id)
+    }
+
     /// Creates and registers a new open query with a unique ID.
     ///
     /// # Purpose
@@ -778,6 +946,32 @@ impl OpenQueryHandler {
         &mut self,
         open_query_id: i32,
     ) -> Option<OpenQuery> {
+        self.add_error_response_to_query(open_query_id)
+    }
+
+    /// Records that the write of the query for a replica that could not be reached was kept
+    /// as a hint, and removes and returns the query if it closed with it.
+    ///
+    /// Under `ANY` a stored hint acknowledges the write like an `OK` does, so a write succeeds
+    /// even if no replica is up. Under every other level it counts as an error response.
+    pub fn add_hinted_response_and_get_if_closed(
+        &mut self,
+        open_query_id: i32,
+    ) -> Option<OpenQuery> {
+        let query = self.get_query_mut(&open_query_id)?;
+        if query.consistency_level != ConsistencyLevel::Any {
+            return self.add_error_response_to_query(open_query_id);
+        }
+
+        query.ok_responses += 1;
+        if query.is_close() {
+            self.queries.remove(&open_query_id)
+        } else {
+            None
+        }
+    }
+
+    fn add_error_response_to_query(&mut self, open_query_id: i32) -> Option<OpenQuery> {
         match self.get_query_mut(&open_query_id) {
             Some(query) => {
                 query.add_error_response();
@@ -810,3 +1004,122 @@ impl fmt::Display for OpenQueryHandler {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internode_protocol::response::InternodeResponseStatus;
+    use query_creator::QueryCreator;
+    use std::sync::mpsc;
+
+    const DC1: [Ipv4Addr; 2] = [Ipv4Addr::new(10, 0, 1, 1), Ipv4Addr::new(10, 0, 1, 2)];
+    const DC2: [Ipv4Addr; 2] = [Ipv4Addr::new(10, 0, 2, 1), Ipv4Addr::new(10, 0, 2, 2)];
+
+    fn ok_response(open_query_id: i32) -> InternodeResponse {
+        InternodeResponse::new(open_query_id as u32, InternodeResponseStatus::Ok, None)
+    }
+
+    // Query con las cuatro replicas, dos en cada datacenter, coordinada desde DC1
+    fn open_query_in_two_datacenters(handler: &mut OpenQueryHandler, consistency: &str) -> i32 {
+        let (tx_reply, _) = mpsc::channel();
+        let query = QueryCreator::new()
+            .handle_query("SELECT id FROM flights WHERE id = 1".to_string())
+            .unwrap();
+        let id = handler.new_open_query(4, tx_reply, query, consistency, None, None);
+        handler
+            .get_query_mut(&id)
+            .unwrap()
+            .set_local_replicas(HashSet::from(DC1));
+        id
+    }
+
+    #[test]
+    fn test_local_quorum_is_satisfied_by_the_local_replicas_only() {
+        let mut handler = OpenQueryHandler::new();
+        let id = open_query_in_two_datacenters(&mut handler, "LOCAL_QUORUM");
+
+        // Las respuestas del otro datacenter no cuentan para el nivel
+        for ip in DC2 {
+            assert!(handler
+                .add_ok_response_and_get_if_closed(id, ok_response(id), ip)
+                .is_none());
+        }
+        assert!(handler
+            .add_ok_response_and_get_if_closed(id, ok_response(id), DC1[0])
+            .is_none());
+
+        let closed = handler
+            .add_ok_response_and_get_if_closed(id, ok_response(id), DC1[1])
+            .unwrap();
+        assert_eq!(closed.get_acumulated_responses().len(), 4);
+    }
+
+    #[test]
+    fn test_local_quorum_does_not_wait_on_the_remote_datacenter() {
+        let mut handler = OpenQueryHandler::new();
+        let id = open_query_in_two_datacenters(&mut handler, "LOCAL_QUORUM");
+
+        // Los errores pueden ser del otro datacenter, que no hace falta
+        for _ in DC2 {
+            assert!(handler.add_error_response_and_get_if_closed(id).is_none());
+        }
+        assert!(handler
+            .add_ok_response_and_get_if_closed(id, ok_response(id), DC1[0])
+            .is_none());
+        let closed = handler
+            .add_ok_response_and_get_if_closed(id, ok_response(id), DC1[1])
+            .unwrap();
+        assert_eq!(
+            closed.get_consistency_level(),
+            ConsistencyLevel::LocalQuorum
+        );
+
+        // Con LOCAL_ONE alcanza la primera replica local, sin escuchar al otro datacenter
+        let id = open_query_in_two_datacenters(&mut handler, "LOCAL_ONE");
+        assert!(handler
+            .add_ok_response_and_get_if_closed(id, ok_response(id), DC1[1])
+            .is_some());
+    }
+
+    #[test]
+    fn test_each_quorum_needs_a_quorum_in_every_datacenter() {
+        let mut handler = OpenQueryHandler::new();
+        let id = open_query_in_two_datacenters(&mut handler, "EACH_QUORUM");
+        let datacenters = DC1
+            .iter()
+            .map(|ip| (*ip, "DC1".to_string()))
+            .chain(DC2.iter().map(|ip| (*ip, "DC2".to_string())))
+            .collect();
+        handler
+            .get_query_mut(&id)
+            .unwrap()
+            .set_replica_datacenters(datacenters);
+
+        // Con las dos replicas de DC1 hay quorum global, pero no en DC2
+        for ip in DC1.into_iter().chain([DC2[0]]) {
+            assert!(handler
+                .add_ok_response_and_get_if_closed(id, ok_response(id), ip)
+                .is_none());
+        }
+        let closed = handler
+            .add_ok_response_and_get_if_closed(id, ok_response(id), DC2[1])
+            .unwrap();
+        assert_eq!(closed.get_consistency_level(), ConsistencyLevel::EachQuorum);
+    }
+
+    #[test]
+    fn test_local_replicas_do_not_change_global_levels() {
+        let mut handler = OpenQueryHandler::new();
+        let id = open_query_in_two_datacenters(&mut handler, "QUORUM");
+
+        assert!(handler
+            .add_ok_response_and_get_if_closed(id, ok_response(id), DC1[0])
+            .is_none());
+        assert!(handler
+            .add_ok_response_and_get_if_closed(id, ok_response(id), DC1[1])
+            .is_none());
+        assert!(handler
+            .add_ok_response_and_get_if_closed(id, ok_response(id), DC2[0])
+            .is_some());
+    }
+}
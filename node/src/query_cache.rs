@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Default amount of results a node keeps in its query cache.
+pub const DEFAULT_QUERY_CACHE_CAPACITY: usize = 256;
+
+/// Default time a cached result is served before the table is read again.
+pub const DEFAULT_QUERY_CACHE_TTL: Duration = Duration::from_millis(500);
+
+/// Identifies a read served by the cache: the same `SELECT` on the same table.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QueryCacheKey {
+    pub keyspace: String,
+    pub table: String,
+    /// The `SELECT` serialized after its columns were resolved, so `SELECT *` and the list of
+    /// every column share an entry.
+    pub query: String,
+}
+
+struct CachedRows {
+    rows: Vec<String>,
+    stored_at: Instant,
+}
+
+/// Bounded cache of the rows returned by the local reads of a node.
+///
+/// Entries expire after `ttl` and are dropped as soon as the node writes to their table. A read
+/// takes a `generation` before going to disk and only stores its rows if no write happened in
+/// between, so a result read while a write was in progress is never kept.
+pub struct QueryCache {
+    capacity: usize,
+    ttl: Duration,
+    entries: HashMap<QueryCacheKey, CachedRows>,
+    // Se incrementa con cada invalidacion
+    generation: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl QueryCache {
+    /// Creates an empty cache that holds at most `capacity` results (at least one), each for `ttl`.
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        QueryCache {
+            capacity: capacity.max(1),
+            ttl,
+            entries: HashMap::new(),
+            generation: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Returns the rows cached for `key`, unless they are missing or expired.
+    pub fn get(&mut self, key: &QueryCacheKey) -> Option<Vec<String>> {
+        let ttl = self.ttl;
+        match self.entries.get(key) {
+            Some(cached) if cached.stored_at.elapsed() < ttl => {
+                self.hits += 1;
+                Some(cached.rows.clone())
+            }
+            Some(_) => {
+                self.entries.remove(key);
+                self.misses += 1;
+                None
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Returns the current generation, to be passed to `insert` once the read finishes.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Stores the rows of a read that started at `generation`.
+    ///
+    /// The rows are discarded if the cache was invalidated since then. When the cache is full
+    /// the oldest entry makes room for the new one.
+    pub fn insert(&mut self, key: QueryCacheKey, rows: Vec<String>, generation: u64) {
+        if generation != self.generation {
+            return;
+        }
+
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, cached)| cached.stored_at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.entries.insert(
+            key,
+            CachedRows {
+                rows,
+                stored_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Drops every result of `keyspace.table`. Called after each write to the table.
+    pub fn invalidate_table(&mut self, keyspace: &str, table: &str) {
+        self.generation += 1;
+        self.entries
+            .retain(|key, _| key.keyspace != keyspace || key.table != table);
+    }
+
+    /// Drops every result, e.g. after the schema or the ring changed.
+    pub fn clear(&mut self) {
+        self.generation += 1;
+        self.entries.clear();
+    }
+
+    /// Amount of reads answered from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Amount of reads that had to go to disk.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn key(table: &str, query: &str) -> QueryCacheKey {
+        QueryCacheKey {
+            keyspace: "sky".to_string(),
+            table: table.to_string(),
+            query: query.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_cached_rows_are_served_until_they_expire() {
+        let mut cache = QueryCache::new(4, Duration::from_millis(50));
+        let generation = cache.generation();
+        cache.insert(key("flights", "q"), vec!["1,EZE".to_string()], generation);
+
+        assert_eq!(
+            cache.get(&key("flights", "q")),
+            Some(vec!["1,EZE".to_string()])
+        );
+        assert_eq!(cache.get(&key("flights", "other")), None);
+
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(cache.get(&key("flights", "q")), None);
+        assert!(cache.is_empty());
+        assert_eq!((cache.hits(), cache.misses()), (1, 2));
+    }
+
+    #[test]
+    fn test_invalidating_a_table_only_drops_its_entries() {
+        let mut cache = QueryCache::new(4, Duration::from_secs(60));
+        let generation = cache.generation();
+        cache.insert(key("flights", "q"), vec![], generation);
+        cache.insert(key("airports", "q"), vec![], generation);
+
+        cache.invalidate_table("sky", "flights");
+
+        assert_eq!(cache.get(&key("flights", "q")), None);
+        assert_eq!(cache.get(&key("airports", "q")), Some(vec![]));
+    }
+
+    #[test]
+    fn test_a_read_that_overlaps_a_write_is_not_cached() {
+        let mut cache = QueryCache::new(4, Duration::from_secs(60));
+        let generation = cache.generation();
+        cache.invalidate_table("sky", "flights");
+
+        cache.insert(key("flights", "q"), vec!["1,EZE".to_string()], generation);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_the_oldest_entry_is_evicted_when_full() {
+        let mut cache = QueryCache::new(2, Duration::from_secs(60));
+        for query in ["a", "b", "c"] {
+            let generation = cache.generation();
+            cache.insert(key("flights", query), vec![], generation);
+            thread::sleep(Duration::from_millis(2));
+        }
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&key("flights", "a")), None);
+        assert!(cache.get(&key("flights", "c")).is_some());
+    }
+}
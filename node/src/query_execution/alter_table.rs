@@ -1,18 +1,35 @@
 // Ordered imports
+use crate::storage_engine::errors::StorageEngineError;
 use crate::NodeError;
 use query_creator::clauses::table::alter_table_cql::AlterTable;
 use query_creator::clauses::types::alter_table_op::AlterTableOperation;
 use query_creator::errors::CQLError;
+use std::collections::HashMap;
 
 use super::QueryExecution;
 
 /// Executes the alteration of a table. This function is public only for internal use
 /// within the library (defined as `pub(crate)`).
 impl QueryExecution {
+    /// Applies the operations of `alter_table` to the table, and forwards them to the other live
+    /// nodes if the query came from a client.
+    ///
+    /// A type change is checked first against the rows this node stores, and the whole ALTER is
+    /// rejected with `CQLError::IncompatibleValues` if any of them does not fit the new type.
+    ///
+    /// # Limitations
+    /// The check only sees the rows of the node that runs it. Each replica checks its own rows
+    /// when the forwarded ALTER reaches it and refuses the change if it finds incompatible ones,
+    /// so the client gets the error, but by then the coordinator and the replicas that accepted
+    /// it already use the new type, and the gossiped schema takes it to the others without any
+    /// check. There is no cluster-wide dry run: before narrowing a type, the rows of every
+    /// replica have to be known to fit it.
     pub(crate) fn execute_alter_table(
         &mut self,
         alter_table: AlterTable,
+        internode: bool,
         open_query_id: i32,
+        client_id: i32,
     ) -> Result<(), NodeError> {
         let mut node = self
             .node_that_execute
@@ -31,32 +48,72 @@ impl QueryExecution {
             .get_table(table_name.clone(), client_keyspace.clone())?
             .inner;
 
-        // Apply each alteration operation
-        for operation in alter_table.get_operations() {
+        let keyspace_name = client_keyspace.get_name();
+        let operations = alter_table.get_operations();
+
+        // Nombre de cada columna renombrada en los archivos, que no cambian hasta el final
+        let mut names_in_files: HashMap<String, String> = HashMap::new();
+
+        // Dry run: every operation is checked on the copy of the table before any file changes
+        for operation in &operations {
             match operation {
-                AlterTableOperation::AddColumn(column) => {
-                    table.add_column(column.clone())?;
-                    self.storage_engine.add_column_to_table(
-                        &client_keyspace.get_name(),
+                AlterTableOperation::AddColumn(column) => table.add_column(column.clone())?,
+                AlterTableOperation::DropColumn(column_name) => table.remove_column(column_name)?,
+                AlterTableOperation::ModifyColumn(column_name, new_data_type, allows_null) => {
+                    table.modify_column(column_name, *new_data_type, *allows_null)?;
+
+                    let name_in_files = names_in_files
+                        .get(column_name)
+                        .unwrap_or(column_name)
+                        .clone();
+                    let rows = self.storage_engine.rows_incompatible_with(
+                        &keyspace_name,
                         &table_name,
-                        &column.name,
+                        &name_in_files,
+                        *new_data_type,
+                        *allows_null,
                     )?;
+                    if !rows.is_empty() {
+                        return Err(NodeError::CQLError(CQLError::IncompatibleValues {
+                            column: column_name.clone(),
+                            data_type: new_data_type.to_string().to_string(),
+                            rows,
+                        }));
+                    }
+                }
+                AlterTableOperation::RenameColumn(old_name, new_name) => {
+                    table.rename_column(old_name, new_name)?;
+                    let name_in_files = names_in_files.remove(old_name).unwrap_or(old_name.clone());
+                    names_in_files.insert(new_name.clone(), name_in_files);
+                }
+            }
+        }
+
+        // The values are stored as text, so only the structural changes touch the files
+        for operation in operations {
+            match operation {
+                AlterTableOperation::AddColumn(column) => {
+                    self.storage_engine
+                        .add_column_to_table(&keyspace_name, &table_name, &column.name)
+                        .map_err(|e| match e {
+                            // Los archivos ya tienen la columna aunque el schema no
+                            StorageEngineError::UnsupportedOperation => {
+                                NodeError::CQLError(CQLError::ColumnAlreadyExist)
+                            }
+                            e => NodeError::from(e),
+                        })?;
                 }
                 AlterTableOperation::DropColumn(column_name) => {
-                    table.remove_column(&column_name)?;
                     self.storage_engine.remove_column_from_table(
-                        &client_keyspace.get_name(),
+                        &keyspace_name,
                         &table_name,
                         &column_name,
                     )?;
                 }
-                AlterTableOperation::ModifyColumn(_column_name, _new_data_type, _allows_null) => {
-                    return Err(NodeError::CQLError(CQLError::InvalidSyntax));
-                }
+                AlterTableOperation::ModifyColumn(..) => {}
                 AlterTableOperation::RenameColumn(old_name, new_name) => {
-                    table.rename_column(&old_name, &new_name)?;
                     self.storage_engine.rename_column_from_table(
-                        &client_keyspace.get_name(),
+                        &keyspace_name,
                         &table_name,
                         &old_name,
                         &new_name,
@@ -66,9 +123,24 @@ impl QueryExecution {
         }
 
         // Save the updated table structure to the node
-        node.update_table(&client_keyspace.get_name(), table)?;
+        node.update_table(&keyspace_name, table)?;
+        drop(node);
 
         self.execution_finished_itself = true;
+
+        if !internode {
+            let forwarded = AlterTable::new(
+                table_name,
+                keyspace_name.clone(),
+                alter_table.get_operations(),
+            );
+            self.how_many_nodes_failed += self.send_to_live_nodes(
+                &forwarded.serialize(),
+                open_query_id,
+                client_id,
+                &keyspace_name,
+            )?;
+        }
         Ok(())
     }
 }
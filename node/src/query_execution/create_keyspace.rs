@@ -10,22 +10,36 @@ impl QueryExecution {
     pub(crate) fn execute_create_keyspace(
         &mut self,
         create_keyspace: CreateKeyspace,
+        internode: bool,
+        open_query_id: i32,
+        client_id: i32,
     ) -> Result<(), NodeError> {
-        // Locks the node to ensure safe concurrent access
+        {
+            // Locks the node to ensure safe concurrent access
+            let mut node = self
+                .node_that_execute
+                .lock()
+                .map_err(|_| NodeError::LockError)?;
 
-        let mut node = self
-            .node_that_execute
-            .lock()
-            .map_err(|_| NodeError::LockError)?;
-
-        // Adds the keyspace to the node
-        if let Err(e) = node.add_keyspace(create_keyspace.clone()) {
-            if !create_keyspace.if_not_exists_clause {
-                return Err(e);
+            // Adds the keyspace to the node
+            if let Err(e) = node.add_keyspace(create_keyspace.clone()) {
+                if !create_keyspace.if_not_exists_clause {
+                    return Err(e);
+                }
             }
         }
 
         self.execution_finished_itself = true;
+
+        if !internode {
+            // Los demas nodos pueden haber recibido el keyspace por gossip antes que la consulta
+            let forwarded = CreateKeyspace {
+                if_not_exists_clause: true,
+                ..create_keyspace
+            };
+            self.how_many_nodes_failed +=
+                self.send_to_live_nodes(&forwarded.serialize(), open_query_id, client_id, "None")?;
+        }
         Ok(())
     }
 }
@@ -12,32 +12,52 @@ impl QueryExecution {
     pub(crate) fn execute_create_table(
         &mut self,
         create_table: CreateTable,
+        internode: bool,
         open_query_id: i32,
+        client_id: i32,
     ) -> Result<(), NodeError> {
-        // Add the table to the node
-        let mut node = self
-            .node_that_execute
-            .lock()
-            .map_err(|_| NodeError::LockError)?;
-
-        let client_keyspace = node
-            .get_open_handle_query()
-            .get_keyspace_of_query(open_query_id)?
-            .ok_or(NodeError::CQLError(CQLError::NoActualKeyspaceError))?;
-
-        if let Err(e) = node.add_table(create_table.clone(), &client_keyspace.get_name()) {
-            if !create_table.get_if_not_exists_clause() {
-                return Err(e);
+        let keyspace_name = {
+            // Add the table to the node
+            let mut node = self
+                .node_that_execute
+                .lock()
+                .map_err(|_| NodeError::LockError)?;
+
+            let client_keyspace = node
+                .get_open_handle_query()
+                .get_keyspace_of_query(open_query_id)?
+                .ok_or(NodeError::CQLError(CQLError::NoActualKeyspaceError))?;
+
+            if let Err(e) = node.add_table(create_table.clone(), &client_keyspace.get_name()) {
+                if !create_table.get_if_not_exists_clause() {
+                    return Err(e);
+                }
             }
-        }
 
-        node.get_open_handle_query().update_table_in_keyspace(
-            &client_keyspace.get_name(),
-            TableSchema::new(create_table.clone()),
-        )?;
+            node.get_open_handle_query().update_table_in_keyspace(
+                &client_keyspace.get_name(),
+                TableSchema::new(create_table.clone()),
+            )?;
+            client_keyspace.get_name()
+        };
 
         self.execution_finished_itself = true;
 
+        if !internode {
+            // Los demas nodos pueden haber recibido la tabla por gossip antes que la consulta
+            let forwarded = CreateTable {
+                keyspace_used_name: keyspace_name.clone(),
+                if_not_exists_clause: true,
+                ..create_table
+            };
+            self.how_many_nodes_failed += self.send_to_live_nodes(
+                &forwarded.serialize(),
+                open_query_id,
+                client_id,
+                &keyspace_name,
+            )?;
+        }
+
         Ok(())
     }
 }
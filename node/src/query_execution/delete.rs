@@ -63,10 +63,16 @@ impl QueryExecution {
             )?;
 
             // Determine the node responsible for deletion based on hashed partition key values
-            let value_to_hash = where_clause
-                .get_value_partitioner_key_condition(partition_keys)?
-                .join("");
-            let node_to_delete = node.partitioner.get_ip(value_to_hash.clone())?;
+            let partition_key = where_clause.get_value_partitioner_key_condition(partition_keys)?;
+            let node_to_delete = node.partitioner.get_ip_for_partition(&partition_key)?;
+            if !internode {
+                let replicas = node.partitioner.get_replicas(
+                    &partition_key,
+                    client_keyspace.get_replication_factor() as usize,
+                )?;
+                node.set_local_replicas_of_query(open_query_id, &replicas)?;
+                node.apply_replication_mode_to_query(open_query_id, &replicas);
+            }
             let self_ip = node.get_ip().clone();
             let logger = node.get_logger();
             // Forward the DELETE operation if the responsible node is different and not an internode operation
@@ -119,13 +125,20 @@ impl QueryExecution {
             self.execution_replicate_itself = true;
         }
 
-        self.storage_engine.delete(
+        let conditional = delete_query.if_clause.is_some() || delete_query.if_exist;
+        let table_name = table.get_name();
+        let applied = self.storage_engine.delete(
             delete_query,
             table,
             &client_keyspace.get_name(),
             replication,
             timestamp,
         )?;
+        self.invalidate_cached_reads(&client_keyspace.get_name(), &table_name)?;
+
+        if conditional {
+            self.applied = Some(applied);
+        }
         Ok(())
     }
 }
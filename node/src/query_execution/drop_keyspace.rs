@@ -10,19 +10,29 @@ impl QueryExecution {
     pub(crate) fn execute_drop_keyspace(
         &mut self,
         drop_keyspace: DropKeyspace,
+        internode: bool,
+        open_query_id: i32,
+        client_id: i32,
     ) -> Result<(), NodeError> {
         // Get the name of the keyspace to delete
         let keyspace_name = drop_keyspace.get_name().clone();
 
         // Lock the node and remove the keyspace from the internal structure
-        let mut node = self
-            .node_that_execute
+        self.node_that_execute
             .lock()
-            .map_err(|_| NodeError::LockError)?;
-
-        node.remove_keyspace(keyspace_name.clone())?;
+            .map_err(|_| NodeError::LockError)?
+            .remove_keyspace(keyspace_name.clone())?;
 
         self.execution_finished_itself = true;
+
+        if !internode {
+            self.how_many_nodes_failed += self.send_to_live_nodes(
+                &drop_keyspace.serialize(),
+                open_query_id,
+                client_id,
+                "None",
+            )?;
+        }
         Ok(())
     }
 }
@@ -9,21 +9,41 @@ impl QueryExecution {
     pub(crate) fn execute_drop_table(
         &mut self,
         drop_table: DropTable,
+        internode: bool,
         open_query_id: i32,
+        client_id: i32,
     ) -> Result<(), NodeError> {
-        let mut node = self
-            .node_that_execute
-            .lock()
-            .map_err(|_| NodeError::LockError)?;
-
         // Get the name of the table to delete
         let table_name = drop_table.get_table_name();
 
-        // Lock the node and remove the table from the internal list
-        node.remove_table(table_name.clone(), open_query_id)?;
+        let keyspace_name = {
+            let mut node = self
+                .node_that_execute
+                .lock()
+                .map_err(|_| NodeError::LockError)?;
+
+            let keyspace_name = node
+                .get_open_handle_query()
+                .get_keyspace_of_query(open_query_id)?
+                .ok_or(NodeError::KeyspaceError)?
+                .get_name();
+
+            // Lock the node and remove the table from the internal list
+            node.remove_table(table_name.clone(), open_query_id)?;
+            keyspace_name
+        };
 
         self.execution_finished_itself = true;
 
+        if !internode {
+            self.how_many_nodes_failed += self.send_to_live_nodes(
+                &format!("DROP TABLE {}.{}", keyspace_name, table_name),
+                open_query_id,
+                client_id,
+                &keyspace_name,
+            )?;
+        }
+
         Ok(())
     }
 }
@@ -0,0 +1,100 @@
+// Ordered imports
+use gossip::structures::application_state::KeyspaceSchema;
+use query_creator::clauses::explain_cql::Explain;
+use std::net::Ipv4Addr;
+
+use super::QueryExecution;
+use crate::NodeError;
+
+impl QueryExecution {
+    /// Builds the plan that would be followed to run the `SELECT` wrapped by an `EXPLAIN`,
+    /// without touching the storage or contacting other nodes.
+    ///
+    /// # Parameters
+    /// - `explain`: The parsed `EXPLAIN` query.
+    /// - `keyspace`: The keyspace the `SELECT` runs in.
+    ///
+    /// # Returns
+    /// One line per aspect of the plan:
+    /// - `table`: the keyspace and table that would be read.
+    /// - `access`: `point read` when the whole primary key is fixed with `=`,
+    ///   `partition slice` when only the partition key is, and `full scan` otherwise.
+    /// - `partition key`: the values used to locate the partition, if any.
+    /// - `index`: whether the clustering index of the table can narrow the read.
+    /// - `replicas`: the nodes that would be contacted, according to the partitioner.
+    ///
+    /// # Errors
+    /// - `NodeError::CQLError` if the table does not exist in the keyspace.
+    /// - `NodeError::PartitionerError` if the replicas cannot be computed.
+    pub(crate) fn explain(
+        &self,
+        explain: &Explain,
+        keyspace: &KeyspaceSchema,
+    ) -> Result<Vec<String>, NodeError> {
+        let node = self.node_that_execute.lock()?;
+        let select = &explain.select;
+        let table = node.get_table(select.table_name.clone(), keyspace.clone())?;
+
+        let partition_keys = table.get_partition_keys()?;
+        let clustering_columns = table.get_clustering_column_in_order();
+
+        // Solo se puede ubicar la particion si todas las partition keys estan fijadas con `=`
+        let partition_values = select.where_clause.as_ref().and_then(|where_clause| {
+            where_clause
+                .get_value_partitioner_key_condition(partition_keys.clone())
+                .ok()
+                .filter(|values| values.len() == partition_keys.len())
+        });
+
+        let clustering_values: Vec<Option<String>> = match &select.where_clause {
+            Some(where_clause) => clustering_columns
+                .iter()
+                .map(|column| where_clause.get_value_for_clustering_column(column))
+                .collect(),
+            None => vec![None; clustering_columns.len()],
+        };
+
+        let mut plan = vec![format!(
+            "table: {}.{}",
+            keyspace.get_name(),
+            table.get_name()
+        )];
+
+        let replicas: Vec<Ipv4Addr> = match &partition_values {
+            Some(values) => {
+                if clustering_values.iter().all(Option::is_some) {
+                    plan.push("access: point read".to_string());
+                } else {
+                    plan.push("access: partition slice".to_string());
+                }
+
+                let conditions: Vec<String> = partition_keys
+                    .iter()
+                    .zip(values)
+                    .map(|(key, value)| format!("{} = {}", key, value))
+                    .collect();
+                plan.push(format!("partition key: {}", conditions.join(" AND ")));
+
+                node.partitioner
+                    .get_replicas(values, keyspace.get_replication_factor() as usize)?
+            }
+            None => {
+                plan.push("access: full scan".to_string());
+                plan.push("partition key: not restricted".to_string());
+                node.partitioner.get_nodes()
+            }
+        };
+
+        match (clustering_columns.first(), clustering_values.first()) {
+            (Some(column), Some(Some(value))) => {
+                plan.push(format!("index: clustering index on {} = {}", column, value))
+            }
+            _ => plan.push("index: none".to_string()),
+        }
+
+        let replicas: Vec<String> = replicas.iter().map(|ip| ip.to_string()).collect();
+        plan.push(format!("replicas: {}", replicas.join(", ")));
+
+        Ok(plan)
+    }
+}
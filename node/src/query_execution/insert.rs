@@ -1,8 +1,10 @@
 // Ordered imports
 // use crate::table::Table;
+use crate::internode_protocol::query::InternodeQuery;
+use crate::storage_engine::escape_value;
 use crate::NodeError;
 use gossip::structures::application_state::TableSchema;
-use query_creator::clauses::insert_cql::Insert;
+use query_creator::clauses::insert_cql::{unescape_literal, Insert, NULL_VALUE};
 use query_creator::clauses::types::column::Column;
 use query_creator::errors::CQLError;
 use uuid;
@@ -22,8 +24,6 @@ impl QueryExecution {
         client_id: i32,
         timestap: i64,
     ) -> Result<(), NodeError> {
-        let mut failed_nodes = 0;
-        let mut internode_failed_nodes = 0;
         let mut node = self.node_that_execute.lock()?;
 
         let mut do_in_this_node = true;
@@ -34,7 +34,7 @@ impl QueryExecution {
             .ok_or(NodeError::CQLError(CQLError::NoActualKeyspaceError))?;
 
         if !node.table_already_exist(table_to_insert.get_name(), client_keyspace.get_name())? {
-            return Err(NodeError::CQLError(CQLError::TableAlreadyExist));
+            return Err(NodeError::CQLError(CQLError::InvalidTable));
         }
 
         // Retrieve columns and the partition keys
@@ -73,13 +73,6 @@ impl QueryExecution {
         // Clone values from the insert query
         let mut values = insert_query.values.clone();
 
-        // Concatenate the partition key column values to generate the hash
-        let value_to_hash = keys_index
-            .iter()
-            .map(|&index| values[index].clone())
-            .collect::<Vec<String>>()
-            .join("");
-
         // Validate and complete row values
         values = self.complete_row(
             columns.clone(),
@@ -87,83 +80,104 @@ impl QueryExecution {
             values,
         )?;
 
+        // The partition key is taken from the completed row, where values follow the schema
+        let partition_key = Self::partition_key_values(&columns, &values);
+
+        // The replicas get the values of the completed row, so they all store the same uuid()
         let mut new_insert = insert_query.clone();
-        let new_values: Vec<String> = values.iter().filter(|v| !v.is_empty()).cloned().collect();
-        new_insert.values = new_values;
+        new_insert.values = new_insert
+            .into_clause
+            .columns
+            .iter()
+            .filter_map(|name| columns.iter().position(|column| &column.name == name))
+            .map(|i| values[i].clone())
+            .collect();
         self.validate_values(columns.clone(), &values)?;
 
-        // Deterclient_keyspacemine the node responsible for the insert
-        let node_to_insert = node.get_partitioner().get_ip(value_to_hash.clone())?;
-        let self_ip = node.get_ip().clone();
+        // The owner of the partition comes first, followed by the nodes that keep its replicas
+        let replicas = node.get_partitioner().get_replicas(
+            &partition_key,
+            client_keyspace.get_replication_factor() as usize,
+        )?;
+        let self_ip = node.get_ip();
         let keyspace_name = client_keyspace.get_name();
         let logger = node.get_logger();
-        // If not internode and the target IP differs, forward the insert
         if !internode {
-            if node_to_insert != self_ip {
-                let serialized_insert = new_insert.serialize();
-                failed_nodes = self.send_to_single_node(
-                    node.get_ip(),
-                    node_to_insert,
-                    &serialized_insert,
-                    open_query_id,
-                    client_id,
-                    &client_keyspace.get_name(),
-                    timestap,
-                    logger.clone(),
-                )?;
-                do_in_this_node = false; // The actual insert will be done by another node
-            } else {
-                self.execution_finished_itself = true; // Insert will be done by this node
-            }
-
-            // Send the insert to replication nodes
-            let serialized_insert = new_insert.serialize();
-            (internode_failed_nodes, replication) = self.send_to_replication_nodes(
-                node,
-                node_to_insert,
-                &serialized_insert,
-                open_query_id,
-                client_id,
-                &client_keyspace.get_name(),
-                timestap,
-                logger,
-            )?;
-            if replication {
-                self.execution_replicate_itself = true; // This node will replicate the insert
-            }
+            node.set_local_replicas_of_query(open_query_id, &replicas)?;
+            node.apply_replication_mode_to_query(open_query_id, &replicas);
         }
+        drop(node);
 
-        failed_nodes += internode_failed_nodes;
-        self.how_many_nodes_failed = failed_nodes;
+        // The coordinator sends the insert to every replica and writes it here only if it is one
+        if !internode {
+            let query = InternodeQuery {
+                query_string: new_insert.serialize(),
+                open_query_id: open_query_id as u32,
+                client_id: client_id as u32,
+                replication: false,
+                keyspace_name: keyspace_name.clone(),
+                timestamp: timestap,
+            };
+            let (failed_nodes, is_owner, is_replica) =
+                self.send_to_replicas(self_ip, &replicas, query, logger)?;
+            self.how_many_nodes_failed = failed_nodes;
+
+            do_in_this_node = is_owner;
+            replication = is_replica;
+            self.execution_finished_itself = is_owner;
+            self.execution_replicate_itself = is_replica;
+        }
 
-        // If the node itself is the target and no further replication is required, finish here
+        // If this node is not one of the replicas, the others do the insert
         if !do_in_this_node && !replication {
             return Ok(());
         }
 
-        // If this node is responsible for the insert, execute it here
+        // If this node is a replica, insert it in the folder that corresponds
         keys_index.extend(&clustering_columns_index);
 
-        self.storage_engine.insert(
+        // Los valores se guardan escapados, asi un texto con `,` o un salto de linea no parte la fila
+        let stored_values: Vec<String> = values.iter().map(|value| escape_value(value)).collect();
+        let applied = self.storage_engine.insert(
             &keyspace_name,
             &insert_query.into_clause.table_name,
-            values.iter().map(|s| s.as_str()).collect(),
+            stored_values.iter().map(|s| s.as_str()).collect(),
             columns,
             table_to_insert.get_clustering_column_in_order(),
             replication,
             insert_query.if_not_exists,
             timestap,
         )?;
+        self.invalidate_cached_reads(&keyspace_name, &insert_query.into_clause.table_name)?;
+
+        if insert_query.if_not_exists {
+            self.applied = Some(applied);
+        }
         Ok(())
     }
 
-    fn complete_row(
+    /// Values of the partition key columns of a complete row, in the order of the schema, as the
+    /// texts they hold, so a literal kept escaped in the insert hashes as it is read.
+    pub(crate) fn partition_key_values(columns: &[Column], row: &[String]) -> Vec<String> {
+        columns
+            .iter()
+            .zip(row)
+            .filter(|(column, _)| column.is_partition_key)
+            .map(|(_, value)| unescape_literal(value).to_string())
+            .collect()
+    }
+
+    /// Puts the `values` of `specified_columns` in the order of `columns`.
+    ///
+    /// The columns left out of the insert are stored as NULL. Every partition and clustering
+    /// column must be given a value that is not NULL.
+    pub(crate) fn complete_row(
         &self,
         columns: Vec<Column>,
         specified_columns: Vec<String>,
         values: Vec<String>,
     ) -> Result<Vec<String>, NodeError> {
-        let mut complete_row = vec!["".to_string(); columns.len()];
+        let mut complete_row = vec![NULL_VALUE.to_string(); columns.len()];
         let mut specified_keys = 0;
 
         for (i, column) in columns.iter().enumerate() {
@@ -178,7 +192,7 @@ impl QueryExecution {
                 complete_row[i] = value.clone();
 
                 // Incrementar contador de claves especificadas si es clave de partición o clustering
-                if column.is_partition_key || column.is_clustering_column {
+                if (column.is_partition_key || column.is_clustering_column) && value != NULL_VALUE {
                     specified_keys += 1;
                 }
             }
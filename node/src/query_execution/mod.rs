@@ -1,12 +1,16 @@
+use crate::bounded_queue::{BoundedQueue, OverflowPolicy};
+use crate::hinted_handoff::{PendingWrite, PendingWrites, ReplicationMode};
 use crate::internode_protocol::message::{InternodeMessage, InternodeMessageContent};
 use crate::internode_protocol::query::InternodeQuery;
 use crate::internode_protocol::response::{
-    InternodeResponse, InternodeResponseContent, InternodeResponseStatus,
+    InternodeErrorDetail, InternodeResponse, InternodeResponseContent, InternodeResponseStatus,
 };
+use crate::internode_protocol_handler::InternodeProtocolHandler;
 use crate::utils::connect_and_send_message;
+use crate::Node;
 use crate::NodeError;
-use crate::{Node, INTERNODE_PORT};
 use logger::{Color, Logger};
+use query_creator::clauses::insert_cql::NULL_VALUE;
 use query_creator::clauses::types::column::Column;
 
 pub mod alter_keyspace;
@@ -16,17 +20,21 @@ pub mod create_table;
 pub mod delete;
 pub mod drop_keyspace;
 pub mod drop_table;
+pub mod explain;
 pub mod insert;
 pub mod select;
 pub mod update;
 pub mod use_cql;
-use super::storage_engine::StorageEngine;
+use super::storage_engine::{self, StorageEngine};
+use native_protocol::messages::result::rows::split_storage_values;
 use query_creator::errors::CQLError;
 use query_creator::Query;
 use std::collections::HashMap;
 use std::net::{Ipv4Addr, TcpStream};
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
 // Si `node` es el módulo raíz
 
 /// Struct for executing various database queries across nodes with support
@@ -38,6 +46,14 @@ pub struct QueryExecution {
     execution_replicate_itself: bool,
     how_many_nodes_failed: i32,
     storage_engine: StorageEngine,
+    // Puerto en el que escuchan los otros nodos
+    internode_port: u16,
+    // Si las escrituras a las otras replicas se esperan o van en segundo plano
+    replication_mode: ReplicationMode,
+    // Escrituras que esperan ser enviadas en segundo plano a cada replica
+    pending_writes: PendingWrites,
+    // Si la escritura condicional se aplico en este nodo (`None` si no hubo condicion)
+    applied: Option<bool>,
 }
 
 impl QueryExecution {
@@ -94,9 +110,31 @@ impl QueryExecution {
         connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
         storage_path: PathBuf,
     ) -> Result<QueryExecution, NodeError> {
-        let ip = { node_that_execute.lock()?.get_ip_string() };
+        let (
+            ip,
+            replication_mode,
+            pending_writes,
+            storage_metrics,
+            durability,
+            internode_port,
+            keyspace_quotas,
+        ) = {
+            let node = node_that_execute.lock()?;
+            (
+                node.get_ip_string(),
+                node.replication_mode(),
+                node.pending_writes.clone(),
+                node.metrics.storage(),
+                node.durability.clone(),
+                node.internode_port,
+                node.keyspace_quotas.clone(),
+            )
+        };
 
-        let storage_engine = StorageEngine::new(storage_path, ip);
+        let storage_engine = StorageEngine::new(storage_path, ip)
+            .with_metrics(storage_metrics)
+            .with_durability(durability)
+            .with_keyspace_quotas(&keyspace_quotas);
         Ok(QueryExecution {
             node_that_execute,
             connections,
@@ -104,6 +142,10 @@ impl QueryExecution {
             execution_replicate_itself: false,
             how_many_nodes_failed: 0,
             storage_engine: storage_engine,
+            internode_port,
+            replication_mode,
+            pending_writes,
+            applied: None,
         })
     }
 
@@ -120,6 +162,8 @@ impl QueryExecution {
     ///     - `Query::CreateTable`, `Query::DropTable`, `Query::AlterTable` for table management.
     ///     - `Query::CreateKeyspace`, `Query::DropKeyspace`, `Query::AlterKeyspace` for keyspace management.
     ///     - `Query::Use` for switching keyspaces.
+    ///     - `Query::Explain` is rejected, its plan is built by the coordinator without executing anything.
+    ///     - `Query::Batch` is rejected, the coordinator executes each of its statements.
     /// - `internode: bool`
     ///   - If `true`, enables internode communication for the query, involving other nodes in the cluster.
     /// - `replication: bool`
@@ -158,6 +202,10 @@ impl QueryExecution {
     ///   - Similar to INSERT but updates rows in the database.
     /// - **DELETE Queries**:
     ///   - Removes rows based on the conditions specified in the query.
+    /// - **Conditional writes** (`IF`, `IF EXISTS`, `IF NOT EXISTS`):
+    ///   - The storage engine checks the condition against the current row under the table lock.
+    ///   - The response content carries a single `[applied]` row with `true` or `false`.
+    ///   - Each replica evaluates the condition on its own copy; there is no consensus round between them.
     /// - **Table and Keyspace Management**:
     ///   - Handles `CREATE`, `DROP`, and `ALTER` operations for tables and keyspaces.
     ///   - Operations are forwarded to specific handlers like `execute_create_table`.
@@ -200,6 +248,7 @@ impl QueryExecution {
                         replication,
                         open_query_id,
                         client_id,
+                        timestap.unwrap_or(0),
                     ) {
                         Ok(select_querys) => {
                             let columns: Vec<String> = select_querys
@@ -217,10 +266,15 @@ impl QueryExecution {
                                     .iter()
                                     .map(|s| {
                                         // Dividir en dos partes por ";"
-                                        if let Some((first_part, second_part)) = s.split_once(';') {
-                                            // Dividir la primera parte por "," y agregar la segunda parte
+                                        if let Some((first_part, second_part)) =
+                                            storage_engine::split_stored_row(s)
+                                        {
+                                            // Dividir la primera parte por las "," sin escapar y agregar la segunda parte
                                             let mut combined: Vec<String> =
-                                                first_part.split(',').map(String::from).collect();
+                                                split_storage_values(first_part)
+                                                    .into_iter()
+                                                    .map(String::from)
+                                                    .collect();
                                             combined.push(second_part.to_string()); // Añadir la parte después de ";"
                                             combined
                                         } else {
@@ -308,37 +362,53 @@ impl QueryExecution {
                     )
                 }
                 Query::CreateTable(create_table) => {
-                    self.execute_create_table(create_table, open_query_id)
+                    self.execute_create_table(create_table, internode, open_query_id, client_id)
+                }
+                Query::DropTable(drop_table) => {
+                    self.execute_drop_table(drop_table, internode, open_query_id, client_id)
                 }
-                Query::DropTable(drop_table) => self.execute_drop_table(drop_table, open_query_id),
                 Query::AlterTable(alter_table) => {
-                    self.execute_alter_table(alter_table, open_query_id)
+                    self.execute_alter_table(alter_table, internode, open_query_id, client_id)
                 }
-                Query::CreateKeyspace(create_keyspace) => {
-                    self.execute_create_keyspace(create_keyspace)
+                Query::CreateKeyspace(create_keyspace) => self.execute_create_keyspace(
+                    create_keyspace,
+                    internode,
+                    open_query_id,
+                    client_id,
+                ),
+                Query::DropKeyspace(drop_keyspace) => {
+                    self.execute_drop_keyspace(drop_keyspace, internode, open_query_id, client_id)
                 }
-                Query::DropKeyspace(drop_keyspace) => self.execute_drop_keyspace(drop_keyspace),
                 Query::AlterKeyspace(alter_keyspace) => self.execute_alter_keyspace(alter_keyspace),
                 Query::Use(_) => {
                     return Err(NodeError::OtherError);
                     //self.execute_use(use_cql, internode, open_query_id, client_id)
                 }
+                Query::Explain(_) | Query::Batch(_) => {
+                    return Err(NodeError::OtherError);
+                }
             }
         };
 
+        if let Some(applied) = self.applied {
+            let applied_column = vec!["[applied]".to_string()];
+            response.content = Some(InternodeResponseContent {
+                columns: applied_column.clone(),
+                select_columns: applied_column,
+                values: vec![vec![applied.to_string()]],
+            });
+        }
+
         if internode {
             let response = {
                 match query_result {
                     Ok(_) => response,
 
-                    Err(_) => {
-                        eprintln!(
-                            "el error en este nodo es {:?} de la query {:?}",
-                            query_result, query
-                        );
+                    Err(e) => {
+                        eprintln!("el error en este nodo es {:?} de la query {:?}", e, query);
                         InternodeResponse {
                             open_query_id: open_query_id as u32,
-                            status: InternodeResponseStatus::Error,
+                            status: InternodeResponseStatus::Error(InternodeErrorDetail::from(&e)),
                             content: None,
                         }
                     }
@@ -370,17 +440,19 @@ impl QueryExecution {
         }
     }
 
-    // Función auxiliar para enviar un mensaje a todos los nodos en el partitioner
-    fn _send_to_other_nodes(
+    // Envia la consulta DDL que este nodo ya aplico al resto de los nodos vivos, devuelve a
+    // cuantos no se pudo enviar
+    fn send_to_live_nodes(
         &self,
-        local_node: MutexGuard<'_, Node>,
         serialized_message: &str,
         open_query_id: i32,
         client_id: i32,
         keyspace_name: &str,
-        timestap: i64,
     ) -> Result<i32, NodeError> {
-        let current_ip = local_node.get_ip();
+        let (current_ip, live_nodes, logger) = {
+            let node = self.node_that_execute.lock()?;
+            (node.get_ip(), node.live_nodes(), node.get_logger())
+        };
         let message = InternodeMessage::new(
             current_ip,
             InternodeMessageContent::Query(InternodeQuery {
@@ -389,17 +461,25 @@ impl QueryExecution {
                 client_id: client_id as u32,
                 replication: false,
                 keyspace_name: keyspace_name.to_string(),
-                timestamp: timestap,
+                timestamp: 0,
             }),
         );
 
         let mut failed_nodes = 0;
 
-        for ip in local_node.get_partitioner().get_nodes() {
+        for ip in live_nodes {
             if ip != current_ip {
+                logger.info(
+                    &format!(
+                        "INTERNODE (Query: {:?}): I SENT {:?} to {:?}",
+                        open_query_id, serialized_message, ip
+                    ),
+                    Color::Green,
+                    true,
+                )?;
                 let result = connect_and_send_message(
                     ip,
-                    INTERNODE_PORT,
+                    self.internode_port,
                     self.connections.clone(),
                     message.clone(),
                 );
@@ -423,30 +503,54 @@ impl QueryExecution {
         timestap: i64,
         logger: Logger,
     ) -> Result<i32, NodeError> {
-        let message = InternodeMessage::new(
+        let content = InternodeMessageContent::Query(InternodeQuery {
+            query_string: serialized_message.to_string(),
+            open_query_id: open_query_id as u32,
+            client_id: client_id as u32,
+            replication: false,
+            keyspace_name: keyspace_name.to_string(),
+            timestamp: timestap,
+        });
+
+        self.send_content_to_single_node(
             self_ip,
-            InternodeMessageContent::Query(InternodeQuery {
-                query_string: serialized_message.to_string(),
-                open_query_id: open_query_id as u32,
-                client_id: client_id as u32,
-                replication: false,
-                keyspace_name: keyspace_name.to_string(),
-                timestamp: timestap,
-            }),
-        );
+            target_ip,
+            content,
+            serialized_message,
+            open_query_id,
+            logger,
+        )
+    }
+
+    // Envía `content` a un nodo específico, devuelve 1 si no se pudo enviar
+    fn send_content_to_single_node(
+        &self,
+        self_ip: Ipv4Addr,
+        target_ip: Ipv4Addr,
+        content: InternodeMessageContent,
+        description: &str,
+        open_query_id: i32,
+        logger: Logger,
+    ) -> Result<i32, NodeError> {
+        let message = InternodeMessage::new(self_ip, content);
 
         logger.info(
             &format!(
                 "INTERNODE (Query: {:?}): I SENT {:?} to {:?}",
-                open_query_id, serialized_message, target_ip
+                open_query_id, description, target_ip
             ),
             Color::Green,
             true,
         )?;
 
+        // Solo las escrituras van en segundo plano, las lecturas esperan su respuesta
+        if matches!(message.content, InternodeMessageContent::Query(_)) {
+            return Ok(self.send_write(target_ip, message, open_query_id));
+        }
+
         let result = connect_and_send_message(
             target_ip,
-            INTERNODE_PORT,
+            self.internode_port,
             self.connections.clone(),
             message.clone(),
         );
@@ -458,10 +562,201 @@ impl QueryExecution {
         Ok(0)
     }
 
+    /// Sends a write to one of its replicas and returns how many sends failed so far.
+    ///
+    /// In both modes a write the replica cannot take is kept as a hint for it.
+    ///
+    /// Under `ReplicationMode::Sync` the write is sent right away and a failure is returned
+    /// as `1`, like any other message. Under `ReplicationMode::Async` it is queued for the
+    /// thread that sends the writes of `target_ip` and `0` is returned: the failure is added to
+    /// the open query when it happens, so the coordinator does not wait for slow or dead
+    /// replicas once the consistency level is met. Under `ANY` a kept hint acknowledges the
+    /// write instead of failing it. If the queue of `target_ip` is full the write is not
+    /// queued nor hinted and `1` is returned: the replica gets it back through read repair.
+    fn send_write(
+        &self,
+        target_ip: Ipv4Addr,
+        message: InternodeMessage,
+        open_query_id: i32,
+    ) -> i32 {
+        if self.replication_mode == ReplicationMode::Sync {
+            let result = connect_and_send_message(
+                target_ip,
+                self.internode_port,
+                self.connections.clone(),
+                message.clone(),
+            );
+            if result.is_ok() {
+                return 0;
+            }
+            // El nodo puede estar bloqueado por quien envia, el hint se guarda desde otro hilo
+            let node = Arc::clone(&self.node_that_execute);
+            thread::spawn(move || {
+                if let Ok(mut node) = node.lock() {
+                    node.hints.add(target_ip, message);
+                }
+            });
+            return 1;
+        }
+
+        let node = Arc::clone(&self.node_that_execute);
+        let connections = self.connections.clone();
+        let internode_port = self.internode_port;
+        let queue = self.pending_writes.queue_of(target_ip, |queue| {
+            thread::spawn(move || {
+                Self::send_pending_writes(target_ip, internode_port, queue, node, connections)
+            });
+        });
+        match queue.and_then(|queue| {
+            queue.push((message, open_query_id), OverflowPolicy::Reject, |_| false)
+        }) {
+            Ok(None) => 0,
+            // La replica no da abasto: la escritura falla sin esperar
+            _ => 1,
+        }
+    }
+
+    // Envía en orden las escrituras de la cola de `target_ip`. Las que no llegan se guardan
+    // como hint y se suman a su consulta abierta.
+    fn send_pending_writes(
+        target_ip: Ipv4Addr,
+        internode_port: u16,
+        queue: Arc<BoundedQueue<PendingWrite>>,
+        node: Arc<Mutex<Node>>,
+        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+    ) {
+        while let Ok(Some((message, open_query_id))) = queue.pop() {
+            if connect_and_send_message(
+                target_ip,
+                internode_port,
+                connections.clone(),
+                message.clone(),
+            )
+            .is_ok()
+            {
+                continue;
+            }
+            if let Ok(mut node) = node.lock() {
+                let kept = node.hints.add(target_ip, message);
+                InternodeProtocolHandler::add_hinted_response_to_open_query_and_send_response_if_closed(
+                    &mut node.open_query_handler,
+                    open_query_id,
+                    kept,
+                )
+                .ok();
+            }
+        }
+    }
+
+    // Envía `query` a cada réplica de la partición: al dueño (la primera) para su carpeta
+    // principal y al resto para la de replicación, que se marca en cada copia. Devuelve cuántos
+    // envíos fallaron y si este nodo es el dueño o una de las otras réplicas.
+    fn send_to_replicas(
+        &self,
+        self_ip: Ipv4Addr,
+        replicas: &[Ipv4Addr],
+        query: InternodeQuery,
+        logger: Logger,
+    ) -> Result<(i32, bool, bool), NodeError> {
+        let mut failed_nodes = 0;
+        let mut is_owner = false;
+        let mut is_replica = false;
+
+        for (position, ip) in replicas.iter().enumerate() {
+            let replication = position > 0;
+            if *ip == self_ip {
+                if replication {
+                    is_replica = true;
+                } else {
+                    is_owner = true;
+                }
+                continue;
+            }
+
+            let content = InternodeMessageContent::Query(InternodeQuery {
+                replication,
+                ..query.clone()
+            });
+
+            failed_nodes += self.send_content_to_single_node(
+                self_ip,
+                *ip,
+                content,
+                &query.query_string,
+                query.open_query_id as i32,
+                logger.clone(),
+            )?;
+        }
+        Ok((failed_nodes, is_owner, is_replica))
+    }
+
+    /// Speculative retry of a read: while the query is still open, every `delay` the read is
+    /// sent to the next replica in `spares`, so a slow replica does not hold the answer back.
+    ///
+    /// Whichever replicas answer first close the query; the answers that arrive after that
+    /// belong to a query that no longer exists and are discarded. A replica that cannot be
+    /// reached counts as an error response, like in the first round of requests.
+    fn speculate_read(
+        &self,
+        open_query_id: i32,
+        spares: Vec<(Ipv4Addr, InternodeMessageContent)>,
+        delay: Duration,
+        logger: Logger,
+    ) {
+        let node = Arc::clone(&self.node_that_execute);
+        let connections = self.connections.clone();
+        let internode_port = self.internode_port;
+
+        thread::spawn(move || {
+            for (ip, content) in spares {
+                thread::sleep(delay);
+
+                let self_ip = match node.lock() {
+                    Ok(mut node) => {
+                        if node
+                            .get_open_handle_query()
+                            .get_query_mut(&open_query_id)
+                            .is_none()
+                        {
+                            return;
+                        }
+                        node.get_ip()
+                    }
+                    Err(_) => return,
+                };
+
+                logger
+                    .info(
+                        &format!(
+                            "INTERNODE (Query: {:?}): no answer after {:?}, I SENT the read to {:?}",
+                            open_query_id, delay, ip
+                        ),
+                        Color::Yellow,
+                        true,
+                    )
+                    .ok();
+
+                let message = InternodeMessage::new(self_ip, content);
+                if connect_and_send_message(ip, internode_port, connections.clone(), message)
+                    .is_err()
+                {
+                    if let Ok(mut node) = node.lock() {
+                        InternodeProtocolHandler::add_error_response_to_open_query_and_send_response_if_closed(
+                            node.get_open_handle_query(),
+                            open_query_id,
+                            None,
+                        )
+                        .ok();
+                    }
+                }
+            }
+        });
+    }
+
     // Función auxiliar para enviar un mensaje a todos los nodos en el partitioner con replicación
     fn send_to_replication_nodes(
         &self,
-        mut local_node: MutexGuard<'_, Node>,
+        local_node: MutexGuard<'_, Node>,
         node_to_get_succesor: Ipv4Addr,
         serialized_message: &str,
         open_query_id: i32,
@@ -470,22 +765,40 @@ impl QueryExecution {
         timestap: i64,
         logger: Logger,
     ) -> Result<(i32, bool), NodeError> {
-        // Serializa el objeto que se quiere enviar
+        let content = InternodeMessageContent::Query(InternodeQuery {
+            query_string: serialized_message.to_string(),
+            open_query_id: open_query_id as u32,
+            client_id: client_id as u32,
+            replication: true,
+            keyspace_name: keyspace_name.to_string(),
+            timestamp: timestap,
+        });
+
+        self.send_content_to_replication_nodes(
+            local_node,
+            node_to_get_succesor,
+            content,
+            serialized_message,
+            open_query_id,
+            logger,
+        )
+    }
 
+    // Envía `content` a los sucesores de `node_to_get_succesor` que guardan las réplicas.
+    // Devuelve cuántos envíos fallaron y si este nodo es una de las réplicas.
+    fn send_content_to_replication_nodes(
+        &self,
+        mut local_node: MutexGuard<'_, Node>,
+        node_to_get_succesor: Ipv4Addr,
+        content: InternodeMessageContent,
+        description: &str,
+        open_query_id: i32,
+        logger: Logger,
+    ) -> Result<(i32, bool), NodeError> {
         // Bloquea el nodo para obtener el partitioner y la IP
         let current_ip = local_node.get_ip();
 
-        let message = InternodeMessage::new(
-            current_ip,
-            InternodeMessageContent::Query(InternodeQuery {
-                query_string: serialized_message.to_string(),
-                open_query_id: open_query_id as u32,
-                client_id: client_id as u32,
-                replication: true,
-                keyspace_name: keyspace_name.to_string(),
-                timestamp: timestap,
-            }),
-        );
+        let message = InternodeMessage::new(current_ip, content);
 
         let replication_factor = local_node
             .get_open_handle_query()
@@ -506,21 +819,13 @@ impl QueryExecution {
                 logger.info(
                     &format!(
                         "INTERNODE (Query: {:?}): I SENT as REPLICATION {:?} to {:?}",
-                        open_query_id, serialized_message, ip
+                        open_query_id, description, ip
                     ),
                     Color::Green,
                     true,
                 )?;
 
-                let result = connect_and_send_message(
-                    ip,
-                    INTERNODE_PORT,
-                    self.connections.clone(),
-                    message.clone(),
-                );
-                if result.is_err() {
-                    failed_nodes += 1;
-                }
+                failed_nodes += self.send_write(ip, message.clone(), open_query_id);
             } else {
                 the_node_has_to_replicate = true;
             }
@@ -528,13 +833,21 @@ impl QueryExecution {
         Ok((failed_nodes, the_node_has_to_replicate))
     }
 
+    /// Drops the reads of `keyspace.table` cached by the node, after this node wrote to it.
+    fn invalidate_cached_reads(&self, keyspace: &str, table: &str) -> Result<(), NodeError> {
+        self.node_that_execute
+            .lock()?
+            .invalidate_cached_reads(keyspace, table);
+        Ok(())
+    }
+
     fn validate_values(&self, columns: Vec<Column>, values: &[String]) -> Result<(), CQLError> {
         if values.len() != columns.len() {
             return Err(CQLError::InvalidSyntax);
         }
 
         for (column, value) in columns.iter().zip(values) {
-            if value == "" {
+            if value == "" || value == NULL_VALUE {
                 continue;
             }
             if !column.data_type.is_valid_value(value) {
@@ -1,8 +1,15 @@
 // Ordered imports
 use super::QueryExecution;
+use crate::internode_protocol::message::InternodeMessageContent;
+use crate::internode_protocol::read_request::InternodeReadRequest;
+use crate::open_query_handler::ConsistencyLevel;
+use crate::query_cache::QueryCacheKey;
+use crate::storage_engine::group_by::rows_of_groups;
 use crate::NodeError;
-use query_creator::clauses::select_cql::Select;
+use query_creator::clauses::select_cql::{CellFunction, Select, COUNT_ALL};
 use query_creator::errors::CQLError;
+use query_creator::Query;
+use std::net::Ipv4Addr;
 
 impl QueryExecution {
     /// Executes the retrieval of row/rows. This function is public only for internal use
@@ -14,12 +21,12 @@ impl QueryExecution {
         mut replication: bool,
         open_query_id: i32,
         client_id: i32,
+        timestamp: i64,
     ) -> Result<Vec<String>, NodeError> {
         let table;
         let mut do_in_this_node = true;
 
         let mut failed_nodes = 0;
-        let mut internode_failed_nodes = 0;
         let client_keyspace;
         {
             // Get the table name and reference the node
@@ -37,82 +44,194 @@ impl QueryExecution {
             // Get the table and replication factor
             table = node.get_table(table_name.clone(), client_keyspace.clone())?;
 
-            // Validate the primary key and where clause
+            // Validate the primary key and where clause. A query that does not fix the
+            // partition reads every node, so it is refused here unless it has ALLOW FILTERING
             let partition_keys = table.get_partition_keys()?;
             let clustering_columns = table.get_clustering_columns()?;
-            let where_clause = select_query
-                .clone()
-                .where_clause
-                .ok_or(NodeError::CQLError(CQLError::NoWhereCondition))?;
-
-            where_clause.validate_cql_conditions(
-                &partition_keys,
-                &clustering_columns,
-                true,
-                false,
-            )?;
+            let query = Query::Select(select_query.clone());
+            select_query.check_distinct_columns(&partition_keys)?;
+            let clustering_in_order = table.get_clustering_column_in_order();
+            select_query.check_group_by_columns(&partition_keys, &clustering_in_order)?;
+            let primary_key: Vec<String> = partition_keys
+                .iter()
+                .cloned()
+                .chain(clustering_in_order)
+                .collect();
+            query.check_full_scan_allowed(&partition_keys)?;
+            let full_scan = query.is_full_scan(&partition_keys);
+
+            if !full_scan {
+                let where_clause = select_query
+                    .clone()
+                    .where_clause
+                    .ok_or(NodeError::CQLError(CQLError::NoWhereCondition))?;
+                where_clause.validate_cql_conditions(
+                    &partition_keys,
+                    &clustering_columns,
+                    true,
+                    false,
+                )?;
+                // Sin ALLOW FILTERING las clustering columns se restringen en orden, como las lee
+                // el indice
+                if !select_query.allow_filtering {
+                    where_clause
+                        .validate_clustering_prefix(&table.get_clustering_column_in_order())?;
+                }
+            }
 
             select_query.validate_order_by_cql_conditions(&clustering_columns)?;
 
             // Ensure that the columns specified in the query exist in the table
             let complet_columns: Vec<String> =
                 table.get_columns().iter().map(|c| c.name.clone()).collect();
+            select_query.check_cell_functions(&complet_columns, &primary_key)?;
 
             if select_query.columns[0] == String::from("*") {
                 select_query.columns = complet_columns;
             } else {
                 for col in select_query.clone().columns {
-                    if !complet_columns.contains(&col) {
+                    // `COUNT(*)` no es una columna, lo calcula el `GROUP BY`, y las funciones
+                    // de una celda ya se validaron
+                    if col != COUNT_ALL
+                        && CellFunction::of_column(&col).is_none()
+                        && !complet_columns.contains(&col)
+                    {
                         return Err(NodeError::CQLError(CQLError::InvalidColumn));
                     }
                 }
             }
 
-            // Determine the target node based on partition key hashing
-            let value_to_hash = where_clause
-                .get_value_partitioner_key_condition(partition_keys)?
-                .join("");
-            let node_to_query = node.partitioner.get_ip(value_to_hash.clone())?;
-            let self_ip = node.get_ip().clone();
-            let logger = node.get_logger();
-            // Forward the SELECT if this is not an internode operation and the target node differs
-            if !internode && node_to_query != self_ip {
-                let serialized_query = select_query.serialize();
-                failed_nodes = self.send_to_single_node(
-                    node.get_ip(),
-                    node_to_query,
-                    &serialized_query,
-                    open_query_id,
-                    client_id,
-                    &client_keyspace.get_name(),
-                    0,
-                    node.get_logger(),
-                )?;
-                do_in_this_node = false;
-            }
+            if full_scan {
+                // Cada nodo responde por los datos de los que es dueño, sin leer sus replicas
+                if !internode {
+                    let self_ip = node.get_ip();
+                    let logger = node.get_logger();
+                    let nodes = node.partitioner.get_nodes();
+                    node.get_open_handle_query()
+                        .get_query_mut(&open_query_id)
+                        .ok_or(NodeError::OpenQueryError)?
+                        .wait_for_every_node(nodes.len());
+                    drop(node);
 
-            // Send the SELECT to replication nodes if needed
-            if !internode {
-                let serialized_select = select_query.serialize();
-                (internode_failed_nodes, replication) = self.send_to_replication_nodes(
-                    node,
-                    node_to_query,
-                    &serialized_select,
-                    open_query_id,
-                    client_id,
-                    &client_keyspace.get_name(),
-                    0,
-                    logger.clone(),
+                    let serialized_query = select_query.serialize();
+                    for ip in nodes.into_iter().filter(|ip| *ip != self_ip) {
+                        let read_request =
+                            InternodeMessageContent::ReadRequest(InternodeReadRequest {
+                                select: select_query.clone(),
+                                open_query_id: open_query_id as u32,
+                                client_id: client_id as u32,
+                                replication: false,
+                                keyspace_name: client_keyspace.get_name(),
+                                consistency: ConsistencyLevel::All,
+                                timestamp,
+                            });
+                        failed_nodes += self.send_content_to_single_node(
+                            self_ip,
+                            ip,
+                            read_request,
+                            &serialized_query,
+                            open_query_id,
+                            logger.clone(),
+                        )?;
+                    }
+                    self.execution_finished_itself = true;
+                }
+            } else {
+                // The owner of the partition comes first, followed by the nodes that keep its replicas
+                let partition_key = select_query
+                    .clone()
+                    .where_clause
+                    .ok_or(NodeError::CQLError(CQLError::NoWhereCondition))?
+                    .get_value_partitioner_key_condition(partition_keys)?;
+                let replicas = node.partitioner.get_replicas(
+                    &partition_key,
+                    client_keyspace.get_replication_factor() as usize,
                 )?;
-            }
+                let owner = replicas[0];
+                let self_ip = node.get_ip();
+                let logger = node.get_logger();
+
+                // Only as many replicas as the consistency level needs are asked at first, and the
+                // others are kept in case one of them is slow
+                if !internode {
+                    let consistency = node
+                        .get_open_handle_query()
+                        .get_query_mut(&open_query_id)
+                        .map(|query| query.get_consistency_level())
+                        .ok_or(NodeError::OpenQueryError)?;
+                    let speculative_retry_delay = node.speculative_retry_delay;
+                    node.set_local_replicas_of_query(open_query_id, &replicas)?;
+
+                    // Este nodo lee primero de si mismo si es una de las replicas; entre las demas,
+                    // se prefieren las de su datacenter y luego la que anuncia menos carga por
+                    // gossip. El orden del anillo desempata porque el sort es estable.
+                    let mut candidates = replicas.clone();
+                    candidates.sort_by_key(|ip| {
+                        let load = node
+                            .gossiper
+                            .get_load(*ip)
+                            .map(|load| load.total())
+                            .unwrap_or(0);
+                        (*ip != self_ip, !node.is_in_local_datacenter(*ip), load)
+                    });
+                    // Con LOCAL_* las respuestas de otros datacenters no cuentan, no se les pregunta
+                    if consistency.is_datacenter_local() {
+                        candidates.retain(|ip| node.is_in_local_datacenter(*ip));
+                    }
+                    drop(node);
+
+                    let read_request = |replication: bool| {
+                        InternodeMessageContent::ReadRequest(InternodeReadRequest {
+                            select: select_query.clone(),
+                            open_query_id: open_query_id as u32,
+                            client_id: client_id as u32,
+                            replication,
+                            keyspace_name: client_keyspace.get_name(),
+                            consistency,
+                            timestamp,
+                        })
+                    };
+                    let serialized_query = select_query.serialize();
+
+                    let mut candidates = candidates.into_iter();
+
+                    do_in_this_node = false;
+                    let required = consistency.required_oks(candidates.len());
+                    let mut asked = 0;
+                    while asked < required {
+                        let Some(ip) = candidates.next() else { break };
+                        if ip == self_ip {
+                            do_in_this_node = ip == owner;
+                            replication = ip != owner;
+                            self.execution_finished_itself = do_in_this_node;
+                            asked += 1;
+                            continue;
+                        }
+
+                        let failed = self.send_content_to_single_node(
+                            self_ip,
+                            ip,
+                            read_request(ip != owner),
+                            &serialized_query,
+                            open_query_id,
+                            logger.clone(),
+                        )?;
+                        if failed == 0 {
+                            asked += 1;
+                        }
+                        failed_nodes += failed;
+                    }
 
-            // Set execution finished if the node itself is the target and no other replication is needed
-            if !internode && node_to_query == self_ip {
-                self.execution_finished_itself = true;
+                    let spares: Vec<(Ipv4Addr, InternodeMessageContent)> = candidates
+                        .map(|ip| (ip, read_request(ip != owner)))
+                        .collect();
+                    if !spares.is_empty() {
+                        self.speculate_read(open_query_id, spares, speculative_retry_delay, logger);
+                    }
+                }
             }
         }
 
-        failed_nodes += internode_failed_nodes;
         self.how_many_nodes_failed = failed_nodes;
         // Return if no local execution or replication is needed
         if !do_in_this_node && !replication {
@@ -123,12 +242,48 @@ impl QueryExecution {
         if replication {
             self.execution_replicate_itself = true;
         }
-        let results = self.storage_engine.select(
-            select_query,
-            table,
-            replication,
-            &client_keyspace.get_name(),
-        )?;
+
+        // Una lectura identica y reciente se responde sin volver a leer la tabla
+        let cache_key = QueryCacheKey {
+            keyspace: client_keyspace.get_name(),
+            table: table.get_name(),
+            query: select_query.serialize(),
+        };
+        let generation = {
+            let mut node = self
+                .node_that_execute
+                .lock()
+                .map_err(|_| NodeError::LockError)?;
+            match node.query_cache.as_mut() {
+                Some(cache) => match cache.get(&cache_key) {
+                    Some(rows) => return Ok(rows),
+                    None => Some(cache.generation()),
+                },
+                None => None,
+            }
+        };
+
+        // Con `GROUP BY` se devuelven las filas de los grupos: el coordinador las reconcilia
+        // entre las replicas antes de agrupar, asi cada grupo cuenta las filas de todas
+        let read_query = if select_query.is_grouped() {
+            rows_of_groups(&select_query)
+        } else {
+            select_query
+        };
+        // Mientras se redistribuyen los datos la fila puede estar en cualquiera de las dos carpetas
+        let results =
+            self.storage_engine
+                .select_merged(read_query, table, &client_keyspace.get_name())?;
+
+        if let Some(generation) = generation {
+            let mut node = self
+                .node_that_execute
+                .lock()
+                .map_err(|_| NodeError::LockError)?;
+            if let Some(cache) = node.query_cache.as_mut() {
+                cache.insert(cache_key, results.clone(), generation);
+            }
+        }
         Ok(results)
     }
 }
@@ -61,11 +61,17 @@ impl QueryExecution {
             }
 
             // Get the value to hash and determine the node responsible for handling the update
-            let value_to_hash = where_clause
-                .get_value_partitioner_key_condition(partition_keys)?
-                .join("");
+            let partition_key = where_clause.get_value_partitioner_key_condition(partition_keys)?;
 
-            let node_to_update = node.partitioner.get_ip(value_to_hash.clone())?;
+            let node_to_update = node.partitioner.get_ip_for_partition(&partition_key)?;
+            if !internode {
+                let replicas = node.partitioner.get_replicas(
+                    &partition_key,
+                    client_keyspace.get_replication_factor() as usize,
+                )?;
+                node.set_local_replicas_of_query(open_query_id, &replicas)?;
+                node.apply_replication_mode_to_query(open_query_id, &replicas);
+            }
             let self_ip = node.get_ip().clone();
             let logger = node.get_logger();
             // If not an internode operation and the target node differs, forward the update
@@ -121,13 +127,20 @@ impl QueryExecution {
         // Validate the update types
         Self::validate_update_types(update_query.clone().set_clause, table.get_columns())?;
 
-        self.storage_engine.update(
+        let conditional = update_query.if_clause.is_some();
+        let table_name = table.get_name();
+        let applied = self.storage_engine.update(
             update_query,
             table,
             replication,
             &client_keyspace.get_name(),
             timestamp,
         )?;
+        self.invalidate_cached_reads(&client_keyspace.get_name(), &table_name)?;
+
+        if conditional {
+            self.applied = Some(applied);
+        }
         Ok(())
     }
 
@@ -24,7 +24,7 @@ impl QueryExecution {
         let keyspace_name = use_keyspace.get_name();
 
         // Set the current keyspace in the node
-        node._set_actual_keyspace(keyspace_name.clone(), client_id)?;
+        node.set_actual_keyspace(keyspace_name.clone(), client_id)?;
 
         let keyspaces = node.schema.keyspaces.clone();
 
@@ -41,13 +41,12 @@ impl QueryExecution {
         if !internode {
             // Serialize the `UseKeyspace` into a simple message
             let serialized_use_keyspace = use_keyspace.serialize();
-            self._send_to_other_nodes(
-                node,
+            drop(node);
+            self.send_to_live_nodes(
                 &serialized_use_keyspace,
                 open_query_id,
                 client_id,
                 &keyspace_name,
-                0,
             )?;
         }
 
@@ -1,6 +1,6 @@
 use std::{
     collections::HashMap,
-    fs::{self, File},
+    fs::File,
     io::{BufRead, BufReader, BufWriter, Write},
     net::{Ipv4Addr, TcpStream},
     sync::{Arc, Mutex},
@@ -17,11 +17,25 @@ use crate::{
         message::{InternodeMessage, InternodeMessageContent},
         query::InternodeQuery,
     },
+    stream_throttle::{StreamThrottle, ThrottledStream},
     utils::connect_and_send_message,
-    INTERNODE_PORT,
 };
 
-use super::{errors::StorageEngineError, StorageEngine};
+use super::{
+    cql_literal, errors::StorageEngineError, parse_row_time,
+    partition_tombstone::read_partition_tombstones, split_stored_row, StorageEngine,
+};
+use native_protocol::messages::result::rows::{split_storage_values, unescape_storage_text};
+
+/// How the storage engine reaches the other nodes when it sends them rows.
+pub struct Peers {
+    /// Open connections to the other nodes.
+    pub connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+    /// Port the other nodes listen on for internode messages.
+    pub internode_port: u16,
+    /// Logger where each row sent is recorded.
+    pub logger: Logger,
+}
 
 impl StorageEngine {
     /// Redistributes data across nodes for the specified keyspaces.
@@ -35,8 +49,9 @@ impl StorageEngine {
     ///
     /// * `keyspaces` - A vector of keyspace schemas to process and redistribute.
     /// * `partitioner` - The partitioner responsible for determining the ownership of data.
-    /// * `logger` - The logger instance for recording progress and errors.
-    /// * `connections` - A shared map of connections to other nodes in the cluster.
+    /// * `peers` - The connections and port the rows are sent through, and the logger that records them.
+    /// * `throttle` - Limit of the rows per second sent to other nodes. Rows that stay in
+    ///   this node are not counted.
     ///
     /// # Returns
     ///
@@ -53,15 +68,19 @@ impl StorageEngine {
         &self,
         keyspaces: Vec<KeyspaceSchema>,
         partitioner: &Partitioner,
-        logger: Logger,
-        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+        peers: &Peers,
+        throttle: &StreamThrottle,
     ) -> Result<(), StorageEngineError> {
+        // Un solo ritmo para todas las tablas, el limite es de la redistribucion entera
+        let mut stream = throttle.stream();
+
         for keyspace in keyspaces {
             let tables = keyspace.clone().get_tables();
 
             for table in tables {
                 // Rutas de archivos
-                let base_folder_path = self.get_keyspace_path(&keyspace.clone().get_name());
+                let base_folder_path =
+                    self.get_keyspace_path(&keyspace.clone().get_name(), &table.get_name());
                 let normal_file_path = base_folder_path.join(format!("{}.csv", table.get_name()));
                 let replication_file_path = base_folder_path
                     .join("replication")
@@ -72,12 +91,11 @@ impl StorageEngine {
                     self.process_file(
                         &normal_file_path,
                         &partitioner,
-                        logger.clone(),
                         keyspace.clone(),
                         table.clone(),
                         false,
-                        self.ip.clone(),
-                        connections.clone(),
+                        peers,
+                        &mut stream,
                     )?;
                 }
 
@@ -86,12 +104,11 @@ impl StorageEngine {
                     self.process_file(
                         &replication_file_path,
                         &partitioner,
-                        logger.clone(),
                         keyspace.clone(),
                         table.clone(),
                         true,
-                        self.ip.clone(),
-                        connections.clone(),
+                        peers,
+                        &mut stream,
                     )?;
                 }
             }
@@ -100,23 +117,186 @@ impl StorageEngine {
         Ok(())
     }
 
+    /// Sends to `replica` the rows this node stores of every table in `keyspaces` that
+    /// `replica` owns or keeps a replica of, as an anti-entropy repair after it restarted.
+    ///
+    /// Each row is sent as an `INSERT` with the timestamp of its last write, so a replica that
+    /// already has a newer version keeps it. Deleted rows are sent as a `DELETE` of their primary
+    /// key, and partitions deleted as a whole as a `DELETE` of their partition key, both with the
+    /// timestamp of the delete, so the replica does not keep rows it missed the delete of.
+    /// Returns how many rows and deletes were sent.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::IoError` if a table file cannot be read.
+    /// - `StorageEngineError::UnsupportedOperation` if the partitioner cannot place a row.
+    pub fn repair_replica(
+        &self,
+        keyspaces: Vec<KeyspaceSchema>,
+        partitioner: &Partitioner,
+        replica: Ipv4Addr,
+        peers: &Peers,
+        throttle: &StreamThrottle,
+    ) -> Result<usize, StorageEngineError> {
+        let self_ip: Ipv4Addr = self
+            .ip
+            .parse()
+            .map_err(|_| StorageEngineError::UnsupportedOperation)?;
+        let mut stream = throttle.stream();
+        let mut sent = 0;
+
+        for keyspace in keyspaces {
+            let successors_count = (keyspace.get_replication_factor() as usize).saturating_sub(1);
+            // Dueño de la particion si la replica guarda sus filas, con los valores escapados
+            let owner_if_stored_by_replica =
+                |partition_key: &[&str]| -> Result<Option<Ipv4Addr>, StorageEngineError> {
+                    let owner = partitioner
+                        .get_ip_for_partition(
+                            &partition_key
+                                .iter()
+                                .map(|value| unescape_storage_text(value))
+                                .collect::<Vec<String>>(),
+                        )
+                        .map_err(|_| StorageEngineError::UnsupportedOperation)?;
+                    let is_replica = owner == replica
+                        || partitioner
+                            .get_n_successors(owner, successors_count)
+                            .map_err(|_| StorageEngineError::UnsupportedOperation)?
+                            .contains(&replica);
+                    Ok(is_replica.then_some(owner))
+                };
+
+            for table in keyspace.get_tables() {
+                let columns: Vec<String> =
+                    table.get_columns().iter().map(|c| c.name.clone()).collect();
+                let partition_key_indices: Vec<usize> = table
+                    .get_columns()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, col)| col.is_partition_key)
+                    .map(|(idx, _)| idx)
+                    .collect();
+                let primary_key_indices: Vec<usize> = table
+                    .get_columns()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, col)| col.is_partition_key || col.is_clustering_column)
+                    .map(|(idx, _)| idx)
+                    .collect();
+
+                for is_replication in [false, true] {
+                    // Todo el espacio de tokens: el dueño de cada fila se decide abajo
+                    let rows = self.scan_range_with_tombstones(
+                        &keyspace.get_name(),
+                        &table,
+                        is_replication,
+                        0,
+                        u64::MAX,
+                    )?;
+                    let folder_path = self
+                        .get_keyspace_path(&keyspace.get_name(), &table.get_name())
+                        .join(if is_replication { "replication" } else { "" });
+                    let partition_tombstones =
+                        read_partition_tombstones(&folder_path, &table.get_name())?;
+
+                    let mut messages = Vec::new();
+                    for line in rows {
+                        let (data, time_of_row) = split_stored_row(&line).unwrap_or((&line, ""));
+                        let row: Vec<&str> = split_storage_values(data);
+                        let partition_key: Vec<&str> = partition_key_indices
+                            .iter()
+                            .map(|&i| row.get(i).copied().unwrap_or_default())
+                            .collect();
+                        let (written_at, is_tombstone) = parse_row_time(time_of_row);
+
+                        // Solo las filas de las que la replica es duena o guarda una copia
+                        let Some(owner) = owner_if_stored_by_replica(&partition_key)? else {
+                            continue;
+                        };
+
+                        let query = if is_tombstone {
+                            Self::create_cql_delete(
+                                &keyspace.get_name(),
+                                &table.get_name(),
+                                &columns,
+                                &primary_key_indices,
+                                &row,
+                            )?
+                        } else {
+                            Self::create_cql_insert(
+                                &keyspace.get_name(),
+                                &table.get_name(),
+                                columns.clone(),
+                                row,
+                            )?
+                        };
+                        messages.push((query, written_at, owner != replica));
+                    }
+
+                    for (key, deleted_at) in &partition_tombstones {
+                        let partition_key: Vec<&str> = split_storage_values(key);
+                        let Some(owner) = owner_if_stored_by_replica(&partition_key)? else {
+                            continue;
+                        };
+                        let mut key_row = vec![""; columns.len()];
+                        for (&i, value) in partition_key_indices.iter().zip(&partition_key) {
+                            key_row[i] = value;
+                        }
+                        let query = Self::create_cql_delete(
+                            &keyspace.get_name(),
+                            &table.get_name(),
+                            &columns,
+                            &partition_key_indices,
+                            &key_row,
+                        )?;
+                        messages.push((query, *deleted_at, owner != replica));
+                    }
+
+                    for (query, timestamp, replication) in messages {
+                        stream.wait_for_next_row();
+                        self.create_and_send_internode_message(
+                            self_ip,
+                            replica,
+                            &keyspace.get_name(),
+                            &query,
+                            timestamp,
+                            replication,
+                            peers,
+                        );
+                        sent += 1;
+                    }
+                }
+            }
+        }
+
+        Ok(sent)
+    }
+
     fn process_file(
         &self,
         file_path: &std::path::Path,
         partitioner: &Partitioner,
-        logger: Logger,
         keyspace: KeyspaceSchema,
         table: TableSchema,
         is_replication: bool,
-        self_ip: String,
-        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>,
+        peers: &Peers,
+        stream: &mut ThrottledStream,
     ) -> Result<(), StorageEngineError> {
-        let self_ip: Ipv4Addr = self_ip
+        let self_ip: Ipv4Addr = self
+            .ip
             .parse()
             .map_err(|_| StorageEngineError::UnsupportedOperation)?;
 
         let columns: Vec<String> = table.get_columns().iter().map(|c| c.name.clone()).collect();
 
+        // El archivo se lee, filtra y reemplaza sin que otra escritura lo cambie en el medio
+        let table_lock = Self::table_lock(file_path)?;
+        let table_guard = table_lock
+            .lock()
+            .map_err(|_| StorageEngineError::LockError)?;
+        // Las filas que van a otros nodos se mandan despues de soltar el lock, que no espera al
+        // ritmo del stream: destino, insert, timestamp y si es una replica
+        let mut outgoing: Vec<(Ipv4Addr, String, i64, bool)> = Vec::new();
+
         let temp_file_path = file_path.with_extension("tmp");
 
         // Crear el archivo de índice con el formato `{nombre_archivo}_index.csv`
@@ -149,6 +329,12 @@ impl StorageEngine {
             .map(|(idx, _)| idx)
             .collect();
 
+        // Las filas de una particion borrada entera se tratan igual que los tombstones
+        let partition_tombstones = read_partition_tombstones(
+            file_path.parent().ok_or(StorageEngineError::IoError)?,
+            &table.get_name(),
+        )?;
+
         let clustering_key_indices: Vec<(usize, String)> = table
             .get_clustering_column_in_order()
             .iter()
@@ -181,20 +367,57 @@ impl StorageEngine {
             }
 
             // Procesar línea de datos
-            if let Some((data, timestamp)) = line.split_once(";") {
-                let row: Vec<&str> = data.split(',').collect();
+            if let Some((data, timestamp)) = split_stored_row(&line) {
+                let row: Vec<&str> = split_storage_values(data);
 
                 // Construir la clave de partición
-                let mut partition_key = String::new();
-                for partition_key_index in &partition_key_indices {
-                    partition_key.push_str(row[*partition_key_index]);
-                }
+                let partition_key: Vec<&str> = partition_key_indices
+                    .iter()
+                    .map(|&partition_key_index| row[partition_key_index])
+                    .collect();
 
-                // Determinar el nodo actual para la clave de partición
+                // Determinar el nodo actual para la clave de partición, con sus valores sin escapar
                 let current_node = partitioner
-                    .get_ip(partition_key.clone())
+                    .get_ip_for_partition(
+                        &partition_key
+                            .iter()
+                            .map(|value| unescape_storage_text(value))
+                            .collect::<Vec<String>>(),
+                    )
                     .map_err(|_| StorageEngineError::UnsupportedOperation)?;
 
+                let (time_of_row, is_tombstone) = parse_row_time(timestamp);
+                let shadowed = partition_tombstones
+                    .get(&partition_key.join(","))
+                    .is_some_and(|&deleted_at| time_of_row <= deleted_at);
+
+                // Los tombstones no se reubican, solo se conservan si la fila sigue siendo de este nodo
+                if is_tombstone || shadowed {
+                    let stays_here = if is_replication {
+                        partitioner
+                            .get_n_successors(
+                                current_node,
+                                keyspace.get_replication_factor() as usize - 1,
+                            )
+                            .map_err(|_| StorageEngineError::UnsupportedOperation)?
+                            .contains(&self_ip)
+                    } else {
+                        current_node == self_ip
+                    };
+                    if stays_here {
+                        writeln!(temp_file, "{};{}", data, timestamp)
+                            .map_err(|_| StorageEngineError::IoError)?;
+                        if let Some(&(idx, _)) = clustering_key_indices.first() {
+                            index_map.insert(
+                                row[idx].to_string(),
+                                (current_byte_offset, current_byte_offset + line_length),
+                            );
+                        }
+                        current_byte_offset += line_length + 1;
+                    }
+                    continue;
+                }
+
                 if current_node == self_ip {
                     if !is_replication {
                         // Si el nodo actual es el dueño de la clave
@@ -215,13 +438,14 @@ impl StorageEngine {
                             .parse()
                             .map_err(|_| StorageEngineError::UnsupportedOperation)?;
 
+                        // La replica paso a ser duenia: la fila va a la tabla principal
                         self.insert(
                             &keyspace.get_name(),
                             &table.get_name(),
                             row.clone(),
                             table.get_columns(),
                             table.get_clustering_column_in_order(),
-                            true,
+                            false,
                             false,
                             timest,
                         )?;
@@ -239,16 +463,7 @@ impl StorageEngine {
                         .parse()
                         .map_err(|_| StorageEngineError::UnsupportedOperation)?;
 
-                    Self::create_and_send_internode_message(
-                        self_ip,
-                        current_node,
-                        &keyspace.get_name(),
-                        &insert_string,
-                        timestamp_n,
-                        false,
-                        connections.clone(),
-                        logger.clone(),
-                    );
+                    outgoing.push((current_node, insert_string, timestamp_n, false));
                 }
 
                 // Manejo de réplicas
@@ -296,16 +511,7 @@ impl StorageEngine {
                             .parse()
                             .map_err(|_| StorageEngineError::UnsupportedOperation)?;
 
-                        Self::create_and_send_internode_message(
-                            self_ip,
-                            rep_ip,
-                            &keyspace.get_name(),
-                            &insert_string,
-                            timestamp_n,
-                            true,
-                            connections.clone(),
-                            logger.clone(),
-                        );
+                        outgoing.push((rep_ip, insert_string, timestamp_n, true));
                     }
                 }
             }
@@ -326,20 +532,36 @@ impl StorageEngine {
                 .map_err(|_| StorageEngineError::IoError)?;
         }
 
-        fs::rename(&temp_file_path, file_path).map_err(|_| StorageEngineError::IoError)?;
+        temp_file.flush().map_err(|_| StorageEngineError::IoError)?;
+        drop(temp_file);
+        self.replace_table_file(&temp_file_path, file_path)?;
+        drop(table_guard);
+
+        for (target_ip, insert_string, timestamp, is_replication) in outgoing {
+            stream.wait_for_next_row();
+            self.create_and_send_internode_message(
+                self_ip,
+                target_ip,
+                &keyspace.get_name(),
+                &insert_string,
+                timestamp,
+                is_replication,
+                peers,
+            );
+        }
 
         Ok(())
     }
 
     fn create_and_send_internode_message(
+        &self,
         self_ip: Ipv4Addr,
         target_ip: Ipv4Addr,
         keyspace_name: &str,
         serialized_message: &str,
         timestamp: i64,
         is_replication: bool,
-        connections: Arc<Mutex<HashMap<String, Arc<Mutex<TcpStream>>>>>, // Ajusta el tipo si es necesario
-        logger: Logger,
+        peers: &Peers,
     ) {
         // Crear el mensaje de internodo
         let message = InternodeMessage::new(
@@ -360,7 +582,8 @@ impl StorageEngine {
             ""
         };
 
-        logger
+        peers
+            .logger
             .info(
                 &format!(
                     "INTERNODE (REDISTRIBUTION): I SENT {:?}{:?} to {:?}",
@@ -373,11 +596,45 @@ impl StorageEngine {
             )
             .ok();
         //thread::sleep(Duration::from_millis(300));
-        let result = connect_and_send_message(target_ip, INTERNODE_PORT, connections, message);
+        let result = connect_and_send_message(
+            target_ip,
+            peers.internode_port,
+            peers.connections.clone(),
+            message,
+        );
         // Manejar errores o resultados
         _ = result;
     }
 
+    // Genera un `DELETE` de la fila con los valores de `key_indices`, sin escapar
+    fn create_cql_delete(
+        keyspace: &str,
+        table: &str,
+        columns: &[String],
+        key_indices: &[usize],
+        values: &[&str],
+    ) -> Result<String, StorageEngineError> {
+        let conditions = key_indices
+            .iter()
+            .map(|&i| {
+                let column = columns
+                    .get(i)
+                    .ok_or(StorageEngineError::UnsupportedOperation)?;
+                let value = values
+                    .get(i)
+                    .ok_or(StorageEngineError::UnsupportedOperation)?;
+                Ok(format!("{} = {}", column, cql_literal(value)))
+            })
+            .collect::<Result<Vec<String>, StorageEngineError>>()?;
+
+        Ok(format!(
+            "DELETE FROM {}.{} WHERE {}",
+            keyspace,
+            table,
+            conditions.join(" AND ")
+        ))
+    }
+
     fn create_cql_insert(
         keyspace: &str,
         table: &str,
@@ -391,10 +648,10 @@ impl StorageEngine {
         // Generar la lista de columnas separadas por comas
         let columns_string = columns.join(", ");
 
-        // Escapar valores si es necesario, rodeándolos con comillas simples
+        // Los valores se guardan escapados, la query lleva el valor original como literal
         let values_string = values
             .iter()
-            .map(|value| format!("'{}'", value.replace("'", "''"))) // Escapar comillas simples en los valores
+            .map(|value| cql_literal(value))
             .collect::<Vec<String>>()
             .join(",");
 
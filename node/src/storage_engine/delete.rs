@@ -7,7 +7,15 @@ use std::{
 use gossip::structures::application_state::TableSchema;
 use query_creator::clauses::delete_cql::Delete;
 
-use super::{errors::StorageEngineError, StorageEngine};
+use super::{
+    errors::StorageEngineError,
+    parse_row_time,
+    partition_tombstone::{deleted_partition, partition_key_indexes},
+    split_stored_row,
+    tombstone_purge::TombstonePurge,
+    StorageEngine, TOMBSTONE,
+};
+use native_protocol::messages::result::rows::{split_storage_values, unescape_storage_text};
 
 impl StorageEngine {
     /// Deletes rows or specific column values from a table within the specified keyspace.
@@ -31,7 +39,8 @@ impl StorageEngine {
     ///
     /// # Returns
     ///
-    /// - `Ok(())`: If the delete operation is successfully completed.
+    /// - `Ok(true)`: If at least one row matched the `WHERE` clause and satisfied the `IF` clause, if any.
+    /// - `Ok(false)`: If nothing was deleted. For a conditional delete this means it was not applied.
     /// - `Err(StorageEngineError)`: If an error occurs during the operation, such as:
     ///   - `DirectoryCreationFailed`: When required directories cannot be created.
     ///   - `FileNotFound`: When the target file for deletion does not exist.
@@ -43,8 +52,16 @@ impl StorageEngine {
     ///
     /// - If specific columns are specified in the `Delete` query:
     ///   - Only the specified columns will be cleared in rows that meet the `WHERE` condition.
-    /// - If no columns are specified:
-    ///   - Entire rows that meet the `WHERE` condition will be deleted.
+    /// - If no columns are specified, the table has clustering columns and the `WHERE` only sets
+    ///   the partition key with `=`:
+    ///   - The whole partition is deleted with a single partition tombstone, stored next to the
+    ///     table with the `timestamp` of the delete, instead of rewriting each of its rows.
+    ///     `select` hides every row of the partition written at or before that timestamp, so
+    ///     rows inserted later are visible again. The result is always `Ok(true)`.
+    /// - Otherwise, if no columns are specified:
+    ///   - Entire rows that meet the `WHERE` condition are replaced by tombstones, which keep
+    ///     the values and the `timestamp` of the delete (see `TOMBSTONE`). Rows that already
+    ///     are tombstones are left untouched and do not count as deleted.
     /// - If the table file does not exist:
     ///   - An error (`FileNotFound`) is returned.
     /// - Temporary files are created during the operation to avoid corruption of the original file.
@@ -55,13 +72,12 @@ impl StorageEngine {
     ///   the function returns an `InvalidQuery` error.
     /// - **Non-Existing Columns:** If the specified columns do not exist in the table,
     ///   they are ignored.
-    /// - **Concurrent Writes:** Simultaneous delete operations on the same table may cause
-    ///   unexpected behavior and are not supported.
+    /// - **Concurrent Writes:** Writes on the same table are serialized by the table lock, so the
+    ///   `IF` clause is checked against the rows the delete modifies.
     ///
     /// # Limitations
     ///
     /// - The function operates only on `.csv` file formats.
-    /// - The `IF` clause in the `Delete` query is currently ignored and must be set to `None`.
     /// - Complex conditions are supported via the `WHERE` clause but may require careful schema validation.
 
     pub fn delete(
@@ -71,9 +87,9 @@ impl StorageEngine {
         keyspace: &str,
         is_replication: bool,
         timestamp: i64,
-    ) -> Result<(), StorageEngineError> {
+    ) -> Result<bool, StorageEngineError> {
         let table_name = table.get_name();
-        let base_folder_path = self.get_keyspace_path(keyspace);
+        let base_folder_path = self.get_keyspace_path(keyspace, &table_name);
 
         // Construcción de la ruta de la carpeta según si es replicación o no
         let folder_path = if is_replication {
@@ -106,6 +122,20 @@ impl StorageEngine {
                 .as_nanos()
         ));
 
+        let table_lock = Self::table_lock(&file_path)?;
+        let _table_guard = table_lock
+            .lock()
+            .map_err(|_| StorageEngineError::LockError)?;
+
+        // Un borrado de la particion entera no toca las filas, solo deja el tombstone de la particion
+        if let Some(partition_key) = deleted_partition(&table, &delete_query) {
+            if !file_path.exists() {
+                return Err(StorageEngineError::FileNotFound);
+            }
+            self.write_partition_tombstone(&folder_path, &table_name, &partition_key, timestamp)?;
+            return Ok(true);
+        }
+
         // Abrir el archivo original, si no existe retornar error
         let file = OpenOptions::new()
             .read(true)
@@ -144,6 +174,14 @@ impl StorageEngine {
             })
             .collect();
 
+        let purge = TombstonePurge::new(
+            self,
+            &folder_path,
+            &table_name,
+            partition_key_indexes(&table),
+        )?;
+        let mut applied = false;
+
         // Iterar sobre cada línea del archivo original
         for (i, line) in reader.lines().enumerate() {
             let line = line.map_err(|_| StorageEngineError::IoError)?;
@@ -154,13 +192,22 @@ impl StorageEngine {
                 writeln!(temp_file, "{}", line)?;
                 continue;
             }
+            if purge.is_purged(&line) {
+                continue;
+            }
 
-            let (line, time_of_row) = line.split_once(";").ok_or(StorageEngineError::IoError)?;
-            let mut columns: Vec<String> = line.split(',').map(|s| s.trim().to_string()).collect();
+            let (line, time_of_row) = split_stored_row(&line).ok_or(StorageEngineError::IoError)?;
+            let mut columns: Vec<String> = split_storage_values(line)
+                .into_iter()
+                .map(|s| s.trim().to_string())
+                .collect();
+            let (_, is_tombstone) = parse_row_time(time_of_row);
 
-            let mut write_line = true; // Flag para determinar si la línea debe ser escrita
             let mut changed_line = false;
-            if let Some(columns_to_delete) = &delete_query.columns {
+            let mut deleted_line = false;
+            if is_tombstone {
+                // La fila ya estaba borrada, se conserva el tombstone como esta
+            } else if let Some(columns_to_delete) = &delete_query.columns {
                 // Si hay columnas específicas para eliminar, borra esos valores
                 if self.should_delete_line(&table, &delete_query, &line)? {
                     for column_name in columns_to_delete {
@@ -168,37 +215,36 @@ impl StorageEngine {
                             columns[index] = "".to_string(); // Vaciar el valor de la columna específica
                         }
                     }
-                } else {
-                    // Si se debe borrar toda la fila, no la escribimos
-                    write_line = true;
                     changed_line = true;
+                    applied = true;
                 }
             } else {
-                // Si no hay columnas específicas, elimina la fila si se cumplen las condiciones
+                // Si no hay columnas específicas, la fila se reemplaza por un tombstone
                 if self.should_delete_line(&table, &delete_query, &line)? {
-                    write_line = false;
+                    deleted_line = true;
+                    applied = true;
                 }
             }
 
-            // Si la línea no debe ser eliminada, escribirla en el archivo temporal
-            if write_line {
-                let time_to_write = if changed_line {
-                    &timestamp.to_string()
-                } else {
-                    time_of_row
-                };
-                writeln!(temp_file, "{};{}", columns.join(","), time_to_write)?;
-                if let Some(&(idx, _)) = clustering_key_order.first() {
-                    if let Some(key) = columns.get(idx) {
-                        let entry = (
-                            key.clone(),
-                            (current_byte_offset, current_byte_offset + line_length),
-                        );
-                        index_map.push(entry);
-                    }
+            let time_to_write = if deleted_line {
+                format!("{};{}", timestamp, TOMBSTONE)
+            } else if changed_line {
+                timestamp.to_string()
+            } else {
+                time_of_row.to_string()
+            };
+            let line_length = (columns.join(",").len() + 1 + time_to_write.len()) as u64;
+            writeln!(temp_file, "{};{}", columns.join(","), time_to_write)?;
+            if let Some(&(idx, _)) = clustering_key_order.first() {
+                if let Some(key) = columns.get(idx) {
+                    let entry = (
+                        key.clone(),
+                        (current_byte_offset, current_byte_offset + line_length),
+                    );
+                    index_map.push(entry);
                 }
-                current_byte_offset += line_length + 1;
             }
+            current_byte_offset += line_length + 1;
         }
 
         // Ordenar el archivo de índices según el orden de las clustering columns
@@ -217,12 +263,13 @@ impl StorageEngine {
         }
 
         // Reemplazar los archivos originales con los temporales
-        fs::rename(&temp_file_path, &file_path)
-            .map_err(|_| StorageEngineError::FileReplacementFailed)?;
-        fs::rename(&temp_index_file_path, &index_file_path)
-            .map_err(|_| StorageEngineError::FileReplacementFailed)?;
+        std::mem::drop(temp_file);
+        std::mem::drop(temp_index_file);
+        self.replace_table_file(&temp_file_path, &file_path)?;
+        self.replace_table_file(&temp_index_file_path, &index_file_path)?;
+        purge.finish(self, &folder_path, &table_name)?;
 
-        Ok(())
+        Ok(applied)
     }
 
     /// Verifica si una línea cumple las condiciones para ser eliminada
@@ -232,7 +279,11 @@ impl StorageEngine {
         delete_query: &Delete,
         line: &str,
     ) -> Result<bool, StorageEngineError> {
-        let columns: Vec<String> = line.split(',').map(|s| s.trim().to_string()).collect();
+        // El WHERE y el IF comparan con los valores como los escribio la query
+        let columns: Vec<String> = split_storage_values(line)
+            .into_iter()
+            .map(|s| unescape_storage_text(s.trim()))
+            .collect();
         let column_value_map = self.create_column_value_map(table, &columns, false);
 
         let columns = table.get_columns();
@@ -275,6 +326,7 @@ mod tests {
     use super::*;
     use query_creator::clauses::condition::Condition;
     use query_creator::clauses::delete_cql::Delete;
+    use query_creator::clauses::select_cql::Select;
     use query_creator::clauses::table::create_table_cql::CreateTable;
     use query_creator::logical_operator::LogicalOperator;
     use query_creator::operator::Operator;
@@ -288,7 +340,7 @@ mod tests {
         let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
 
         let table_path = storage
-            .get_keyspace_path(keyspace)
+            .get_keyspace_path(keyspace, table_name)
             .join(format!("{}.csv", table_name));
         fs::create_dir_all(table_path.parent().unwrap()).unwrap();
 
@@ -339,9 +391,10 @@ mod tests {
         let reader = BufReader::new(file);
         let lines: Vec<_> = reader.lines().map(|l| l.unwrap()).collect();
 
-        // La fila con id=2 debería haber sido eliminada
-        assert_eq!(lines.len(), 2); // Header + 1 row
+        // La fila con id=2 debería haber sido reemplazada por un tombstone
+        assert_eq!(lines.len(), 3); // Header + 1 row + 1 tombstone
         assert_eq!(lines[1], "1,John,30;1234567890");
+        assert_eq!(lines[2], "2,Alice,25;1234567890;tombstone");
     }
 
     #[test]
@@ -352,7 +405,7 @@ mod tests {
         let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
 
         let table_path = storage
-            .get_keyspace_path(keyspace)
+            .get_keyspace_path(keyspace, table_name)
             .join(format!("{}.csv", table_name));
         fs::create_dir_all(table_path.parent().unwrap()).unwrap();
 
@@ -412,10 +465,11 @@ mod tests {
         let reader = BufReader::new(file);
         let lines: Vec<_> = reader.lines().map(|l| l.unwrap()).collect();
 
-        // La fila con name=Alice y age=25 debería haber sido eliminada
-        assert_eq!(lines.len(), 3); // Header + 2 rows
+        // La fila con name=Alice y age=25 debería haber sido reemplazada por un tombstone
+        assert_eq!(lines.len(), 4); // Header + 2 rows + 1 tombstone
         assert_eq!(lines[1], "1,John,30;1234567890");
-        assert_eq!(lines[2], "3,Bob,40;1234567890");
+        assert_eq!(lines[2], "2,Alice,25;1234567890;tombstone");
+        assert_eq!(lines[3], "3,Bob,40;1234567890");
     }
 
     #[test]
@@ -426,7 +480,7 @@ mod tests {
         let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
 
         let table_path = storage
-            .get_keyspace_path(keyspace)
+            .get_keyspace_path(keyspace, table_name)
             .join(format!("{}.csv", table_name));
         fs::create_dir_all(table_path.parent().unwrap()).unwrap();
 
@@ -480,4 +534,205 @@ mod tests {
         assert_eq!(lines.len(), 2); // Header + 1 row
         assert_eq!(lines[1], "1,John,30;1234567890");
     }
+
+    #[test]
+    fn test_delete_by_partition_key_deletes_the_whole_partition() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", uuid::Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        let keyspace = "test_keyspace";
+        let table_name = "positions";
+        let table = TableSchema::new(
+            CreateTable::new_from_tokens(vec![
+                "CREATE".to_string(),
+                "TABLE".to_string(),
+                format!("{}.{}", keyspace, table_name),
+                "flight TEXT, time INT, height INT, PRIMARY KEY (flight, time)".to_string(),
+            ])
+            .unwrap(),
+        );
+
+        let folder_path = storage.get_keyspace_path(keyspace, table_name);
+        fs::create_dir_all(&folder_path).unwrap();
+        fs::write(
+            folder_path.join(format!("{}.csv", table_name)),
+            "flight,time,height\n",
+        )
+        .unwrap();
+        let insert = |values: Vec<&str>, timestamp: i64| {
+            storage
+                .insert(
+                    keyspace,
+                    table_name,
+                    values,
+                    table.get_columns(),
+                    table.get_clustering_column_in_order(),
+                    false,
+                    false,
+                    timestamp,
+                )
+                .unwrap();
+        };
+        for (flight, time) in [("AR1", "1"), ("AR2", "2"), ("AR1", "3"), ("AR1", "4")] {
+            insert(vec![flight, time, "100"], 10);
+        }
+
+        let delete_query = Delete::deserialize(&format!(
+            "DELETE FROM {}.{} WHERE flight = AR1",
+            keyspace, table_name
+        ))
+        .unwrap();
+        assert!(storage
+            .delete(delete_query, table.clone(), keyspace, false, 20)
+            .unwrap());
+
+        let live_rows = || -> Vec<String> {
+            let select = Select::deserialize(&format!(
+                "SELECT flight,time FROM {}.{} WHERE height > 0",
+                keyspace, table_name
+            ))
+            .unwrap();
+            storage
+                .select(select, table.clone(), false, keyspace)
+                .unwrap()[2..]
+                .iter()
+                .filter(|row| !row.ends_with(TOMBSTONE))
+                .cloned()
+                .collect()
+        };
+
+        // Las filas de AR1 se devuelven como tombstones con el timestamp del borrado
+        assert_eq!(live_rows(), vec!["AR2,2,100;10".to_string()]);
+
+        // Una fila escrita despues del borrado vuelve a verse
+        insert(vec!["AR1", "5", "100"], 30);
+        let mut rows = live_rows();
+        rows.sort();
+        assert_eq!(
+            rows,
+            vec!["AR1,5,100;30".to_string(), "AR2,2,100;10".to_string()]
+        );
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_rewrites_drop_the_tombstones_past_the_grace_period() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", uuid::Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string())
+            .with_tombstone_grace_seconds(100);
+        let keyspace = "test_keyspace";
+        let table_name = "test_table";
+        let table = TableSchema::new(
+            CreateTable::new_from_tokens(vec![
+                "CREATE".to_string(),
+                "TABLE".to_string(),
+                format!("{}.{}", keyspace, table_name),
+                "id INT, name TEXT, PRIMARY KEY (id)".to_string(),
+            ])
+            .unwrap(),
+        );
+        let now = chrono::Utc::now().timestamp();
+
+        let table_path = storage
+            .get_keyspace_path(keyspace, table_name)
+            .join(format!("{}.csv", table_name));
+        fs::create_dir_all(table_path.parent().unwrap()).unwrap();
+        fs::write(
+            &table_path,
+            format!(
+                "id,name\n1,John;{};tombstone\n2,Alice;{};tombstone\n3,Bob;{}\n",
+                now - 200,
+                now - 50,
+                now - 300
+            ),
+        )
+        .unwrap();
+
+        let delete_query = Delete::deserialize(&format!(
+            "DELETE FROM {}.{} WHERE id = 3",
+            keyspace, table_name
+        ))
+        .unwrap();
+        assert!(storage
+            .delete(delete_query, table, keyspace, false, now)
+            .unwrap());
+
+        // El tombstone vencido se descarta, el reciente y el nuevo se conservan
+        let lines: Vec<String> = fs::read_to_string(&table_path)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "id,name".to_string(),
+                format!("2,Alice;{};tombstone", now - 50),
+                format!("3,Bob;{};tombstone", now),
+            ]
+        );
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_rewrites_drop_the_partitions_deleted_before_the_grace_period() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", uuid::Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string())
+            .with_tombstone_grace_seconds(100);
+        let keyspace = "test_keyspace";
+        let table_name = "positions";
+        let table = TableSchema::new(
+            CreateTable::new_from_tokens(vec![
+                "CREATE".to_string(),
+                "TABLE".to_string(),
+                format!("{}.{}", keyspace, table_name),
+                "flight TEXT, time INT, height INT, PRIMARY KEY (flight, time)".to_string(),
+            ])
+            .unwrap(),
+        );
+        let now = chrono::Utc::now().timestamp();
+
+        let folder_path = storage.get_keyspace_path(keyspace, table_name);
+        fs::create_dir_all(&folder_path).unwrap();
+        let table_path = folder_path.join(format!("{}.csv", table_name));
+        fs::write(&table_path, "flight,time,height\n").unwrap();
+        let insert = |values: Vec<&str>, timestamp: i64| {
+            storage
+                .insert(
+                    keyspace,
+                    table_name,
+                    values,
+                    table.get_columns(),
+                    table.get_clustering_column_in_order(),
+                    false,
+                    false,
+                    timestamp,
+                )
+                .unwrap();
+        };
+        insert(vec!["AR1", "1", "100"], now - 300);
+        insert(vec!["AR2", "2", "100"], now - 300);
+        let delete_query = Delete::deserialize(&format!(
+            "DELETE FROM {}.{} WHERE flight = AR1",
+            keyspace, table_name
+        ))
+        .unwrap();
+        assert!(storage
+            .delete(delete_query, table.clone(), keyspace, false, now - 200)
+            .unwrap());
+
+        // La siguiente escritura descarta el borrado vencido junto con las filas que borraba
+        insert(vec!["AR2", "3", "100"], now);
+        let rows = fs::read_to_string(&table_path).unwrap();
+        assert!(!rows.contains("AR1"));
+        assert!(rows.contains("AR2,2,100"));
+        let tombstones = fs::read_to_string(
+            folder_path.join(format!("{}_partition_tombstones.csv", table_name)),
+        )
+        .unwrap();
+        assert_eq!(tombstones, "partition_key,timestamp\n");
+
+        fs::remove_dir_all(&root).ok();
+    }
 }
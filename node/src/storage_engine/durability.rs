@@ -0,0 +1,282 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::Duration;
+
+use super::errors::StorageEngineError;
+
+/// When the table files a `StorageEngine` writes are forced to disk with fsync.
+///
+/// Until a file is synced, a crash of the machine (not only of the node) can lose the last
+/// writes to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DurabilityPolicy {
+    /// Every write is synced before it is acknowledged.
+    #[default]
+    Always,
+    /// The files written are synced in the background every that many milliseconds.
+    EveryMillis(u64),
+    /// Files are only synced by an explicit `flush`, or whenever the OS decides to.
+    Never,
+}
+
+/// Fsync policy of a `StorageEngine`, with the files written and not synced yet.
+///
+/// Clones share the same pending files, so the engines a node creates for each query can all
+/// be flushed together. With `DurabilityPolicy::EveryMillis` a background thread flushes them,
+/// and it stops once every clone is dropped.
+#[derive(Debug, Clone, Default)]
+pub struct Durability {
+    policy: DurabilityPolicy,
+    state: Arc<DurabilityState>,
+}
+
+#[derive(Debug, Default)]
+struct DurabilityState {
+    pending: Mutex<HashSet<PathBuf>>,
+    syncs: AtomicU64,
+}
+
+impl Durability {
+    pub fn new(policy: DurabilityPolicy) -> Self {
+        let durability = Durability {
+            policy,
+            state: Arc::new(DurabilityState::default()),
+        };
+        if let DurabilityPolicy::EveryMillis(millis) = policy {
+            let state = Arc::downgrade(&durability.state);
+            thread::spawn(move || flush_periodically(state, Duration::from_millis(millis)));
+        }
+        durability
+    }
+
+    pub fn policy(&self) -> DurabilityPolicy {
+        self.policy
+    }
+
+    /// Replaces the table file at `path` with `temp_path`, that holds its new content.
+    ///
+    /// With `DurabilityPolicy::Always` the new content is synced before the rename and the
+    /// folder after it, so a crash leaves either the old file or the whole new one. With the
+    /// other policies the file is kept for the next flush.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::IoError` if the file cannot be renamed, or it or its folder
+    ///   cannot be synced.
+    pub(crate) fn replace_file(
+        &self,
+        temp_path: &Path,
+        path: &Path,
+    ) -> Result<(), StorageEngineError> {
+        match self.policy {
+            DurabilityPolicy::Always => {
+                File::open(temp_path)?.sync_all()?;
+                fs::rename(temp_path, path)?;
+                self.state.sync_folder(path)?;
+                self.state.syncs.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            DurabilityPolicy::EveryMillis(_) | DurabilityPolicy::Never => {
+                fs::rename(temp_path, path)?;
+                self.state
+                    .pending
+                    .lock()
+                    .map_err(|_| StorageEngineError::LockError)?
+                    .insert(path.to_path_buf());
+                Ok(())
+            }
+        }
+    }
+
+    /// Syncs every file written and not synced yet, whatever the policy.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::IoError` if a file cannot be synced. The files that were not
+    ///   synced are kept for the next flush.
+    pub fn flush(&self) -> Result<(), StorageEngineError> {
+        self.state.flush()
+    }
+
+    /// Files written and not synced yet.
+    pub fn pending_files(&self) -> usize {
+        self.state.pending.lock().map_or(0, |pending| pending.len())
+    }
+
+    /// How many times a file was synced, by every clone.
+    pub fn syncs(&self) -> u64 {
+        self.state.syncs.load(Ordering::Relaxed)
+    }
+}
+
+impl DurabilityState {
+    fn flush(&self) -> Result<(), StorageEngineError> {
+        let paths: Vec<PathBuf> = self
+            .pending
+            .lock()
+            .map_err(|_| StorageEngineError::LockError)?
+            .drain()
+            .collect();
+
+        for (i, path) in paths.iter().enumerate() {
+            if let Err(e) = self.sync(path) {
+                // Los que faltan se sincronizan en el proximo flush
+                if let Ok(mut pending) = self.pending.lock() {
+                    pending.extend(paths[i..].iter().cloned());
+                }
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    // Sincroniza el archivo y su carpeta, para que tambien quede el rename que lo reemplazo
+    fn sync(&self, path: &Path) -> Result<(), StorageEngineError> {
+        match File::open(path) {
+            Ok(file) => file.sync_all()?,
+            // Se borro la tabla, no hay nada que sincronizar
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+        self.sync_folder(path)?;
+        self.syncs.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    // Sincroniza la carpeta del archivo, donde queda registrado el rename
+    fn sync_folder(&self, path: &Path) -> Result<(), StorageEngineError> {
+        if let Some(folder) = path.parent() {
+            File::open(folder)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+fn flush_periodically(state: Weak<DurabilityState>, every: Duration) {
+    loop {
+        thread::sleep(every);
+        let Some(state) = state.upgrade() else {
+            return;
+        };
+        // Si falla, los archivos quedan pendientes para la proxima vuelta
+        let _ = state.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage_engine::StorageEngine;
+    use query_creator::clauses::types::{column::Column, datatype::DataType};
+    use std::time::Instant;
+    use uuid::Uuid;
+
+    fn insert_rows(storage: &StorageEngine, rows: &[&str]) {
+        let columns = vec![
+            Column::new("id", DataType::Int, true, false),
+            Column::new("origin", DataType::String, false, false),
+        ];
+        for (i, id) in rows.iter().enumerate() {
+            storage
+                .insert(
+                    "sky",
+                    "flights",
+                    vec![id, "EZE"],
+                    columns.clone(),
+                    vec![],
+                    false,
+                    false,
+                    i as i64 + 1,
+                )
+                .unwrap();
+        }
+    }
+
+    fn storage_with(policy: DurabilityPolicy) -> (StorageEngine, Durability, PathBuf) {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let durability = Durability::new(policy);
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string())
+            .with_durability(durability.clone());
+        storage
+            .create_table("sky", "flights", vec!["id", "origin"])
+            .unwrap();
+        (storage, durability, root)
+    }
+
+    #[test]
+    fn test_always_syncs_every_write_and_never_only_on_flush() {
+        let (storage, always, root) = storage_with(DurabilityPolicy::Always);
+        insert_rows(&storage, &["1", "2", "3"]);
+        assert_eq!(always.syncs(), 3);
+        assert_eq!(always.pending_files(), 0);
+        fs::remove_dir_all(root).ok();
+
+        let (storage, never, root) = storage_with(DurabilityPolicy::Never);
+        insert_rows(&storage, &["1", "2", "3"]);
+        assert_eq!(never.syncs(), 0);
+        // Las escrituras a la misma tabla se sincronizan una sola vez
+        assert_eq!(never.pending_files(), 1);
+
+        storage.flush().unwrap();
+        assert_eq!(never.syncs(), 1);
+        assert_eq!(never.pending_files(), 0);
+        let contents = fs::read_to_string(
+            storage
+                .get_keyspace_path("sky", "flights")
+                .join("flights.csv"),
+        )
+        .unwrap();
+        assert_eq!(contents.lines().count(), 4);
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_always_syncs_the_rewrites_of_a_column_change() {
+        let (storage, always, root) = storage_with(DurabilityPolicy::Always);
+        insert_rows(&storage, &["1"]);
+        let syncs = always.syncs();
+
+        // Se reescriben la tabla y su replicacion
+        storage
+            .add_column_to_table("sky", "flights", "gate")
+            .unwrap();
+        storage
+            .rename_column_from_table("sky", "flights", "gate", "terminal")
+            .unwrap();
+        storage
+            .remove_column_from_table("sky", "flights", "terminal")
+            .unwrap();
+        assert_eq!(always.syncs(), syncs + 6);
+
+        let contents = fs::read_to_string(
+            storage
+                .get_keyspace_path("sky", "flights")
+                .join("flights.csv"),
+        )
+        .unwrap();
+        assert_eq!(contents, "id,origin\n1,EZE;1\n");
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_every_millis_syncs_in_the_background() {
+        let (storage, durability, root) = storage_with(DurabilityPolicy::EveryMillis(20));
+        insert_rows(&storage, &["1"]);
+        assert_eq!(durability.syncs(), 0);
+
+        let started = Instant::now();
+        while durability.syncs() == 0 {
+            assert!(
+                started.elapsed() < Duration::from_secs(2),
+                "the write was never synced"
+            );
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(durability.pending_files(), 0);
+
+        fs::remove_dir_all(root).ok();
+    }
+}
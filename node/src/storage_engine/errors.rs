@@ -1,3 +1,5 @@
+use native_protocol::messages::error::Error;
+
 /// Enumeration of possible errors that can be returned by the `StorageEngine`.
 ///
 /// This enum represents various types of errors that can occur during operations
@@ -86,6 +88,37 @@ pub enum StorageEngineError {
     /// This error is returned when an operation is attempted that is not supported
     /// by the storage engine.
     UnsupportedOperation,
+
+    /// Error when the lock of a table cannot be acquired.
+    ///
+    /// This error occurs when a thread panicked while holding the lock of the table.
+    LockError,
+
+    /// Error when a table already exists with a different set of columns.
+    ///
+    /// This error occurs when creating a table whose files already exist with another header.
+    TableHeaderMismatch,
+
+    /// Error when the clustering columns given to an insert are not the ones of the table.
+    ///
+    /// This error occurs when they are missing, repeated, unknown, or in another order than
+    /// the one the table declares, which would leave the rows of the file mis-sorted.
+    ClusteringOrderMismatch,
+
+    /// Error when the type of a column cannot change because some values do not fit in it.
+    ///
+    /// This error carries the column, the new type and the rows, without their timestamp,
+    /// whose value cannot be represented in that type.
+    IncompatibleColumnType {
+        column: String,
+        data_type: String,
+        rows: Vec<String>,
+    },
+
+    /// Error when a write would take a keyspace over its quota.
+    ///
+    /// This error carries the keyspace and its quota in bytes. Nothing is written.
+    StorageFull { keyspace: String, quota: u64 },
 }
 
 impl std::fmt::Display for StorageEngineError {
@@ -120,12 +153,76 @@ impl std::fmt::Display for StorageEngineError {
                 write!(f, "Clustering key values are incomplete or mismatched.")
             }
             StorageEngineError::UnsupportedOperation => write!(f, "This operation is unsupported."),
+            StorageEngineError::LockError => write!(f, "Failed to lock the table."),
+            StorageEngineError::TableHeaderMismatch => {
+                write!(f, "The table already exists with different columns.")
+            }
+            StorageEngineError::ClusteringOrderMismatch => {
+                write!(
+                    f,
+                    "The clustering columns do not match the order of the table."
+                )
+            }
+            StorageEngineError::IncompatibleColumnType {
+                column,
+                data_type,
+                rows,
+            } => {
+                write!(
+                    f,
+                    "{} row(s) cannot change column {} to {}: {}",
+                    rows.len(),
+                    column,
+                    data_type,
+                    rows.join(" | ")
+                )
+            }
+            StorageEngineError::StorageFull { keyspace, quota } => {
+                write!(
+                    f,
+                    "Keyspace {} is full: its quota is {} bytes.",
+                    keyspace, quota
+                )
+            }
         }
     }
 }
 
 impl std::error::Error for StorageEngineError {}
 
+impl From<&StorageEngineError> for Error {
+    /// Maps the error to the native protocol error reported to the client.
+    ///
+    /// Queries that do not fit the table are `Invalid`, creating a table that exists with other
+    /// columns is `AlreadyExists` and a full keyspace is `Overloaded`. Failures of the files or
+    /// locks of the node are a `ServerError`.
+    fn from(error: &StorageEngineError) -> Self {
+        let message = error.to_string();
+        match error {
+            StorageEngineError::InvalidQuery
+            | StorageEngineError::PrimaryKeyModificationNotAllowed
+            | StorageEngineError::ColumnNotFound
+            | StorageEngineError::MissingWhereClause
+            | StorageEngineError::PartitionKeyMismatch
+            | StorageEngineError::ClusteringKeyMismatch
+            | StorageEngineError::ClusteringOrderMismatch
+            | StorageEngineError::UnsupportedOperation
+            | StorageEngineError::IncompatibleColumnType { .. }
+            | StorageEngineError::FileNotFound => Error::Invalid(message),
+            StorageEngineError::TableHeaderMismatch => Error::AlreadyExists(message),
+            StorageEngineError::StorageFull { .. } => Error::Overloaded(message),
+            StorageEngineError::IoError
+            | StorageEngineError::TempFileCreationFailed
+            | StorageEngineError::FileWriteFailed
+            | StorageEngineError::FileReadFailed
+            | StorageEngineError::FileDeletionFailed
+            | StorageEngineError::DirectoryCreationFailed
+            | StorageEngineError::FileReplacementFailed
+            | StorageEngineError::LockError => Error::ServerError(message),
+        }
+    }
+}
+
 impl From<std::io::Error> for StorageEngineError {
     fn from(_: std::io::Error) -> Self {
         StorageEngineError::IoError
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use native_protocol::messages::result::rows::split_storage_values;
+use query_creator::clauses::select_cql::{Select, COUNT_ALL};
+
+use super::{
+    errors::StorageEngineError, parse_row_time, partition_tombstone::partition_key_of,
+    split_stored_row,
+};
+
+/// Returns the query that reads the rows a `GROUP BY` aggregates: every matching row, since
+/// `LIMIT`, `PER PARTITION LIMIT` and `ORDER BY` apply to the groups and not to the rows.
+pub(crate) fn rows_of_groups(select_query: &Select) -> Select {
+    let mut rows_query = select_query.clone();
+    rows_query.group_by = Vec::new();
+    rows_query.orderby_clause = None;
+    rows_query.per_partition_limit = None;
+    rows_query.limit = None;
+    rows_query.distinct = false;
+    rows_query
+}
+
+/// Groups `results`, as returned by `select`, by the `GROUP BY` columns of `select_query`.
+///
+/// Each group, in the order its first row was read, becomes a single row with the values of
+/// the `GROUP BY` columns followed by the requested ones: `COUNT(*)` is the number of live rows
+/// of the group and any other column takes the value of its first row. The timestamp of the
+/// group is the one of its newest row. Tombstones are not counted, and a group without live
+/// rows is left out.
+///
+/// The first header is the `GROUP BY` columns followed by the requested ones, and the second
+/// the requested columns, as in `select`. `LIMIT` keeps only the first groups.
+///
+/// # Errors
+/// - `StorageEngineError::ColumnNotFound` if a `GROUP BY` or requested column is not in the
+///   first header of `results`.
+pub(crate) fn group_rows(
+    results: Vec<String>,
+    select_query: &Select,
+) -> Result<Vec<String>, StorageEngineError> {
+    let mut results = results.into_iter();
+    let complete_columns: Vec<String> = results
+        .next()
+        .unwrap_or_default()
+        .split(',')
+        .map(String::from)
+        .collect();
+    // La segunda cabecera la reemplazan las columnas del grupo
+    results.next();
+
+    let index_of = |name: &str| {
+        complete_columns
+            .iter()
+            .position(|column| column == name)
+            .ok_or(StorageEngineError::ColumnNotFound)
+    };
+    let group_indexes = select_query
+        .group_by
+        .iter()
+        .map(|column| index_of(column))
+        .collect::<Result<Vec<usize>, _>>()?;
+    // `None` es `COUNT(*)`, que no es una columna de la tabla
+    let selected_indexes = select_query
+        .columns
+        .iter()
+        .map(|column| match column.as_str() {
+            COUNT_ALL => Ok(None),
+            column => index_of(column).map(Some),
+        })
+        .collect::<Result<Vec<Option<usize>>, _>>()?;
+
+    // Por cada grupo: los valores de su primera fila, cuantas filas tiene y su ultima escritura
+    let mut groups: Vec<(Vec<String>, u64, i64)> = Vec::new();
+    let mut position_of_group: HashMap<String, usize> = HashMap::new();
+    for row in results {
+        let (values, time_of_row) = split_stored_row(&row).unwrap_or((&row, ""));
+        let (written_at, is_tombstone) = parse_row_time(time_of_row);
+        if is_tombstone {
+            continue;
+        }
+
+        let values: Vec<&str> = split_storage_values(values);
+        let group_key = partition_key_of(&values, &group_indexes);
+        match position_of_group.get(&group_key) {
+            Some(&i) => {
+                let (_, rows, newest) = &mut groups[i];
+                *rows += 1;
+                *newest = (*newest).max(written_at);
+            }
+            None => {
+                position_of_group.insert(group_key, groups.len());
+                groups.push((
+                    values.into_iter().map(String::from).collect(),
+                    1,
+                    written_at,
+                ));
+            }
+        }
+    }
+
+    let mut grouped = vec![
+        select_query
+            .group_by
+            .iter()
+            .chain(&select_query.columns)
+            .cloned()
+            .collect::<Vec<String>>()
+            .join(","),
+        select_query.columns.join(","),
+    ];
+    let limit = select_query.limit.unwrap_or(usize::MAX);
+    for (values, rows, newest) in groups.into_iter().take(limit) {
+        let value_at = |i: usize| values.get(i).cloned().unwrap_or_default();
+        let row: Vec<String> = group_indexes
+            .iter()
+            .map(|&i| value_at(i))
+            .chain(selected_indexes.iter().map(|index| match index {
+                Some(i) => value_at(*i),
+                None => rows.to_string(),
+            }))
+            .collect();
+        grouped.push(format!("{};{}", row.join(","), newest));
+    }
+    Ok(grouped)
+}
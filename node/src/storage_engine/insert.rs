@@ -4,8 +4,13 @@ use std::{
 };
 
 use query_creator::{clauses::types::column::Column, operator::Operator};
+use uuid::Uuid;
 
-use super::{errors::StorageEngineError, StorageEngine};
+use super::{
+    errors::StorageEngineError, parse_row_time, split_stored_row, tombstone_purge::TombstonePurge,
+    StorageEngine,
+};
+use native_protocol::messages::result::rows::{split_storage_values, unescape_storage_text};
 
 impl StorageEngine {
     /// Inserts a new row into a table within the specified keyspace.
@@ -20,7 +25,8 @@ impl StorageEngine {
     /// # Arguments
     /// - `keyspace`: The name of the keyspace where the table resides.
     /// - `table`: The name of the table into which the row will be inserted.
-    /// - `values`: A vector of string slices representing the values for the row, in column order.
+    /// - `values`: A vector of string slices representing the values for the row, in column order,
+    ///   as they are stored: the values of a query are escaped first with `escape_value`.
     /// - `columns`: A vector of `Column` structs defining the table's schema.
     /// - `clustering_columns_in_order`: A vector of strings indicating the clustering columns and their order.
     /// - `is_replication`: A boolean indicating whether the insertion is part of a replication process.
@@ -28,12 +34,18 @@ impl StorageEngine {
     /// - `timestamp`: A 64-bit integer representing the timestamp of the operation.
     ///
     /// # Returns
-    /// - `Ok(())`: If the row is successfully inserted.
+    /// - `Ok(true)`: If the row is successfully inserted.
+    /// - `Ok(false)`: If `if_not_exist` is `true` and a row with the same primary key already exists,
+    ///   in which case nothing is written.
     /// - `Err(StorageEngineError)`: If an error occurs during the operation, such as:
     ///   - `DirectoryCreationFailed`: When the required directories cannot be created.
     ///   - `IoError`: For issues reading or writing to files.
     ///   - `UnsupportedOperation`: If an unsupported operation is encountered (e.g., invalid data type comparison).
     ///   - `TempFileCreationFailed`: If a temporary file cannot be created.
+    ///   - `ClusteringOrderMismatch`: If `clustering_columns_in_order` does not match the table
+    ///     (see [`StorageEngine::validate_clustering_order`]). Nothing is written in that case.
+    ///   - `StorageFull`: If the row would take the keyspace over its quota (see
+    ///     [`StorageEngine::with_keyspace_quota`]). Nothing is written in that case.
     ///
     /// # Behavior
     /// - If the table file does not exist:
@@ -54,7 +66,8 @@ impl StorageEngine {
     /// # Edge Cases
     /// - **Empty `values` or `columns`:** The function will return an error if the values or columns are missing.
     /// - **Invalid clustering order:** If a clustering column's order is unspecified or inconsistent, an error may occur.
-    /// - **Concurrent writes:** Simultaneous calls to `insert` on the same table may cause unexpected behavior and are not supported.
+    /// - **Concurrent writes:** Writes on the same table are serialized by the table lock, so the
+    ///   existence check of `if_not_exist` and the insertion happen atomically.
     ///
     /// # Limitations
     /// - The function currently supports only `.csv` file formats.
@@ -70,10 +83,12 @@ impl StorageEngine {
         is_replication: bool,
         if_not_exist: bool,
         timestamp: i64,
-    ) -> Result<(), StorageEngineError> {
-        let folder_path =
-            self.get_keyspace_path(keyspace)
-                .join(if is_replication { "replication" } else { "" });
+    ) -> Result<bool, StorageEngineError> {
+        self.validate_clustering_order(keyspace, table, &columns, &clustering_columns_in_order)?;
+
+        let folder_path = self
+            .get_keyspace_path(keyspace, table)
+            .join(if is_replication { "replication" } else { "" });
 
         if !folder_path.exists() {
             fs::create_dir_all(&folder_path)
@@ -81,13 +96,34 @@ impl StorageEngine {
         }
 
         let file_path = folder_path.join(format!("{}.csv", table));
-        let temp_file_path = folder_path.join(format!("temp_{}.csv", timestamp));
+        let temp_file_path = folder_path.join(format!("{}_{}.tmp", table, Uuid::new_v4()));
         let index_file_path = folder_path.join(format!("{}_index.csv", table));
 
         let clustering_indices =
             Self::get_clustering_indices(&columns, &clustering_columns_in_order)?;
         let partition_key_indices = Self::get_partition_key_indices(&columns);
 
+        let table_lock = Self::table_lock(&file_path)?;
+        let _table_guard = table_lock
+            .lock()
+            .map_err(|_| StorageEngineError::LockError)?;
+
+        if if_not_exist
+            && Self::row_exists(
+                &file_path,
+                &values,
+                &partition_key_indices,
+                &clustering_indices,
+            )?
+        {
+            return Ok(false);
+        }
+
+        // `valores;timestamp` y el salto de linea
+        let row_bytes = values.join(",").len() + timestamp.to_string().len() + 2;
+        self.check_keyspace_quota(keyspace, row_bytes as u64)?;
+
+        let purge = TombstonePurge::new(self, &folder_path, table, partition_key_indices.clone())?;
         let mut inserted = false;
         let mut current_byte_offset: u64 = 0;
         let mut index_map = std::collections::BTreeMap::new();
@@ -113,18 +149,22 @@ impl StorageEngine {
             }
             for (_, line) in lines.enumerate() {
                 let line = line.map_err(|_| StorageEngineError::IoError)?;
+                if purge.is_purged(&line) {
+                    continue;
+                }
                 let line_length = line.len() as u64;
 
                 let (line_content, row_timestamp) = Self::split_line(&line)?;
-                let row: Vec<&str> = line_content.split(',').collect();
+                let row: Vec<&str> = split_storage_values(line_content);
 
                 let is_same_partition =
                     Self::is_same_partition(&row, &values, &partition_key_indices);
                 let clustering_cmp =
                     Self::compare_clustering(&row, &values, &clustering_indices, &columns)?;
 
-                if clustering_cmp == std::cmp::Ordering::Equal {
-                    if is_same_partition && if_not_exist {
+                // Una fila de otra particion con la misma clave de clustering se conserva
+                if clustering_cmp == std::cmp::Ordering::Equal && is_same_partition {
+                    if if_not_exist && !parse_row_time(row_timestamp).1 {
                         writeln!(temp_file, "{};{}", line_content, row_timestamp)
                             .map_err(|_| StorageEngineError::IoError)?;
                         current_byte_offset += line_length + 1;
@@ -190,8 +230,258 @@ impl StorageEngine {
                 .map_err(|_| StorageEngineError::IoError)?;
         }
 
-        fs::rename(&temp_file_path, &file_path).map_err(|_| StorageEngineError::IoError)?;
-        Ok(())
+        self.replace_table_file(&temp_file_path, &file_path)?;
+        purge.finish(self, &folder_path, table)?;
+        Ok(true)
+    }
+
+    /// Inserts several rows into a table, rewriting the table file only once.
+    ///
+    /// Calling `insert` for each row rewrites the whole `.csv` every time. Here the current rows are
+    /// read once, every new row is merged into them in memory and the result is written to a single
+    /// temporary file that replaces the table.
+    ///
+    /// # Arguments
+    /// - `keyspace`: The name of the keyspace where the table resides.
+    /// - `table`: The name of the table into which the rows will be inserted.
+    /// - `rows`: The values of each row, in column order and escaped as they are stored, with the
+    ///   timestamp of its write.
+    /// - `columns`: A vector of `Column` structs defining the table's schema.
+    /// - `clustering_columns_in_order`: A vector of strings indicating the clustering columns and their order.
+    /// - `is_replication`: A boolean indicating whether the insertion is part of a replication process.
+    /// - `if_not_exist`: Whether each row should only be inserted if its primary key is not in the table yet.
+    ///
+    /// # Returns
+    /// - `Ok(applied)`: One flag per row of `rows`, `false` for the rows skipped by `if_not_exist`.
+    /// - `Err(StorageEngineError)`: For the same errors as `insert`. The quota of the keyspace
+    ///   is checked for all the rows together, so either every row fits or none is written.
+    ///
+    /// # Behavior
+    /// Rows are applied in the given order with the semantics of `insert`, so the resulting file is the
+    /// same as the one left by calling `insert` once per row:
+    /// - A row with the primary key of an existing row replaces it (the last write wins). Rows
+    ///   of other partitions with the same clustering key are kept.
+    /// - `if_not_exist` also sees the rows inserted earlier in the same batch.
+    /// - Tables without clustering columns keep the rows in insertion order.
+    #[allow(clippy::too_many_arguments)]
+    pub fn batch_insert(
+        &self,
+        keyspace: &str,
+        table: &str,
+        rows: Vec<(Vec<&str>, i64)>,
+        columns: Vec<Column>,
+        clustering_columns_in_order: Vec<String>,
+        is_replication: bool,
+        if_not_exist: bool,
+    ) -> Result<Vec<bool>, StorageEngineError> {
+        let folder_path = self
+            .get_keyspace_path(keyspace, table)
+            .join(if is_replication { "replication" } else { "" });
+
+        if !folder_path.exists() {
+            fs::create_dir_all(&folder_path)
+                .map_err(|_| StorageEngineError::DirectoryCreationFailed)?;
+        }
+
+        let file_path = folder_path.join(format!("{}.csv", table));
+        let index_file_path = folder_path.join(format!("{}_index.csv", table));
+
+        let clustering_indices =
+            Self::get_clustering_indices(&columns, &clustering_columns_in_order)?;
+        let partition_key_indices = Self::get_partition_key_indices(&columns);
+
+        let table_lock = Self::table_lock(&file_path)?;
+        let _table_guard = table_lock
+            .lock()
+            .map_err(|_| StorageEngineError::LockError)?;
+
+        let rows_bytes: usize = rows
+            .iter()
+            .map(|(values, timestamp)| values.join(",").len() + timestamp.to_string().len() + 2)
+            .sum();
+        self.check_keyspace_quota(keyspace, rows_bytes as u64)?;
+
+        // Se leen las filas actuales una sola vez, sin los borrados que ya vencieron
+        let purge = TombstonePurge::new(self, &folder_path, table, partition_key_indices.clone())?;
+        let mut header = None;
+        let mut lines: Vec<String> = vec![];
+        if let Ok(file) = File::open(&file_path) {
+            let mut reader_lines = BufReader::new(file).lines();
+            if let Some(header_line) = reader_lines.next() {
+                header = Some(header_line.map_err(|_| StorageEngineError::IoError)?);
+            }
+            for line in reader_lines {
+                let line = line.map_err(|_| StorageEngineError::IoError)?;
+                if !purge.is_purged(&line) {
+                    lines.push(line);
+                }
+            }
+        }
+
+        let mut applied = Vec::with_capacity(rows.len());
+        for (values, timestamp) in rows {
+            let new_line = format!("{};{}", values.join(","), timestamp);
+
+            if clustering_indices.is_empty() {
+                // Sin clustering columns `insert` siempre agrega la fila al final
+                if if_not_exist
+                    && Self::lines_contain_partition(&lines, &values, &partition_key_indices)?
+                {
+                    applied.push(false);
+                    continue;
+                }
+                lines.push(new_line);
+                applied.push(true);
+                continue;
+            }
+
+            // Las filas estan ordenadas: se busca la primera que no va antes de la nueva
+            let mut position_error = None;
+            let position = lines.partition_point(|line| {
+                let ordering = Self::split_line(line).and_then(|(line_content, _)| {
+                    let row: Vec<&str> = split_storage_values(line_content);
+                    Self::compare_clustering(&row, &values, &clustering_indices, &columns)
+                });
+                match ordering {
+                    Ok(ordering) => ordering == std::cmp::Ordering::Less,
+                    Err(e) => {
+                        position_error = Some(e);
+                        false
+                    }
+                }
+            });
+            if let Some(e) = position_error {
+                return Err(e);
+            }
+
+            // Las filas con la misma clave de clustering pueden ser de otras particiones: la
+            // nueva reemplaza a la de su particion o va despues de todas ellas
+            let mut same_key_end = position;
+            let mut same_primary_key = None;
+            while let Some(line) = lines.get(same_key_end) {
+                let (line_content, time_of_row) = Self::split_line(line)?;
+                let row: Vec<&str> = split_storage_values(line_content);
+                if Self::compare_clustering(&row, &values, &clustering_indices, &columns)?
+                    != std::cmp::Ordering::Equal
+                {
+                    break;
+                }
+                if Self::is_same_partition(&row, &values, &partition_key_indices) {
+                    // Un tombstone con la misma clave se reemplaza como cualquier otra fila
+                    same_primary_key = Some((same_key_end, parse_row_time(time_of_row).1));
+                    break;
+                }
+                same_key_end += 1;
+            }
+
+            match same_primary_key {
+                Some((_, false)) if if_not_exist => applied.push(false),
+                Some((index, _)) => {
+                    lines[index] = new_line;
+                    applied.push(true);
+                }
+                None => {
+                    lines.insert(same_key_end, new_line);
+                    applied.push(true);
+                }
+            }
+        }
+
+        let temp_file_path = folder_path.join(format!("{}_{}.tmp", table, Uuid::new_v4()));
+        let mut temp_file =
+            BufWriter::new(File::create(&temp_file_path).map_err(|_| StorageEngineError::IoError)?);
+        let mut current_byte_offset: u64 = 0;
+        let mut index_map = std::collections::BTreeMap::new();
+
+        if let Some(header_line) = header {
+            writeln!(temp_file, "{}", header_line).map_err(|_| StorageEngineError::IoError)?;
+            current_byte_offset += header_line.len() as u64 + 1;
+        }
+        for line in &lines {
+            let line_length = line.len() as u64;
+            writeln!(temp_file, "{}", line).map_err(|_| StorageEngineError::IoError)?;
+
+            let (line_content, _) = Self::split_line(line)?;
+            let row: Vec<&str> = split_storage_values(line_content);
+            Self::update_index_map(
+                &row,
+                &clustering_indices,
+                &mut index_map,
+                current_byte_offset,
+                line_length,
+            );
+            current_byte_offset += line_length + 1;
+        }
+        temp_file.flush().map_err(|_| StorageEngineError::IoError)?;
+
+        let mut index_file = BufWriter::new(
+            File::create(&index_file_path).map_err(|_| StorageEngineError::IoError)?,
+        );
+        writeln!(index_file, "clustering_column,start_byte,end_byte")
+            .map_err(|_| StorageEngineError::IoError)?;
+        for (key, (start_byte, end_byte)) in index_map {
+            writeln!(index_file, "{},{},{}", key, start_byte, end_byte)
+                .map_err(|_| StorageEngineError::IoError)?;
+        }
+        index_file
+            .flush()
+            .map_err(|_| StorageEngineError::IoError)?;
+
+        self.replace_table_file(&temp_file_path, &file_path)?;
+        purge.finish(self, &folder_path, table)?;
+        Ok(applied)
+    }
+
+    // Checks if any of `lines` belongs to the partition of `values`.
+    fn lines_contain_partition(
+        lines: &[String],
+        values: &[&str],
+        partition_key_indices: &[usize],
+    ) -> Result<bool, StorageEngineError> {
+        for line in lines {
+            let (line_content, time_of_row) = Self::split_line(line)?;
+            let row: Vec<&str> = split_storage_values(line_content);
+            if !parse_row_time(time_of_row).1
+                && Self::is_same_partition(&row, values, partition_key_indices)
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    // Checks if the table stored in `file_path` already has a row with the primary key of `values`.
+    fn row_exists(
+        file_path: &std::path::Path,
+        values: &[&str],
+        partition_key_indices: &[usize],
+        clustering_indices: &[(usize, String)],
+    ) -> Result<bool, StorageEngineError> {
+        let file = match File::open(file_path) {
+            Ok(file) => file,
+            Err(_) => return Ok(false),
+        };
+
+        // Se saltea el encabezado
+        for line in BufReader::new(file).lines().skip(1) {
+            let line = line.map_err(|_| StorageEngineError::IoError)?;
+            let (line_content, time_of_row) = Self::split_line(&line)?;
+            // Una fila borrada no cuenta como existente
+            if parse_row_time(time_of_row).1 {
+                continue;
+            }
+            let row: Vec<&str> = split_storage_values(line_content);
+
+            let same_clustering = clustering_indices
+                .iter()
+                .all(|&(index, _)| row.get(index) == values.get(index));
+
+            if same_clustering && Self::is_same_partition(&row, values, partition_key_indices) {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
     }
 
     fn write_inserted_row(
@@ -219,7 +509,7 @@ impl StorageEngine {
         Ok(())
     }
 
-    fn update_index_map(
+    pub(super) fn update_index_map(
         row: &[&str],
         clustering_indices: &[(usize, String)],
         index_map: &mut std::collections::BTreeMap<String, (u64, u64)>,
@@ -235,7 +525,51 @@ impl StorageEngine {
         }
     }
 
-    fn get_clustering_indices(
+    /// Checks that `clustering_columns_in_order` is the clustering key of the table, so rows
+    /// are sorted the same way by every insert.
+    ///
+    /// Every name has to be a column of `columns` and appear only once. When `columns` marks
+    /// clustering columns, the names have to be exactly those. When the table has its
+    /// definition saved on disk, they also have to be in the order it declares.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::ClusteringOrderMismatch` if any of the checks fails.
+    pub fn validate_clustering_order(
+        &self,
+        keyspace: &str,
+        table: &str,
+        columns: &[Column],
+        clustering_columns_in_order: &[String],
+    ) -> Result<(), StorageEngineError> {
+        let mut seen = std::collections::HashSet::new();
+        for name in clustering_columns_in_order {
+            if !seen.insert(name) || !columns.iter().any(|column| column.name == *name) {
+                return Err(StorageEngineError::ClusteringOrderMismatch);
+            }
+        }
+
+        let marked: Vec<&String> = columns
+            .iter()
+            .filter(|column| column.is_clustering_column)
+            .map(|column| &column.name)
+            .collect();
+        if !marked.is_empty()
+            && (marked.len() != seen.len() || marked.iter().any(|name| !seen.contains(name)))
+        {
+            return Err(StorageEngineError::ClusteringOrderMismatch);
+        }
+
+        // El orden declarado en la definicion guardada es el que manda
+        if let Some(declared) = self.declared_clustering_order(keyspace, table)? {
+            if declared != clustering_columns_in_order {
+                return Err(StorageEngineError::ClusteringOrderMismatch);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn get_clustering_indices(
         columns: &[Column],
         clustering_columns: &[String],
     ) -> Result<Vec<(usize, String)>, StorageEngineError> {
@@ -266,8 +600,8 @@ impl StorageEngine {
             .collect()
     }
 
-    fn split_line(line: &str) -> Result<(&str, &str), StorageEngineError> {
-        line.split_once(";").ok_or(StorageEngineError::IoError)
+    pub(super) fn split_line(line: &str) -> Result<(&str, &str), StorageEngineError> {
+        split_stored_row(line).ok_or(StorageEngineError::IoError)
     }
 
     fn is_same_partition(row: &[&str], values: &[&str], partition_indices: &[usize]) -> bool {
@@ -276,7 +610,7 @@ impl StorageEngine {
             .all(|&index| row.get(index) == values.get(index))
     }
 
-    fn compare_clustering(
+    pub(super) fn compare_clustering(
         row: &[&str],
         values: &[&str],
         clustering_indices: &[(usize, String)],
@@ -289,9 +623,14 @@ impl StorageEngine {
             let row_val = row.get(idx).unwrap_or(&"");
             let value = values.get(idx).unwrap_or(&"");
             if row_val != value {
+                // Se comparan los valores como los escribio la query, no escapados
                 let is_less = columns[idx]
                     .data_type
-                    .compare(row_val, value, &Operator::Lesser)
+                    .compare(
+                        &unescape_storage_text(row_val),
+                        &unescape_storage_text(value),
+                        &Operator::Lesser,
+                    )
                     .map_err(|_| StorageEngineError::UnsupportedOperation)?;
                 return Ok(match (is_less, order.as_str()) {
                     (true, "DESC") | (false, "ASC") => std::cmp::Ordering::Less,
@@ -307,6 +646,7 @@ impl StorageEngine {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use query_creator::clauses::table::create_table_cql::CreateTable;
     use query_creator::clauses::types::column::Column;
     use query_creator::clauses::types::datatype::DataType;
     use std::fs::{self, File};
@@ -333,7 +673,7 @@ mod tests {
         let timestamp = 1234567890;
 
         // Clean the environment
-        let folder_path = storage.get_keyspace_path(keyspace);
+        let folder_path = storage.get_keyspace_path(keyspace, table);
         if folder_path.exists() {
             fs::remove_dir_all(&folder_path).unwrap();
         }
@@ -414,7 +754,7 @@ mod tests {
         let timestamp2 = 1234567891;
 
         // Clean the environment
-        let folder_path = storage.get_keyspace_path(keyspace);
+        let folder_path = storage.get_keyspace_path(keyspace, table);
         if folder_path.exists() {
             fs::remove_dir_all(&folder_path).unwrap();
         }
@@ -477,4 +817,217 @@ mod tests {
             fs::remove_dir_all(&root).unwrap();
         }
     }
+
+    fn flight_columns() -> Vec<Column> {
+        let mut route_column = Column::new("route", DataType::String, true, false);
+        route_column.is_partition_key = true;
+        let mut id_column = Column::new("id", DataType::Int, false, false);
+        id_column.is_clustering_column = true;
+        id_column.clustering_order = "ASC".to_string();
+        let status_column = Column::new("status", DataType::String, false, true);
+
+        vec![route_column, id_column, status_column]
+    }
+
+    // Crea el archivo de la tabla con su encabezado, como lo hace `CREATE TABLE`
+    fn create_flights_table(storage: &StorageEngine) {
+        let folder_path = storage.get_keyspace_path("sky", "flights");
+        fs::create_dir_all(&folder_path).unwrap();
+        fs::write(folder_path.join("flights.csv"), "route,id,status\n").unwrap();
+    }
+
+    fn table_rewrites() -> usize {
+        crate::storage_engine::TABLE_REWRITES.with(|rewrites| rewrites.get())
+    }
+
+    #[test]
+    fn test_batch_insert_matches_individual_inserts() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let one_by_one = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        let batched = StorageEngine::new(root.clone(), "127.0.0.2".to_string());
+        let columns = flight_columns();
+        let clustering_columns_in_order = vec!["id".to_string()];
+        create_flights_table(&one_by_one);
+        create_flights_table(&batched);
+
+        // Ids desordenados y con repetidos, para que algunas filas se pisen, en dos particiones
+        // que comparten claves de clustering
+        let rows: Vec<(&str, String, String)> = (0..1000u64)
+            .map(|i| {
+                let route = if i % 3 == 0 { "AEP-MDZ" } else { "EZE-COR" };
+                let id = (i * 7919 + 13) % 800;
+                (route, id.to_string(), format!("status{}", i))
+            })
+            .collect();
+        let primary_keys: std::collections::HashSet<(&str, &str)> = rows
+            .iter()
+            .map(|(route, id, _)| (*route, id.as_str()))
+            .collect();
+
+        let rewrites_before = table_rewrites();
+        for (i, (route, id, status)) in rows.iter().enumerate() {
+            one_by_one
+                .insert(
+                    "sky",
+                    "flights",
+                    vec![route, id, status],
+                    columns.clone(),
+                    clustering_columns_in_order.clone(),
+                    false,
+                    false,
+                    i as i64,
+                )
+                .unwrap();
+        }
+        assert_eq!(table_rewrites() - rewrites_before, 1000);
+
+        let rewrites_before = table_rewrites();
+        let applied = batched
+            .batch_insert(
+                "sky",
+                "flights",
+                rows.iter()
+                    .enumerate()
+                    .map(|(i, (route, id, status))| (vec![*route, id.as_str(), status], i as i64))
+                    .collect(),
+                columns,
+                clustering_columns_in_order,
+                false,
+                false,
+            )
+            .unwrap();
+        assert_eq!(table_rewrites() - rewrites_before, 1);
+        assert!(applied.iter().all(|&applied| applied));
+
+        let one_by_one_path = one_by_one.get_keyspace_path("sky", "flights");
+        let batched_path = batched.get_keyspace_path("sky", "flights");
+        let batched_rows = fs::read_to_string(batched_path.join("flights.csv")).unwrap();
+        assert_eq!(
+            fs::read_to_string(one_by_one_path.join("flights.csv")).unwrap(),
+            batched_rows
+        );
+        // Una fila por clave primaria, sin importar la particion
+        assert_eq!(batched_rows.lines().count() - 1, primary_keys.len());
+        assert_eq!(
+            fs::read_to_string(one_by_one_path.join("flights_index.csv")).unwrap(),
+            fs::read_to_string(batched_path.join("flights_index.csv")).unwrap()
+        );
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_batch_insert_if_not_exists_sees_earlier_rows() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        let columns = flight_columns();
+        create_flights_table(&storage);
+
+        storage
+            .insert(
+                "sky",
+                "flights",
+                vec!["EZE-COR", "1", "boarding"],
+                columns.clone(),
+                vec!["id".to_string()],
+                false,
+                false,
+                1,
+            )
+            .unwrap();
+
+        let applied = storage
+            .batch_insert(
+                "sky",
+                "flights",
+                vec![
+                    (vec!["EZE-COR", "1", "delayed"], 2),
+                    (vec!["EZE-COR", "2", "on time"], 3),
+                    (vec!["EZE-COR", "2", "canceled"], 4),
+                ],
+                columns,
+                vec!["id".to_string()],
+                false,
+                true,
+            )
+            .unwrap();
+        assert_eq!(applied, vec![false, true, false]);
+
+        let content = fs::read_to_string(
+            storage
+                .get_keyspace_path("sky", "flights")
+                .join("flights.csv"),
+        )
+        .unwrap();
+        assert_eq!(
+            content,
+            "route,id,status\nEZE-COR,1,boarding;1\nEZE-COR,2,on time;3\n"
+        );
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_insert_validates_the_clustering_order_of_the_table() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        let table = CreateTable::deserialize(
+            "CREATE TABLE sky.seats (flight INT, day INT, seat INT, PRIMARY KEY ((flight), day, seat)) WITH CLUSTERING ORDER BY (day ASC, seat DESC)",
+        )
+        .unwrap();
+        let columns = table.get_columns();
+        let names: Vec<&str> = columns.iter().map(|c| c.name.as_str()).collect();
+        storage.create_table("sky", "seats", names).unwrap();
+        storage.save_table_schema("sky", &table).unwrap();
+        let file_path = storage.get_keyspace_path("sky", "seats").join("seats.csv");
+
+        let insert = |values: Vec<&str>, order: Vec<&str>, timestamp: i64| {
+            storage.insert(
+                "sky",
+                "seats",
+                values,
+                columns.clone(),
+                order.into_iter().map(str::to_string).collect(),
+                false,
+                false,
+                timestamp,
+            )
+        };
+
+        // Columnas de clustering en otro orden, repetidas o de mas
+        for order in [
+            vec!["seat", "day"],
+            vec!["day"],
+            vec!["day", "day"],
+            vec!["day", "seat", "flight"],
+        ] {
+            assert!(matches!(
+                insert(vec!["1", "1", "1"], order, 1),
+                Err(StorageEngineError::ClusteringOrderMismatch)
+            ));
+        }
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "flight,day,seat\n");
+
+        for (i, values) in [
+            ["1", "2", "5"],
+            ["1", "1", "3"],
+            ["1", "1", "7"],
+            ["1", "2", "1"],
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            insert(values.to_vec(), vec!["day", "seat"], i as i64).unwrap();
+        }
+
+        let rows: Vec<String> = fs::read_to_string(&file_path)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(|line| line.split(';').next().unwrap().to_string())
+            .collect();
+        assert_eq!(rows, vec!["1,1,7", "1,1,3", "1,2,5", "1,2,1"]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
 }
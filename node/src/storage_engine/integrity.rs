@@ -0,0 +1,440 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use gossip::structures::application_state::{KeyspaceSchema, TableSchema};
+use query_creator::clauses::insert_cql::NULL_VALUE;
+use query_creator::clauses::types::column::Column;
+
+use super::{errors::StorageEngineError, split_stored_row, StorageEngine, TOMBSTONE};
+use native_protocol::messages::result::rows::{split_storage_values, unescape_storage_text};
+
+/// Why a row of a table file does not match its table.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MalformationReason {
+    /// The row has no `;` separating its values from its timestamp.
+    MissingTimestamp,
+    /// The timestamp of the row is not a number, or is followed by something that is not
+    /// the tombstone mark.
+    InvalidTimestamp(String),
+    /// The row does not have one value for each column of the header.
+    WrongColumnCount { expected: usize, found: usize },
+    /// The value of a column cannot be read as the type of the column.
+    InvalidValue { column: String, value: String },
+}
+
+impl fmt::Display for MalformationReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MalformationReason::MissingTimestamp => write!(f, "the row has no timestamp"),
+            MalformationReason::InvalidTimestamp(time_of_row) => {
+                write!(f, "invalid timestamp '{}'", time_of_row)
+            }
+            MalformationReason::WrongColumnCount { expected, found } => {
+                write!(f, "expected {} values, found {}", expected, found)
+            }
+            MalformationReason::InvalidValue { column, value } => {
+                write!(f, "invalid value '{}' for column {}", value, column)
+            }
+        }
+    }
+}
+
+/// A row that `flush_and_verify` found malformed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MalformedRow {
+    pub keyspace: String,
+    pub table: String,
+    /// The row is part of the data the node stores as a replica.
+    pub replication: bool,
+    /// Line of the row in the table file. The header is line 1.
+    pub line: usize,
+    pub reason: MalformationReason,
+}
+
+/// Result of `StorageEngine::flush_and_verify`.
+#[derive(Debug, Default, PartialEq)]
+pub struct IntegrityReport {
+    /// Table files that were checked, counting the replicated data apart.
+    pub tables_checked: usize,
+    /// Rows read, malformed or not.
+    pub rows_checked: usize,
+    pub malformed_rows: Vec<MalformedRow>,
+    /// Whether the malformed rows were moved out of the table files.
+    pub quarantined: bool,
+}
+
+impl IntegrityReport {
+    /// `true` if no malformed row was found.
+    pub fn is_clean(&self) -> bool {
+        self.malformed_rows.is_empty()
+    }
+}
+
+impl StorageEngine {
+    /// Checks the files of every table of `keyspaces`, both the owned and the replicated data,
+    /// and rebuilds their indexes. Meant to be run after a crash, before serving queries.
+    ///
+    /// Each file is synced to disk first. Then every row is checked to have a timestamp, one
+    /// value per column of the header, and values that can be read as the type of their
+    /// column. Empty values are nulls and are only rejected in primary key columns.
+    ///
+    /// The table files are not modified unless `quarantine` is `true`: then the malformed
+    /// rows are moved, as they were, to `<table>_quarantine.csv` next to the table file. The
+    /// index is always rebuilt from the rows that are left.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::IoError` if a file cannot be read or written.
+    /// - `StorageEngineError::LockError` if the lock of a table is poisoned.
+    pub fn flush_and_verify(
+        &self,
+        keyspaces: &[KeyspaceSchema],
+        quarantine: bool,
+    ) -> Result<IntegrityReport, StorageEngineError> {
+        let mut report = IntegrityReport {
+            quarantined: quarantine,
+            ..Default::default()
+        };
+
+        for keyspace in keyspaces {
+            let keyspace_name = keyspace.get_name();
+            for table in keyspace.get_tables() {
+                let folder_path = self.get_keyspace_path(&keyspace_name, &table.get_name());
+                for (folder_path, replication) in [
+                    (folder_path.clone(), false),
+                    (folder_path.join("replication"), true),
+                ] {
+                    let malformed =
+                        self.verify_table_file(&folder_path, &table, quarantine, &mut report)?;
+                    report
+                        .malformed_rows
+                        .extend(malformed.into_iter().map(|(line, reason)| MalformedRow {
+                            keyspace: keyspace_name.clone(),
+                            table: table.get_name(),
+                            replication,
+                            line,
+                            reason,
+                        }));
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    // Devuelve las filas mal formadas de un archivo de tabla, con su numero de linea
+    fn verify_table_file(
+        &self,
+        folder_path: &Path,
+        table: &TableSchema,
+        quarantine: bool,
+        report: &mut IntegrityReport,
+    ) -> Result<Vec<(usize, MalformationReason)>, StorageEngineError> {
+        let table_name = table.get_name();
+        let file_path = folder_path.join(format!("{}.csv", table_name));
+        if !file_path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let lock = Self::table_lock(&file_path)?;
+        let _guard = lock.lock().map_err(|_| StorageEngineError::LockError)?;
+
+        File::open(&file_path)?.sync_all()?;
+        report.tables_checked += 1;
+
+        let mut lines = BufReader::new(File::open(&file_path)?).lines();
+        let header = match lines.next() {
+            Some(header) => header?,
+            None => return Ok(Vec::new()),
+        };
+        let columns = table.get_columns();
+        let header_columns: Vec<Option<&Column>> = header
+            .split(',')
+            .map(|name| columns.iter().find(|column| column.name == name))
+            .collect();
+
+        // Las filas en el orden del archivo, con el motivo si estan mal formadas
+        let mut rows = Vec::new();
+        for line in lines {
+            let line = line?;
+            let reason = check_row(&line, &header_columns);
+            rows.push((line, reason));
+        }
+        report.rows_checked += rows.len();
+
+        let malformed: Vec<(usize, MalformationReason)> = rows
+            .iter()
+            .enumerate()
+            // La linea 1 es el header
+            .filter_map(|(i, (_, reason))| reason.clone().map(|reason| (i + 2, reason)))
+            .collect();
+
+        if quarantine && !malformed.is_empty() {
+            let quarantine_path = folder_path.join(format!("{}_quarantine.csv", table_name));
+            let mut quarantine_file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(quarantine_path)?;
+            for (line, _) in rows.iter().filter(|(_, reason)| reason.is_some()) {
+                writeln!(quarantine_file, "{}", line)?;
+            }
+            quarantine_file.sync_all()?;
+
+            rows.retain(|(_, reason)| reason.is_none());
+
+            let temp_file_path = folder_path.join(format!("verify_{}.csv", table_name));
+            let mut temp_file = BufWriter::new(File::create(&temp_file_path)?);
+            writeln!(temp_file, "{}", header)?;
+            for (line, _) in &rows {
+                writeln!(temp_file, "{}", line)?;
+            }
+            temp_file.flush()?;
+            self.replace_table_file(&temp_file_path, &file_path)?;
+        }
+
+        self.rebuild_index(folder_path, table, &header, &rows)?;
+
+        Ok(malformed)
+    }
+
+    // Reescribe el indice de la primera clustering column. Las filas mal formadas que siguen
+    // en el archivo no se indexan, pero ocupan sus bytes
+    fn rebuild_index(
+        &self,
+        folder_path: &Path,
+        table: &TableSchema,
+        header: &str,
+        rows: &[(String, Option<MalformationReason>)],
+    ) -> Result<(), StorageEngineError> {
+        let clustering_indices = Self::get_clustering_indices(
+            &table.get_columns(),
+            &table.get_clustering_column_in_order(),
+        )?;
+
+        let mut index_map = BTreeMap::new();
+        let mut current_byte_offset = header.len() as u64 + 1;
+        for (line, reason) in rows {
+            let line_length = line.len() as u64;
+            if reason.is_none() {
+                let (line_content, _) = Self::split_line(line)?;
+                let row: Vec<&str> = split_storage_values(line_content);
+                Self::update_index_map(
+                    &row,
+                    &clustering_indices,
+                    &mut index_map,
+                    current_byte_offset,
+                    line_length,
+                );
+            }
+            current_byte_offset += line_length + 1;
+        }
+
+        let index_file_path = folder_path.join(format!("{}_index.csv", table.get_name()));
+        let mut index_file = BufWriter::new(File::create(&index_file_path)?);
+        writeln!(index_file, "clustering_column,start_byte,end_byte")?;
+        for (key, (start_byte, end_byte)) in index_map {
+            writeln!(index_file, "{},{},{}", key, start_byte, end_byte)?;
+        }
+        index_file.flush()?;
+        index_file.get_ref().sync_all()?;
+
+        Ok(())
+    }
+}
+
+fn check_row(line: &str, header_columns: &[Option<&Column>]) -> Option<MalformationReason> {
+    let Some((line_content, time_of_row)) = split_stored_row(line) else {
+        return Some(MalformationReason::MissingTimestamp);
+    };
+
+    let (timestamp, marker) = match time_of_row.split_once(';') {
+        Some((timestamp, marker)) => (timestamp, Some(marker)),
+        None => (time_of_row, None),
+    };
+    if timestamp.trim().parse::<i64>().is_err() || marker.is_some_and(|m| m != TOMBSTONE) {
+        return Some(MalformationReason::InvalidTimestamp(
+            time_of_row.to_string(),
+        ));
+    }
+
+    let values: Vec<&str> = split_storage_values(line_content);
+    if values.len() != header_columns.len() {
+        return Some(MalformationReason::WrongColumnCount {
+            expected: header_columns.len(),
+            found: values.len(),
+        });
+    }
+
+    // Las columnas del header que no estan en el esquema no se pueden validar
+    for (value, column) in values.iter().zip(header_columns) {
+        let Some(column) = column else {
+            continue;
+        };
+        let is_null = (value.is_empty() || *value == NULL_VALUE) && !column.is_primary_key;
+        if !is_null
+            && !column
+                .data_type
+                .is_valid_value(&unescape_storage_text(value))
+        {
+            return Some(MalformationReason::InvalidValue {
+                column: column.name.clone(),
+                value: value.to_string(),
+            });
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use query_creator::clauses::keyspace::create_keyspace_cql::CreateKeyspace;
+    use query_creator::clauses::table::create_table_cql::CreateTable;
+    use std::fs;
+    use std::path::PathBuf;
+    use uuid::Uuid;
+
+    fn keyspace() -> KeyspaceSchema {
+        let create_keyspace = CreateKeyspace::deserialize(
+            "CREATE KEYSPACE sky WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 1}",
+        )
+        .unwrap();
+        let create_table = CreateTable::deserialize(
+            "CREATE TABLE sky.flights (id INT, origin TEXT, seats INT, PRIMARY KEY (origin, id))",
+        )
+        .unwrap();
+        KeyspaceSchema::new(create_keyspace, vec![TableSchema::new(create_table)])
+    }
+
+    // Crea la tabla con tres filas validas. Devuelve el engine, su raiz y la carpeta de la tabla
+    fn storage_with_flights() -> (StorageEngine, PathBuf, PathBuf) {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        let table = keyspace().get_table("flights").unwrap();
+
+        storage.create_keyspace("sky").unwrap();
+        storage
+            .create_table("sky", "flights", vec!["id", "origin", "seats"])
+            .unwrap();
+        storage
+            .batch_insert(
+                "sky",
+                "flights",
+                vec![
+                    (vec!["1", "EZE", "180"], 10),
+                    (vec!["2", "EZE", "150"], 10),
+                    (vec!["3", "MDZ", ""], 10),
+                ],
+                table.get_columns(),
+                table.get_clustering_column_in_order(),
+                false,
+                false,
+            )
+            .unwrap();
+
+        let folder_path = storage.get_keyspace_path("sky", "flights");
+        (storage, root, folder_path)
+    }
+
+    fn plant_malformed_rows(file_path: &Path) {
+        let mut content = fs::read_to_string(file_path).unwrap();
+        content.push_str("4,AEP,many;10\n");
+        content.push_str("5,AEP;10\n");
+        content.push_str("6,AEP,120\n");
+        content.push_str("7,AEP,100;yesterday\n");
+        fs::write(file_path, content).unwrap();
+    }
+
+    #[test]
+    fn test_flush_and_verify_reports_malformed_rows_without_touching_data() {
+        let (storage, root, folder_path) = storage_with_flights();
+        let file_path = folder_path.join("flights.csv");
+        plant_malformed_rows(&file_path);
+        let content = fs::read_to_string(&file_path).unwrap();
+
+        let report = storage.flush_and_verify(&[keyspace()], false).unwrap();
+
+        let reasons: Vec<(usize, MalformationReason)> = report
+            .malformed_rows
+            .iter()
+            .map(|row| (row.line, row.reason.clone()))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                (
+                    5,
+                    MalformationReason::InvalidValue {
+                        column: "seats".to_string(),
+                        value: "many".to_string()
+                    }
+                ),
+                (
+                    6,
+                    MalformationReason::WrongColumnCount {
+                        expected: 3,
+                        found: 2
+                    }
+                ),
+                (7, MalformationReason::MissingTimestamp),
+                (
+                    8,
+                    MalformationReason::InvalidTimestamp("yesterday".to_string())
+                ),
+            ]
+        );
+        assert!(report
+            .malformed_rows
+            .iter()
+            .all(|row| row.keyspace == "sky" && row.table == "flights" && !row.replication));
+        // La tabla y su replica, que esta vacia
+        assert_eq!(report.tables_checked, 2);
+        assert_eq!(report.rows_checked, 7);
+        assert!(!report.is_clean());
+
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), content);
+        assert!(!folder_path.join("flights_quarantine.csv").exists());
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_flush_and_verify_quarantines_malformed_rows_and_rebuilds_the_index() {
+        let (storage, root, folder_path) = storage_with_flights();
+        let file_path = folder_path.join("flights.csv");
+        let index_path = folder_path.join("flights_index.csv");
+        let clean_content = fs::read_to_string(&file_path).unwrap();
+        let clean_index = fs::read_to_string(&index_path).unwrap();
+
+        plant_malformed_rows(&file_path);
+        fs::write(
+            &index_path,
+            "clustering_column,start_byte,end_byte\n1,0,1\n",
+        )
+        .unwrap();
+
+        let report = storage.flush_and_verify(&[keyspace()], true).unwrap();
+
+        assert_eq!(report.malformed_rows.len(), 4);
+        assert!(report.quarantined);
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), clean_content);
+        assert_eq!(fs::read_to_string(&index_path).unwrap(), clean_index);
+        assert_eq!(
+            fs::read_to_string(folder_path.join("flights_quarantine.csv")).unwrap(),
+            "4,AEP,many;10\n5,AEP;10\n6,AEP,120\n7,AEP,100;yesterday\n"
+        );
+
+        // Una vez en cuarentena, la tabla queda limpia
+        assert!(storage
+            .flush_and_verify(&[keyspace()], false)
+            .unwrap()
+            .is_clean());
+
+        fs::remove_dir_all(&root).ok();
+    }
+}
@@ -1,4 +1,9 @@
 use super::{errors::StorageEngineError, StorageEngine};
+use query_creator::clauses::keyspace::create_keyspace_cql::CreateKeyspace;
+use std::fs;
+
+/// File inside the folder of a keyspace with its `CREATE KEYSPACE` statement.
+const KEYSPACE_SCHEMA_FILE: &str = "keyspace.cql";
 
 impl StorageEngine {
     /// Creates a keyspace in the storage location.
@@ -17,20 +22,18 @@ impl StorageEngine {
     /// This function will return an error if the directory or any subdirectory cannot be created.
 
     pub fn create_keyspace(&self, name: &str) -> Result<(), StorageEngineError> {
-        // Generate the folder name where the keyspace will be stored
-        let ip_str = self.ip.replace(".", "_");
-        let keyspace_folder = format!("keyspaces_of_{}", ip_str);
-        let keyspace_path = self.root.join(&keyspace_folder).join(name);
-
-        // Create the keyspace folder if it doesn't exist
-        if let Err(_) = std::fs::create_dir_all(&keyspace_path) {
-            return Err(StorageEngineError::DirectoryCreationFailed);
-        }
-
-        // Create the replication folder inside the keyspace folder
-        let replication_path = keyspace_path.join("replication");
-        if let Err(_) = std::fs::create_dir_all(&replication_path) {
-            return Err(StorageEngineError::DirectoryCreationFailed);
+        // The keyspace has a folder in every root
+        for keyspace_path in self.get_keyspace_paths(name) {
+            // Create the keyspace folder if it doesn't exist
+            if let Err(_) = std::fs::create_dir_all(&keyspace_path) {
+                return Err(StorageEngineError::DirectoryCreationFailed);
+            }
+
+            // Create the replication folder inside the keyspace folder
+            let replication_path = keyspace_path.join("replication");
+            if let Err(_) = std::fs::create_dir_all(&replication_path) {
+                return Err(StorageEngineError::DirectoryCreationFailed);
+            }
         }
 
         Ok(())
@@ -42,7 +45,6 @@ impl StorageEngine {
     ///
     /// # Arguments
     /// - `name`: The name of the keyspace to delete.
-    /// - `ip`: The IP address used to locate the keyspace folder.
     ///
     /// # Returns
     /// - `Ok(())` if the keyspace directory is successfully removed.
@@ -51,19 +53,99 @@ impl StorageEngine {
     /// # Errors
     /// This function will return an error if the keyspace directory cannot be removed.
 
-    pub fn drop_keyspace(&self, name: &str, ip: &str) -> Result<(), StorageEngineError> {
-        // Generate the folder name where the keyspace is stored
-        let ip_str = ip.replace(".", "_");
-        let keyspace_folder = format!("keyspaces_of_{}", ip_str);
-        let keyspace_path = self.root.join(&keyspace_folder).join(name);
+    pub fn drop_keyspace(&self, name: &str) -> Result<(), StorageEngineError> {
+        // Remove the keyspace folder of every root
+        for keyspace_path in self.get_keyspace_paths(name) {
+            if let Err(_) = std::fs::remove_dir_all(&keyspace_path) {
+                return Err(StorageEngineError::FileDeletionFailed);
+            }
+        }
 
-        // Remove the keyspace folder
-        if let Err(_) = std::fs::remove_dir_all(&keyspace_path) {
-            return Err(StorageEngineError::FileDeletionFailed);
+        Ok(())
+    }
+
+    /// Writes the definition of `keyspace` next to its data, so a node that restarts can
+    /// recover its replication settings.
+    ///
+    /// The definition is stored as the `CREATE KEYSPACE` statement of the keyspace, in the
+    /// folder of the keyspace of every root.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::DirectoryCreationFailed` if the folder of the keyspace cannot be created.
+    /// - `StorageEngineError::FileWriteFailed` if the file cannot be written.
+    pub fn save_keyspace_schema(
+        &self,
+        keyspace: &CreateKeyspace,
+    ) -> Result<(), StorageEngineError> {
+        for keyspace_path in self.get_keyspace_paths(&keyspace.get_name()) {
+            fs::create_dir_all(&keyspace_path)
+                .map_err(|_| StorageEngineError::DirectoryCreationFailed)?;
+            fs::write(
+                keyspace_path.join(KEYSPACE_SCHEMA_FILE),
+                keyspace.serialize(),
+            )
+            .map_err(|_| StorageEngineError::FileWriteFailed)?;
         }
 
         Ok(())
     }
+
+    /// Reads the definition saved by [`StorageEngine::save_keyspace_schema`].
+    ///
+    /// # Returns
+    /// - `Ok(None)` if no root has a definition for the keyspace.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::FileReadFailed` if the file exists but cannot be read or parsed.
+    pub fn load_keyspace_schema(
+        &self,
+        name: &str,
+    ) -> Result<Option<CreateKeyspace>, StorageEngineError> {
+        for keyspace_path in self.get_keyspace_paths(name) {
+            let path = keyspace_path.join(KEYSPACE_SCHEMA_FILE);
+            if !path.exists() {
+                continue;
+            }
+            let statement =
+                fs::read_to_string(path).map_err(|_| StorageEngineError::FileReadFailed)?;
+            let keyspace = CreateKeyspace::deserialize(statement.trim())
+                .map_err(|_| StorageEngineError::FileReadFailed)?;
+            return Ok(Some(keyspace));
+        }
+
+        Ok(None)
+    }
+
+    /// Names of the keyspaces this node has on disk, in any of its roots, sorted.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::FileReadFailed` if the folder of the node cannot be read.
+    pub fn list_keyspaces(&self) -> Result<Vec<String>, StorageEngineError> {
+        let mut keyspaces = Vec::new();
+
+        for root in &self.roots {
+            let node_folder = self.keyspaces_folder(root);
+            if !node_folder.exists() {
+                continue;
+            }
+            for entry in
+                fs::read_dir(node_folder).map_err(|_| StorageEngineError::FileReadFailed)?
+            {
+                let path = entry
+                    .map_err(|_| StorageEngineError::FileReadFailed)?
+                    .path();
+                if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+                    if path.is_dir() {
+                        keyspaces.push(name.to_string());
+                    }
+                }
+            }
+        }
+
+        keyspaces.sort();
+        keyspaces.dedup();
+        Ok(keyspaces)
+    }
 }
 
 #[cfg(test)]
@@ -122,7 +204,7 @@ mod tests {
         );
 
         // Call the function
-        let result = storage.drop_keyspace(keyspace_name, &ip);
+        let result = storage.drop_keyspace(keyspace_name);
         assert!(result.is_ok(), "Failed to drop keyspace");
 
         // Check that the keyspace directory was deleted
@@ -136,4 +218,27 @@ mod tests {
             fs::remove_dir_all(root.join(&keyspace_folder)).unwrap();
         }
     }
+
+    #[test]
+    fn test_keyspace_schema_is_saved_and_listed() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", uuid::Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        let keyspace = CreateKeyspace {
+            name: "sky".to_string(),
+            if_not_exists_clause: false,
+            replication_class: "SimpleStrategy".to_string(),
+            replication_factor: 3,
+        };
+        storage.create_keyspace("sky").unwrap();
+        storage.create_keyspace("ground").unwrap();
+        storage.save_keyspace_schema(&keyspace).unwrap();
+
+        assert_eq!(storage.list_keyspaces().unwrap(), vec!["ground", "sky"]);
+        let loaded = storage.load_keyspace_schema("sky").unwrap().unwrap();
+        assert_eq!(loaded.get_replication_factor(), 3);
+        assert_eq!(loaded.get_replication_class(), "SimpleStrategy");
+        assert!(storage.load_keyspace_schema("ground").unwrap().is_none());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
 }
@@ -0,0 +1,81 @@
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Counters of the work a `StorageEngine` does on disk, to tell how much data each query
+/// touches.
+///
+/// Clones share the same counters, so the engines a node creates for each query can all add
+/// to the counters of the node.
+#[derive(Debug, Clone, Default)]
+pub struct StorageMetrics {
+    rows_scanned: Arc<AtomicU64>,
+    rows_matched: Arc<AtomicU64>,
+    bytes_read: Arc<AtomicU64>,
+    bytes_written: Arc<AtomicU64>,
+}
+
+/// Values of the counters of a `StorageMetrics` at some point.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StorageMetricsSnapshot {
+    /// Rows read from the table files and checked against the `WHERE` of a read.
+    pub rows_scanned: u64,
+    /// Scanned rows that matched the `WHERE`.
+    pub rows_matched: u64,
+    /// Bytes of rows read from the table files.
+    pub bytes_read: u64,
+    /// Bytes of the table files written by inserts, updates and deletes.
+    pub bytes_written: u64,
+}
+
+impl StorageMetricsSnapshot {
+    /// What was counted between `earlier` and this snapshot, for example by a single query.
+    pub fn since(&self, earlier: &StorageMetricsSnapshot) -> StorageMetricsSnapshot {
+        StorageMetricsSnapshot {
+            rows_scanned: self.rows_scanned.saturating_sub(earlier.rows_scanned),
+            rows_matched: self.rows_matched.saturating_sub(earlier.rows_matched),
+            bytes_read: self.bytes_read.saturating_sub(earlier.bytes_read),
+            bytes_written: self.bytes_written.saturating_sub(earlier.bytes_written),
+        }
+    }
+
+    /// Rows scanned for each row returned, the read amplification. `None` if nothing matched.
+    pub fn read_amplification(&self) -> Option<f64> {
+        (self.rows_matched > 0).then(|| self.rows_scanned as f64 / self.rows_matched as f64)
+    }
+}
+
+impl StorageMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a row of `bytes` bytes read from a table file and whether it matched.
+    pub fn record_scanned_row(&self, bytes: u64, matched: bool) {
+        self.rows_scanned.fetch_add(1, Ordering::Relaxed);
+        self.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+        if matched {
+            self.rows_matched.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts the size of the table file at `path`, just written as a whole.
+    pub fn record_file_written(&self, path: &Path) {
+        // Si no se puede leer el tamaño no se cuenta, la escritura ya se hizo
+        if let Ok(metadata) = fs::metadata(path) {
+            self.bytes_written
+                .fetch_add(metadata.len(), Ordering::Relaxed);
+        }
+    }
+
+    /// Current values of the counters.
+    pub fn snapshot(&self) -> StorageMetricsSnapshot {
+        StorageMetricsSnapshot {
+            rows_scanned: self.rows_scanned.load(Ordering::Relaxed),
+            rows_matched: self.rows_matched.load(Ordering::Relaxed),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -1,19 +1,115 @@
+use gossip::structures::application_state::{KeyspaceSchema, Schema, TableSchema};
+use native_protocol::messages::result::rows::{escape_storage_text, unescape_storage_text};
+use query_creator::clauses::insert_cql::{unescape_literal, NULL_VALUE};
+use query_creator::clauses::keyspace::create_keyspace_cql::CreateKeyspace;
+use query_creator::clauses::table::create_table_cql::CreateTable;
+use query_creator::clauses::types::{column::Column, datatype::DataType};
+use std::collections::HashMap;
 use std::fs::{self};
-use std::path::PathBuf;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
 
 pub mod data_redistribution;
 pub mod delete;
+pub mod durability;
 pub mod errors;
+pub(crate) mod group_by;
 pub mod insert;
+pub mod integrity;
 pub mod keyspace_operations;
+pub mod metrics;
+mod partition_tombstone;
+pub mod quota;
+pub mod reconcile;
+pub mod scan_range;
 pub mod select;
+pub mod snapshot;
 pub mod table_operations;
+pub mod table_schema;
+mod tombstone_purge;
 pub mod update;
+use durability::Durability;
 use errors::StorageEngineError;
+use metrics::{StorageMetrics, StorageMetricsSnapshot};
+use table_schema::ClusteringOrderCache;
+pub use tombstone_purge::DEFAULT_TOMBSTONE_GRACE_SECONDS;
+
+#[cfg(test)]
+thread_local! {
+    // Cantidad de veces que se reemplazo el archivo de una tabla en este thread
+    static TABLE_REWRITES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Marca que sigue al timestamp de una fila borrada: `valores;timestamp;tombstone`.
+///
+/// Las filas borradas se conservan como tombstones, asi el coordinador puede distinguir
+/// una fila que una replica borro de una que a otra replica todavia no le llego.
+pub const TOMBSTONE: &str = "tombstone";
+
+// Locks de las tablas que alguien tiene tomados, por archivo (ver `StorageEngine::table_lock`)
+static TABLE_LOCKS: OnceLock<Mutex<HashMap<PathBuf, Weak<Mutex<()>>>>> = OnceLock::new();
+
+// Archivo con el token que el nodo tomo del nodo muerto que reemplazo
+const ADOPTED_TOKEN_FILE: &str = "adopted_token";
+
+/// Splits the part of a row that follows the first `;` into its timestamp and whether
+/// the row is a tombstone. A timestamp that cannot be parsed is read as 0.
+pub fn parse_row_time(time_of_row: &str) -> (i64, bool) {
+    let (timestamp, is_tombstone) = match time_of_row.split_once(';') {
+        Some((timestamp, marker)) => (timestamp, marker == TOMBSTONE),
+        None => (time_of_row, false),
+    };
+    (timestamp.trim().parse::<i64>().unwrap_or(0), is_tombstone)
+}
+
+/// Splits a stored line into the values of the row and the part that follows them, on the
+/// first `;` that is not escaped. `None` if the line has no `;`.
+pub fn split_stored_row(line: &str) -> Option<(&str, &str)> {
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ';' => return Some((&line[..i], &line[i + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Escapes a value of a query to store it in a row, so a `,`, `;` or line break in a text does
+/// not split the row. A null is kept as its marker, and a literal escaped so it is not taken for
+/// it is stored as its text, which no longer reads as the marker once escaped.
+pub fn escape_value(value: &str) -> String {
+    if value == NULL_VALUE {
+        return value.to_string();
+    }
+    escape_storage_text(unescape_literal(value))
+}
+
+/// Writes a value, as it is stored in a row, as the CQL literal of a query that writes it again:
+/// its text between single quotes, with the quotes inside it written twice. A null is written as
+/// an unquoted `null`, since a quoted `'\N'` is the text `\N`.
+pub fn cql_literal(stored: &str) -> String {
+    if stored == NULL_VALUE {
+        return "null".to_string();
+    }
+    format!("'{}'", unescape_storage_text(stored).replace('\'', "''"))
+}
 
 pub struct StorageEngine {
-    root: PathBuf,
+    /// Folders where the data is stored. There is always at least one.
+    roots: Vec<PathBuf>,
     ip: String,
+    metrics: StorageMetrics,
+    durability: Durability,
+    /// Bytes that each keyspace with a quota can take on this node.
+    keyspace_quotas: HashMap<String, u64>,
+    /// Seconds a tombstone is kept before a rewrite of its table drops it.
+    tombstone_grace_seconds: i64,
+    /// Clustering order of the saved table definitions, read once per change of their file.
+    clustering_orders: ClusteringOrderCache,
 }
 
 impl StorageEngine {
@@ -25,42 +121,386 @@ impl StorageEngine {
     ///
 
     pub fn new(root: PathBuf, ip: String) -> Self {
-        Self { root, ip }
+        Self {
+            roots: vec![root],
+            ip,
+            metrics: StorageMetrics::new(),
+            durability: Durability::default(),
+            keyspace_quotas: HashMap::new(),
+            tombstone_grace_seconds: DEFAULT_TOMBSTONE_GRACE_SECONDS,
+            clustering_orders: ClusteringOrderCache::default(),
+        }
     }
 
-    /// Resets the keyspace directories associated with the storage engine.
+    /// Makes the engine add what it reads and writes to `metrics` instead of to counters of
+    /// its own, so several engines can share them.
+    pub fn with_metrics(mut self, metrics: StorageMetrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Rows scanned and bytes read and written by this engine, and by every other engine that
+    /// shares its counters.
+    pub fn metrics(&self) -> StorageMetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// Makes the engine sync the table files it writes as `durability` says, instead of after
+    /// every write. Engines that share `durability` are flushed together.
+    pub fn with_durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    /// Makes the engine keep the clustering orders it reads in `cache` instead of in a cache of
+    /// its own, so several engines read each table definition only once.
+    pub fn with_clustering_order_cache(mut self, cache: ClusteringOrderCache) -> Self {
+        self.clustering_orders = cache;
+        self
+    }
+
+    /// Makes the rewrites of a table drop the tombstones, of rows and of partitions, written
+    /// more than `seconds` ago instead of after `DEFAULT_TOMBSTONE_GRACE_SECONDS`.
+    pub fn with_tombstone_grace_seconds(mut self, seconds: i64) -> Self {
+        self.tombstone_grace_seconds = seconds;
+        self
+    }
+
+    /// Syncs to disk every table file written and not synced yet by this engine, and by every
+    /// other engine that shares its durability policy.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::IoError` if a file cannot be synced.
+    pub fn flush(&self) -> Result<(), StorageEngineError> {
+        self.durability.flush()
+    }
+
+    /// Creates a new instance of `StorageEngine` that spreads the tables across several roots,
+    /// for example one in each disk.
     ///
-    /// If the directory for keyspaces already exists, it will be completely deleted
-    /// and recreated. If it does not exist, it will be created.
+    /// Each table is stored in the root chosen by hashing its name, so the same table is always
+    /// found in the same root as long as `roots` keeps the same order. Every root has its own
+    /// folder for each keyspace.
     ///
     /// # Returns
-    /// - `Ok(())` on success.
+    /// - `Err(StorageEngineError::UnsupportedOperation)` if `roots` is empty.
+    pub fn new_multi(roots: Vec<PathBuf>, ip: String) -> Result<Self, StorageEngineError> {
+        if roots.is_empty() {
+            return Err(StorageEngineError::UnsupportedOperation);
+        }
+        Ok(Self {
+            roots,
+            ip,
+            metrics: StorageMetrics::new(),
+            durability: Durability::default(),
+            keyspace_quotas: HashMap::new(),
+            tombstone_grace_seconds: DEFAULT_TOMBSTONE_GRACE_SECONDS,
+            clustering_orders: ClusteringOrderCache::default(),
+        })
+    }
+
+    /// Prepares the keyspace directories of the node before it starts.
+    ///
+    /// On a first start the folder of the node does not exist yet and it is created empty.
+    /// When the folder already exists the node is restarting: its keyspaces and tables are
+    /// kept, and only the temporary files that an interrupted write left behind are removed
+    /// (see [`StorageEngine::is_orphaned_temp_file`]).
+    ///
+    /// # Returns
+    /// - `Ok(true)` if the node is restarting and its previous data was kept.
+    /// - `Ok(false)` if the folders were created for the first time.
     /// - `Err(StorageEngineError)` if there is an issue deleting or creating the directories.
-    /// # Note
-    /// - Deleted directories cannot be recovered.
+    pub fn reset_folders(&self) -> Result<bool, StorageEngineError> {
+        let mut is_restart = false;
 
-    pub fn reset_folders(&self) -> Result<(), StorageEngineError> {
-        let ip_str = self.ip.replace(".", "_");
-        let keyspace_folder = format!("keyspaces_of_{}", ip_str);
-        let keyspace_path = self.root.join(&keyspace_folder);
+        for root in &self.roots {
+            let keyspace_path = self.keyspaces_folder(root);
 
-        // Check if the folder exists and delete it if it does
-        if keyspace_path.exists() {
-            fs::remove_dir_all(&keyspace_path)
-                .map_err(|_| StorageEngineError::DirectoryCreationFailed)?;
+            if keyspace_path.exists() {
+                is_restart = true;
+                Self::remove_orphaned_temp_files(&keyspace_path)?;
+            } else {
+                fs::create_dir_all(&keyspace_path)
+                    .map_err(|_| StorageEngineError::DirectoryCreationFailed)?;
+            }
         }
 
-        // Create the folder
-        fs::create_dir_all(&keyspace_path)
-            .map_err(|_| StorageEngineError::DirectoryCreationFailed)?;
+        Ok(is_restart)
+    }
 
+    /// Writes the token a node took over from the dead node `replaced` in the folder of the
+    /// node, so it keeps that position in the ring when it restarts.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::FileWriteFailed` if the file cannot be written.
+    pub fn save_adopted_token(
+        &self,
+        token: u64,
+        replaced: Ipv4Addr,
+    ) -> Result<(), StorageEngineError> {
+        for root in &self.roots {
+            fs::write(
+                self.keyspaces_folder(root).join(ADOPTED_TOKEN_FILE),
+                format!("{}\n{}\n", token, replaced),
+            )
+            .map_err(|_| StorageEngineError::FileWriteFailed)?;
+        }
         Ok(())
     }
 
-    fn get_keyspace_path(&self, keyspace: &str) -> PathBuf {
-        let ip_str = self.ip.replace(".", "_");
-        let keyspace_folder = format!("keyspaces_of_{}", ip_str);
-        self.root.join(&keyspace_folder).join(keyspace)
+    /// Reads the token and the replaced node saved by [`StorageEngine::save_adopted_token`].
+    ///
+    /// # Returns
+    /// - `Ok(None)` if the node never replaced another one.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::FileReadFailed` if the file exists but cannot be read or parsed.
+    pub fn load_adopted_token(&self) -> Result<Option<(u64, Ipv4Addr)>, StorageEngineError> {
+        for root in &self.roots {
+            let path = self.keyspaces_folder(root).join(ADOPTED_TOKEN_FILE);
+            if !path.exists() {
+                continue;
+            }
+            let contents =
+                fs::read_to_string(path).map_err(|_| StorageEngineError::FileReadFailed)?;
+            let mut lines = contents.lines();
+            let token = lines.next().and_then(|line| line.parse::<u64>().ok());
+            let replaced = lines.next().and_then(|line| line.parse::<Ipv4Addr>().ok());
+            return match (token, replaced) {
+                (Some(token), Some(replaced)) => Ok(Some((token, replaced))),
+                _ => Err(StorageEngineError::FileReadFailed),
+            };
+        }
+        Ok(None)
+    }
+
+    /// Removes, in `folder` and its subfolders, the files left by writes that did not finish.
+    fn remove_orphaned_temp_files(folder: &Path) -> Result<(), StorageEngineError> {
+        let entries = fs::read_dir(folder).map_err(|_| StorageEngineError::FileReadFailed)?;
+
+        for entry in entries {
+            let path = entry
+                .map_err(|_| StorageEngineError::FileReadFailed)?
+                .path();
+            if path.is_dir() {
+                Self::remove_orphaned_temp_files(&path)?;
+            } else if Self::is_orphaned_temp_file(&path) {
+                fs::remove_file(&path).map_err(|_| StorageEngineError::FileDeletionFailed)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether `path` is a temporary file of a write that was interrupted.
+    ///
+    /// The writes of the engine build the new version of a table in a `.tmp` or `.temp` file,
+    /// or in a `temp_<timestamp>.csv` / `verify_<table>.csv` file, and then rename it over the
+    /// table. The last two are only considered temporary when they have no index next to them,
+    /// since every table has one and a table may be called `temp_...` too. Index and quarantine
+    /// files are never temporary.
+    fn is_orphaned_temp_file(path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            return false;
+        };
+        if name.ends_with(".tmp") || name.ends_with(".temp") {
+            return true;
+        }
+
+        let Some(stem) = name.strip_suffix(".csv") else {
+            return false;
+        };
+        if stem.ends_with("_index") || stem.ends_with("_quarantine") {
+            return false;
+        }
+        let is_temp = match stem.strip_prefix("temp_") {
+            Some(timestamp) => timestamp.chars().all(|c| c.is_ascii_digit()),
+            None => stem.starts_with("verify_"),
+        };
+        is_temp && !path.with_file_name(format!("{}_index.csv", stem)).exists()
+    }
+
+    /// Rebuilds the schema of the node from the keyspaces and tables it has on disk.
+    ///
+    /// Keyspaces and tables are found with [`StorageEngine::list_keyspaces`] and
+    /// [`StorageEngine::list_tables`]. The definition of each keyspace is read from the file
+    /// written by [`StorageEngine::save_keyspace_schema`]; a keyspace without it gets a
+    /// `SimpleStrategy` with a replication factor of 1. Each table is read from the file
+    /// written by [`StorageEngine::save_table_schema`]. A table without it only has the names
+    /// of its columns, so every column is read as text and the first one is taken as the
+    /// partition key. Nothing in the schema has a version, so any schema gossiped by the rest
+    /// of the cluster replaces it.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::FileReadFailed` if a folder, a keyspace definition or the header
+    ///   of a table cannot be read.
+    pub fn load_schema_from_disk(&self) -> Result<Schema, StorageEngineError> {
+        let mut schema = Schema::new();
+
+        for keyspace_name in self.list_keyspaces()? {
+            let create_keyspace = self
+                .load_keyspace_schema(&keyspace_name)?
+                .unwrap_or_else(|| CreateKeyspace {
+                    name: keyspace_name.clone(),
+                    if_not_exists_clause: false,
+                    replication_class: "SimpleStrategy".to_string(),
+                    replication_factor: 1,
+                });
+
+            let mut tables = Vec::new();
+            for table_name in self.list_tables(&keyspace_name)? {
+                if let Some(table) = self.load_table_schema(&keyspace_name, &table_name)? {
+                    tables.push(TableSchema::new(table));
+                    continue;
+                }
+
+                let header = self.read_table_header(&keyspace_name, &table_name)?;
+                let columns = header
+                    .split(',')
+                    .enumerate()
+                    .map(|(i, name)| {
+                        let mut column = Column::new(name, DataType::String, i == 0, i != 0);
+                        column.is_partition_key = i == 0;
+                        column
+                    })
+                    .collect();
+                tables.push(TableSchema::new(CreateTable {
+                    name: table_name,
+                    keyspace_used_name: keyspace_name.clone(),
+                    if_not_exists_clause: false,
+                    columns,
+                    clustering_columns_in_order: vec![],
+                }));
+            }
+
+            schema
+                .keyspaces
+                .insert(keyspace_name, KeyspaceSchema::new(create_keyspace, tables));
+        }
+
+        Ok(schema)
+    }
+
+    /// Moves the data of the node from the folder of `old_ip` to the folder of `new_ip`, so it
+    /// is not lost when the node changes its address.
+    ///
+    /// The folder of each root is renamed with `fs::rename`, which is atomic inside a file
+    /// system. If a root fails, the roots already renamed are moved back, so the data ends up
+    /// either entirely under the old name or entirely under the new one. Roots without a
+    /// folder for `old_ip` are skipped. After the rename the engine works with `new_ip`.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::FileReplacementFailed` if a root already has a folder for
+    ///   `new_ip` or if a folder cannot be renamed.
+    pub fn rename_node(&mut self, old_ip: &str, new_ip: &str) -> Result<(), StorageEngineError> {
+        let old_name = Self::node_folder_name(old_ip);
+        let new_name = Self::node_folder_name(new_ip);
+        if old_name == new_name {
+            self.ip = new_ip.to_string();
+            return Ok(());
+        }
+
+        let moves: Vec<(PathBuf, PathBuf)> = self
+            .roots
+            .iter()
+            .map(|root| (root.join(&old_name), root.join(&new_name)))
+            .filter(|(old_path, _)| old_path.exists())
+            .collect();
+        if moves.iter().any(|(_, new_path)| new_path.exists()) {
+            return Err(StorageEngineError::FileReplacementFailed);
+        }
+
+        for (done, (old_path, new_path)) in moves.iter().enumerate() {
+            if fs::rename(old_path, new_path).is_err() {
+                // Volver atras lo que ya se renombro
+                for (old_path, new_path) in &moves[..done] {
+                    let _ = fs::rename(new_path, old_path);
+                }
+                return Err(StorageEngineError::FileReplacementFailed);
+            }
+        }
+
+        self.ip = new_ip.to_string();
+        Ok(())
+    }
+
+    /// Returns the lock of the table stored in `file_path`.
+    ///
+    /// Locks are shared by every `StorageEngine` of the process, so a write holding the lock
+    /// of a table can check the current rows and modify them without another write on the
+    /// same table running in between (needed by `IF` and `IF NOT EXISTS`).
+    ///
+    /// Only the locks someone holds are kept: the ones of tables nobody is writing, dropped
+    /// tables and temporary files included, are forgotten and created again when needed.
+    fn table_lock(file_path: &Path) -> Result<Arc<Mutex<()>>, StorageEngineError> {
+        let mut locks = TABLE_LOCKS
+            .get_or_init(|| Mutex::new(HashMap::new()))
+            .lock()
+            .map_err(|_| StorageEngineError::LockError)?;
+
+        locks.retain(|_, lock| lock.strong_count() > 0);
+        if let Some(lock) = locks.get(file_path).and_then(Weak::upgrade) {
+            return Ok(lock);
+        }
+        let lock = Arc::new(Mutex::new(()));
+        locks.insert(file_path.to_path_buf(), Arc::downgrade(&lock));
+        Ok(lock)
+    }
+
+    /// Replaces a file of a table (its rows, index or tombstones) with the temporary file that
+    /// holds its new content, and syncs it as the durability policy of the engine says.
+    ///
+    /// Every rewrite of a table file goes through here, so none of them skips the policy.
+    fn replace_table_file(
+        &self,
+        temp_file_path: &Path,
+        file_path: &Path,
+    ) -> Result<(), StorageEngineError> {
+        self.durability.replace_file(temp_file_path, file_path)?;
+        self.metrics.record_file_written(file_path);
+
+        #[cfg(test)]
+        TABLE_REWRITES.with(|rewrites| rewrites.set(rewrites.get() + 1));
+
+        Ok(())
+    }
+
+    /// Name of the folder that holds the keyspaces of the node with the given `ip`.
+    ///
+    /// This is the only place where the name is built, so every path of a node changes
+    /// together when the node is renamed.
+    fn node_folder_name(ip: &str) -> String {
+        format!("keyspaces_of_{}", ip.replace(".", "_"))
+    }
+
+    /// Folder with the keyspaces of this node inside `root`.
+    fn keyspaces_folder(&self, root: &Path) -> PathBuf {
+        root.join(Self::node_folder_name(&self.ip))
+    }
+
+    /// Folder of `keyspace` in the root where `table` is stored.
+    fn get_keyspace_path(&self, keyspace: &str, table: &str) -> PathBuf {
+        self.keyspaces_folder(self.root_of_table(table))
+            .join(keyspace)
+    }
+
+    /// Folders of `keyspace` in every root.
+    fn get_keyspace_paths(&self, keyspace: &str) -> Vec<PathBuf> {
+        self.roots
+            .iter()
+            .map(|root| self.keyspaces_folder(root).join(keyspace))
+            .collect()
+    }
+
+    /// Root where `table` is stored.
+    ///
+    /// Uses FNV-1a instead of the hasher of the standard library, whose output may change
+    /// between Rust versions and would move tables to another root.
+    fn root_of_table(&self, table: &str) -> &Path {
+        let hash = table.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        });
+        &self.roots[(hash % self.roots.len() as u64) as usize]
     }
 }
 
@@ -70,16 +510,102 @@ mod tests {
     use std::fs;
     use std::path::PathBuf;
 
+    #[test]
+    fn test_table_locks_are_forgotten_once_nobody_holds_them() {
+        let path = PathBuf::from(format!(
+            "/tmp/table_lock_test_{}/flights.csv",
+            uuid::Uuid::new_v4()
+        ));
+        let is_kept = |path: &Path| {
+            TABLE_LOCKS
+                .get()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .contains_key(path)
+        };
+
+        let lock = StorageEngine::table_lock(&path).unwrap();
+        let same = StorageEngine::table_lock(&path).unwrap();
+        assert!(Arc::ptr_eq(&lock, &same));
+        assert!(is_kept(&path));
+
+        drop((lock, same));
+        let other = path.with_file_name("airports.csv");
+        let _other_lock = StorageEngine::table_lock(&other).unwrap();
+        assert!(!is_kept(&path));
+        assert!(is_kept(&other));
+    }
+
+    #[test]
+    fn test_a_text_that_reads_as_the_null_marker_is_not_stored_as_null() {
+        use native_protocol::messages::result::rows::unescape_storage_text;
+        use query_creator::clauses::insert_cql::Insert;
+
+        let insert =
+            Insert::deserialize(r"INSERT INTO notes (id, text) VALUES ('\N', null)").unwrap();
+        let stored: Vec<String> = insert.values.iter().map(|v| escape_value(v)).collect();
+
+        assert_eq!(stored[1], NULL_VALUE);
+        assert_ne!(stored[0], NULL_VALUE);
+        assert_eq!(unescape_storage_text(&stored[0]), NULL_VALUE);
+    }
+
     #[test]
     fn test_storage_engine_new() {
         let root = PathBuf::from("/tmp/storage");
         let ip = "127.0.0.1".to_string();
         let storage = StorageEngine::new(root.clone(), ip.clone());
 
-        assert_eq!(storage.root, root);
+        assert_eq!(storage.roots, vec![root]);
         assert_eq!(storage.ip, ip);
     }
 
+    #[test]
+    fn test_rename_node_keeps_keyspaces_and_tables() {
+        let base = PathBuf::from(format!("/tmp/storage_test_{}", uuid::Uuid::new_v4()));
+        let roots = vec![base.join("disk_0"), base.join("disk_1")];
+        let mut storage = StorageEngine::new_multi(roots.clone(), "127.0.0.1".to_string()).unwrap();
+        let tables = ["flights", "airports", "passengers", "crew"];
+        storage.create_keyspace("sky").unwrap();
+        storage.create_keyspace("ground").unwrap();
+        for table in tables {
+            storage
+                .create_table("sky", table, vec!["id", "name"])
+                .unwrap();
+        }
+
+        storage.rename_node("127.0.0.1", "10.0.0.7").unwrap();
+
+        for root in &roots {
+            assert!(!root.join("keyspaces_of_127_0_0_1").exists());
+            let node_folder = root.join("keyspaces_of_10_0_0_7");
+            assert!(node_folder.join("sky").join("replication").exists());
+            assert!(node_folder.join("ground").join("replication").exists());
+        }
+        for table in tables {
+            let table_path = storage
+                .get_keyspace_path("sky", table)
+                .join(format!("{}.csv", table));
+            assert!(table_path.starts_with(&base));
+            assert!(table_path
+                .to_string_lossy()
+                .contains("keyspaces_of_10_0_0_7"));
+            assert_eq!(fs::read_to_string(table_path).unwrap(), "id,name\n");
+        }
+
+        // Si ya hay datos con el nombre nuevo no se pisan
+        let mut other = StorageEngine::new(base.join("disk_0"), "10.0.0.8".to_string());
+        other.create_keyspace("sky").unwrap();
+        assert!(matches!(
+            other.rename_node("10.0.0.8", "10.0.0.7"),
+            Err(StorageEngineError::FileReplacementFailed)
+        ));
+        assert!(base.join("disk_0").join("keyspaces_of_10_0_0_8").exists());
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
     #[test]
     fn test_reset_folders() {
         let root = PathBuf::from("/tmp/storage_test");
@@ -96,7 +622,7 @@ mod tests {
 
         // Ejecutar la función de reinicio
         let result = storage.reset_folders();
-        assert!(result.is_ok());
+        assert!(matches!(result, Ok(false)));
 
         // Verificar que la carpeta fue creada correctamente
         assert!(keyspace_path.exists());
@@ -104,4 +630,59 @@ mod tests {
         // Limpiar después de la prueba
         fs::remove_dir_all(&keyspace_path).unwrap();
     }
+
+    #[test]
+    fn test_reset_folders_on_restart_only_removes_temp_files() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", uuid::Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        assert!(!storage.reset_folders().unwrap());
+        storage.create_keyspace("sky").unwrap();
+        storage
+            .create_table("sky", "flights", vec!["id", "origin"])
+            .unwrap();
+        storage
+            .create_table("sky", "temp_1", vec!["id", "origin"])
+            .unwrap();
+
+        // Archivos que deja una escritura interrumpida
+        let keyspace_path = storage.get_keyspace_path("sky", "flights");
+        let orphans = [
+            keyspace_path.join("1700000000.tmp"),
+            keyspace_path.join("flights.csv.temp"),
+            keyspace_path.join("temp_1700000000.csv"),
+            keyspace_path.join("replication").join("verify_flights.csv"),
+        ];
+        for orphan in &orphans {
+            fs::write(orphan, "id,origin\n").unwrap();
+        }
+
+        assert!(storage.reset_folders().unwrap());
+
+        for orphan in &orphans {
+            assert!(!orphan.exists(), "{:?} was kept", orphan);
+        }
+        assert!(keyspace_path.join("flights.csv").exists());
+        assert!(keyspace_path.join("flights_index.csv").exists());
+        // Una tabla que se llama como un archivo temporal no se borra
+        assert!(storage
+            .get_keyspace_path("sky", "temp_1")
+            .join("temp_1.csv")
+            .exists());
+
+        let schema = storage.load_schema_from_disk().unwrap();
+        let keyspace = &schema.keyspaces["sky"];
+        let mut tables: Vec<String> = keyspace.tables.iter().map(|t| t.get_name()).collect();
+        tables.sort();
+        assert_eq!(tables, vec!["flights", "temp_1"]);
+        assert_eq!(
+            keyspace
+                .get_table("flights")
+                .unwrap()
+                .get_partition_keys()
+                .unwrap(),
+            vec!["id"]
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
 }
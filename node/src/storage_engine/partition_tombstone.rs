@@ -0,0 +1,200 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use gossip::structures::application_state::TableSchema;
+use query_creator::clauses::{condition::Condition, delete_cql::Delete};
+use query_creator::{logical_operator::LogicalOperator, operator::Operator};
+
+use super::{
+    errors::StorageEngineError, escape_value, parse_row_time, split_stored_row, StorageEngine,
+    TOMBSTONE,
+};
+use native_protocol::messages::result::rows::split_storage_values;
+
+/// Suffix of the file, next to `<table>.csv`, with the partitions deleted as a whole.
+const PARTITION_TOMBSTONES_SUFFIX: &str = "_partition_tombstones.csv";
+
+/// Path of the partition tombstones of `table`, inside the folder of its data.
+pub(crate) fn partition_tombstones_path(folder: &Path, table: &str) -> PathBuf {
+    folder.join(format!("{}{}", table, PARTITION_TOMBSTONES_SUFFIX))
+}
+
+/// Reads the partition tombstones of `table`: for each deleted partition key, the timestamp of
+/// its newest delete. Every row of the partition written at or before it counts as deleted.
+pub(crate) fn read_partition_tombstones(
+    folder: &Path,
+    table: &str,
+) -> Result<HashMap<String, i64>, StorageEngineError> {
+    let path = partition_tombstones_path(folder, table);
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+
+    let contents = fs::read_to_string(path).map_err(|_| StorageEngineError::FileReadFailed)?;
+    contents
+        .lines()
+        .skip(1)
+        .map(|line| {
+            // La clave puede tener comas si la particion es compuesta, el timestamp va al final
+            let (key, timestamp) = line.rsplit_once(',').ok_or(StorageEngineError::IoError)?;
+            let timestamp = timestamp
+                .parse::<i64>()
+                .map_err(|_| StorageEngineError::IoError)?;
+            Ok((key.to_string(), timestamp))
+        })
+        .collect()
+}
+
+impl StorageEngine {
+    /// Records that the partition `partition_key` of `table` was deleted at `timestamp`.
+    ///
+    /// Only the newest delete of each partition is kept. The file is replaced through a `.tmp`
+    /// file, and the caller must hold the lock of the table.
+    pub(crate) fn write_partition_tombstone(
+        &self,
+        folder: &Path,
+        table: &str,
+        partition_key: &str,
+        timestamp: i64,
+    ) -> Result<(), StorageEngineError> {
+        let mut tombstones = read_partition_tombstones(folder, table)?;
+        let newest = tombstones
+            .entry(partition_key.to_string())
+            .or_insert(timestamp);
+        *newest = (*newest).max(timestamp);
+        self.write_partition_tombstones(folder, table, &tombstones)
+    }
+
+    /// Replaces the partition tombstones of `table` with `tombstones`, through a `.tmp` file.
+    /// The caller must hold the lock of the table.
+    pub(crate) fn write_partition_tombstones(
+        &self,
+        folder: &Path,
+        table: &str,
+        tombstones: &HashMap<String, i64>,
+    ) -> Result<(), StorageEngineError> {
+        let mut keys: Vec<&String> = tombstones.keys().collect();
+        keys.sort();
+        let mut contents = String::from("partition_key,timestamp\n");
+        for key in keys {
+            contents.push_str(&format!("{},{}\n", key, tombstones[key]));
+        }
+
+        let path = partition_tombstones_path(folder, table);
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, contents).map_err(|_| StorageEngineError::FileWriteFailed)?;
+        self.replace_table_file(&temp_path, &path)
+    }
+}
+
+/// Positions of the partition key columns in the rows of `table`.
+pub(crate) fn partition_key_indexes(table: &TableSchema) -> Vec<usize> {
+    table
+        .get_columns()
+        .iter()
+        .enumerate()
+        .filter(|(_, column)| column.is_partition_key)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Partition key of a row, as written in the partition tombstones: its values as they are
+/// stored, escaped.
+pub(crate) fn partition_key_of(values: &[&str], partition_key_indexes: &[usize]) -> String {
+    partition_key_indexes
+        .iter()
+        .map(|&i| values.get(i).copied().unwrap_or_default())
+        .collect::<Vec<&str>>()
+        .join(",")
+}
+
+/// Returns `row` (`values;timestamp`) as a tombstone with the timestamp of the delete if its
+/// partition was deleted at or after the row was written. Any other row is returned as is.
+pub(crate) fn shadow_row(
+    row: String,
+    tombstones: &HashMap<String, i64>,
+    partition_key_indexes: &[usize],
+) -> String {
+    if tombstones.is_empty() {
+        return row;
+    }
+
+    let Some((values, time_of_row)) = split_stored_row(&row) else {
+        return row;
+    };
+    let (written_at, is_tombstone) = parse_row_time(time_of_row);
+    let partition_key = partition_key_of(&split_storage_values(values), partition_key_indexes);
+    match tombstones.get(&partition_key) {
+        Some(&deleted_at) if !is_tombstone && written_at <= deleted_at => {
+            format!("{};{};{}", values, deleted_at, TOMBSTONE)
+        }
+        _ => row,
+    }
+}
+
+/// Returns the partition key `delete_query` removes as a whole: a `DELETE` of full rows, without
+/// `IF`, whose `WHERE` only sets each partition key column with `=`.
+///
+/// Tables without clustering columns have a single row per partition, which the usual row
+/// tombstone already deletes, so they never get partition tombstones.
+pub(crate) fn deleted_partition(table: &TableSchema, delete_query: &Delete) -> Option<String> {
+    if delete_query.columns.is_some()
+        || delete_query.if_clause.is_some()
+        || delete_query.if_exist
+        || table.get_clustering_column_in_order().is_empty()
+    {
+        return None;
+    }
+
+    let mut equalities = HashMap::new();
+    collect_equalities(
+        &delete_query.where_clause.as_ref()?.condition,
+        &mut equalities,
+    )?;
+
+    let partition_keys: Vec<String> = table
+        .get_columns()
+        .into_iter()
+        .filter(|column| column.is_partition_key)
+        .map(|column| column.name)
+        .collect();
+    if equalities.len() != partition_keys.len() {
+        return None;
+    }
+
+    // La clave se guarda escapada, como los valores de las filas
+    let values = partition_keys
+        .iter()
+        .map(|key| equalities.get(key).map(|value| escape_value(value)))
+        .collect::<Option<Vec<String>>>()?;
+    Some(values.join(","))
+}
+
+// Junta las condiciones `columna = valor` unidas por `AND`; `None` si hay otro tipo de condicion
+fn collect_equalities(
+    condition: &Condition,
+    equalities: &mut HashMap<String, String>,
+) -> Option<()> {
+    match condition {
+        Condition::Simple {
+            field,
+            operator: Operator::Equal,
+            value,
+        } => match equalities.insert(field.clone(), value.clone()) {
+            Some(previous) if previous != *value => None,
+            _ => Some(()),
+        },
+        Condition::Complex {
+            left: Some(left),
+            operator: LogicalOperator::And,
+            right,
+        } => {
+            collect_equalities(left, equalities)?;
+            collect_equalities(right, equalities)
+        }
+        _ => None,
+    }
+}
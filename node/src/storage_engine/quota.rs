@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use super::{errors::StorageEngineError, StorageEngine};
+
+impl StorageEngine {
+    /// Limits the data of `keyspace` on this node to `bytes`, so a table that grows without
+    /// control cannot fill the disk. Keyspaces without a quota have no limit.
+    ///
+    /// The limit counts every file of the keyspace in every root, replicas and indexes
+    /// included (see [`StorageEngine::keyspace_size`]).
+    pub fn with_keyspace_quota(mut self, keyspace: &str, bytes: u64) -> Self {
+        self.keyspace_quotas.insert(keyspace.to_string(), bytes);
+        self
+    }
+
+    /// Sets the quota of each keyspace in `quotas`, as [`StorageEngine::with_keyspace_quota`].
+    pub fn with_keyspace_quotas(mut self, quotas: &HashMap<String, u64>) -> Self {
+        self.keyspace_quotas.extend(quotas.clone());
+        self
+    }
+
+    /// Bytes that the files of `keyspace` take on this node, adding up its folder in every
+    /// root. A keyspace without folders takes 0 bytes.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::IoError` if a folder or the size of a file cannot be read.
+    pub fn keyspace_size(&self, keyspace: &str) -> Result<u64, StorageEngineError> {
+        let mut size = 0;
+        for keyspace_path in self.get_keyspace_paths(keyspace) {
+            size += folder_size(&keyspace_path)?;
+        }
+        Ok(size)
+    }
+
+    /// Checks that writing `new_bytes` more to `keyspace` keeps it within its quota.
+    ///
+    /// The written bytes are counted as new even when the row replaces another one, so a
+    /// keyspace at its limit only accepts writes again after some of its data is dropped.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::StorageFull` if the keyspace would go over its quota.
+    pub(super) fn check_keyspace_quota(
+        &self,
+        keyspace: &str,
+        new_bytes: u64,
+    ) -> Result<(), StorageEngineError> {
+        let Some(&quota) = self.keyspace_quotas.get(keyspace) else {
+            return Ok(());
+        };
+        if self.keyspace_size(keyspace)? + new_bytes > quota {
+            return Err(StorageEngineError::StorageFull {
+                keyspace: keyspace.to_string(),
+                quota,
+            });
+        }
+        Ok(())
+    }
+}
+
+// Suma el tamaño de los archivos de `path` y de sus subcarpetas
+fn folder_size(path: &Path) -> Result<u64, StorageEngineError> {
+    if !path.exists() {
+        return Ok(0);
+    }
+    let mut size = 0;
+    for entry in fs::read_dir(path).map_err(|_| StorageEngineError::IoError)? {
+        let entry = entry.map_err(|_| StorageEngineError::IoError)?;
+        let metadata = entry.metadata().map_err(|_| StorageEngineError::IoError)?;
+        size += if metadata.is_dir() {
+            folder_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use query_creator::clauses::types::{column::Column, datatype::DataType};
+    use std::path::PathBuf;
+
+    fn flight_columns() -> Vec<Column> {
+        vec![
+            Column::new("id", DataType::Int, true, false),
+            Column::new("origin", DataType::String, false, true),
+        ]
+    }
+
+    fn insert_flight(
+        storage: &StorageEngine,
+        id: &str,
+        timestamp: i64,
+    ) -> Result<bool, StorageEngineError> {
+        storage.insert(
+            "sky",
+            "flights",
+            vec![id, "EZE"],
+            flight_columns(),
+            vec![],
+            false,
+            false,
+            timestamp,
+        )
+    }
+
+    #[test]
+    fn test_inserts_past_the_quota_are_rejected_until_data_is_dropped() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", uuid::Uuid::new_v4()));
+        let unlimited = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        unlimited.create_keyspace("sky").unwrap();
+        unlimited.create_keyspace("ground").unwrap();
+        unlimited
+            .create_table("sky", "flights", vec!["id", "origin"])
+            .unwrap();
+        let empty_size = unlimited.keyspace_size("sky").unwrap();
+        assert_eq!(unlimited.keyspace_size("missing").unwrap(), 0);
+
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string())
+            .with_keyspace_quota("sky", empty_size + 100);
+        let mut inserted = 0;
+        let rejected = loop {
+            match insert_flight(&storage, &inserted.to_string(), 1000 + inserted) {
+                Ok(_) => inserted += 1,
+                Err(e) => break e,
+            }
+        };
+        assert!(inserted > 0);
+        assert!(matches!(
+            rejected,
+            StorageEngineError::StorageFull { ref keyspace, quota }
+                if keyspace == "sky" && quota == empty_size + 100
+        ));
+        assert!(storage.keyspace_size("sky").unwrap() <= empty_size + 100);
+
+        // Los lotes tambien se rechazan y no se escribe ninguna de sus filas
+        let size = storage.keyspace_size("sky").unwrap();
+        assert!(matches!(
+            storage.batch_insert(
+                "sky",
+                "flights",
+                vec![(vec!["100", "EZE"], 2000), (vec!["101", "EZE"], 2001)],
+                flight_columns(),
+                vec![],
+                false,
+                false,
+            ),
+            Err(StorageEngineError::StorageFull { .. })
+        ));
+        assert_eq!(storage.keyspace_size("sky").unwrap(), size);
+
+        // Otro keyspace no tiene limite
+        unlimited
+            .create_table("ground", "airports", vec!["id", "origin"])
+            .unwrap();
+        storage
+            .insert(
+                "ground",
+                "airports",
+                vec!["1", "EZE"],
+                flight_columns(),
+                vec![],
+                false,
+                false,
+                3000,
+            )
+            .unwrap();
+
+        // Borrar la tabla libera la cuota
+        storage.drop_table("sky", "flights").unwrap();
+        assert!(storage.keyspace_size("sky").unwrap() < empty_size);
+        assert!(insert_flight(&storage, "1", 4000).unwrap());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_updates_past_the_quota_are_rejected_and_leave_the_table_as_it_was() {
+        use gossip::structures::application_state::TableSchema;
+        use query_creator::clauses::{table::create_table_cql::CreateTable, update_cql::Update};
+
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", uuid::Uuid::new_v4()));
+        let unlimited = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        unlimited.create_keyspace("sky").unwrap();
+        unlimited
+            .create_table("sky", "flights", vec!["id", "origin"])
+            .unwrap();
+        let table = TableSchema::new(
+            CreateTable::deserialize(
+                "CREATE TABLE sky.flights (id INT, origin TEXT, PRIMARY KEY (id))",
+            )
+            .unwrap(),
+        );
+        unlimited
+            .insert(
+                "sky",
+                "flights",
+                vec!["0", "EZE"],
+                table.get_columns(),
+                vec![],
+                false,
+                false,
+                1000,
+            )
+            .unwrap();
+
+        let table_path = unlimited.get_keyspace_path("sky", "flights");
+        let files_before: Vec<String> = ["flights.csv", "flights_index.csv"]
+            .iter()
+            .map(|file| fs::read_to_string(table_path.join(file)).unwrap())
+            .collect();
+
+        // Reescribir la fila no entra en los bytes que le quedan al keyspace
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string())
+            .with_keyspace_quota("sky", unlimited.keyspace_size("sky").unwrap() + 5);
+        let update =
+            Update::deserialize("UPDATE sky.flights SET origin = 'AEP' WHERE id = 0").unwrap();
+        assert!(matches!(
+            storage.update(update.clone(), table.clone(), false, "sky", 2000),
+            Err(StorageEngineError::StorageFull { .. })
+        ));
+        let files_after: Vec<String> = ["flights.csv", "flights_index.csv"]
+            .iter()
+            .map(|file| fs::read_to_string(table_path.join(file)).unwrap())
+            .collect();
+        assert_eq!(files_after, files_before);
+
+        assert!(unlimited.update(update, table, false, "sky", 2000).unwrap());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
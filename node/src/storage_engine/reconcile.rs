@@ -0,0 +1,259 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use gossip::structures::application_state::TableSchema;
+
+use super::{
+    errors::StorageEngineError,
+    escape_value,
+    partition_tombstone::{
+        partition_key_indexes, partition_key_of, read_partition_tombstones, shadow_row,
+    },
+    select::{is_newer_version, row_values},
+    StorageEngine,
+};
+
+impl StorageEngine {
+    /// Reads the row of `table` with the given primary key from the folder of the data owned by
+    /// the node and from its `replication` folder, and returns the version that wins.
+    ///
+    /// `primary_key` has the values of the partition key and clustering columns, in the order
+    /// of the columns of the table, as a query writes them. While data is being redistributed both folders can have a
+    /// copy of the row, and they can disagree: the newest write wins, and on a tie the
+    /// tombstone does (as in `select_merged`). The partition tombstones of both folders apply
+    /// to both copies. The row is returned formatted as in `select`, so a deleted row is
+    /// returned as a tombstone, and `None` means no folder has it.
+    ///
+    /// With `heal`, when both folders have the row and one copy loses, it is rewritten with the
+    /// winning version. A folder without a copy is left as is: the row may not belong there.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::IoError` if a table file cannot be read or rewritten.
+    /// - `StorageEngineError::LockError` if the lock of the table is poisoned.
+    pub fn read_reconciled(
+        &self,
+        keyspace: &str,
+        table: &TableSchema,
+        primary_key: &[&str],
+        heal: bool,
+    ) -> Result<Option<String>, StorageEngineError> {
+        let table_name = table.get_name();
+        let primary_path = self.get_keyspace_path(keyspace, &table_name);
+        let replication_path = primary_path.join("replication");
+        let primary_key_indexes = primary_key_indexes(table);
+        let partition_key_indexes = partition_key_indexes(table);
+        // Las filas guardan sus valores escapados
+        let primary_key = primary_key
+            .iter()
+            .map(|value| escape_value(value))
+            .collect::<Vec<String>>()
+            .join(",");
+
+        let mut partition_tombstones: HashMap<String, i64> = HashMap::new();
+        for folder in [&primary_path, &replication_path] {
+            for (partition_key, deleted_at) in read_partition_tombstones(folder, &table_name)? {
+                let newest = partition_tombstones
+                    .entry(partition_key)
+                    .or_insert(deleted_at);
+                *newest = (*newest).max(deleted_at);
+            }
+        }
+
+        let mut copies = Vec::new();
+        for folder in [&primary_path, &replication_path] {
+            let copy = latest_version_in(folder, &table_name, &primary_key, &primary_key_indexes)?
+                .map(|row| shadow_row(row, &partition_tombstones, &partition_key_indexes));
+            copies.push((folder, copy));
+        }
+
+        let winner = match (&copies[0].1, &copies[1].1) {
+            (Some(primary), Some(replica)) => {
+                let (winner, loser_folder) = if is_newer_version(replica, primary) {
+                    (replica.clone(), copies[0].0)
+                } else {
+                    (primary.clone(), copies[1].0)
+                };
+                if heal && primary != replica {
+                    self.heal_row(loser_folder, table, &primary_key, &winner)?;
+                }
+                Some(winner)
+            }
+            (Some(row), None) | (None, Some(row)) => Some(row.clone()),
+            (None, None) => None,
+        };
+        Ok(winner)
+    }
+
+    // Reemplaza la fila con `primary_key` de la tabla en `folder` por `winner`, salvo que entre
+    // tanto otra escritura la haya dejado en una version igual o mas nueva
+    fn heal_row(
+        &self,
+        folder: &Path,
+        table: &TableSchema,
+        primary_key: &str,
+        winner: &str,
+    ) -> Result<(), StorageEngineError> {
+        let table_name = table.get_name();
+        let file_path = folder.join(format!("{}.csv", table_name));
+
+        let table_lock = Self::table_lock(&file_path)?;
+        let _table_guard = table_lock
+            .lock()
+            .map_err(|_| StorageEngineError::LockError)?;
+
+        let primary_key_indexes = primary_key_indexes(table);
+        let file = File::open(&file_path).map_err(|_| StorageEngineError::IoError)?;
+        let mut lines = BufReader::new(file)
+            .lines()
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(|_| StorageEngineError::IoError)?;
+        let Some(position) = lines.iter().skip(1).rposition(|line| {
+            partition_key_of(&row_values(line), &primary_key_indexes) == primary_key
+        }) else {
+            return Ok(());
+        };
+        // Se saltea el encabezado
+        let position = position + 1;
+        if !is_newer_version(winner, &lines[position]) {
+            return Ok(());
+        }
+        lines[position] = winner.to_string();
+
+        self.rewrite_table_file(
+            folder,
+            &table_name,
+            &table.get_columns(),
+            &table.get_clustering_column_in_order(),
+            &lines,
+            "heal",
+        )
+    }
+}
+
+// Posiciones de las columnas de la clave primaria en las filas de `table`
+fn primary_key_indexes(table: &TableSchema) -> Vec<usize> {
+    table
+        .get_columns()
+        .iter()
+        .enumerate()
+        .filter(|(_, column)| column.is_partition_key || column.is_clustering_column)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+// Ultima version de la fila con `primary_key` en la tabla de `folder`: en una misma carpeta
+// la ultima linea es la ultima escritura
+fn latest_version_in(
+    folder: &Path,
+    table: &str,
+    primary_key: &str,
+    primary_key_indexes: &[usize],
+) -> Result<Option<String>, StorageEngineError> {
+    let file = match File::open(folder.join(format!("{}.csv", table))) {
+        Ok(file) => file,
+        Err(_) => return Ok(None),
+    };
+
+    let mut latest = None;
+    for line in BufReader::new(file).lines().skip(1) {
+        let line = line.map_err(|_| StorageEngineError::IoError)?;
+        if partition_key_of(&row_values(&line), primary_key_indexes) == primary_key {
+            latest = Some(line);
+        }
+    }
+    Ok(latest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use query_creator::clauses::table::create_table_cql::CreateTable;
+    use std::fs;
+    use std::path::PathBuf;
+    use uuid::Uuid;
+
+    fn flights_table() -> TableSchema {
+        let create_table = CreateTable::new_from_tokens(vec![
+            "CREATE".to_string(),
+            "TABLE".to_string(),
+            "sky.flights".to_string(),
+            "id INT, seat INT, origin TEXT, PRIMARY KEY (id, seat)".to_string(),
+        ])
+        .unwrap();
+        TableSchema::new(create_table)
+    }
+
+    fn insert(storage: &StorageEngine, values: Vec<&str>, is_replication: bool, timestamp: i64) {
+        let table = flights_table();
+        storage
+            .insert(
+                "sky",
+                "flights",
+                values,
+                table.get_columns(),
+                table.get_clustering_column_in_order(),
+                is_replication,
+                false,
+                timestamp,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_read_reconciled_returns_the_newest_copy_and_heals_the_older() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        storage
+            .create_table("sky", "flights", vec!["id", "seat", "origin"])
+            .unwrap();
+        let table = flights_table();
+        insert(&storage, vec!["1", "1", "EZE"], false, 10);
+        insert(&storage, vec!["1", "2", "EZE"], false, 10);
+        insert(&storage, vec!["1", "2", "AEP"], true, 20);
+        insert(&storage, vec!["1", "3", "COR"], true, 10);
+
+        // Sin `heal` la copia vieja queda como estaba
+        let row = storage
+            .read_reconciled("sky", &table, &["1", "2"], false)
+            .unwrap();
+        assert_eq!(row, Some("1,2,AEP;20".to_string()));
+        let primary_path = storage.get_keyspace_path("sky", "flights");
+        let rows_of = |folder: &Path| fs::read_to_string(folder.join("flights.csv")).unwrap();
+        assert!(rows_of(&primary_path).contains("1,2,EZE;10"));
+
+        let row = storage
+            .read_reconciled("sky", &table, &["1", "2"], true)
+            .unwrap();
+        assert_eq!(row, Some("1,2,AEP;20".to_string()));
+        assert!(rows_of(&primary_path).contains("1,2,AEP;20"));
+        assert!(!rows_of(&primary_path).contains("1,2,EZE;10"));
+
+        // El indice sigue apuntando a las filas reescritas
+        let rows = storage
+            .scan_range("sky", &table, false, 0, u64::MAX)
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+        assert!(rows.contains(&"1,2,AEP;20".to_string()));
+
+        // Una fila que solo esta en una carpeta no se copia a la otra
+        assert_eq!(
+            storage
+                .read_reconciled("sky", &table, &["1", "3"], true)
+                .unwrap(),
+            Some("1,3,COR;10".to_string())
+        );
+        assert!(!rows_of(&primary_path).contains("1,3,COR"));
+        assert_eq!(
+            storage
+                .read_reconciled("sky", &table, &["2", "1"], true)
+                .unwrap(),
+            None
+        );
+
+        fs::remove_dir_all(&root).ok();
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+};
+
+use gossip::structures::application_state::TableSchema;
+use native_protocol::messages::result::rows::{split_storage_values, unescape_storage_text};
+use partitioner::Partitioner;
+
+use super::{
+    errors::StorageEngineError,
+    parse_row_time,
+    partition_tombstone::{partition_key_of, read_partition_tombstones},
+    split_stored_row, StorageEngine,
+};
+
+impl StorageEngine {
+    /// Reads the live rows of `table` whose partition token is in `[start_token, end_token)`,
+    /// in the order they are stored, as `values;timestamp` without headers.
+    ///
+    /// The token of each row is computed from its partition key columns with
+    /// `Partitioner::partition_token`, the same encoding the coordinator uses to route it.
+    /// Deleted rows and rows of partitions deleted as a whole are left out. A table without
+    /// a file has no rows.
+    ///
+    /// # Parameters
+    /// - `keyspace`: The keyspace of the table.
+    /// - `table`: The schema of the table, which tells its partition key columns.
+    /// - `is_replication`: Whether to read the replicas this node keeps instead of its rows.
+    /// - `start_token`: The first token of the range.
+    /// - `end_token`: The token after the last one of the range.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::IoError` if the table file cannot be read.
+    /// - `StorageEngineError::FileReadFailed` if the partition tombstones cannot be read.
+    /// - `StorageEngineError::UnsupportedOperation` if the token of a row cannot be computed.
+    pub fn scan_range(
+        &self,
+        keyspace: &str,
+        table: &TableSchema,
+        is_replication: bool,
+        start_token: u64,
+        end_token: u64,
+    ) -> Result<Vec<String>, StorageEngineError> {
+        self.read_range(
+            keyspace,
+            table,
+            is_replication,
+            start_token,
+            end_token,
+            false,
+        )
+    }
+
+    /// Like [`StorageEngine::scan_range`], but keeps the deleted rows as `values;timestamp;tombstone`.
+    ///
+    /// Rows of partitions deleted as a whole are still left out, since the partition tombstone
+    /// already covers them.
+    pub(crate) fn scan_range_with_tombstones(
+        &self,
+        keyspace: &str,
+        table: &TableSchema,
+        is_replication: bool,
+        start_token: u64,
+        end_token: u64,
+    ) -> Result<Vec<String>, StorageEngineError> {
+        self.read_range(
+            keyspace,
+            table,
+            is_replication,
+            start_token,
+            end_token,
+            true,
+        )
+    }
+
+    fn read_range(
+        &self,
+        keyspace: &str,
+        table: &TableSchema,
+        is_replication: bool,
+        start_token: u64,
+        end_token: u64,
+        keep_tombstones: bool,
+    ) -> Result<Vec<String>, StorageEngineError> {
+        let table_name = table.get_name();
+        let folder_path = self
+            .get_keyspace_path(keyspace, &table_name)
+            .join(if is_replication { "replication" } else { "" });
+        let file_path = folder_path.join(format!("{}.csv", table_name));
+        if !file_path.exists() || start_token >= end_token {
+            return Ok(Vec::new());
+        }
+
+        let partition_key_indexes: Vec<usize> = table
+            .get_columns()
+            .iter()
+            .enumerate()
+            .filter(|(_, column)| column.is_partition_key)
+            .map(|(i, _)| i)
+            .collect();
+        let partition_tombstones = read_partition_tombstones(&folder_path, &table_name)?;
+
+        let file = File::open(&file_path).map_err(|_| StorageEngineError::IoError)?;
+        let mut rows = Vec::new();
+        for line in BufReader::new(file).lines().skip(1) {
+            let line = line.map_err(|_| StorageEngineError::IoError)?;
+            let Some((data, time_of_row)) = split_stored_row(&line) else {
+                continue;
+            };
+            let values: Vec<&str> = split_storage_values(data);
+            // El token sale de los valores sin escapar, como lo calcula el coordinador
+            let partition_key: Vec<String> = partition_key_indexes
+                .iter()
+                .map(|&i| unescape_storage_text(values.get(i).copied().unwrap_or_default()))
+                .collect();
+
+            let (written_at, is_tombstone) = parse_row_time(time_of_row);
+            let shadowed = partition_tombstones
+                .get(&partition_key_of(&values, &partition_key_indexes))
+                .is_some_and(|&deleted_at| written_at <= deleted_at);
+            if (is_tombstone && !keep_tombstones) || shadowed {
+                continue;
+            }
+
+            let token = Partitioner::partition_token(&partition_key)
+                .map_err(|_| StorageEngineError::UnsupportedOperation)?;
+            if (start_token..end_token).contains(&token) {
+                rows.push(line);
+            }
+        }
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use query_creator::clauses::table::create_table_cql::CreateTable;
+    use std::{fs, path::PathBuf};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_scan_range_returns_exactly_the_rows_in_the_range() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        let table = TableSchema::new(
+            CreateTable::deserialize(
+                "CREATE TABLE sky.positions (flight TEXT, time INT, height INT, PRIMARY KEY ((flight), time))",
+            )
+            .unwrap(),
+        );
+
+        let folder_path = storage.get_keyspace_path("sky", "positions");
+        fs::create_dir_all(&folder_path).unwrap();
+        let mut contents = String::from("flight,time,height\n");
+        for i in 0..50 {
+            contents.push_str(&format!("AR{},{},{};{}\n", i, i % 3, i * 100, i + 1));
+        }
+        // Una fila borrada no se devuelve
+        contents.push_str("AR7,9,0;60;tombstone\n");
+        fs::write(folder_path.join("positions.csv"), contents).unwrap();
+
+        let token_of = |i: usize| Partitioner::partition_token(&[format!("AR{}", i)]).unwrap();
+        let mut tokens: Vec<u64> = (0..50).map(token_of).collect();
+        tokens.sort_unstable();
+        let (start, end) = (tokens[10], tokens[40]);
+
+        let rows = storage
+            .scan_range("sky", &table, false, start, end)
+            .unwrap();
+        let expected: Vec<String> = (0..50)
+            .filter(|&i| (start..end).contains(&token_of(i)))
+            .map(|i| format!("AR{},{},{};{}", i, i % 3, i * 100, i + 1))
+            .collect();
+        assert_eq!(expected.len(), 30);
+        assert_eq!(rows, expected);
+
+        // Todo el espacio de tokens devuelve todas las filas vivas
+        let all = storage
+            .scan_range("sky", &table, false, 0, u64::MAX)
+            .unwrap();
+        assert_eq!(all.len(), 50);
+        assert!(storage
+            .scan_range("sky", &table, false, end, start)
+            .unwrap()
+            .is_empty());
+        assert!(storage
+            .scan_range("sky", &table, true, 0, u64::MAX)
+            .unwrap()
+            .is_empty());
+
+        // La reparacion tambien necesita los tombstones
+        let with_tombstones = storage
+            .scan_range_with_tombstones("sky", &table, false, 0, u64::MAX)
+            .unwrap();
+        assert_eq!(with_tombstones.len(), 51);
+        assert_eq!(with_tombstones[50], "AR7,9,0;60;tombstone");
+
+        fs::remove_dir_all(root).ok();
+    }
+}
@@ -1,12 +1,28 @@
 use std::{
-    fs::{self, OpenOptions},
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
     io::{BufRead, BufReader, Seek},
 };
 
 use gossip::structures::application_state::TableSchema;
-use query_creator::clauses::select_cql::Select;
+use partitioner::Partitioner;
+use query_creator::clauses::{
+    condition::Condition,
+    insert_cql::NULL_VALUE,
+    select_cql::{CellFunction, Select},
+};
+use query_creator::operator::Operator;
 
-use super::{errors::StorageEngineError, StorageEngine};
+use super::{
+    errors::StorageEngineError,
+    group_by::{group_rows, rows_of_groups},
+    parse_row_time,
+    partition_tombstone::{
+        partition_key_indexes, partition_key_of, read_partition_tombstones, shadow_row,
+    },
+    split_stored_row, StorageEngine,
+};
+use native_protocol::messages::result::rows::{split_storage_values, unescape_storage_text};
 
 impl StorageEngine {
     /// Executes a `SELECT` query on a table stored as CSV files, returning rows that match the given conditions.
@@ -57,18 +73,24 @@ impl StorageEngine {
     ///    - Adds the complete column list (all table columns) and the selected column list (columns in the `SELECT` query) as the first two rows of the result.
     ///
     /// 5. **Row Filtering**:
-    ///    - Reads rows within the specified byte range (or the entire file if no clustering column is specified).
+    ///    - Reads rows through `select_iter`, within the specified byte range (or the entire file if no clustering column is specified).
     ///    - Evaluates each row against the `WHERE` clause conditions using the `line_matches_where_clause` helper function.
     ///    - Adds rows matching the conditions to the result vector.
     ///
-    /// 6. **Apply `LIMIT`**:
+    /// 6. **Apply `PER PARTITION LIMIT` and `LIMIT`**:
+    ///    - Keeps only the first rows of each partition, in clustering order, if a `PER PARTITION LIMIT` clause is present.
+    ///    - A `SELECT DISTINCT` keeps only the first row of each partition, so each partition key is returned once.
     ///    - Truncates the results to include only the specified number of rows if a `LIMIT` clause is present.
     ///
     /// 7. **Apply `ORDER BY`**:
     ///    - Sorts the results based on a single column and order (ascending or descending) if specified in the `ORDER BY` clause.
     ///    - Uses the `sort_results_single_column` helper function for sorting.
     ///
-    /// 8. **Return Results**:
+    /// 8. **Apply `GROUP BY`**:
+    ///    - A query with `GROUP BY` reads every matching row and returns one row per group instead,
+    ///      with `COUNT(*)` as the number of rows of the group. `LIMIT` and `ORDER BY` apply to the groups.
+    ///
+    /// 9. **Return Results**:
     ///    - Returns the vector of rows as `Ok(Vec<String>)`.
     ///    - If no rows match the conditions, the result includes only the headers.
     ///
@@ -87,7 +109,10 @@ impl StorageEngine {
     ///   If the directory for the keyspace or replication files cannot be created.
     ///
     /// - **`StorageEngineError::MissingWhereClause`**:
-    ///   If the `WHERE` clause is missing and is required for clustering column evaluation.
+    ///   If a row cannot be evaluated against the `WHERE` clause.
+    ///
+    /// - **`StorageEngineError::UnsupportedOperation`**:
+    ///   If the `WHERE` clause uses `CONTAINS` or `CONTAINS KEY`, which only apply to collection columns.
     ///
     /// - **`StorageEngineError::IoError`**:
     ///   For general input/output issues during file reading or seeking.
@@ -105,8 +130,201 @@ impl StorageEngine {
         is_replication: bool,
         keyspace: &str,
     ) -> Result<Vec<String>, StorageEngineError> {
+        if select_query.is_grouped() {
+            let rows = self.select(
+                rows_of_groups(&select_query),
+                table,
+                is_replication,
+                keyspace,
+            )?;
+            return Self::order_groups(group_rows(rows, &select_query)?, &select_query);
+        }
+
+        let mut results = Vec::new();
+        let complete_columns: Vec<String> =
+            table.get_columns().iter().map(|c| c.name.clone()).collect();
+        results.push(complete_columns.join(","));
+        results.push(select_query.columns.join(","));
+
+        // `PER PARTITION LIMIT` y `LIMIT` ya los aplica el iterador
+        for row in self.select_iter(&select_query, &table, is_replication, keyspace)? {
+            results.push(row?);
+        }
+
+        // Ordenar los resultados si hay cláusula `ORDER BY`
+        if let Some(order_by) = select_query.orderby_clause {
+            Self::sort_results_single_column(&mut results, &order_by.columns[0], &order_by.order)?
+        }
+
+        Ok(results)
+    }
+
+    /// Executes a `SELECT` over the folder of the data owned by the node and its `replication`
+    /// folder together, as if they were a single table.
+    ///
+    /// While data is being redistributed a row can be in either folder, so a read that only
+    /// looks at one of them may miss it. When both folders have a row with the same primary key
+    /// the newest write is kept, and on a tie the tombstone wins. Within a folder, the last line
+    /// with a primary key is its latest version. The partition tombstones of
+    /// both folders apply to the rows of both.
+    ///
+    /// `PER PARTITION LIMIT` and `LIMIT` are applied to the merged rows, in clustering order, and
+    /// `ORDER BY` afterwards. The result has the same format as `select`, tombstones included. A
+    /// folder without the table file is skipped. A `GROUP BY` groups the merged rows.
+    ///
+    /// # Errors
+    /// The same as `select`.
+    pub fn select_merged(
+        &self,
+        select_query: Select,
+        table: TableSchema,
+        keyspace: &str,
+    ) -> Result<Vec<String>, StorageEngineError> {
+        if select_query.is_grouped() {
+            let rows = self.select_merged(rows_of_groups(&select_query), table, keyspace)?;
+            return Self::order_groups(group_rows(rows, &select_query)?, &select_query);
+        }
+
+        let mut results = Vec::new();
+        let complete_columns: Vec<String> =
+            table.get_columns().iter().map(|c| c.name.clone()).collect();
+        results.push(complete_columns.join(","));
+        results.push(select_query.columns.join(","));
+
+        // Los limites se aplican despues de juntar las dos carpetas
+        let mut unlimited_query = select_query.clone();
+        unlimited_query.limit = None;
+        unlimited_query.per_partition_limit = None;
+        unlimited_query.distinct = false;
+
+        let table_name = table.get_name();
+        let columns = table.get_columns();
+        let primary_key_indexes: Vec<usize> = columns
+            .iter()
+            .enumerate()
+            .filter(|(_, column)| column.is_partition_key || column.is_clustering_column)
+            .map(|(i, _)| i)
+            .collect();
+        let partition_key_indexes = partition_key_indexes(&table);
+
+        let mut merged: Vec<String> = Vec::new();
+        let mut position_of_key: HashMap<String, (usize, bool)> = HashMap::new();
+        let mut partition_tombstones: HashMap<String, i64> = HashMap::new();
+        for is_replication in [false, true] {
+            let mut folder_path = self.get_keyspace_path(keyspace, &table_name);
+            if is_replication {
+                folder_path = folder_path.join("replication");
+            }
+            if !folder_path.join(format!("{}.csv", table_name)).exists() {
+                continue;
+            }
+
+            for (partition_key, deleted_at) in read_partition_tombstones(&folder_path, &table_name)?
+            {
+                let newest = partition_tombstones
+                    .entry(partition_key)
+                    .or_insert(deleted_at);
+                *newest = (*newest).max(deleted_at);
+            }
+
+            for row in self.select_iter(&unlimited_query, &table, is_replication, keyspace)? {
+                let row = row?;
+                let primary_key = partition_key_of(&row_values(&row), &primary_key_indexes);
+                // En una misma carpeta la ultima linea es la ultima escritura
+                match position_of_key.get(&primary_key) {
+                    Some(&(i, from_replication)) => {
+                        if from_replication == is_replication || is_newer_version(&row, &merged[i])
+                        {
+                            merged[i] = row;
+                            position_of_key.insert(primary_key, (i, is_replication));
+                        }
+                    }
+                    None => {
+                        position_of_key.insert(primary_key, (merged.len(), is_replication));
+                        merged.push(row);
+                    }
+                }
+            }
+        }
+
+        // Cada carpeta ya esta en orden de clustering, el sort estable solo intercala las dos
+        let clustering_indices =
+            Self::get_clustering_indices(&columns, &table.get_clustering_column_in_order())?;
+        if !clustering_indices.is_empty() {
+            merged.sort_by(|a, b| {
+                Self::compare_clustering(
+                    &row_values(a),
+                    &row_values(b),
+                    &clustering_indices,
+                    &columns,
+                )
+                .unwrap_or(std::cmp::Ordering::Equal)
+            });
+        }
+
+        let mut remaining = select_query.limit;
+        let mut rows_per_partition: HashMap<String, usize> = HashMap::new();
+        for row in merged {
+            if remaining == Some(0) {
+                break;
+            }
+
+            let row = shadow_row(row, &partition_tombstones, &partition_key_indexes);
+            let time_of_row = split_stored_row(&row).map_or("", |(_, time)| time);
+            if !parse_row_time(time_of_row).1 {
+                if let Some(per_partition_limit) = select_query.rows_per_partition_limit() {
+                    let partition_key = partition_key_of(&row_values(&row), &partition_key_indexes);
+                    let taken = rows_per_partition.entry(partition_key).or_insert(0);
+                    if *taken >= per_partition_limit {
+                        continue;
+                    }
+                    *taken += 1;
+                }
+                if let Some(remaining) = remaining.as_mut() {
+                    *remaining -= 1;
+                }
+            }
+            results.push(row);
+        }
+
+        if let Some(order_by) = select_query.orderby_clause {
+            Self::sort_results_single_column(&mut results, &order_by.columns[0], &order_by.order)?
+        }
+
+        Ok(results)
+    }
+
+    /// Returns an iterator over the rows of a table that match a `SELECT`, reading them from disk
+    /// as the iterator advances instead of loading the whole result in memory.
+    ///
+    /// The iterator uses the clustering index like `select` to read only the byte range of the
+    /// first clustering column, skips the rows of a partition past its `PER PARTITION LIMIT`, and
+    /// stops after `LIMIT` rows. Each item is a row formatted as in
+    /// `select` (`value,value,...;timestamp`), without the two header rows. `ORDER BY` is not
+    /// applied, since sorting needs every row: `select` sorts the collected rows afterwards.
+    ///
+    /// Deleted rows are returned too, as tombstones (`value,value,...;timestamp;tombstone`), so
+    /// the coordinator can tell them apart from rows a replica is missing. They do not count
+    /// towards either limit. A row of a partition deleted as a whole, written at or before the
+    /// partition tombstone, is returned as a tombstone with the timestamp of that delete.
+    ///
+    /// # Errors
+    /// - Opening the table fails with the same errors as `select`.
+    /// - Each item is an `Err(StorageEngineError)` if the row cannot be read or evaluated; the
+    ///   iterator ends after returning an error.
+    pub fn select_iter<'a>(
+        &'a self,
+        select_query: &Select,
+        table: &TableSchema,
+        is_replication: bool,
+        keyspace: &str,
+    ) -> Result<SelectRows<'a>, StorageEngineError> {
+        if let Some(where_clause) = &select_query.where_clause {
+            check_where_operators(&where_clause.condition)?;
+        }
+
         let table_name = table.get_name();
-        let base_folder_path = self.get_keyspace_path(keyspace);
+        let base_folder_path = self.get_keyspace_path(keyspace, &table_name);
 
         // Construcción de la ruta de la carpeta según si es replicación o no
         let folder_path = if is_replication {
@@ -136,11 +354,10 @@ impl StorageEngine {
 
         // Obtener la primera columna de clustering y sus valores
         if let Some(first_clustering_column) = table.get_clustering_column_in_order().get(0) {
-            let clustering_value = select_query
-                .clone()
-                .where_clause
-                .ok_or(StorageEngineError::MissingWhereClause)?
-                .get_value_for_clustering_column(&first_clustering_column);
+            // Un scan con ALLOW FILTERING puede no tener WHERE y lee todo el archivo
+            let clustering_value = select_query.where_clause.as_ref().and_then(|where_clause| {
+                where_clause.get_value_for_clustering_column(first_clustering_column)
+            });
 
             if let Some(clustering_column_value) = clustering_value {
                 for (i, line) in index_reader.lines().enumerate() {
@@ -149,8 +366,10 @@ impl StorageEngine {
                         continue;
                     }
                     let line = line?;
-                    let parts: Vec<&str> = line.split(',').collect();
-                    if parts.len() == 3 && parts[0] == clustering_column_value {
+                    let parts: Vec<&str> = split_storage_values(&line);
+                    if parts.len() == 3
+                        && unescape_storage_text(parts[0]) == clustering_column_value
+                    {
                         start_byte = parts[1].parse::<u64>().unwrap_or(0);
                         end_byte = parts[2].parse::<u64>().unwrap_or(u64::MAX);
                         break;
@@ -168,48 +387,35 @@ impl StorageEngine {
             reader.read_line(&mut buffer)?; // Leer y descartar el header
         }
 
-        let mut results = Vec::new();
-        let complete_columns: Vec<String> =
-            table.get_columns().iter().map(|c| c.name.clone()).collect();
-        results.push(complete_columns.join(","));
-        results.push(select_query.columns.join(","));
-
-        // Leer las líneas del rango especificado
-        let mut current_byte_offset = start_byte;
-
-        while current_byte_offset < end_byte {
-            let mut buffer = String::new();
-            let bytes_read = reader.read_line(&mut buffer)?;
-            if bytes_read == 0 {
-                break; // Fin del archivo
-            }
-            current_byte_offset += bytes_read as u64;
-            let (line, _) = buffer
-                .trim_end()
-                .split_once(";")
-                .ok_or(StorageEngineError::IoError)?;
-            if self.line_matches_where_clause(&line, &table, &select_query)? {
-                results.push(buffer.trim_end().to_string());
-            }
-        }
+        let partition_tombstones = read_partition_tombstones(&folder_path, &table_name)?;
 
-        // Aplicar `LIMIT` si está presente
-        if let Some(limit) = select_query.limit {
-            if limit < results.len() - 2 {
-                results = results[..limit + 2].to_vec();
-            }
-        }
+        Ok(SelectRows {
+            storage_engine: self,
+            reader,
+            select_query: select_query.clone(),
+            table: table.clone(),
+            current_byte_offset: start_byte,
+            end_byte,
+            remaining: select_query.limit,
+            partition_key_indexes: partition_key_indexes(table),
+            partition_tombstones,
+            rows_per_partition: HashMap::new(),
+            finished: false,
+        })
+    }
 
-        // Ordenar los resultados si hay cláusula `ORDER BY`
-        if let Some(order_by) = select_query.orderby_clause {
-            self.sort_results_single_column(&mut results, &order_by.columns[0], &order_by.order)?
+    // Aplica el `ORDER BY` a las filas de los grupos, que ya tienen el `LIMIT` aplicado
+    fn order_groups(
+        mut groups: Vec<String>,
+        select_query: &Select,
+    ) -> Result<Vec<String>, StorageEngineError> {
+        if let Some(order_by) = &select_query.orderby_clause {
+            Self::sort_results_single_column(&mut groups, &order_by.columns[0], &order_by.order)?
         }
-
-        Ok(results)
+        Ok(groups)
     }
 
     fn sort_results_single_column(
-        &self,
         results: &mut Vec<String>,
         order_by_column: &str,
         order: &str, // Either "ASC" or "DESC"
@@ -233,9 +439,10 @@ impl StorageEngine {
         if let Some(col_index) = col_index {
             // Define sort closure based on order
             rows.sort_by(|a, b| {
-                let a_val = a.split(',').nth(col_index).unwrap_or("");
-                let b_val = b.split(',').nth(col_index).unwrap_or("");
-                let cmp = a_val.cmp(b_val);
+                let value_of = |row: &str| {
+                    unescape_storage_text(row_values(row).get(col_index).unwrap_or(&""))
+                };
+                let cmp = value_of(a).cmp(&value_of(b));
 
                 match order {
                     "ASC" => cmp,
@@ -259,7 +466,11 @@ impl StorageEngine {
     ) -> Result<bool, StorageEngineError> {
         // Convert the line into a map of column to value
 
-        let values: Vec<String> = line.split(',').map(|s| s.trim().to_string()).collect();
+        // El WHERE compara con los valores como los escribio la query
+        let values: Vec<String> = split_storage_values(line)
+            .into_iter()
+            .map(|s| unescape_storage_text(s.trim()))
+            .collect();
         let column_value_map = self.create_column_value_map(table, &values, false);
 
         let columns = table.get_columns();
@@ -275,6 +486,306 @@ impl StorageEngine {
     }
 }
 
+/// Orders the rows of `table` that several nodes answered for `select_query` as a single node
+/// would, and applies the limits of the query to them.
+///
+/// Each node sorts and limits only its own rows, so the merged rows of a full scan, or of the
+/// replicas of a partition, can be out of order and past the limits. The partitions go in
+/// the order of their token, and the rows of each one by the `ORDER BY` column, or in
+/// clustering order without one. Then `PER PARTITION LIMIT` and `LIMIT` are applied.
+///
+/// The rows are the ones the nodes answer: their values joined by `,`, with the timestamp
+/// last.
+///
+/// # Errors
+/// - `StorageEngineError::UnsupportedOperation` if a value cannot be compared with its type.
+pub fn order_and_limit_merged_rows(
+    rows: Vec<String>,
+    select_query: &Select,
+    table: &TableSchema,
+) -> Result<Vec<String>, StorageEngineError> {
+    let columns = table.get_columns();
+    let partition_key_indexes = partition_key_indexes(table);
+    let mut sort_by: Vec<(usize, String)> = match &select_query.orderby_clause {
+        // Los pares van como los arma `get_clustering_indices`, con el orden invertido
+        Some(order_by) => columns
+            .iter()
+            .position(|column| column.name == order_by.columns[0])
+            .map(|index| {
+                let order = if order_by.order == "DESC" {
+                    "ASC"
+                } else {
+                    "DESC"
+                };
+                vec![(index, order.to_string())]
+            })
+            .unwrap_or_default(),
+        None => Vec::new(),
+    };
+    sort_by.extend(StorageEngine::get_clustering_indices(
+        &columns,
+        &table.get_clustering_column_in_order(),
+    )?);
+
+    let mut keyed_rows = Vec::with_capacity(rows.len());
+    for row in rows {
+        let values = split_storage_values(&row);
+        let partition_key: Vec<String> = partition_key_indexes
+            .iter()
+            .map(|&i| unescape_storage_text(values.get(i).copied().unwrap_or_default()))
+            .collect();
+        let token = Partitioner::partition_token(&partition_key)
+            .map_err(|_| StorageEngineError::UnsupportedOperation)?;
+        keyed_rows.push((
+            token,
+            partition_key_of(&values, &partition_key_indexes),
+            row,
+        ));
+    }
+
+    let mut failed = false;
+    keyed_rows.sort_by(|(token, key, row), (other_token, other_key, other_row)| {
+        token
+            .cmp(other_token)
+            .then_with(|| key.cmp(other_key))
+            .then_with(|| {
+                if sort_by.is_empty() {
+                    return std::cmp::Ordering::Equal;
+                }
+                StorageEngine::compare_clustering(
+                    &split_storage_values(row),
+                    &split_storage_values(other_row),
+                    &sort_by,
+                    &columns,
+                )
+                .unwrap_or_else(|_| {
+                    failed = true;
+                    std::cmp::Ordering::Equal
+                })
+            })
+    });
+    if failed {
+        return Err(StorageEngineError::UnsupportedOperation);
+    }
+
+    let mut limited = Vec::new();
+    let mut rows_per_partition: HashMap<String, usize> = HashMap::new();
+    for (_, partition_key, row) in keyed_rows {
+        if select_query
+            .limit
+            .is_some_and(|limit| limited.len() >= limit)
+        {
+            break;
+        }
+        if let Some(per_partition_limit) = select_query.rows_per_partition_limit() {
+            let taken = rows_per_partition.entry(partition_key).or_insert(0);
+            if *taken >= per_partition_limit {
+                continue;
+            }
+            *taken += 1;
+        }
+        limited.push(row);
+    }
+    Ok(limited)
+}
+
+/// Groups the rows of `table` that several nodes answered for the `GROUP BY` of
+/// `select_query`, once the coordinator kept the latest version of each, as a single node
+/// would.
+///
+/// The nodes answer the rows of the groups and not the groups: the replicas of a partition
+/// can each be missing different rows, so none of their counts has to be right. The rows are
+/// the ones the nodes answer, with the timestamp last, and so are the groups returned: the
+/// values of the `GROUP BY` columns followed by the requested ones, and the timestamp of the
+/// newest row of the group.
+///
+/// # Errors
+/// - `StorageEngineError::UnsupportedOperation` if a value cannot be compared with its type.
+/// - `StorageEngineError::ColumnNotFound` if a `GROUP BY` or requested column is not in `table`.
+pub fn group_merged_rows(
+    rows: Vec<String>,
+    select_query: &Select,
+    table: &TableSchema,
+) -> Result<Vec<String>, StorageEngineError> {
+    // Los grupos salen en el orden en que se lee su primera fila, como en un solo nodo
+    let rows = order_and_limit_merged_rows(rows, &rows_of_groups(select_query), table)?;
+    let complete_columns: Vec<String> =
+        table.get_columns().iter().map(|c| c.name.clone()).collect();
+    let mut results = vec![complete_columns.join(","), select_query.columns.join(",")];
+    results.extend(rows.into_iter().map(|row| match row.rsplit_once(',') {
+        Some((values, time_of_row)) => format!("{};{}", values, time_of_row),
+        None => row,
+    }));
+
+    let groups = StorageEngine::order_groups(group_rows(results, select_query)?, select_query)?;
+    Ok(groups
+        .iter()
+        .skip(2)
+        .map(|group| match split_stored_row(group) {
+            Some((values, time_of_row)) => format!("{},{}", values, time_of_row),
+            None => group.clone(),
+        })
+        .collect())
+}
+
+// Valores de una fila `valor,valor,...;timestamp`
+pub(super) fn row_values(row: &str) -> Vec<&str> {
+    split_storage_values(split_stored_row(row).map_or(row, |(values, _)| values))
+}
+
+// Si `row` le gana a `other`, la version de la misma fila de la otra carpeta
+pub(super) fn is_newer_version(row: &str, other: &str) -> bool {
+    let time_of = |row: &str| parse_row_time(split_stored_row(row).map_or("", |(_, time)| time));
+    let (written_at, is_tombstone) = time_of(row);
+    let (other_written_at, other_is_tombstone) = time_of(other);
+    written_at > other_written_at
+        || (written_at == other_written_at && is_tombstone && !other_is_tombstone)
+}
+
+/// Value that `WRITETIME` or `TTL` return for a `cell` of a row written at `time_of_row`, the
+/// part of a stored row that follows its values.
+///
+/// Every cell of a row is written together, so `WRITETIME` is the timestamp of the row. Rows
+/// are stored without a TTL and never expire, so `TTL` is null, as it is in Cassandra for a
+/// cell without one. Both are null for a null cell.
+pub fn cell_function_value(function: CellFunction, cell: &str, time_of_row: &str) -> String {
+    if cell == NULL_VALUE {
+        return NULL_VALUE.to_string();
+    }
+    match function {
+        CellFunction::WriteTime => parse_row_time(time_of_row).0.to_string(),
+        CellFunction::Ttl => NULL_VALUE.to_string(),
+    }
+}
+
+/// Rejects the conditions that cannot be evaluated on any column of the table.
+///
+/// `CONTAINS` and `CONTAINS KEY` only apply to collection columns, and there are no collection
+/// types yet, so a `WHERE` that uses them fails here instead of on the first row read.
+fn check_where_operators(condition: &Condition) -> Result<(), StorageEngineError> {
+    match condition {
+        Condition::Simple { operator, .. } => match operator {
+            Operator::Contains | Operator::ContainsKey => {
+                Err(StorageEngineError::UnsupportedOperation)
+            }
+            _ => Ok(()),
+        },
+        Condition::Complex { left, right, .. } => {
+            if let Some(left) = left {
+                check_where_operators(left)?;
+            }
+            check_where_operators(right)
+        }
+    }
+}
+
+/// Iterator over the rows that match a `SELECT`, returned by `StorageEngine::select_iter`.
+///
+/// Rows are read from the table file one at a time, so only the current row is kept in memory.
+pub struct SelectRows<'a> {
+    storage_engine: &'a StorageEngine,
+    reader: BufReader<File>,
+    select_query: Select,
+    table: TableSchema,
+    current_byte_offset: u64,
+    end_byte: u64,
+    // Filas que faltan devolver segun el `LIMIT`, si hay uno
+    remaining: Option<usize>,
+    partition_key_indexes: Vec<usize>,
+    // Timestamp del ultimo borrado de cada particion borrada entera
+    partition_tombstones: HashMap<String, i64>,
+    // Filas devueltas de cada particion, para el `PER PARTITION LIMIT`
+    rows_per_partition: HashMap<String, usize>,
+    finished: bool,
+}
+
+impl SelectRows<'_> {
+    /// Position in the table file up to which rows were read.
+    pub fn bytes_read(&self) -> u64 {
+        self.current_byte_offset
+    }
+
+    fn next_matching_row(&mut self) -> Result<Option<String>, StorageEngineError> {
+        while self.current_byte_offset < self.end_byte {
+            let mut buffer = String::new();
+            let bytes_read = self.reader.read_line(&mut buffer)?;
+            if bytes_read == 0 {
+                break; // Fin del archivo
+            }
+            self.current_byte_offset += bytes_read as u64;
+            let (line, _) =
+                split_stored_row(buffer.trim_end()).ok_or(StorageEngineError::IoError)?;
+            let matches = self.storage_engine.line_matches_where_clause(
+                line,
+                &self.table,
+                &self.select_query,
+            )?;
+            self.storage_engine
+                .metrics
+                .record_scanned_row(bytes_read as u64, matches);
+            if matches {
+                return Ok(Some(buffer.trim_end().to_string()));
+            }
+        }
+        Ok(None)
+    }
+
+    // Cuenta la fila en su particion; `false` si la particion ya llego a su limite
+    fn take_from_partition(&mut self, row: &str) -> bool {
+        let Some(per_partition_limit) = self.select_query.rows_per_partition_limit() else {
+            return true;
+        };
+
+        let partition_key = partition_key_of(&row_values(row), &self.partition_key_indexes);
+
+        let taken = self.rows_per_partition.entry(partition_key).or_insert(0);
+        if *taken < per_partition_limit {
+            *taken += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Iterator for SelectRows<'_> {
+    type Item = Result<String, StorageEngineError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished || self.remaining == Some(0) {
+            return None;
+        }
+
+        loop {
+            match self.next_matching_row() {
+                Ok(Some(row)) => {
+                    // Si la fila quedo tapada por el borrado de su particion, sale como tombstone
+                    let row =
+                        shadow_row(row, &self.partition_tombstones, &self.partition_key_indexes);
+                    let (values, time_of_row) =
+                        split_stored_row(&row).unwrap_or((row.as_str(), ""));
+                    let is_tombstone = parse_row_time(time_of_row).1;
+                    if !is_tombstone && !self.take_from_partition(values) {
+                        continue;
+                    }
+                    if let Some(remaining) = self.remaining.as_mut().filter(|_| !is_tombstone) {
+                        *remaining -= 1;
+                    }
+                    return Some(Ok(row));
+                }
+                Ok(None) => {
+                    self.finished = true;
+                    return None;
+                }
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -307,7 +818,7 @@ mod tests {
         let timestamp = 1234567890;
 
         // Limpiar el entorno
-        let folder_path = storage.get_keyspace_path(keyspace);
+        let folder_path = storage.get_keyspace_path(keyspace, table_name);
         if folder_path.exists() {
             fs::remove_dir_all(&folder_path).unwrap();
         }
@@ -391,6 +902,231 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_writetime_is_the_timestamp_of_the_last_write_of_the_cell() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        let columns = vec![
+            Column::new("id", DataType::Int, true, false),
+            Column::new("origin", DataType::String, false, true),
+        ];
+        storage.create_keyspace("sky").unwrap();
+        storage
+            .create_table("sky", "flights", vec!["id", "origin"])
+            .unwrap();
+        let table = TableSchema::new(
+            CreateTable::deserialize("CREATE TABLE sky.flights (id INT PRIMARY KEY, origin TEXT)")
+                .unwrap(),
+        );
+        let insert = |values: Vec<&str>, timestamp: i64| {
+            storage
+                .insert(
+                    "sky",
+                    "flights",
+                    values,
+                    columns.clone(),
+                    vec![],
+                    false,
+                    false,
+                    timestamp,
+                )
+                .unwrap();
+        };
+        let select_cells = |id: &str| {
+            let select = Select::deserialize(&format!(
+                "SELECT WRITETIME(origin), TTL(origin) FROM sky.flights WHERE id = {}",
+                id
+            ))
+            .unwrap();
+            let rows = storage
+                .select_merged(select.clone(), table.clone(), "sky")
+                .unwrap();
+            assert_eq!(rows[1], "WRITETIME(origin),TTL(origin)");
+            let (values, time_of_row) = rows[2].split_once(';').unwrap();
+            let origin = row_values(values)[1];
+            select
+                .columns
+                .iter()
+                .map(|column| {
+                    let (function, _) = CellFunction::of_column(column).unwrap();
+                    cell_function_value(function, origin, time_of_row)
+                })
+                .collect::<Vec<String>>()
+        };
+
+        insert(vec!["1", "EZE"], 1_700_000_000);
+        insert(vec!["2", NULL_VALUE], 1_700_000_001);
+        assert_eq!(select_cells("1"), ["1700000000", NULL_VALUE]);
+        assert_eq!(select_cells("2"), [NULL_VALUE, NULL_VALUE]);
+
+        // Una escritura nueva de la fila cambia el timestamp de la celda
+        insert(vec!["1", "AEP"], 1_700_000_500);
+        assert_eq!(select_cells("1"), ["1700000500", NULL_VALUE]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_texts_with_separators_read_back_as_they_were_written() {
+        use native_protocol::frame::Frame;
+        use native_protocol::messages::result::{result_, rows::ColumnType, rows::ColumnValue};
+
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        storage.create_keyspace("sky").unwrap();
+        storage
+            .create_table("sky", "notes", vec!["city", "id", "note"])
+            .unwrap();
+        let table = TableSchema::new(
+            CreateTable::deserialize(
+                "CREATE TABLE sky.notes (city TEXT, id INT, note TEXT, PRIMARY KEY ((city), id))",
+            )
+            .unwrap(),
+        );
+
+        // Cada texto se guarda escapado y se lee como se escribio
+        let city = "Buenos Aires, AR";
+        let notes = ["a,b;c", "back\\slash", "two\nlines\r", "\\,\\;"];
+        for (i, note) in notes.iter().enumerate() {
+            let id = i.to_string();
+            let values = [city, id.as_str(), note].map(super::super::escape_value);
+            storage
+                .insert(
+                    "sky",
+                    "notes",
+                    values.iter().map(String::as_str).collect(),
+                    table.get_columns(),
+                    table.get_clustering_column_in_order(),
+                    false,
+                    false,
+                    10,
+                )
+                .unwrap();
+        }
+
+        let select = Select::deserialize(&format!(
+            "SELECT city, id, note FROM sky.notes WHERE city = '{}'",
+            city
+        ))
+        .unwrap();
+        let rows: Vec<String> = storage
+            .select_merged(select, table.clone(), "sky")
+            .unwrap()
+            .into_iter()
+            .skip(2)
+            .map(|row| split_stored_row(&row).unwrap().0.to_string())
+            .collect();
+        assert_eq!(rows.len(), notes.len());
+
+        let col_types = vec![
+            ("city".to_string(), ColumnType::Varchar),
+            ("id".to_string(), ColumnType::Int),
+            ("note".to_string(), ColumnType::Varchar),
+        ];
+        let Frame::Result(result_::Result::Rows(rows)) =
+            query_creator::create_rows_frame(col_types, &rows).unwrap()
+        else {
+            panic!("expected rows");
+        };
+        for (row, note) in rows.rows_content.iter().zip(notes) {
+            assert_eq!(
+                row.get("city"),
+                Some(&ColumnValue::Varchar(city.to_string()))
+            );
+            assert_eq!(
+                row.get("note"),
+                Some(&ColumnValue::Varchar(note.to_string()))
+            );
+        }
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_tables_of_a_multi_root_engine_are_spread_and_readable() {
+        let base = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let roots: Vec<PathBuf> = (0..3).map(|i| base.join(format!("disk_{}", i))).collect();
+        let storage = StorageEngine::new_multi(roots.clone(), "127.0.0.1".to_string()).unwrap();
+        let keyspace = "test_keyspace";
+        let columns = vec![
+            Column::new("id", DataType::Int, true, false),
+            Column::new("name", DataType::String, false, false),
+        ];
+        let table_names: Vec<String> = (0..8).map(|i| format!("table_{}", i)).collect();
+
+        storage.create_keyspace(keyspace).unwrap();
+        for table_name in &table_names {
+            storage
+                .create_table(keyspace, table_name, vec!["id", "name"])
+                .unwrap();
+            storage
+                .insert(
+                    keyspace,
+                    table_name,
+                    vec!["1", table_name],
+                    columns.clone(),
+                    vec!["id".to_string()],
+                    false,
+                    false,
+                    10,
+                )
+                .unwrap();
+        }
+
+        // Cada tabla está en una sola raíz, y se usa más de una
+        let mut used_roots = Vec::new();
+        for table_name in &table_names {
+            let holders: Vec<&PathBuf> = roots
+                .iter()
+                .filter(|root| {
+                    root.join("keyspaces_of_127_0_0_1")
+                        .join(keyspace)
+                        .join(format!("{}.csv", table_name))
+                        .exists()
+                })
+                .collect();
+            assert_eq!(holders.len(), 1);
+            if !used_roots.contains(&holders[0]) {
+                used_roots.push(holders[0]);
+            }
+        }
+        assert!(used_roots.len() > 1);
+
+        for table_name in &table_names {
+            let full_name = format!("{}.{}", keyspace, table_name);
+            let create_table = CreateTable::new_from_tokens(vec![
+                "CREATE".to_string(),
+                "TABLE".to_string(),
+                full_name.clone(),
+                "id INT PRIMARY KEY, name TEXT".to_string(),
+            ])
+            .unwrap();
+            let select_query = Select::new_from_tokens(vec![
+                "SELECT".to_string(),
+                "id,name".to_string(),
+                "FROM".to_string(),
+                full_name,
+                "WHERE".to_string(),
+                "id".to_string(),
+                "=".to_string(),
+                "1".to_string(),
+            ])
+            .unwrap();
+
+            let rows = storage
+                .select(
+                    select_query,
+                    TableSchema::new(create_table),
+                    false,
+                    keyspace,
+                )
+                .unwrap();
+            assert_eq!(rows[2], format!("1,{};10", table_name));
+        }
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
     #[test]
     fn test_select_with_limit() {
         let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
@@ -399,12 +1135,12 @@ mod tests {
 
         let keyspace = "test_keyspace";
         let table_name = "test_table";
-        let mut name_column = Column::new("name", DataType::String, false, false);
-        name_column.is_clustering_column = true;
+        let mut age_column = Column::new("age", DataType::Int, false, false);
+        age_column.is_clustering_column = true;
         let columns = vec![
             Column::new("id", DataType::Int, true, false),
-            name_column,
-            Column::new("age", DataType::Int, false, false),
+            Column::new("name", DataType::String, false, false),
+            age_column,
         ];
         let clustering_columns_in_order = vec!["age".to_string()];
         let values_row1 = vec!["1", "John", "18"];
@@ -412,7 +1148,7 @@ mod tests {
         let values_row3 = vec!["1", "Jol", "20"];
         let timestamp = 1234567890;
 
-        let folder_path = storage.get_keyspace_path(keyspace);
+        let folder_path = storage.get_keyspace_path(keyspace, table_name);
         if folder_path.exists() {
             fs::remove_dir_all(&folder_path).unwrap();
         }
@@ -518,7 +1254,7 @@ mod tests {
         let values_row3 = vec!["3", "John", "20"];
         let timestamp = 1234567890;
 
-        let folder_path = storage.get_keyspace_path(keyspace);
+        let folder_path = storage.get_keyspace_path(keyspace, table_name);
         if folder_path.exists() {
             fs::remove_dir_all(&folder_path).unwrap();
         }
@@ -604,4 +1340,558 @@ mod tests {
             fs::remove_dir_all(&root).unwrap();
         }
     }
+
+    /// Inserta tres filas en `test_keyspace.test_table` y devuelve el esquema de la tabla.
+    fn storage_with_three_rows(storage: &StorageEngine) -> TableSchema {
+        let folder_path = storage.get_keyspace_path("test_keyspace", "test_table");
+        fs::create_dir_all(&folder_path).unwrap();
+        let mut file = File::create(folder_path.join("test_table.csv")).unwrap();
+        writeln!(file, "id,name,age").unwrap();
+
+        let columns = vec![
+            Column::new("id", DataType::Int, true, false),
+            Column::new("name", DataType::String, false, false),
+            Column::new("age", DataType::Int, false, false),
+        ];
+        for values in [
+            vec!["1", "John", "18"],
+            vec!["2", "Jane", "19"],
+            vec!["3", "Jol", "20"],
+        ] {
+            storage
+                .insert(
+                    "test_keyspace",
+                    "test_table",
+                    values,
+                    columns.clone(),
+                    vec![],
+                    false,
+                    false,
+                    1234567890,
+                )
+                .unwrap();
+        }
+
+        let create_table = CreateTable::new_from_tokens(vec![
+            "CREATE".to_string(),
+            "TABLE".to_string(),
+            "test_keyspace.test_table".to_string(),
+            "id INT PRIMARY KEY, name TEXT, age INT".to_string(),
+        ])
+        .unwrap();
+        TableSchema::new(create_table)
+    }
+
+    #[test]
+    fn test_select_merged_finds_rows_that_are_only_in_the_replication_folder() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        let table = storage_with_three_rows(&storage);
+
+        let replication_path = storage
+            .get_keyspace_path("test_keyspace", "test_table")
+            .join("replication");
+        fs::create_dir_all(&replication_path).unwrap();
+        fs::write(replication_path.join("test_table.csv"), "id,name,age\n").unwrap();
+        for (values, timestamp) in [
+            (vec!["4", "Ana", "21"], 1234567890),
+            (vec!["2", "Janet", "30"], 1234567900),
+        ] {
+            storage
+                .insert(
+                    "test_keyspace",
+                    "test_table",
+                    values,
+                    table.get_columns(),
+                    vec![],
+                    true,
+                    false,
+                    timestamp,
+                )
+                .unwrap();
+        }
+        let query = |id: &str| {
+            Select::deserialize(&format!(
+                "SELECT id,name,age FROM test_keyspace.test_table WHERE id = {}",
+                id
+            ))
+            .unwrap()
+        };
+
+        // La fila 4 todavia no llego a la carpeta principal
+        let primary = storage
+            .select(query("4"), table.clone(), false, "test_keyspace")
+            .unwrap();
+        assert_eq!(primary.len(), 2);
+        let merged = storage
+            .select_merged(query("4"), table.clone(), "test_keyspace")
+            .unwrap();
+        assert_eq!(merged[2..], ["4,Ana,21;1234567890".to_string()]);
+
+        // Si las dos carpetas tienen la fila, gana la escritura mas nueva
+        let merged = storage
+            .select_merged(query("2"), table.clone(), "test_keyspace")
+            .unwrap();
+        assert_eq!(merged[2..], ["2,Janet,30;1234567900".to_string()]);
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_select_with_not_equal_where() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        let table = storage_with_three_rows(&storage);
+
+        let select_query = Select::new_from_tokens(vec![
+            "SELECT".to_string(),
+            "id,name".to_string(),
+            "FROM".to_string(),
+            "test_keyspace.test_table".to_string(),
+            "WHERE".to_string(),
+            "name".to_string(),
+            "!=".to_string(),
+            "Jane".to_string(),
+            "AND".to_string(),
+            "age".to_string(),
+            "!=".to_string(),
+            "20".to_string(),
+        ])
+        .unwrap();
+        let result_rows = storage
+            .select(select_query, table, false, "test_keyspace")
+            .unwrap();
+
+        assert_eq!(result_rows.len(), 3); // Cabeceras + 1 fila
+        assert_eq!(result_rows[2], "1,John,18;1234567890");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_select_with_contains_on_a_column_that_is_not_a_collection() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        let table = storage_with_three_rows(&storage);
+
+        for operator in [vec!["CONTAINS"], vec!["CONTAINS", "KEY"]] {
+            let mut tokens: Vec<String> = ["SELECT", "id", "FROM", "test_keyspace.test_table"]
+                .iter()
+                .map(|token| token.to_string())
+                .collect();
+            tokens.extend(["WHERE".to_string(), "name".to_string()]);
+            tokens.extend(operator.iter().map(|token| token.to_string()));
+            tokens.push("Jane".to_string());
+
+            let select_query = Select::new_from_tokens(tokens).unwrap();
+            assert!(matches!(
+                storage.select(select_query, table.clone(), false, "test_keyspace"),
+                Err(StorageEngineError::UnsupportedOperation)
+            ));
+        }
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_select_iter_reads_rows_lazily() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        let keyspace = "test_keyspace";
+        let table_name = "test_table";
+        let columns = vec![
+            Column::new("id", DataType::Int, true, false),
+            Column::new("name", DataType::String, false, false),
+        ];
+
+        let folder_path = storage.get_keyspace_path(keyspace, table_name);
+        fs::create_dir_all(&folder_path).unwrap();
+        let table_file_path = folder_path.join(format!("{}.csv", table_name));
+        fs::write(&table_file_path, "id,name\n").unwrap();
+
+        let names: Vec<String> = (0..5000).map(|i| format!("passenger{}", i)).collect();
+        let ids: Vec<String> = (0..5000).map(|i: i32| i.to_string()).collect();
+        storage
+            .batch_insert(
+                keyspace,
+                table_name,
+                ids.iter()
+                    .zip(&names)
+                    .map(|(id, name)| (vec![id.as_str(), name.as_str()], 1))
+                    .collect(),
+                columns,
+                vec![],
+                false,
+                false,
+            )
+            .unwrap();
+
+        let create_table = CreateTable::new_from_tokens(vec![
+            "CREATE".to_string(),
+            "TABLE".to_string(),
+            "test_keyspace.test_table".to_string(),
+            "id INT PRIMARY KEY, name TEXT".to_string(),
+        ])
+        .unwrap();
+        let table = TableSchema::new(create_table);
+        let select_query =
+            Select::deserialize("SELECT id,name FROM test_keyspace.test_table").unwrap();
+
+        let collected = storage
+            .select(select_query.clone(), table.clone(), false, keyspace)
+            .unwrap();
+        assert_eq!(collected.len(), 5002);
+
+        let mut rows = storage
+            .select_iter(&select_query, &table, false, keyspace)
+            .unwrap();
+        let first_rows: Vec<String> = rows.by_ref().take(10).map(Result::unwrap).collect();
+        assert_eq!(first_rows, collected[2..12].to_vec());
+
+        // Solo se leyo el principio del archivo
+        let file_size = fs::metadata(&table_file_path).unwrap().len();
+        assert!(rows.bytes_read() < file_size / 100);
+
+        let remaining: Vec<String> = rows.map(Result::unwrap).collect();
+        assert_eq!(remaining, collected[12..].to_vec());
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_select_with_per_partition_limit_caps_each_partition() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        let keyspace = "test_keyspace";
+        let table_name = "positions";
+        let mut time_column = Column::new("time", DataType::Int, false, false);
+        time_column.is_clustering_column = true;
+        let columns = vec![
+            Column::new("flight", DataType::String, true, false),
+            time_column,
+            Column::new("height", DataType::Int, false, false),
+        ];
+
+        let folder_path = storage.get_keyspace_path(keyspace, table_name);
+        fs::create_dir_all(&folder_path).unwrap();
+        fs::write(
+            folder_path.join(format!("{}.csv", table_name)),
+            "flight,time,height\n",
+        )
+        .unwrap();
+
+        // Los valores de clustering no se repiten entre particiones
+        let rows = [
+            ("AR1", "6", "900"),
+            ("AR2", "3", "100"),
+            ("AR1", "1", "300"),
+            ("AR3", "5", "700"),
+            ("AR1", "8", "1000"),
+            ("AR3", "2", "600"),
+            ("AR1", "4", "500"),
+            ("AR3", "7", "800"),
+        ];
+        storage
+            .batch_insert(
+                keyspace,
+                table_name,
+                rows.iter()
+                    .map(|(flight, time, height)| (vec![*flight, *time, *height], 1))
+                    .collect(),
+                columns,
+                vec!["time".to_string()],
+                false,
+                false,
+            )
+            .unwrap();
+
+        let table = TableSchema::new(
+            CreateTable::new_from_tokens(vec![
+                "CREATE".to_string(),
+                "TABLE".to_string(),
+                "test_keyspace.positions".to_string(),
+                "flight TEXT, time INT, height INT, PRIMARY KEY (flight, time)".to_string(),
+            ])
+            .unwrap(),
+        );
+        let select = |query: &str| {
+            let rows = storage
+                .select(
+                    Select::deserialize(query).unwrap(),
+                    table.clone(),
+                    false,
+                    keyspace,
+                )
+                .unwrap();
+            rows[2..].to_vec()
+        };
+        let of_partition = |rows: &[String], flight: &str| -> Vec<String> {
+            rows.iter()
+                .filter(|row| row.starts_with(&format!("{},", flight)))
+                .cloned()
+                .collect()
+        };
+
+        let all = select("SELECT flight,time FROM test_keyspace.positions WHERE height > 0");
+        let capped =
+            select("SELECT flight,time FROM test_keyspace.positions WHERE height > 0 PER PARTITION LIMIT 2");
+
+        // Cada particion se corta por separado, con sus primeras filas en orden de clustering
+        assert_eq!(capped.len(), 5);
+        for (flight, expected) in [("AR1", 2), ("AR2", 1), ("AR3", 2)] {
+            let partition = of_partition(&capped, flight);
+            assert_eq!(partition.len(), expected, "{}", flight);
+            assert_eq!(partition, of_partition(&all, flight)[..expected].to_vec());
+        }
+
+        // El `LIMIT` global se aplica sobre las filas que quedan
+        let limited =
+            select("SELECT flight,time FROM test_keyspace.positions WHERE height > 0 PER PARTITION LIMIT 2 LIMIT 3");
+        assert_eq!(limited, capped[..3].to_vec());
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_select_distinct_returns_one_row_per_partition() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        let keyspace = "test_keyspace";
+        let table_name = "positions";
+        let mut time_column = Column::new("time", DataType::Int, false, false);
+        time_column.is_clustering_column = true;
+        let columns = vec![
+            Column::new("flight", DataType::String, true, false),
+            time_column,
+            Column::new("height", DataType::Int, false, false),
+        ];
+
+        let folder_path = storage.get_keyspace_path(keyspace, table_name);
+        fs::create_dir_all(&folder_path).unwrap();
+        fs::write(
+            folder_path.join(format!("{}.csv", table_name)),
+            "flight,time,height\n",
+        )
+        .unwrap();
+
+        let rows = [
+            ("AR1", "6", "900"),
+            ("AR2", "3", "100"),
+            ("AR1", "1", "300"),
+            ("AR3", "5", "700"),
+            ("AR1", "8", "1000"),
+            ("AR3", "2", "600"),
+        ];
+        storage
+            .batch_insert(
+                keyspace,
+                table_name,
+                rows.iter()
+                    .map(|(flight, time, height)| (vec![*flight, *time, *height], 1))
+                    .collect(),
+                columns,
+                vec!["time".to_string()],
+                false,
+                false,
+            )
+            .unwrap();
+
+        let table = TableSchema::new(
+            CreateTable::new_from_tokens(vec![
+                "CREATE".to_string(),
+                "TABLE".to_string(),
+                "test_keyspace.positions".to_string(),
+                "flight TEXT, time INT, height INT, PRIMARY KEY (flight, time)".to_string(),
+            ])
+            .unwrap(),
+        );
+        let distinct = storage
+            .select(
+                Select::deserialize("SELECT DISTINCT flight FROM test_keyspace.positions").unwrap(),
+                table,
+                false,
+                keyspace,
+            )
+            .unwrap();
+
+        let mut flights: Vec<&str> = distinct[2..]
+            .iter()
+            .map(|row| row.split(',').next().unwrap())
+            .collect();
+        flights.sort();
+        assert_eq!(flights, ["AR1", "AR2", "AR3"]);
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_select_group_by_partition_key_counts_the_rows_of_each_partition() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        let keyspace = "test_keyspace";
+        let table_name = "positions";
+        let mut time_column = Column::new("time", DataType::Int, false, false);
+        time_column.is_clustering_column = true;
+        let columns = vec![
+            Column::new("flight", DataType::String, true, false),
+            time_column,
+            Column::new("height", DataType::Int, false, false),
+        ];
+
+        let folder_path = storage.get_keyspace_path(keyspace, table_name);
+        fs::create_dir_all(&folder_path).unwrap();
+        fs::write(
+            folder_path.join(format!("{}.csv", table_name)),
+            "flight,time,height\n",
+        )
+        .unwrap();
+
+        let rows = [
+            ("AR1", "6", "900", 10),
+            ("AR2", "3", "100", 20),
+            ("AR1", "1", "300", 30),
+            ("AR3", "5", "700", 40),
+            ("AR1", "8", "1000", 50),
+            ("AR3", "2", "600", 60),
+        ];
+        storage
+            .batch_insert(
+                keyspace,
+                table_name,
+                rows.iter()
+                    .map(|(flight, time, height, timestamp)| {
+                        (vec![*flight, *time, *height], *timestamp)
+                    })
+                    .collect(),
+                columns,
+                vec!["time".to_string()],
+                false,
+                false,
+            )
+            .unwrap();
+
+        let table = TableSchema::new(
+            CreateTable::new_from_tokens(vec![
+                "CREATE".to_string(),
+                "TABLE".to_string(),
+                "test_keyspace.positions".to_string(),
+                "flight TEXT, time INT, height INT, PRIMARY KEY (flight, time)".to_string(),
+            ])
+            .unwrap(),
+        );
+        let select = |query: &str| {
+            let grouped = storage
+                .select(
+                    Select::deserialize(query).unwrap(),
+                    table.clone(),
+                    false,
+                    keyspace,
+                )
+                .unwrap();
+            let merged = storage
+                .select_merged(Select::deserialize(query).unwrap(), table.clone(), keyspace)
+                .unwrap();
+            assert_eq!(grouped, merged);
+            grouped
+        };
+
+        // Un grupo por particion, con la ultima escritura de sus filas como timestamp
+        let mut counts =
+            select("SELECT flight, COUNT(*) FROM test_keyspace.positions GROUP BY flight");
+        assert_eq!(counts[..2], ["flight,flight,COUNT(*)", "flight,COUNT(*)"]);
+        counts[2..].sort();
+        assert_eq!(
+            counts[2..],
+            ["AR1,AR1,3;50", "AR2,AR2,1;20", "AR3,AR3,2;60"]
+        );
+
+        // El `WHERE` filtra las filas antes de agruparlas
+        assert_eq!(
+            select("SELECT COUNT(*) FROM test_keyspace.positions WHERE height > 500 GROUP BY flight ORDER BY flight DESC")[2..],
+            ["AR3,2;60", "AR1,2;50"]
+        );
+
+        // El `LIMIT` cuenta grupos y no filas
+        assert_eq!(
+            select("SELECT flight, COUNT(*) FROM test_keyspace.positions GROUP BY flight LIMIT 2")
+                .len(),
+            4
+        );
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_metrics_count_rows_scanned_by_full_scans_and_indexed_lookups() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        let keyspace = "test_keyspace";
+        let table_name = "positions";
+        let mut time_column = Column::new("time", DataType::Int, false, false);
+        time_column.is_clustering_column = true;
+        let columns = vec![
+            Column::new("flight", DataType::String, true, false),
+            time_column,
+            Column::new("height", DataType::Int, false, false),
+        ];
+
+        storage.create_keyspace(keyspace).unwrap();
+        storage
+            .create_table(keyspace, table_name, vec!["flight", "time", "height"])
+            .unwrap();
+        let times: Vec<String> = (0..50).map(|time| time.to_string()).collect();
+        for time in &times {
+            storage
+                .insert(
+                    keyspace,
+                    table_name,
+                    vec!["AR1", time, "100"],
+                    columns.clone(),
+                    vec!["time".to_string()],
+                    false,
+                    false,
+                    1,
+                )
+                .unwrap();
+        }
+
+        let table = TableSchema::new(
+            CreateTable::new_from_tokens(vec![
+                "CREATE".to_string(),
+                "TABLE".to_string(),
+                "test_keyspace.positions".to_string(),
+                "flight TEXT, time INT, height INT, PRIMARY KEY (flight, time)".to_string(),
+            ])
+            .unwrap(),
+        );
+        let scanned_by = |query: &str| {
+            let before = storage.metrics();
+            let rows = storage
+                .select(
+                    Select::deserialize(query).unwrap(),
+                    table.clone(),
+                    false,
+                    keyspace,
+                )
+                .unwrap();
+            (rows.len() - 2, storage.metrics().since(&before))
+        };
+
+        let (full_scan_rows, full_scan) =
+            scanned_by("SELECT flight,time FROM test_keyspace.positions WHERE flight = 'AR1'");
+        assert_eq!(full_scan_rows, 50);
+        assert_eq!(full_scan.rows_scanned, 50);
+        assert_eq!(full_scan.rows_matched, 50);
+        assert!(full_scan.bytes_read > 0);
+
+        let (lookup_rows, lookup) = scanned_by(
+            "SELECT flight,time FROM test_keyspace.positions WHERE flight = 'AR1' AND time = 25",
+        );
+        assert_eq!(lookup_rows, 1);
+        assert_eq!(lookup.rows_matched, 1);
+        assert!(lookup.rows_scanned < 5);
+        assert!(lookup.bytes_read < full_scan.bytes_read);
+
+        fs::remove_dir_all(&root).ok();
+    }
 }
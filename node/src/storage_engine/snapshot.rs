@@ -0,0 +1,231 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+
+use super::{errors::StorageEngineError, table_operations::files_of_table, StorageEngine};
+
+impl StorageEngine {
+    /// Copies the files of `table` (rows, index, partition tombstones and schema, of this node
+    /// and of the replicas it keeps) into a new snapshot folder inside `dest_dir`, and returns
+    /// its path.
+    ///
+    /// The snapshot is a consistent cut: the writes to the table wait while it is taken. It is
+    /// built in a temporary folder that is renamed once complete, so a snapshot folder that
+    /// exists is never partial. The folder is named `<keyspace>.<table>.<timestamp>`, with the
+    /// timestamp in milliseconds, and holds the replicas in a `replication` subfolder, like
+    /// the keyspace folder does.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::FileNotFound` if the table does not exist.
+    /// - `StorageEngineError::DirectoryCreationFailed` if the snapshot folder cannot be created.
+    /// - `StorageEngineError::IoError` if a file cannot be copied.
+    pub fn snapshot(
+        &self,
+        keyspace: &str,
+        table: &str,
+        dest_dir: &Path,
+    ) -> Result<PathBuf, StorageEngineError> {
+        let keyspace_path = self.get_keyspace_path(keyspace, table);
+        let replication_path = keyspace_path.join("replication");
+        if !keyspace_path.join(format!("{}.csv", table)).exists() {
+            return Err(StorageEngineError::FileNotFound);
+        }
+
+        let name = format!("{}.{}.{}", keyspace, table, Utc::now().timestamp_millis());
+        let snapshot_path = dest_dir.join(&name);
+        let temp_path = dest_dir.join(format!("{}.tmp", name));
+        fs::create_dir_all(temp_path.join("replication"))
+            .map_err(|_| StorageEngineError::DirectoryCreationFailed)?;
+
+        let copied = self.with_table_locked(&keyspace_path, &replication_path, table, || {
+            copy_files_of_table(&keyspace_path, &temp_path, table)?;
+            copy_files_of_table(&replication_path, &temp_path.join("replication"), table)
+        });
+        if let Err(e) = copied.and_then(|_| Ok(fs::rename(&temp_path, &snapshot_path)?)) {
+            let _ = fs::remove_dir_all(&temp_path);
+            return Err(e);
+        }
+        Ok(snapshot_path)
+    }
+
+    /// Replaces the files of `table` with the ones of a snapshot taken by
+    /// [`StorageEngine::snapshot`], so the table has again the rows it had then.
+    ///
+    /// The writes to the table wait while it is restored. The files of the table that are not
+    /// in the snapshot (e.g. partition tombstones written after it) are removed.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::FileNotFound` if `snapshot_path` is not a snapshot of `table`.
+    /// - `StorageEngineError::FileDeletionFailed` if a file of the table cannot be removed.
+    /// - `StorageEngineError::IoError` or `StorageEngineError::FileReplacementFailed` if a
+    ///   file cannot be copied back.
+    pub fn restore_snapshot(
+        &self,
+        keyspace: &str,
+        table: &str,
+        snapshot_path: &Path,
+    ) -> Result<(), StorageEngineError> {
+        if !snapshot_path.join(format!("{}.csv", table)).exists() {
+            return Err(StorageEngineError::FileNotFound);
+        }
+        let keyspace_path = self.get_keyspace_path(keyspace, table);
+        let replication_path = keyspace_path.join("replication");
+        fs::create_dir_all(&replication_path)
+            .map_err(|_| StorageEngineError::DirectoryCreationFailed)?;
+
+        self.with_table_locked(&keyspace_path, &replication_path, table, || {
+            for (from, to) in [
+                (snapshot_path.to_path_buf(), &keyspace_path),
+                (snapshot_path.join("replication"), &replication_path),
+            ] {
+                for path in files_of_table(to, table)? {
+                    let in_snapshot = path
+                        .file_name()
+                        .is_some_and(|name| from.join(name).exists());
+                    if in_snapshot {
+                        continue;
+                    }
+                    match fs::remove_file(&path) {
+                        Ok(()) => {}
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                        Err(_) => return Err(StorageEngineError::FileDeletionFailed),
+                    }
+                }
+
+                // Cada archivo se copia aparte y reemplaza al de la tabla de una sola vez
+                for path in existing_files_of_table(&from, table)? {
+                    let Some(name) = path.file_name() else {
+                        continue;
+                    };
+                    let target = to.join(name);
+                    let temp = to.join(format!("{}.restore.tmp", name.to_string_lossy()));
+                    fs::copy(&path, &temp)?;
+                    self.replace_table_file(&temp, &target)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    // Corre `action` con los locks de la tabla y de sus replicas tomados, para que ninguna
+    // escritura la modifique mientras tanto
+    fn with_table_locked<T>(
+        &self,
+        keyspace_path: &Path,
+        replication_path: &Path,
+        table: &str,
+        action: impl FnOnce() -> Result<T, StorageEngineError>,
+    ) -> Result<T, StorageEngineError> {
+        let primary_lock = Self::table_lock(&keyspace_path.join(format!("{}.csv", table)))?;
+        let replication_lock = Self::table_lock(&replication_path.join(format!("{}.csv", table)))?;
+        let _primary = primary_lock
+            .lock()
+            .map_err(|_| StorageEngineError::LockError)?;
+        let _replication = replication_lock
+            .lock()
+            .map_err(|_| StorageEngineError::LockError)?;
+        action()
+    }
+}
+
+// Archivos de la tabla que existen en `folder`, sin los temporales de escrituras interrumpidas
+fn existing_files_of_table(folder: &Path, table: &str) -> Result<Vec<PathBuf>, StorageEngineError> {
+    Ok(files_of_table(folder, table)?
+        .into_iter()
+        .filter(|path| path.is_file() && !StorageEngine::is_orphaned_temp_file(path))
+        .collect())
+}
+
+fn copy_files_of_table(from: &Path, to: &Path, table: &str) -> Result<(), StorageEngineError> {
+    for path in existing_files_of_table(from, table)? {
+        if let Some(name) = path.file_name() {
+            fs::copy(&path, to.join(name))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use query_creator::clauses::types::{column::Column, datatype::DataType};
+    use uuid::Uuid;
+
+    fn insert(storage: &StorageEngine, id: &str, origin: &str, timestamp: i64) {
+        let columns = vec![
+            Column::new("id", DataType::Int, true, false),
+            Column::new("origin", DataType::String, false, false),
+        ];
+        storage
+            .insert(
+                "sky",
+                "flights",
+                vec![id, origin],
+                columns,
+                vec![],
+                false,
+                false,
+                timestamp,
+            )
+            .unwrap();
+    }
+
+    fn rows_of(storage: &StorageEngine) -> String {
+        fs::read_to_string(
+            storage
+                .get_keyspace_path("sky", "flights")
+                .join("flights.csv"),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_a_snapshot_keeps_the_rows_of_when_it_was_taken_and_restores_them() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let storage = StorageEngine::new(root.join("data"), "127.0.0.1".to_string());
+        storage
+            .create_table("sky", "flights", vec!["id", "origin"])
+            .unwrap();
+        insert(&storage, "1", "EZE", 1);
+        insert(&storage, "2", "COR", 2);
+        let before = rows_of(&storage);
+
+        let snapshot = storage
+            .snapshot("sky", "flights", &root.join("backups"))
+            .unwrap();
+        assert!(snapshot
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("sky.flights."));
+        assert!(snapshot.join("flights_index.csv").exists());
+        assert!(snapshot.join("replication").join("flights.csv").exists());
+
+        // Lo que se escribe despues no cambia el snapshot
+        insert(&storage, "3", "MDZ", 3);
+        insert(&storage, "1", "AEP", 4);
+        assert_ne!(rows_of(&storage), before);
+        assert_eq!(
+            fs::read_to_string(snapshot.join("flights.csv")).unwrap(),
+            before
+        );
+
+        storage
+            .restore_snapshot("sky", "flights", &snapshot)
+            .unwrap();
+        assert_eq!(rows_of(&storage), before);
+        assert!(!storage
+            .get_keyspace_path("sky", "flights")
+            .join("flights.csv.restore.tmp")
+            .exists());
+
+        assert!(matches!(
+            storage.snapshot("sky", "airports", &root.join("backups")),
+            Err(StorageEngineError::FileNotFound)
+        ));
+        fs::remove_dir_all(root).ok();
+    }
+}
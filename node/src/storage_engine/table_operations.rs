@@ -1,10 +1,25 @@
-use super::{errors::StorageEngineError, StorageEngine};
-use std::fs::{self, OpenOptions};
-use std::io::{BufRead, BufReader, Write};
+use super::{
+    errors::StorageEngineError, escape_value, parse_row_time,
+    partition_tombstone::partition_tombstones_path, split_stored_row, StorageEngine,
+};
+use gossip::structures::application_state::{DataDigest, TableSchema};
+use native_protocol::messages::result::rows::{split_storage_values, unescape_storage_text};
+use partitioner::Partitioner;
+use query_creator::clauses::insert_cql::NULL_VALUE;
+use query_creator::clauses::types::column::Column;
+use query_creator::clauses::types::datatype::DataType;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
 
 impl StorageEngine {
     /// Creates a new table in the given keyspace.
     ///
+    /// Creating a table that already exists with the same columns does nothing, so its rows are
+    /// kept.
+    ///
     /// # Parameters
     ///
     /// * `keyspace`: The name of the keyspace where the table will be stored.
@@ -21,7 +36,8 @@ impl StorageEngine {
     ///
     /// * `StorageEngineError::DirectoryCreationFailed` if the directory for the table cannot be created.
     /// * `StorageEngineError::FileWriteFailed` if writing to the table or replication files fails.
-    /// * `StorageEngineError::IoError` if an I/O error occurs while renaming files.
+    /// * `StorageEngineError::FileReadFailed` if the header of an existing table cannot be read.
+    /// * `StorageEngineError::TableHeaderMismatch` if the table already exists with other columns.
     pub fn create_table(
         &self,
         keyspace: &str,
@@ -29,7 +45,7 @@ impl StorageEngine {
         columns: Vec<&str>,
     ) -> Result<(), StorageEngineError> {
         // Generate the folder name where the keyspace will be stored
-        let keyspace_path = self.get_keyspace_path(keyspace);
+        let keyspace_path = self.get_keyspace_path(keyspace, table);
         let replication_path = keyspace_path.join("replication");
 
         let primary_file_path = keyspace_path.join(format!("{}.csv", table));
@@ -41,61 +57,121 @@ impl StorageEngine {
         std::fs::create_dir_all(&replication_path)
             .map_err(|_| StorageEngineError::DirectoryCreationFailed)?;
 
-        // Create the file in the primary folder and write the columns as the header
-        let mut primary_file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(&primary_file_path)
-            .map_err(|_| StorageEngineError::FileWriteFailed)?;
-
         let header: Vec<String> = columns.iter().map(|col| col.to_string()).collect();
-        writeln!(primary_file, "{}", header.join(","))
-            .map_err(|_| StorageEngineError::FileWriteFailed)?;
-
-        // Create the same file in the replication folder and write the columns as the header
-        let mut replication_file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(&replication_file_path)
-            .map_err(|_| StorageEngineError::FileWriteFailed)?;
+        let header = header.join(",");
 
-        writeln!(replication_file, "{}", header.join(","))
-            .map_err(|_| StorageEngineError::FileWriteFailed)?;
+        // Create the files in the primary and replication folders with the columns as the header
+        Self::create_file_with_header(&primary_file_path, &header)?;
+        Self::create_file_with_header(&replication_file_path, &header)?;
 
-        // Create the index file in the primary folder
+        // Create the index files in the primary and replication folders
         let index_file_path = keyspace_path.join(format!("{}_index.csv", table));
-        let mut index_file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(&index_file_path)
-            .map_err(|_| StorageEngineError::FileWriteFailed)?;
-
-        writeln!(index_file, "clustering_column,start_byte,end_byte")
-            .map_err(|_| StorageEngineError::FileWriteFailed)?;
+        Self::create_file_with_header(&index_file_path, "clustering_column,start_byte,end_byte")?;
 
-        // Create the index file in the replication folder
         let replication_index_file_path = replication_path.join(format!("{}_index.csv", table));
-        let mut replication_index_file = OpenOptions::new()
-            .create(true)
+        Self::create_file_with_header(
+            &replication_index_file_path,
+            "clustering_column,first_byte,last_byte",
+        )?;
+
+        Ok(())
+    }
+
+    /// Creates the file at `path` with `header` as its first line.
+    ///
+    /// If the file already exists it is left untouched, as long as its first line is `header`.
+    fn create_file_with_header(path: &Path, header: &str) -> Result<(), StorageEngineError> {
+        if path.exists() {
+            if Self::first_line(path)? != header {
+                return Err(StorageEngineError::TableHeaderMismatch);
+            }
+            return Ok(());
+        }
+
+        let mut file = OpenOptions::new()
+            .create_new(true)
             .write(true)
-            .truncate(true)
-            .open(&replication_index_file_path)
+            .open(path)
             .map_err(|_| StorageEngineError::FileWriteFailed)?;
 
-        writeln!(
-            replication_index_file,
-            "clustering_column,first_byte,last_byte"
-        )
-        .map_err(|_| StorageEngineError::FileWriteFailed)?;
+        writeln!(file, "{}", header).map_err(|_| StorageEngineError::FileWriteFailed)
+    }
 
-        Ok(())
+    /// Names of the tables of `keyspace` this node has on disk, sorted.
+    ///
+    /// A table is a `<table>.csv` file with its `<table>_index.csv` next to it, so the files
+    /// of an interrupted write and the quarantine files are not listed. Only the folder of
+    /// the keyspace is read, not its `replication` folder.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::FileReadFailed` if the folder of the keyspace cannot be read.
+    pub fn list_tables(&self, keyspace: &str) -> Result<Vec<String>, StorageEngineError> {
+        let mut tables = Vec::new();
+
+        for keyspace_path in self.get_keyspace_paths(keyspace) {
+            if !keyspace_path.exists() {
+                continue;
+            }
+            for entry in
+                fs::read_dir(&keyspace_path).map_err(|_| StorageEngineError::FileReadFailed)?
+            {
+                let path = entry
+                    .map_err(|_| StorageEngineError::FileReadFailed)?
+                    .path();
+                let Some(table) = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| name.strip_suffix(".csv"))
+                else {
+                    continue;
+                };
+                if path.with_file_name(format!("{}_index.csv", table)).exists() {
+                    tables.push(table.to_string());
+                }
+            }
+        }
+
+        tables.sort();
+        tables.dedup();
+        Ok(tables)
+    }
+
+    /// Reads the header of `table`, with the names of its columns separated by commas.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::FileReadFailed` if the file of the table cannot be read.
+    pub fn read_table_header(
+        &self,
+        keyspace: &str,
+        table: &str,
+    ) -> Result<String, StorageEngineError> {
+        let path = self
+            .get_keyspace_path(keyspace, table)
+            .join(format!("{}.csv", table));
+        Self::first_line(&path)
+    }
+
+    /// First line of the file at `path`, or an empty string if the file is empty.
+    fn first_line(path: &Path) -> Result<String, StorageEngineError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .open(path)
+            .map_err(|_| StorageEngineError::FileReadFailed)?;
+        Ok(BufReader::new(file)
+            .lines()
+            .next()
+            .transpose()
+            .map_err(|_| StorageEngineError::FileReadFailed)?
+            .unwrap_or_default())
     }
 
     /// Drops a table from storage.
     ///
+    /// Removes every file of the table from the primary and replication folders: its rows,
+    /// its index, its partition tombstones, and any file named `<table>.<something>`, such as
+    /// its schema sidecar or secondary-index files. Table names cannot contain a dot, so those
+    /// files cannot belong to another table. Files that are already missing are skipped.
+    ///
     /// # Parameters
     ///
     /// * `keyspace`: The name of the keyspace that contains the table.
@@ -109,35 +185,21 @@ impl StorageEngine {
     ///
     /// This function can return the following errors:
     ///
-    /// * `StorageEngineError::FileDeletionFailed` if the table or replication files cannot be deleted.
+    /// * `StorageEngineError::FileDeletionFailed` if a file of the table cannot be deleted.
     pub fn drop_table(&self, keyspace: &str, table: &str) -> Result<(), StorageEngineError> {
-        let keyspace_path = self.get_keyspace_path(keyspace);
+        let keyspace_path = self.get_keyspace_path(keyspace, table);
         let replication_path = keyspace_path.join("replication");
 
-        // Paths for primary and replication files and index files
-        let primary_file_path = keyspace_path.join(format!("{}.csv", table));
-        let replication_file_path = replication_path.join(format!("{}.csv", table));
-        let primary_index_path = keyspace_path.join(format!("{}_index.csv", table));
-        let replication_index_path = replication_path.join(format!("{}_index.csv", table));
-
-        // Remove the primary and replication files
-        if let Err(_) = std::fs::remove_file(&primary_file_path) {
-            return Err(StorageEngineError::FileDeletionFailed);
-        }
-
-        if let Err(_) = std::fs::remove_file(&replication_file_path) {
-            return Err(StorageEngineError::FileDeletionFailed);
-        }
-
-        // Remove the primary and replication index files
-        if let Err(_) = std::fs::remove_file(&primary_index_path) {
-            return Err(StorageEngineError::FileDeletionFailed);
-        }
-
-        if let Err(_) = std::fs::remove_file(&replication_index_path) {
-            return Err(StorageEngineError::FileDeletionFailed);
+        for folder in [&keyspace_path, &replication_path] {
+            for path in files_of_table(folder, table)? {
+                match fs::remove_file(&path) {
+                    Ok(()) => {}
+                    // Otro borrado pudo haberlo eliminado antes
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(_) => return Err(StorageEngineError::FileDeletionFailed),
+                }
+            }
         }
-
         Ok(())
     }
 
@@ -157,21 +219,24 @@ impl StorageEngine {
     ///
     /// This function can return the following errors:
     ///
+    /// * `StorageEngineError::UnsupportedOperation` if the table already has a column with that name.
     /// * `StorageEngineError::IoError` if an I/O error occurs when adding the column to the file.
+    ///
+    /// The rows already in the table are NULL for the new column.
     pub fn add_column_to_table(
         &self,
         keyspace: &str,
         table: &str,
         column: &str,
     ) -> Result<(), StorageEngineError> {
-        let keyspace_path = self.get_keyspace_path(keyspace);
+        let keyspace_path = self.get_keyspace_path(keyspace, table);
         let file_path = keyspace_path.join(format!("{}.csv", table));
         let replica_path = keyspace_path
             .join("replication")
             .join(format!("{}.csv", table));
 
-        Self::add_column_to_file(file_path.to_str().unwrap(), column)?;
-        Self::add_column_to_file(replica_path.to_str().unwrap(), column)?;
+        self.add_column_to_file(&file_path, column)?;
+        self.add_column_to_file(&replica_path, column)?;
 
         Ok(())
     }
@@ -200,14 +265,14 @@ impl StorageEngine {
         table: &str,
         column: &str,
     ) -> Result<(), StorageEngineError> {
-        let keyspace_path = self.get_keyspace_path(keyspace);
+        let keyspace_path = self.get_keyspace_path(keyspace, table);
         let file_path = keyspace_path.join(format!("{}.csv", table));
         let replica_path = keyspace_path
             .join("replication")
             .join(format!("{}.csv", table));
 
-        Self::remove_column_from_file(file_path.to_str().unwrap(), column)?;
-        Self::remove_column_from_file(replica_path.to_str().unwrap(), column)?;
+        self.remove_column_from_file(&file_path, column)?;
+        self.remove_column_from_file(&replica_path, column)?;
 
         Ok(())
     }
@@ -237,27 +302,359 @@ impl StorageEngine {
         column: &str,
         new_column: &str,
     ) -> Result<(), StorageEngineError> {
-        let keyspace_path = self.get_keyspace_path(keyspace);
+        let keyspace_path = self.get_keyspace_path(keyspace, table);
         let file_path = keyspace_path.join(format!("{}.csv", table));
         let replica_path = keyspace_path
             .join("replication")
             .join(format!("{}.csv", table));
 
-        Self::rename_column_in_file(file_path.to_str().unwrap(), column, new_column)?;
-        Self::rename_column_in_file(replica_path.to_str().unwrap(), column, new_column)?;
+        self.rename_column_in_file(&file_path, column, new_column)?;
+        self.rename_column_in_file(&replica_path, column, new_column)?;
 
         Ok(())
     }
 
+    /// Finds the rows of a table whose value in `column` cannot be stored with a new type.
+    ///
+    /// Used before changing the type of a column, so the change is rejected instead of leaving
+    /// values that the new type would read wrong. Both the primary and the replication files
+    /// are checked, and deleted rows are skipped.
+    ///
+    /// # Parameters
+    ///
+    /// * `keyspace`: The name of the keyspace that contains the table.
+    /// * `table`: The name of the table.
+    /// * `column`: The name of the column in the table files.
+    /// * `data_type`: The new type of the column.
+    /// * `allows_null`: Whether the column may still have empty values.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<String>)` with the values of each incompatible row, without its timestamp. It is
+    ///   empty if every row fits, or if the files do not have the column.
+    ///
+    /// # Errors
+    ///
+    /// * `StorageEngineError::IoError` if a file of the table cannot be read.
+    pub fn rows_incompatible_with(
+        &self,
+        keyspace: &str,
+        table: &str,
+        column: &str,
+        data_type: DataType,
+        allows_null: bool,
+    ) -> Result<Vec<String>, StorageEngineError> {
+        let keyspace_path = self.get_keyspace_path(keyspace, table);
+        let file_paths = [
+            keyspace_path.join(format!("{}.csv", table)),
+            keyspace_path
+                .join("replication")
+                .join(format!("{}.csv", table)),
+        ];
+
+        let mut incompatible = Vec::new();
+        for file_path in file_paths {
+            if !file_path.exists() {
+                continue;
+            }
+            let file = OpenOptions::new().read(true).open(&file_path)?;
+            let mut lines = BufReader::new(file).lines();
+
+            let header = match lines.next() {
+                Some(header) => header?,
+                None => continue,
+            };
+            let index = match header.split(',').position(|name| name == column) {
+                Some(index) => index,
+                None => continue,
+            };
+
+            for line in lines {
+                let line = line?;
+                let (row, time_of_row) = Self::split_line(&line)?;
+                if parse_row_time(time_of_row).1 {
+                    continue;
+                }
+
+                let value = split_storage_values(row).get(index).copied().unwrap_or("");
+                let fits = if value.is_empty() || value == NULL_VALUE {
+                    allows_null
+                } else {
+                    data_type.is_valid_value(&unescape_storage_text(value))
+                };
+                if !fits {
+                    incompatible.push(row.to_string());
+                }
+            }
+        }
+        Ok(incompatible)
+    }
+
+    /// Changes the type of `column` of `table` to `data_type`, in its files and in its definition
+    /// (`<table>.schema.json`).
+    ///
+    /// Every value of the column the node stores, in both folders, has to be representable in
+    /// the new type. The values are stored as text, so the ones that are already valid, like
+    /// an `INT` that becomes a `DOUBLE`, are kept as written. The ones that convert without
+    /// losing anything, like `20.0` to `INT`, are rewritten as `20`. A value that the new type
+    /// would round or overflow, like `16777217` or `1e300` as a `FLOAT`, does not fit. Empty
+    /// values and deleted rows are kept as they are. If a single value does not fit, nothing
+    /// changes.
+    ///
+    /// The change is local to this node: the schema the node gossips is not updated, so the
+    /// next schema of the table received by gossip overwrites the new type.
+    ///
+    /// # Errors
+    ///
+    /// * `StorageEngineError::FileNotFound` if the table has no definition on disk.
+    /// * `StorageEngineError::ColumnNotFound` if the table does not have the column.
+    /// * `StorageEngineError::IncompatibleColumnType` with the rows whose value does not fit in
+    ///   `data_type`.
+    /// * `StorageEngineError::PrimaryKeyModificationNotAllowed` if a value of a primary key
+    ///   column would have to be rewritten, since the row would move to another partition.
+    /// * `StorageEngineError::IoError` if a file of the table cannot be read or rewritten.
+    /// * `StorageEngineError::LockError` if the lock of the table is poisoned.
+    pub fn alter_column_type(
+        &self,
+        keyspace: &str,
+        table: &str,
+        column: &str,
+        data_type: DataType,
+    ) -> Result<(), StorageEngineError> {
+        let mut definition = self
+            .load_table_schema(keyspace, table)?
+            .ok_or(StorageEngineError::FileNotFound)?;
+        let (is_primary_key, allows_null) = definition
+            .get_columns()
+            .iter()
+            .find(|c| c.name == column)
+            .map(|c| (c.is_primary_key, c.allows_null))
+            .ok_or(StorageEngineError::ColumnNotFound)?;
+
+        let keyspace_path = self.get_keyspace_path(keyspace, table);
+        let folders = [keyspace_path.clone(), keyspace_path.join("replication")];
+        let file_paths: Vec<PathBuf> = folders
+            .iter()
+            .map(|folder| folder.join(format!("{}.csv", table)))
+            .collect();
+        let table_locks = file_paths
+            .iter()
+            .map(|file_path| Self::table_lock(file_path))
+            .collect::<Result<Vec<_>, _>>()?;
+        let _table_guards = table_locks
+            .iter()
+            .map(|lock| lock.lock().map_err(|_| StorageEngineError::LockError))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Primero se convierten todas las filas, los archivos solo cambian si todas entran
+        let mut incompatible = Vec::new();
+        let mut rewrites = Vec::new();
+        for (folder, file_path) in folders.iter().zip(&file_paths) {
+            if !file_path.exists() {
+                continue;
+            }
+            let file = File::open(file_path)?;
+            let mut lines = BufReader::new(file)
+                .lines()
+                .collect::<Result<Vec<String>, _>>()?;
+            let Some(index) = lines
+                .first()
+                .and_then(|header| header.split(',').position(|name| name == column))
+            else {
+                return Err(StorageEngineError::ColumnNotFound);
+            };
+
+            let mut changed = false;
+            for line in lines.iter_mut().skip(1) {
+                let Some((row, time_of_row)) = split_stored_row(line) else {
+                    continue;
+                };
+                if parse_row_time(time_of_row).1 {
+                    continue;
+                }
+                let mut values: Vec<&str> = split_storage_values(row);
+                let value = values.get(index).copied().unwrap_or("");
+                if value.is_empty() || value == NULL_VALUE {
+                    continue;
+                }
+                match convert_value(&unescape_storage_text(value), data_type)
+                    .map(|v| escape_value(&v))
+                {
+                    None => incompatible.push(row.to_string()),
+                    Some(converted) if converted != value => {
+                        if is_primary_key {
+                            return Err(StorageEngineError::PrimaryKeyModificationNotAllowed);
+                        }
+                        values[index] = &converted;
+                        *line = format!("{};{}", values.join(","), time_of_row);
+                        changed = true;
+                    }
+                    Some(_) => {}
+                }
+            }
+            if changed {
+                rewrites.push((folder, lines));
+            }
+        }
+        if !incompatible.is_empty() {
+            return Err(StorageEngineError::IncompatibleColumnType {
+                column: column.to_string(),
+                data_type: data_type.to_string().to_string(),
+                rows: incompatible,
+            });
+        }
+
+        definition
+            .modify_column(column, data_type, allows_null)
+            .map_err(|_| StorageEngineError::ColumnNotFound)?;
+        for (folder, lines) in rewrites {
+            self.rewrite_table_file(
+                folder,
+                table,
+                &definition.get_columns(),
+                &definition.clustering_columns_in_order,
+                &lines,
+                "alter",
+            )?;
+        }
+        self.save_table_schema(keyspace, &definition)
+    }
+
+    /// Replaces the file of `table` in `folder` with `lines`, header included, and rebuilds its
+    /// index, through `<file>.<suffix>.tmp` files. The caller has to hold the lock of the table.
+    pub(super) fn rewrite_table_file(
+        &self,
+        folder: &Path,
+        table: &str,
+        columns: &[Column],
+        clustering_columns: &[String],
+        lines: &[String],
+        suffix: &str,
+    ) -> Result<(), StorageEngineError> {
+        let file_path = folder.join(format!("{}.csv", table));
+        let index_file_path = folder.join(format!("{}_index.csv", table));
+        let temp_file_path = folder.join(format!("{}.csv.{}.tmp", table, suffix));
+        let temp_index_path = folder.join(format!("{}_index.csv.{}.tmp", table, suffix));
+
+        let clustering_indices = Self::get_clustering_indices(columns, clustering_columns)?;
+        let mut index_map = BTreeMap::new();
+        let mut current_byte_offset: u64 = 0;
+        let mut temp_file = BufWriter::new(
+            File::create(&temp_file_path)
+                .map_err(|_| StorageEngineError::TempFileCreationFailed)?,
+        );
+        for (i, line) in lines.iter().enumerate() {
+            writeln!(temp_file, "{}", line).map_err(|_| StorageEngineError::IoError)?;
+            let line_length = line.len() as u64;
+            if i > 0 {
+                let values: Vec<&str> = split_storage_values(
+                    split_stored_row(line).map_or(line.as_str(), |(values, _)| values),
+                );
+                Self::update_index_map(
+                    &values,
+                    &clustering_indices,
+                    &mut index_map,
+                    current_byte_offset,
+                    line_length,
+                );
+            }
+            current_byte_offset += line_length + 1;
+        }
+        temp_file.flush().map_err(|_| StorageEngineError::IoError)?;
+
+        let mut temp_index = BufWriter::new(
+            File::create(&temp_index_path)
+                .map_err(|_| StorageEngineError::TempFileCreationFailed)?,
+        );
+        writeln!(temp_index, "clustering_column,start_byte,end_byte")
+            .map_err(|_| StorageEngineError::IoError)?;
+        for (key, (start_byte, end_byte)) in index_map {
+            writeln!(temp_index, "{},{},{}", key, start_byte, end_byte)
+                .map_err(|_| StorageEngineError::IoError)?;
+        }
+        temp_index
+            .flush()
+            .map_err(|_| StorageEngineError::IoError)?;
+
+        self.replace_table_file(&temp_file_path, &file_path)?;
+        self.replace_table_file(&temp_index_path, &index_file_path)
+    }
+
+    /// Summarizes, one token range at a time, the rows of `table` that the node stores, both
+    /// its own and the ones it keeps as a replica, to compare each range with the other
+    /// replicas that hold it.
+    ///
+    /// Each range is named by the node that owns it in `partitioner`, the one the partition
+    /// key of its rows routes to, so two replicas of a range compare exactly the rows they
+    /// should share. The checksum adds up the CRC32 of each row, timestamp included, so two
+    /// nodes with the same rows have the same digest no matter the folder or order they are
+    /// stored in.
+    ///
+    /// # Errors
+    ///
+    /// * `StorageEngineError::IoError` if a file of the table cannot be read.
+    /// * `StorageEngineError::UnsupportedOperation` if the owner of a row cannot be computed.
+    pub fn data_digests_by_range(
+        &self,
+        keyspace: &str,
+        table: &TableSchema,
+        partitioner: &Partitioner,
+    ) -> Result<BTreeMap<Ipv4Addr, DataDigest>, StorageEngineError> {
+        let table_name = table.get_name();
+        let keyspace_path = self.get_keyspace_path(keyspace, &table_name);
+        let file_paths = [
+            keyspace_path.join(format!("{}.csv", table_name)),
+            keyspace_path
+                .join("replication")
+                .join(format!("{}.csv", table_name)),
+        ];
+        let partition_key_indexes: Vec<usize> = table
+            .get_columns()
+            .iter()
+            .enumerate()
+            .filter(|(_, column)| column.is_partition_key)
+            .map(|(i, _)| i)
+            .collect();
+
+        let mut digests: BTreeMap<Ipv4Addr, DataDigest> = BTreeMap::new();
+        for file_path in file_paths {
+            if !file_path.exists() {
+                continue;
+            }
+            let file = OpenOptions::new().read(true).open(&file_path)?;
+            // La primera linea es la cabecera
+            for line in BufReader::new(file).lines().skip(1) {
+                let line = line?;
+                if line.is_empty() {
+                    continue;
+                }
+                let data = split_stored_row(&line).map_or(line.as_str(), |(data, _)| data);
+                let values: Vec<&str> = split_storage_values(data);
+                let partition_key: Vec<String> = partition_key_indexes
+                    .iter()
+                    .map(|&i| unescape_storage_text(values.get(i).copied().unwrap_or_default()))
+                    .collect();
+                let owner = partitioner
+                    .get_ip_for_partition(&partition_key)
+                    .map_err(|_| StorageEngineError::UnsupportedOperation)?;
+
+                let digest = digests.entry(owner).or_default();
+                digest.rows += 1;
+                digest.checksum = digest
+                    .checksum
+                    .wrapping_add(crc32fast::hash(line.as_bytes()));
+            }
+        }
+        Ok(digests)
+    }
+
     pub(crate) fn add_column_to_file(
-        file_path: &str,
+        &self,
+        file_path: &Path,
         column_name: &str,
     ) -> Result<(), StorageEngineError> {
-        let temp_path = format!("{}.temp", file_path);
-        let mut temp_file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .open(&temp_path)?;
+        let temp_path = file_path.with_extension("csv.temp");
+        let mut temp_file = BufWriter::new(File::create(&temp_path)?);
 
         let file = OpenOptions::new().read(true).open(file_path)?;
         let reader = BufReader::new(file);
@@ -266,26 +663,38 @@ impl StorageEngine {
         for line in reader.lines() {
             let mut line = line?;
             if first_line {
+                // Una columna repetida desalinearía todas las filas
+                if line.split(',').any(|column| column == column_name) {
+                    drop(temp_file);
+                    fs::remove_file(&temp_path).map_err(|_| StorageEngineError::IoError)?;
+                    return Err(StorageEngineError::UnsupportedOperation);
+                }
                 line.push_str(&format!(",{}", column_name));
                 first_line = false;
             } else {
-                line.push_str(","); // Append an empty cell for the new column in each row
+                // Las filas que ya estaban quedan en NULL para la columna nueva, antes del timestamp
+                line = match split_stored_row(&line) {
+                    Some((values, time_of_row)) => {
+                        format!("{},{};{}", values, NULL_VALUE, time_of_row)
+                    }
+                    None => format!("{},{}", line, NULL_VALUE),
+                };
             }
             writeln!(temp_file, "{}", line)?;
         }
 
-        fs::rename(temp_path, file_path).map_err(|_| StorageEngineError::IoError)
+        temp_file.flush()?;
+        drop(temp_file);
+        self.replace_table_file(&temp_path, file_path)
     }
 
     pub(crate) fn remove_column_from_file(
-        file_path: &str,
+        &self,
+        file_path: &Path,
         column_name: &str,
     ) -> Result<(), StorageEngineError> {
-        let temp_path = format!("{}.temp", file_path);
-        let mut temp_file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .open(&temp_path)?;
+        let temp_path = file_path.with_extension("csv.temp");
+        let mut temp_file = BufWriter::new(File::create(&temp_path)?);
 
         let file = OpenOptions::new().read(true).open(file_path)?;
         let reader = BufReader::new(file);
@@ -293,7 +702,12 @@ impl StorageEngine {
 
         for line in reader.lines() {
             let line = line?;
-            let cells: Vec<&str> = line.split(',').collect();
+            // El timestamp de la fila queda aparte, aunque se borre la ultima columna
+            let (data, time_of_row) = match split_stored_row(&line) {
+                Some((data, time_of_row)) => (data, Some(time_of_row)),
+                None => (line.as_str(), None),
+            };
+            let cells: Vec<&str> = split_storage_values(data);
 
             if col_index.is_none() {
                 col_index = cells.iter().position(|&col| col == column_name);
@@ -309,22 +723,27 @@ impl StorageEngine {
                 .map(|(_, &cell)| cell)
                 .collect();
 
-            writeln!(temp_file, "{}", filtered_line.join(","))?;
+            match time_of_row {
+                Some(time_of_row) => {
+                    writeln!(temp_file, "{};{}", filtered_line.join(","), time_of_row)?
+                }
+                None => writeln!(temp_file, "{}", filtered_line.join(","))?,
+            }
         }
 
-        fs::rename(temp_path, file_path).map_err(|_| StorageEngineError::IoError)
+        temp_file.flush()?;
+        drop(temp_file);
+        self.replace_table_file(&temp_path, file_path)
     }
 
     pub(crate) fn rename_column_in_file(
-        file_path: &str,
+        &self,
+        file_path: &Path,
         old_name: &str,
         new_name: &str,
     ) -> Result<(), StorageEngineError> {
-        let temp_path = format!("{}.temp", file_path);
-        let mut temp_file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .open(&temp_path)?;
+        let temp_path = file_path.with_extension("csv.temp");
+        let mut temp_file = BufWriter::new(File::create(&temp_path)?);
 
         let file = OpenOptions::new().read(true).open(file_path)?;
         let reader = BufReader::new(file);
@@ -337,15 +756,79 @@ impl StorageEngine {
             writeln!(temp_file, "{}", line)?;
         }
 
-        fs::rename(temp_path, file_path).map_err(|_| StorageEngineError::IoError)
+        temp_file.flush()?;
+        drop(temp_file);
+        self.replace_table_file(&temp_path, file_path)
+    }
+}
+
+// Valor de `value` en `data_type`: el mismo si ya es valido, reescrito si se convierte sin
+// perder nada, o `None` si no se puede representar
+fn convert_value(value: &str, data_type: DataType) -> Option<String> {
+    match data_type {
+        // Un FLOAT parsea casi cualquier numero, pero 1e300 queda infinito y 16777217 se
+        // redondea: solo entra si sigue siendo el mismo numero
+        DataType::Float => {
+            let number = value.parse::<f64>().ok()?;
+            let float = value.parse::<f32>().ok()?;
+            let same = number.is_nan() || float.to_string().parse::<f64>() == Ok(number);
+            same.then(|| value.to_string())
+        }
+        _ if data_type.is_valid_value(value) => Some(value.to_string()),
+        // Un numero con decimales en cero entra en un INT si esta en su rango
+        DataType::Int => {
+            let number = value.parse::<f64>().ok()?;
+            let in_range = (f64::from(i32::MIN)..=f64::from(i32::MAX)).contains(&number);
+            (in_range && number.fract() == 0.0).then(|| (number as i32).to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Files of `table` in `folder`: the ones every table has, whether they exist or not, and the
+/// ones named `<table>.<something>` that are there.
+pub(super) fn files_of_table(
+    folder: &Path,
+    table: &str,
+) -> Result<Vec<PathBuf>, StorageEngineError> {
+    let mut files = vec![
+        folder.join(format!("{}.csv", table)),
+        folder.join(format!("{}_index.csv", table)),
+        partition_tombstones_path(folder, table),
+    ];
+
+    let entries = match fs::read_dir(folder) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(files),
+        Err(_) => return Err(StorageEngineError::FileReadFailed),
+    };
+    let prefix = format!("{}.", table);
+    for entry in entries {
+        let path = entry
+            .map_err(|_| StorageEngineError::FileReadFailed)?
+            .path();
+        let is_of_table = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(&prefix));
+        if is_of_table && path.is_file() && !files.contains(&path) {
+            files.push(path);
+        }
     }
+    Ok(files)
 }
 
 #[cfg(test)]
 mod tests {
     use super::StorageEngine;
-    use std::fs::File;
-    use std::io::{BufRead, BufReader};
+    use crate::storage_engine::errors::StorageEngineError;
+    use crate::storage_engine::partition_tombstone::partition_tombstones_path;
+    use gossip::structures::application_state::TableSchema;
+    use query_creator::clauses::insert_cql::NULL_VALUE;
+    use query_creator::clauses::table::create_table_cql::CreateTable;
+    use query_creator::clauses::types::datatype::DataType;
+    use std::fs::{File, OpenOptions};
+    use std::io::{BufRead, BufReader, Write};
     use std::path::PathBuf;
     use uuid::Uuid;
 
@@ -381,6 +864,42 @@ mod tests {
         assert!(index_file_path.exists(), "Index file not created");
     }
 
+    #[test]
+    fn test_create_existing_table_keeps_its_rows() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let keyspace = "test_keyspace";
+        let table_name = "test_table";
+
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        storage
+            .create_table(keyspace, table_name, vec!["id", "name", "age"])
+            .unwrap();
+
+        let file_path = root
+            .join("keyspaces_of_127_0_0_1")
+            .join(keyspace)
+            .join(format!("{}.csv", table_name));
+        let mut file = OpenOptions::new().append(true).open(&file_path).unwrap();
+        writeln!(file, "1,Juan,30;1").unwrap();
+
+        // Crear de nuevo la tabla no borra sus filas
+        storage
+            .create_table(keyspace, table_name, vec!["id", "name", "age"])
+            .unwrap();
+        let content = std::fs::read_to_string(&file_path).unwrap();
+        assert_eq!(content, "id,name,age\n1,Juan,30;1\n");
+
+        // Pero con otras columnas es un error
+        assert!(matches!(
+            storage.create_table(keyspace, table_name, vec!["id", "email"]),
+            Err(StorageEngineError::TableHeaderMismatch)
+        ));
+        let content = std::fs::read_to_string(&file_path).unwrap();
+        assert_eq!(content, "id,name,age\n1,Juan,30;1\n");
+
+        std::fs::remove_dir_all(root).ok();
+    }
+
     #[test]
     fn test_drop_table() {
         let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
@@ -418,6 +937,55 @@ mod tests {
         assert!(!index_file_path.exists(), "Index file not deleted");
     }
 
+    #[test]
+    fn test_drop_table_removes_every_file_of_the_table() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        let table = CreateTable::deserialize(
+            "CREATE TABLE sky.flights (id INT, origin TEXT, PRIMARY KEY (id))",
+        )
+        .unwrap();
+        storage
+            .create_table("sky", "flights", vec!["id", "origin"])
+            .unwrap();
+        storage.save_table_schema("sky", &table).unwrap();
+        // Una tabla cuyo nombre empieza igual no se toca
+        storage
+            .create_table("sky", "flights_archive", vec!["id", "origin"])
+            .unwrap();
+
+        let keyspace_path = root.join("keyspaces_of_127_0_0_1").join("sky");
+        let replication_path = keyspace_path.join("replication");
+        for folder in [&keyspace_path, &replication_path] {
+            File::create(folder.join("flights.by_origin.csv")).unwrap();
+            File::create(partition_tombstones_path(folder, "flights")).unwrap();
+        }
+        // Falta un archivo: el borrado sigue con los demas
+        std::fs::remove_file(replication_path.join("flights_index.csv")).unwrap();
+
+        storage.drop_table("sky", "flights").unwrap();
+
+        let file_names = |folder: &PathBuf| {
+            let mut names: Vec<String> = std::fs::read_dir(folder)
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| path.is_file())
+                .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+        let remaining = vec!["flights_archive.csv", "flights_archive_index.csv"];
+        assert_eq!(file_names(&keyspace_path), remaining);
+        assert_eq!(file_names(&replication_path), remaining);
+        assert!(storage
+            .load_table_schema("sky", "flights")
+            .unwrap()
+            .is_none());
+
+        std::fs::remove_dir_all(root).ok();
+    }
+
     #[test]
     fn test_add_column_to_table() {
         let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
@@ -449,6 +1017,71 @@ mod tests {
         assert!(header.contains("email"), "Column not added");
     }
 
+    #[test]
+    fn test_added_column_is_null_in_the_existing_rows() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let keyspace = "test_keyspace";
+        let table_name = "test_table";
+
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        storage
+            .create_table(keyspace, table_name, vec!["id", "name"])
+            .unwrap();
+
+        let keyspace_path = root.join("keyspaces_of_127_0_0_1").join(keyspace);
+        let file_path = keyspace_path.join(format!("{}.csv", table_name));
+        let mut file = OpenOptions::new().append(true).open(&file_path).unwrap();
+        writeln!(file, "1,Juan;10").unwrap();
+        writeln!(file, "2,Ana;20;tombstone").unwrap();
+
+        storage
+            .add_column_to_table(keyspace, table_name, "age")
+            .unwrap();
+
+        let content = std::fs::read_to_string(&file_path).unwrap();
+        assert_eq!(
+            content,
+            format!(
+                "id,name,age\n1,Juan,{null};10\n2,Ana,{null};20;tombstone\n",
+                null = NULL_VALUE
+            )
+        );
+
+        std::fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_add_duplicated_column_to_table() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let keyspace = "test_keyspace";
+        let table_name = "test_table";
+
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        storage
+            .create_table(keyspace, table_name, vec!["id", "name", "age"])
+            .unwrap();
+
+        let keyspace_path = root.join("keyspaces_of_127_0_0_1").join(keyspace);
+        let file_path = keyspace_path.join(format!("{}.csv", table_name));
+        let mut file = OpenOptions::new().append(true).open(&file_path).unwrap();
+        writeln!(file, "1,Juan,30;1").unwrap();
+
+        let result = storage.add_column_to_table(keyspace, table_name, "name");
+        assert!(matches!(
+            result,
+            Err(StorageEngineError::UnsupportedOperation)
+        ));
+
+        // El archivo queda igual y no se deja el temporal
+        let content = std::fs::read_to_string(&file_path).unwrap();
+        assert_eq!(content, "id,name,age\n1,Juan,30;1\n");
+        assert!(!keyspace_path
+            .join(format!("{}.csv.temp", table_name))
+            .exists());
+
+        std::fs::remove_dir_all(root).ok();
+    }
+
     #[test]
     fn test_remove_column_from_table() {
         let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
@@ -510,4 +1143,183 @@ mod tests {
         // Verificar que la columna "age" ha sido renombrada a "years"
         assert!(header.contains("years"), "Column not renamed");
     }
+
+    #[test]
+    fn test_rows_incompatible_with_a_new_type() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let keyspace = "test_keyspace";
+        let table_name = "test_table";
+
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        storage
+            .create_table(keyspace, table_name, vec!["id", "age"])
+            .unwrap();
+
+        let keyspace_path = root.join("keyspaces_of_127_0_0_1").join(keyspace);
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(keyspace_path.join(format!("{}.csv", table_name)))
+            .unwrap();
+        writeln!(file, "1,30;1").unwrap();
+        writeln!(file, "2,thirty;1").unwrap();
+        writeln!(file, "3,;1").unwrap();
+        // Una fila borrada no cuenta
+        writeln!(file, "4,forty;2;tombstone").unwrap();
+        let mut replica = OpenOptions::new()
+            .append(true)
+            .open(
+                keyspace_path
+                    .join("replication")
+                    .join(format!("{}.csv", table_name)),
+            )
+            .unwrap();
+        writeln!(replica, "5,2.5;1").unwrap();
+
+        let rows = storage
+            .rows_incompatible_with(keyspace, table_name, "age", DataType::Int, true)
+            .unwrap();
+        assert_eq!(rows, vec!["2,thirty", "5,2.5"]);
+
+        // Sin valores nulos, la fila vacia tampoco entra
+        let rows = storage
+            .rows_incompatible_with(keyspace, table_name, "age", DataType::String, false)
+            .unwrap();
+        assert_eq!(rows, vec!["3,"]);
+
+        // Una columna que no esta en los archivos no tiene filas que revisar
+        let rows = storage
+            .rows_incompatible_with(keyspace, table_name, "height", DataType::Int, false)
+            .unwrap();
+        assert!(rows.is_empty());
+    }
+    // Tabla `sky.<name>` con su definicion en disco y una fila por cada valor de `value`
+    fn table_with_values(
+        storage: &StorageEngine,
+        name: &str,
+        value_type: &str,
+        values: &[&str],
+    ) -> CreateTable {
+        let table = CreateTable::new_from_tokens(vec![
+            "CREATE".to_string(),
+            "TABLE".to_string(),
+            format!("sky.{}", name),
+            format!(
+                "id INT, seat INT, value {}, PRIMARY KEY (id, seat)",
+                value_type
+            ),
+        ])
+        .unwrap();
+        storage
+            .create_table("sky", name, vec!["id", "seat", "value"])
+            .unwrap();
+        storage.save_table_schema("sky", &table).unwrap();
+        for (seat, value) in values.iter().enumerate() {
+            let seat = seat.to_string();
+            storage
+                .insert(
+                    "sky",
+                    name,
+                    vec!["1", &seat, value],
+                    table.get_columns(),
+                    table.clustering_columns_in_order.clone(),
+                    false,
+                    false,
+                    10,
+                )
+                .unwrap();
+        }
+        table
+    }
+
+    fn type_of_value(storage: &StorageEngine, table: &str) -> DataType {
+        let table = storage.load_table_schema("sky", table).unwrap().unwrap();
+        table.get_columns()[2].data_type
+    }
+
+    #[test]
+    fn test_alter_column_type_widening_keeps_the_values() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        table_with_values(&storage, "flights", "INT", &["180", "-3"]);
+        let file_path = storage
+            .get_keyspace_path("sky", "flights")
+            .join("flights.csv");
+        let rows_before = std::fs::read_to_string(&file_path).unwrap();
+
+        storage
+            .alter_column_type("sky", "flights", "value", DataType::Double)
+            .unwrap();
+
+        assert_eq!(type_of_value(&storage, "flights"), DataType::Double);
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), rows_before);
+
+        std::fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_alter_column_type_narrowing_with_values_out_of_range_is_rejected() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        table_with_values(&storage, "flights", "DOUBLE", &["20.0", "3000000000"]);
+        let file_path = storage
+            .get_keyspace_path("sky", "flights")
+            .join("flights.csv");
+        let rows_before = std::fs::read_to_string(&file_path).unwrap();
+
+        match storage.alter_column_type("sky", "flights", "value", DataType::Int) {
+            Err(StorageEngineError::IncompatibleColumnType {
+                column,
+                data_type,
+                rows,
+            }) => {
+                assert_eq!(column, "value");
+                assert_eq!(data_type, "INT");
+                assert_eq!(rows, vec!["1,1,3000000000"]);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(type_of_value(&storage, "flights"), DataType::Double);
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), rows_before);
+
+        // Si todos los valores entran, los que no son un INT valido se reescriben
+        let fares = table_with_values(&storage, "fares", "DOUBLE", &["20.0", "7"]);
+        storage
+            .alter_column_type("sky", "fares", "value", DataType::Int)
+            .unwrap();
+        assert_eq!(type_of_value(&storage, "fares"), DataType::Int);
+        let rows = storage
+            .scan_range("sky", &TableSchema::new(fares), false, 0, u64::MAX)
+            .unwrap();
+        assert_eq!(rows, vec!["1,0,20;10", "1,1,7;10"]);
+
+        std::fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_alter_column_type_to_float_rejects_values_that_would_change() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        let rejected_rows =
+            |table: &str| match storage.alter_column_type("sky", table, "value", DataType::Float) {
+                Err(StorageEngineError::IncompatibleColumnType { rows, .. }) => rows,
+                other => panic!("unexpected result {:?}", other),
+            };
+
+        // 1e300 no entra en un FLOAT y 16777217 se redondearia a 16777216
+        table_with_values(&storage, "flights", "DOUBLE", &["1e300", "0.5"]);
+        assert_eq!(rejected_rows("flights"), vec!["1,0,1e300"]);
+        assert_eq!(type_of_value(&storage, "flights"), DataType::Double);
+        table_with_values(&storage, "fares", "INT", &["16777217", "3"]);
+        assert_eq!(rejected_rows("fares"), vec!["1,0,16777217"]);
+        assert_eq!(type_of_value(&storage, "fares"), DataType::Int);
+
+        // Los que un FLOAT representa igual se conservan como estaban escritos
+        table_with_values(&storage, "seats", "DOUBLE", &["16777216", "0.1"]);
+        storage
+            .alter_column_type("sky", "seats", "value", DataType::Float)
+            .unwrap();
+        assert_eq!(type_of_value(&storage, "seats"), DataType::Float);
+
+        std::fs::remove_dir_all(root).ok();
+    }
 }
@@ -0,0 +1,282 @@
+use super::{errors::StorageEngineError, StorageEngine};
+use query_creator::clauses::table::create_table_cql::CreateTable;
+use query_creator::clauses::types::column::Column;
+use query_creator::clauses::types::datatype::DataType;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Extension of the file, next to `<table>.csv`, with the definition of the table.
+const TABLE_SCHEMA_EXTENSION: &str = "schema.json";
+
+/// Clustering order of the tables whose definition is on disk, shared by the engines of a node
+/// so an insert does not read and parse the definition of its table every time.
+///
+/// Each order is kept with the modification time of the file it was read from, and read again
+/// once the file changes, so an ALTER or a DROP made through any engine is always seen.
+#[derive(Debug, Clone, Default)]
+pub struct ClusteringOrderCache {
+    orders: Arc<Mutex<HashMap<PathBuf, (SystemTime, Vec<String>)>>>,
+}
+
+impl StorageEngine {
+    /// Writes the definition of `table` to `<table>.schema.json`, next to its data.
+    ///
+    /// The header of the CSV only has the names of the columns. This file keeps the rest of
+    /// the `CreateTable`: the type of each column, which ones are partition and clustering
+    /// keys, the clustering order and whether they allow nulls, so a node that restarts
+    /// recovers the table exactly as it was. It has to be written again after every ALTER.
+    ///
+    /// The file is replaced through a `.tmp` file, so a crash in the middle of a write never
+    /// leaves half a definition behind.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::DirectoryCreationFailed` if the folder of the keyspace cannot be created.
+    /// - `StorageEngineError::FileWriteFailed` if the file cannot be written.
+    pub fn save_table_schema(
+        &self,
+        keyspace: &str,
+        table: &CreateTable,
+    ) -> Result<(), StorageEngineError> {
+        let path = self.table_schema_path(keyspace, &table.get_name());
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder).map_err(|_| StorageEngineError::DirectoryCreationFailed)?;
+        }
+
+        let contents = serde_json::to_string_pretty(&table_to_json(table))
+            .map_err(|_| StorageEngineError::FileWriteFailed)?;
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, contents).map_err(|_| StorageEngineError::FileWriteFailed)?;
+        fs::rename(&temp_path, &path).map_err(|_| StorageEngineError::FileWriteFailed)
+    }
+
+    /// Reads the definition saved by [`StorageEngine::save_table_schema`].
+    ///
+    /// # Returns
+    /// - `Ok(None)` if the table has no definition on disk.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::FileReadFailed` if the file cannot be read or is not a valid
+    ///   definition.
+    pub fn load_table_schema(
+        &self,
+        keyspace: &str,
+        table: &str,
+    ) -> Result<Option<CreateTable>, StorageEngineError> {
+        let path = self.table_schema_path(keyspace, table);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let contents = fs::read_to_string(path).map_err(|_| StorageEngineError::FileReadFailed)?;
+        let value: Value =
+            serde_json::from_str(&contents).map_err(|_| StorageEngineError::FileReadFailed)?;
+        table_from_json(&value)
+            .map(Some)
+            .ok_or(StorageEngineError::FileReadFailed)
+    }
+
+    /// Clustering columns of `table`, in the order its saved definition declares, through the
+    /// cache of the engine.
+    ///
+    /// # Returns
+    /// - `Ok(None)` if the table has no definition on disk.
+    ///
+    /// # Errors
+    /// - `StorageEngineError::FileReadFailed` if the definition cannot be read.
+    /// - `StorageEngineError::LockError` if the cache was poisoned.
+    pub fn declared_clustering_order(
+        &self,
+        keyspace: &str,
+        table: &str,
+    ) -> Result<Option<Vec<String>>, StorageEngineError> {
+        let path = self.table_schema_path(keyspace, table);
+        let mut orders = self
+            .clustering_orders
+            .orders
+            .lock()
+            .map_err(|_| StorageEngineError::LockError)?;
+
+        let Ok(modified) = fs::metadata(&path).and_then(|metadata| metadata.modified()) else {
+            orders.remove(&path);
+            return Ok(None);
+        };
+        if let Some((cached_at, order)) = orders.get(&path) {
+            if *cached_at == modified {
+                return Ok(Some(order.clone()));
+            }
+        }
+
+        let Some(definition) = self.load_table_schema(keyspace, table)? else {
+            orders.remove(&path);
+            return Ok(None);
+        };
+        orders.insert(
+            path,
+            (modified, definition.clustering_columns_in_order.clone()),
+        );
+        Ok(Some(definition.clustering_columns_in_order))
+    }
+
+    fn table_schema_path(&self, keyspace: &str, table: &str) -> PathBuf {
+        self.get_keyspace_path(keyspace, table)
+            .join(format!("{}.{}", table, TABLE_SCHEMA_EXTENSION))
+    }
+}
+
+fn table_to_json(table: &CreateTable) -> Value {
+    let columns: Vec<Value> = table
+        .get_columns()
+        .iter()
+        .map(|column| {
+            json!({
+                "name": column.name,
+                "type": column.data_type.to_string(),
+                "primary_key": column.is_primary_key,
+                "partition_key": column.is_partition_key,
+                "clustering_column": column.is_clustering_column,
+                "clustering_order": column.clustering_order,
+                "allows_null": column.allows_null,
+            })
+        })
+        .collect();
+
+    json!({
+        "keyspace": table.keyspace_used_name,
+        "name": table.name,
+        "columns": columns,
+        "clustering_columns_in_order": table.clustering_columns_in_order,
+    })
+}
+
+/// Builds the table back from its definition, or `None` if a field is missing or invalid.
+fn table_from_json(value: &Value) -> Option<CreateTable> {
+    let text = |value: &Value, field: &str| value.get(field)?.as_str().map(str::to_string);
+    let flag = |value: &Value, field: &str| value.get(field)?.as_bool();
+
+    let mut columns = Vec::new();
+    for column in value.get("columns")?.as_array()? {
+        columns.push(Column {
+            name: text(column, "name")?,
+            data_type: DataType::from_str(&text(column, "type")?).ok()?,
+            is_primary_key: flag(column, "primary_key")?,
+            allows_null: flag(column, "allows_null")?,
+            is_clustering_column: flag(column, "clustering_column")?,
+            is_partition_key: flag(column, "partition_key")?,
+            clustering_order: text(column, "clustering_order")?,
+        });
+    }
+
+    let clustering_columns_in_order = value
+        .get("clustering_columns_in_order")?
+        .as_array()?
+        .iter()
+        .map(|name| name.as_str().map(str::to_string))
+        .collect::<Option<Vec<String>>>()?;
+
+    Some(CreateTable {
+        name: text(value, "name")?,
+        keyspace_used_name: text(value, "keyspace")?,
+        if_not_exists_clause: false,
+        columns,
+        clustering_columns_in_order,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_table_schema_roundtrip() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string());
+        let table = CreateTable::deserialize(
+            "CREATE TABLE sky.flights (origin TEXT, departure TIMESTAMP, seats INT, PRIMARY KEY ((origin), departure)) WITH CLUSTERING ORDER BY (departure DESC)",
+        )
+        .unwrap();
+
+        assert!(storage
+            .load_table_schema("sky", "flights")
+            .unwrap()
+            .is_none());
+        storage.save_table_schema("sky", &table).unwrap();
+
+        let loaded = storage
+            .load_table_schema("sky", "flights")
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.keyspace_used_name, "sky");
+        assert_eq!(loaded.clustering_columns_in_order, vec!["departure"]);
+        for (loaded, expected) in loaded.get_columns().iter().zip(table.get_columns()) {
+            assert_eq!(loaded.name, expected.name);
+            assert_eq!(loaded.data_type, expected.data_type);
+            assert_eq!(loaded.is_partition_key, expected.is_partition_key);
+            assert_eq!(loaded.is_clustering_column, expected.is_clustering_column);
+            assert_eq!(loaded.clustering_order, expected.clustering_order);
+        }
+
+        storage.drop_table("sky", "flights").unwrap();
+        assert!(storage
+            .load_table_schema("sky", "flights")
+            .unwrap()
+            .is_none());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_declared_clustering_order_follows_the_saved_definition() {
+        let root = PathBuf::from(format!("/tmp/storage_test_{}", Uuid::new_v4()));
+        let cache = ClusteringOrderCache::default();
+        let storage = StorageEngine::new(root.clone(), "127.0.0.1".to_string())
+            .with_clustering_order_cache(cache.clone());
+        // Otro motor del mismo nodo, que comparte el cache
+        let other = StorageEngine::new(root.clone(), "127.0.0.1".to_string())
+            .with_clustering_order_cache(cache.clone());
+        let table = |order: &str| {
+            CreateTable::deserialize(&format!(
+                "CREATE TABLE sky.flights (origin TEXT, departure TIMESTAMP, seats INT, PRIMARY KEY ((origin), {}))",
+                order
+            ))
+            .unwrap()
+        };
+
+        assert_eq!(
+            storage.declared_clustering_order("sky", "flights").unwrap(),
+            None
+        );
+        storage
+            .save_table_schema("sky", &table("departure, seats"))
+            .unwrap();
+        assert_eq!(
+            storage.declared_clustering_order("sky", "flights").unwrap(),
+            Some(vec!["departure".to_string(), "seats".to_string()])
+        );
+        assert_eq!(cache.orders.lock().unwrap().len(), 1);
+
+        // Un cambio en el archivo, hecho por cualquier motor, se ve en la proxima consulta
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        other
+            .save_table_schema("sky", &table("seats, departure"))
+            .unwrap();
+        assert_eq!(
+            storage.declared_clustering_order("sky", "flights").unwrap(),
+            Some(vec!["seats".to_string(), "departure".to_string()])
+        );
+
+        other.drop_table("sky", "flights").unwrap();
+        assert_eq!(
+            storage.declared_clustering_order("sky", "flights").unwrap(),
+            None
+        );
+        assert!(cache.orders.lock().unwrap().is_empty());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,86 @@
+use std::{collections::HashMap, path::Path};
+
+use chrono::Utc;
+
+use super::{
+    errors::StorageEngineError,
+    parse_row_time,
+    partition_tombstone::{partition_key_of, read_partition_tombstones},
+    split_stored_row, StorageEngine,
+};
+use native_protocol::messages::result::rows::split_storage_values;
+
+/// Seconds a tombstone is kept before a rewrite of its table drops it, as the `gc_grace_seconds`
+/// of Cassandra: the time a replica that missed the delete has to get it through a repair.
+pub const DEFAULT_TOMBSTONE_GRACE_SECONDS: i64 = 10 * 24 * 60 * 60;
+
+/// The deletes of a table that are older than the grace period of the engine, and so are dropped
+/// the next time the table is rewritten.
+///
+/// A row tombstone older than the grace period is dropped as is. A partition tombstone older than
+/// it is dropped together with every row it deletes, so none of them comes back.
+pub(crate) struct TombstonePurge {
+    // Las filas y los tombstones escritos antes de este momento ya se pueden borrar
+    cutoff: i64,
+    expired_partitions: HashMap<String, i64>,
+    partition_key_indexes: Vec<usize>,
+}
+
+impl TombstonePurge {
+    /// Reads the partition tombstones of `table`, in `folder`, that are past the grace period of
+    /// `engine`. The caller must hold the lock of the table.
+    pub(crate) fn new(
+        engine: &StorageEngine,
+        folder: &Path,
+        table: &str,
+        partition_key_indexes: Vec<usize>,
+    ) -> Result<Self, StorageEngineError> {
+        let cutoff = Utc::now().timestamp() - engine.tombstone_grace_seconds;
+        let expired_partitions = read_partition_tombstones(folder, table)?
+            .into_iter()
+            .filter(|&(_, deleted_at)| deleted_at < cutoff)
+            .collect();
+        Ok(Self {
+            cutoff,
+            expired_partitions,
+            partition_key_indexes,
+        })
+    }
+
+    /// Whether the stored `line` is a delete past the grace period, or a row deleted by one, and
+    /// must be left out of the rewritten table.
+    pub(crate) fn is_purged(&self, line: &str) -> bool {
+        let Some((values, time_of_row)) = split_stored_row(line) else {
+            return false;
+        };
+        let (written_at, is_tombstone) = parse_row_time(time_of_row);
+        if is_tombstone && written_at < self.cutoff {
+            return true;
+        }
+        if self.expired_partitions.is_empty() {
+            return false;
+        }
+        let partition_key =
+            partition_key_of(&split_storage_values(values), &self.partition_key_indexes);
+        matches!(
+            self.expired_partitions.get(&partition_key),
+            Some(&deleted_at) if written_at <= deleted_at
+        )
+    }
+
+    /// Drops the expired partition tombstones of `table`. Called once the rewritten table
+    /// replaced the old one, so the rows they deleted are already gone.
+    pub(crate) fn finish(
+        self,
+        engine: &StorageEngine,
+        folder: &Path,
+        table: &str,
+    ) -> Result<(), StorageEngineError> {
+        if self.expired_partitions.is_empty() {
+            return Ok(());
+        }
+        let mut tombstones = read_partition_tombstones(folder, table)?;
+        tombstones.retain(|partition_key, _| !self.expired_partitions.contains_key(partition_key));
+        engine.write_partition_tombstones(folder, table, &tombstones)
+    }
+}
@@ -8,7 +8,12 @@ use std::{
 use gossip::structures::application_state::TableSchema;
 use query_creator::clauses::update_cql::Update;
 
-use super::{errors::StorageEngineError, StorageEngine};
+use super::{
+    errors::StorageEngineError, escape_value, parse_row_time,
+    partition_tombstone::partition_key_indexes, split_stored_row, tombstone_purge::TombstonePurge,
+    StorageEngine,
+};
+use native_protocol::messages::result::rows::{split_storage_values, unescape_storage_text};
 
 impl StorageEngine {
     /// Performs an update on rows in a table by applying an `UPDATE` query to the records
@@ -34,9 +39,10 @@ impl StorageEngine {
     ///
     /// # Returns
     ///
-    /// Returns a `Result<(), StorageEngineError>`:
+    /// Returns a `Result<bool, StorageEngineError>`:
     ///
-    /// - `Ok(())` if the update is successful.
+    /// - `Ok(true)` if at least one row matched the `WHERE` clause and satisfied the `IF` clause, if any.
+    /// - `Ok(false)` if no row was modified. For a conditional update this means it was not applied.
     /// - `Err(StorageEngineError)` if an error occurs during the update process, such as file creation failures,
     ///   I/O errors, or violation of constraints (like modifying primary keys).
     ///
@@ -82,9 +88,9 @@ impl StorageEngine {
         is_replication: bool,
         keyspace: &str,
         timestamp: i64,
-    ) -> Result<(), StorageEngineError> {
+    ) -> Result<bool, StorageEngineError> {
         let table_name = table.get_name();
-        let base_folder_path = self.get_keyspace_path(keyspace);
+        let base_folder_path = self.get_keyspace_path(keyspace, &table_name);
 
         // Construcción de la ruta de la carpeta según si es replicación o no
         let folder_path = if is_replication {
@@ -109,12 +115,12 @@ impl StorageEngine {
                 .map_err(|_| StorageEngineError::TempFileCreationFailed)?
                 .as_nanos()
         ));
-        let mut temp_index = BufWriter::new(
-            File::create(&index_file_path).map_err(|_| StorageEngineError::IoError)?,
-        );
 
-        writeln!(temp_index, "clustering_column,start_byte,end_byte")
-            .map_err(|_| StorageEngineError::IoError)?;
+        // El IF se evalua y se aplica sin que otra escritura de la tabla se meta en el medio
+        let table_lock = Self::table_lock(&file_path)?;
+        let _table_guard = table_lock
+            .lock()
+            .map_err(|_| StorageEngineError::LockError)?;
 
         let columns = table.get_columns();
         let clustering_key_index =
@@ -158,12 +164,23 @@ impl StorageEngine {
             .map_err(|_| StorageEngineError::FileWriteFailed)?;
         current_byte_offset += header_line.len() as u64; // Contar el tamaño del encabezado
 
-        let mut _found_match = false;
+        let purge = TombstonePurge::new(
+            self,
+            &folder_path,
+            &table_name,
+            partition_key_indexes(&table),
+        )?;
+        let mut found_match = false;
+        let mut updated_bytes = 0;
 
         // Iterar sobre las líneas del archivo original y aplicar la actualización
         for line in reader.lines() {
             let line = line?;
-            _found_match |= self.update_or_write_line(
+            if purge.is_purged(&line) {
+                continue;
+            }
+            let offset_before = current_byte_offset;
+            if self.update_or_write_line(
                 &table,
                 &update_query,
                 &line,
@@ -172,14 +189,30 @@ impl StorageEngine {
                 clustering_key_index,
                 &mut current_byte_offset,
                 timestamp,
-            )?;
+            )? {
+                found_match = true;
+                updated_bytes += current_byte_offset - offset_before;
+            }
+        }
+
+        // Las filas actualizadas cuentan para la cuota como las insertadas
+        if updated_bytes > 0 {
+            if let Err(e) = self.check_keyspace_quota(keyspace, updated_bytes) {
+                fs::remove_file(&temp_file_path).ok();
+                return Err(e);
+            }
         }
 
         // Reemplazar el archivo original con el actualizado
-        fs::rename(&temp_file_path, &file_path)
-            .map_err(|_| StorageEngineError::DirectoryCreationFailed)?;
+        self.replace_table_file(&temp_file_path, &file_path)?;
+        purge.finish(self, &folder_path, &table_name)?;
 
         // Actualizar el archivo de índices
+        let mut temp_index = BufWriter::new(
+            File::create(&index_file_path).map_err(|_| StorageEngineError::IoError)?,
+        );
+        writeln!(temp_index, "clustering_column,start_byte,end_byte")
+            .map_err(|_| StorageEngineError::IoError)?;
         for (key, (start_byte, end_byte)) in index_map {
             writeln!(temp_index, "{},{},{}", key, start_byte, end_byte)
                 .map_err(|_| StorageEngineError::IoError)?;
@@ -191,7 +224,7 @@ impl StorageEngine {
             self.add_new_row_in_update(&table, &update_query, keyspace, is_replication, timestamp)?;
         }*/
 
-        Ok(())
+        Ok(found_match)
     }
 
     /// Crea un mapa de valores de columna para una fila dada.
@@ -226,12 +259,17 @@ impl StorageEngine {
     ) -> Result<bool, StorageEngineError> {
         // Dividir la línea en contenido y timestamp
         let (line_content, time_of_row) =
-            line.split_once(";").ok_or(StorageEngineError::IoError)?;
-        let mut columns: Vec<String> = line_content
-            .split(',')
+            split_stored_row(line).ok_or(StorageEngineError::IoError)?;
+        let mut columns: Vec<String> = split_storage_values(line_content)
+            .into_iter()
             .map(|s| s.trim().to_string())
             .collect();
-        let column_value_map = self.create_column_value_map(table, &columns, false);
+        // El WHERE y el IF comparan con los valores como los escribio la query
+        let unescaped: Vec<String> = columns
+            .iter()
+            .map(|value| unescape_storage_text(value))
+            .collect();
+        let column_value_map = self.create_column_value_map(table, &unescaped, false);
 
         let columns_schema = table.get_columns();
 
@@ -245,12 +283,13 @@ impl StorageEngine {
                 .execute(&column_value_map, columns_schema.clone())
                 .unwrap_or(false)
             {
-                // Evaluar la cláusula IF, si está presente
+                // Evaluar la cláusula IF, si está presente. Una fila borrada nunca la cumple
                 if let Some(if_clause) = &update_query.if_clause {
-                    if !if_clause
-                        .condition
-                        .execute(&column_value_map, columns_schema.clone())
-                        .unwrap_or(false)
+                    if parse_row_time(time_of_row).1
+                        || !if_clause
+                            .condition
+                            .execute(&column_value_map, columns_schema.clone())
+                            .unwrap_or(false)
                     {
                         // Si la cláusula IF no se cumple, escribir la línea original
                         writeln!(temp_file, "{};{}", line_content, time_of_row)?;
@@ -278,7 +317,7 @@ impl StorageEngine {
                     let index = table
                         .get_column_index(&column)
                         .ok_or(StorageEngineError::ColumnNotFound)?;
-                    columns[index] = new_value.clone();
+                    columns[index] = escape_value(new_value);
                 }
 
                 // Crear línea actualizada con el nuevo timestamp
@@ -453,7 +492,7 @@ mod tests {
         let timestamp = 1234567890;
 
         // Limpiar el entorno
-        let folder_path = storage.get_keyspace_path(keyspace);
+        let folder_path = storage.get_keyspace_path(keyspace, table_name);
         if folder_path.exists() {
             fs::remove_dir_all(&folder_path).unwrap();
         }
@@ -548,7 +587,7 @@ mod tests {
         let timestamp = 1234567890;
 
         // Limpiar el entorno
-        let folder_path = storage.get_keyspace_path(keyspace);
+        let folder_path = storage.get_keyspace_path(keyspace, table_name);
         if folder_path.exists() {
             fs::remove_dir_all(&folder_path).unwrap();
         }
@@ -629,7 +668,7 @@ mod tests {
         let timestamp = 1234567890;
 
         // Limpiar el entorno
-        let folder_path = storage.get_keyspace_path(keyspace);
+        let folder_path = storage.get_keyspace_path(keyspace, table_name);
         if folder_path.exists() {
             fs::remove_dir_all(&folder_path).unwrap();
         }
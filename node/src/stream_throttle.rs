@@ -0,0 +1,141 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Caps the amount of rows per second a node streams to other nodes while it redistributes its
+/// data, so a node joining or leaving the ring does not take the bandwidth of client queries.
+///
+/// Clones share the same limit. A clone taken before a redistribution starts can change the
+/// limit while it runs, and the new value applies from the next row sent.
+#[derive(Debug, Clone, Default)]
+pub struct StreamThrottle {
+    // 0 significa sin limite
+    rows_per_second: Arc<AtomicU64>,
+}
+
+impl StreamThrottle {
+    /// A throttle that never waits.
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// A throttle that lets at most `rows_per_second` rows through each second (at least one).
+    pub fn new(rows_per_second: u64) -> Self {
+        let throttle = Self::default();
+        throttle.set_limit(Some(rows_per_second));
+        throttle
+    }
+
+    /// Rows per second allowed, `None` when there is no limit.
+    pub fn limit(&self) -> Option<u64> {
+        match self.rows_per_second.load(Ordering::Relaxed) {
+            0 => None,
+            rows_per_second => Some(rows_per_second),
+        }
+    }
+
+    /// Changes the limit of this throttle and of all its clones. `None` removes it.
+    pub fn set_limit(&self, rows_per_second: Option<u64>) {
+        let value = rows_per_second.map_or(0, |rows| rows.max(1));
+        self.rows_per_second.store(value, Ordering::Relaxed);
+    }
+
+    /// Starts pacing a new stream of rows with the limit of this throttle.
+    pub fn stream(&self) -> ThrottledStream {
+        ThrottledStream {
+            throttle: self.clone(),
+            started_at: Instant::now(),
+            sent: 0,
+            rows_per_second: None,
+        }
+    }
+}
+
+/// Pace of a single stream of rows, see [`StreamThrottle::stream`].
+#[derive(Debug)]
+pub struct ThrottledStream {
+    throttle: StreamThrottle,
+    started_at: Instant,
+    sent: u64,
+    // Limite con el que se cuenta desde `started_at`
+    rows_per_second: Option<u64>,
+}
+
+impl ThrottledStream {
+    /// Blocks until the next row can be sent without going over the limit.
+    ///
+    /// The `n`-th row of the stream is let through `n / limit` seconds after the stream
+    /// started, so a stream that fell behind never sends a burst to catch up beyond the
+    /// limit. When the limit changes the count starts again from the current row.
+    pub fn wait_for_next_row(&mut self) {
+        let limit = self.throttle.limit();
+        if limit != self.rows_per_second {
+            self.rows_per_second = limit;
+            self.started_at = Instant::now();
+            self.sent = 0;
+        }
+
+        if let Some(rows_per_second) = limit {
+            let due = self.started_at
+                + Duration::from_secs_f64(self.sent as f64 / rows_per_second as f64);
+            let now = Instant::now();
+            if due > now {
+                thread::sleep(due - now);
+            }
+        }
+        self.sent += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Filas por segundo que salen de `stream` al enviar `rows` filas
+    fn measured_rate(stream: &mut ThrottledStream, rows: u64) -> f64 {
+        let start = Instant::now();
+        for _ in 0..rows {
+            stream.wait_for_next_row();
+        }
+        // La primera fila sale sin esperar, el intervalo medido cubre las siguientes
+        (rows - 1) as f64 / start.elapsed().as_secs_f64()
+    }
+
+    #[test]
+    fn test_a_stream_stays_under_the_configured_rate() {
+        let throttle = StreamThrottle::new(40);
+        let mut stream = throttle.stream();
+
+        let rate = measured_rate(&mut stream, 21);
+        assert!(rate <= 40.0, "streamed {} rows per second", rate);
+        assert!(rate > 20.0, "streamed {} rows per second", rate);
+    }
+
+    #[test]
+    fn test_the_limit_can_be_changed_while_streaming() {
+        let throttle = StreamThrottle::new(1_000);
+        let mut stream = throttle.stream();
+        measured_rate(&mut stream, 10);
+
+        // Un clon comparte el limite, como el que guarda el nodo
+        throttle.clone().set_limit(Some(20));
+        assert_eq!(throttle.limit(), Some(20));
+
+        let rate = measured_rate(&mut stream, 6);
+        assert!(rate <= 20.0, "streamed {} rows per second", rate);
+    }
+
+    #[test]
+    fn test_an_unlimited_stream_never_waits() {
+        let throttle = StreamThrottle::unlimited();
+        let mut stream = throttle.stream();
+
+        let start = Instant::now();
+        for _ in 0..10_000 {
+            stream.wait_for_next_row();
+        }
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert_eq!(throttle.limit(), None);
+    }
+}
@@ -150,7 +150,7 @@ pub fn connect_and_send_message(
 ///
 /// # Errors
 ///
-/// Returns an error of type `NodeError::CQLError` with `InvalidSyntax` if the keyspace is not found
+/// Returns an error of type `NodeError::CQLError` with `NoActualKeyspaceError` if the keyspace is not found
 /// after the maximum number of retries.
 ///
 pub fn check_keyspace(
@@ -187,7 +187,7 @@ pub fn check_keyspace(
     }
 
     // Si no se encuentra el keyspace después de los intentos, retornar error
-    Err(NodeError::CQLError(CQLError::InvalidSyntax))
+    Err(NodeError::CQLError(CQLError::NoActualKeyspaceError))
 }
 
 /// Checks if a table exists in the keyspace for the given query and client ID.
@@ -212,8 +212,8 @@ pub fn check_keyspace(
 ///
 /// # Errors
 ///
-/// Returns an error of type `NodeError::CQLError` with `InvalidSyntax` if the table is not found
-/// after the maximum number of retries, or if no keyspace is available.
+/// Returns an error of type `NodeError::CQLError` with `InvalidTable` if the table is not found
+/// after the maximum number of retries, or with `NoActualKeyspaceError` if no keyspace is available.
 ///
 pub fn check_table(
     node: &Arc<Mutex<Node>>,
@@ -242,7 +242,8 @@ pub fn check_table(
 
             // Si no se encuentra el keyspace, retornar un error
             if keyspace.is_none() {
-                return Err(NodeError::CQLError(CQLError::InvalidSyntax)); // Keyspace no encontrado
+                return Err(NodeError::CQLError(CQLError::NoActualKeyspaceError));
+                // Keyspace no encontrado
             }
 
             // Si se obtiene el keyspace, intentar obtener la tabla
@@ -270,5 +271,5 @@ pub fn check_table(
     }
 
     // Si no se encuentra la tabla después de los intentos, retornar error
-    Err(NodeError::CQLError(CQLError::InvalidSyntax)) // Tabla no encontrada
+    Err(NodeError::CQLError(CQLError::InvalidTable)) // Tabla no encontrada
 }
@@ -10,6 +10,7 @@ use std::thread;
 use std::time::Duration;
 
 // Import the Node struct from the "node" library
+use node::config::NodeConfig;
 use node::Node; // Assumes that Node is defined in the crate "node"
 
 /// Main entry point to start a node in the distributed system.
@@ -19,6 +20,8 @@ use node::Node; // Assumes that Node is defined in the crate "node"
 /// provided as a command-line argument, and seed IPs are read from a `seed_nodes.txt` file.
 ///
 /// Optionally, a custom path for the node's storage can be provided as a third argument.
+/// The datacenter of the node, used by `LOCAL_ONE` and `LOCAL_QUORUM`, is read from the
+/// `DATACENTER` environment variable if it is set.
 ///
 /// # Usage
 ///
@@ -73,9 +76,13 @@ fn main() -> Result<(), String> {
     // Read seed node IPs from the seed_nodes.txt file
     let seed_ips = read_seed_ips("seed_nodes.txt")?;
 
-    // Create the node with the specified IP and the list of seed IPs
+    // Create the node with the specified IP, the list of seed IPs and its datacenter, if any
+    let mut config = NodeConfig::new(node_ip, seed_ips, path_buf);
+    if let Ok(datacenter) = env::var("DATACENTER") {
+        config = config.with_datacenter(&datacenter);
+    }
     let node = Arc::new(Mutex::new(
-        Node::new(node_ip, seed_ips, path_buf).map_err(|e| e.to_string())?,
+        Node::from_config(config).map_err(|e| e.to_string())?,
     ));
 
     // Initialize the connections map
@@ -4,8 +4,28 @@ use std::collections::BTreeMap;
 use std::fmt;
 use std::io::Cursor;
 use std::net::Ipv4Addr;
+use std::ops::RangeInclusive;
 pub mod errors;
 
+/// Tokens of the ring between two of its nodes, both ends included.
+pub type TokenRange = RangeInclusive<u64>;
+
+/// Canonical byte encoding of the partition key of a row, the input of its token.
+///
+/// Each value is written as its length (`u32`, big endian) followed by its bytes, in the
+/// order the partition key columns have in the table. The prefix keeps the values apart, so
+/// `("ab", "c")` and `("a", "bc")` are different keys. Every component that computes the
+/// token of a row (coordinator routing, replication and redistribution) must use it.
+pub fn partition_key_bytes<S: AsRef<str>>(values: &[S]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for value in values {
+        let value = value.as_ref().as_bytes();
+        bytes.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(value);
+    }
+    bytes
+}
+
 #[derive(Clone)]
 pub struct Partitioner {
     nodes: BTreeMap<u64, Ipv4Addr>,
@@ -56,7 +76,7 @@ impl Partitioner {
     /// - `PartitionerError::NodeAlreadyExists` - If the node's hash already exists in the partitioner.
     pub fn add_node(&mut self, ip: Ipv4Addr) -> Result<(), PartitionerError> {
         let hash = Self::hash_value(ip.to_string())?;
-        if self.nodes.contains_key(&hash) {
+        if self.nodes.contains_key(&hash) || self.contains_node(&ip) {
             return Err(PartitionerError::NodeAlreadyExists);
         }
         self.nodes.insert(hash, ip);
@@ -74,24 +94,75 @@ impl Partitioner {
     ///   or `PartitionerError::NodeNotFound` if the node does not exist.
     ///
     /// # Errors
-    /// - `PartitionerError::HashError` - If there is an issue hashing the IP address.
     /// - `PartitionerError::NodeNotFound` - If the node is not found in the partitioner.
     pub fn remove_node(&mut self, ip: Ipv4Addr) -> Result<Ipv4Addr, PartitionerError> {
-        let hash = Self::hash_value(ip.to_string())?;
+        let token = self.token_of(&ip).ok_or(PartitionerError::NodeNotFound)?;
 
         self.nodes
-            .remove(&hash)
+            .remove(&token)
             .ok_or(PartitionerError::NodeNotFound)
     }
 
-    pub fn node_already_in_partitioner(&mut self, ip: &Ipv4Addr) -> Result<bool, PartitionerError> {
-        let hash = Self::hash_value(ip.to_string())?;
+    /// Puts `replacement` in the place of the node `dead` in the ring, so it owns the same
+    /// token ranges `dead` owned, instead of the ones the hash of its own ip would give it.
+    ///
+    /// `dead` does not need to be in the partitioner anymore: a node that was removed when it
+    /// died gets its token back through its replacement.
+    ///
+    /// # Parameters
+    /// - `dead`: The IP address of the node being replaced.
+    /// - `replacement`: The IP address of the node that takes its place.
+    ///
+    /// # Errors
+    /// - `PartitionerError::HashError` - If there is an issue hashing the IP address of `dead`.
+    /// - `PartitionerError::NodeAlreadyExists` - If `replacement` is already in the partitioner,
+    ///   or another node has the token of `dead`.
+    pub fn replace_node(
+        &mut self,
+        dead: Ipv4Addr,
+        replacement: Ipv4Addr,
+    ) -> Result<(), PartitionerError> {
+        if self.contains_node(&replacement) {
+            return Err(PartitionerError::NodeAlreadyExists);
+        }
+        let token = match self.token_of(&dead) {
+            Some(token) => token,
+            None => Self::hash_value(dead.to_string())?,
+        };
+        if self.nodes.get(&token).is_some_and(|ip| *ip != dead) {
+            return Err(PartitionerError::NodeAlreadyExists);
+        }
+        self.nodes.insert(token, replacement);
 
-        if self.nodes.contains_key(&hash) {
-            Ok(true)
-        } else {
-            Ok(false)
+        Ok(())
+    }
+
+    /// Adds the node with the given IP address at `token`, instead of the one the hash of its
+    /// ip would give it: the token a node took over when it replaced another one.
+    ///
+    /// # Errors
+    /// - `PartitionerError::NodeAlreadyExists` - If the node is already in the partitioner, or
+    ///   another node has `token`.
+    pub fn add_node_at(&mut self, ip: Ipv4Addr, token: u64) -> Result<(), PartitionerError> {
+        if self.nodes.contains_key(&token) || self.contains_node(&ip) {
+            return Err(PartitionerError::NodeAlreadyExists);
         }
+        self.nodes.insert(token, ip);
+
+        Ok(())
+    }
+
+    /// Token of the node with the given IP address in the ring, `None` if it is not in the
+    /// partitioner. It is the hash of its ip, unless the node replaced another one.
+    pub fn token_of(&self, ip: &Ipv4Addr) -> Option<u64> {
+        self.nodes
+            .iter()
+            .find(|(_, addr)| *addr == ip)
+            .map(|(token, _)| *token)
+    }
+
+    pub fn node_already_in_partitioner(&mut self, ip: &Ipv4Addr) -> Result<bool, PartitionerError> {
+        Ok(self.contains_node(ip))
     }
     /// Retrieves the IP address of the node responsible for a given value.
     ///
@@ -111,15 +182,29 @@ impl Partitioner {
             return Err(PartitionerError::EmptyPartitioner);
         }
 
-        match self.nodes.range(hash..).next() {
-            Some((_key, addr)) => Ok(*addr),
-            None => self
-                .nodes
-                .values()
-                .next()
-                .cloned()
-                .ok_or(PartitionerError::EmptyPartitioner),
-        }
+        self.owner_of_token(hash)
+            .ok_or(PartitionerError::EmptyPartitioner)
+    }
+
+    /// Token of the partition whose partition key columns have the given `values`.
+    ///
+    /// # Errors
+    /// - `PartitionerError::HashError` - If there is an issue hashing the key.
+    pub fn partition_token<S: AsRef<str>>(values: &[S]) -> Result<u64, PartitionerError> {
+        Self::hash_value(partition_key_bytes(values))
+    }
+
+    /// Retrieves the IP address of the node that owns the partition whose partition key
+    /// columns have the given `values`, encoded with `partition_key_bytes`.
+    ///
+    /// # Errors
+    /// - `PartitionerError::HashError` - If there is an issue hashing the key.
+    /// - `PartitionerError::EmptyPartitioner` - If the partitioner contains no nodes.
+    pub fn get_ip_for_partition<S: AsRef<str>>(
+        &self,
+        values: &[S],
+    ) -> Result<Ipv4Addr, PartitionerError> {
+        self.get_ip(partition_key_bytes(values))
     }
 
     /// Returns a list of all nodes' IP addresses within the partitioner.
@@ -138,8 +223,7 @@ impl Partitioner {
     /// # Returns
     /// * `bool` - Returns `true` if the node exists, `false` otherwise.
     pub fn contains_node(&self, ip: &Ipv4Addr) -> bool {
-        let hash = Self::hash_value(ip.to_string()).unwrap_or_default();
-        self.nodes.contains_key(&hash)
+        self.nodes.values().any(|addr| addr == ip)
     }
 
     /// Retrieves the IP addresses of the next `n` successor nodes in the partitioner,
@@ -164,7 +248,11 @@ impl Partitioner {
             return Err(PartitionerError::EmptyPartitioner);
         }
 
-        let hash = Self::hash_value(ip.to_string())?;
+        // Un nodo que reemplazo a otro esta en el token del reemplazado
+        let hash = match self.token_of(&ip) {
+            Some(token) => token,
+            None => Self::hash_value(ip.to_string())?,
+        };
         let mut successors = Vec::new();
 
         for (_key, addr) in self.nodes.range(hash..) {
@@ -188,6 +276,159 @@ impl Partitioner {
         }
         Ok(successors)
     }
+
+    /// Retrieves the nodes that store the partition whose partition key columns have the given
+    /// `values`: the owner of the partition first, followed by the successors that keep its
+    /// replicas.
+    ///
+    /// # Parameters
+    /// - `values`: The values of the partition key columns.
+    /// - `replication_factor`: How many copies of the partition the keyspace keeps.
+    ///
+    /// # Returns
+    /// * `Result<Vec<Ipv4Addr>, PartitionerError>` - The replicas, without duplicates. There are
+    ///   fewer than `replication_factor` if the partitioner does not have that many nodes, and
+    ///   never fewer than one.
+    ///
+    /// # Errors
+    /// - `PartitionerError::EmptyPartitioner` - If there are no nodes in the partitioner.
+    /// - `PartitionerError::HashError` - If there is an issue hashing the key.
+    pub fn get_replicas<S: AsRef<str>>(
+        &self,
+        values: &[S],
+        replication_factor: usize,
+    ) -> Result<Vec<Ipv4Addr>, PartitionerError> {
+        let owner = self.get_ip_for_partition(values)?;
+        let successors = replication_factor
+            .saturating_sub(1)
+            .min(self.nodes.len().saturating_sub(1));
+
+        let mut replicas = vec![owner];
+        replicas.extend(self.get_n_successors(owner, successors)?);
+        Ok(replicas)
+    }
+
+    /// Node that owns `token`: the first one whose token is greater or equal, going back to
+    /// the start of the ring after the last node. `None` if the partitioner has no nodes.
+    pub fn owner_of_token(&self, token: u64) -> Option<Ipv4Addr> {
+        self.nodes
+            .range(token..)
+            .next()
+            .or_else(|| self.nodes.iter().next())
+            .map(|(_, addr)| *addr)
+    }
+
+    /// Nodes responsible for some token of the range from `start` to `end`, both included:
+    /// the owner of each part of the range followed by the successors that keep its replicas,
+    /// as in `get_replicas`. A range whose `start` is greater than its `end` goes around the
+    /// end of the ring.
+    ///
+    /// Streaming the rows of a range, like the ones of `rebalance_plan`, to every node in the
+    /// result leaves each of them with all the rows it has to store.
+    ///
+    /// # Parameters
+    /// - `start`: The first token of the range.
+    /// - `end`: The last token of the range.
+    /// - `replication_factor`: How many copies of each partition the keyspace keeps.
+    ///
+    /// # Returns
+    /// * `Vec<Ipv4Addr>` - The nodes without duplicates, in ring order starting from the owner
+    ///   of `start`. It is empty if the partitioner has no nodes.
+    pub fn nodes_owning_range(
+        &self,
+        start: u64,
+        end: u64,
+        replication_factor: usize,
+    ) -> Vec<Ipv4Addr> {
+        // Cada nodo es dueño de los tokens hasta el suyo: son dueños los nodos con token en el
+        // rango y el primero despues de su final
+        let inside: Vec<Ipv4Addr> = if start <= end {
+            self.nodes.range(start..=end).map(|(_, ip)| *ip).collect()
+        } else {
+            self.nodes
+                .range(start..)
+                .chain(self.nodes.range(..=end))
+                .map(|(_, ip)| *ip)
+                .collect()
+        };
+        let owners = inside.into_iter().chain(self.owner_of_token(end));
+
+        let successors = replication_factor
+            .saturating_sub(1)
+            .min(self.nodes.len().saturating_sub(1));
+        let mut nodes: Vec<Ipv4Addr> = Vec::new();
+        for owner in owners {
+            let replicas = self.get_n_successors(owner, successors).unwrap_or_default();
+            for node in std::iter::once(owner).chain(replicas) {
+                if !nodes.contains(&node) {
+                    nodes.push(node);
+                }
+            }
+        }
+        nodes
+    }
+
+    /// Computes which token ranges change owner when the ring goes from `old` to `new`, so
+    /// only the rows in those ranges have to be moved.
+    ///
+    /// The ring is cut at the token of every node of both states. Each piece has a single
+    /// owner in each state, and the pieces whose owner changed are returned, joining the ones
+    /// that are next to each other and move between the same nodes.
+    ///
+    /// # Parameters
+    /// - `old`: The partitioner before the membership change.
+    /// - `new`: The partitioner after the membership change.
+    ///
+    /// # Returns
+    /// * `Vec<(TokenRange, Ipv4Addr, Ipv4Addr)>` - Each range with its owner in `old` and its
+    ///   owner in `new`, in token order. It is empty if the owners did not change, or if one of
+    ///   the states has no nodes, since then there is nothing to move or nowhere to move it.
+    pub fn rebalance_plan(
+        old: &Partitioner,
+        new: &Partitioner,
+    ) -> Vec<(TokenRange, Ipv4Addr, Ipv4Addr)> {
+        if old.nodes.is_empty() || new.nodes.is_empty() {
+            return Vec::new();
+        }
+
+        let mut cuts: Vec<u64> = old.nodes.keys().chain(new.nodes.keys()).copied().collect();
+        cuts.sort_unstable();
+        cuts.dedup();
+
+        // Cada pedazo termina en un corte, salvo el ultimo que llega al final del ring
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        for cut in &cuts {
+            pieces.push(start..=*cut);
+            start = cut.saturating_add(1);
+        }
+        if cuts.last().is_some_and(|last| *last < u64::MAX) {
+            pieces.push(start..=u64::MAX);
+        }
+
+        let mut plan: Vec<(TokenRange, Ipv4Addr, Ipv4Addr)> = Vec::new();
+        for piece in pieces {
+            let (from, to) = match (
+                old.owner_of_token(*piece.end()),
+                new.owner_of_token(*piece.end()),
+            ) {
+                (Some(from), Some(to)) if from != to => (from, to),
+                _ => continue,
+            };
+
+            match plan.last_mut() {
+                Some((range, last_from, last_to))
+                    if *last_from == from
+                        && *last_to == to
+                        && range.end().checked_add(1) == Some(*piece.start()) =>
+                {
+                    *range = *range.start()..=*piece.end();
+                }
+                _ => plan.push((piece, from, to)),
+            }
+        }
+        plan
+    }
 }
 
 impl fmt::Debug for Partitioner {
@@ -252,6 +493,329 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_get_replicas_starts_with_the_owner() {
+        let mut partitioner = Partitioner::new();
+        for last in 1..=4 {
+            partitioner
+                .add_node(Ipv4Addr::new(192, 168, 0, last))
+                .unwrap();
+        }
+
+        let values = ["AR1234"];
+        let replicas = partitioner.get_replicas(&values, 3).unwrap();
+
+        assert_eq!(replicas.len(), 3);
+        assert_eq!(
+            replicas[0],
+            partitioner.get_ip_for_partition(&values).unwrap()
+        );
+        assert_eq!(
+            replicas[1..],
+            partitioner.get_n_successors(replicas[0], 2).unwrap()[..]
+        );
+    }
+
+    #[test]
+    fn test_get_replicas_is_capped_by_the_nodes() {
+        let mut partitioner = Partitioner::new();
+        partitioner.add_node(Ipv4Addr::new(192, 168, 0, 1)).unwrap();
+        partitioner.add_node(Ipv4Addr::new(192, 168, 0, 2)).unwrap();
+
+        let replicas = partitioner.get_replicas(&["AR1234"], 5).unwrap();
+        assert_eq!(replicas.len(), 2);
+        assert_ne!(replicas[0], replicas[1]);
+
+        assert_eq!(partitioner.get_replicas(&["AR1234"], 0).unwrap().len(), 1);
+        assert!(matches!(
+            Partitioner::new().get_replicas(&["AR1234"], 3),
+            Err(PartitionerError::EmptyPartitioner)
+        ));
+    }
+
+    const DISTRIBUTION_KEYS: usize = 10_000;
+
+    fn ring_of(nodes: usize) -> Partitioner {
+        let mut partitioner = Partitioner::new();
+        for last in 1..=nodes {
+            partitioner
+                .add_node(Ipv4Addr::new(10, 0, 0, last as u8))
+                .unwrap();
+        }
+        partitioner
+    }
+
+    // Dueño de cada una de las claves de prueba, en orden
+    fn owners(partitioner: &Partitioner) -> Vec<Ipv4Addr> {
+        (0..DISTRIBUTION_KEYS)
+            .map(|key| {
+                partitioner
+                    .get_ip_for_partition(&[format!("flight-{}", key)])
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    // Claves de prueba que cambian de dueño entre `before` y `after`
+    fn remapped_keys(before: &[Ipv4Addr], after: &[Ipv4Addr]) -> Vec<usize> {
+        (0..before.len())
+            .filter(|&key| before[key] != after[key])
+            .collect()
+    }
+
+    // Parte del anillo (los tokens de murmur3 son de 32 bits) que cambia de dueño
+    fn moved_share(old: &Partitioner, new: &Partitioner) -> f64 {
+        let moved: u64 = Partitioner::rebalance_plan(old, new)
+            .iter()
+            .map(|(range, _, _)| range.end().min(&u64::from(u32::MAX)) - range.start() + 1)
+            .sum();
+        moved as f64 / (u64::from(u32::MAX) + 1) as f64
+    }
+
+    #[test]
+    fn test_adding_a_node_only_remaps_the_keys_it_takes() {
+        let mut normalized_fractions = Vec::new();
+        for nodes in [3, 5, 8, 16] {
+            let ring = ring_of(nodes);
+            let before = owners(&ring);
+            let new_ip = Ipv4Addr::new(10, 0, 0, nodes as u8 + 1);
+            let mut grown = ring.clone();
+            grown.add_node(new_ip).unwrap();
+            let after = owners(&grown);
+
+            // Las claves que se mueven van todas al nodo nuevo, el resto no cambia de dueño
+            let remapped = remapped_keys(&before, &after);
+            assert!(remapped.iter().all(|&key| after[key] == new_ip));
+
+            // Se mueve la parte del anillo que toma el nodo nuevo, no todas las claves
+            let fraction = remapped.len() as f64 / DISTRIBUTION_KEYS as f64;
+            assert!((fraction - moved_share(&ring, &grown)).abs() < 0.02);
+            assert!(fraction < 2.0 / (nodes + 1) as f64, "{} nodes", nodes);
+            normalized_fractions.push(fraction * (nodes + 1) as f64);
+        }
+
+        // En promedio el nodo nuevo se queda con alrededor de 1/N de las claves
+        let mean = normalized_fractions.iter().sum::<f64>() / normalized_fractions.len() as f64;
+        assert!((0.5..2.0).contains(&mean), "{}", mean);
+    }
+
+    #[test]
+    fn test_removing_a_node_only_remaps_the_keys_it_owned() {
+        let ring = ring_of(8);
+        let before = owners(&ring);
+        let removed_ip = Ipv4Addr::new(10, 0, 0, 3);
+        let successor = ring.get_n_successors(removed_ip, 1).unwrap()[0];
+        let mut shrunk = ring.clone();
+        shrunk.remove_node(removed_ip).unwrap();
+        let after = owners(&shrunk);
+
+        let remapped = remapped_keys(&before, &after);
+        let owned = before.iter().filter(|&&owner| owner == removed_ip).count();
+        assert_eq!(remapped.len(), owned);
+        for key in remapped {
+            assert_eq!(before[key], removed_ip);
+            assert_eq!(after[key], successor);
+        }
+    }
+
+    #[test]
+    fn test_a_node_added_at_a_token_takes_that_place_in_the_ring() {
+        let mut ring = ring_of(4);
+        let removed_ip = Ipv4Addr::new(10, 0, 0, 2);
+        let token = ring.token_of(&removed_ip).unwrap();
+        ring.remove_node(removed_ip).unwrap();
+
+        let ip = Ipv4Addr::new(10, 0, 1, 2);
+        ring.add_node_at(ip, token).unwrap();
+        assert_eq!(ring.token_of(&ip), Some(token));
+        assert_eq!(
+            ring.add_node_at(Ipv4Addr::new(10, 0, 1, 3), token),
+            Err(PartitionerError::NodeAlreadyExists)
+        );
+        assert_eq!(
+            ring.add_node_at(ip, token + 1),
+            Err(PartitionerError::NodeAlreadyExists)
+        );
+    }
+
+    #[test]
+    fn test_a_replacement_owns_the_keys_of_the_node_it_replaces() {
+        let ring = ring_of(8);
+        let before = owners(&ring);
+        let dead_ip = Ipv4Addr::new(10, 0, 0, 3);
+        let replacement_ip = Ipv4Addr::new(10, 0, 1, 3);
+        let dead_token = ring.token_of(&dead_ip).unwrap();
+        let successors = ring.get_n_successors(dead_ip, 2).unwrap();
+
+        // Da lo mismo si el nodo muerto ya se habia sacado del anillo
+        let mut shrunk = ring.clone();
+        shrunk.remove_node(dead_ip).unwrap();
+        for mut replaced in [ring.clone(), shrunk] {
+            replaced.replace_node(dead_ip, replacement_ip).unwrap();
+
+            assert!(!replaced.contains_node(&dead_ip));
+            assert_eq!(replaced.token_of(&replacement_ip), Some(dead_token));
+            assert_eq!(
+                replaced.get_n_successors(replacement_ip, 2).unwrap(),
+                successors
+            );
+            let after = owners(&replaced);
+            for (owner_before, owner_after) in before.iter().zip(&after) {
+                if *owner_before == dead_ip {
+                    assert_eq!(*owner_after, replacement_ip);
+                } else {
+                    assert_eq!(owner_after, owner_before);
+                }
+            }
+
+            // El reemplazo sale del anillo como cualquier otro nodo
+            assert_eq!(
+                replaced.replace_node(dead_ip, replacement_ip),
+                Err(PartitionerError::NodeAlreadyExists)
+            );
+            replaced.remove_node(replacement_ip).unwrap();
+            assert_eq!(replaced.get_nodes().len(), 7);
+        }
+    }
+
+    #[test]
+    fn test_tokens_past_the_last_node_wrap_to_the_first() {
+        let ring = ring_of(5);
+        let first_token = *ring.nodes.keys().next().unwrap();
+        let last_token = *ring.nodes.keys().next_back().unwrap();
+        let first = ring.nodes[&first_token];
+
+        assert_eq!(ring.owner_of_token(last_token + 1), Some(first));
+        assert_eq!(ring.owner_of_token(u64::MAX), Some(first));
+        assert_eq!(ring.owner_of_token(0), Some(first));
+        assert_eq!(Partitioner::new().owner_of_token(0), None);
+
+        // Las claves de prueba que caen despues del ultimo nodo tambien son del primero
+        let wrapped: Vec<usize> = (0..DISTRIBUTION_KEYS)
+            .filter(|key| {
+                Partitioner::partition_token(&[format!("flight-{}", key)]).unwrap() > last_token
+            })
+            .collect();
+        assert!(!wrapped.is_empty());
+        let owners = owners(&ring);
+        assert!(wrapped.iter().all(|&key| owners[key] == first));
+    }
+
+    #[test]
+    fn test_rebalance_plan_after_adding_a_node_only_moves_its_range() {
+        let mut old = Partitioner::new();
+        for last in 1..=3 {
+            old.add_node(Ipv4Addr::new(192, 168, 0, last)).unwrap();
+        }
+        let new_ip = Ipv4Addr::new(192, 168, 0, 4);
+        let mut new = old.clone();
+        new.add_node(new_ip).unwrap();
+
+        let new_token = Partitioner::hash_value(new_ip.to_string()).unwrap();
+        let previous_owner = old.owner_of_token(new_token).unwrap();
+        // El nodo nuevo toma los tokens entre su predecesor y el suyo
+        let expected: Vec<TokenRange> = match old.nodes.range(..new_token).next_back() {
+            Some((previous_token, _)) => vec![previous_token + 1..=new_token],
+            None => {
+                let last_token = *old.nodes.keys().next_back().unwrap();
+                vec![0..=new_token, last_token + 1..=u64::MAX]
+            }
+        };
+
+        let plan = Partitioner::rebalance_plan(&old, &new);
+        let ranges: Vec<TokenRange> = plan.iter().map(|(range, _, _)| range.clone()).collect();
+        assert_eq!(ranges, expected);
+        for (_, from, to) in &plan {
+            assert_eq!(*from, previous_owner);
+            assert_eq!(*to, new_ip);
+        }
+    }
+
+    #[test]
+    fn test_rebalance_plan_after_removing_a_node_and_without_changes() {
+        let mut old = Partitioner::new();
+        for last in 1..=4 {
+            old.add_node(Ipv4Addr::new(192, 168, 0, last)).unwrap();
+        }
+        let removed_ip = Ipv4Addr::new(192, 168, 0, 2);
+        let mut new = old.clone();
+        new.remove_node(removed_ip).unwrap();
+
+        let plan = Partitioner::rebalance_plan(&old, &new);
+        assert!(!plan.is_empty());
+        for (range, from, to) in &plan {
+            assert_eq!(*from, removed_ip);
+            assert_eq!(new.owner_of_token(*range.start()), Some(*to));
+            assert_eq!(new.owner_of_token(*range.end()), Some(*to));
+        }
+
+        assert!(Partitioner::rebalance_plan(&old, &old).is_empty());
+        assert!(Partitioner::rebalance_plan(&Partitioner::new(), &old).is_empty());
+    }
+
+    #[test]
+    fn test_nodes_owning_range_covers_every_part_of_the_range() {
+        let mut partitioner = Partitioner::new();
+        for last in 1..=5 {
+            partitioner
+                .add_node(Ipv4Addr::new(192, 168, 0, last))
+                .unwrap();
+        }
+        let tokens: Vec<u64> = partitioner.nodes.keys().copied().collect();
+        let ring: Vec<Ipv4Addr> = partitioner.nodes.values().copied().collect();
+        let replicas_of = |token: u64, rf: usize| {
+            let owner = partitioner.owner_of_token(token).unwrap();
+            let mut replicas = vec![owner];
+            replicas.extend(partitioner.get_n_successors(owner, rf - 1).unwrap());
+            replicas
+        };
+
+        // Del medio del rango del segundo nodo al medio del rango del cuarto: cruza dos tokens
+        let start = tokens[0] + (tokens[1] - tokens[0]) / 2;
+        let end = tokens[2] + (tokens[3] - tokens[2]) / 2;
+        assert_eq!(
+            partitioner.nodes_owning_range(start, end, 1),
+            vec![ring[1], ring[2], ring[3]]
+        );
+
+        let nodes = partitioner.nodes_owning_range(start, end, 2);
+        assert_eq!(nodes, vec![ring[1], ring[2], ring[3], ring[4]]);
+        for token in [start, tokens[1], tokens[1] + 1, tokens[2], end] {
+            assert!(replicas_of(token, 2).iter().all(|ip| nodes.contains(ip)));
+        }
+
+        // Un rango dentro del de un solo nodo son sus replicas
+        assert_eq!(
+            partitioner.nodes_owning_range(start, start, 3),
+            replicas_of(start, 3)
+        );
+
+        // Un rango que da la vuelta al ring
+        assert_eq!(
+            partitioner.nodes_owning_range(tokens[4] + 1, tokens[0], 1),
+            vec![ring[0]]
+        );
+        assert!(Partitioner::new()
+            .nodes_owning_range(0, u64::MAX, 3)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_partition_key_bytes_keeps_the_values_apart() {
+        assert_ne!(
+            partition_key_bytes(&["ab", "c"]),
+            partition_key_bytes(&["a", "bc"])
+        );
+        assert_eq!(
+            partition_key_bytes(&["ab".to_string(), "c".to_string()]),
+            vec![0, 0, 0, 2, b'a', b'b', 0, 0, 0, 1, b'c']
+        );
+        assert_eq!(
+            Partitioner::partition_token(&["ab", "c"]).unwrap(),
+            Partitioner::hash_value(partition_key_bytes(&["ab", "c"])).unwrap()
+        );
+    }
+
     #[test]
     fn test_debug_trait() {
         let mut partitioner = Partitioner::new();
@@ -0,0 +1,172 @@
+use crate::errors::CQLError;
+use crate::{ConditionalWrite, Query, QueryCreator};
+
+/// Struct that represents a `BEGIN BATCH ... APPLY BATCH` statement.
+/// A batch groups several writes so the client sends them in a single request.
+///
+/// # Fields
+///
+/// * `statements` - The `INSERT`, `UPDATE` and `DELETE` statements of the batch, in order.
+/// * `logged` - `false` if the batch was declared as `UNLOGGED`.
+///
+#[derive(Debug, Clone)]
+pub struct Batch {
+    pub statements: Vec<Query>,
+    pub logged: bool,
+}
+
+impl Batch {
+    /// Returns `true` if `query` starts a batch (`BEGIN BATCH` or `BEGIN UNLOGGED BATCH`).
+    pub fn is_batch(query: &str) -> bool {
+        query
+            .split_whitespace()
+            .next()
+            .is_some_and(|word| word.eq_ignore_ascii_case("BEGIN"))
+    }
+
+    /// Creates and returns a new `Batch` instance from a query string.
+    ///
+    /// The query should have the form
+    /// `BEGIN [UNLOGGED | LOGGED] BATCH statement; statement; ... APPLY BATCH`, where every
+    /// statement is an unconditional `INSERT`, `UPDATE` or `DELETE`.
+    ///
+    /// # Returns
+    /// * `Ok(Batch)` - A successfully parsed `Batch` struct.
+    /// * `Err(CQLError::InvalidSyntax)` - If the batch is malformed, is empty or has a statement
+    ///   that cannot be batched.
+    pub fn new_from_query(query: &str) -> Result<Self, CQLError> {
+        let rest = strip_word(query, "BEGIN").ok_or(CQLError::InvalidSyntax)?;
+        let (logged, rest) = if let Some(rest) = strip_word(rest, "UNLOGGED") {
+            (false, rest)
+        } else if let Some(rest) = strip_word(rest, "LOGGED") {
+            (true, rest)
+        } else {
+            (true, rest)
+        };
+        let body = strip_word(rest, "BATCH").ok_or(CQLError::InvalidSyntax)?;
+
+        // Lo que queda entre el encabezado y `APPLY BATCH`
+        let body = body.trim_end().trim_end_matches(';').trim_end();
+        let body = strip_suffix_ignore_case(body, "APPLY BATCH").ok_or(CQLError::InvalidSyntax)?;
+
+        let statements = split_statements(body)
+            .into_iter()
+            .map(|statement| QueryCreator::new().handle_query(statement))
+            .collect::<Result<Vec<Query>, CQLError>>()?;
+
+        if statements.is_empty() {
+            return Err(CQLError::InvalidSyntax);
+        }
+
+        for statement in &statements {
+            match statement {
+                Query::Insert(_) | Query::Update(_) | Query::Delete(_)
+                    if !statement.is_conditional() => {}
+                _ => return Err(CQLError::InvalidSyntax),
+            }
+        }
+
+        Ok(Self { statements, logged })
+    }
+}
+
+// Saca `word` del principio de `string` si esta como palabra completa
+fn strip_word<'a>(string: &'a str, word: &str) -> Option<&'a str> {
+    let string = string.trim_start();
+    let (start, rest) = (string.get(..word.len())?, string.get(word.len()..)?);
+
+    let is_word_end = rest.is_empty() || rest.starts_with(char::is_whitespace);
+    if start.eq_ignore_ascii_case(word) && is_word_end {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+fn strip_suffix_ignore_case<'a>(string: &'a str, suffix: &str) -> Option<&'a str> {
+    let split = string.len().checked_sub(suffix.len())?;
+    let (rest, end) = (string.get(..split)?, string.get(split..)?);
+
+    if end.eq_ignore_ascii_case(suffix) {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+// Separa por ';' salvo dentro de strings
+fn split_statements(body: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for char in body.chars() {
+        match char {
+            '\'' => {
+                in_quotes = !in_quotes;
+                current.push(char);
+            }
+            ';' if !in_quotes => statements.push(std::mem::take(&mut current)),
+            _ => current.push(char),
+        }
+    }
+    statements.push(current);
+
+    statements
+        .into_iter()
+        .map(|statement| statement.trim().to_string())
+        .filter(|statement| !statement.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_keeps_its_statements_in_order() {
+        let batch = Batch::new_from_query(
+            "BEGIN BATCH \
+             INSERT INTO sky.flights (id, origin) VALUES (1, 'EZE; MDZ'); \
+             UPDATE sky.flights SET origin = 'AEP' WHERE id = 2; \
+             DELETE FROM sky.flights WHERE id = 3; \
+             APPLY BATCH;",
+        )
+        .unwrap();
+
+        assert!(batch.logged);
+        assert_eq!(batch.statements.len(), 3);
+        assert!(matches!(batch.statements[0], Query::Insert(_)));
+        assert!(matches!(batch.statements[1], Query::Update(_)));
+        assert!(matches!(batch.statements[2], Query::Delete(_)));
+    }
+
+    #[test]
+    fn test_unlogged_batch() {
+        let batch = Batch::new_from_query(
+            "begin unlogged batch UPDATE flights SET origin = 'AEP' WHERE id = 2 apply batch",
+        )
+        .unwrap();
+
+        assert!(!batch.logged);
+        assert_eq!(batch.statements.len(), 1);
+    }
+
+    #[test]
+    fn test_batch_rejects_reads_conditional_writes_and_empty_batches() {
+        for query in [
+            "BEGIN BATCH SELECT id FROM flights WHERE id = 1; APPLY BATCH",
+            "BEGIN BATCH UPDATE flights SET origin = 'AEP' WHERE id = 2 IF origin = 'EZE'; APPLY BATCH",
+            "BEGIN BATCH APPLY BATCH",
+            "BEGIN BATCH INSERT INTO flights (id) VALUES (1);",
+            "BEGIN COUNTER BATCH INSERT INTO flights (id) VALUES (1); APPLY BATCH",
+        ] {
+            assert_eq!(
+                Batch::new_from_query(query).unwrap_err(),
+                CQLError::InvalidSyntax,
+                "{}",
+                query
+            );
+        }
+    }
+}
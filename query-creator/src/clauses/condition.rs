@@ -1,7 +1,7 @@
 use crate::{errors::CQLError, logical_operator::LogicalOperator, operator::Operator};
 use std::collections::HashMap;
 
-use super::types::column::Column;
+use super::{insert_cql::NULL_VALUE, types::column::Column};
 
 /// Represents a condition in a `WHERE` clause of a CQL query.
 ///
@@ -32,6 +32,9 @@ pub enum Condition {
 impl Condition {
     /// Creates a new `Simple` condition from tokens.
     ///
+    /// The operator is one token (`=`, `>`, `<`, `!=`, `CONTAINS`) except for `CONTAINS KEY`,
+    /// which takes two.
+    ///
     /// # Parameters
     /// - `tokens: &[&str]`:
     ///   - A slice of tokens representing the condition.
@@ -50,6 +53,14 @@ impl Condition {
             if let Some(operator) = tokens.get(*pos) {
                 *pos += 1;
 
+                // `CONTAINS KEY` es el único operador que ocupa dos tokens
+                let operator = if *operator == "CONTAINS" && tokens.get(*pos) == Some(&"KEY") {
+                    *pos += 1;
+                    "CONTAINS KEY"
+                } else {
+                    operator
+                };
+
                 if let Some(value) = tokens.get(*pos) {
                     *pos += 1;
                     Ok(Condition::new_simple(field, operator, value)?)
@@ -69,6 +80,9 @@ impl Condition {
             "=" => Operator::Equal,
             ">" => Operator::Greater,
             "<" => Operator::Lesser,
+            "!=" => Operator::NotEqual,
+            "CONTAINS" => Operator::Contains,
+            "CONTAINS KEY" => Operator::ContainsKey,
             _ => return Err(CQLError::InvalidSyntax),
         };
 
@@ -105,6 +119,10 @@ impl Condition {
 
     /// Executes the condition on a given register.
     ///
+    /// Null values follow CQL semantics: comparing a null or missing column is neither true
+    /// nor false but unknown, and a row only matches if the whole condition is true. See
+    /// [`Condition::evaluate`].
+    ///
     /// # Parameters
     /// - `register: &HashMap<String, String>`:
     ///   - A map representing the record to evaluate.
@@ -114,67 +132,129 @@ impl Condition {
     /// # Returns
     /// - `Ok(bool)`:
     ///   - `true` if the condition evaluates to `true`.
-    ///   - `false` otherwise.
+    ///   - `false` otherwise, including when it is unknown.
     /// - `Err(CQLError)`:
-    ///   - If the condition cannot be evaluated due to invalid types or missing fields.
+    ///   - If the condition cannot be evaluated due to invalid types or unknown fields.
     pub fn execute(
         &self,
         register: &HashMap<String, String>,
         columns: Vec<Column>,
     ) -> Result<bool, CQLError> {
-        let op_result: Result<bool, CQLError> = match &self {
+        Ok(self.evaluate(register, &columns)? == Some(true))
+    }
+
+    /// Evaluates the condition on a given register with three-valued logic, where `None` is
+    /// unknown: the result of comparing a column that is null ([`NULL_VALUE`]) or missing
+    /// from the register.
+    ///
+    /// `AND` is false if either side is false and `OR` is true if either side is true, even
+    /// if the other is unknown; `NOT` of unknown is unknown. Every field, value and operator
+    /// of the condition is checked first, so an invalid one is reported whatever the register;
+    /// then both short-circuit: the right side is not compared if the left one decides the
+    /// result.
+    ///
+    /// # Errors
+    /// - `CQLError::Error` if a field is not a column of the table, or `AND`/`OR` have no left
+    ///   side.
+    /// - `CQLError::InvalidSyntax` if a value is not valid for the type of its column.
+    /// - `CQLError::InvalidCondition` if the operator does not apply to the column.
+    pub fn evaluate(
+        &self,
+        register: &HashMap<String, String>,
+        columns: &[Column],
+    ) -> Result<Option<bool>, CQLError> {
+        self.validate(columns)?;
+        self.evaluate_validated(register, columns)
+    }
+
+    // Revisa que cada campo sea una columna, su valor sea de su tipo y el operador se le aplique
+    fn validate(&self, columns: &[Column]) -> Result<(), CQLError> {
+        match self {
             Condition::Simple {
                 field,
                 operator,
                 value,
             } => {
-                let y = value;
-                if let Some(x) = register.get(field) {
-                    let col = columns
-                        .iter()
-                        .find(|col| &col.name == field)
-                        .ok_or(CQLError::Error)?;
-                    let col_type = &col.data_type;
-                    if col_type.is_valid_value(value) {
-                        let comparison = col_type.compare(x, y, operator)?;
-                        return Ok(comparison);
-                    } else {
-                        return Err(CQLError::InvalidSyntax);
-                    }
-                } else {
-                    Err(CQLError::Error)
+                let col = columns
+                    .iter()
+                    .find(|col| &col.name == field)
+                    .ok_or(CQLError::Error)?;
+                if !col.data_type.is_valid_value(value) {
+                    return Err(CQLError::InvalidSyntax);
+                }
+                // Ningun tipo de columna es una coleccion
+                if matches!(operator, Operator::Contains | Operator::ContainsKey) {
+                    return Err(CQLError::InvalidCondition);
                 }
+                Ok(())
             }
             Condition::Complex {
                 left,
                 operator,
                 right,
-            } => match operator {
-                LogicalOperator::Not => {
-                    let result = right.execute(register, columns)?;
-                    Ok(!result)
+            } => {
+                match left {
+                    Some(left) => left.validate(columns)?,
+                    None if *operator != LogicalOperator::Not => return Err(CQLError::Error),
+                    None => {}
+                }
+                right.validate(columns)
+            }
+        }
+    }
+
+    fn evaluate_validated(
+        &self,
+        register: &HashMap<String, String>,
+        columns: &[Column],
+    ) -> Result<Option<bool>, CQLError> {
+        match &self {
+            Condition::Simple {
+                field,
+                operator,
+                value,
+            } => {
+                let col_type = &columns
+                    .iter()
+                    .find(|col| &col.name == field)
+                    .ok_or(CQLError::Error)?
+                    .data_type;
+                // Comparar contra null no es ni verdadero ni falso
+                match register.get(field) {
+                    Some(x) if x != NULL_VALUE => Ok(Some(col_type.compare(x, value, operator)?)),
+                    _ => Ok(None),
                 }
+            }
+            Condition::Complex {
+                left,
+                operator,
+                right,
+            } => match operator {
+                LogicalOperator::Not => Ok(right
+                    .evaluate_validated(register, columns)?
+                    .map(|result| !result)),
                 LogicalOperator::Or => {
-                    if let Some(left) = left {
-                        let left_result = left.execute(register, columns.clone())?;
-                        let right_result = right.execute(register, columns.clone())?;
-                        Ok(left_result || right_result)
-                    } else {
-                        Err(CQLError::Error)
+                    let left = left.as_ref().ok_or(CQLError::Error)?;
+                    match left.evaluate_validated(register, columns)? {
+                        Some(true) => Ok(Some(true)),
+                        Some(false) => right.evaluate_validated(register, columns),
+                        None => Ok(right
+                            .evaluate_validated(register, columns)?
+                            .filter(|result| *result)),
                     }
                 }
                 LogicalOperator::And => {
-                    if let Some(left) = left {
-                        let left_result = left.execute(register, columns.clone())?;
-                        let right_result = right.execute(register, columns.clone())?;
-                        Ok(left_result && right_result)
-                    } else {
-                        Err(CQLError::Error)
+                    let left = left.as_ref().ok_or(CQLError::Error)?;
+                    match left.evaluate_validated(register, columns)? {
+                        Some(false) => Ok(Some(false)),
+                        Some(true) => right.evaluate_validated(register, columns),
+                        None => Ok(right
+                            .evaluate_validated(register, columns)?
+                            .filter(|result| !*result)),
                     }
                 }
             },
-        };
-        op_result
+        }
     }
 
     /// Serializes the condition into a string.
@@ -240,7 +320,10 @@ impl Condition {
     ///   - If the tokens are invalid or improperly formatted.
     fn parse_tokens(tokens: &[&str], mut start: usize, end: usize) -> Result<Self, CQLError> {
         // Si solo tiene 3 tokens, es una condición simple (e.g., `field = value`)
-        if end - start == 3 {
+        // (o 4 si el operador es `CONTAINS KEY`)
+        if end - start == 3
+            || (end - start == 4 && tokens[start + 1] == "CONTAINS" && tokens[start + 2] == "KEY")
+        {
             return Self::new_simple_from_tokens(tokens, &mut start);
         }
 
@@ -276,11 +359,12 @@ impl Condition {
 
 #[cfg(test)]
 mod tests {
-    use super::Condition;
+    use super::{Condition, NULL_VALUE};
     use crate::clauses::{
         condition::{LogicalOperator, Operator},
         types::{column::Column, datatype::DataType},
     };
+    use crate::errors::CQLError;
     use std::collections::HashMap;
 
     #[test]
@@ -312,6 +396,59 @@ mod tests {
         )
     }
 
+    #[test]
+    fn create_not_equal_and_contains_from_tokens() {
+        let tokens = vec!["status", "!=", "cancelled"];
+        let mut pos = 0;
+        let condition = Condition::new_simple_from_tokens(&tokens, &mut pos).unwrap();
+        assert_eq!(
+            condition,
+            Condition::Simple {
+                field: String::from("status"),
+                operator: Operator::NotEqual,
+                value: String::from("cancelled")
+            }
+        );
+
+        let tokens = vec!["tags", "CONTAINS", "delayed"];
+        let mut pos = 0;
+        let condition = Condition::new_simple_from_tokens(&tokens, &mut pos).unwrap();
+        assert_eq!(
+            condition,
+            Condition::Simple {
+                field: String::from("tags"),
+                operator: Operator::Contains,
+                value: String::from("delayed")
+            }
+        );
+
+        let tokens = vec!["crew", "CONTAINS", "KEY", "pilot"];
+        let mut pos = 0;
+        let condition = Condition::new_simple_from_tokens(&tokens, &mut pos).unwrap();
+        assert_eq!(pos, 4);
+        assert_eq!(
+            condition,
+            Condition::Simple {
+                field: String::from("crew"),
+                operator: Operator::ContainsKey,
+                value: String::from("pilot")
+            }
+        );
+    }
+
+    #[test]
+    fn serialize_and_deserialize_contains_key() {
+        let condition = Condition::Simple {
+            field: String::from("crew"),
+            operator: Operator::ContainsKey,
+            value: String::from("pilot"),
+        };
+
+        let serialized = condition.serialize();
+        assert_eq!(serialized, "crew CONTAINS KEY pilot");
+        assert_eq!(Condition::deserialize(&serialized).unwrap(), condition);
+    }
+
     #[test]
     fn create_complex_with_left() {
         let left = Condition::Simple {
@@ -403,6 +540,44 @@ mod tests {
         assert_eq!(result_false, false);
     }
 
+    #[test]
+    fn execute_not_equal() {
+        let mut register = HashMap::new();
+        register.insert(String::from("name"), String::from("Alen"));
+        register.insert(String::from("age"), String::from("24"));
+
+        let columns: Vec<Column> = vec![
+            Column::new("name", DataType::String, false, false),
+            Column::new("age", DataType::Int, false, false),
+        ];
+
+        let different = Condition::new_simple("name", "!=", "Ana").unwrap();
+        let same = Condition::new_simple("age", "!=", "24").unwrap();
+
+        assert!(different.execute(&register, columns.clone()).unwrap());
+        assert!(!same.execute(&register, columns).unwrap());
+    }
+
+    #[test]
+    fn execute_contains_on_a_column_that_is_not_a_collection() {
+        let mut register = HashMap::new();
+        register.insert(String::from("name"), String::from("Alen"));
+
+        let columns: Vec<Column> = vec![Column::new("name", DataType::String, false, false)];
+
+        let contains = Condition::new_simple("name", "CONTAINS", "Alen").unwrap();
+        let contains_key = Condition::new_simple("name", "CONTAINS KEY", "Alen").unwrap();
+
+        assert_eq!(
+            contains.execute(&register, columns.clone()),
+            Err(CQLError::InvalidCondition)
+        );
+        assert_eq!(
+            contains_key.execute(&register, columns),
+            Err(CQLError::InvalidCondition)
+        );
+    }
+
     #[test]
     fn execute_and() {
         let mut register = HashMap::new();
@@ -502,6 +677,87 @@ mod tests {
         assert_eq!(result, true)
     }
 
+    #[test]
+    fn comparisons_with_null_columns_are_unknown() {
+        let mut register = HashMap::new();
+        register.insert(String::from("x"), String::from("1"));
+        register.insert(String::from("y"), NULL_VALUE.to_string());
+
+        let columns: Vec<Column> = vec![
+            Column::new("x", DataType::Int, false, false),
+            Column::new("y", DataType::Int, false, false),
+            Column::new("z", DataType::Int, false, false),
+        ];
+
+        let null_y = Condition::deserialize("x = 1 AND y > 2").unwrap();
+        assert_eq!(null_y.evaluate(&register, &columns), Ok(None));
+        assert!(!null_y.execute(&register, columns.clone()).unwrap());
+
+        // Una columna que no está en la fila también es null
+        let missing_z = Condition::deserialize("x = 1 AND z > 2").unwrap();
+        assert!(!missing_z.execute(&register, columns.clone()).unwrap());
+
+        let negated = Condition::deserialize("NOT y > 2").unwrap();
+        assert!(!negated.execute(&register, columns.clone()).unwrap());
+
+        // Un lado falso o verdadero decide aunque el otro sea null
+        let false_and_null = Condition::deserialize("x = 2 AND y > 2").unwrap();
+        assert_eq!(
+            false_and_null.evaluate(&register, &columns),
+            Ok(Some(false))
+        );
+        let null_or_true = Condition::deserialize("y > 2 OR x = 1").unwrap();
+        assert_eq!(null_or_true.evaluate(&register, &columns), Ok(Some(true)));
+    }
+
+    #[test]
+    fn and_and_or_do_not_compare_the_right_side_when_the_left_decides() {
+        let mut register = HashMap::new();
+        register.insert(String::from("x"), String::from("1"));
+        // Un valor guardado que no es del tipo de su columna no se puede comparar
+        register.insert(String::from("y"), String::from("not a number"));
+
+        let columns: Vec<Column> = vec![
+            Column::new("x", DataType::Int, false, false),
+            Column::new("y", DataType::Int, false, false),
+        ];
+
+        let or = Condition::deserialize("x = 1 OR y = 2").unwrap();
+        assert_eq!(or.execute(&register, columns.clone()), Ok(true));
+
+        let and = Condition::deserialize("x = 2 AND y = 2").unwrap();
+        assert_eq!(and.execute(&register, columns.clone()), Ok(false));
+
+        let not_decided = Condition::deserialize("x = 2 OR y = 2").unwrap();
+        assert_eq!(
+            not_decided.execute(&register, columns),
+            Err(CQLError::InvalidCondition)
+        );
+    }
+
+    #[test]
+    fn unknown_columns_are_rejected_even_if_the_left_side_decides() {
+        let mut register = HashMap::new();
+        register.insert(String::from("x"), String::from("1"));
+
+        let columns: Vec<Column> = vec![Column::new("x", DataType::Int, false, false)];
+
+        let or = Condition::deserialize("x = 1 OR unknown = 2").unwrap();
+        assert_eq!(or.execute(&register, columns.clone()), Err(CQLError::Error));
+
+        let and = Condition::deserialize("x = 2 AND unknown = 2").unwrap();
+        assert_eq!(
+            and.execute(&register, columns.clone()),
+            Err(CQLError::Error)
+        );
+
+        let invalid_value = Condition::deserialize("x = 1 OR x = abc").unwrap();
+        assert_eq!(
+            invalid_value.execute(&register, columns),
+            Err(CQLError::InvalidSyntax)
+        );
+    }
+
     #[test]
     fn execute_and_or() {
         let mut register = HashMap::new();
@@ -0,0 +1,102 @@
+use super::select_cql::Select;
+use crate::errors::CQLError;
+use crate::QueryCreator;
+
+/// Struct that represents the `EXPLAIN` clause.
+/// The `EXPLAIN` clause wraps a `SELECT` and asks for the plan that would be used to run it,
+/// instead of running it.
+///
+/// # Fields
+///
+/// * `select` - The `SELECT` query to be explained.
+///
+#[derive(Debug, PartialEq, Clone)]
+pub struct Explain {
+    pub select: Select,
+}
+
+impl Explain {
+    /// Creates and returns a new `Explain` instance from a vector of tokens.
+    ///
+    /// # Arguments
+    ///
+    /// * `tokens` - A vector of strings that contains the tokens to be parsed.
+    ///
+    /// The tokens should be in the following order: `EXPLAIN`, followed by the tokens of a `SELECT`.
+    ///
+    /// # Returns
+    /// * `Ok(Explain)` - A successfully parsed `Explain` struct.
+    /// * `Err(CQLError::InvalidSyntax)` - If the tokens are invalid or the explained query is not a `SELECT`.
+    pub fn new_from_tokens(tokens: Vec<String>) -> Result<Self, CQLError> {
+        if tokens.len() < 2 || tokens[0] != "EXPLAIN" || tokens[1] != "SELECT" {
+            return Err(CQLError::InvalidSyntax);
+        }
+
+        let select = Select::new_from_tokens(tokens[1..].to_vec())?;
+        Ok(Self { select })
+    }
+
+    /// Serializes the `Explain` struct into a query string representation.
+    ///
+    /// # Returns
+    /// A `String` in the format `EXPLAIN SELECT ...`
+    pub fn serialize(&self) -> String {
+        format!("EXPLAIN {}", self.select.serialize())
+    }
+
+    /// Deserializes a query string into an `Explain` struct.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - A string query in the format `EXPLAIN SELECT ...`.
+    ///
+    /// # Returns
+    /// * `Ok(Explain)` - If the string is successfully parsed.
+    /// * `Err(CQLError::InvalidSyntax)` - If the string is invalid or improperly formatted.
+    pub fn deserialize(query: &str) -> Result<Self, CQLError> {
+        let tokens = QueryCreator::tokens_from_query(query);
+        Self::new_from_tokens(tokens)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn new_valid_syntax() {
+        let tokens = QueryCreator::tokens_from_query(
+            "EXPLAIN SELECT id, origin FROM sky.flights WHERE id = 1",
+        );
+        let explain = Explain::new_from_tokens(tokens).unwrap();
+
+        assert_eq!(explain.select.table_name, "flights");
+        assert_eq!(explain.select.keyspace_used_name, "sky");
+        assert_eq!(explain.select.columns, vec!["id", "origin"]);
+        assert!(explain.select.where_clause.is_some());
+    }
+
+    #[test]
+    fn new_without_select_is_invalid() {
+        let tokens = QueryCreator::tokens_from_query("EXPLAIN DELETE FROM flights WHERE id = 1");
+        assert_eq!(
+            Explain::new_from_tokens(tokens),
+            Err(CQLError::InvalidSyntax)
+        );
+
+        let tokens = vec![String::from("EXPLAIN")];
+        assert_eq!(
+            Explain::new_from_tokens(tokens),
+            Err(CQLError::InvalidSyntax)
+        );
+    }
+
+    #[test]
+    fn serialize_and_deserialize() {
+        let explain = Explain::deserialize("EXPLAIN SELECT id FROM flights WHERE id = 1").unwrap();
+        let serialized = explain.serialize();
+
+        assert!(serialized.starts_with("EXPLAIN SELECT id FROM flights WHERE"));
+        assert_eq!(Explain::deserialize(&serialized).unwrap(), explain);
+    }
+}
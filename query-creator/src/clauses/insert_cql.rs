@@ -2,6 +2,39 @@ use super::into_cql::Into;
 use crate::errors::CQLError;
 use crate::utils::{is_insert, is_values};
 use crate::QueryCreator;
+use native_protocol::messages::result::rows::STORAGE_NULL;
+
+/// How a `NULL` value is kept in the `values` of an `Insert` and in the rows stored by the nodes,
+/// so it is never mistaken for an empty string.
+pub const NULL_VALUE: &str = STORAGE_NULL;
+
+/// Writes a literal value as it is kept in the `values` of an `Insert`, so it is never taken for
+/// [`NULL_VALUE`]: a value made of backslashes and a final `N`, like the text `\N`, gets one more
+/// backslash in front. [`unescape_literal`] takes it back out.
+pub fn escape_literal(value: &str) -> String {
+    if collides_with_null(value) {
+        format!("\\{}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// The text of a value kept in the `values` of an `Insert`, the inverse of [`escape_literal`].
+/// [`NULL_VALUE`] is kept as is.
+pub fn unescape_literal(value: &str) -> &str {
+    if value != NULL_VALUE && collides_with_null(value) {
+        &value[1..]
+    } else {
+        value
+    }
+}
+
+// Un texto como `\N`, `\\N`, ... se confundiria con el marcador de NULL o con uno ya escapado
+fn collides_with_null(value: &str) -> bool {
+    value
+        .strip_suffix('N')
+        .is_some_and(|prefix| !prefix.is_empty() && prefix.chars().all(|c| c == '\\'))
+}
 
 /// Represents the `INSERT` clause in CQL queries.
 ///
@@ -41,6 +74,10 @@ impl Insert {
     /// - The expected token order is:
     ///   `"INSERT", "INTO", "table_name", "columns", "VALUES", "values" [IF NOT EXISTS]`.
     /// - Column names and values should be enclosed in parentheses and separated by commas.
+    /// - An unquoted `null` is stored as [`NULL_VALUE`]; a quoted `'null'` is the text `null`.
+    /// - A literal that reads as [`NULL_VALUE`], like `'\N'`, is kept escaped with
+    ///   [`escape_literal`].
+    /// - A quoted value can have commas, and a quote inside it is written twice: `'O''Hare'`.
     pub fn new_from_tokens(tokens: Vec<String>) -> Result<Self, CQLError> {
         if tokens.len() < 6 {
             return Err(CQLError::InvalidSyntax);
@@ -60,15 +97,7 @@ impl Insert {
         if is_values(&tokens[i]) {
             i += 1;
 
-            let vals: Vec<String> = tokens[i]
-                .replace("\'", "")
-                .split(",")
-                .map(|c| c.trim().to_string())
-                .collect();
-
-            for val in vals {
-                values.push(val);
-            }
+            values = split_values(&tokens[i]);
             i += 1;
         }
 
@@ -103,9 +132,23 @@ impl Insert {
     ///     ```sql
     ///     INSERT INTO [keyspace.]table_name (columns) VALUES (values) [IF NOT EXISTS];
     ///     `
+    ///   - [`NULL_VALUE`] is written as `null`, and a value with a comma or a quote is quoted.
+    ///   - A literal escaped with [`escape_literal`] is written as its text.
     pub fn serialize(&self) -> String {
         let columns = self.into_clause.columns.join(", ");
-        let values = self.values.join(", ");
+        let values = self
+            .values
+            .iter()
+            .map(|value| match unescape_literal(value) {
+                _ if value == NULL_VALUE => "null".to_string(),
+                // Se citan para que no se lean de vuelta como NULL ni se partan en las comas
+                text if text.eq_ignore_ascii_case("null") || text.contains([',', '\'']) => {
+                    format!("'{}'", text.replace('\'', "''"))
+                }
+                other => other.to_string(),
+            })
+            .collect::<Vec<String>>()
+            .join(", ");
 
         let if_not_exists = if self.if_not_exists {
             " IF NOT EXISTS"
@@ -145,8 +188,57 @@ impl Insert {
     }
 }
 
+// Separa los valores de `VALUES (...)` en las comas que no estan entre comillas. A un valor
+// entre comillas se le sacan las comillas y `''` queda como una comilla.
+fn split_values(values: &str) -> Vec<String> {
+    let mut split = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut in_quotes = false;
+    let mut chars = values.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' if in_quotes && chars.peek() == Some(&'\'') => {
+                current.push('\'');
+                chars.next();
+            }
+            '\'' => {
+                // Lo que hay antes de la comilla que abre son solo espacios
+                if !in_quotes {
+                    current.clear();
+                }
+                in_quotes = !in_quotes;
+                quoted = true;
+            }
+            c if quoted && !in_quotes && c.is_whitespace() => {}
+            ',' if !in_quotes => {
+                split.push(value_of(&current, quoted));
+                current.clear();
+                quoted = false;
+            }
+            c => current.push(c),
+        }
+    }
+    split.push(value_of(&current, quoted));
+    split
+}
+
+// Solo un `null` sin comillas es NULL, `'null'` es un texto
+fn value_of(value: &str, quoted: bool) -> String {
+    if quoted {
+        return escape_literal(value);
+    }
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("null") {
+        NULL_VALUE.to_string()
+    } else {
+        escape_literal(value)
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use super::{unescape_literal, NULL_VALUE};
     use crate::{clauses::into_cql, errors::CQLError, Insert};
 
     #[test]
@@ -238,4 +330,51 @@ mod test {
         let deserialized = Insert::deserialize(s);
         assert_eq!(deserialized, Err(CQLError::InvalidSyntax));
     }
+
+    #[test]
+    fn deserialize_insert_with_null_keeps_it_apart_from_text() {
+        let s = "INSERT INTO table (name, nickname, age) VALUES ('Alen', 'null', NULL)";
+        let deserialized = Insert::deserialize(s).unwrap();
+
+        assert_eq!(deserialized.values, vec!["Alen", "null", NULL_VALUE]);
+        assert_eq!(
+            deserialized.serialize(),
+            "INSERT INTO table (name, nickname, age) VALUES (Alen, 'null', null)"
+        );
+        assert_eq!(
+            Insert::deserialize(&deserialized.serialize()).unwrap(),
+            deserialized
+        );
+    }
+
+    #[test]
+    fn deserialize_insert_with_commas_and_quotes_inside_a_text() {
+        let s = "INSERT INTO table (city, note) VALUES ('Buenos Aires, AR', 'O''Hare,  gate 3')";
+        let deserialized = Insert::deserialize(s).unwrap();
+
+        assert_eq!(
+            deserialized.values,
+            vec!["Buenos Aires, AR", "O'Hare,  gate 3"]
+        );
+        assert_eq!(
+            Insert::deserialize(&deserialized.serialize()).unwrap(),
+            deserialized
+        );
+    }
+
+    #[test]
+    fn deserialize_insert_with_a_text_that_reads_as_the_null_marker() {
+        let s = r"INSERT INTO table (name, nickname, note) VALUES ('\N', \\N, null)";
+        let deserialized = Insert::deserialize(s).unwrap();
+
+        assert_eq!(deserialized.values, vec![r"\\N", r"\\\N", NULL_VALUE]);
+        assert_ne!(deserialized.values[0], NULL_VALUE);
+        assert_eq!(unescape_literal(&deserialized.values[0]), r"\N");
+        assert_eq!(unescape_literal(&deserialized.values[1]), r"\\N");
+        assert_eq!(unescape_literal(NULL_VALUE), NULL_VALUE);
+        assert_eq!(
+            Insert::deserialize(&deserialized.serialize()).unwrap(),
+            deserialized
+        );
+    }
 }
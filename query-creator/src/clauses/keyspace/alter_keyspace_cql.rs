@@ -27,19 +27,13 @@ impl AlterKeyspace {
     /// - The replication class must be `SimpleStrategy`.
     /// - The replication factor must be a valid unsigned integer.
     pub fn new_from_tokens(query: Vec<String>) -> Result<Self, CQLError> {
-        if query.len() < 10
-            || query[0].to_uppercase() != "ALTER"
-            || query[1].to_uppercase() != "KEYSPACE"
-        {
+        if query.len() < 10 || query[0] != "ALTER" || query[1] != "KEYSPACE" {
             return Err(CQLError::InvalidSyntax);
         }
 
         let keyspace_name = query[2].to_string();
 
-        if query[3].to_uppercase() != "WITH"
-            || query[4].to_uppercase() != "REPLICATION"
-            || query[5] != "="
-        {
+        if query[3] != "WITH" || query[4] != "REPLICATION" || query[5] != "=" {
             return Err(CQLError::InvalidSyntax);
         }
 
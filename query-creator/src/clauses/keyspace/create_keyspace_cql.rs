@@ -42,31 +42,25 @@ impl CreateKeyspace {
     /// - The replication class must be `SimpleStrategy`.
     /// - The replication factor must be a valid unsigned integer.
     pub fn new_from_tokens(query: Vec<String>) -> Result<Self, CQLError> {
-        if query.len() < 10
-            || query[0].to_uppercase() != "CREATE"
-            || query[1].to_uppercase() != "KEYSPACE"
-        {
+        if query.len() < 10 || query[0] != "CREATE" || query[1] != "KEYSPACE" {
             return Err(CQLError::InvalidSyntax);
         }
 
         // Check for IF NOT EXISTS
         let mut index = 2;
-        let if_not_exists_clause = if query.len() > 3
-            && query[2].to_uppercase() == "IF"
-            && query[3].to_uppercase() == "NOT"
-            && query[4].to_uppercase() == "EXISTS"
-        {
-            index += 3; // Skip the "IF NOT EXISTS" part
-            true
-        } else {
-            index += 0; // No change in index
-            false
-        };
+        let if_not_exists_clause =
+            if query.len() > 3 && query[2] == "IF" && query[3] == "NOT" && query[4] == "EXISTS" {
+                index += 3; // Skip the "IF NOT EXISTS" part
+                true
+            } else {
+                index += 0; // No change in index
+                false
+            };
 
         let keyspace_name = query[index].to_string();
 
-        if query[index + 1].to_uppercase() != "WITH"
-            || query[index + 2].to_uppercase() != "REPLICATION"
+        if query[index + 1] != "WITH"
+            || query[index + 2] != "REPLICATION"
             || query[index + 3] != "="
         {
             return Err(CQLError::InvalidSyntax);
@@ -209,7 +203,7 @@ mod tests {
             "KEYSPACE".to_string(),
             "example".to_string(),
             "WITH".to_string(),
-            "replication".to_string(),
+            "REPLICATION".to_string(),
             "=".to_string(),
             "{".to_string(),
             "class".to_string(),
@@ -281,7 +275,7 @@ mod tests {
             "EXISTS".to_string(),
             "example".to_string(),
             "WITH".to_string(),
-            "replication".to_string(),
+            "REPLICATION".to_string(),
             "=".to_string(),
             "{".to_string(),
             "class".to_string(),
@@ -32,10 +32,7 @@ impl DropKeyspace {
     /// - The query must begin with `DROP KEYSPACE`.
     ///
     pub fn new_from_tokens(query: Vec<String>) -> Result<Self, CQLError> {
-        if query.len() != 3
-            || query[0].to_uppercase() != "DROP"
-            || query[1].to_uppercase() != "KEYSPACE"
-        {
+        if query.len() != 3 || query[0] != "DROP" || query[1] != "KEYSPACE" {
             return Err(CQLError::InvalidSyntax);
         }
 
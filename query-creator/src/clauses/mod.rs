@@ -1,5 +1,7 @@
+pub mod batch_cql;
 pub mod condition;
 pub mod delete_cql;
+pub mod explain_cql;
 pub mod if_cql;
 pub mod insert_cql;
 pub mod into_cql;
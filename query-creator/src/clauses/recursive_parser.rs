@@ -133,6 +133,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn not_equal_and_contains_conditions() {
+        let tokens = vec![
+            "status",
+            "!=",
+            "cancelled",
+            "AND",
+            "tags",
+            "CONTAINS",
+            "delayed",
+        ];
+        let mut pos = 0;
+        let condition = parse_condition(&tokens, &mut pos).unwrap();
+        assert_eq!(
+            condition,
+            Condition::Complex {
+                left: Some(Box::new(Condition::Simple {
+                    field: String::from("status"),
+                    operator: Operator::NotEqual,
+                    value: String::from("cancelled"),
+                })),
+                operator: LogicalOperator::And,
+                right: Box::new(Condition::Simple {
+                    field: String::from("tags"),
+                    operator: Operator::Contains,
+                    value: String::from("delayed"),
+                }),
+            }
+        );
+
+        let tokens = vec!["crew", "CONTAINS", "KEY", "pilot", "OR", "seats", "!=", "0"];
+        let mut pos = 0;
+        let condition = parse_condition(&tokens, &mut pos).unwrap();
+        assert_eq!(
+            condition,
+            Condition::Complex {
+                left: Some(Box::new(Condition::Simple {
+                    field: String::from("crew"),
+                    operator: Operator::ContainsKey,
+                    value: String::from("pilot"),
+                })),
+                operator: LogicalOperator::Or,
+                right: Box::new(Condition::Simple {
+                    field: String::from("seats"),
+                    operator: Operator::NotEqual,
+                    value: String::from("0"),
+                }),
+            }
+        );
+    }
+
     #[test]
     fn not() {
         let tokens = vec!["NOT", "city", "=", "Gaiman"];
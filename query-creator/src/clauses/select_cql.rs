@@ -2,7 +2,10 @@ use super::{order_by_cql::OrderBy, where_cql::Where};
 use crate::QueryCreator;
 use crate::{
     errors::CQLError,
-    utils::{is_by, is_from, is_limit, is_order, is_select, is_where},
+    utils::{
+        is_allow_filtering, is_by, is_distinct, is_from, is_group_by, is_limit, is_order,
+        is_per_partition_limit, is_select, is_where,
+    },
 };
 
 /// Struct that represents the `SELECT` SQL clause.
@@ -11,34 +14,109 @@ use crate::{
 /// # Fields
 ///
 /// * `table_name` - The name of the table to select data from.
+/// * `distinct` - Whether the query returns each partition key once (`SELECT DISTINCT`).
 /// * `columns` - The columns to select from the table.
 /// * `where_clause` - The `WHERE` clause to filter the result set.
+/// * `group_by` - The columns of the `GROUP BY`, empty if the query has none.
 /// * `orderby_clause` - The `ORDER BY` clause to sort the result set.
+/// * `per_partition_limit` - The maximum number of rows to return from each partition.
+/// * `limit` - The maximum number of rows to return.
+/// * `allow_filtering` - Whether the query may read every partition of the table.
 ///
 #[derive(Debug, PartialEq, Clone)]
 pub struct Select {
     pub table_name: String,
     pub keyspace_used_name: String,
+    /// Whether the query is a `SELECT DISTINCT`, which returns one row per partition.
+    pub distinct: bool,
     pub columns: Vec<String>,
     pub where_clause: Option<Where>,
+    /// Columns the rows are grouped by, a prefix of the primary key. Empty without `GROUP BY`.
+    pub group_by: Vec<String>,
     pub orderby_clause: Option<OrderBy>,
+    pub per_partition_limit: Option<usize>,
     pub limit: Option<usize>,
+    /// Whether the query ends with `ALLOW FILTERING`, which lets it read every partition.
+    pub allow_filtering: bool,
 }
 
-fn parse_columns<'a>(tokens: &'a [String], i: &mut usize) -> Result<Vec<&'a String>, CQLError> {
+/// Column of a `SELECT` that counts the rows of each group.
+pub const COUNT_ALL: &str = "COUNT(*)";
+
+/// Function of a `SELECT` that returns something about the cell of a column instead of its
+/// value. It is kept among the selected columns as `NAME(column)`, such as `WRITETIME(origin)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellFunction {
+    /// `WRITETIME(column)`: the timestamp of the write that stored the cell.
+    WriteTime,
+    /// `TTL(column)`: the seconds left until the cell expires.
+    Ttl,
+}
+
+impl CellFunction {
+    /// The function called `name`, in any case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name.eq_ignore_ascii_case("WRITETIME") {
+            Some(CellFunction::WriteTime)
+        } else if name.eq_ignore_ascii_case("TTL") {
+            Some(CellFunction::Ttl)
+        } else {
+            None
+        }
+    }
+
+    /// Name of the function, in uppercase.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CellFunction::WriteTime => "WRITETIME",
+            CellFunction::Ttl => "TTL",
+        }
+    }
+
+    /// The function and the column of a selected column such as `WRITETIME(origin)`, or `None`
+    /// if it is a plain column.
+    pub fn of_column(selected: &str) -> Option<(Self, &str)> {
+        let (name, rest) = selected.split_once('(')?;
+        let column = rest.strip_suffix(')')?;
+        Some((Self::from_name(name)?, column))
+    }
+}
+
+// Devuelve si la consulta es `DISTINCT` y las columnas pedidas
+fn parse_columns(tokens: &[String], i: &mut usize) -> Result<(bool, Vec<String>), CQLError> {
     let mut columns = Vec::new();
+    let mut distinct = false;
     if is_select(&tokens[*i]) {
         if *i < tokens.len() {
             *i += 1;
+            // `SELECT distinct FROM t` pide una columna que se llama asi
+            if tokens.get(*i).is_some_and(|token| is_distinct(token))
+                && tokens.get(*i + 1).is_some_and(|token| !is_from(token))
+            {
+                distinct = true;
+                *i += 1;
+            }
             while !is_from(&tokens[*i]) && *i < tokens.len() {
-                columns.push(&tokens[*i]);
+                // Los parentesis no llegan como tokens: `COUNT(*)` es `COUNT` y `*`
+                if tokens[*i] == "COUNT" && tokens.get(*i + 1).is_some_and(|token| token == "*") {
+                    columns.push(COUNT_ALL.to_string());
+                    *i += 2;
+                    continue;
+                }
+                // `WRITETIME(col)` y `TTL(col)` llegan como un solo token
+                match CellFunction::of_column(&tokens[*i]) {
+                    Some((function, column)) => {
+                        columns.push(format!("{}({})", function.name(), column))
+                    }
+                    None => columns.push(tokens[*i].clone()),
+                }
                 *i += 1;
             }
         }
     } else {
         return Err(CQLError::InvalidSyntax);
     }
-    Ok(columns)
+    Ok((distinct, columns))
 }
 
 fn parse_table_name(tokens: &[String], i: &mut usize) -> Result<String, CQLError> {
@@ -53,30 +131,69 @@ fn parse_table_name(tokens: &[String], i: &mut usize) -> Result<String, CQLError
 }
 
 type Tokens<'a> = Vec<&'a str>;
-type ParsedResult<'a> = Result<(Tokens<'a>, Tokens<'a>, Option<usize>), CQLError>;
+type ParsedResult<'a> = Result<
+    (
+        Tokens<'a>,
+        Vec<String>,
+        Tokens<'a>,
+        Option<usize>,
+        Option<usize>,
+    ),
+    CQLError,
+>;
+
+// `LIMIT`, `PER PARTITION LIMIT` o `ALLOW FILTERING` terminan el `WHERE` y el `ORDER BY`
+fn is_any_limit(tokens: &[String], i: usize) -> bool {
+    is_limit(&tokens[i]) || is_per_partition_limit(tokens, i) || is_allow_filtering(tokens, i)
+}
 
 fn parse_where_orderby_limit<'a>(tokens: &'a [String], i: &mut usize) -> ParsedResult<'a> {
     let mut where_tokens = Vec::new();
+    let mut group_by = Vec::new();
     let mut orderby_tokens = Vec::new();
+    let mut per_partition_limit = None;
     let mut limit = None;
 
     if *i < tokens.len() {
         if is_where(&tokens[*i]) {
-            while *i < tokens.len() && !is_order(&tokens[*i]) && !is_limit(&tokens[*i]) {
+            while *i < tokens.len()
+                && !is_group_by(tokens, *i)
+                && !is_order(&tokens[*i])
+                && !is_any_limit(tokens, *i)
+            {
                 where_tokens.push(tokens[*i].as_str());
                 *i += 1;
             }
         }
+        if is_group_by(tokens, *i) {
+            *i += 2;
+            while *i < tokens.len() && !is_order(&tokens[*i]) && !is_any_limit(tokens, *i) {
+                group_by.push(tokens[*i].clone());
+                *i += 1;
+            }
+            if group_by.is_empty() {
+                return Err(CQLError::InvalidSyntax);
+            }
+        }
         if *i < tokens.len() && is_order(&tokens[*i]) {
             orderby_tokens.push(tokens[*i].as_str());
             *i += 1;
             if *i < tokens.len() && is_by(&tokens[*i]) {
-                while *i < tokens.len() && !is_limit(&tokens[*i]) {
+                while *i < tokens.len() && !is_any_limit(tokens, *i) {
                     orderby_tokens.push(tokens[*i].as_str());
                     *i += 1;
                 }
             }
         }
+        if is_per_partition_limit(tokens, *i) {
+            *i += 3;
+            // A diferencia de `LIMIT`, un valor invalido es un error
+            per_partition_limit = match tokens.get(*i).map(|token| token.parse::<usize>()) {
+                Some(Ok(n)) if n > 0 => Some(n),
+                _ => return Err(CQLError::InvalidSyntax),
+            };
+            *i += 1;
+        }
         if *i < tokens.len() && is_limit(&tokens[*i]) {
             *i += 1;
             if *i < tokens.len() {
@@ -85,7 +202,13 @@ fn parse_where_orderby_limit<'a>(tokens: &'a [String], i: &mut usize) -> ParsedR
             }
         }
     }
-    Ok((where_tokens, orderby_tokens, limit))
+    Ok((
+        where_tokens,
+        group_by,
+        orderby_tokens,
+        per_partition_limit,
+        limit,
+    ))
 }
 
 impl Select {
@@ -103,8 +226,10 @@ impl Select {
     ///
     /// # Notes
     /// - The expected token order is:
-    ///   `"SELECT", "columns", "FROM", "table_name", "[WHERE condition]", "[ORDER BY columns order]", "[LIMIT number]"`.
-    /// - The `columns` should be comma-separated.
+    ///   `"SELECT", "columns", "FROM", "table_name", "[WHERE condition]", "[GROUP BY columns]", "[ORDER BY columns order]", "[PER PARTITION LIMIT number]", "[LIMIT number]", "[ALLOW FILTERING]"`.
+    /// - The `columns` should be comma-separated. `COUNT(*)` is kept as the column [`COUNT_ALL`].
+    ///   `WRITETIME(column)` and `TTL(column)` are kept with the name of the function in
+    ///   uppercase (see [`CellFunction`]).
     pub fn new_from_tokens(tokens: Vec<String>) -> Result<Self, CQLError> {
         if tokens.len() < 4 {
             return Err(CQLError::InvalidSyntax);
@@ -112,7 +237,7 @@ impl Select {
 
         let mut i = 0;
 
-        let columns = parse_columns(&tokens, &mut i)?;
+        let (distinct, columns) = parse_columns(&tokens, &mut i)?;
         let full_table_name = parse_table_name(&tokens, &mut i)?;
 
         let (keyspace_used_name, table_name) = if full_table_name.contains('.') {
@@ -126,7 +251,9 @@ impl Select {
             return Err(CQLError::InvalidSyntax);
         }
 
-        let (where_tokens, orderby_tokens, limit) = parse_where_orderby_limit(&tokens, &mut i)?;
+        let (where_tokens, group_by, orderby_tokens, per_partition_limit, limit) =
+            parse_where_orderby_limit(&tokens, &mut i)?;
+        let allow_filtering = is_allow_filtering(&tokens, i);
 
         let where_clause = if !where_tokens.is_empty() {
             Some(Where::new_from_tokens(where_tokens)?)
@@ -145,10 +272,14 @@ impl Select {
         Ok(Self {
             table_name,
             keyspace_used_name,
-            columns: columns.iter().map(|c| c.to_string()).collect(),
+            distinct,
+            columns,
             where_clause,
+            group_by,
             orderby_clause,
+            per_partition_limit,
             limit,
+            allow_filtering,
         })
     }
 
@@ -158,7 +289,7 @@ impl Select {
     /// - `String`:
     ///   - A string representation of the `SELECT` query in the following format:
     ///     ```sql
-    ///     SELECT columns FROM [keyspace.]table_name [WHERE condition] [ORDER BY columns order] [LIMIT number];
+    ///     SELECT [DISTINCT] columns FROM [keyspace.]table_name [WHERE condition] [GROUP BY columns] [ORDER BY columns order] [PER PARTITION LIMIT number] [LIMIT number] [ALLOW FILTERING];
     ///    
     pub fn serialize(&self) -> String {
         let table_name_str = if !self.keyspace_used_name.is_empty() {
@@ -166,22 +297,40 @@ impl Select {
         } else {
             self.table_name.clone()
         };
-        let mut result = format!("SELECT {} FROM {}", self.columns.join(","), table_name_str);
+        let distinct = if self.distinct { "DISTINCT " } else { "" };
+        let mut result = format!(
+            "SELECT {}{} FROM {}",
+            distinct,
+            self.columns.join(","),
+            table_name_str
+        );
 
         // Agrega el `WHERE` si existe
         if let Some(where_clause) = &self.where_clause {
             result.push_str(&format!(" WHERE {}", where_clause.serialize()));
         }
 
+        if !self.group_by.is_empty() {
+            result.push_str(&format!(" GROUP BY {}", self.group_by.join(",")));
+        }
+
         // Agrega el `ORDER BY` si existe
         if let Some(orderby_clause) = &self.orderby_clause {
             result.push_str(&format!(" ORDER BY {}", orderby_clause.serialize()));
         }
 
+        if let Some(per_partition_limit) = &self.per_partition_limit {
+            result.push_str(&format!(" PER PARTITION LIMIT {}", per_partition_limit));
+        }
+
         // Agrega el `LIMIT` si existe
         if let Some(limit) = &self.limit {
             result.push_str(&format!(" LIMIT {}", limit));
         }
+
+        if self.allow_filtering {
+            result.push_str(" ALLOW FILTERING");
+        }
         result
     }
 
@@ -201,6 +350,142 @@ impl Select {
         Self::new_from_tokens(tokens)
     }
 
+    /// Whether the query reads every partition of the table: it has no `WHERE`, or the `WHERE`
+    /// does not set each column of `partition_keys` with `=`.
+    pub fn is_full_scan(&self, partition_keys: &[String]) -> bool {
+        match &self.where_clause {
+            Some(where_clause) => where_clause
+                .get_value_partitioner_key_condition(partition_keys.to_vec())
+                .map_or(true, |values| values.len() != partition_keys.len()),
+            None => true,
+        }
+    }
+
+    /// Most rows each partition may return: 1 for a `SELECT DISTINCT`, otherwise the
+    /// `PER PARTITION LIMIT`, if there is one.
+    pub fn rows_per_partition_limit(&self) -> Option<usize> {
+        if self.distinct {
+            Some(1)
+        } else {
+            self.per_partition_limit
+        }
+    }
+
+    /// Checks that a `SELECT DISTINCT` requests every column of `partition_keys` and nothing
+    /// else, as Cassandra does. Any other query is valid.
+    ///
+    /// # Errors
+    /// - `CQLError::InvalidDistinct` naming the first column that is not a partition key, or
+    ///   the first partition key that is missing.
+    pub fn check_distinct_columns(&self, partition_keys: &[String]) -> Result<(), CQLError> {
+        if !self.distinct {
+            return Ok(());
+        }
+
+        if let Some(column) = self
+            .columns
+            .iter()
+            .find(|column| !partition_keys.contains(column))
+        {
+            return Err(CQLError::InvalidDistinct(format!(
+                "SELECT DISTINCT queries must only request partition key columns (not {})",
+                column
+            )));
+        }
+        match partition_keys
+            .iter()
+            .find(|key| !self.columns.contains(key))
+        {
+            Some(missing) => Err(CQLError::InvalidDistinct(format!(
+                "SELECT DISTINCT queries must request all the partition key columns (missing {})",
+                missing
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether the query returns one row per group of rows instead of the rows themselves.
+    pub fn is_grouped(&self) -> bool {
+        !self.group_by.is_empty()
+    }
+
+    /// Checks that the `GROUP BY` columns are a prefix of the primary key, the `partition_keys`
+    /// followed by the `clustering_columns` in order, as Cassandra does, that covers the whole
+    /// partition key. So every group is inside a single partition, and the nodes that answer
+    /// for different partitions never answer for the same group. An aggregate such as
+    /// `COUNT(*)` is only accepted with a `GROUP BY`.
+    ///
+    /// # Errors
+    /// - `CQLError::InvalidGroupBy` naming the first column out of place, if only a part of the
+    ///   partition key is grouped by, or if an aggregate is requested without a `GROUP BY`.
+    pub fn check_group_by_columns(
+        &self,
+        partition_keys: &[String],
+        clustering_columns: &[String],
+    ) -> Result<(), CQLError> {
+        if !self.is_grouped() {
+            if self.columns.iter().any(|column| column == COUNT_ALL) {
+                return Err(CQLError::InvalidGroupBy(format!(
+                    "{} is only supported with a GROUP BY",
+                    COUNT_ALL
+                )));
+            }
+            return Ok(());
+        }
+
+        let mut primary_key = partition_keys.iter().chain(clustering_columns);
+        for column in &self.group_by {
+            if primary_key.next() != Some(column) {
+                return Err(CQLError::InvalidGroupBy(format!(
+                    "Group by currently only support groups of columns following their declared order in the PRIMARY KEY (not {})",
+                    column
+                )));
+            }
+        }
+        if self.group_by.len() < partition_keys.len() {
+            return Err(CQLError::InvalidGroupBy(
+                "Group by is not supported on only a part of the partition key".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks the columns that `WRITETIME` and `TTL` are applied to: they have to be columns
+    /// of the table outside of `primary_key`, since the keys of a row are not cells, and they
+    /// cannot be combined with a `GROUP BY`.
+    ///
+    /// # Errors
+    /// - `CQLError::InvalidColumn` if a function is applied to a column the table does not have.
+    /// - `CQLError::InvalidSelection` naming the function and the column otherwise.
+    pub fn check_cell_functions(
+        &self,
+        columns: &[String],
+        primary_key: &[String],
+    ) -> Result<(), CQLError> {
+        for selected in &self.columns {
+            let Some((function, column)) = CellFunction::of_column(selected) else {
+                continue;
+            };
+            if !columns.iter().any(|name| name == column) {
+                return Err(CQLError::InvalidColumn);
+            }
+            if primary_key.iter().any(|name| name == column) {
+                return Err(CQLError::InvalidSelection(format!(
+                    "Cannot use selection function {} on PRIMARY KEY part {}",
+                    function.name(),
+                    column
+                )));
+            }
+            if self.is_grouped() {
+                return Err(CQLError::InvalidSelection(format!(
+                    "{} is not supported with a GROUP BY",
+                    function.name()
+                )));
+            }
+        }
+        Ok(())
+    }
+
     /// Validates the `ORDER BY` clause in the `Select` query.
     ///
     /// # Parameters
@@ -239,7 +524,7 @@ impl Select {
 #[cfg(test)]
 mod tests {
 
-    use super::Select;
+    use super::{CellFunction, Select, COUNT_ALL};
     use crate::{
         clauses::{condition::Condition, order_by_cql::OrderBy},
         errors::CQLError,
@@ -308,7 +593,7 @@ mod tests {
             String::from("col"),
             String::from("FROM"),
             String::from("table"),
-            String::from("Limit"),
+            String::from("LIMIT"),
             String::from("10"),
         ];
         let select = Select::new_from_tokens(tokens).unwrap();
@@ -387,7 +672,7 @@ mod tests {
             String::from("BY"),
             String::from("email"),
             String::from("ASC"),
-            String::from("Limit"),
+            String::from("LIMIT"),
             String::from("10"),
         ];
         let select = Select::new_from_tokens(tokens).unwrap();
@@ -414,4 +699,268 @@ mod tests {
         );
         assert_eq!(select.limit.unwrap(), 10)
     }
+
+    #[test]
+    fn new_with_per_partition_limit() {
+        let select = Select::deserialize(
+            "SELECT id, time FROM sky.positions WHERE id = 1 ORDER BY time DESC PER PARTITION LIMIT 3 LIMIT 10",
+        )
+        .unwrap();
+
+        assert_eq!(
+            select.where_clause.unwrap().condition,
+            Condition::Simple {
+                field: String::from("id"),
+                operator: Operator::Equal,
+                value: String::from("1"),
+            }
+        );
+        assert_eq!(
+            select.orderby_clause.unwrap(),
+            OrderBy {
+                columns: vec![String::from("time")],
+                order: String::from("DESC")
+            }
+        );
+        assert_eq!(select.per_partition_limit, Some(3));
+        assert_eq!(select.limit, Some(10));
+    }
+
+    #[test]
+    fn new_with_only_per_partition_limit() {
+        let select = Select::deserialize("SELECT id FROM positions per partition limit 2").unwrap();
+
+        assert_eq!(select.where_clause, None);
+        assert_eq!(select.per_partition_limit, Some(2));
+        assert_eq!(select.limit, None);
+    }
+
+    #[test]
+    fn new_with_invalid_per_partition_limit() {
+        for query in [
+            "SELECT id FROM positions PER PARTITION LIMIT",
+            "SELECT id FROM positions PER PARTITION LIMIT 0",
+            "SELECT id FROM positions PER PARTITION LIMIT many",
+        ] {
+            assert_eq!(
+                Select::deserialize(query),
+                Err(CQLError::InvalidSyntax),
+                "{}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn serialize_with_per_partition_limit() {
+        let select = Select::deserialize(
+            "SELECT id FROM sky.positions WHERE id = 1 PER PARTITION LIMIT 2 LIMIT 5",
+        )
+        .unwrap();
+
+        let serialized = select.serialize();
+
+        assert!(serialized.ends_with(" PER PARTITION LIMIT 2 LIMIT 5"));
+        assert_eq!(Select::deserialize(&serialized).unwrap(), select);
+    }
+
+    #[test]
+    fn allow_filtering_ends_the_where_and_is_serialized() {
+        let select =
+            Select::deserialize("SELECT id FROM flights WHERE origin = 'EZE' allow filtering")
+                .unwrap();
+
+        assert!(select.allow_filtering);
+        assert_eq!(
+            select.where_clause.as_ref().unwrap().condition,
+            Condition::Simple {
+                field: "origin".to_string(),
+                operator: Operator::Equal,
+                value: "EZE".to_string(),
+            }
+        );
+        assert_eq!(Select::deserialize(&select.serialize()).unwrap(), select);
+        assert!(
+            !Select::deserialize("SELECT id FROM flights")
+                .unwrap()
+                .allow_filtering
+        );
+    }
+
+    #[test]
+    fn distinct_is_parsed_and_serialized() {
+        let select = Select::deserialize("SELECT DISTINCT id FROM sky.flights").unwrap();
+        assert!(select.distinct);
+        assert_eq!(select.columns, ["id"]);
+        assert_eq!(select.rows_per_partition_limit(), Some(1));
+        assert_eq!(select.serialize(), "SELECT DISTINCT id FROM sky.flights");
+
+        let lowercase = Select::deserialize("select distinct id from sky.flights").unwrap();
+        assert!(lowercase.distinct);
+
+        // Una columna que se llama `distinct` no hace la consulta `DISTINCT`
+        let column = Select::deserialize("SELECT distinct FROM sky.flights").unwrap();
+        assert!(!column.distinct);
+        assert_eq!(column.columns, ["distinct"]);
+    }
+
+    #[test]
+    fn distinct_only_requests_every_partition_key() {
+        let partition_keys = ["airline".to_string(), "number".to_string()];
+
+        let select = Select::deserialize("SELECT DISTINCT airline, number FROM flights").unwrap();
+        assert!(select.check_distinct_columns(&partition_keys).is_ok());
+
+        let select =
+            Select::deserialize("SELECT DISTINCT airline, number, origin FROM flights").unwrap();
+        assert!(matches!(
+            select.check_distinct_columns(&partition_keys),
+            Err(CQLError::InvalidDistinct(message)) if message.contains("not origin")
+        ));
+
+        let select = Select::deserialize("SELECT DISTINCT airline FROM flights").unwrap();
+        assert!(matches!(
+            select.check_distinct_columns(&partition_keys),
+            Err(CQLError::InvalidDistinct(message)) if message.contains("missing number")
+        ));
+
+        let select = Select::deserialize("SELECT * FROM flights").unwrap();
+        assert!(select.check_distinct_columns(&partition_keys).is_ok());
+    }
+
+    #[test]
+    fn group_by_is_parsed_and_serialized() {
+        let select = Select::deserialize(
+            "SELECT origin, COUNT(*) FROM sky.flights WHERE airline = 'AR' GROUP BY airline, origin ORDER BY origin DESC LIMIT 3",
+        )
+        .unwrap();
+
+        assert_eq!(select.columns, ["origin", COUNT_ALL]);
+        assert_eq!(select.group_by, ["airline", "origin"]);
+        assert!(select.is_grouped());
+        assert_eq!(
+            select.where_clause.as_ref().unwrap().condition,
+            Condition::Simple {
+                field: "airline".to_string(),
+                operator: Operator::Equal,
+                value: "AR".to_string(),
+            }
+        );
+        assert_eq!(select.orderby_clause.as_ref().unwrap().columns, ["origin"]);
+        assert_eq!(select.limit, Some(3));
+
+        let grouped = Select::deserialize(
+            "SELECT airline, COUNT(*) FROM sky.flights GROUP BY airline LIMIT 2",
+        )
+        .unwrap();
+        assert_eq!(Select::deserialize(&grouped.serialize()).unwrap(), grouped);
+
+        let lowercase =
+            Select::deserialize("select origin, count(*) from flights group by origin").unwrap();
+        assert_eq!(lowercase.columns, ["origin", COUNT_ALL]);
+        assert_eq!(lowercase.group_by, ["origin"]);
+
+        assert!(!Select::deserialize("SELECT origin FROM flights")
+            .unwrap()
+            .is_grouped());
+        assert_eq!(
+            Select::deserialize("SELECT origin FROM flights GROUP BY"),
+            Err(CQLError::InvalidSyntax)
+        );
+    }
+
+    #[test]
+    fn group_by_only_accepts_a_prefix_of_the_primary_key() {
+        let partition_keys = ["airline".to_string()];
+        let clustering_columns = ["origin".to_string(), "departure".to_string()];
+        let check = |query: &str| {
+            Select::deserialize(query)
+                .unwrap()
+                .check_group_by_columns(&partition_keys, &clustering_columns)
+        };
+
+        assert!(check("SELECT airline, COUNT(*) FROM flights GROUP BY airline").is_ok());
+        assert!(check("SELECT COUNT(*) FROM flights GROUP BY airline, origin").is_ok());
+        assert!(matches!(
+            check("SELECT origin, COUNT(*) FROM flights GROUP BY origin"),
+            Err(CQLError::InvalidGroupBy(message)) if message.contains("not origin")
+        ));
+        assert!(matches!(
+            check("SELECT COUNT(*) FROM flights GROUP BY airline, departure"),
+            Err(CQLError::InvalidGroupBy(message)) if message.contains("not departure")
+        ));
+        assert!(matches!(
+            check("SELECT COUNT(*) FROM flights"),
+            Err(CQLError::InvalidGroupBy(_))
+        ));
+        assert!(check("SELECT * FROM flights").is_ok());
+    }
+
+    #[test]
+    fn group_by_has_to_cover_the_whole_partition_key() {
+        let partition_keys = ["airline".to_string(), "origin".to_string()];
+        let check = |query: &str| {
+            Select::deserialize(query)
+                .unwrap()
+                .check_group_by_columns(&partition_keys, &["departure".to_string()])
+        };
+
+        assert!(matches!(
+            check("SELECT COUNT(*) FROM flights GROUP BY airline"),
+            Err(CQLError::InvalidGroupBy(message)) if message.contains("part of the partition key")
+        ));
+        assert!(check("SELECT COUNT(*) FROM flights GROUP BY airline, origin").is_ok());
+        assert!(check("SELECT COUNT(*) FROM flights GROUP BY airline, origin, departure").is_ok());
+    }
+
+    #[test]
+    fn writetime_and_ttl_are_parsed_and_serialized() {
+        let select = Select::deserialize(
+            "select id, writetime(origin), TTL( origin ) from sky.flights where id = 1",
+        )
+        .unwrap();
+
+        assert_eq!(select.columns, ["id", "WRITETIME(origin)", "TTL(origin)"]);
+        assert_eq!(
+            CellFunction::of_column(&select.columns[1]),
+            Some((CellFunction::WriteTime, "origin"))
+        );
+        assert_eq!(
+            CellFunction::of_column(&select.columns[2]),
+            Some((CellFunction::Ttl, "origin"))
+        );
+        assert_eq!(CellFunction::of_column("origin"), None);
+        assert_eq!(Select::deserialize(&select.serialize()).unwrap(), select);
+
+        // Sin parentesis son columnas que se llaman asi
+        let plain = Select::deserialize("SELECT ttl, writetime FROM flights").unwrap();
+        assert_eq!(plain.columns, ["ttl", "writetime"]);
+        assert_eq!(CellFunction::of_column(&plain.columns[0]), None);
+    }
+
+    #[test]
+    fn writetime_and_ttl_only_accept_regular_columns() {
+        let columns = ["id".to_string(), "origin".to_string(), "seats".to_string()];
+        let primary_key = ["id".to_string()];
+        let check = |query: &str| {
+            Select::deserialize(query)
+                .unwrap()
+                .check_cell_functions(&columns, &primary_key)
+        };
+
+        assert!(check("SELECT WRITETIME(origin), TTL(seats) FROM flights WHERE id = 1").is_ok());
+        assert!(check("SELECT id, origin FROM flights").is_ok());
+        assert!(matches!(
+            check("SELECT WRITETIME(id) FROM flights"),
+            Err(CQLError::InvalidSelection(message)) if message.contains("PRIMARY KEY part id")
+        ));
+        assert_eq!(
+            check("SELECT TTL(destination) FROM flights"),
+            Err(CQLError::InvalidColumn)
+        );
+        assert!(matches!(
+            check("SELECT WRITETIME(origin) FROM flights GROUP BY id"),
+            Err(CQLError::InvalidSelection(_))
+        ));
+    }
 }
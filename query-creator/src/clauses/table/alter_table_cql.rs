@@ -84,10 +84,7 @@ impl AlterTable {
     /// - The query must begin with `ALTER TABLE`.
     /// - Operations supported include `ADD`, `DROP`, `MODIFY`, and `RENAME`.
     pub fn new_from_tokens(query: Vec<String>) -> Result<AlterTable, CQLError> {
-        if query.len() < 4
-            || query[0].to_uppercase() != "ALTER"
-            || query[1].to_uppercase() != "TABLE"
-        {
+        if query.len() < 4 || query[0] != "ALTER" || query[1] != "TABLE" {
             return Err(CQLError::InvalidSyntax);
         }
 
@@ -105,11 +102,11 @@ impl AlterTable {
         let mut i = 0;
 
         while i < operations.len() {
-            match operations[i].to_uppercase().as_str() {
+            match operations[i].as_str() {
                 "ADD" => {
                     // Soporte para omitir "COLUMN"
                     let mut offset = 1;
-                    if i + 2 < operations.len() && operations[i + 1].to_uppercase() == "COLUMN" {
+                    if i + 2 < operations.len() && operations[i + 1] == "COLUMN" {
                         offset = 2;
                     }
 
@@ -118,13 +115,15 @@ impl AlterTable {
                     }
 
                     let col_name = operations[i + offset].to_string();
+                    if !is_valid_column_name(&col_name) {
+                        return Err(CQLError::InvalidColumn);
+                    }
                     let col_type = DataType::from_str(&operations[i + offset + 1])?;
 
                     let allows_null = if operations.len() > i + offset + 2
-                        && operations[i + offset + 2].to_uppercase() == "NOT"
+                        && operations[i + offset + 2] == "NOT"
                     {
-                        if operations.len() < i + offset + 4
-                            || operations[i + offset + 3].to_uppercase() != "NULL"
+                        if operations.len() < i + offset + 4 || operations[i + offset + 3] != "NULL"
                         {
                             return Err(CQLError::InvalidSyntax);
                         }
@@ -150,10 +149,8 @@ impl AlterTable {
                     let col_name = operations[i + 1].to_string();
                     let col_type = DataType::from_str(&operations[i + 2])?;
 
-                    let allows_null = if operations.len() > i + 3
-                        && operations[i + 3].to_uppercase() == "NOT"
-                    {
-                        if operations.len() < i + 5 || operations[i + 4].to_uppercase() != "NULL" {
+                    let allows_null = if operations.len() > i + 3 && operations[i + 3] == "NOT" {
+                        if operations.len() < i + 5 || operations[i + 4] != "NULL" {
                             return Err(CQLError::InvalidSyntax);
                         }
                         false
@@ -249,6 +246,13 @@ impl PartialEq for AlterTable {
     }
 }
 
+// Un nombre de columna empieza con una letra y sigue con letras, numeros o `_`
+fn is_valid_column_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -278,6 +282,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_alter_table_add_column_rejects_invalid_names_and_types() {
+        let tokens = |column: &str, data_type: &str| {
+            ["ALTER", "TABLE", "sky.airports", "ADD", column, data_type]
+                .iter()
+                .map(|token| token.to_string())
+                .collect::<Vec<String>>()
+        };
+
+        assert!(matches!(
+            AlterTable::new_from_tokens(tokens("1st_gate", "INT")),
+            Err(CQLError::InvalidColumn)
+        ));
+        assert!(matches!(
+            AlterTable::new_from_tokens(tokens("gate-b", "INT")),
+            Err(CQLError::InvalidColumn)
+        ));
+        assert!(AlterTable::new_from_tokens(tokens("gate", "NUMBER")).is_err());
+        assert!(AlterTable::new_from_tokens(tokens("gate_2", "INT")).is_ok());
+    }
+
     #[test]
     fn test_alter_table_serialize() {
         let operations = vec![AlterTableOperation::AddColumn(Column::new(
@@ -41,10 +41,10 @@ impl CreateTable {
     ///
     /// # Returns
     /// - `Ok(())` if the column is successfully added.
-    /// - `Err(CQLError::InvalidColumn)` if a column with the same name already exists.
+    /// - `Err(CQLError::ColumnAlreadyExist)` if a column with the same name already exists.
     pub fn add_column(&mut self, column: Column) -> Result<(), CQLError> {
         if self.columns.iter().any(|col| col.name == column.name) {
-            return Err(CQLError::InvalidColumn);
+            return Err(CQLError::ColumnAlreadyExist);
         }
         self.columns.push(column);
         Ok(())
@@ -352,10 +352,6 @@ impl CreateTable {
             // Identificar las columnas de clave primaria y órdenes de clustering
             if col.is_partition_key {
                 partition_key_cols.push(col.name.clone());
-                // Si hay una sola partition key sin clustering columns, agregar PRIMARY KEY aquí
-                if partition_key_cols.len() == 1 && clustering_key_cols.is_empty() {
-                    col_def.push_str(" PRIMARY KEY");
-                }
             } else if col.is_clustering_column {
                 clustering_key_cols.push(col.name.clone());
                 if !col.clustering_order.is_empty() {
@@ -378,17 +374,19 @@ impl CreateTable {
             }
         }
 
-        // Construir la definición de la clave primaria si hay clustering columns
-        let primary_key =
-            if !partition_key_cols.is_empty() && !ordered_clustering_key_cols.is_empty() {
-                format!(
-                    "PRIMARY KEY (({}), {})",
-                    partition_key_cols.join(", "),
-                    ordered_clustering_key_cols.join(", ")
-                )
-            } else {
-                String::new()
-            };
+        // La clave primaria va siempre al final, asi se conservan las particiones compuestas
+        let primary_key = match (
+            partition_key_cols.is_empty(),
+            ordered_clustering_key_cols.is_empty(),
+        ) {
+            (true, _) => String::new(),
+            (false, true) => format!("PRIMARY KEY (({}))", partition_key_cols.join(", ")),
+            (false, false) => format!(
+                "PRIMARY KEY (({}), {})",
+                partition_key_cols.join(", "),
+                ordered_clustering_key_cols.join(", ")
+            ),
+        };
 
         // Añadir la definición de la Primary Key al final de la tabla si aplica
         if !primary_key.is_empty() {
@@ -661,4 +659,40 @@ mod tests {
             vec!["iata".to_string(), "name".to_string()]
         );
     }
+
+    #[test]
+    fn test_serialize_keeps_the_primary_key() {
+        for query in [
+            "CREATE TABLE ks.t (a INT, b TEXT, c INT, PRIMARY KEY ((a, b), c)) WITH CLUSTERING ORDER BY (c DESC)",
+            "CREATE TABLE ks.t (a INT, b TEXT, c INT, PRIMARY KEY ((a, b)))",
+            "CREATE TABLE IF NOT EXISTS t (a INT PRIMARY KEY, b TEXT)",
+        ] {
+            let table = CreateTable::deserialize(query).unwrap();
+            let serialized = CreateTable::deserialize(&table.serialize()).unwrap();
+
+            let keys = |table: &CreateTable| -> Vec<(String, bool, bool, String)> {
+                table
+                    .get_columns()
+                    .into_iter()
+                    .map(|column| {
+                        (
+                            column.name,
+                            column.is_partition_key,
+                            column.is_clustering_column,
+                            column.clustering_order,
+                        )
+                    })
+                    .collect()
+            };
+            assert_eq!(keys(&serialized), keys(&table), "{}", table.serialize());
+            assert_eq!(
+                serialized.get_clustering_column_in_order(),
+                table.get_clustering_column_in_order()
+            );
+            assert_eq!(
+                serialized.get_if_not_exists_clause(),
+                table.get_if_not_exists_clause()
+            );
+        }
+    }
 }
@@ -34,10 +34,7 @@ impl DropTable {
     /// - The query must contain exactly 3 tokens.
     /// - The query must begin with `DROP TABLE`.
     pub fn new_from_tokens(query: Vec<String>) -> Result<Self, CQLError> {
-        if query.len() != 3
-            || query[0].to_uppercase() != "DROP"
-            || query[1].to_uppercase() != "TABLE"
-        {
+        if query.len() != 3 || query[0] != "DROP" || query[1] != "TABLE" {
             return Err(CQLError::InvalidSyntax);
         }
 
@@ -72,7 +72,7 @@ impl DataType {
         }
     }
 
-    /// Compares two values (as strings) of the current `DataType` with a specified operator (e.g., `=`, `>`, `<`).
+    /// Compares two values (as strings) of the current `DataType` with a specified operator (e.g., `=`, `>`, `<`, `!=`).
     ///
     /// # Arguments
     ///
@@ -84,6 +84,8 @@ impl DataType {
     ///
     /// A `Result<bool, CQLError>`, where `Ok(true)` or `Ok(false)` indicates whether the comparison is true or false,
     /// and `Err(CQLError::InvalidCondition)` indicates that the values could not be parsed for comparison.
+    /// `CONTAINS` and `CONTAINS KEY` also return `Err(CQLError::InvalidCondition)`: they only apply to
+    /// collection columns, and none of these types is a collection.
     pub fn compare(&self, x: &str, y: &str, operator: &Operator) -> Result<bool, CQLError> {
         match self {
             DataType::Int => {
@@ -93,6 +95,8 @@ impl DataType {
                     Operator::Equal => Ok(x == y),
                     Operator::Greater => Ok(x > y),
                     Operator::Lesser => Ok(x < y),
+                    Operator::NotEqual => Ok(x != y),
+                    Operator::Contains | Operator::ContainsKey => Err(CQLError::InvalidCondition),
                 }
             }
             DataType::String => {
@@ -106,6 +110,8 @@ impl DataType {
                     Operator::Equal => Ok(x == y),
                     Operator::Greater => Ok(x > y),
                     Operator::Lesser => Ok(x < y),
+                    Operator::NotEqual => Ok(x != y),
+                    Operator::Contains | Operator::ContainsKey => Err(CQLError::InvalidCondition),
                 }
             }
             DataType::Boolean => {
@@ -115,6 +121,8 @@ impl DataType {
                     Operator::Equal => Ok(x == y),
                     Operator::Greater => Ok(x & !y),
                     Operator::Lesser => Ok(!x & y),
+                    Operator::NotEqual => Ok(x != y),
+                    Operator::Contains | Operator::ContainsKey => Err(CQLError::InvalidCondition),
                 }
             }
             DataType::Float => {
@@ -124,6 +132,8 @@ impl DataType {
                     Operator::Equal => Ok(x == y),
                     Operator::Greater => Ok(x > y),
                     Operator::Lesser => Ok(x < y),
+                    Operator::NotEqual => Ok(x != y),
+                    Operator::Contains | Operator::ContainsKey => Err(CQLError::InvalidCondition),
                 }
             }
             DataType::Double => {
@@ -133,6 +143,8 @@ impl DataType {
                     Operator::Equal => Ok(x == y),
                     Operator::Greater => Ok(x > y),
                     Operator::Lesser => Ok(x < y),
+                    Operator::NotEqual => Ok(x != y),
+                    Operator::Contains | Operator::ContainsKey => Err(CQLError::InvalidCondition),
                 }
             }
             DataType::Timestamp => {
@@ -142,6 +154,8 @@ impl DataType {
                     Operator::Equal => Ok(x == y),
                     Operator::Greater => Ok(x > y),
                     Operator::Lesser => Ok(x < y),
+                    Operator::NotEqual => Ok(x != y),
+                    Operator::Contains | Operator::ContainsKey => Err(CQLError::InvalidCondition),
                 }
             }
             DataType::Uuid => {
@@ -151,6 +165,8 @@ impl DataType {
                     Operator::Equal => Ok(x == y),
                     Operator::Greater => Ok(x > y),
                     Operator::Lesser => Ok(x < y),
+                    Operator::NotEqual => Ok(x != y),
+                    Operator::Contains | Operator::ContainsKey => Err(CQLError::InvalidCondition),
                 }
             }
         }
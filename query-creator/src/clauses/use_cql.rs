@@ -25,7 +25,7 @@ impl Use {
     /// * `Ok(Use)` - A successfully parsed `Use` struct.
     /// * `Err(CQLError::InvalidSyntax)` - If the tokens are invalid or improperly formatted.
     pub fn new_from_tokens(tokens: Vec<String>) -> Result<Self, CQLError> {
-        if tokens.len() != 2 || tokens[0].to_uppercase() != "USE" {
+        if tokens.len() != 2 || tokens[0] != "USE" {
             return Err(CQLError::InvalidSyntax);
         }
 
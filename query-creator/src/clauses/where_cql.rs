@@ -121,6 +121,85 @@ impl Where {
         Ok(())
     }
 
+    /// Validates that the clustering columns restricted by the `WHERE` clause form a prefix
+    /// of the clustering key, as Cassandra requires for reads that are not `ALLOW FILTERING`.
+    ///
+    /// A clustering column can only be restricted if every column before it is restricted
+    /// with `=`; the last restricted column may use any other operator (e.g. a range).
+    ///
+    /// # Arguments
+    ///
+    /// * `clustering_columns_in_order` - The clustering columns of the table, in the order of
+    ///   the clustering key.
+    ///
+    /// # Examples
+    ///
+    /// With the clustering key `(c1, c2, c3)`:
+    /// ```sql
+    /// WHERE id = 1 AND c1 = 2                  // Valid
+    /// WHERE id = 1 AND c1 = 2 AND c2 > 3       // Valid
+    /// WHERE id = 1 AND c2 = 3                  // Invalid: `c1` is not restricted
+    /// WHERE id = 1 AND c1 > 2 AND c2 = 3       // Invalid: `c1` is not restricted with `=`
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// - `CQLError::InvalidCondition` if the restricted clustering columns are not a prefix of
+    ///   the clustering key, or a column other than the last one is not restricted with `=`.
+    pub fn validate_clustering_prefix(
+        &self,
+        clustering_columns_in_order: &[String],
+    ) -> Result<(), CQLError> {
+        let mut operators: Vec<Vec<&Operator>> = vec![vec![]; clustering_columns_in_order.len()];
+        Self::collect_clustering_operators(
+            &self.condition,
+            clustering_columns_in_order,
+            &mut operators,
+        );
+
+        // Cantidad de columnas restringidas al principio de la clave
+        let prefix = operators.iter().take_while(|ops| !ops.is_empty()).count();
+        if operators[prefix..].iter().any(|ops| !ops.is_empty()) {
+            return Err(CQLError::InvalidCondition); // Se saltea una clustering column
+        }
+
+        // Solo la ultima columna del prefijo puede no compararse por igualdad
+        let only_equalities = |ops: &Vec<&Operator>| ops.iter().all(|op| **op == Operator::Equal);
+        if prefix > 1 && !operators[..prefix - 1].iter().all(only_equalities) {
+            return Err(CQLError::InvalidCondition);
+        }
+        Ok(())
+    }
+
+    fn collect_clustering_operators<'a>(
+        condition: &'a Condition,
+        clustering_columns_in_order: &[String],
+        operators: &mut Vec<Vec<&'a Operator>>,
+    ) {
+        match condition {
+            Condition::Simple {
+                field, operator, ..
+            } => {
+                if let Some(index) = clustering_columns_in_order
+                    .iter()
+                    .position(|column| column == field)
+                {
+                    operators[index].push(operator);
+                }
+            }
+            Condition::Complex { left, right, .. } => {
+                if let Some(left_condition) = left.as_ref() {
+                    Self::collect_clustering_operators(
+                        left_condition,
+                        clustering_columns_in_order,
+                        operators,
+                    );
+                }
+                Self::collect_clustering_operators(right, clustering_columns_in_order, operators);
+            }
+        }
+    }
+
     // Método recursivo para validar las condiciones de las claves primarias y de clustering.
     #[allow(clippy::too_many_arguments)]
     fn recursive_validate_conditions(
@@ -237,7 +316,7 @@ impl Where {
         &self,
         partitioner_keys: Vec<String>,
     ) -> Result<Vec<String>, CQLError> {
-        let mut result = vec![];
+        let mut conditions = vec![];
 
         match &self.condition {
             Condition::Simple {
@@ -247,7 +326,7 @@ impl Where {
             } => {
                 // Si es una condición simple y la clave está en partitioner_keys y el operador es `=`
                 if partitioner_keys.contains(field) && *operator == Operator::Equal {
-                    result.push(value.clone());
+                    conditions.push((field.clone(), value.clone()));
                 }
             }
             Condition::Complex { left, right, .. } => {
@@ -256,13 +335,25 @@ impl Where {
                     Self::collect_partitioner_key_values(
                         left_condition,
                         &partitioner_keys,
-                        &mut result,
+                        &mut conditions,
                     );
                 }
-                Self::collect_partitioner_key_values(right, &partitioner_keys, &mut result);
+                Self::collect_partitioner_key_values(right, &partitioner_keys, &mut conditions);
             }
         }
 
+        // Los valores van en el orden de las claves, no en el que aparecen en el WHERE,
+        // para que el token sea el mismo que el de la fila insertada
+        let result: Vec<String> = partitioner_keys
+            .iter()
+            .filter_map(|key| {
+                conditions
+                    .iter()
+                    .find(|(field, _)| field == key)
+                    .map(|(_, value)| value.clone())
+            })
+            .collect();
+
         if result.is_empty() {
             Err(CQLError::InvalidColumn)
         } else {
@@ -274,7 +365,7 @@ impl Where {
     fn collect_partitioner_key_values(
         condition: &Condition,
         partitioner_keys: &[String],
-        result: &mut Vec<String>,
+        result: &mut Vec<(String, String)>,
     ) {
         match condition {
             Condition::Simple {
@@ -284,7 +375,7 @@ impl Where {
             } => {
                 // Si la condición simple corresponde a una partitioner key
                 if partitioner_keys.contains(field) && *operator == Operator::Equal {
-                    result.push(value.clone());
+                    result.push((field.clone(), value.clone()));
                 }
             }
             Condition::Complex {
@@ -547,6 +638,48 @@ mod tests {
         );
     }
 
+    fn where_clause(conditions: &str) -> Where {
+        let tokens: Vec<&str> = conditions.split_whitespace().collect();
+        Where::new_from_tokens(tokens).unwrap()
+    }
+
+    #[test]
+    fn test_validate_clustering_prefix() {
+        let clustering_columns = vec!["c1".to_string(), "c2".to_string(), "c3".to_string()];
+
+        // Un prefijo de la clave de clustering
+        assert!(where_clause("WHERE id = 1 AND c1 = 2 AND c2 = 3")
+            .validate_clustering_prefix(&clustering_columns)
+            .is_ok());
+        assert!(where_clause("WHERE id = 1")
+            .validate_clustering_prefix(&clustering_columns)
+            .is_ok());
+
+        // Falta c1
+        assert_eq!(
+            where_clause("WHERE id = 1 AND c2 = 3").validate_clustering_prefix(&clustering_columns),
+            Err(CQLError::InvalidCondition)
+        );
+        assert_eq!(
+            where_clause("WHERE id = 1 AND c1 = 2 AND c3 = 4")
+                .validate_clustering_prefix(&clustering_columns),
+            Err(CQLError::InvalidCondition)
+        );
+
+        // Igualdad en c1 y rango en c2
+        assert!(
+            where_clause("WHERE id = 1 AND c1 = 2 AND c2 > 3 AND c2 < 9")
+                .validate_clustering_prefix(&clustering_columns)
+                .is_ok()
+        );
+        // Un rango en c1 no deja restringir c2
+        assert_eq!(
+            where_clause("WHERE id = 1 AND c1 > 2 AND c2 = 3")
+                .validate_clustering_prefix(&clustering_columns),
+            Err(CQLError::InvalidCondition)
+        );
+    }
+
     #[test]
     fn test_get_value_partitioner_key_condition_single_key() {
         let partitioner_keys = vec!["id".to_string()];
@@ -1,3 +1,4 @@
+use native_protocol::messages::error::Error;
 use std::fmt::Display;
 
 /// Enum representing the possible errors that can occur when processing SQL queries.
@@ -7,6 +8,19 @@ use std::fmt::Display;
 /// - `InvalidTable`: related to problems with the processing of tables.
 /// - `InvalidColumn`: related to problems with the processing of columns.
 /// - `InvalidSyntax`: related to problems with the processing of queries.
+/// - `KeyspaceDoesNotExist`: the query refers to a keyspace that does not exist.
+/// - `IncompatibleValues`: an `ALTER TABLE` changes the type of a column that has rows whose
+///   values the new type cannot hold.
+/// - `FullScanNotAllowed`: a `SELECT` would read every partition of the table and does not
+///   have `ALLOW FILTERING`.
+/// - `InvalidDistinct`: a `SELECT DISTINCT` requests columns other than the partition keys,
+///   or not all of them.
+/// - `InvalidGroupBy`: the columns of a `GROUP BY` are not a prefix of the primary key, or a
+///   `SELECT` with an aggregate has no `GROUP BY`.
+/// - `InvalidSelection`: a `SELECT` applies `WRITETIME` or `TTL` to a column they cannot be
+///   applied to.
+/// - `ValueOutOfRange`: a number does not fit in the range of its column type.
+/// - `InvalidValue`: a value cannot be read as its column type, such as a text in an `int`.
 /// - `Error`: generic type for other possible errors detected.
 ///
 #[derive(Debug, PartialEq)]
@@ -15,13 +29,35 @@ pub enum CQLError {
     InvalidColumn,
     InvalidSyntax,
     NoActualKeyspaceError,
+    KeyspaceDoesNotExist,
     TableAlreadyExist,
+    ColumnAlreadyExist,
     NoWhereCondition,
     MissingPartitionOrClusteringColumns,
     InvalidCondition,
+    IncompatibleValues {
+        column: String,
+        data_type: String,
+        rows: Vec<String>,
+    },
+    FullScanNotAllowed,
+    InvalidDistinct(String),
+    InvalidGroupBy(String),
+    InvalidSelection(String),
+    ValueOutOfRange {
+        value: String,
+        data_type: String,
+    },
+    InvalidValue {
+        value: String,
+        data_type: String,
+    },
     Error,
 }
 
+// Cuantas filas se muestran en el mensaje de `IncompatibleValues`
+const MAX_ROWS_IN_MESSAGE: usize = 5;
+
 impl Display for CQLError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -31,9 +67,15 @@ impl Display for CQLError {
             CQLError::NoActualKeyspaceError => {
                 write!(f, "[NoActualKeyspace]: [There is no current keyspace]")
             }
+            CQLError::KeyspaceDoesNotExist => {
+                write!(f, "[KeyspaceDoesNotExist]: [The keyspace does not exist]")
+            }
             CQLError::TableAlreadyExist => {
                 write!(f, "[TableAlreadyExist]: [The table already exists]")
             }
+            CQLError::ColumnAlreadyExist => {
+                write!(f, "[ColumnAlreadyExist]: [The column already exists]")
+            }
             CQLError::NoWhereCondition => {
                 write!(
                     f,
@@ -49,7 +91,85 @@ impl Display for CQLError {
                     "[InvalidCondition]: [The condition in the query is invalid]"
                 )
             }
+            CQLError::IncompatibleValues {
+                column,
+                data_type,
+                rows,
+            } => {
+                let shown: Vec<&str> = rows
+                    .iter()
+                    .take(MAX_ROWS_IN_MESSAGE)
+                    .map(String::as_str)
+                    .collect();
+                write!(
+                    f,
+                    "[IncompatibleValues]: [{} row(s) cannot change column {} to {}: {}{}]",
+                    rows.len(),
+                    column,
+                    data_type,
+                    shown.join(" | "),
+                    if rows.len() > shown.len() {
+                        " | ..."
+                    } else {
+                        ""
+                    }
+                )
+            }
+            CQLError::FullScanNotAllowed => write!(
+                f,
+                "[FullScanNotAllowed]: [The query would read every partition of the table: restrict each partition key column with `=`, or add ALLOW FILTERING to scan it anyway]"
+            ),
+            CQLError::InvalidDistinct(reason) => write!(f, "[InvalidDistinct]: [{}]", reason),
+            CQLError::InvalidGroupBy(reason) => write!(f, "[InvalidGroupBy]: [{}]", reason),
+            CQLError::InvalidSelection(reason) => write!(f, "[InvalidSelection]: [{}]", reason),
+            CQLError::ValueOutOfRange { value, data_type } => write!(
+                f,
+                "[ValueOutOfRange]: [{} is out of range for type {}]",
+                value, data_type
+            ),
+            CQLError::InvalidValue { value, data_type } => write!(
+                f,
+                "[InvalidValue]: [{} is not a valid {}]",
+                value, data_type
+            ),
             CQLError::Error => write!(f, "[Error]: [An unspecified error occurred]"),
         }
     }
 }
+
+impl From<&CQLError> for Error {
+    /// Maps the error to the native protocol error reported to the client.
+    ///
+    /// Creating an existing table or column is `AlreadyExists`, and queries that cannot be parsed or
+    /// refer to missing or wrong elements are `Invalid`.
+    fn from(error: &CQLError) -> Self {
+        let message = error.to_string();
+        match error {
+            CQLError::TableAlreadyExist | CQLError::ColumnAlreadyExist => {
+                Error::AlreadyExists(message)
+            }
+            CQLError::InvalidSyntax
+            | CQLError::InvalidTable
+            | CQLError::InvalidColumn
+            | CQLError::NoActualKeyspaceError
+            | CQLError::KeyspaceDoesNotExist
+            | CQLError::NoWhereCondition
+            | CQLError::MissingPartitionOrClusteringColumns
+            | CQLError::InvalidCondition
+            | CQLError::IncompatibleValues { .. }
+            | CQLError::FullScanNotAllowed
+            | CQLError::InvalidDistinct(_)
+            | CQLError::InvalidGroupBy(_)
+            | CQLError::InvalidSelection(_)
+            | CQLError::ValueOutOfRange { .. }
+            | CQLError::InvalidValue { .. } => Error::Invalid(message),
+            CQLError::Error => Error::ServerError(message),
+        }
+    }
+}
+
+impl From<CQLError> for Error {
+    fn from(error: CQLError) -> Self {
+        Error::from(&error)
+    }
+}
@@ -1,4 +1,4 @@
-﻿pub mod clauses;
+pub mod clauses;
 pub mod errors;
 pub mod logical_operator;
 pub mod operator;
@@ -14,16 +14,83 @@ use clauses::table::{
 use clauses::types::column::Column;
 use clauses::types::datatype::DataType;
 use clauses::{
-    delete_cql::Delete, insert_cql::Insert, select_cql::Select, update_cql::Update, use_cql::Use,
+    batch_cql::Batch,
+    delete_cql::Delete,
+    explain_cql::Explain,
+    insert_cql::{Insert, NULL_VALUE},
+    select_cql::{CellFunction, Select},
+    update_cql::Update,
+    use_cql::Use,
 };
 use errors::CQLError;
 use native_protocol::frame::Frame;
 use native_protocol::messages::result::result_;
-use native_protocol::messages::result::rows::{ColumnType, ColumnValue, Rows};
+use native_protocol::messages::result::rows::{
+    decode_storage_hex, split_storage_values, unescape_storage_text, ColumnType, ColumnValue, Row,
+    Rows,
+};
 use native_protocol::messages::result::schema_change;
 use native_protocol::messages::result::schema_change::SchemaChange;
-use std::collections::BTreeMap;
 use std::fmt;
+use std::num::{IntErrorKind, ParseIntError};
+use std::str::FromStr;
+
+/// Keywords recognized by the parsers outside of parentheses and braces.
+/// CQL keywords are case-insensitive, so the tokenizer uppercases them; identifiers and
+/// string literals keep the case they were written with.
+/// Words that are keywords only next to another one, like `TABLE` or `DISTINCT`, can also be
+/// names of tables or columns, so they are only uppercased where they are keywords.
+const KEYWORDS: &[&str] = &[
+    "ADD",
+    "ALLOW",
+    "ALTER",
+    "AND",
+    "ASC",
+    "BY",
+    "CLUSTERING",
+    "CONTAINS",
+    "CREATE",
+    "DELETE",
+    "DESC",
+    "DROP",
+    "EXISTS",
+    "EXPLAIN",
+    "FROM",
+    "IF",
+    "INSERT",
+    "INTO",
+    "KEY",
+    "LIMIT",
+    "MODIFY",
+    "NOT",
+    "OR",
+    "ORDER",
+    "PRIMARY",
+    "RENAME",
+    "REPLICATION",
+    "SELECT",
+    "SET",
+    "TO",
+    "UPDATE",
+    "USE",
+    "VALUES",
+    "WHERE",
+    "WITH",
+];
+
+/// Words that are keywords only right after one of the given words, which the tokenizer has
+/// already uppercased. Anywhere else they keep their case, as any other identifier.
+const CONTEXTUAL_KEYWORDS: &[(&str, &[&str])] = &[
+    ("COLUMN", &["ADD"]),
+    ("FILTERING", &["ALLOW"]),
+    ("KEYSPACE", &["CREATE", "DROP", "ALTER"]),
+    ("NULL", &["NOT"]),
+    ("PARTITION", &["PER"]),
+    ("TABLE", &["CREATE", "DROP", "ALTER"]),
+];
+
+/// Words that are keywords only right before the given word, in any case.
+const LEADING_KEYWORDS: &[(&str, &str)] = &[("GROUP", "BY"), ("PER", "PARTITION")];
 
 /// The `NeededResponses` trait defines how many responses are required for a given query.
 /// Queries like `CREATE` and `DROP` often require responses from all nodes in a distributed system,
@@ -84,6 +151,22 @@ pub trait NeedsTable {
     fn needs_table(&self) -> bool;
 }
 
+/// A trait to determine if a query is a conditional write (a lightweight transaction).
+///
+/// # Purpose
+/// Conditional writes (`UPDATE ... IF`, `DELETE ... IF` / `IF EXISTS` and `INSERT ... IF NOT EXISTS`)
+/// are only performed when the current row satisfies the condition, and they answer the client
+/// with an `[applied]` row instead of a `Void` result.
+///
+/// # Method
+/// - `fn is_conditional(&self) -> bool`:
+///   - Returns:
+///     - `true` if the query carries a condition.
+///     - `false` otherwise.
+pub trait ConditionalWrite {
+    fn is_conditional(&self) -> bool;
+}
+
 /// A trait for creating client-compatible responses from a query.
 ///
 /// # Purpose
@@ -159,11 +242,16 @@ pub trait GetUsedKeyspace {
 /// # Variants
 /// - `One`
 ///   - Indicates that only a single response is required.
-///   - Typically used for consistency levels like `ONE` or minimal guarantees.
+///   - Typically used for consistency levels like `ONE` or minimal guarantees, and for queries the
+///     coordinator resolves by itself, like `USE`: its own answer is the only one.
 /// - `ReplicationFactor`
 ///   - Indicates that the required number of responses is equal to the replication factor.
 ///   - Typically used for consistency levels like `QUORUM` or `ALL`, where responses depend
 ///     on the replication configuration of the cluster.
+/// - `AllLiveNodes`
+///   - Indicates that every node gossip reports as alive must answer, whatever the consistency
+///     level. Used for DDL, which changes the schema of every node: a node that is down is
+///     left out and gets the change through the gossiped schema when it comes back.
 ///
 /// # Usage
 /// This enum helps differentiate between fixed and dynamic response requirements:
@@ -174,6 +262,7 @@ pub trait GetUsedKeyspace {
 pub enum NeededResponseCount {
     One,
     ReplicationFactor,
+    AllLiveNodes,
 }
 
 /// `Query` is an enumeration representing different query types supported by the system,
@@ -192,6 +281,111 @@ pub enum Query {
     DropKeyspace(DropKeyspace),
     AlterKeyspace(AlterKeyspace),
     Use(Use),
+    Explain(Explain),
+    Batch(Batch),
+}
+
+/// Rough cost of running a query, used by the coordinator to refuse the expensive ones before
+/// contacting any replica.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueryCost {
+    /// The query touches a single partition, or does not read rows at all.
+    Bounded,
+    /// The query reads every partition of the table, in every node.
+    FullScan,
+}
+
+impl Query {
+    /// Estimates the cost of the query for a table whose partition key columns are
+    /// `partition_keys`.
+    ///
+    /// Only a `SELECT` that does not set each partition key column with `=` is a `FullScan`;
+    /// every other query is `Bounded`.
+    pub fn cost_estimate(&self, partition_keys: &[String]) -> QueryCost {
+        match self {
+            Query::Select(select) if select.is_full_scan(partition_keys) => QueryCost::FullScan,
+            _ => QueryCost::Bounded,
+        }
+    }
+
+    /// Whether the query reads every partition of the table, see `cost_estimate`.
+    pub fn is_full_scan(&self, partition_keys: &[String]) -> bool {
+        self.cost_estimate(partition_keys) == QueryCost::FullScan
+    }
+
+    /// Rejects a full scan unless the query asked for it with `ALLOW FILTERING`.
+    ///
+    /// A `SELECT DISTINCT` is allowed anyway, as in Cassandra: it lists the partitions of the
+    /// table and reads a single row of each. So is a `SELECT` with `GROUP BY`, which only
+    /// aggregates the rows of each group.
+    ///
+    /// # Errors
+    /// - `CQLError::FullScanNotAllowed` if the query is a full scan without `ALLOW FILTERING`.
+    pub fn check_full_scan_allowed(&self, partition_keys: &[String]) -> Result<(), CQLError> {
+        match self {
+            Query::Select(select)
+                if !select.allow_filtering
+                    && !select.distinct
+                    && !select.is_grouped()
+                    && self.is_full_scan(partition_keys) =>
+            {
+                Err(CQLError::FullScanNotAllowed)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Normalized text of the query, so two queries that only differ in how they were written
+    /// have the same one and can share a cache entry or a prepared statement.
+    ///
+    /// It is built from the parsed query: keywords are uppercased and whitespace is collapsed,
+    /// while identifiers and literals keep their case. The parts whose order does not change
+    /// what the query does are sorted: the columns of an `INSERT`, with their values, and the
+    /// assignments of an `UPDATE`. The conditions of a `WHERE` and the statements of a batch
+    /// keep their order.
+    pub fn canonical_text(&self) -> String {
+        match self {
+            Query::Select(select) => select.serialize(),
+            Query::Insert(insert) => {
+                let mut insert = insert.clone();
+                // Con `uuid()` u otra cantidad de valores el orden se deja como esta
+                if insert.values.len() == insert.into_clause.columns.len() {
+                    let mut pairs: Vec<(String, String)> = insert
+                        .into_clause
+                        .columns
+                        .drain(..)
+                        .zip(insert.values.drain(..))
+                        .collect();
+                    pairs.sort();
+                    (insert.into_clause.columns, insert.values) = pairs.into_iter().unzip();
+                }
+                insert.serialize()
+            }
+            Query::Update(update) => {
+                let mut update = update.clone();
+                update.set_clause.0.sort();
+                update.serialize()
+            }
+            Query::Delete(delete) => delete.serialize(),
+            Query::CreateTable(create_table) => create_table.serialize(),
+            Query::DropTable(drop_table) => drop_table.serialize(),
+            Query::AlterTable(alter_table) => alter_table.serialize(),
+            Query::CreateKeyspace(create_keyspace) => create_keyspace.serialize(),
+            Query::DropKeyspace(drop_keyspace) => drop_keyspace.serialize(),
+            Query::AlterKeyspace(alter_keyspace) => alter_keyspace.serialize(),
+            Query::Use(use_cql) => use_cql.serialize(),
+            Query::Explain(explain) => explain.serialize(),
+            Query::Batch(batch) => {
+                let statements: Vec<String> =
+                    batch.statements.iter().map(Query::canonical_text).collect();
+                format!(
+                    "BEGIN {}BATCH {}; APPLY BATCH",
+                    if batch.logged { "" } else { "UNLOGGED " },
+                    statements.join("; ")
+                )
+            }
+        }
+    }
 }
 
 /// Implements the `fmt::Display` trait for `Query`. This allows the enum to be printed in a human-readable format.
@@ -209,6 +403,8 @@ impl fmt::Display for Query {
             Query::DropKeyspace(_) => "DropKeyspace",
             Query::AlterKeyspace(_) => "AlterKeyspace",
             Query::Use(_) => "Use",
+            Query::Explain(_) => "Explain",
+            Query::Batch(_) => "Batch",
         };
         write!(f, "{}", query_type)
     }
@@ -229,16 +425,52 @@ impl From<DataType> for ColumnType {
     }
 }
 
+/// Builds the `Rows` result of a `SELECT`.
+///
+/// `col_types` are the selected columns with their types, in the order their values appear in
+/// each of the `rows`, which are separated by commas.
+pub fn create_rows_frame(
+    col_types: Vec<(String, ColumnType)>,
+    rows: &[String],
+) -> Result<Frame, CQLError> {
+    let mut records = Vec::new();
+
+    for row in rows {
+        let mut record = Row::new();
+
+        for (idx, value) in split_storage_values(row).into_iter().enumerate() {
+            let (name, r#type) = col_types.get(idx).ok_or(CQLError::Error)?;
+            let col_value = create_column_value_from_type(r#type, value)?;
+
+            record.push(name.to_string(), col_value);
+        }
+
+        records.push(record);
+    }
+
+    let rows = Rows::new(col_types, records);
+
+    Ok(Frame::Result(result_::Result::Rows(rows)))
+}
+
+/// Reads a value of type `col_type` as it is stored in a row, the inverse of
+/// [`ColumnValue::to_storage_string`]. An empty value of a type that has no empty form is
+/// read as its zero.
 fn create_column_value_from_type(
     col_type: &ColumnType,
     value: &str,
 ) -> Result<ColumnValue, CQLError> {
+    if value == NULL_VALUE {
+        return Ok(ColumnValue::Null);
+    }
+
     // Si el valor está vacío, devolver un ColumnValue vacío según el tipo de columna
     if value.is_empty() {
         return match col_type {
+            ColumnType::Custom(_) => Ok(ColumnValue::Custom(String::new())),
             ColumnType::Ascii => Ok(ColumnValue::Ascii(String::new())),
             ColumnType::Bigint => Ok(ColumnValue::Bigint(0)),
-            // ColumnType::Blob => Ok(ColumnValue::Blob(vec![])),
+            ColumnType::Blob => Ok(ColumnValue::Blob(vec![])),
             ColumnType::Boolean => Ok(ColumnValue::Boolean(false)),
             ColumnType::Counter => Ok(ColumnValue::Counter(0)),
             ColumnType::Decimal => Ok(ColumnValue::Decimal {
@@ -261,32 +493,36 @@ fn create_column_value_from_type(
 
     // Caso normal: procesar el valor según el tipo de columna
     match col_type {
-        ColumnType::Ascii => Ok(ColumnValue::Ascii(value.to_string())),
-        ColumnType::Bigint => Ok(ColumnValue::Bigint(
-            value.parse::<i64>().map_err(|_| CQLError::Error)?,
+        ColumnType::Custom(_) => Ok(ColumnValue::Custom(unescape_storage_text(value))),
+        ColumnType::Ascii => Ok(ColumnValue::Ascii(unescape_storage_text(value))),
+        ColumnType::Bigint => Ok(ColumnValue::Bigint(parse_integer(value, "bigint")?)),
+        ColumnType::Blob => Ok(ColumnValue::Blob(
+            decode_storage_hex(value).map_err(|_| CQLError::Error)?,
         )),
-        // ColumnType::Blob => Ok(ColumnValue::Blob(
-        //     hex::decode(value).map_err(|_| CQLError::Error)?,
-        // )),
         ColumnType::Boolean => Ok(ColumnValue::Boolean(
             value.parse::<bool>().map_err(|_| CQLError::Error)?,
         )),
         ColumnType::Counter => Ok(ColumnValue::Counter(
             value.parse::<i64>().map_err(|_| CQLError::Error)?,
         )),
-        ColumnType::Decimal => Ok(ColumnValue::Decimal {
-            scale: value.parse::<i32>().map_err(|_| CQLError::Error)?,
-            unscaled: value.as_bytes().to_vec(),
-        }),
-        ColumnType::Double => Ok(ColumnValue::Double(
-            value.parse::<f64>().map_err(|_| CQLError::Error)?,
-        )),
-        ColumnType::Float => Ok(ColumnValue::Float(
-            value.parse::<f32>().map_err(|_| CQLError::Error)?,
-        )),
-        ColumnType::Int => Ok(ColumnValue::Int(
-            value.parse::<i32>().map_err(|_| CQLError::Error)?,
-        )),
+        ColumnType::Decimal => {
+            let (scale, unscaled) = value.split_once(':').ok_or(CQLError::Error)?;
+            Ok(ColumnValue::Decimal {
+                scale: scale.parse::<i32>().map_err(|_| CQLError::Error)?,
+                unscaled: decode_storage_hex(unscaled).map_err(|_| CQLError::Error)?,
+            })
+        }
+        ColumnType::Double => Ok(ColumnValue::Double(parse_float(
+            value,
+            "double",
+            |parsed: &f64| parsed.is_infinite(),
+        )?)),
+        ColumnType::Float => Ok(ColumnValue::Float(parse_float(
+            value,
+            "float",
+            |parsed: &f32| parsed.is_infinite(),
+        )?)),
+        ColumnType::Int => Ok(ColumnValue::Int(parse_integer(value, "int")?)),
         ColumnType::Timestamp => Ok(ColumnValue::Timestamp(
             value.parse::<i64>().map_err(|_| CQLError::Error)?,
         )),
@@ -295,12 +531,71 @@ fn create_column_value_from_type(
             let uuid = uuid::Uuid::parse_str(value).map_err(|_| CQLError::Error)?;
             Ok(ColumnValue::Uuid(uuid))
         }
-        ColumnType::Varchar => Ok(ColumnValue::Varchar(value.to_string())),
-        ColumnType::Varint => Ok(ColumnValue::Varint(value.as_bytes().to_vec())),
+        ColumnType::Varchar => Ok(ColumnValue::Varchar(unescape_storage_text(value))),
+        ColumnType::Varint => Ok(ColumnValue::Varint(
+            decode_storage_hex(value).map_err(|_| CQLError::Error)?,
+        )),
+        ColumnType::Timeuuid => Ok(ColumnValue::Timeuuid(
+            uuid::Uuid::parse_str(value).map_err(|_| CQLError::Error)?,
+        )),
+        ColumnType::Inet => Ok(ColumnValue::Inet(
+            value.parse().map_err(|_| CQLError::Error)?,
+        )),
         _ => Err(CQLError::Error),
     }
 }
 
+/// Reads an integer of the CQL type `data_type`.
+///
+/// # Errors
+/// - `CQLError::ValueOutOfRange` if `value` is an integer that does not fit in the type.
+/// - `CQLError::InvalidValue` if `value` is not an integer.
+fn parse_integer<T: FromStr<Err = ParseIntError>>(
+    value: &str,
+    data_type: &str,
+) -> Result<T, CQLError> {
+    value.parse::<T>().map_err(|error| match error.kind() {
+        IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => CQLError::ValueOutOfRange {
+            value: value.to_string(),
+            data_type: data_type.to_string(),
+        },
+        _ => CQLError::InvalidValue {
+            value: value.to_string(),
+            data_type: data_type.to_string(),
+        },
+    })
+}
+
+/// Reads a floating point number of the CQL type `data_type`.
+///
+/// A number too large for the type parses as infinity, so it is out of range unless `value`
+/// is an infinity itself, as `to_storage_string` writes them.
+///
+/// # Errors
+/// - `CQLError::ValueOutOfRange` if `value` is a number that does not fit in the type.
+/// - `CQLError::InvalidValue` if `value` is not a number.
+fn parse_float<T: FromStr>(
+    value: &str,
+    data_type: &str,
+    is_infinite: impl Fn(&T) -> bool,
+) -> Result<T, CQLError> {
+    let parsed = value.parse::<T>().map_err(|_| CQLError::InvalidValue {
+        value: value.to_string(),
+        data_type: data_type.to_string(),
+    })?;
+    let written_as_infinity = value
+        .trim_start_matches(['+', '-'])
+        .to_ascii_lowercase()
+        .starts_with("inf");
+    if is_infinite(&parsed) && !written_as_infinity {
+        return Err(CQLError::ValueOutOfRange {
+            value: value.to_string(),
+            data_type: data_type.to_string(),
+        });
+    }
+    Ok(parsed)
+}
+
 // Implements the CreateClientResponse that return the Frame to respond to the client depending of what Query is.
 impl CreateClientResponse for Query {
     fn create_client_response(
@@ -330,27 +625,15 @@ impl CreateClientResponse for Query {
                     })
                     .collect();
 
-                let col_types = col_types?;
-
-                let mut records = Vec::new();
-
-                for row in &rows[1..] {
-                    let mut record = BTreeMap::new();
-
-                    for (idx, value) in row.split(",").enumerate() {
-                        let (name, r#type) = col_types.get(idx).ok_or(CQLError::Error)?;
-                        let col_value = create_column_value_from_type(r#type, value)
-                            .map_err(|_| CQLError::Error)?;
-
-                        record.insert(name.to_string(), col_value);
-                    }
-
-                    records.push(record);
-                }
-
-                let rows = Rows::new(col_types, records);
+                create_rows_frame(col_types?, &rows[1..])?
+            }
+            Query::Insert(_) | Query::Update(_) | Query::Delete(_) if self.is_conditional() => {
+                // La unica fila indica si la condicion se cumplio y la escritura se aplico
+                let applied = rows.first().is_some_and(|applied| applied == "true");
+                let col_types = vec![("[applied]".to_string(), ColumnType::Boolean)];
+                let record = Row::from([("[applied]".to_string(), ColumnValue::Boolean(applied))]);
 
-                Frame::Result(result_::Result::Rows(rows))
+                Frame::Result(result_::Result::Rows(Rows::new(col_types, vec![record])))
             }
             Query::Insert(_) => Frame::Result(result_::Result::Void),
             Query::Update(_) => Frame::Result(result_::Result::Void),
@@ -399,6 +682,17 @@ impl CreateClientResponse for Query {
                 )))
             }
             Query::Use(_) => Frame::Result(result_::Result::SetKeyspace(keyspace)),
+            Query::Batch(_) => Frame::Result(result_::Result::Void),
+            Query::Explain(_) => {
+                // Each row is one line of the plan
+                let col_types = vec![("plan".to_string(), ColumnType::Ascii)];
+                let records = rows
+                    .into_iter()
+                    .map(|line| Row::from([("plan".to_string(), ColumnValue::Ascii(line))]))
+                    .collect();
+
+                Frame::Result(result_::Result::Rows(Rows::new(col_types, records)))
+            }
         };
 
         Ok(query_type)
@@ -406,7 +700,8 @@ impl CreateClientResponse for Query {
 }
 
 // Implements the `NeededResponses` trait for each type of query. Queries like `SELECT` and `INSERT`
-// require a specific number of responses, while `CREATE` and `DROP` require responses from all nodes.
+// require a response from each replica, `CREATE` and `DROP` one from each live node, and `USE` is
+// answered by the coordinator.
 impl NeededResponses for Query {
     fn needed_responses(&self) -> NeededResponseCount {
         match self {
@@ -414,13 +709,30 @@ impl NeededResponses for Query {
             Query::Insert(_) => NeededResponseCount::ReplicationFactor,
             Query::Update(_) => NeededResponseCount::ReplicationFactor,
             Query::Delete(_) => NeededResponseCount::ReplicationFactor,
-            Query::CreateTable(_) => NeededResponseCount::One,
-            Query::DropTable(_) => NeededResponseCount::One,
-            Query::AlterTable(_) => NeededResponseCount::One,
-            Query::CreateKeyspace(_) => NeededResponseCount::One,
-            Query::DropKeyspace(_) => NeededResponseCount::One,
+            // El DDL lo aplican todos los nodos vivos, los caidos lo reciben por gossip al volver
+            Query::CreateTable(_) => NeededResponseCount::AllLiveNodes,
+            Query::DropTable(_) => NeededResponseCount::AllLiveNodes,
+            Query::AlterTable(_) => NeededResponseCount::AllLiveNodes,
+            Query::CreateKeyspace(_) => NeededResponseCount::AllLiveNodes,
+            Query::DropKeyspace(_) => NeededResponseCount::AllLiveNodes,
+            // ALTER KEYSPACE todavia no se ejecuta en ningun nodo
             Query::AlterKeyspace(_) => NeededResponseCount::One,
+            // USE solo cambia el keyspace de la conexion, no consulta a ningun otro nodo
             Query::Use(_) => NeededResponseCount::One,
+            Query::Explain(_) => NeededResponseCount::One,
+            // Un lote necesita lo que necesite el mas exigente de sus miembros
+            Query::Batch(batch) => {
+                if batch.statements.iter().any(|statement| {
+                    matches!(
+                        statement.needed_responses(),
+                        NeededResponseCount::ReplicationFactor
+                    )
+                }) {
+                    NeededResponseCount::ReplicationFactor
+                } else {
+                    NeededResponseCount::One
+                }
+            }
         }
     }
 }
@@ -439,6 +751,8 @@ impl NeedsKeyspace for Query {
             Query::Insert(_) => true,          // `INSERT` no es una consulta que necesite keyspace
             Query::Update(_) => true,          // `UPDATE` no es una consulta que necesite keyspace
             Query::Delete(_) => true,          // `DELETE` no es una consulta que necesite keyspace
+            Query::Explain(_) => true,         // `EXPLAIN` necesita el keyspace del `SELECT`
+            Query::Batch(_) => false,          // Cada sentencia del lote se valida al ejecutarla
         }
     }
 }
@@ -457,6 +771,19 @@ impl NeedsTable for Query {
             Query::DropKeyspace(_) => false,   // `DROP KEYSPACE` no requiere tabla
             Query::AlterKeyspace(_) => false,  // `ALTER KEYSPACE` no requiere tabla
             Query::Use(_) => false,            // `USE` no requiere tabla
+            Query::Explain(_) => true,         // `EXPLAIN` requiere la tabla del `SELECT`
+            Query::Batch(_) => false,          // Cada sentencia del lote tiene su tabla
+        }
+    }
+}
+
+impl ConditionalWrite for Query {
+    fn is_conditional(&self) -> bool {
+        match self {
+            Query::Insert(insert) => insert.if_not_exists,
+            Query::Update(update) => update.if_clause.is_some(),
+            Query::Delete(delete) => delete.if_clause.is_some() || delete.if_exist,
+            _ => false,
         }
     }
 }
@@ -478,6 +805,8 @@ impl GetTableName for Query {
                 Query::DropKeyspace(_) => None,
                 Query::AlterKeyspace(_) => None,
                 Query::Use(_) => None,
+                Query::Explain(explain) => Some(explain.select.table_name.clone()),
+                Query::Batch(_) => None,
             }
         }
     }
@@ -539,6 +868,56 @@ impl GetUsedKeyspace for Query {
             Query::DropKeyspace(_) => None,
             Query::AlterKeyspace(_) => None,
             Query::Use(_) => None,
+            Query::Explain(explain) => {
+                if explain.select.keyspace_used_name.is_empty() {
+                    None
+                } else {
+                    Some(explain.select.keyspace_used_name.clone())
+                }
+            }
+            Query::Batch(_) => None,
+        }
+    }
+}
+
+/// Longest query, in bytes, that `QueryCreator::handle_query` accepts by default.
+pub const DEFAULT_MAX_QUERY_LENGTH: usize = 64 * 1024;
+
+/// Largest number of tokens a query may have by default.
+pub const DEFAULT_MAX_TOKENS: usize = 4096;
+
+/// Deepest nesting of parentheses a query may have by default.
+pub const DEFAULT_MAX_PAREN_DEPTH: usize = 32;
+
+/// Limits on the size of a query, checked while it is tokenized so that a huge or deeply
+/// nested query from a client is rejected early instead of being parsed without bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParserLimits {
+    /// Longest query accepted, in bytes.
+    pub max_query_length: usize,
+    /// Largest number of tokens accepted.
+    pub max_tokens: usize,
+    /// Deepest nesting of parentheses accepted.
+    pub max_paren_depth: usize,
+}
+
+impl Default for ParserLimits {
+    fn default() -> Self {
+        Self {
+            max_query_length: DEFAULT_MAX_QUERY_LENGTH,
+            max_tokens: DEFAULT_MAX_TOKENS,
+            max_paren_depth: DEFAULT_MAX_PAREN_DEPTH,
+        }
+    }
+}
+
+impl ParserLimits {
+    /// Limits that never trigger, for text that does not come from a client.
+    pub fn unlimited() -> Self {
+        Self {
+            max_query_length: usize::MAX,
+            max_tokens: usize::MAX,
+            max_paren_depth: usize::MAX,
         }
     }
 }
@@ -547,7 +926,9 @@ impl GetUsedKeyspace for Query {
 /// It parses a query string into tokens, determines the type of query, and returns a corresponding
 /// `Query` enum variant.
 #[derive(Debug)]
-pub struct QueryCreator;
+pub struct QueryCreator {
+    limits: ParserLimits,
+}
 
 impl Default for QueryCreator {
     fn default() -> Self {
@@ -556,9 +937,17 @@ impl Default for QueryCreator {
 }
 
 impl QueryCreator {
-    /// Creates a new instance of `QueryCreator`.
+    /// Creates a new instance of `QueryCreator` with the default `ParserLimits`.
     pub fn new() -> QueryCreator {
-        QueryCreator {}
+        QueryCreator {
+            limits: ParserLimits::default(),
+        }
+    }
+
+    /// Replaces the limits on the size of the queries this `QueryCreator` accepts.
+    pub fn with_limits(mut self, limits: ParserLimits) -> QueryCreator {
+        self.limits = limits;
+        self
     }
 
     /// Parses a query string and determines the type of query (e.g., `SELECT`, `INSERT`, `CREATE TABLE`).
@@ -568,9 +957,23 @@ impl QueryCreator {
     /// - `query`: A `String` representing the query to be handled.
     ///
     /// # Returns
-    /// A `Result` containing either a `Query` enum or a `CQLError`.
+    /// A `Result` containing either a `Query` enum or a `CQLError`. Queries that exceed the
+    /// `ParserLimits` of this `QueryCreator` fail with `CQLError::InvalidSyntax`.
     pub fn handle_query(self, query: String) -> Result<Query, CQLError> {
-        let tokens = Self::tokens_from_query(&query);
+        // Se corta antes de copiar o recorrer la query
+        if query.len() > self.limits.max_query_length {
+            return Err(CQLError::InvalidSyntax);
+        }
+
+        // Las sentencias de un lote se separan con ';', que el tokenizer descarta
+        if Batch::is_batch(&query) {
+            return Ok(Query::Batch(Batch::new_from_query(&query)?));
+        }
+
+        let tokens = Self::tokens_from_query_with_limits(&query, &self.limits)?;
+        if tokens.is_empty() {
+            return Err(CQLError::InvalidSyntax);
+        }
 
         match tokens[0].as_str() {
             "SELECT" => {
@@ -626,12 +1029,17 @@ impl QueryCreator {
                 let use_cql = Use::new_from_tokens(tokens)?;
                 Ok(Query::Use(use_cql))
             }
+            "EXPLAIN" => {
+                let explain = Explain::new_from_tokens(tokens)?;
+                Ok(Query::Explain(explain))
+            }
             _ => Err(CQLError::InvalidSyntax),
         }
     }
 
     /// Tokenizes a query string by breaking it into its constituent parts.
     /// This function handles various elements such as braces, parentheses, and quotes.
+    /// Keywords are returned in uppercase, whatever case they were written in.
     ///
     /// # Parameters
     /// - `string`: The query string to be tokenized.
@@ -639,16 +1047,37 @@ impl QueryCreator {
     /// # Returns
     /// A `Vec<String>` containing the tokens.
     pub fn tokens_from_query(string: &str) -> Vec<String> {
+        Self::tokens_from_query_with_limits(string, &ParserLimits::unlimited()).unwrap_or_default()
+    }
+
+    /// Tokenizes a query like [`QueryCreator::tokens_from_query`], but stops as soon as the
+    /// query goes over one of `limits`.
+    ///
+    /// # Errors
+    /// - `CQLError::InvalidSyntax` if the query is longer than `max_query_length`, has more
+    ///   than `max_tokens` tokens or nests more than `max_paren_depth` parentheses.
+    pub fn tokens_from_query_with_limits(
+        string: &str,
+        limits: &ParserLimits,
+    ) -> Result<Vec<String>, CQLError> {
         let mut index = 0;
         let mut tokens = Vec::new();
         let mut current = String::new();
         let mut in_braces = false;
 
-        let string = string.replace(";", "");
-        let length = string.len();
+        if string.len() > limits.max_query_length {
+            return Err(CQLError::InvalidSyntax);
+        }
+
+        // Los indices son posiciones de caracteres, no de bytes
+        let chars: Vec<char> = string.replace(";", "").chars().collect();
+        let length = chars.len();
 
         while index < length {
-            let char = string.chars().nth(index).unwrap_or('0');
+            if tokens.len() > limits.max_tokens {
+                return Err(CQLError::InvalidSyntax);
+            }
+            let char = chars[index];
 
             if char == '{' {
                 tokens.push("{".to_string());
@@ -664,7 +1093,7 @@ impl QueryCreator {
                 index += 1;
             } else if in_braces {
                 if char == '\'' {
-                    index = Self::process_quotes(&string, index, &mut current, &mut tokens);
+                    index = Self::process_quotes(&chars, index, &mut current, &mut tokens);
                 } else if char.is_alphanumeric() || char == '_' {
                     current.push(char);
                     index += 1;
@@ -678,15 +1107,21 @@ impl QueryCreator {
                     index += 1;
                 }
             } else if char.is_alphanumeric() || char == '_' || char == '@' || char == '-' {
-                index = Self::process_alfa(&string, index, &mut current, &mut tokens);
+                index = Self::process_alfa(&chars, index, &mut current, &mut tokens);
             } else if char == '\'' {
-                index = Self::process_quotes(&string, index, &mut current, &mut tokens);
+                index = Self::process_quotes(&chars, index, &mut current, &mut tokens);
             } else if char == '(' {
-                index = Self::process_paren(&string, index, &mut current, &mut tokens);
+                index = Self::process_paren(
+                    &chars,
+                    index,
+                    &mut current,
+                    &mut tokens,
+                    limits.max_paren_depth,
+                )?;
             } else if char.is_whitespace() || char == ',' {
                 index += 1;
             } else {
-                index = Self::process_other(&string, index, &mut current, &mut tokens);
+                index = Self::process_other(&chars, index, &mut current, &mut tokens);
             }
         }
 
@@ -695,17 +1130,20 @@ impl QueryCreator {
         }
 
         tokens.retain(|s| !s.is_empty());
-        tokens
+        if tokens.len() > limits.max_tokens {
+            return Err(CQLError::InvalidSyntax);
+        }
+        Ok(tokens)
     }
 
     fn process_alfa(
-        string: &str,
+        chars: &[char],
         mut index: usize,
         current: &mut String,
         tokens: &mut Vec<String>,
     ) -> usize {
-        while index < string.len() {
-            let char = string.chars().nth(index).unwrap_or('0');
+        while index < chars.len() {
+            let char = chars[index];
 
             // Aceptamos cualquier caracter alfanumérico, guiones bajos, arroba, punto o guión
             if char.is_alphanumeric() || char == '_' || char == '@' || char == '.' || char == '-' {
@@ -717,22 +1155,65 @@ impl QueryCreator {
         }
 
         if !current.is_empty() {
-            tokens.push(current.clone());
+            let upper = current.to_uppercase();
+            // `WRITETIME(col)` y `TTL(col)` quedan en un solo token, asi no se confunden con una
+            // columna que se llame igual
+            if CellFunction::from_name(&upper).is_some() && chars.get(index) == Some(&'(') {
+                if let Some(close) = chars[index..].iter().position(|char| *char == ')') {
+                    let column: String = chars[index + 1..index + close].iter().collect();
+                    tokens.push(format!("{}({})", upper, column.trim()));
+                    current.clear();
+                    return index + close + 1;
+                }
+            }
+            if Self::is_keyword(&upper, tokens.last(), chars, index) {
+                tokens.push(upper);
+            } else {
+                tokens.push(current.clone());
+            }
             current.clear();
         }
 
         index
     }
 
+    // Si la palabra `upper`, que termina en `index`, es una keyword en el lugar en que aparece
+    fn is_keyword(upper: &str, previous: Option<&String>, chars: &[char], index: usize) -> bool {
+        let next_word: String = chars[index..]
+            .iter()
+            .skip_while(|char| char.is_whitespace())
+            .take_while(|char| char.is_alphanumeric() || **char == '_')
+            .collect::<String>()
+            .to_uppercase();
+        let after =
+            |words: &[&str]| previous.is_some_and(|previous| words.contains(&previous.as_str()));
+
+        match upper {
+            // `COUNT(*)` es una funcion solo si le sigue el parentesis
+            "COUNT" => chars.get(index) == Some(&'('),
+            // En `SELECT distinct FROM ...` es el nombre de la columna
+            "DISTINCT" => after(&["SELECT"]) && next_word != "FROM",
+            _ => {
+                KEYWORDS.contains(&upper)
+                    || CONTEXTUAL_KEYWORDS
+                        .iter()
+                        .any(|(keyword, previous)| *keyword == upper && after(previous))
+                    || LEADING_KEYWORDS
+                        .iter()
+                        .any(|(keyword, next)| *keyword == upper && next_word == *next)
+            }
+        }
+    }
+
     fn process_quotes(
-        string: &str,
+        chars: &[char],
         mut index: usize,
         current: &mut String,
         tokens: &mut Vec<String>,
     ) -> usize {
         index += 1;
-        while index < string.len() {
-            let char = string.chars().nth(index).unwrap_or('0');
+        while index < chars.len() {
+            let char = chars[index];
             if char == '\'' {
                 break;
             }
@@ -746,20 +1227,24 @@ impl QueryCreator {
     }
 
     fn process_paren(
-        string: &str,
+        chars: &[char],
         mut index: usize,
         current: &mut String,
         tokens: &mut Vec<String>,
-    ) -> usize {
+        max_depth: usize,
+    ) -> Result<usize, CQLError> {
         let mut paren_count = 1;
         index += 1; // Skip the opening parenthesis
 
         // No agregamos el paréntesis de apertura al current
 
-        while index < string.len() {
-            let char = string.chars().nth(index).unwrap_or('0');
+        while index < chars.len() {
+            let char = chars[index];
             if char == '(' {
                 paren_count += 1;
+                if paren_count > max_depth {
+                    return Err(CQLError::InvalidSyntax);
+                }
                 current.push(char);
             } else if char == ')' {
                 paren_count -= 1;
@@ -777,18 +1262,41 @@ impl QueryCreator {
 
         tokens.push(current.clone());
         current.clear();
-        index
+        Ok(index)
+    }
+
+    // Los operadores de comparación son siempre un token propio, tengan o no espacios alrededor
+    fn process_operator(chars: &[char], index: usize, tokens: &mut Vec<String>) -> usize {
+        let char = chars[index];
+        if chars.get(index + 1) == Some(&'=') && char != '=' {
+            tokens.push(format!("{}=", char));
+            index + 2
+        } else {
+            tokens.push(char.to_string());
+            index + 1
+        }
+    }
+
+    fn is_operator_char(char: char) -> bool {
+        matches!(char, '<' | '>' | '!' | '=')
     }
 
     fn process_other(
-        string: &str,
+        chars: &[char],
         mut index: usize,
         current: &mut String,
         tokens: &mut Vec<String>,
     ) -> usize {
-        while index < string.len() {
-            let char = string.chars().nth(index).unwrap_or('0');
-            if char.is_alphanumeric() || char.is_whitespace() {
+        if Self::is_operator_char(chars[index]) {
+            return Self::process_operator(chars, index, tokens);
+        }
+        while index < chars.len() {
+            let char = chars[index];
+            if char.is_alphanumeric()
+                || char.is_whitespace()
+                || Self::is_operator_char(char)
+                || matches!(char, '\'' | '(' | ',')
+            {
                 break;
             }
             current.push(char);
@@ -802,6 +1310,7 @@ impl QueryCreator {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
     #[test]
     fn test_create_select_query() {
@@ -818,6 +1327,41 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_full_scans_are_only_allowed_with_allow_filtering() {
+        let partition_keys = vec!["airport".to_string()];
+        let query = |cql: &str| QueryCreator::new().handle_query(cql.to_string()).unwrap();
+
+        let keyed = query("SELECT * FROM flights WHERE airport = 'EZE' AND number > 10");
+        assert_eq!(keyed.cost_estimate(&partition_keys), QueryCost::Bounded);
+        assert_eq!(keyed.check_full_scan_allowed(&partition_keys), Ok(()));
+
+        let unkeyed = query("SELECT * FROM flights WHERE number > 10");
+        assert!(unkeyed.is_full_scan(&partition_keys));
+        assert_eq!(
+            unkeyed.check_full_scan_allowed(&partition_keys),
+            Err(CQLError::FullScanNotAllowed)
+        );
+        let without_where = query("SELECT * FROM flights");
+        assert_eq!(
+            without_where.check_full_scan_allowed(&partition_keys),
+            Err(CQLError::FullScanNotAllowed)
+        );
+
+        let filtering = query("SELECT * FROM flights WHERE number > 10 LIMIT 5 ALLOW FILTERING");
+        assert!(filtering.is_full_scan(&partition_keys));
+        assert_eq!(filtering.check_full_scan_allowed(&partition_keys), Ok(()));
+
+        // Listar las particiones lee una fila de cada una
+        let distinct = query("SELECT DISTINCT airport FROM flights");
+        assert!(distinct.is_full_scan(&partition_keys));
+        assert_eq!(distinct.check_full_scan_allowed(&partition_keys), Ok(()));
+
+        // Las escrituras siempre van a una particion
+        let insert = query("INSERT INTO flights (airport, number) VALUES ('EZE', 1)");
+        assert_eq!(insert.cost_estimate(&partition_keys), QueryCost::Bounded);
+    }
+
     #[test]
     fn test_create_insert_query() {
         let coordinator = QueryCreator::new();
@@ -863,6 +1407,20 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_create_explain_query() {
+        let coordinator = QueryCreator::new();
+        let query = "EXPLAIN SELECT name, age FROM test.users WHERE name = 'John';".to_string();
+        let result = coordinator.handle_query(query);
+        assert!(matches!(result, Ok(Query::Explain(_))));
+
+        if let Ok(query) = result {
+            assert!(matches!(query.needed_responses(), NeededResponseCount::One));
+            assert_eq!(query.get_table_name(), Some("users".to_string()));
+            assert_eq!(query.get_used_keyspace(), Some("test".to_string()));
+        }
+    }
+
     #[test]
     fn test_create_table_query_success() {
         let coordinator = QueryCreator::new();
@@ -872,7 +1430,10 @@ mod tests {
         assert!(matches!(result, Ok(Query::CreateTable(_))));
 
         if let Ok(query) = result {
-            assert!(matches!(query.needed_responses(), NeededResponseCount::One));
+            assert!(matches!(
+                query.needed_responses(),
+                NeededResponseCount::AllLiveNodes
+            ));
         }
     }
 
@@ -884,7 +1445,10 @@ mod tests {
         assert!(matches!(result, Ok(Query::CreateKeyspace(_))));
 
         if let Ok(query) = result {
-            assert!(matches!(query.needed_responses(), NeededResponseCount::One));
+            assert!(matches!(
+                query.needed_responses(),
+                NeededResponseCount::AllLiveNodes
+            ));
         }
     }
 
@@ -896,7 +1460,10 @@ mod tests {
         assert!(matches!(result, Ok(Query::DropKeyspace(_))));
 
         if let Ok(query) = result {
-            assert!(matches!(query.needed_responses(), NeededResponseCount::One));
+            assert!(matches!(
+                query.needed_responses(),
+                NeededResponseCount::AllLiveNodes
+            ));
         }
     }
 
@@ -911,4 +1478,515 @@ mod tests {
             assert!(matches!(query.needed_responses(), NeededResponseCount::One));
         }
     }
+
+    #[test]
+    fn test_use_only_needs_the_answer_of_the_coordinator() {
+        let result = QueryCreator::new().handle_query("USE sky".to_string());
+        assert!(matches!(result, Ok(Query::Use(_))));
+
+        if let Ok(query) = result {
+            assert!(matches!(query.needed_responses(), NeededResponseCount::One));
+        }
+    }
+
+    #[test]
+    fn test_batch_needs_the_responses_of_its_most_demanding_member() {
+        let result = QueryCreator::new().handle_query(
+            "BEGIN BATCH \
+             INSERT INTO flights (id, origin) VALUES (1, 'EZE'); \
+             INSERT INTO flights (id, origin) VALUES (2, 'AEP'); \
+             APPLY BATCH"
+                .to_string(),
+        );
+
+        match result {
+            Ok(query @ Query::Batch(_)) => assert!(matches!(
+                query.needed_responses(),
+                NeededResponseCount::ReplicationFactor
+            )),
+            other => panic!("expected a batch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_keywords_are_case_insensitive() {
+        for query in [
+            "select id from flights where id = 1",
+            "Select id From flights Where id = 1",
+            "SELECT id FROM flights WHERE id = 1",
+        ] {
+            let result = QueryCreator::new().handle_query(query.to_string());
+            assert!(matches!(result, Ok(Query::Select(_))), "{}", query);
+        }
+
+        let result = QueryCreator::new()
+            .handle_query("create table flights (id INT, PRIMARY KEY (id))".to_string());
+        assert!(matches!(result, Ok(Query::CreateTable(_))));
+    }
+
+    #[test]
+    fn test_the_tokenizer_uppercases_keywords_only_where_they_are_keywords() {
+        let tokens = QueryCreator::tokens_from_query(
+            "select distinct count(*) from table group by id per partition limit 1 allow filtering",
+        );
+        assert_eq!(
+            tokens,
+            vec![
+                "SELECT",
+                "DISTINCT",
+                "COUNT",
+                "*",
+                "FROM",
+                "table",
+                "GROUP",
+                "BY",
+                "id",
+                "PER",
+                "PARTITION",
+                "LIMIT",
+                "1",
+                "ALLOW",
+                "FILTERING"
+            ]
+        );
+
+        let tokens =
+            QueryCreator::tokens_from_query("alter table group add column count int not null");
+        assert_eq!(
+            tokens,
+            vec!["ALTER", "TABLE", "group", "ADD", "COLUMN", "count", "int", "NOT", "NULL"]
+        );
+
+        let tokens = QueryCreator::tokens_from_query("select distinct from partition");
+        assert_eq!(tokens, vec!["SELECT", "distinct", "FROM", "partition"]);
+    }
+
+    #[test]
+    fn test_queries_written_differently_have_the_same_canonical_text() {
+        let canonical = |query: &str| {
+            QueryCreator::new()
+                .handle_query(query.to_string())
+                .unwrap()
+                .canonical_text()
+        };
+
+        for (query, same) in [
+            ("SELECT * FROM t", "select  *  from  t"),
+            (
+                "SELECT id FROM sky.flights WHERE id = 1 AND origin = 'EZE'",
+                "select id\n  from sky.flights\twhere id=1 and origin = 'EZE';",
+            ),
+            (
+                "INSERT INTO sky.flights (id, origin) VALUES (1, 'EZE')",
+                "insert into sky.flights (origin, id) values ('EZE', 1)",
+            ),
+            (
+                "UPDATE sky.flights SET origin = 'EZE', seats = 10 WHERE id = 1",
+                "update sky.flights set seats = 10, origin = 'EZE' where id = 1",
+            ),
+            (
+                "BEGIN BATCH INSERT INTO t (id) VALUES (1); DELETE FROM t WHERE id = 2; APPLY BATCH",
+                "begin batch insert into t (id) values (1);delete from t where id = 2; apply batch",
+            ),
+        ] {
+            assert_eq!(canonical(query), canonical(same), "{}", query);
+        }
+
+        for (query, other) in [
+            ("SELECT * FROM t", "SELECT * FROM u"),
+            ("SELECT * FROM t", "SELECT id FROM t"),
+            (
+                "SELECT * FROM t WHERE origin = 'EZE'",
+                "SELECT * FROM t WHERE origin = 'eze'",
+            ),
+            (
+                "SELECT * FROM t WHERE id = 1 LIMIT 1",
+                "SELECT * FROM t WHERE id = 1",
+            ),
+            (
+                "INSERT INTO t (id, origin) VALUES (1, 'EZE')",
+                "INSERT INTO t (id, origin) VALUES (1, 'AEP')",
+            ),
+            (
+                "INSERT INTO t (id, origin) VALUES (1, 'EZE')",
+                "INSERT INTO t (origin, id) VALUES (1, 'EZE')",
+            ),
+            ("DELETE FROM t WHERE id = 1", "SELECT * FROM t WHERE id = 1"),
+        ] {
+            assert_ne!(canonical(query), canonical(other), "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_where_with_not_equal_and_contains() {
+        let result = QueryCreator::new().handle_query(
+            "select id from flights where status != 'cancelled' and tags contains key 'delayed'"
+                .to_string(),
+        );
+        let condition = match result {
+            Ok(Query::Select(select)) => select.where_clause.unwrap().condition,
+            other => panic!("expected a select, got {:?}", other),
+        };
+        assert_eq!(
+            condition.serialize(),
+            "status != cancelled AND tags CONTAINS KEY delayed"
+        );
+    }
+
+    #[test]
+    fn test_identifiers_and_literals_keep_their_case() {
+        let result = QueryCreator::new()
+            .handle_query("select id from Flights where origin = 'and'".to_string());
+        match result {
+            Ok(Query::Select(select)) => assert_eq!(select.table_name, "Flights"),
+            other => panic!("expected a select, got {:?}", other),
+        }
+
+        let tokens = QueryCreator::tokens_from_query("update flights set origin = 'from'");
+        assert_eq!(
+            tokens,
+            vec!["UPDATE", "flights", "SET", "origin", "=", "from"]
+        );
+    }
+
+    #[test]
+    fn test_conditional_writes_answer_with_applied_row() {
+        let query = QueryCreator::new()
+            .handle_query(
+                "UPDATE flights SET status = 'late' WHERE id = 1 IF status = 'on time'".to_string(),
+            )
+            .unwrap();
+        assert!(query.is_conditional());
+
+        let frame = query
+            .create_client_response(vec![], "sky".to_string(), vec!["false".to_string()])
+            .unwrap();
+        match frame {
+            Frame::Result(result_::Result::Rows(rows)) => {
+                assert_eq!(
+                    rows.rows_content,
+                    vec![Row::from([(
+                        "[applied]".to_string(),
+                        ColumnValue::Boolean(false)
+                    )])]
+                );
+            }
+            other => panic!("expected an [applied] row, got {:?}", other),
+        }
+
+        let query = QueryCreator::new()
+            .handle_query("UPDATE flights SET status = 'late' WHERE id = 1".to_string())
+            .unwrap();
+        assert!(!query.is_conditional());
+        assert!(matches!(
+            query.create_client_response(vec![], "sky".to_string(), vec![]),
+            Ok(Frame::Result(result_::Result::Void))
+        ));
+    }
+
+    #[test]
+    fn test_comparison_operators_tokenize_the_same_with_and_without_spaces() {
+        for operator in [">=", "<=", "!=", ">", "<", "="] {
+            let expected = vec!["WHERE", "a", operator, "5"];
+            for query in [
+                format!("WHERE a {} 5", operator),
+                format!("WHERE a{}5", operator),
+                format!("WHERE a {}5", operator),
+                format!("WHERE a{} 5", operator),
+            ] {
+                assert_eq!(
+                    QueryCreator::tokens_from_query(&query),
+                    expected,
+                    "{}",
+                    query
+                );
+            }
+        }
+
+        assert_eq!(
+            QueryCreator::tokens_from_query("WHERE a>=-5 AND b='x'"),
+            vec!["WHERE", "a", ">=", "-5", "AND", "b", "=", "x"]
+        );
+    }
+
+    #[test]
+    fn test_tokenizer_keeps_accented_literals() {
+        let tokens = QueryCreator::tokens_from_query(
+            "INSERT INTO airports (code, name) VALUES ('RCU', 'Río Cuarto — Güemes') IF NOT EXISTS;",
+        );
+        assert_eq!(
+            tokens,
+            vec![
+                "INSERT",
+                "INTO",
+                "airports",
+                "code, name",
+                "VALUES",
+                "'RCU', 'Río Cuarto — Güemes'",
+                "IF",
+                "NOT",
+                "EXISTS"
+            ]
+        );
+
+        let tokens = QueryCreator::tokens_from_query(
+            "SELECT código FROM aeropuertos WHERE ciudad = 'Córdoba'",
+        );
+        assert_eq!(
+            tokens,
+            vec![
+                "SELECT",
+                "código",
+                "FROM",
+                "aeropuertos",
+                "WHERE",
+                "ciudad",
+                "=",
+                "Córdoba"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_over_long_query_is_rejected() {
+        let values = vec!["1"; DEFAULT_MAX_QUERY_LENGTH].join(", ");
+        let query = format!("INSERT INTO flights (id) VALUES ({})", values);
+
+        let result = QueryCreator::new().handle_query(query.clone());
+        assert!(matches!(result, Err(CQLError::InvalidSyntax)));
+
+        // Con limites mas amplios la misma query deja de fallar por su largo
+        let limits = ParserLimits {
+            max_query_length: query.len(),
+            max_tokens: 10,
+            ..ParserLimits::default()
+        };
+        assert!(matches!(
+            QueryCreator::tokens_from_query_with_limits(&query, &limits),
+            Ok(tokens) if tokens.len() <= 10
+        ));
+        let limits = ParserLimits {
+            max_query_length: 64,
+            ..ParserLimits::default()
+        };
+        assert!(matches!(
+            QueryCreator::tokens_from_query_with_limits(&query, &limits),
+            Err(CQLError::InvalidSyntax)
+        ));
+    }
+
+    #[test]
+    fn test_too_many_tokens_are_rejected() {
+        let columns = (0..DEFAULT_MAX_TOKENS)
+            .map(|i| format!("c{}", i))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let query = format!("SELECT {} FROM flights", columns);
+        assert!(query.len() < DEFAULT_MAX_QUERY_LENGTH);
+
+        let result = QueryCreator::new().handle_query(query);
+        assert!(matches!(result, Err(CQLError::InvalidSyntax)));
+    }
+
+    #[test]
+    fn test_deeply_nested_parens_are_rejected() {
+        let depth = 100_000;
+        let query = format!(
+            "SELECT id FROM flights WHERE id IN {}1{}",
+            "(".repeat(depth),
+            ")".repeat(depth)
+        );
+
+        let started = std::time::Instant::now();
+        let result = QueryCreator::new().with_limits(ParserLimits {
+            max_query_length: usize::MAX,
+            ..ParserLimits::default()
+        });
+        assert!(matches!(
+            result.handle_query(query),
+            Err(CQLError::InvalidSyntax)
+        ));
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+
+        // Un anidamiento dentro del limite se sigue aceptando
+        let nested = format!(
+            "{}1{}",
+            "(".repeat(DEFAULT_MAX_PAREN_DEPTH),
+            ")".repeat(DEFAULT_MAX_PAREN_DEPTH)
+        );
+        assert!(
+            QueryCreator::tokens_from_query_with_limits(&nested, &ParserLimits::default()).is_ok()
+        );
+    }
+
+    // Un valor de cada tipo que se guarda, con los casos borde de su forma en el CSV
+    fn stored_values() -> Vec<(ColumnType, ColumnValue)> {
+        let uuid = uuid::Uuid::parse_str("67e55044-10b1-426f-9247-bb680e5fe0c8").unwrap();
+        vec![
+            (
+                ColumnType::Custom("flight".to_string()),
+                ColumnValue::Custom("AR, 1130".to_string()),
+            ),
+            (
+                ColumnType::Ascii,
+                ColumnValue::Ascii("a,b;c\\d\ne\r".to_string()),
+            ),
+            (ColumnType::Ascii, ColumnValue::Ascii("\\N".to_string())),
+            (ColumnType::Ascii, ColumnValue::Ascii(String::new())),
+            (ColumnType::Bigint, ColumnValue::Bigint(i64::MIN)),
+            (ColumnType::Blob, ColumnValue::Blob(vec![0x00, 0xAB, 0xFF])),
+            (ColumnType::Blob, ColumnValue::Blob(vec![])),
+            (ColumnType::Boolean, ColumnValue::Boolean(false)),
+            (ColumnType::Counter, ColumnValue::Counter(-42)),
+            (
+                ColumnType::Decimal,
+                ColumnValue::Decimal {
+                    scale: -3,
+                    unscaled: vec![0xFF, 0x01],
+                },
+            ),
+            (ColumnType::Double, ColumnValue::Double(-0.1)),
+            (ColumnType::Double, ColumnValue::Double(f64::MAX)),
+            (ColumnType::Double, ColumnValue::Double(f64::INFINITY)),
+            (ColumnType::Float, ColumnValue::Float(f32::MIN_POSITIVE)),
+            (ColumnType::Int, ColumnValue::Int(i32::MAX)),
+            (
+                ColumnType::Timestamp,
+                ColumnValue::Timestamp(1_700_000_000_000),
+            ),
+            (ColumnType::Uuid, ColumnValue::Uuid(uuid)),
+            (
+                ColumnType::Varchar,
+                ColumnValue::Varchar("São Paulo; Brasil".to_string()),
+            ),
+            (ColumnType::Varint, ColumnValue::Varint(vec![0x80, 0x00])),
+            (ColumnType::Timeuuid, ColumnValue::Timeuuid(uuid)),
+            (
+                ColumnType::Inet,
+                ColumnValue::Inet(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))),
+            ),
+            (
+                ColumnType::Inet,
+                ColumnValue::Inet(IpAddr::V6(Ipv6Addr::LOCALHOST)),
+            ),
+            (ColumnType::Int, ColumnValue::Null),
+        ]
+    }
+
+    #[test]
+    fn test_storage_strings_read_back_as_the_same_value() {
+        for (col_type, value) in stored_values() {
+            let stored = value.to_storage_string().unwrap();
+            assert_eq!(
+                create_column_value_from_type(&col_type, &stored).unwrap(),
+                value,
+                "stored as {:?}",
+                stored
+            );
+        }
+
+        let stored = ColumnValue::Double(f64::NAN).to_storage_string().unwrap();
+        assert!(matches!(
+            create_column_value_from_type(&ColumnType::Double, &stored),
+            Ok(ColumnValue::Double(value)) if value.is_nan()
+        ));
+    }
+
+    #[test]
+    fn test_a_row_of_storage_strings_reads_back_value_by_value() {
+        let values = stored_values();
+        let col_types: Vec<(String, ColumnType)> = values
+            .iter()
+            .enumerate()
+            .map(|(i, (col_type, _))| (format!("c{}", i), col_type.clone()))
+            .collect();
+        let row: Vec<String> = values
+            .iter()
+            .map(|(_, value)| value.to_storage_string().unwrap())
+            .collect();
+
+        let Frame::Result(result_::Result::Rows(rows)) =
+            create_rows_frame(col_types, &[row.join(",")]).unwrap()
+        else {
+            panic!("expected rows");
+        };
+        for (i, (_, value)) in values.into_iter().enumerate() {
+            assert_eq!(rows.rows_content[0].get(&format!("c{}", i)), Some(&value));
+        }
+    }
+
+    #[test]
+    fn test_collections_have_no_storage_string() {
+        let list = ColumnValue::List(vec![ColumnValue::Int(1)]);
+        assert!(list.to_storage_string().is_err());
+    }
+
+    #[test]
+    fn test_numbers_out_of_range_are_told_apart_from_malformed_ones() {
+        assert_eq!(
+            create_column_value_from_type(&ColumnType::Int, "3000000000"),
+            Err(CQLError::ValueOutOfRange {
+                value: "3000000000".to_string(),
+                data_type: "int".to_string(),
+            })
+        );
+        assert_eq!(
+            create_column_value_from_type(&ColumnType::Bigint, "-9223372036854775809"),
+            Err(CQLError::ValueOutOfRange {
+                value: "-9223372036854775809".to_string(),
+                data_type: "bigint".to_string(),
+            })
+        );
+        assert_eq!(
+            create_column_value_from_type(&ColumnType::Float, "1e39"),
+            Err(CQLError::ValueOutOfRange {
+                value: "1e39".to_string(),
+                data_type: "float".to_string(),
+            })
+        );
+        assert_eq!(
+            create_column_value_from_type(&ColumnType::Double, "-1e309"),
+            Err(CQLError::ValueOutOfRange {
+                value: "-1e309".to_string(),
+                data_type: "double".to_string(),
+            })
+        );
+
+        for col_type in [
+            ColumnType::Int,
+            ColumnType::Bigint,
+            ColumnType::Float,
+            ColumnType::Double,
+        ] {
+            assert!(
+                matches!(
+                    create_column_value_from_type(&col_type, "EZE"),
+                    Err(CQLError::InvalidValue { value, .. }) if value == "EZE"
+                ),
+                "{:?}",
+                col_type
+            );
+        }
+
+        assert_eq!(
+            create_column_value_from_type(&ColumnType::Int, "2147483647"),
+            Ok(ColumnValue::Int(i32::MAX))
+        );
+        assert_eq!(
+            create_column_value_from_type(&ColumnType::Float, "-inf"),
+            Ok(ColumnValue::Float(f32::NEG_INFINITY))
+        );
+        assert_ne!(
+            CQLError::ValueOutOfRange {
+                value: "3000000000".to_string(),
+                data_type: "int".to_string(),
+            }
+            .to_string(),
+            CQLError::InvalidValue {
+                value: "3000000000".to_string(),
+                data_type: "int".to_string(),
+            }
+            .to_string()
+        );
+    }
 }
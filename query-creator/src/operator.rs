@@ -2,6 +2,9 @@
 /// - `Equal`: Equal operator
 /// - `Greater`: Greater than operator
 /// - `Lesser`: Lesser than operator
+/// - `NotEqual`: Not equal operator
+/// - `Contains`: Collection contains a value
+/// - `ContainsKey`: Map contains a key
 ///
 ///
 ///
@@ -17,6 +20,12 @@ use crate::CQLError;
 ///   - Represents the greater than (`>`) operator.
 /// - `Lesser`
 ///   - Represents the lesser than (`<`) operator.
+/// - `NotEqual`
+///   - Represents the not equal (`!=`) operator.
+/// - `Contains`
+///   - Represents the `CONTAINS` operator, true when a collection column holds the value.
+/// - `ContainsKey`
+///   - Represents the `CONTAINS KEY` operator, true when a map column has the value as a key.
 ///
 /// # Purpose
 /// The `Operator` enum encapsulates comparison operators commonly used in SQL-like query conditions. It provides methods to serialize these operators to their string representations and deserialize them back into enum variants.
@@ -29,6 +38,9 @@ pub enum Operator {
     Equal,
     Greater,
     Lesser,
+    NotEqual,
+    Contains,
+    ContainsKey,
 }
 
 impl Operator {
@@ -43,25 +55,31 @@ impl Operator {
     ///     - `"="` for `Operator::Equal`.
     ///     - `">"` for `Operator::Greater`.
     ///     - `"<"` for `Operator::Lesser`.
+    ///     - `"!="` for `Operator::NotEqual`.
+    ///     - `"CONTAINS"` for `Operator::Contains`.
+    ///     - `"CONTAINS KEY"` for `Operator::ContainsKey`.
 
     pub fn serialize(&self) -> &str {
         match self {
             Operator::Equal => "=",
             Operator::Greater => ">",
             Operator::Lesser => "<",
+            Operator::NotEqual => "!=",
+            Operator::Contains => "CONTAINS",
+            Operator::ContainsKey => "CONTAINS KEY",
         }
     }
 
     /// Deserializes a string to an `Operator`.
     ///
     /// # Purpose
-    /// Converts a string representation of a comparison operator (e.g., `"="`, `">"`, `"<"`)
+    /// Converts a string representation of a comparison operator (e.g., `"="`, `">"`, `"!="`)
     /// into the corresponding `Operator` enum variant.
     ///
     /// # Parameters
     /// - `op_str: &str`:
     ///   - A string slice representing a comparison operator.
-    ///     - Valid inputs: `"="`, `">"`, `"<"`.
+    ///     - Valid inputs: `"="`, `">"`, `"<"`, `"!="`, `"CONTAINS"`, `"CONTAINS KEY"`.
     ///
     /// # Returns
     /// - `Result<Operator, CQLError>`:
@@ -73,6 +91,9 @@ impl Operator {
             "=" => Ok(Operator::Equal),
             ">" => Ok(Operator::Greater),
             "<" => Ok(Operator::Lesser),
+            "!=" => Ok(Operator::NotEqual),
+            "CONTAINS" => Ok(Operator::Contains),
+            "CONTAINS KEY" => Ok(Operator::ContainsKey),
             _ => Err(CQLError::InvalidSyntax),
         }
     }
@@ -89,6 +110,9 @@ mod tests {
         assert_eq!(Operator::Equal.serialize(), "=");
         assert_eq!(Operator::Greater.serialize(), ">");
         assert_eq!(Operator::Lesser.serialize(), "<");
+        assert_eq!(Operator::NotEqual.serialize(), "!=");
+        assert_eq!(Operator::Contains.serialize(), "CONTAINS");
+        assert_eq!(Operator::ContainsKey.serialize(), "CONTAINS KEY");
     }
 
     #[test]
@@ -97,6 +121,12 @@ mod tests {
         assert_eq!(Operator::deserialize("="), Ok(Operator::Equal));
         assert_eq!(Operator::deserialize(">"), Ok(Operator::Greater));
         assert_eq!(Operator::deserialize("<"), Ok(Operator::Lesser));
+        assert_eq!(Operator::deserialize("!="), Ok(Operator::NotEqual));
+        assert_eq!(Operator::deserialize("CONTAINS"), Ok(Operator::Contains));
+        assert_eq!(
+            Operator::deserialize("CONTAINS KEY"),
+            Ok(Operator::ContainsKey)
+        );
     }
 
     #[test]
@@ -112,7 +142,14 @@ mod tests {
     #[test]
     fn test_serialize_and_deserialize_roundtrip() {
         // Test that serialization and deserialization are inverses
-        let operators = vec![Operator::Equal, Operator::Greater, Operator::Lesser];
+        let operators = vec![
+            Operator::Equal,
+            Operator::Greater,
+            Operator::Lesser,
+            Operator::NotEqual,
+            Operator::Contains,
+            Operator::ContainsKey,
+        ];
 
         for op in operators {
             let serialized = op.serialize();
@@ -80,5 +80,36 @@ pub fn is_values(token: &str) -> bool {
 
 /// Returns true if the token is equal to "LIMIT"
 pub fn is_limit(token: &str) -> bool {
-    token.eq_ignore_ascii_case("LIMIT")
+    token == "LIMIT"
+}
+
+/// Returns true if the token is equal to "DISTINCT"
+pub fn is_distinct(token: &str) -> bool {
+    token == "DISTINCT"
+}
+
+/// Returns true if the tokens starting at `i` are "ALLOW FILTERING"
+pub fn is_allow_filtering(tokens: &[String], i: usize) -> bool {
+    match tokens.get(i..i + 2) {
+        Some([allow, filtering]) => allow == "ALLOW" && filtering == "FILTERING",
+        _ => false,
+    }
+}
+
+/// Returns true if the tokens starting at `i` are "GROUP BY"
+pub fn is_group_by(tokens: &[String], i: usize) -> bool {
+    match tokens.get(i..i + 2) {
+        Some([group, by]) => group == "GROUP" && by == "BY",
+        _ => false,
+    }
+}
+
+/// Returns true if the tokens starting at `i` are "PER PARTITION LIMIT"
+pub fn is_per_partition_limit(tokens: &[String], i: usize) -> bool {
+    match tokens.get(i..i + 3) {
+        Some([per, partition, limit]) => {
+            per == "PER" && partition == "PARTITION" && is_limit(limit)
+        }
+        _ => false,
+    }
 }
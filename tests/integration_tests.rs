@@ -49,6 +49,25 @@ fn execute_and_verify(
     }
 }
 
+// Execute a conditional write and verify whether it was applied
+fn execute_and_verify_applied(client: &mut CassandraClient, query: &str, expected: bool) -> bool {
+    match client.execute(query, "quorum") {
+        Ok(QueryResult::Result(Result::Rows(rows))) => rows
+            .rows_content
+            .first()
+            .and_then(|row| row.get("[applied]"))
+            .is_some_and(|applied| *applied == ColumnValue::Boolean(expected)),
+        Ok(query_result) => {
+            eprintln!("Unexpected query result type: {:?}", query_result);
+            false
+        }
+        Err(e) => {
+            eprintln!("Error executing query: {}\nError: {:?}", query, e);
+            false
+        }
+    }
+}
+
 // Function to delete folders created by nodes based on IP
 fn delete_node_directories(ip_addresses: Vec<&str>) {
     for ip in ip_addresses {
@@ -238,7 +257,7 @@ fn execute_insert_queries(client: &mut CassandraClient) {
     let query =
         "INSERT INTO test_keyspace.test_table (id, name, last_name) VALUES (3, 'Charlie', 'Cox') IF NOT EXISTS";
     assert!(
-        execute_and_verify(client, query, QueryResult::Result(Result::Void)),
+        execute_and_verify_applied(client, query, true),
         "Insert with IF NOT EXISTS failed (when row does not exist)"
     );
     println!(
@@ -259,7 +278,7 @@ fn execute_insert_queries(client: &mut CassandraClient) {
     let query =
         "INSERT INTO test_keyspace.test_table (id, name, last_name) VALUES (3, 'Charlie', 'Bet') IF NOT EXISTS";
     assert!(
-        execute_and_verify(client, query, QueryResult::Result(Result::Void)),
+        execute_and_verify_applied(client, query, false),
         "Insert with IF NOT EXISTS should not insert when row exists"
     );
     println!(
@@ -310,7 +329,7 @@ fn execute_update_queries(client: &mut CassandraClient) {
     // 2. Actualización con condición IF que cumple
     let update_query = "UPDATE test_keyspace.test_table SET last_name = 'Chap' WHERE id = 1 AND name = 'Alice' IF last_name = 'Rake'";
     assert!(
-        execute_and_verify(client, update_query, QueryResult::Result(Result::Void)),
+        execute_and_verify_applied(client, update_query, true),
         "Update with IF condition (matching) failed"
     );
     println!("Update with IF condition (matching) executed successfully");
@@ -362,7 +381,7 @@ fn execute_update_queries(client: &mut CassandraClient) {
     let update_query =
         "UPDATE test_keyspace.test_table SET last_name = 'Tel' WHERE id = 2 AND name = 'Bob' IF last_name = 'Prin'";
     assert!(
-        execute_and_verify(client, update_query, QueryResult::Result(Result::Void)),
+        execute_and_verify_applied(client, update_query, false),
         "Update with non-matching IF and WHERE should do nothing"
     );
     println!("Update with non-matching IF and WHERE condition executed successfully");
@@ -398,7 +417,7 @@ fn execute_delete_queries(client: &mut CassandraClient) {
     let delete_query =
         "DELETE FROM test_keyspace.test_table WHERE id = 1 AND name = 'Alice' IF last_name = 'Chap'";
     assert!(
-        execute_and_verify(client, delete_query, QueryResult::Result(Result::Void)),
+        execute_and_verify_applied(client, delete_query, true),
         "Delete with matching IF condition failed"
     );
     println!("Delete with matching IF condition executed successfully");
@@ -415,7 +434,7 @@ fn execute_delete_queries(client: &mut CassandraClient) {
     let delete_query =
         "DELETE FROM test_keyspace.test_table WHERE id = 2 AND name = 'Bob' IF last_name = 'NonExistingLastName'";
     assert!(
-        execute_and_verify(client, delete_query, QueryResult::Result(Result::Void)),
+        execute_and_verify_applied(client, delete_query, false),
         "Delete with non-matching IF condition should fail (row should not be deleted)"
     );
     println!("Delete with non-matching IF condition executed successfully");